@@ -0,0 +1,31 @@
+#!/bin/bash
+#
+# Sugar CLI - downloads the programs used by the integration tests
+#
+# The programs are dumped from mainnet (or the cluster specified by the flag -u <url>)
+# into tests/programs, or the folder set in SUGAR_TEST_PROGRAMS.
+
+SCRIPT_DIR=$(cd -- $(dirname -- "${BASH_SOURCE[0]}") &>/dev/null && pwd)
+PARENT_DIR="$(dirname "$SCRIPT_DIR")"
+PROGRAMS_DIR=${SUGAR_TEST_PROGRAMS:-"$PARENT_DIR/tests/programs"}
+CLUSTER="mainnet-beta"
+
+while getopts u: flag; do
+    case "${flag}" in
+    u) CLUSTER=${OPTARG} ;;
+    *) echo "usage: $0 [-u <cluster url>]" && exit 1 ;;
+    esac
+done
+
+mkdir -p "$PROGRAMS_DIR"
+
+dump() {
+    echo "Dumping $2 ($1)"
+    solana program dump -u "$CLUSTER" "$1" "$PROGRAMS_DIR/$2" || exit 1
+}
+
+dump CndyV3LdqHUfDLmE5naZjVN8rBZz4tqhdefbAnjHG3JR mpl_candy_machine_core.so
+dump Guard1JwRhJkVH6XZhzoYxeBVQe872VH6QggF4BWmS9g mpl_candy_guard.so
+dump metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s mpl_token_metadata.so
+
+echo "Programs saved to $PROGRAMS_DIR"
@@ -0,0 +1,246 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use anchor_client::solana_sdk::{account::Account, pubkey::Pubkey};
+use solana_client::rpc_client::RpcClient;
+
+use crate::{common::*, output::style};
+
+/// Maximum number of accounts of a `getMultipleAccounts` request.
+pub const MAX_MULTIPLE_ACCOUNTS: usize = 100;
+
+/// Time that a fetched account is served from the cache.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(30);
+
+// number of `getMultipleAccounts` requests sent in parallel
+const PARALLEL_REQUESTS: usize = 8;
+
+/// Source of account data, implemented by the RPC client (and by mock transports in tests).
+pub trait AccountSource: Sync {
+    fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>>;
+}
+
+impl AccountSource for RpcClient {
+    fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        Ok(RpcClient::get_multiple_accounts(self, pubkeys)?)
+    }
+}
+
+impl<T: AccountSource + Send> AccountSource for Arc<T> {
+    fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        self.as_ref().get_multiple_accounts(pubkeys)
+    }
+}
+
+/// Fetches accounts in `getMultipleAccounts` batches, keeping the results in a small
+/// TTL cache so that repeated lookups do not hit the RPC again.
+pub struct AccountFetcher<S: AccountSource> {
+    source: S,
+    ttl: Duration,
+    cache: Mutex<HashMap<Pubkey, (Instant, Option<Account>)>>,
+    requests: AtomicU64,
+    accounts: AtomicU64,
+}
+
+impl<S: AccountSource> AccountFetcher<S> {
+    pub fn new(source: S) -> Self {
+        Self::with_ttl(source, DEFAULT_TTL)
+    }
+
+    pub fn with_ttl(source: S, ttl: Duration) -> Self {
+        Self {
+            source,
+            ttl,
+            cache: Mutex::new(HashMap::new()),
+            requests: AtomicU64::new(0),
+            accounts: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the accounts (in the same order as `pubkeys`), fetching the ones that are
+    /// not in the cache.
+    pub fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+        let mut found = HashMap::with_capacity(pubkeys.len());
+        let mut missing = Vec::new();
+
+        {
+            let cache = self.cache.lock().unwrap();
+            for pubkey in pubkeys {
+                if found.contains_key(pubkey) {
+                    continue;
+                }
+                match cache.get(pubkey) {
+                    Some((fetched_at, account)) if fetched_at.elapsed() < self.ttl => {
+                        found.insert(*pubkey, account.clone());
+                    }
+                    _ => {
+                        // placeholder to skip duplicated pubkeys
+                        found.insert(*pubkey, None);
+                        missing.push(*pubkey);
+                    }
+                }
+            }
+        }
+
+        for batch in missing.chunks(MAX_MULTIPLE_ACCOUNTS * PARALLEL_REQUESTS) {
+            let results = thread::scope(|scope| {
+                let handles: Vec<_> = batch
+                    .chunks(MAX_MULTIPLE_ACCOUNTS)
+                    .map(|chunk| scope.spawn(move || self.source.get_multiple_accounts(chunk)))
+                    .collect();
+
+                handles
+                    .into_iter()
+                    .map(|handle| handle.join().expect("Failed to join account request."))
+                    .collect::<Vec<_>>()
+            });
+
+            let fetched_at = Instant::now();
+            let mut cache = self.cache.lock().unwrap();
+
+            for (chunk, result) in batch.chunks(MAX_MULTIPLE_ACCOUNTS).zip(results) {
+                self.requests.fetch_add(1, Ordering::Relaxed);
+                self.accounts
+                    .fetch_add(chunk.len() as u64, Ordering::Relaxed);
+
+                for (pubkey, account) in chunk.iter().zip(result?) {
+                    cache.insert(*pubkey, (fetched_at, account.clone()));
+                    found.insert(*pubkey, account);
+                }
+            }
+        }
+
+        Ok(pubkeys
+            .iter()
+            .map(|pubkey| found.get(pubkey).cloned().flatten())
+            .collect())
+    }
+
+    /// Returns the account, fetching it if it is not in the cache.
+    pub fn get_account(&self, pubkey: &Pubkey) -> Result<Option<Account>> {
+        Ok(self
+            .get_multiple_accounts(std::slice::from_ref(pubkey))?
+            .pop()
+            .flatten())
+    }
+
+    /// Loads the accounts into the cache, so that subsequent lookups are served without
+    /// additional requests.
+    pub fn prefetch(&self, pubkeys: &[Pubkey]) -> Result<()> {
+        self.get_multiple_accounts(pubkeys).map(|_| ())
+    }
+
+    /// Removes the accounts from the cache, e.g. after they have been modified.
+    pub fn invalidate(&self, pubkeys: &[Pubkey]) {
+        let mut cache = self.cache.lock().unwrap();
+        for pubkey in pubkeys {
+            cache.remove(pubkey);
+        }
+    }
+
+    /// Number of RPC requests sent.
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    /// Number of accounts requested from the RPC.
+    pub fn accounts(&self) -> u64 {
+        self.accounts.load(Ordering::Relaxed)
+    }
+
+    pub fn print_summary(&self) {
+        report!(
+            "{} {} ({} account(s) fetched)",
+            style("RPC requests:").bold(),
+            self.requests(),
+            self.accounts()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mock transport that counts the requests.
+    #[derive(Default)]
+    struct MockSource {
+        requests: AtomicU64,
+    }
+
+    impl MockSource {
+        fn account(pubkey: &Pubkey) -> Account {
+            Account {
+                data: pubkey.to_bytes().to_vec(),
+                ..Account::default()
+            }
+        }
+
+        fn get_account(&self, pubkey: &Pubkey) -> Option<Account> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Some(Self::account(pubkey))
+        }
+    }
+
+    impl AccountSource for MockSource {
+        fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            assert!(pubkeys.len() <= MAX_MULTIPLE_ACCOUNTS);
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Ok(pubkeys.iter().map(|p| Some(Self::account(p))).collect())
+        }
+    }
+
+    #[test]
+    fn batches_lookups() {
+        let pubkeys: Vec<Pubkey> = (0..10_000).map(|_| Pubkey::new_unique()).collect();
+
+        // one request per item
+        let unbatched = MockSource::default();
+        for pubkey in &pubkeys {
+            unbatched.get_account(pubkey).unwrap();
+        }
+
+        let fetcher = AccountFetcher::new(MockSource::default());
+        fetcher.prefetch(&pubkeys).unwrap();
+        for pubkey in &pubkeys {
+            let account = fetcher.get_account(pubkey).unwrap().unwrap();
+            assert_eq!(account.data, pubkey.to_bytes().to_vec());
+        }
+
+        let unbatched = unbatched.requests.load(Ordering::Relaxed);
+        assert_eq!(fetcher.requests(), 100);
+        assert!(unbatched >= 10 * fetcher.requests());
+    }
+
+    #[test]
+    fn preserves_order_and_duplicates() {
+        let a = Pubkey::new_unique();
+        let b = Pubkey::new_unique();
+        let fetcher = AccountFetcher::new(MockSource::default());
+
+        let accounts = fetcher.get_multiple_accounts(&[b, a, b]).unwrap();
+
+        assert_eq!(fetcher.accounts(), 2);
+        assert_eq!(accounts[0].as_ref().unwrap().data, b.to_bytes().to_vec());
+        assert_eq!(accounts[1].as_ref().unwrap().data, a.to_bytes().to_vec());
+        assert_eq!(accounts[2].as_ref().unwrap().data, b.to_bytes().to_vec());
+    }
+
+    #[test]
+    fn refetches_expired_accounts() {
+        let pubkey = Pubkey::new_unique();
+        let fetcher = AccountFetcher::with_ttl(MockSource::default(), Duration::ZERO);
+
+        fetcher.get_account(&pubkey).unwrap();
+        fetcher.get_account(&pubkey).unwrap();
+
+        assert_eq!(fetcher.requests(), 2);
+    }
+}
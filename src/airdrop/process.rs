@@ -5,20 +5,28 @@ use std::{
 
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anyhow::Result;
-use console::style;
 use tokio::sync::Semaphore;
 
 use crate::{
+    account_fetcher::{AccountFetcher, AccountSource},
     airdrop::{
         errors::AirDropError,
-        structs::{AirDropTargets, TransactionResult},
+        structs::{AirDropTargets, SerdePubkey, TransactionResult},
         utils::{load_airdrop_list, load_airdrop_results, write_airdrop_results},
     },
+    bubblegum::BUBBLEGUM_ID,
     cache::load_cache,
+    cancel,
     candy_machine::{CANDY_MACHINE_ID, *},
     common::*,
-    mint::mint,
+    compute_budget::ComputeBudget,
+    lut::cache_lookup_table,
+    mint::{bot_tax_error, record_compressed_mint, send_mint, CompressedMinter, MintedNft},
+    output::style,
     pdas::get_metadata_pda,
+    priority_fee::{resolve_priority_fee, PriorityFee},
+    progress_stream,
+    tree::{get_tree_config, print_tree_info},
     utils::*,
 };
 
@@ -28,10 +36,21 @@ pub struct AirdropArgs {
     pub cache: String,
     pub candy_machine: Option<String>,
     pub airdrop_list: String,
-    pub priority_fee: u64,
+    pub priority_fee: PriorityFee,
+    pub max_total_priority_sol: Option<f64>,
+    pub compressed: bool,
+    pub merkle_tree: Option<String>,
+    pub config: String,
+    pub use_lut: bool,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
+    if args.compressed {
+        return process_airdrop_compressed(args);
+    }
+
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(CANDY_MACHINE_ID);
@@ -44,7 +63,7 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
     let airdrop_total = airdrop_list.iter().fold(0, |acc, x| acc + x.1);
 
     if airdrop_total_original != airdrop_total {
-        print!(
+        report_text!(
             "Skipping {} mints due to existing transactions in airdrop_results.json",
             airdrop_total_original - airdrop_total
         );
@@ -55,7 +74,8 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
     let candy_machine_id = match args.candy_machine {
         Some(candy_machine_id) => candy_machine_id,
         None => {
-            let cache = load_cache(&args.cache, false)?;
+            let mut cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
             cache.program.candy_machine
         }
     };
@@ -69,12 +89,13 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
         }
     };
 
-    println!(
+    report!(
         "{} {}Loading candy machine",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
-    println!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+    report!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+    progress_stream::phase_started("load_candy_machine", 1, 2);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -85,12 +106,21 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
     let collection_update_authority = collection_metadata.update_authority;
 
     pb.finish_with_message("Done");
+    progress_stream::phase_finished();
+
+    let lookup_table = if args.use_lut {
+        let cache = load_cache(&args.cache, false)?;
+        cache_lookup_table(&program.rpc(), &cache)?.map(Arc::new)
+    } else {
+        None
+    };
 
-    println!(
+    report!(
         "\n{} {}Minting from candy machine",
         style("[2/2]").bold().dim(),
         CANDY_EMOJI
     );
+    progress_stream::phase_started("airdrop", 2, 2);
 
     let available = candy_machine_state.data.items_available - candy_machine_state.items_redeemed;
 
@@ -100,46 +130,66 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
         );
     }
 
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        args.compute_budget.compute_unit_limit(COMPUTE_UNITS),
+        airdrop_total,
+        args.max_total_priority_sol,
+    )?;
+    let compute_budget = args.compute_budget;
+
     info!("Minting NFT from candy machine: {}", &candy_machine_id);
     info!("Candy machine program id: {:?}", CANDY_MACHINE_ID);
 
+    progress_stream::totals(airdrop_total);
     let pb = progress_bar_with_style(airdrop_total);
     let mut tasks = Vec::new();
     let semaphore = Arc::new(Semaphore::new(10));
     let config = Arc::new(sugar_config);
 
-    for (address, num) in airdrop_list.drain() {
+    let _cancellable = cancel::cancellable();
+
+    'airdrop: for (address, num) in airdrop_list.drain() {
         for _i in 0..num {
+            if cancel::is_cancelled() {
+                break 'airdrop;
+            }
+
             let results = airdrop_results.clone();
             let config = config.clone();
             let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
             let candy_machine_state = candy_machine_state.clone();
+            let lookup_table = lookup_table.clone();
             let target = address.0;
             let pb = pb.clone();
 
             // Start tasks
             tasks.push(tokio::spawn(async move {
                 let _permit = permit;
-                let res = mint(
+                let res = send_mint(
                     config,
                     candy_pubkey,
                     candy_machine_state,
                     collection_update_authority,
                     target,
-                    args.priority_fee,
+                    priority_fee,
+                    lookup_table,
+                    compute_budget,
                 )
                 .await;
                 pb.inc(1);
+                progress_stream::item_completed(Some(target.to_string()), res.as_ref().map(|_| ()));
 
                 let mut results = results.lock().unwrap();
                 results.entry(address).or_insert_with(Vec::new);
                 let signatures = results.get_mut(&address).unwrap();
 
                 match &res {
-                    Ok((signature, _)) => {
+                    Ok(MintedNft { signature, .. }) => {
                         signatures.push(TransactionResult {
                             signature: signature.to_string(),
                             status: true,
+                            asset_id: None,
                         });
                     }
                     Err(err) => {
@@ -149,32 +199,69 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
                                 signature: "RPC timeout: unknown if transaction succeeded"
                                     .to_string(),
                                 status: true,
+                                asset_id: None,
                             });
                         }
                         signatures.push(TransactionResult {
                             signature: err.to_string(),
                             status: false,
+                            asset_id: None,
                         });
                     }
                 }
 
-                res
+                res.map(|minted| (address, minted))
             }));
         }
     }
 
     let mut error_count = 0;
+    let started = tasks.len() as u64;
+    let mut minted = Vec::new();
 
     // Resolve tasks
     for task in tasks {
-        let res = task.await.unwrap();
-        if let Err(e) = res {
-            error_count += 1;
-            error!("{:?}, continuing. . .", e);
+        match task.await.unwrap() {
+            Ok(nft) => minted.push(nft),
+            Err(e) => {
+                error_count += 1;
+                error!("{:?}, continuing. . .", e);
+            }
+        }
+    }
+
+    // the token accounts of the minted NFTs are checked in batches
+    let fetcher = AccountFetcher::new(program.rpc());
+    let failed = missing_token_accounts(&fetcher, &minted)?;
+    fetcher.print_summary();
+
+    for (address, nft) in failed {
+        error_count += 1;
+        let error = bot_tax_error(program.rpc(), &nft.signature);
+        error!("{:?}, continuing. . .", error);
+
+        let mut results = airdrop_results.lock().unwrap();
+        if let Some(result) = results.get_mut(address).and_then(|signatures| {
+            signatures
+                .iter_mut()
+                .find(|result| result.signature == nft.signature.to_string())
+        }) {
+            result.signature = error.to_string();
+            result.status = false;
         }
     }
 
     write_airdrop_results(&airdrop_results.lock().unwrap())?;
+    if cancel::is_cancelled() {
+        pb.abandon_with_message(format!(
+            "{} {}/{} {}",
+            style("Minted").yellow().bold(),
+            started - error_count,
+            airdrop_total,
+            style("of the items").yellow().bold()
+        ));
+        return Err(cancel::cancelled().into());
+    }
     if error_count > 0 {
         pb.abandon_with_message(format!(
             "{} {} items failed.",
@@ -190,6 +277,239 @@ pub async fn process_airdrop(args: AirdropArgs) -> Result<()> {
         ));
     }
     pb.finish();
+    progress_stream::phase_finished();
 
     Ok(())
 }
+
+/// Returns the minted NFTs without a token account, i.e. the mints that paid the bot tax
+/// instead of creating the NFT, loading the accounts 100 at a time.
+fn missing_token_accounts<'a, S: AccountSource>(
+    fetcher: &AccountFetcher<S>,
+    minted: &'a [(SerdePubkey, MintedNft)],
+) -> Result<Vec<&'a (SerdePubkey, MintedNft)>> {
+    let tokens: Vec<Pubkey> = minted.iter().map(|(_, nft)| nft.token).collect();
+    let accounts = fetcher.get_multiple_accounts(&tokens)?;
+
+    Ok(minted
+        .iter()
+        .zip(accounts)
+        .filter(|(_, account)| account.is_none())
+        .map(|(minted, _)| minted)
+        .collect())
+}
+
+fn process_airdrop_compressed(args: AirdropArgs) -> Result<()> {
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(BUBBLEGUM_ID);
+
+    let mut airdrop_list: AirDropTargets = load_airdrop_list(args.airdrop_list)?;
+
+    // load_airdrop_results syncs airdrop_list and airdrop_results in case of rerun failures
+    let airdrop_total_original = airdrop_list.iter().fold(0, |acc, x| acc + x.1);
+    let mut airdrop_results = load_airdrop_results(&mut airdrop_list)?;
+    let airdrop_total = airdrop_list.iter().fold(0, |acc, x| acc + x.1);
+
+    if airdrop_total_original != airdrop_total {
+        report_text!(
+            "Skipping {} mints due to existing transactions in airdrop_results.json",
+            airdrop_total_original - airdrop_total
+        );
+    }
+
+    let mut cache = load_cache(&args.cache, false)?;
+    cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
+
+    report!(
+        "{} {}Loading merkle tree",
+        style("[1/2]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+    progress_stream::phase_started("load_merkle_tree", 1, 2);
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let minter = CompressedMinter::load(&cache, &args.config, args.merkle_tree)?;
+    minter.verify_collection_authority(&program)?;
+    let tree_config = get_tree_config(&program.rpc(), &minter.merkle_tree)?;
+
+    pb.finish_with_message("Done");
+    progress_stream::phase_finished();
+
+    print_tree_info(&minter.merkle_tree, &tree_config);
+
+    report!(
+        "\n{} {}Airdropping compressed NFTs",
+        style("[2/2]").bold().dim(),
+        CANDY_EMOJI
+    );
+    progress_stream::phase_started("airdrop", 2, 2);
+
+    let available = minter.available(&tree_config);
+
+    if airdrop_total > available {
+        return Err(
+            AirDropError::AirdropTotalIsHigherThanAvailable(airdrop_total, available).into(),
+        );
+    }
+
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        args.compute_budget.compute_unit_limit(COMPUTE_UNITS),
+        airdrop_total,
+        args.max_total_priority_sol,
+    )?;
+
+    // compressed mints are sent sequentially since the asset id depends on the leaf index
+    progress_stream::totals(airdrop_total);
+    let pb = progress_bar_with_style(airdrop_total);
+    let mut error_count = 0;
+    let mut started = 0;
+    let _cancellable = cancel::cancellable();
+
+    'airdrop: for (address, num) in airdrop_list.drain() {
+        for _i in 0..num {
+            if cancel::is_cancelled() {
+                break 'airdrop;
+            }
+
+            started += 1;
+            let res = minter.mint(
+                &program,
+                &sugar_config.keypair,
+                address.0,
+                priority_fee,
+                &args.compute_budget,
+            );
+            pb.inc(1);
+            progress_stream::item_completed(Some(address.0.to_string()), res.as_ref().map(|_| ()));
+
+            let signatures = airdrop_results.entry(address).or_insert_with(Vec::new);
+
+            match res {
+                Ok(result) => {
+                    signatures.push(TransactionResult {
+                        signature: result.signature.to_string(),
+                        status: true,
+                        asset_id: Some(result.asset_id.to_string()),
+                    });
+                    record_compressed_mint(&mut cache, &result)?;
+                }
+                Err(err) => {
+                    error_count += 1;
+                    error!("{:?}, continuing. . .", err);
+
+                    // Assume timeouts succeed to avoid sending double to a recipient.
+                    if err.to_string().contains("Transaction was not confirmed in") {
+                        signatures.push(TransactionResult {
+                            signature: "RPC timeout: unknown if transaction succeeded".to_string(),
+                            status: true,
+                            asset_id: None,
+                        });
+                    }
+                    signatures.push(TransactionResult {
+                        signature: err.to_string(),
+                        status: false,
+                        asset_id: None,
+                    });
+                }
+            }
+
+            // results are written after each mint so reruns can resume
+            write_airdrop_results(&airdrop_results)?;
+        }
+    }
+
+    if cancel::is_cancelled() {
+        pb.abandon_with_message(format!(
+            "{} {}/{} {}",
+            style("Minted").yellow().bold(),
+            started - error_count,
+            airdrop_total,
+            style("of the items").yellow().bold()
+        ));
+        return Err(cancel::cancelled().into());
+    }
+
+    if error_count > 0 {
+        pb.abandon_with_message(format!(
+            "{} {} items failed.",
+            style("Some of the items failed to mint.").red().bold(),
+            error_count
+        ));
+        return Err(anyhow!(
+            "{} {}/{} {}",
+            style("Minted").red().bold(),
+            airdrop_total - error_count,
+            airdrop_total,
+            style("of the items").red().bold()
+        ));
+    }
+    pb.finish();
+    progress_stream::phase_finished();
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use anchor_client::solana_sdk::{account::Account, signature::Signature};
+
+    use super::*;
+
+    /// Mock transport with the `existing` accounts, counting the requests.
+    struct MockSource {
+        existing: HashSet<Pubkey>,
+        requests: AtomicUsize,
+    }
+
+    impl AccountSource for MockSource {
+        fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Ok(pubkeys
+                .iter()
+                .map(|pubkey| self.existing.contains(pubkey).then(Account::default))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn checks_token_accounts_in_batches() {
+        let minted: Vec<(SerdePubkey, MintedNft)> = (0..1000)
+            .map(|_| {
+                let nft = MintedNft {
+                    signature: Signature::default(),
+                    mint: Pubkey::new_unique(),
+                    token: Pubkey::new_unique(),
+                };
+                (SerdePubkey::new(Pubkey::new_unique()), nft)
+            })
+            .collect();
+
+        // the mint of every 100th NFT paid the bot tax
+        let existing = minted
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| i % 100 != 0)
+            .map(|(_, (_, nft))| nft.token)
+            .collect();
+
+        let source = Arc::new(MockSource {
+            existing,
+            requests: AtomicUsize::new(0),
+        });
+        let fetcher = AccountFetcher::new(source.clone());
+        let missing = missing_token_accounts(&fetcher, &minted).unwrap();
+
+        assert_eq!(missing.len(), 10);
+        assert_eq!(missing[1].1.token, minted[100].1.token);
+        assert_eq!(source.requests.load(Ordering::Relaxed), 10);
+    }
+}
@@ -67,6 +67,9 @@ pub type AirDropTargets = HashMap<SerdePubkey, u64>;
 pub struct TransactionResult {
     pub signature: String,
     pub status: bool,
+    /// Asset id of the compressed NFT minted (compressed airdrops only).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset_id: Option<String>,
 }
 
 pub type AirDropResults = HashMap<SerdePubkey, Vec<TransactionResult>>;
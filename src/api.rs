@@ -0,0 +1,420 @@
+//! Programmatic API for embedding Sugar in other Rust applications.
+//!
+//! The functions in this module take the same inputs as the CLI commands and
+//! return typed results read back from the cache, so callers do not need to
+//! parse the command output. They print nothing and never prompt: the output of
+//! the commands goes to the terminal set by the CLI (see
+//! [`set_terminal`](crate::output::set_terminal)), and the commands that need an
+//! answer fail instead of waiting for one.
+//!
+//! The example deploys a collection to a local validator; it runs with the
+//! validator of the integration tests (`cargo test --features integration-tests --doc`).
+//!
+#![cfg_attr(feature = "integration-tests", doc = "```")]
+#![cfg_attr(not(feature = "integration-tests"), doc = "```ignore")]
+//! use sugar_cli::api::{self, SugarOptions};
+//! # use sugar_cli::test_validator::{Fixture, TestValidator, ITEMS};
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! # let validator = TestValidator::start("api-doc")?;
+//! # let fixture = Fixture::new("api-doc", &validator)?;
+//! # fixture.create_config(None)?;
+//! # let assets_dir = fixture.assets_dir();
+//! // deploys the assets to a local validator
+//! let options = SugarOptions {
+//!     rpc_url: Some("http://127.0.0.1:8899".to_string()),
+//!     ..SugarOptions::default()
+//! };
+//! # let options = fixture.options.clone();
+//!
+//! let upload = api::upload(&options, &assets_dir).await?;
+//! println!("uploaded {} items", upload.items);
+//! # assert_eq!(upload.items, ITEMS as usize);
+//!
+//! let deploy = api::deploy(&options, None).await?;
+//! println!("candy machine: {}", deploy.candy_machine);
+//!
+//! let verify = api::verify(&options).await?;
+//! assert_eq!(verify.candy_machine, deploy.candy_machine);
+//! assert_eq!(verify.items, upload.items);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{atomic::AtomicBool, Arc};
+
+use crate::{
+    ardrive::{
+        process_ardrive_list_all_drives, process_ardrive_list_drive_files,
+        ArdriveListDriveFilesArgs, ArdriveOptions, OutputFormat,
+    },
+    cache::load_cache,
+    common::*,
+    compute_budget::ComputeBudget,
+    config::ConfigData,
+    create_config::default_config_data,
+    deploy::{process_deploy, DeployArgs, DEFAULT_DEPLOY_PARALLELISM, DEFAULT_MAX_RESUBMITS},
+    freeze::{process_initialize, process_thaw, InitializeArgs, ThawArgs},
+    guard::{process_guard_add, GuardAddArgs},
+    import_nfts::{
+        process_import, ImportNFTsArgs, NameTemplate, DEFAULT_FLUSH_EVERY,
+        DEFAULT_HASHLIST_BATCH_SIZE, DEFAULT_IMPORT_CONCURRENCY,
+    },
+    mint::{process_mint, MintArgs},
+    priority_fee::PriorityFee,
+    upload::{process_upload, UploadArgs, UploadScope, DEFAULT_UPLOAD_FLUSH_EVERY},
+    verify::{process_verify, VerifyArgs},
+    withdraw::{process_withdraw, WithdrawArgs},
+};
+
+pub use crate::ardrive::{ArDriveDrive, ArDriveFile, FileQuery};
+
+/// Options shared by all API calls.
+#[derive(Clone, Debug)]
+pub struct SugarOptions {
+    /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json".
+    pub keypair: Option<String>,
+    /// RPC Url, uses Sol config when not specified.
+    pub rpc_url: Option<String>,
+    /// Path to the cache file.
+    pub cache: String,
+    /// Path to the config file.
+    pub config: String,
+    /// Priority fee value.
+    pub priority_fee: u64,
+    /// Number of completed uploads between two writes of the cache (0 writes it at the end
+    /// only).
+    pub flush_every: usize,
+    /// Compute unit limit and simulation of the transactions (`--compute-unit-limit` and
+    /// `--simulate`).
+    pub compute_budget: ComputeBudget,
+    /// Skip the check that the RPC is connected to the cluster recorded in the cache.
+    pub allow_cluster_mismatch: bool,
+}
+
+impl Default for SugarOptions {
+    fn default() -> Self {
+        SugarOptions {
+            keypair: None,
+            rpc_url: None,
+            cache: DEFAULT_CACHE.to_string(),
+            config: DEFAULT_CONFIG.to_string(),
+            priority_fee: DEFAULT_PRIORITY_FEE,
+            flush_every: DEFAULT_UPLOAD_FLUSH_EVERY,
+            compute_budget: ComputeBudget::default(),
+            allow_cluster_mismatch: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct UploadResult {
+    /// Number of items in the cache (excluding the collection item).
+    pub items: usize,
+    /// Metadata link of the collection item, if present.
+    pub collection_metadata_link: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct DeployResult {
+    pub candy_machine: Pubkey,
+    pub collection_mint: Pubkey,
+}
+
+#[derive(Clone, Debug)]
+pub struct VerifyResult {
+    pub candy_machine: Pubkey,
+    /// Number of items verified on-chain.
+    pub items: usize,
+}
+
+/// Creates the config file without prompting, using the values of the metadata files in
+/// `assets_dir` and the keypair as the only creator. Returns the config saved.
+pub fn create_config(options: &SugarOptions, assets_dir: &str) -> Result<ConfigData> {
+    let sugar_config = sugar_setup(options.keypair.clone(), options.rpc_url.clone())?;
+    let config_data = default_config_data(assets_dir, sugar_config.keypair.pubkey())?;
+
+    let file = File::create(&options.config)?;
+    serde_json::to_writer_pretty(file, &config_data)?;
+
+    Ok(config_data)
+}
+
+/// Uploads the assets from `assets_dir` and returns a summary of the cache.
+pub async fn upload(options: &SugarOptions, assets_dir: &str) -> Result<UploadResult> {
+    process_upload(UploadArgs {
+        assets_dir: assets_dir.to_string(),
+        config: options.config.clone(),
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        cache: options.cache.clone(),
+        interrupted: Arc::new(AtomicBool::new(false)),
+        priority_fee: options.priority_fee,
+        concurrency: None,
+        only_missing: false,
+        force: false,
+        dry_run: false,
+        auto_fund: false,
+        max_fund_lamports: None,
+        bundlr_node: None,
+        requests_per_second: None,
+        scope: UploadScope::All,
+        error_report: DEFAULT_UPLOAD_ERROR_REPORT.to_string(),
+        from_manifest: None,
+        shuffle: false,
+        shuffle_seed: None,
+        summary: None,
+        recursive: false,
+        optimize_images: false,
+        verify_receipts: false,
+        receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+        flush_every: options.flush_every,
+    })
+    .await?;
+
+    let cache = load_cache(&options.cache, false)?;
+
+    Ok(UploadResult {
+        items: cache.items.keys().filter(|key| *key != "-1").count(),
+        collection_metadata_link: cache.items.get("-1").map(|item| item.metadata_link.clone()),
+    })
+}
+
+/// Deploys the cache items to a candy machine.
+pub async fn deploy(
+    options: &SugarOptions,
+    collection_mint: Option<String>,
+) -> Result<DeployResult> {
+    process_deploy(DeployArgs {
+        config: options.config.clone(),
+        cache: options.cache.clone(),
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        interrupted: Arc::new(AtomicBool::new(false)),
+        collection_mint,
+        priority_fee: PriorityFee::MicroLamportsPerCu(options.priority_fee),
+        max_total_priority_sol: None,
+        dry_run: false,
+        force: false,
+        lines_per_tx: None,
+        parallelism: DEFAULT_DEPLOY_PARALLELISM,
+        max_resubmits: DEFAULT_MAX_RESUBMITS,
+        unsigned_tx: None,
+        use_lut: false,
+        chunk: None,
+        chunk_size: None,
+        verify: false,
+        candy_machine_keypair: None,
+        compute_budget: options.compute_budget,
+        allow_cluster_mismatch: options.allow_cluster_mismatch,
+    })
+    .await?;
+
+    let cache = load_cache(&options.cache, false)?;
+
+    Ok(DeployResult {
+        candy_machine: Pubkey::from_str(&cache.program.candy_machine)?,
+        collection_mint: Pubkey::from_str(&cache.program.collection_mint)?,
+    })
+}
+
+/// Mints `number` NFTs from the candy machine to `receiver` (defaults to the keypair).
+pub async fn mint(options: &SugarOptions, number: u64, receiver: Option<String>) -> Result<()> {
+    process_mint(MintArgs {
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        cache: options.cache.clone(),
+        number: Some(number),
+        receiver,
+        candy_machine: None,
+        priority_fee: PriorityFee::MicroLamportsPerCu(options.priority_fee),
+        max_total_priority_sol: None,
+        compressed: false,
+        merkle_tree: None,
+        config: options.config.clone(),
+        use_lut: false,
+        chunk: None,
+        compute_budget: options.compute_budget,
+        allow_cluster_mismatch: options.allow_cluster_mismatch,
+    })
+    .await
+}
+
+/// Verifies that the cache items match the on-chain config lines.
+pub async fn verify(options: &SugarOptions) -> Result<VerifyResult> {
+    process_verify(VerifyArgs {
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        cache: options.cache.clone(),
+        chunk: None,
+        ipfs_gateway: DEFAULT_IPFS_GATEWAY.to_string(),
+    })
+    .await?;
+
+    let cache = load_cache(&options.cache, false)?;
+
+    Ok(VerifyResult {
+        candy_machine: Pubkey::from_str(&cache.program.candy_machine)?,
+        items: cache
+            .items
+            .iter()
+            .filter(|(key, item)| *key != "-1" && item.on_chain)
+            .count(),
+    })
+}
+
+/// Creates a candy guard with the guards of the config file and wraps the candy machine.
+/// Returns the candy guard id.
+pub fn guard_add(options: &SugarOptions) -> Result<Pubkey> {
+    process_guard_add(GuardAddArgs {
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        cache: options.cache.clone(),
+        config: options.config.clone(),
+        candy_machine: None,
+        candy_guard: None,
+        priority_fee: options.priority_fee,
+        no_wrap: false,
+        dry_run: false,
+        yes: true,
+        compute_budget: options.compute_budget,
+        allow_cluster_mismatch: options.allow_cluster_mismatch,
+    })?;
+
+    let cache = load_cache(&options.cache, false)?;
+    Ok(Pubkey::from_str(&cache.program.candy_guard)?)
+}
+
+/// Initializes the freeze escrow of the freeze guard (`label` selects the group).
+pub fn freeze_initialize(options: &SugarOptions, label: Option<String>, period: u64) -> Result<()> {
+    process_initialize(InitializeArgs {
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        cache: options.cache.clone(),
+        config: options.config.clone(),
+        candy_guard: None,
+        candy_machine: None,
+        label,
+        period,
+        priority_fee: options.priority_fee,
+        compute_budget: options.compute_budget,
+        allow_cluster_mismatch: options.allow_cluster_mismatch,
+    })
+}
+
+/// Thaws all the NFTs frozen by the freeze guard (`label` selects the group).
+pub async fn thaw_all(options: &SugarOptions, label: Option<String>) -> Result<()> {
+    process_thaw(ThawArgs {
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        cache: options.cache.clone(),
+        config: options.config.clone(),
+        all: true,
+        nft_mint: None,
+        candy_guard: None,
+        candy_machine: None,
+        destination: None,
+        label,
+        use_cache: false,
+        timeout: None,
+        token: false,
+        priority_fee: options.priority_fee,
+        compute_budget: options.compute_budget,
+        allow_cluster_mismatch: options.allow_cluster_mismatch,
+    })
+    .await
+}
+
+/// Withdraws the rent of the candy machine from the cache, closing the account.
+pub fn withdraw(options: &SugarOptions) -> Result<()> {
+    let cache = load_cache(&options.cache, false)?;
+
+    process_withdraw(WithdrawArgs {
+        candy_machine: Some(cache.program.candy_machine),
+        keypair: options.keypair.clone(),
+        rpc_url: options.rpc_url.clone(),
+        list: false,
+        authority: None,
+        priority_fee: options.priority_fee,
+        cache: options.cache.clone(),
+        chunk: None,
+        allow_cluster_mismatch: options.allow_cluster_mismatch,
+    })
+}
+
+/// Imports a list of metadata URLs into a new cache file and returns it, fetching the
+/// metadata to name the items and link their images.
+pub async fn import(input_file: &Path, output_file: &Path) -> Result<Cache> {
+    process_import(ImportNFTsArgs {
+        import: Some(input_file.to_path_buf()),
+        hashlist: None,
+        batch_size: DEFAULT_HASHLIST_BATCH_SIZE,
+        from_candy_machine: None,
+        keypair: None,
+        rpc_url: None,
+        format: None,
+        column_map: None,
+        output: output_file.to_path_buf(),
+        concurrency: DEFAULT_IMPORT_CONCURRENCY,
+        offline: false,
+        allow_duplicates: false,
+        skip_invalid: false,
+        dry_run: false,
+        name_template: NameTemplate::default(),
+        force_template: false,
+        verify: false,
+        continue_on_error: false,
+        update_hash: false,
+        config: DEFAULT_CONFIG.to_string(),
+        flush_every: DEFAULT_FLUSH_EVERY,
+        ipfs_gateway: DEFAULT_IPFS_GATEWAY.to_string(),
+        fresh: false,
+        append: false,
+        overwrite_items: false,
+        start_index: None,
+        renumber: false,
+        chunk_size: None,
+        chunk_prefix: None,
+        force: true,
+    })
+    .await?;
+    load_cache(&path_to_string(output_file)?, false)
+}
+
+/// Lists all ArDrive drives of the wallet.
+pub async fn ardrive_list_drives(wallet: Option<PathBuf>) -> Result<Vec<ArDriveDrive>> {
+    process_ardrive_list_all_drives(
+        wallet,
+        None,
+        None,
+        OutputFormat::Table,
+        false,
+        &ArdriveOptions::default(),
+    )
+    .await
+}
+
+/// Lists the files of an ArDrive drive, optionally filtered by extension.
+pub async fn ardrive_list_files(
+    wallet: Option<PathBuf>,
+    drive_id: String,
+    filter_ext: Option<&str>,
+) -> Result<Vec<ArDriveFile>> {
+    let query = FileQuery {
+        extension: filter_ext.map(String::from),
+        ..FileQuery::default()
+    };
+    process_ardrive_list_drive_files(ArdriveListDriveFilesArgs {
+        wallet,
+        drive_id,
+        output_path: None,
+        query,
+        drive_password: None,
+        format: OutputFormat::Table,
+        use_node_cli: false,
+        check_status: false,
+        options: ArdriveOptions::default(),
+    })
+    .await
+}
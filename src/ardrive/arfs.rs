@@ -0,0 +1,1175 @@
+//! Native ArFS reads using the Arweave GraphQL gateway.
+//!
+//! ArFS entities are Arweave transactions: their tags identify the entity (`Entity-Type`,
+//! `Drive-Id`, `File-Id`) and their data holds the entity metadata (name, size, data
+//! transaction). Every update creates a new transaction, so only the most recent
+//! transaction of each entity is used. The metadata of private drives is encrypted and is
+//! only read when the drive password is provided.
+
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+};
+
+use anyhow::{anyhow, Context, Result};
+use data_encoding::BASE64URL_NOPAD;
+use futures::{stream, StreamExt};
+use reqwest::{Client, StatusCode, Url};
+use serde::Serialize;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tracing::info;
+
+use super::{
+    crypto::{self, EntityKey},
+    get_arweave_url, progress_spinner, with_retry, ArDriveDrive, ArDriveError, ArDriveFile,
+    ArdriveOptions, EntityType, FileQuery, FolderPaths, TransientError,
+};
+use crate::utils::ProgressBar;
+
+/// Default Arweave gateway.
+pub const DEFAULT_GATEWAY: &str = "https://arweave.net";
+
+/// Environment variable setting the Arweave gateway when `--gateway` is not used.
+pub const GATEWAY_ENV: &str = "SUGAR_ARWEAVE_GATEWAY";
+
+/// Returns the Arweave gateway (without trailing slash) of the GraphQL queries, downloads and
+/// generated links: the `--gateway` URL, falling back to the `SUGAR_ARWEAVE_GATEWAY`
+/// environment variable and then to arweave.net.
+pub fn resolve_gateway(url: Option<String>) -> Result<String> {
+    let url = url.or_else(|| env::var(GATEWAY_ENV).ok().filter(|v| !v.trim().is_empty()));
+
+    let gateway = match url {
+        Some(url) => parse_gateway(&url)?,
+        None => DEFAULT_GATEWAY.to_string(),
+    };
+    info!("Using Arweave gateway {}", gateway);
+
+    Ok(gateway)
+}
+
+fn parse_gateway(url: &str) -> Result<String> {
+    let url = url.trim().trim_end_matches('/');
+    let parsed =
+        Url::parse(url).map_err(|e| anyhow!("Invalid Arweave gateway '{}': {}", url, e))?;
+
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+        return Err(anyhow!(
+            "Invalid Arweave gateway '{}': expected an http(s) URL",
+            url
+        ));
+    }
+
+    Ok(url.to_string())
+}
+
+/// HTTP client of the gateway requests, retried with the options of the ardrive command.
+#[derive(Clone)]
+pub struct ArweaveClient {
+    pub(super) http: Client,
+    pub(super) options: ArdriveOptions,
+}
+
+impl ArweaveClient {
+    pub fn new(options: &ArdriveOptions) -> Self {
+        Self {
+            http: Client::new(),
+            options: options.clone(),
+        }
+    }
+}
+
+/// Number of winston in one AR.
+pub const WINSTON_PER_AR: u128 = 1_000_000_000_000;
+
+// Number of transactions per GraphQL page (gateway maximum).
+const PAGE_SIZE: usize = 100;
+
+// Number of entity metadata requests in parallel.
+const PARALLEL_REQUESTS: usize = 10;
+
+const QUERY: &str = r#"query($owners: [String!], $tags: [TagFilter!], $first: Int, $after: String) {
+  transactions(owners: $owners, tags: $tags, first: $first, after: $after, sort: HEIGHT_DESC) {
+    pageInfo { hasNextPage }
+    edges { cursor node { id tags { name value } } }
+  }
+}"#;
+
+const TRANSACTION_QUERY: &str = r#"query($ids: [ID!], $tags: [TagFilter!]) {
+  transactions(ids: $ids, tags: $tags, first: 1, sort: HEIGHT_DESC) {
+    edges { node { id owner { address } data { size } block { height timestamp } tags { name value } } }
+  }
+}"#;
+
+/// ArFS entity transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entity {
+    pub tx_id: String,
+    pub tags: HashMap<String, String>,
+}
+
+impl Entity {
+    fn tag(&self, name: &str) -> Option<String> {
+        self.tags.get(name).cloned()
+    }
+
+    /// Entities of private drives are encrypted and their metadata can only be read with the
+    /// drive password.
+    fn is_encrypted(&self) -> bool {
+        self.tags.contains_key("Cipher")
+    }
+
+    /// Derives the key of a private drive entity.
+    fn drive_key(&self, wallet: &str, password: &str) -> Result<EntityKey> {
+        let drive_id = self
+            .tag("Drive-Id")
+            .ok_or_else(|| anyhow!("Drive {} has no Drive-Id tag", self.tx_id))?;
+
+        // signature type 2 drives store the key signature in a separate (encrypted) entity
+        if self.tag("Signature-Type").as_deref() == Some("2") {
+            return Err(anyhow!(
+                "Private drive {} uses signature type 2, list it with --use-node-cli",
+                drive_id
+            ));
+        }
+
+        crypto::drive_key(wallet, &drive_id, password)
+    }
+}
+
+/// Transaction with its owner and block, as returned by the transaction query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Transaction {
+    entity: Entity,
+    owner: Option<String>,
+    data_size: Option<u64>,
+    /// Missing while the transaction is pending.
+    block_height: Option<u64>,
+    block_timestamp: Option<u64>,
+}
+
+/// Details of an ArFS entity, or of a transaction that is not an ArFS entity (e.g. the data
+/// transaction of a file).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntityInfo {
+    /// drive, folder, file or transaction.
+    pub kind: String,
+    /// Drive, folder or file ID (the transaction ID for a transaction).
+    pub id: String,
+    /// Transaction of the latest revision of the entity.
+    pub tx_id: String,
+    pub name: Option<String>,
+    pub drive_id: Option<String>,
+    pub parent_folder_id: Option<String>,
+    pub root_folder_id: Option<String>,
+    pub data_tx_id: Option<String>,
+    pub size: Option<u64>,
+    pub content_type: Option<String>,
+    /// Arweave address of the owner.
+    pub owner: Option<String>,
+    pub block_height: Option<u64>,
+    /// Unix time (in seconds) of the block.
+    pub block_timestamp: Option<u64>,
+    /// Unix time (in seconds) of the ArFS transaction.
+    pub unix_time: Option<u64>,
+    /// Unix time (in milliseconds) of the last modification of a file.
+    pub last_modified_date: Option<u64>,
+    /// The metadata of private entities is encrypted and is not read.
+    pub private: bool,
+}
+
+/// Finds an entity by drive, folder or file ID, or by transaction ID (the metadata transaction
+/// of an entity or any other transaction), returning `None` if the gateway does not know it.
+pub async fn entity_info(options: &ArdriveOptions, id: &str) -> Result<Option<EntityInfo>> {
+    info!("ArFS: looking up {}", id);
+
+    let client = ArweaveClient::new(options);
+
+    let transaction = if is_entity_id(id) {
+        let mut found = None;
+        for (id_tag, entity_type) in [
+            ("Drive-Id", "drive"),
+            ("Folder-Id", "folder"),
+            ("File-Id", "file"),
+        ] {
+            found = query_transaction(&client, None, &[(id_tag, id), ("Entity-Type", entity_type)])
+                .await?;
+            if found.is_some() {
+                break;
+            }
+        }
+        found
+    } else {
+        query_transaction(&client, Some(id), &[]).await?
+    };
+
+    let transaction = match transaction {
+        Some(transaction) => transaction,
+        None => return Ok(None),
+    };
+
+    let metadata = if transaction.entity.tags.contains_key("Entity-Type") {
+        fetch_metadata(&client, std::slice::from_ref(&transaction.entity), &[None])
+            .await
+            .into_iter()
+            .next()
+            .transpose()?
+            .flatten()
+    } else {
+        None
+    };
+
+    Ok(Some(entity_info_from(transaction, metadata)))
+}
+
+/// Returns `true` if the ID is an ArFS entity ID (a UUID) rather than a transaction ID.
+pub(super) fn is_entity_id(id: &str) -> bool {
+    id.len() == 36
+        && id.chars().enumerate().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+fn entity_info_from(transaction: Transaction, metadata: Option<Value>) -> EntityInfo {
+    let entity = transaction.entity;
+    let metadata = metadata.unwrap_or_default();
+
+    let kind = entity
+        .tag("Entity-Type")
+        .unwrap_or_else(|| "transaction".to_string());
+    let id = match kind.as_str() {
+        "drive" => entity.tag("Drive-Id"),
+        "folder" => entity.tag("Folder-Id"),
+        "file" => entity.tag("File-Id"),
+        _ => None,
+    };
+    let is_transaction = kind == "transaction";
+
+    EntityInfo {
+        id: id.unwrap_or_else(|| entity.tx_id.clone()),
+        name: metadata["name"].as_str().map(String::from),
+        drive_id: entity.tag("Drive-Id"),
+        parent_folder_id: entity.tag("Parent-Folder-Id"),
+        root_folder_id: metadata["rootFolderId"].as_str().map(String::from),
+        data_tx_id: metadata["dataTxId"].as_str().map(String::from),
+        size: if is_transaction {
+            transaction.data_size
+        } else {
+            metadata["size"].as_u64()
+        },
+        content_type: metadata["dataContentType"]
+            .as_str()
+            .map(String::from)
+            .or_else(|| entity.tag("Content-Type")),
+        owner: transaction.owner,
+        block_height: transaction.block_height,
+        block_timestamp: transaction.block_timestamp,
+        unix_time: entity.tag("Unix-Time").and_then(|time| time.parse().ok()),
+        last_modified_date: metadata["lastModifiedDate"].as_u64(),
+        private: entity.is_encrypted(),
+        tx_id: entity.tx_id,
+        kind,
+    }
+}
+
+/// Returns the Arweave address of a JWK wallet (base64url of the SHA-256 of the modulus).
+pub fn wallet_address(wallet: &str) -> Result<String> {
+    let jwk: Value = serde_json::from_str(wallet).context("ArDrive wallet is not valid JSON")?;
+    let modulus = jwk["n"]
+        .as_str()
+        .ok_or_else(|| anyhow!("ArDrive wallet is missing the 'n' (modulus) field"))?;
+    let modulus = BASE64URL_NOPAD
+        .decode(modulus.trim_end_matches('=').as_bytes())
+        .map_err(|e| anyhow!("Invalid wallet modulus: {e}"))?;
+
+    Ok(BASE64URL_NOPAD.encode(&Sha256::digest(modulus)))
+}
+
+/// Lists the drives owned by the wallet; private drives are decrypted with `password`.
+pub async fn list_drives(
+    options: &ArdriveOptions,
+    wallet: &str,
+    password: Option<&str>,
+) -> Result<Vec<ArDriveDrive>> {
+    let owner = wallet_address(wallet)?;
+    info!("ArFS: listing drives of {}", owner);
+
+    let client = ArweaveClient::new(options);
+    let entities = latest_entities(
+        query_entities(&client, &owner, &[("Entity-Type", "drive")]).await?,
+        "Drive-Id",
+        &mut HashSet::new(),
+    );
+
+    let keys = entities
+        .iter()
+        .map(|entity| match password {
+            Some(password) if entity.is_encrypted() => entity.drive_key(wallet, password).map(Some),
+            _ => Ok(None),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let metadata = fetch_metadata(&client, &entities, &keys).await;
+
+    entities
+        .into_iter()
+        .zip(metadata)
+        .map(|(entity, metadata)| metadata.map(|metadata| drive_from_entity(entity, metadata)))
+        .collect()
+}
+
+/// Lists the files of a drive owned by the wallet matching the query; the files of a private
+/// drive are decrypted with `password`. The folders of the drive are listed first to resolve
+/// the paths of the files, then the transactions are read one GraphQL page at a time, stopping
+/// as soon as the requested page of files is filled.
+pub async fn list_drive_files(
+    options: &ArdriveOptions,
+    wallet: &str,
+    drive_id: &str,
+    query: &FileQuery,
+    password: Option<&str>,
+) -> Result<Vec<ArDriveFile>> {
+    let owner = wallet_address(wallet)?;
+    info!("ArFS: listing files of drive {} ({})", drive_id, owner);
+
+    let client = ArweaveClient::new(options);
+
+    let drive_key = match password {
+        Some(password) => private_drive_key(&client, wallet, &owner, drive_id, password).await?,
+        None => None,
+    };
+
+    let pb = progress_spinner(options, "Listing the drive files...");
+
+    let folder_query = FileQuery {
+        entity_type: EntityType::Folder,
+        ..FileQuery::default()
+    };
+    let mut folders = list_entities(
+        &client,
+        &owner,
+        drive_id,
+        &folder_query,
+        drive_key,
+        &FolderPaths::default(),
+        &pb,
+    )
+    .await?;
+    let paths = FolderPaths::new(&folders);
+
+    let files = if query.entity_type == EntityType::Folder {
+        paths.set_paths(&mut folders);
+        query.apply(folders)
+    } else {
+        query.apply(list_entities(&client, &owner, drive_id, query, drive_key, &paths, &pb).await?)
+    };
+
+    pb.finish_and_clear();
+
+    Ok(files)
+}
+
+/// Lists the entities of a drive matching the query (with their paths), stopping once the
+/// page is filled.
+async fn list_entities(
+    client: &ArweaveClient,
+    owner: &str,
+    drive_id: &str,
+    query: &FileQuery,
+    drive_key: Option<EntityKey>,
+    paths: &FolderPaths,
+    pb: &ProgressBar,
+) -> Result<Vec<ArDriveFile>> {
+    let (entity_type, id_tag) = match query.entity_type {
+        EntityType::File => ("file", "File-Id"),
+        EntityType::Folder => ("folder", "Folder-Id"),
+    };
+
+    let mut tags = vec![("Drive-Id", drive_id), ("Entity-Type", entity_type)];
+    if let Some(folder) = &query.parent_folder {
+        tags.push(("Parent-Folder-Id", folder.as_str()));
+    }
+
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    let mut after: Option<String> = None;
+
+    loop {
+        let (entities, cursor) = query_page(client, owner, &tags, after).await?;
+        let entities = latest_entities(entities, id_tag, &mut seen);
+
+        // folder metadata is encrypted with the drive key, file metadata with the file key
+        let keys = entities
+            .iter()
+            .map(|entity| match (&drive_key, entity.tag(id_tag)) {
+                (Some(drive_key), Some(_)) if query.entity_type == EntityType::Folder => {
+                    Ok(entity.is_encrypted().then_some(*drive_key))
+                }
+                (Some(drive_key), Some(file_id)) if entity.is_encrypted() => {
+                    crypto::file_key(drive_key, &file_id).map(Some)
+                }
+                _ => Ok(None),
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let metadata = fetch_metadata(client, &entities, &keys).await;
+
+        for (entity, metadata) in entities.into_iter().zip(metadata) {
+            let metadata = metadata?;
+            // hidden in the ArDrive apps (their latest revision has `isHidden`)
+            if is_hidden(metadata.as_ref()) {
+                continue;
+            }
+
+            let mut file = file_from_entity(entity, metadata);
+            file.path = paths.path_of(&file);
+            if query.matches(&file) {
+                files.push(file);
+            }
+        }
+
+        pb.set_message(format!("Listing the drive files: {} found", files.len()));
+
+        if query.is_filled(files.len()) {
+            break;
+        }
+
+        match cursor {
+            Some(cursor) => after = Some(cursor),
+            None => break,
+        }
+    }
+
+    Ok(files)
+}
+
+/// Finds the folder named `name` in the parent folder, returning its ID.
+pub async fn find_folder(
+    options: &ArdriveOptions,
+    wallet: &str,
+    drive_id: &str,
+    parent_folder_id: &str,
+    name: &str,
+) -> Result<Option<String>> {
+    let owner = wallet_address(wallet)?;
+    info!(
+        "ArFS: looking for folder '{}' in {} of drive {}",
+        name, parent_folder_id, drive_id
+    );
+
+    let client = ArweaveClient::new(options);
+    let entities = latest_entities(
+        query_entities(
+            &client,
+            &owner,
+            &[
+                ("Drive-Id", drive_id),
+                ("Entity-Type", "folder"),
+                ("Parent-Folder-Id", parent_folder_id),
+            ],
+        )
+        .await?,
+        "Folder-Id",
+        &mut HashSet::new(),
+    );
+
+    let metadata = fetch_metadata(&client, &entities, &vec![None; entities.len()]).await;
+
+    for (entity, metadata) in entities.into_iter().zip(metadata) {
+        let metadata = metadata?;
+        if !is_hidden(metadata.as_ref())
+            && metadata.and_then(|m| m["name"].as_str().map(String::from)) == Some(name.to_string())
+        {
+            return Ok(entity.tag("Folder-Id"));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the price (in winston) to store `bytes` bytes on Arweave.
+pub async fn storage_price(options: &ArdriveOptions, bytes: u64) -> Result<u128> {
+    let url = format!("{}/price/{bytes}", options.gateway);
+    let price = get_bytes(&ArweaveClient::new(options), &url, "Arweave price endpoint").await?;
+
+    parse_winston(&price, "storage price")
+}
+
+/// Returns the balance (in winston) of an Arweave address.
+pub async fn wallet_balance(options: &ArdriveOptions, address: &str) -> Result<u128> {
+    let url = format!("{}/wallet/{address}/balance", options.gateway);
+    let balance = get_bytes(&ArweaveClient::new(options), &url, "Arweave wallet balance").await?;
+
+    parse_winston(&balance, "wallet balance")
+}
+
+/// Returns the status of a transaction: 200 when confirmed, 202 when pending and 404 when the
+/// gateway does not know it (yet).
+pub async fn transaction_status(client: &ArweaveClient, tx_id: &str) -> Result<StatusCode> {
+    let url = &format!("{}/tx/{}/status", client.options.gateway, tx_id);
+
+    with_retry(
+        &client.options,
+        &format!("Status of transaction {tx_id}"),
+        move || async move {
+            let status = client
+                .http
+                .get(url)
+                .send()
+                .await
+                .context("transaction status request failed")?
+                .status();
+
+            if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+                return Err(
+                    ArDriveError::http_status("transaction status endpoint", status).into(),
+                );
+            }
+
+            Ok(status)
+        },
+    )
+    .await
+}
+
+/// Formats a winston amount in AR.
+pub fn format_ar(winston: u128) -> String {
+    format!(
+        "{}.{:012}",
+        winston / WINSTON_PER_AR,
+        winston % WINSTON_PER_AR
+    )
+}
+
+fn parse_winston(body: &[u8], what: &str) -> Result<u128> {
+    let value = String::from_utf8_lossy(body);
+
+    value
+        .trim()
+        .parse()
+        .map_err(|_| anyhow!("Invalid {} returned by Arweave: {}", what, value))
+}
+
+/// Returns the key of the drive if it is private, checking the password by decrypting the
+/// drive metadata.
+async fn private_drive_key(
+    client: &ArweaveClient,
+    wallet: &str,
+    owner: &str,
+    drive_id: &str,
+    password: &str,
+) -> Result<Option<EntityKey>> {
+    let entities = latest_entities(
+        query_entities(
+            client,
+            owner,
+            &[("Drive-Id", drive_id), ("Entity-Type", "drive")],
+        )
+        .await?,
+        "Drive-Id",
+        &mut HashSet::new(),
+    );
+
+    let entity = match entities.first() {
+        Some(entity) if entity.is_encrypted() => entity,
+        Some(_) => return Ok(None),
+        None => {
+            return Err(ArDriveError::InvalidDriveId {
+                drive_id: drive_id.to_string(),
+                reason: "drive not found".to_string(),
+            }
+            .into())
+        }
+    };
+
+    let key = entity.drive_key(wallet, password)?;
+
+    for metadata in fetch_metadata(client, &entities[..1], &[Some(key)]).await {
+        metadata?;
+    }
+
+    Ok(Some(key))
+}
+
+/// Queries all transactions of `owner` with the specified tags (newest first).
+async fn query_entities(
+    client: &ArweaveClient,
+    owner: &str,
+    tags: &[(&str, &str)],
+) -> Result<Vec<Entity>> {
+    let mut entities = Vec::new();
+    let mut after: Option<String> = None;
+
+    loop {
+        let (mut page_entities, cursor) = query_page(client, owner, tags, after).await?;
+        entities.append(&mut page_entities);
+
+        match cursor {
+            Some(cursor) => after = Some(cursor),
+            None => break,
+        }
+    }
+
+    Ok(entities)
+}
+
+/// Queries a page of transactions of `owner` with the specified tags, returning the
+/// entities and the cursor of the next page.
+async fn query_page(
+    client: &ArweaveClient,
+    owner: &str,
+    tags: &[(&str, &str)],
+    after: Option<String>,
+) -> Result<(Vec<Entity>, Option<String>)> {
+    let tags: Vec<Value> = tags
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "values": [value] }))
+        .collect();
+
+    let body = json!({
+        "query": QUERY,
+        "variables": {
+            "owners": [owner],
+            "tags": tags,
+            "first": PAGE_SIZE,
+            "after": after,
+        }
+    });
+
+    parse_page(&graphql(client, &body).await?)
+}
+
+/// Queries the latest transaction with the ID (of any owner) and tags.
+async fn query_transaction(
+    client: &ArweaveClient,
+    id: Option<&str>,
+    tags: &[(&str, &str)],
+) -> Result<Option<Transaction>> {
+    let tags: Vec<Value> = tags
+        .iter()
+        .map(|(name, value)| json!({ "name": name, "values": [value] }))
+        .collect();
+
+    let body = json!({
+        "query": TRANSACTION_QUERY,
+        "variables": {
+            "ids": id.map(|id| vec![id]),
+            "tags": tags,
+        }
+    });
+
+    parse_transaction(&graphql(client, &body).await?)
+}
+
+/// Sends a GraphQL query to the gateway, retrying transient errors.
+async fn graphql(client: &ArweaveClient, body: &Value) -> Result<Value> {
+    let url = &format!("{}/graphql", client.options.gateway);
+    with_retry(
+        &client.options,
+        "Arweave GraphQL query",
+        move || async move {
+            let response = client
+                .http
+                .post(url)
+                .json(body)
+                .send()
+                .await
+                .context("Failed to query the Arweave GraphQL gateway")?;
+
+            let status = response.status();
+            if !status.is_success() {
+                return Err(ArDriveError::http_status("Arweave GraphQL gateway", status).into());
+            }
+
+            response
+                .json()
+                .await
+                .context("Failed to read the Arweave GraphQL response")
+        },
+    )
+    .await
+}
+
+/// Downloads the content of `url` (`what` describes it in errors), retrying transient errors.
+pub(super) async fn get_bytes(client: &ArweaveClient, url: &str, what: &str) -> Result<Vec<u8>> {
+    get_file_bytes(client, url, what, None).await
+}
+
+/// Downloads the content of a file of `size` bytes (when known), retrying transient errors and
+/// truncated responses.
+pub(super) async fn get_file_bytes(
+    client: &ArweaveClient,
+    url: &str,
+    what: &str,
+    size: Option<u64>,
+) -> Result<Vec<u8>> {
+    with_retry(
+        &client.options,
+        &format!("Download of {what}"),
+        move || async move {
+            let response = client
+                .http
+                .get(url)
+                .send()
+                .await
+                .with_context(|| format!("Failed to download {what}"))?;
+
+            let status = response.status();
+            if !status.is_success() {
+                return Err(ArDriveError::http_status(what, status).into());
+            }
+
+            let data = response
+                .bytes()
+                .await
+                .with_context(|| format!("Failed to download {what}"))?
+                .to_vec();
+
+            match size {
+                Some(size) if data.len() as u64 != size => Err(TransientError::from(anyhow!(
+                    "downloaded {} bytes of {}, expected {}",
+                    data.len(),
+                    what,
+                    size
+                ))
+                .into()),
+                _ => Ok(data),
+            }
+        },
+    )
+    .await
+}
+
+/// Parses a page of GraphQL results, returning the entities and the cursor of the next
+/// page (if there is one).
+fn parse_page(page: &Value) -> Result<(Vec<Entity>, Option<String>)> {
+    if let Some(errors) = page.get("errors") {
+        return Err(anyhow!("Arweave GraphQL query failed: {}", errors));
+    }
+
+    let transactions = &page["data"]["transactions"];
+    let edges = transactions["edges"]
+        .as_array()
+        .ok_or_else(|| anyhow!("Unexpected GraphQL response: {}", page))?;
+
+    let entities = edges
+        .iter()
+        .filter_map(|edge| {
+            let node = &edge["node"];
+            let tags = node["tags"]
+                .as_array()?
+                .iter()
+                .filter_map(|tag| {
+                    Some((
+                        tag["name"].as_str()?.to_string(),
+                        tag["value"].as_str()?.to_string(),
+                    ))
+                })
+                .collect();
+
+            Some(Entity {
+                tx_id: node["id"].as_str()?.to_string(),
+                tags,
+            })
+        })
+        .collect();
+
+    let cursor = if transactions["pageInfo"]["hasNextPage"].as_bool() == Some(true) {
+        edges
+            .last()
+            .and_then(|edge| edge["cursor"].as_str())
+            .map(String::from)
+    } else {
+        None
+    };
+
+    Ok((entities, cursor))
+}
+
+/// Parses the result of the transaction query.
+fn parse_transaction(page: &Value) -> Result<Option<Transaction>> {
+    let (entities, _) = parse_page(page)?;
+    let node = &page["data"]["transactions"]["edges"][0]["node"];
+
+    Ok(entities.into_iter().next().map(|entity| Transaction {
+        entity,
+        owner: node["owner"]["address"].as_str().map(String::from),
+        // sizes are strings in the GraphQL schema
+        data_size: node["data"]["size"]
+            .as_str()
+            .and_then(|size| size.parse().ok()),
+        block_height: node["block"]["height"].as_u64(),
+        block_timestamp: node["block"]["timestamp"].as_u64(),
+    }))
+}
+
+/// Keeps the most recent transaction of each entity (`id_tag` identifies the entity) not in
+/// `seen`; the entities are expected to be sorted newest first.
+fn latest_entities(entities: Vec<Entity>, id_tag: &str, seen: &mut HashSet<String>) -> Vec<Entity> {
+    entities
+        .into_iter()
+        .filter(|entity| match entity.tags.get(id_tag) {
+            Some(id) => seen.insert(id.clone()),
+            None => false,
+        })
+        .collect()
+}
+
+/// Returns `true` if the entity metadata marks it as hidden (`isHidden`).
+fn is_hidden(metadata: Option<&Value>) -> bool {
+    metadata
+        .and_then(|metadata| metadata["isHidden"].as_bool())
+        .unwrap_or(false)
+}
+
+/// Downloads the metadata (transaction data) of the entities, in the same order, decrypting
+/// encrypted entities with their key (`keys` has the key of each entity); encrypted entities
+/// without a key have no metadata.
+async fn fetch_metadata(
+    client: &ArweaveClient,
+    entities: &[Entity],
+    keys: &[Option<EntityKey>],
+) -> Vec<Result<Option<Value>>> {
+    stream::iter(entities.iter().zip(keys))
+        .map(|(entity, key)| async move {
+            let key = match key {
+                Some(key) if entity.is_encrypted() => Some(key),
+                _ if entity.is_encrypted() => return Ok(None),
+                _ => None,
+            };
+
+            let url = get_arweave_url(&client.options.gateway, &entity.tx_id);
+            let data = get_bytes(client, &url, &format!("ArFS metadata {url}")).await?;
+
+            let data = match key {
+                Some(key) => decrypt_metadata(entity, key, &data)?,
+                None => data,
+            };
+
+            Ok(Some(
+                serde_json::from_slice::<Value>(&data)
+                    .with_context(|| format!("Invalid ArFS metadata {url}"))?,
+            ))
+        })
+        .buffered(PARALLEL_REQUESTS)
+        .collect()
+        .await
+}
+
+fn decrypt_metadata(entity: &Entity, key: &EntityKey, data: &[u8]) -> Result<Vec<u8>> {
+    let cipher_iv = entity
+        .tag("Cipher-IV")
+        .ok_or_else(|| anyhow!("Encrypted ArFS entity {} has no Cipher-IV", entity.tx_id))?;
+
+    crypto::decrypt(key, &cipher_iv, data).ok_or_else(|| {
+        match (entity.tag("Entity-Type").as_deref(), entity.tag("Drive-Id")) {
+            (Some("drive"), Some(drive_id)) => {
+                anyhow!("incorrect drive password for drive {}", drive_id)
+            }
+            _ => anyhow!("Failed to decrypt ArFS entity {}", entity.tx_id),
+        }
+    })
+}
+
+fn drive_from_entity(entity: Entity, metadata: Option<Value>) -> ArDriveDrive {
+    let metadata = metadata.unwrap_or_default();
+
+    ArDriveDrive {
+        app_name: entity.tag("App-Name"),
+        app_version: entity.tag("App-Version"),
+        ar_fs: entity.tag("ArFS"),
+        content_type: entity.tag("Content-Type"),
+        drive_id: entity.tag("Drive-Id"),
+        entity_type: entity.tag("Entity-Type"),
+        name: metadata["name"].as_str().map(String::from),
+        unix_time: entity.tag("Unix-Time").and_then(|time| time.parse().ok()),
+        custom_meta_data_gql_tags: None,
+        custom_meta_data_json: None,
+        drive_privacy: entity.tag("Drive-Privacy"),
+        root_folder_id: metadata["rootFolderId"].as_str().map(String::from),
+        drive_auth_mode: entity.tag("Drive-Auth-Mode"),
+        cipher: entity.tag("Cipher"),
+        cipher_iv: entity.tag("Cipher-IV"),
+        drive_signature_type: entity
+            .tag("Signature-Type")
+            .and_then(|signature| signature.parse().ok()),
+        tx_id: Some(entity.tx_id),
+    }
+}
+
+fn file_from_entity(entity: Entity, metadata: Option<Value>) -> ArDriveFile {
+    let metadata = metadata.unwrap_or_default();
+
+    ArDriveFile {
+        entity_type: entity.tag("Entity-Type"),
+        name: metadata["name"].as_str().map(String::from),
+        data_tx_id: metadata["dataTxId"].as_str().map(String::from),
+        metadata_tx_id: Some(entity.tx_id.clone()),
+        parent_folder_id: entity.tag("Parent-Folder-Id"),
+        size: metadata["size"].as_u64(),
+        last_modified_date: metadata["lastModifiedDate"].as_u64(),
+        content_type: entity.tag("Content-Type"),
+        data_content_type: metadata["dataContentType"].as_str().map(String::from),
+        entity_id: entity.tag("File-Id").or_else(|| entity.tag("Folder-Id")),
+        path: None,
+        status: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge(id: &str, tags: &[(&str, &str)]) -> Value {
+        json!({
+            "cursor": format!("cursor-{id}"),
+            "node": {
+                "id": id,
+                "tags": tags
+                    .iter()
+                    .map(|(name, value)| json!({ "name": name, "value": value }))
+                    .collect::<Vec<_>>(),
+            }
+        })
+    }
+
+    fn page(edges: Vec<Value>, has_next_page: bool) -> Value {
+        json!({
+            "data": {
+                "transactions": {
+                    "pageInfo": { "hasNextPage": has_next_page },
+                    "edges": edges,
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn parses_gateways() {
+        assert_eq!(
+            parse_gateway(" https://ar-io.net/ ").unwrap(),
+            "https://ar-io.net"
+        );
+        assert_eq!(
+            parse_gateway("http://localhost:1984").unwrap(),
+            "http://localhost:1984"
+        );
+        assert!(parse_gateway("ar-io.net").is_err());
+        assert!(parse_gateway("ftp://ar-io.net").is_err());
+    }
+
+    #[test]
+    fn formats_winston_amounts() {
+        assert_eq!(format_ar(1_500_000_000_000), "1.500000000000");
+        assert_eq!(format_ar(42), "0.000000000042");
+
+        assert_eq!(parse_winston(b"1500\n", "balance").unwrap(), 1500);
+        assert!(parse_winston(b"Not Found", "balance").is_err());
+    }
+
+    #[test]
+    fn parses_pages_and_cursors() {
+        let (entities, cursor) = parse_page(&page(
+            vec![
+                edge("tx1", &[("Entity-Type", "file"), ("File-Id", "a")]),
+                edge("tx2", &[("Entity-Type", "file"), ("File-Id", "b")]),
+            ],
+            true,
+        ))
+        .unwrap();
+
+        assert_eq!(entities.len(), 2);
+        assert_eq!(entities[1].tx_id, "tx2");
+        assert_eq!(entities[1].tag("File-Id").as_deref(), Some("b"));
+        assert_eq!(cursor.as_deref(), Some("cursor-tx2"));
+
+        let (_, cursor) = parse_page(&page(vec![edge("tx3", &[])], false)).unwrap();
+        assert!(cursor.is_none());
+
+        assert!(parse_page(&json!({ "errors": [{ "message": "bad query" }] })).is_err());
+    }
+
+    #[test]
+    fn reads_entity_details() {
+        let mut file = edge(
+            "metadata-tx",
+            &[
+                ("Entity-Type", "file"),
+                ("File-Id", "0b7e3f52-6f4a-4c1e-9a38-2f1d7c5e8a90"),
+                ("Drive-Id", "drive"),
+                ("Parent-Folder-Id", "folder"),
+                ("Content-Type", "application/json"),
+                ("Unix-Time", "1700000000"),
+            ],
+        );
+        file["node"]["owner"] = json!({ "address": "owner" });
+        file["node"]["data"] = json!({ "size": "120" });
+        file["node"]["block"] = json!({ "height": 1300000, "timestamp": 1700000100 });
+
+        let transaction = parse_transaction(&page(vec![file], false))
+            .unwrap()
+            .unwrap();
+        assert_eq!(transaction.block_height, Some(1_300_000));
+        assert_eq!(transaction.data_size, Some(120));
+
+        let info = entity_info_from(
+            transaction,
+            Some(json!({
+                "name": "0.png",
+                "size": 4096,
+                "dataTxId": "data-tx",
+                "dataContentType": "image/png",
+                "lastModifiedDate": 1700000000000u64
+            })),
+        );
+        assert_eq!(info.kind, "file");
+        assert_eq!(info.id, "0b7e3f52-6f4a-4c1e-9a38-2f1d7c5e8a90");
+        assert_eq!(info.tx_id, "metadata-tx");
+        assert_eq!(info.size, Some(4096));
+        assert_eq!(info.content_type.as_deref(), Some("image/png"));
+        assert_eq!(info.owner.as_deref(), Some("owner"));
+        assert_eq!(info.unix_time, Some(1_700_000_000));
+
+        // pending data transaction, not an ArFS entity
+        let mut data = edge("data-tx", &[("Content-Type", "image/png")]);
+        data["node"]["data"] = json!({ "size": "4096" });
+        let transaction = parse_transaction(&page(vec![data], false))
+            .unwrap()
+            .unwrap();
+        let info = entity_info_from(transaction, None);
+        assert_eq!(info.kind, "transaction");
+        assert_eq!(info.id, "data-tx");
+        assert_eq!(info.size, Some(4096));
+        assert_eq!(info.block_height, None);
+
+        assert!(parse_transaction(&page(vec![], false)).unwrap().is_none());
+
+        assert!(is_entity_id("0b7e3f52-6f4a-4c1e-9a38-2f1d7c5e8a90"));
+        assert!(!is_entity_id("Hk2dQb4cXjJp8RZ9gqkQ2c6yYw1hZ0b7e3f52x6f4a4"));
+    }
+
+    #[test]
+    fn keeps_latest_revision_of_each_entity() {
+        let (entities, _) = parse_page(&page(
+            vec![
+                edge("new", &[("File-Id", "a")]),
+                edge("other", &[("File-Id", "b")]),
+                edge("old", &[("File-Id", "a")]),
+                edge("untagged", &[]),
+            ],
+            false,
+        ))
+        .unwrap();
+
+        let ids: Vec<String> = latest_entities(entities, "File-Id", &mut HashSet::new())
+            .into_iter()
+            .map(|entity| entity.tx_id)
+            .collect();
+
+        assert_eq!(ids, vec!["new", "other"]);
+    }
+
+    #[test]
+    fn detects_hidden_entities() {
+        assert!(is_hidden(Some(
+            &json!({ "name": "0.png", "isHidden": true })
+        )));
+        assert!(!is_hidden(Some(
+            &json!({ "name": "0.png", "isHidden": false })
+        )));
+        assert!(!is_hidden(Some(&json!({ "name": "0.png" }))));
+        // encrypted entities without a key have no metadata
+        assert!(!is_hidden(None));
+    }
+
+    #[test]
+    fn builds_files_and_drives_from_entities() {
+        let (entities, _) = parse_page(&page(
+            vec![
+                edge(
+                    "meta",
+                    &[
+                        ("Entity-Type", "file"),
+                        ("Parent-Folder-Id", "folder"),
+                        ("Content-Type", "application/json"),
+                    ],
+                ),
+                edge(
+                    "drive",
+                    &[
+                        ("Entity-Type", "drive"),
+                        ("Drive-Id", "d"),
+                        ("Drive-Privacy", "private"),
+                        ("Cipher", "AES256-GCM"),
+                        ("Unix-Time", "1700000000"),
+                    ],
+                ),
+            ],
+            false,
+        ))
+        .unwrap();
+
+        let file = file_from_entity(
+            entities[0].clone(),
+            Some(json!({
+                "name": "0.png",
+                "size": 1024,
+                "lastModifiedDate": 1700000000000u64,
+                "dataTxId": "data",
+                "dataContentType": "image/png",
+            })),
+        );
+        assert_eq!(file.name.as_deref(), Some("0.png"));
+        assert_eq!(file.data_tx_id.as_deref(), Some("data"));
+        assert_eq!(file.metadata_tx_id.as_deref(), Some("meta"));
+        assert_eq!(file.parent_folder_id.as_deref(), Some("folder"));
+        assert_eq!(file.size, Some(1024));
+
+        assert!(entities[1].is_encrypted());
+        let drive = drive_from_entity(entities[1].clone(), None);
+        assert_eq!(drive.drive_id.as_deref(), Some("d"));
+        assert_eq!(drive.name, None);
+        assert_eq!(drive.unix_time, Some(1700000000));
+        assert_eq!(drive.tx_id.as_deref(), Some("drive"));
+    }
+
+    #[test]
+    fn reports_incorrect_drive_passwords() {
+        let (entities, _) = parse_page(&page(
+            vec![
+                edge(
+                    "drive",
+                    &[
+                        ("Entity-Type", "drive"),
+                        ("Drive-Id", "d"),
+                        ("Cipher", "AES256-GCM"),
+                        ("Cipher-IV", "AQEBAQEBAQEBAQEB"),
+                    ],
+                ),
+                edge("file", &[("Entity-Type", "file"), ("Cipher", "AES256-GCM")]),
+            ],
+            false,
+        ))
+        .unwrap();
+
+        let error = decrypt_metadata(&entities[0], &[0; 32], &[0; 48]).unwrap_err();
+        assert_eq!(error.to_string(), "incorrect drive password for drive d");
+
+        let error = decrypt_metadata(&entities[1], &[0; 32], &[0; 48]).unwrap_err();
+        assert!(error.to_string().contains("no Cipher-IV"));
+    }
+
+    #[test]
+    fn derives_wallet_address_from_modulus() {
+        let modulus = BASE64URL_NOPAD.encode(b"modulus");
+        let expected = BASE64URL_NOPAD.encode(&Sha256::digest(b"modulus"));
+
+        let address = wallet_address(&json!({ "kty": "RSA", "n": modulus }).to_string()).unwrap();
+        assert_eq!(address, expected);
+        assert_eq!(address.len(), 43);
+
+        assert!(wallet_address("{}").is_err());
+    }
+}
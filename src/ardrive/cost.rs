@@ -0,0 +1,251 @@
+//! Storage cost estimate of an assets directory uploaded to ArDrive.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use tracing::info;
+
+use super::{
+    arfs::{self, format_ar, ArweaveClient, WINSTON_PER_AR},
+    collect_files, ArdriveOptions,
+};
+use crate::output::style;
+
+/// Estimated size of the ArFS metadata transaction of each uploaded file (the JSON metadata
+/// with the name, size, dates, content type and data transaction).
+pub const METADATA_OVERHEAD_BYTES: u64 = 512;
+
+/// Public API of the AR price in USD.
+const FIAT_PRICE_URL: &str =
+    "https://api.coingecko.com/api/v3/simple/price?ids=arweave&vs_currencies=usd";
+
+// extensions of the image and animation files, as paired by the upload of the assets
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "gif", "png"];
+
+const ANIMATION_EXTENSIONS: [&str; 7] = ["mp3", "mp4", "mov", "webm", "glb", "gltf", "html"];
+
+pub struct ArdriveCostArgs {
+    pub assets_dir: PathBuf,
+    /// Also print the approximate cost in USD.
+    pub fiat: bool,
+    pub options: ArdriveOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Category {
+    Images,
+    Metadata,
+    Animations,
+    Other,
+}
+
+impl Category {
+    const ALL: [Category; 4] = [
+        Category::Images,
+        Category::Metadata,
+        Category::Animations,
+        Category::Other,
+    ];
+
+    fn of(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        if extension == "json" {
+            Category::Metadata
+        } else if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            Category::Images
+        } else if ANIMATION_EXTENSIONS.contains(&extension.as_str()) {
+            Category::Animations
+        } else {
+            Category::Other
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Category::Images => "images",
+            Category::Metadata => "metadata",
+            Category::Animations => "animations",
+            Category::Other => "other",
+        }
+    }
+}
+
+/// Number of files and bytes (file data and ArFS metadata) of a category.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Usage {
+    files: usize,
+    bytes: u64,
+}
+
+#[derive(Debug)]
+struct CostBreakdown {
+    usage: Vec<(Category, Usage)>,
+}
+
+impl CostBreakdown {
+    fn new(files: &[(PathBuf, u64)]) -> Self {
+        let usage = Category::ALL
+            .iter()
+            .map(|category| {
+                let usage = files
+                    .iter()
+                    .filter(|(path, _)| Category::of(path) == *category)
+                    .fold(Usage::default(), |usage, (_, size)| Usage {
+                        files: usage.files + 1,
+                        bytes: usage.bytes + size + METADATA_OVERHEAD_BYTES,
+                    });
+                (*category, usage)
+            })
+            .collect();
+
+        CostBreakdown { usage }
+    }
+
+    /// Bytes of the upload, including the metadata of the folder created for the directory.
+    fn total_bytes(&self) -> u64 {
+        self.usage.iter().map(|(_, usage)| usage.bytes).sum::<u64>() + METADATA_OVERHEAD_BYTES
+    }
+
+    fn total_files(&self) -> usize {
+        self.usage.iter().map(|(_, usage)| usage.files).sum()
+    }
+}
+
+/// Prints the estimated cost of uploading an assets directory to ArDrive, per category of
+/// files, with the current Arweave storage price.
+pub async fn process_ardrive_cost(args: ArdriveCostArgs) -> Result<()> {
+    if !args.assets_dir.is_dir() {
+        return Err(anyhow!(
+            "Assets directory {} not found",
+            args.assets_dir.display()
+        ));
+    }
+
+    let breakdown = CostBreakdown::new(&collect_files(&args.assets_dir)?);
+    let total_bytes = breakdown.total_bytes();
+    info!(
+        "ArDrive: cost of {} ({} files, {} bytes)",
+        args.assets_dir.display(),
+        breakdown.total_files(),
+        total_bytes
+    );
+
+    let price = arfs::storage_price(&args.options, total_bytes).await?;
+
+    report!(
+        "{:12} | {:>7} | {:>14} | {:>16}",
+        "category",
+        "files",
+        "bytes",
+        "AR"
+    );
+    report!("{:-<12} | {:-<7} | {:-<14} | {:-<16}", "", "", "", "");
+
+    for (category, usage) in breakdown.usage.iter().filter(|(_, u)| u.files > 0) {
+        report!(
+            "{:12} | {:>7} | {:>14} | {:>16}",
+            category.name(),
+            usage.files,
+            usage.bytes,
+            format_ar(share(price, usage.bytes, total_bytes))
+        );
+    }
+
+    report!(
+        "\nTotal: {} AR ({} winston) for {} file(s), {} bytes including {} bytes of ArFS metadata per file",
+        format_ar(price),
+        price,
+        breakdown.total_files(),
+        total_bytes,
+        METADATA_OVERHEAD_BYTES
+    );
+
+    if args.fiat {
+        match ar_usd_price(&ArweaveClient::new(&args.options)).await {
+            Ok(usd) => report!(
+                "{}",
+                style(format!(
+                    "Approximately ${:.2} USD (at ${:.2}/AR, indicative only)",
+                    price as f64 / WINSTON_PER_AR as f64 * usd,
+                    usd
+                ))
+                .dim()
+            ),
+            Err(err) => {
+                info!("Failed to fetch the AR price: {:#}", err);
+                report!("{}", style("USD estimate unavailable").dim());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Part of the price of `total` bytes corresponding to `bytes`.
+fn share(price: u128, bytes: u64, total: u64) -> u128 {
+    if total == 0 {
+        return 0;
+    }
+
+    price * bytes as u128 / total as u128
+}
+
+async fn ar_usd_price(client: &ArweaveClient) -> Result<f64> {
+    let body = arfs::get_bytes(client, FIAT_PRICE_URL, "AR price API").await?;
+    let value: Value = serde_json::from_slice(&body)?;
+
+    value["arweave"]["usd"]
+        .as_f64()
+        .ok_or_else(|| anyhow!("Unexpected AR price API response: {}", value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cost_breakdown() {
+        let files = vec![
+            (PathBuf::from("assets/0.png"), 1_000),
+            (PathBuf::from("assets/0.json"), 100),
+            (PathBuf::from("assets/1.JPG"), 2_000),
+            (PathBuf::from("assets/1.json"), 100),
+            (PathBuf::from("assets/1.mp4"), 10_000),
+            (PathBuf::from("assets/notes.txt"), 10),
+        ];
+        let breakdown = CostBreakdown::new(&files);
+        let usage = |category: Category| {
+            breakdown
+                .usage
+                .iter()
+                .find(|(c, _)| *c == category)
+                .map(|(_, usage)| *usage)
+                .unwrap()
+        };
+
+        assert_eq!(
+            usage(Category::Images),
+            Usage {
+                files: 2,
+                bytes: 3_000 + 2 * METADATA_OVERHEAD_BYTES
+            }
+        );
+        assert_eq!(usage(Category::Metadata).files, 2);
+        assert_eq!(usage(Category::Animations).files, 1);
+        assert_eq!(usage(Category::Other).files, 1);
+
+        assert_eq!(breakdown.total_files(), 6);
+        assert_eq!(
+            breakdown.total_bytes(),
+            13_210 + 7 * METADATA_OVERHEAD_BYTES
+        );
+
+        assert_eq!(share(1_000, 250, 1_000), 250);
+        assert_eq!(share(1_000, 0, 0), 0);
+    }
+}
@@ -0,0 +1,331 @@
+//! Creation of ArFS drives and folders.
+//!
+//! The entities are created by the ArDrive Node CLI; the command then waits for the gateway to
+//! accept the metadata transactions so that the returned IDs can be used right away (e.g. by
+//! `upload --parent-folder` or `generate-cache --parent-folder`).
+
+use std::{
+    ffi::OsStr,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Result};
+use reqwest::StatusCode;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::info;
+
+use super::{
+    arfs::{self, ArweaveClient},
+    check_drive_id, created_entity, entity_field, listing, progress_spinner,
+    resolve_ardrive_wallet_content, resolve_drive_password, root_folder, run_ardrive,
+    write_temp_wallet, ArdriveOptions, OutputFormat,
+};
+use crate::{constants::WARNING_EMOJI, output::style};
+
+/// Maximum time to wait for the gateway to accept the created transactions.
+const ACCEPTANCE_TIMEOUT: Duration = Duration::from_secs(120);
+
+// Delay between two transaction status checks.
+const ACCEPTANCE_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct ArdriveCreateDriveArgs {
+    pub wallet: Option<PathBuf>,
+    pub name: String,
+    pub private: bool,
+    /// Password of a private drive, defaults to the ARDRIVE_DRIVE_PASSWORD env var.
+    pub drive_password: Option<String>,
+    pub format: OutputFormat,
+    pub options: ArdriveOptions,
+}
+
+pub struct ArdriveCreateFolderArgs {
+    pub wallet: Option<PathBuf>,
+    pub drive_id: String,
+    pub name: String,
+    /// Folder to create the folder in, defaults to the root folder of the drive.
+    pub parent_folder: Option<String>,
+    /// Password of a private drive, defaults to the ARDRIVE_DRIVE_PASSWORD env var.
+    pub drive_password: Option<String>,
+    pub format: OutputFormat,
+    pub options: ArdriveOptions,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedDrive {
+    name: String,
+    drive_id: String,
+    root_folder_id: String,
+    private: bool,
+    metadata_tx_ids: Vec<String>,
+    /// Whether the gateway accepted the transactions before the timeout.
+    accepted: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreatedFolder {
+    name: String,
+    folder_id: String,
+    drive_id: String,
+    parent_folder_id: String,
+    metadata_tx_ids: Vec<String>,
+    /// Whether the gateway accepted the transactions before the timeout.
+    accepted: bool,
+}
+
+/// Creates a drive (and its root folder), printing the drive and root folder IDs. A drive with
+/// the same name in the wallet is reported but does not prevent the creation.
+pub async fn process_ardrive_create_drive(args: ArdriveCreateDriveArgs) -> Result<()> {
+    info!(
+        "ArDrive: create-drive called: name={} private={}",
+        args.name, args.private
+    );
+
+    let password = drive_password(args.private, args.drive_password)?;
+    let content = resolve_ardrive_wallet_content(&args.options, args.wallet)?;
+
+    let drives = arfs::list_drives(&args.options, &content, password.as_deref()).await?;
+    if drives
+        .iter()
+        .any(|drive| drive.name.as_deref() == Some(args.name.as_str()))
+    {
+        args.format.status(format!(
+            "{}{}",
+            WARNING_EMOJI,
+            style(format!(
+                "The wallet already has a drive named '{}', creating another one",
+                args.name
+            ))
+            .yellow()
+        ));
+    }
+
+    let wallet_file = write_temp_wallet(&content)?;
+    let mut command = vec![
+        OsStr::new("create-drive"),
+        OsStr::new("--drive-name"),
+        OsStr::new(&args.name),
+        OsStr::new("--wallet-file"),
+        wallet_file.path().as_os_str(),
+    ];
+    if let Some(password) = &password {
+        command.extend([
+            OsStr::new("--private"),
+            OsStr::new("--unsafe-drive-password"),
+            OsStr::new(password),
+        ]);
+    }
+
+    let mut drive = created_drive(
+        &run_ardrive(&args.options, command)?,
+        &args.name,
+        password.is_some(),
+    )?;
+    drive.accepted =
+        wait_for_acceptance(&args.options, &drive.metadata_tx_ids, args.format).await?;
+
+    match args.format {
+        OutputFormat::Json => report!("{}", serde_json::to_string_pretty(&drive)?),
+        OutputFormat::Table => {
+            report!("✅ Created drive '{}'", drive.name);
+            report!("  Drive ID:       {}", drive.drive_id);
+            report!("  Root folder ID: {}", drive.root_folder_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Creates a folder in a drive (in its root folder unless a parent folder is given), printing
+/// the folder ID.
+pub async fn process_ardrive_create_folder(args: ArdriveCreateFolderArgs) -> Result<()> {
+    info!(
+        "ArDrive: create-folder called: name={} drive={} parent folder={:?}",
+        args.name, args.drive_id, args.parent_folder
+    );
+
+    check_drive_id(&args.drive_id)?;
+    let password = resolve_drive_password(args.drive_password);
+    let content = resolve_ardrive_wallet_content(&args.options, args.wallet)?;
+
+    let parent_folder = match args.parent_folder {
+        Some(folder) => folder,
+        None => root_folder(&args.options, &content, &args.drive_id, password.as_deref()).await?,
+    };
+
+    let wallet_file = write_temp_wallet(&content)?;
+    let mut command = vec![
+        OsStr::new("create-folder"),
+        OsStr::new("--parent-folder-id"),
+        OsStr::new(&parent_folder),
+        OsStr::new("--folder-name"),
+        OsStr::new(&args.name),
+        OsStr::new("--wallet-file"),
+        wallet_file.path().as_os_str(),
+    ];
+    if let Some(password) = &password {
+        command.extend([
+            OsStr::new("--private"),
+            OsStr::new("--unsafe-drive-password"),
+            OsStr::new(password),
+        ]);
+    }
+
+    let output = run_ardrive(&args.options, command)?;
+    let folder = created_entity(&output, "folder")?;
+    listing::invalidate(&args.drive_id);
+
+    let mut folder = CreatedFolder {
+        name: args.name,
+        folder_id: entity_field(folder, "entityId")?,
+        drive_id: args.drive_id,
+        parent_folder_id: parent_folder,
+        metadata_tx_ids: metadata_tx_ids(&output),
+        accepted: false,
+    };
+    folder.accepted =
+        wait_for_acceptance(&args.options, &folder.metadata_tx_ids, args.format).await?;
+
+    match args.format {
+        OutputFormat::Json => report!("{}", serde_json::to_string_pretty(&folder)?),
+        OutputFormat::Table => {
+            report!("✅ Created folder '{}'", folder.name);
+            report!("  Folder ID: {}", folder.folder_id);
+            report!("  Drive ID:  {}", folder.drive_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the password of a new drive: none for a public drive, required for a private one.
+fn drive_password(private: bool, password: Option<String>) -> Result<Option<String>> {
+    if !private {
+        return Ok(None);
+    }
+
+    resolve_drive_password(password).map(Some).ok_or_else(|| {
+        anyhow!("A private drive needs a password (--drive-password or ARDRIVE_DRIVE_PASSWORD)")
+    })
+}
+
+/// Reads the drive and root folder created by `ardrive create-drive`.
+fn created_drive(output: &Value, name: &str, private: bool) -> Result<CreatedDrive> {
+    Ok(CreatedDrive {
+        name: name.to_string(),
+        drive_id: entity_field(created_entity(output, "drive")?, "entityId")?,
+        root_folder_id: entity_field(created_entity(output, "folder")?, "entityId")?,
+        private,
+        metadata_tx_ids: metadata_tx_ids(output),
+        accepted: false,
+    })
+}
+
+/// Returns the metadata transactions of the entities created by an ArDrive CLI command.
+fn metadata_tx_ids(output: &Value) -> Vec<String> {
+    output["created"]
+        .as_array()
+        .map(|created| {
+            created
+                .iter()
+                .filter_map(|entity| entity["metadataTxId"].as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Waits until the gateway knows the transactions (pending or confirmed), returning `false`
+/// if it did not within the timeout.
+async fn wait_for_acceptance(
+    options: &ArdriveOptions,
+    tx_ids: &[String],
+    format: OutputFormat,
+) -> Result<bool> {
+    let client = ArweaveClient::new(options);
+    let start = Instant::now();
+    let pb = progress_spinner(options, "Waiting for the transactions to be accepted...");
+
+    let mut pending: Vec<&String> = tx_ids.iter().collect();
+
+    while !pending.is_empty() {
+        let mut still_pending = Vec::new();
+        for tx_id in pending {
+            let status = arfs::transaction_status(&client, tx_id).await?;
+            if !matches!(status, StatusCode::OK | StatusCode::ACCEPTED) {
+                still_pending.push(tx_id);
+            }
+        }
+        pending = still_pending;
+
+        if pending.is_empty() {
+            break;
+        }
+
+        if start.elapsed() >= ACCEPTANCE_TIMEOUT {
+            pb.finish_and_clear();
+            format.status(format!(
+                "{}{}",
+                WARNING_EMOJI,
+                style(format!(
+                    "The gateway did not accept {} transaction(s) within {} seconds, the entities may take a few minutes to appear",
+                    pending.len(),
+                    ACCEPTANCE_TIMEOUT.as_secs()
+                ))
+                .yellow()
+            ));
+            return Ok(false);
+        }
+
+        tokio::time::sleep(ACCEPTANCE_POLL_INTERVAL).await;
+    }
+
+    pb.finish_and_clear();
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_created_drive() {
+        // output of `ardrive create-drive`
+        let output = json!({
+            "created": [
+                {
+                    "type": "drive",
+                    "metadataTxId": "drive-tx",
+                    "entityId": "e6b3d2a1-0000-4000-8000-000000000001"
+                },
+                {
+                    "type": "folder",
+                    "metadataTxId": "folder-tx",
+                    "entityId": "e6b3d2a1-0000-4000-8000-000000000002"
+                }
+            ],
+            "tips": [],
+            "fees": { "drive-tx": "1000", "folder-tx": "1000" }
+        });
+
+        let drive = created_drive(&output, "collection", false).unwrap();
+        assert_eq!(drive.drive_id, "e6b3d2a1-0000-4000-8000-000000000001");
+        assert_eq!(drive.root_folder_id, "e6b3d2a1-0000-4000-8000-000000000002");
+        assert_eq!(drive.metadata_tx_ids, vec!["drive-tx", "folder-tx"]);
+
+        assert!(created_drive(&json!({ "created": [] }), "collection", false).is_err());
+
+        assert!(drive_password(false, Some("secret".to_string()))
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            drive_password(true, Some("secret".to_string())).unwrap(),
+            Some("secret".to_string())
+        );
+    }
+}
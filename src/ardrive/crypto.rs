@@ -0,0 +1,202 @@
+//! Keys and decryption of private (password-protected) ArFS drives.
+//!
+//! The drive key is derived (HKDF-SHA256) from the drive password and the wallet signature
+//! (RSA-PSS without salt) of the drive ID; each file key is derived from the drive key and the
+//! file ID. Entity metadata is encrypted with AES-256-GCM, the IV being the `Cipher-IV` tag.
+//!
+//! Without salt, RSA-PSS is deterministic, so the same key is derived every time.
+
+use anyhow::{anyhow, Context, Result};
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use rand::rngs::OsRng;
+use ring::{aead, hkdf};
+use rsa::{BigUint, PaddingScheme, RsaPrivateKey};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// ArFS drive and file key (AES-256).
+pub type EntityKey = [u8; 32];
+
+/// Derives the key of a private drive.
+pub fn drive_key(wallet: &str, drive_id: &str, password: &str) -> Result<EntityKey> {
+    let key = private_key(wallet)?;
+
+    let mut message = b"drive".to_vec();
+    message.extend_from_slice(&uuid_bytes(drive_id)?);
+
+    // no salt, the signature must be the same every time the key is derived
+    let signature = sign_pss(&key, &message)?;
+
+    hkdf_sha256(&signature, password.as_bytes())
+}
+
+/// Derives the key of a file of a private drive.
+pub fn file_key(drive_key: &EntityKey, file_id: &str) -> Result<EntityKey> {
+    hkdf_sha256(drive_key, &uuid_bytes(file_id)?)
+}
+
+/// Decrypts entity data (ciphertext followed by the authentication tag); returns `None` if the
+/// key is wrong or the data was tampered with.
+pub fn decrypt(key: &EntityKey, cipher_iv: &str, data: &[u8]) -> Option<Vec<u8>> {
+    let iv = BASE64.decode(cipher_iv.as_bytes()).ok()?;
+    let nonce = aead::Nonce::try_assume_unique_for_key(&iv).ok()?;
+    let key = aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_256_GCM, key).ok()?);
+
+    let mut data = data.to_vec();
+    let length = key
+        .open_in_place(nonce, aead::Aad::empty(), &mut data)
+        .ok()?
+        .len();
+    data.truncate(length);
+
+    Some(data)
+}
+
+fn hkdf_sha256(ikm: &[u8], info: &[u8]) -> Result<EntityKey> {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, &[]).extract(ikm);
+    let info = [info];
+    let mut key = EntityKey::default();
+
+    prk.expand(&info, hkdf::HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut key))
+        .map_err(|_| anyhow!("Failed to derive the ArFS key"))?;
+
+    Ok(key)
+}
+
+/// Returns the RSA key of a JWK wallet.
+fn private_key(wallet: &str) -> Result<RsaPrivateKey> {
+    let jwk: Value = serde_json::from_str(wallet).context("ArDrive wallet is not valid JSON")?;
+
+    let component = |name: &str| -> Result<BigUint> {
+        let value = jwk[name]
+            .as_str()
+            .ok_or_else(|| anyhow!("ArDrive wallet is missing the '{name}' field"))?;
+        let bytes = BASE64URL_NOPAD
+            .decode(value.trim_end_matches('=').as_bytes())
+            .map_err(|e| anyhow!("Invalid wallet '{name}' field: {e}"))?;
+        Ok(BigUint::from_bytes_be(&bytes))
+    };
+
+    let key = RsaPrivateKey::from_components(
+        component("n")?,
+        component("e")?,
+        component("d")?,
+        vec![component("p")?, component("q")?],
+    );
+
+    key.validate()
+        .map_err(|e| anyhow!("Invalid ArDrive wallet key: {e}"))?;
+
+    Ok(key)
+}
+
+/// Signs the SHA-256 digest of `message` with RSASSA-PSS (SHA-256 and MGF1-SHA-256) without
+/// salt; the private key operation is blinded.
+fn sign_pss(key: &RsaPrivateKey, message: &[u8]) -> Result<Vec<u8>> {
+    let padding = PaddingScheme::new_pss_with_salt::<sha2_09::Sha256, _>(OsRng, 0);
+
+    key.sign_blinded(&mut OsRng, padding, &Sha256::digest(message))
+        .map_err(|e| anyhow!("Failed to sign with the ArDrive wallet: {e}"))
+}
+
+/// Returns the 16 bytes of an entity ID (UUID).
+fn uuid_bytes(id: &str) -> Result<Vec<u8>> {
+    let bytes = hex::decode(id.replace('-', "")).map_err(|_| anyhow!("Invalid ArFS ID {id}"))?;
+
+    if bytes.len() != 16 {
+        return Err(anyhow!("Invalid ArFS ID {id}"));
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const DRIVE_ID: &str = "6939d2b5-3e1f-4d52-9ab4-5d5c3a1f3b64";
+
+    /// 1024-bit test key.
+    fn test_wallet() -> String {
+        json!({
+            "kty": "RSA",
+            "n": "znBlRPYi1UKiNXMDC0tPGuGRI_acqNbJu1ih33i5q4CjxWtHBgYX0SZrLkz26nsDWawAB4OxS2vG_\
+                wjYx10YBNREdheiW7zqtu_jwACkbsyZdVtQ3Vpk-mlHozFo9QsxvVN-cELxvo0y-xfqd7V6TWTYWSD4b5_\
+                bAp-3V5SmUeE",
+            "e": "AQAB",
+            "d": "GOdSMCmJNjTdoJkJSvmAx3tiqPX9HBkdKO6eSHkl3Nyd5Rj17nIZuW9rCqDaQJsqYkzvi_iFENYloWq\
+                NzC8c0HDN9U6TfKYVSMbhebrpEZD2BoYLeWEqQJ0d4E3bqdyX9hfCGaUjCb2-Tj4enfCfXNlGOLi4YykDi\
+                toFicsBegE",
+            "p": "8ET7qhZgPyX-NaGLfdajubXtl7mUGGd2XOzLWiEE2qP9StOi6qwBU8e75D03qQ4YCmJbEtEv17PJBCistEZ3EQ",
+            "q": "2_Rmh2zsF5Ptwu2sHKZnNiHIQ35vxRQPTgGvogxumD3grrU_enGu85rpUKltZ-NBkpLw0EVNVhYh61SEpl9N0Q",
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn signs_with_pss_without_salt() {
+        // openssl dgst -sha256 -sign key.pem -sigopt rsa_padding_mode:pss \
+        //     -sigopt rsa_pss_saltlen:0
+        let key = private_key(&test_wallet()).unwrap();
+        let mut message = b"drive".to_vec();
+        message.extend_from_slice(&uuid_bytes(DRIVE_ID).unwrap());
+
+        assert_eq!(
+            hex::encode(sign_pss(&key, &message).unwrap()),
+            "61ee55f78dc1217f876295439de6c4f788003fc2397f5388839d460576fdbdaaccf403edf844529e9919\
+            e31b06d51285dfcc696fe1933303be292cb88bd1ed80176042d2d9856fb6651708676380469e5d313e66\
+            4630594d596238cc94960012064eccedb7177027c2800f8eef19e0b9e86be58d07f0cbb1626fa9f83f43\
+            a4bf"
+        );
+    }
+
+    #[test]
+    fn derives_the_same_drive_key_for_the_same_password() {
+        let wallet = test_wallet();
+
+        let key = drive_key(&wallet, DRIVE_ID, "secret").unwrap();
+        assert_eq!(key, drive_key(&wallet, DRIVE_ID, "secret").unwrap());
+        assert_ne!(key, drive_key(&wallet, DRIVE_ID, "wrong").unwrap());
+
+        assert!(drive_key(&wallet, "not-a-uuid", "secret").is_err());
+        assert!(drive_key("{}", DRIVE_ID, "secret").is_err());
+    }
+
+    #[test]
+    fn derives_keys_with_hkdf_sha256() {
+        // RFC 5869 test case 3 (no salt, no info)
+        let key = hkdf_sha256(&[0x0b; 22], &[]).unwrap();
+        assert_eq!(
+            hex::encode(key),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d"
+        );
+    }
+
+    #[test]
+    fn decrypts_with_the_right_key_only() {
+        let key = file_key(&[7; 32], DRIVE_ID).unwrap();
+        let iv = [1u8; 12];
+
+        let sealing_key =
+            aead::LessSafeKey::new(aead::UnboundKey::new(&aead::AES_256_GCM, &key).unwrap());
+        let mut data = br#"{"name":"0.png"}"#.to_vec();
+        sealing_key
+            .seal_in_place_append_tag(
+                aead::Nonce::assume_unique_for_key(iv),
+                aead::Aad::empty(),
+                &mut data,
+            )
+            .unwrap();
+
+        let cipher_iv = BASE64.encode(&iv);
+        assert_eq!(
+            decrypt(&key, &cipher_iv, &data).unwrap(),
+            br#"{"name":"0.png"}"#
+        );
+        assert!(decrypt(&[0; 32], &cipher_iv, &data).is_none());
+        assert!(decrypt(&key, "invalid", &data).is_none());
+    }
+}
@@ -0,0 +1,222 @@
+//! Removal of ArFS files and folders.
+//!
+//! Arweave data is permanent, so an entity cannot be deleted: the ArDrive Node CLI creates a
+//! new revision of its metadata marking it hidden, and ArFS clients stop listing it.
+
+use std::{ffi::OsStr, fs, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::info;
+
+use super::{
+    arfs::{self, EntityInfo},
+    created_entity, entity_field, listing, resolve_ardrive_wallet_content, resolve_drive_password,
+    run_ardrive, write_temp_wallet, ArdriveOptions,
+};
+use crate::output::{confirm, Confirmation};
+
+pub struct ArdriveDeleteArgs {
+    pub wallet: Option<PathBuf>,
+    /// IDs of the files and folders to hide.
+    pub ids: Vec<String>,
+    /// File with one ID per line (blank lines and `#` comments are ignored).
+    pub ids_file: Option<PathBuf>,
+    /// Password of a private drive, defaults to the ARDRIVE_DRIVE_PASSWORD env var.
+    pub drive_password: Option<String>,
+    /// Hide several entities without asking for confirmation.
+    pub yes: bool,
+    /// Only list the entities that would be hidden.
+    pub dry_run: bool,
+    pub options: ArdriveOptions,
+}
+
+/// Hides files and folders of the drives of the wallet, printing the new metadata transactions.
+/// Every entity is looked up before anything is sent, and hiding more than one entity needs
+/// `--yes` or a confirmation.
+pub async fn process_ardrive_delete(args: ArdriveDeleteArgs) -> Result<()> {
+    let ids = collect_ids(args.ids, args.ids_file)?;
+    info!("ArDrive: delete called: {} id(s)", ids.len());
+
+    let password = resolve_drive_password(args.drive_password);
+    let content = resolve_ardrive_wallet_content(&args.options, args.wallet)?;
+    let owner = arfs::wallet_address(&content)?;
+
+    let mut entities = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let entity = arfs::entity_info(&args.options, id)
+            .await?
+            .ok_or_else(|| anyhow!("No ArFS entity found for {}", id))?;
+        check_entity(&entity, &owner, password.is_some())?;
+        entities.push(entity);
+    }
+
+    report!("Entities to hide:");
+    for entity in &entities {
+        report!("  {}", describe(entity));
+    }
+
+    if args.dry_run {
+        report!(
+            "Dry run: {} file(s) and folder(s) would be hidden, nothing was sent",
+            entities.len()
+        );
+        return Ok(());
+    }
+
+    if entities.len() > 1
+        && !args.yes
+        && !confirm(
+            Confirmation::new(format!("Hide these {} entities?", entities.len())).default(false),
+        )?
+    {
+        return Err(anyhow!("Delete aborted."));
+    }
+
+    let wallet_file = write_temp_wallet(&content)?;
+
+    for entity in &entities {
+        let command = match entity.kind.as_str() {
+            "file" => ["hide-file", "--file-id"],
+            _ => ["hide-folder", "--folder-id"],
+        };
+
+        let mut arguments = vec![
+            OsStr::new(command[0]),
+            OsStr::new(command[1]),
+            OsStr::new(&entity.id),
+            OsStr::new("--wallet-file"),
+            wallet_file.path().as_os_str(),
+        ];
+        if let (true, Some(password)) = (entity.private, &password) {
+            arguments.extend([
+                OsStr::new("--private"),
+                OsStr::new("--unsafe-drive-password"),
+                OsStr::new(password),
+            ]);
+        }
+
+        let output = run_ardrive(&args.options, arguments)
+            .with_context(|| format!("Failed to hide {}", describe(entity)))?;
+        let metadata_tx = entity_field(created_entity(&output, &entity.kind)?, "metadataTxId")?;
+
+        if let Some(drive_id) = &entity.drive_id {
+            listing::invalidate(drive_id);
+        }
+
+        report!(
+            "✅ Hidden {} (metadata tx {})",
+            describe(entity),
+            metadata_tx
+        );
+    }
+
+    report!("Hid {} file(s) and folder(s)", entities.len());
+
+    Ok(())
+}
+
+/// Returns the IDs passed on the command line followed by the IDs of the file, without
+/// duplicates.
+fn collect_ids(ids: Vec<String>, ids_file: Option<PathBuf>) -> Result<Vec<String>> {
+    let mut all = ids;
+
+    if let Some(path) = ids_file {
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read ID file {}", path.display()))?;
+        all.extend(parse_ids(&content));
+    }
+
+    let mut unique: Vec<String> = Vec::with_capacity(all.len());
+    for id in all {
+        if !unique.contains(&id) {
+            unique.push(id);
+        }
+    }
+
+    if unique.is_empty() {
+        return Err(anyhow!(
+            "No IDs to delete: pass file or folder IDs or --ids-file"
+        ));
+    }
+
+    Ok(unique)
+}
+
+/// Reads one ID per line, ignoring blank lines and `#` comments.
+fn parse_ids(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|line| !line.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Checks that the entity is a file or folder of the wallet that can be hidden.
+fn check_entity(entity: &EntityInfo, owner: &str, has_password: bool) -> Result<()> {
+    if entity.kind != "file" && entity.kind != "folder" {
+        return Err(anyhow!(
+            "{} is a {}, only files and folders can be deleted",
+            entity.id,
+            entity.kind
+        ));
+    }
+
+    if entity.owner.as_deref() != Some(owner) {
+        return Err(anyhow!(
+            "{} {} is not owned by the wallet {}",
+            entity.kind,
+            entity.id,
+            owner
+        ));
+    }
+
+    if entity.private && !has_password {
+        return Err(anyhow!(
+            "{} {} is in a private drive: pass --drive-password or set ARDRIVE_DRIVE_PASSWORD",
+            entity.kind,
+            entity.id
+        ));
+    }
+
+    Ok(())
+}
+
+fn describe(entity: &EntityInfo) -> String {
+    match &entity.name {
+        Some(name) => format!("{} '{}' ({})", entity.kind, name, entity.id),
+        None => format!("{} {}", entity.kind, entity.id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ids() {
+        let content = "\
+# images to remove
+6939d2b5-1ef2-4f2b-9b5c-6c2a4b4c9f10
+
+  7a3c1f20-0b6e-4c8d-8f0e-2d1b5a9e4c31  # old metadata
+6939d2b5-1ef2-4f2b-9b5c-6c2a4b4c9f10
+";
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ids.txt");
+        fs::write(&path, content).unwrap();
+
+        assert_eq!(
+            collect_ids(
+                vec!["7a3c1f20-0b6e-4c8d-8f0e-2d1b5a9e4c31".to_string()],
+                Some(path)
+            )
+            .unwrap(),
+            vec![
+                "7a3c1f20-0b6e-4c8d-8f0e-2d1b5a9e4c31",
+                "6939d2b5-1ef2-4f2b-9b5c-6c2a4b4c9f10"
+            ]
+        );
+        assert!(collect_ids(Vec::new(), None).is_err());
+    }
+}
@@ -0,0 +1,123 @@
+use reqwest::StatusCode;
+use thiserror::Error;
+
+use super::arfs;
+
+/// Exit code of an ardrive command without a configured wallet.
+pub const EXIT_WALLET_NOT_CONFIGURED: i32 = 10;
+
+/// Exit code of an ardrive command with an invalid or unknown drive ID.
+pub const EXIT_INVALID_DRIVE_ID: i32 = 11;
+
+/// Exit code of an ardrive command that failed on a gateway error or could not reach it.
+pub const EXIT_GATEWAY_ERROR: i32 = 12;
+
+/// Exit code of an ardrive command that could not parse the ArDrive CLI output.
+pub const EXIT_PARSE_ERROR: i32 = 13;
+
+/// Exit code of an ardrive command whose ArDrive CLI command failed.
+pub const EXIT_SUBPROCESS_FAILED: i32 = 14;
+
+/// Errors of the ardrive commands, mapped to distinct exit codes so that scripts can tell
+/// them apart. They are returned inside the `anyhow` errors of the `process_ardrive_*`
+/// functions (use [`ardrive_exit_code`] to find them).
+#[derive(Debug, Error)]
+pub enum ArDriveError {
+    #[error("No ardrive wallet provided: pass -w/--wallet, set ARDRIVE_WALLET env var, or run 'sugar ardrive set-wallet <file>' to store one.")]
+    WalletNotConfigured,
+
+    #[error("Wallet profile '{0}' not found: run 'sugar ardrive set-wallet <file> --profile {0}' to store it.")]
+    WalletProfileNotFound(String),
+
+    #[error("Invalid drive ID '{drive_id}': {reason}")]
+    InvalidDriveId { drive_id: String, reason: String },
+
+    /// A gateway request returned an error status, or the gateway could not be reached
+    /// (`status` is `None`).
+    #[error("{message}")]
+    GatewayError {
+        status: Option<StatusCode>,
+        message: String,
+    },
+
+    #[error("{message}\n\nNote: Make sure ardrive-cli is installed with `pnpm add ardrive-cli`")]
+    ParseError { message: String },
+
+    /// An ArDrive CLI command exited with an error (`code` is `None` if it could not be run or
+    /// was killed).
+    #[error("{message}")]
+    SubprocessFailed { code: Option<i32>, message: String },
+}
+
+impl ArDriveError {
+    /// Error of a request that returned an unsuccessful HTTP status.
+    pub fn http_status(what: impl Into<String>, status: StatusCode) -> Self {
+        ArDriveError::GatewayError {
+            status: Some(status),
+            message: format!("{} returned {}", what.into(), status),
+        }
+    }
+
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ArDriveError::WalletNotConfigured | ArDriveError::WalletProfileNotFound(_) => {
+                EXIT_WALLET_NOT_CONFIGURED
+            }
+            ArDriveError::InvalidDriveId { .. } => EXIT_INVALID_DRIVE_ID,
+            ArDriveError::GatewayError { .. } => EXIT_GATEWAY_ERROR,
+            ArDriveError::ParseError { .. } => EXIT_PARSE_ERROR,
+            ArDriveError::SubprocessFailed { .. } => EXIT_SUBPROCESS_FAILED,
+        }
+    }
+}
+
+/// Checks that a drive ID is an ArFS entity ID (a UUID), before any request is made.
+pub fn check_drive_id(drive_id: &str) -> Result<(), ArDriveError> {
+    if arfs::is_entity_id(drive_id) {
+        Ok(())
+    } else {
+        Err(ArDriveError::InvalidDriveId {
+            drive_id: drive_id.to_string(),
+            reason: "expected a UUID such as 6939d2b5-1ef2-4f2b-9b5c-6c2a4b4c9f10".to_string(),
+        })
+    }
+}
+
+/// Returns the exit code of the ardrive error in the error chain, if there is one.
+pub fn ardrive_exit_code(err: &anyhow::Error) -> Option<i32> {
+    err.downcast_ref::<ArDriveError>()
+        .or_else(|| {
+            err.chain()
+                .find_map(|cause| cause.downcast_ref::<ArDriveError>())
+        })
+        .map(ArDriveError::exit_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+
+    #[test]
+    fn test_ardrive_exit_code() {
+        let err: anyhow::Error = ArDriveError::WalletNotConfigured.into();
+        assert_eq!(ardrive_exit_code(&err), Some(EXIT_WALLET_NOT_CONFIGURED));
+
+        let err = Err::<(), _>(ArDriveError::http_status(
+            "gateway",
+            StatusCode::BAD_GATEWAY,
+        ))
+        .context("Failed to list the drive")
+        .unwrap_err();
+        assert_eq!(ardrive_exit_code(&err), Some(EXIT_GATEWAY_ERROR));
+
+        assert_eq!(
+            check_drive_id("not-a-drive").unwrap_err().exit_code(),
+            EXIT_INVALID_DRIVE_ID
+        );
+        assert!(check_drive_id("6939d2b5-1ef2-4f2b-9b5c-6c2a4b4c9f10").is_ok());
+
+        assert_eq!(ardrive_exit_code(&anyhow::anyhow!("other error")), None);
+    }
+}
@@ -0,0 +1,242 @@
+//! Journal of the files uploaded by `ardrive upload`, stored next to the uploaded path in
+//! `.ardrive-upload-state.json`.
+//!
+//! Every completed upload is recorded with the hash of the file, so that an interrupted upload
+//! can be restarted without uploading the same files again, even when the gateway does not list
+//! the new files yet. A file is only skipped if it did not change and its data transaction is
+//! confirmed.
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+/// Name of the journal file, written in the directory containing the uploaded path.
+pub const JOURNAL_FILE_NAME: &str = ".ardrive-upload-state.json";
+
+// version of the journal format
+const JOURNAL_VERSION: u32 = 1;
+
+/// Upload journal, e.g.
+///
+/// ```json
+/// {
+///   "version": 1,
+///   "files": {
+///     "assets/0.png": {
+///       "data_tx": "Hk2...",
+///       "metadata_tx": "q9F...",
+///       "folder_id": "6939d2b5-...",
+///       "bytes": 48213,
+///       "sha256": "9f86d0..."
+///     }
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UploadJournal {
+    /// Version of the journal format.
+    pub version: u32,
+    /// Uploaded files, keyed by their path relative to the directory of the journal (with `/`
+    /// separators).
+    pub files: BTreeMap<String, JournalEntry>,
+}
+
+/// Uploaded file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Transaction of the file data.
+    pub data_tx: String,
+    /// Transaction of the ArFS metadata of the file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata_tx: Option<String>,
+    /// Folder the file was uploaded to.
+    pub folder_id: String,
+    /// Size of the file in bytes.
+    pub bytes: u64,
+    /// SHA-256 (hex) of the file content.
+    pub sha256: String,
+}
+
+impl Default for UploadJournal {
+    fn default() -> Self {
+        UploadJournal {
+            version: JOURNAL_VERSION,
+            files: BTreeMap::new(),
+        }
+    }
+}
+
+impl UploadJournal {
+    /// Reads the journal, returning an empty journal if the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(UploadJournal::default());
+        }
+
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read upload journal {}", path.display()))?;
+        let journal: UploadJournal = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid upload journal {}", path.display()))?;
+
+        if journal.version != JOURNAL_VERSION {
+            return Err(anyhow!(
+                "Unsupported upload journal version {} in {} (expected {}), use --force-reupload to replace it",
+                journal.version,
+                path.display(),
+                JOURNAL_VERSION
+            ));
+        }
+
+        info!(
+            "Loaded upload journal {} ({} files)",
+            path.display(),
+            journal.files.len()
+        );
+
+        Ok(journal)
+    }
+
+    /// Writes the journal, replacing the previous file only once the new one is complete.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let tmp = path.with_extension("json.tmp");
+
+        fs::write(&tmp, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write upload journal {}", tmp.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to write upload journal {}", path.display()))
+    }
+
+    /// Returns the entry of the file if it was uploaded to the folder with the same content.
+    pub fn uploaded(
+        &self,
+        key: &str,
+        folder_id: &str,
+        bytes: u64,
+        sha256: &str,
+    ) -> Option<&JournalEntry> {
+        self.files.get(key).filter(|entry| {
+            entry.folder_id == folder_id && entry.bytes == bytes && entry.sha256 == sha256
+        })
+    }
+}
+
+/// Returns the default journal of an uploaded path: in the directory containing it.
+pub fn default_journal_path(path: &Path) -> PathBuf {
+    base_dir(path).join(JOURNAL_FILE_NAME)
+}
+
+/// Returns the key of a file in the journal of the uploaded path: its path relative to the
+/// directory containing the uploaded path.
+pub fn journal_key(uploaded_path: &Path, file: &Path) -> String {
+    let base = base_dir(uploaded_path);
+    let relative = file.strip_prefix(&base).unwrap_or(file);
+
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn base_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(sha256: &str) -> JournalEntry {
+        JournalEntry {
+            data_tx: "data-tx".to_string(),
+            metadata_tx: Some("metadata-tx".to_string()),
+            folder_id: "folder".to_string(),
+            bytes: 10,
+            sha256: sha256.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_journal_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE_NAME);
+
+        assert_eq!(
+            UploadJournal::load(&path).unwrap(),
+            UploadJournal::default()
+        );
+
+        let mut journal = UploadJournal::default();
+        journal
+            .files
+            .insert("assets/0.png".to_string(), entry("abc"));
+        journal.files.insert(
+            "assets/0.json".to_string(),
+            JournalEntry {
+                metadata_tx: None,
+                ..entry("def")
+            },
+        );
+        journal.save(&path).unwrap();
+
+        let loaded = UploadJournal::load(&path).unwrap();
+        assert_eq!(loaded, journal);
+        assert!(!path.with_extension("json.tmp").exists());
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.contains("\"data_tx\": \"data-tx\""));
+        assert!(!content.contains("\"metadata_tx\": null"));
+
+        fs::write(&path, r#"{"version": 2, "files": {}}"#).unwrap();
+        assert!(UploadJournal::load(&path).is_err());
+    }
+
+    #[test]
+    fn test_journal_matches_unchanged_files_only() {
+        let mut journal = UploadJournal::default();
+        journal
+            .files
+            .insert("assets/0.png".to_string(), entry("abc"));
+
+        assert!(journal
+            .uploaded("assets/0.png", "folder", 10, "abc")
+            .is_some());
+        assert!(journal
+            .uploaded("assets/0.png", "folder", 10, "abd")
+            .is_none());
+        assert!(journal
+            .uploaded("assets/0.png", "folder", 11, "abc")
+            .is_none());
+        assert!(journal
+            .uploaded("assets/0.png", "other", 10, "abc")
+            .is_none());
+        assert!(journal
+            .uploaded("assets/1.png", "folder", 10, "abc")
+            .is_none());
+
+        assert_eq!(
+            journal_key(Path::new("assets"), Path::new("assets/0.png")),
+            "assets/0.png"
+        );
+        assert_eq!(
+            journal_key(
+                Path::new("/tmp/drop/assets"),
+                Path::new("/tmp/drop/assets/0.png")
+            ),
+            "assets/0.png"
+        );
+        assert_eq!(
+            default_journal_path(Path::new("assets")),
+            Path::new(".").join(JOURNAL_FILE_NAME)
+        );
+    }
+}
@@ -0,0 +1,207 @@
+//! On-disk cache of the file listings of public drives, stored in
+//! `~/.config/sugar-cli/ardrive-listings/<drive id>.json`.
+//!
+//! Listing a large drive takes minutes, so the commands reading the files of a drive (list,
+//! generate-cache, download and sync) reuse a listing younger than the TTL. `--refresh` bypasses
+//! the cache, and uploads remove the listing of the drive. Listings of private drives are never
+//! written to disk.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use super::{ArDriveFile, ArdriveOptions};
+
+/// Default time (in minutes) a drive listing is reused for.
+pub const DEFAULT_LISTING_TTL_MINUTES: u64 = 15;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct DriveListing {
+    drive_id: String,
+    /// Unix time (in seconds) of the listing.
+    timestamp: u64,
+    files: Vec<ArDriveFile>,
+}
+
+/// Returns the cached files of the drive, unless the listing is missing, stale or a refresh
+/// was requested.
+pub fn load(options: &ArdriveOptions, drive_id: &str) -> Option<Vec<ArDriveFile>> {
+    if options.refresh {
+        return None;
+    }
+
+    let path = listing_path(drive_id)?;
+    let files = read_listing(&path, drive_id, now(), ttl_secs(options))?;
+
+    // listings written before the file paths were resolved
+    if files
+        .iter()
+        .any(|file| file.entity_type.as_deref() != Some("folder") && file.path.is_none())
+    {
+        info!(
+            "Ignoring listing cache {} without file paths",
+            path.display()
+        );
+        return None;
+    }
+
+    info!(
+        "Using cached listing of drive {} ({} files) from {}",
+        drive_id,
+        files.len(),
+        path.display()
+    );
+
+    Some(files)
+}
+
+/// Caches the files of the drive; failures are logged, the cache being an optimization.
+pub fn store(options: &ArdriveOptions, drive_id: &str, files: &[ArDriveFile]) {
+    if ttl_secs(options) == 0 {
+        return;
+    }
+
+    let result = listing_path(drive_id)
+        .ok_or_else(|| anyhow!("No listing cache path for drive {}", drive_id))
+        .and_then(|path| write_listing(&path, drive_id, now(), files));
+
+    if let Err(err) = result {
+        info!(
+            "Failed to cache the listing of drive {}: {:#}",
+            drive_id, err
+        );
+    }
+}
+
+/// Removes the cached listing of the drive (e.g. after uploading files to it).
+pub fn invalidate(drive_id: &str) {
+    if let Some(path) = listing_path(drive_id) {
+        if path.exists() {
+            if let Err(err) = fs::remove_file(&path) {
+                info!("Failed to remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Time (in seconds) a listing is reused for.
+fn ttl_secs(options: &ArdriveOptions) -> u64 {
+    options.listing_ttl_minutes.saturating_mul(60)
+}
+
+fn listing_path(drive_id: &str) -> Option<PathBuf> {
+    // drive IDs are UUIDs, anything else must not end up in a path
+    if drive_id.is_empty()
+        || !drive_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-')
+    {
+        return None;
+    }
+
+    dirs::home_dir().map(|home| {
+        home.join(".config")
+            .join("sugar-cli")
+            .join("ardrive-listings")
+            .join(format!("{drive_id}.json"))
+    })
+}
+
+fn read_listing(path: &Path, drive_id: &str, now: u64, ttl: u64) -> Option<Vec<ArDriveFile>> {
+    let content = fs::read_to_string(path).ok()?;
+
+    let listing = match serde_json::from_str::<DriveListing>(&content) {
+        Ok(listing) => listing,
+        Err(err) => {
+            info!("Ignoring invalid listing cache {}: {}", path.display(), err);
+            return None;
+        }
+    };
+
+    if listing.drive_id != drive_id {
+        info!(
+            "Ignoring listing cache {}: it is for drive {}",
+            path.display(),
+            listing.drive_id
+        );
+        return None;
+    }
+
+    if now.saturating_sub(listing.timestamp) >= ttl {
+        info!("Ignoring stale listing cache {}", path.display());
+        return None;
+    }
+
+    Some(listing.files)
+}
+
+fn write_listing(path: &Path, drive_id: &str, now: u64, files: &[ArDriveFile]) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+    }
+
+    let listing = DriveListing {
+        drive_id: drive_id.to_string(),
+        timestamp: now,
+        files: files.to_vec(),
+    };
+
+    fs::write(path, serde_json::to_string(&listing)?)
+        .with_context(|| format!("Failed to write listing cache {}", path.display()))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const DRIVE_ID: &str = "6939d2b5-3e1f-4d52-9ab4-5d5c3a1f3b64";
+
+    #[test]
+    fn reuses_fresh_listings_of_the_same_drive_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("listings").join(format!("{DRIVE_ID}.json"));
+        let files: Vec<ArDriveFile> =
+            serde_json::from_value(json!([{ "name": "0.png", "size": 10 }])).unwrap();
+
+        assert!(read_listing(&path, DRIVE_ID, 1_000, 900).is_none());
+
+        write_listing(&path, DRIVE_ID, 1_000, &files).unwrap();
+
+        let cached = read_listing(&path, DRIVE_ID, 1_899, 900).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].name.as_deref(), Some("0.png"));
+
+        // stale
+        assert!(read_listing(&path, DRIVE_ID, 1_900, 900).is_none());
+        // mismatched drive
+        assert!(read_listing(&path, "other-drive", 1_000, 900).is_none());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(read_listing(&path, DRIVE_ID, 1_000, 900).is_none());
+    }
+
+    #[test]
+    fn rejects_drive_ids_that_are_not_file_names() {
+        assert!(listing_path("../wallet").is_none());
+        assert!(listing_path("").is_none());
+        assert!(listing_path(DRIVE_ID)
+            .map(|path| path.ends_with(format!("ardrive-listings/{DRIVE_ID}.json")))
+            .unwrap_or(true));
+    }
+}
@@ -1,3 +1,23 @@
+pub mod arfs;
+pub mod cost;
+pub mod create;
+pub mod crypto;
+pub mod delete;
+pub mod errors;
+pub mod journal;
+pub mod listing;
 pub mod process;
+pub mod profile;
+pub mod status;
+pub mod upload;
+pub mod verify;
 
+pub use cost::*;
+pub use create::*;
+pub use delete::*;
+pub use errors::*;
 pub use process::*;
+pub use profile::*;
+pub use status::*;
+pub use upload::*;
+pub use verify::*;
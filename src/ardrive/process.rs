@@ -1,15 +1,255 @@
 use std::{
-    fs,
+    collections::HashMap,
+    fmt, fs,
+    future::Future,
     io::Write,
     path::{Path, PathBuf},
     process::Command,
+    str::FromStr,
+    thread,
+    time::Duration,
 };
 
 use anyhow::{anyhow, Context, Result};
+use chrono::NaiveDateTime;
+use data_encoding::HEXLOWER;
+use indicatif::HumanBytes;
+use lazy_static::lazy_static;
+use rand::Rng;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tempfile::NamedTempFile;
+use thiserror::Error;
 use tracing::info;
 
+use super::{
+    arfs, check_drive_id, check_tx_statuses, count_statuses, default_wallet_path, listing,
+    profile_wallet_path, ArDriveError, TxStatus,
+};
+use crate::{
+    constants::WARNING_EMOJI,
+    output::{confirm, style, Confirmation},
+    utils::{progress_bar_with_style, spinner_with_style, ProgressBar, ProgressStyle},
+};
+
+/// Default number of retries of a failed ArDrive/Arweave request.
+pub const DEFAULT_MAX_RETRIES: usize = 5;
+
+/// Default delay (in milliseconds) before the first retry, doubled on each retry.
+pub const DEFAULT_RETRY_DELAY_MS: u64 = 500;
+
+// Maximum delay between two attempts.
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+/// Options shared by the ardrive commands, set by the flags of the `ardrive` command.
+#[derive(Clone, Debug)]
+pub struct ArdriveOptions {
+    /// Number of retries of a failed ArDrive/Arweave request.
+    pub max_retries: usize,
+    /// Delay (in milliseconds) before the first retry, doubled on each retry.
+    pub retry_delay_ms: u64,
+    /// Ignore the cached drive listings (they are still updated).
+    pub refresh: bool,
+    /// Time (in minutes) a drive listing is reused for, 0 disables the listing cache.
+    pub listing_ttl_minutes: u64,
+    /// Arweave gateway (without trailing slash), see [`arfs::resolve_gateway`].
+    pub gateway: String,
+    /// Hide the progress bars and the detailed output, the commands then only print their
+    /// summary line (e.g. when running from cron or CI).
+    pub quiet: bool,
+    /// Wallet profile used when no wallet file is given and `ARDRIVE_WALLET` is not set (and
+    /// written by `set-wallet`).
+    pub profile: Option<String>,
+}
+
+impl Default for ArdriveOptions {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay_ms: DEFAULT_RETRY_DELAY_MS,
+            refresh: false,
+            listing_ttl_minutes: listing::DEFAULT_LISTING_TTL_MINUTES,
+            gateway: arfs::DEFAULT_GATEWAY.to_string(),
+            quiet: false,
+            profile: None,
+        }
+    }
+}
+
+/// Prints a line of the detailed output, hidden in quiet mode.
+pub(super) fn print_detail(options: &ArdriveOptions, message: impl fmt::Display) {
+    if !options.quiet {
+        report!("{}", message);
+    }
+}
+
+/// Progress bar of `len` files with the ETA, hidden in quiet mode.
+pub(super) fn files_progress_bar(options: &ArdriveOptions, len: u64) -> ProgressBar {
+    if options.quiet {
+        return ProgressBar::hidden();
+    }
+
+    let pb = progress_bar_with_style(len);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("[{elapsed_precise}] {msg}{wide_bar} {pos}/{len} (ETA {eta})"),
+    );
+    pb
+}
+
+/// Spinner of an operation of unknown length, hidden in quiet mode.
+pub(super) fn progress_spinner(options: &ArdriveOptions, message: &str) -> ProgressBar {
+    if options.quiet {
+        return ProgressBar::hidden();
+    }
+
+    let pb = spinner_with_style();
+    pb.set_message(message.to_string());
+    pb
+}
+
+/// Error of an ArDrive CLI command that failed on a gateway error worth retrying.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct TransientError(#[from] anyhow::Error);
+
+/// Runs a network operation, retrying it (with exponential backoff and jitter) while it fails
+/// with a transient error: a timeout, a connection failure, a 429 or a 5xx status.
+pub(crate) async fn with_retry<T, F, Fut>(
+    options: &ArdriveOptions,
+    operation: &str,
+    mut f: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match f().await {
+            Ok(value) => return Ok(value),
+            Err(err) => tokio::time::sleep(next_retry(options, operation, attempt, err)?).await,
+        }
+        attempt += 1;
+    }
+}
+
+/// Blocking version of [`with_retry`], used for the ArDrive CLI commands.
+pub(crate) fn with_retry_blocking<T, F>(
+    options: &ArdriveOptions,
+    operation: &str,
+    mut f: F,
+) -> Result<T>
+where
+    F: FnMut() -> Result<T>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(err) => thread::sleep(next_retry(options, operation, attempt, err)?),
+        }
+        attempt += 1;
+    }
+}
+
+/// Returns the delay before retrying a failed attempt, or the final error when the error is
+/// not transient or there are no retries left.
+fn next_retry(
+    options: &ArdriveOptions,
+    operation: &str,
+    attempt: usize,
+    err: anyhow::Error,
+) -> Result<Duration> {
+    let transient = is_transient(&err);
+
+    if attempt > options.max_retries || !transient {
+        return Err(if transient {
+            // the gateway kept failing (or could not be reached)
+            ArDriveError::GatewayError {
+                status: gateway_status(&err),
+                message: format!("{} failed after {} attempts: {:#}", operation, attempt, err),
+            }
+            .into()
+        } else if attempt > 1 {
+            anyhow!("{} failed after {} attempts: {:#}", operation, attempt, err)
+        } else {
+            err
+        });
+    }
+
+    let delay = options
+        .retry_delay_ms
+        .saturating_mul(1 << (attempt - 1).min(16))
+        .min(MAX_RETRY_DELAY_MS);
+    let delay = delay + rand::thread_rng().gen_range(0..=delay / 2);
+
+    info!(
+        "{} failed (attempt {}), retrying in {}ms: {:#}",
+        operation, attempt, delay, err
+    );
+
+    Ok(Duration::from_millis(delay))
+}
+
+/// Returns `true` if the error is worth retrying.
+fn is_transient(err: &anyhow::Error) -> bool {
+    let transient_status =
+        |status: reqwest::StatusCode| status.as_u16() == 429 || status.is_server_error();
+
+    err.chain().any(|cause| {
+        if let Some(ArDriveError::GatewayError { status, .. }) =
+            cause.downcast_ref::<ArDriveError>()
+        {
+            status.map(transient_status).unwrap_or(true)
+        } else if let Some(err) = cause.downcast_ref::<reqwest::Error>() {
+            err.is_timeout()
+                || err.is_connect()
+                || err.is_request()
+                || err.is_body()
+                || err.status().map(transient_status).unwrap_or(false)
+        } else {
+            cause.is::<TransientError>()
+        }
+    })
+}
+
+/// Returns the HTTP status of the gateway error in the error chain.
+fn gateway_status(err: &anyhow::Error) -> Option<reqwest::StatusCode> {
+    err.chain().find_map(|cause| {
+        if let Some(ArDriveError::GatewayError { status, .. }) =
+            cause.downcast_ref::<ArDriveError>()
+        {
+            *status
+        } else {
+            cause
+                .downcast_ref::<reqwest::Error>()
+                .and_then(|err| err.status())
+        }
+    })
+}
+
+/// Marks the error of a failed ArDrive CLI command as transient when its output reports a
+/// gateway error (rate limit, unavailable gateway, timeout).
+pub(super) fn cli_error(output: &str, err: anyhow::Error) -> anyhow::Error {
+    lazy_static! {
+        static ref TRANSIENT_OUTPUT: Regex = Regex::new(
+            r"(?i)\b(429|502|503|504)\b|too many requests|bad gateway|service unavailable|timed? ?out|econnreset|etimedout|socket hang up"
+        )
+        .unwrap();
+    }
+
+    if TRANSIENT_OUTPUT.is_match(output) {
+        TransientError(err).into()
+    } else {
+        err
+    }
+}
+
 /// Find node executable in common locations or PATH
 #[allow(dead_code)]
 fn find_node() -> Result<PathBuf> {
@@ -40,7 +280,7 @@ fn find_node() -> Result<PathBuf> {
 }
 
 /// Walk upwards from cwd to find node_modules/.bin/ardrive
-fn find_local_ardrive() -> Option<PathBuf> {
+pub(super) fn find_local_ardrive() -> Option<PathBuf> {
     if let Ok(mut dir) = std::env::current_dir() {
         loop {
             let candidate = dir.join("node_modules/.bin/ardrive");
@@ -123,7 +363,6 @@ fn extract_drives_from_value(v: &Value) -> Option<Vec<Value>> {
 }
 
 /// Remove common ANSI escape sequences from output (very small scanner, no external deps).
-#[allow(dead_code)]
 fn strip_ansi_codes(s: &str) -> String {
     let mut out = String::with_capacity(s.len());
     let mut chars = s.chars().peekable();
@@ -153,52 +392,60 @@ fn strip_ansi_codes(s: &str) -> String {
     out
 }
 
-/// Try several heuristics to extract a JSON document from CLI output and parse it.
-#[allow(dead_code)]
-fn try_parse_json_flex(raw: &str) -> Result<Value, serde_json::Error> {
-    let cleaned = strip_ansi_codes(raw).trim().to_string();
-
-    // If the whole cleaned string parses, return it.
-    if let Ok(v) = serde_json::from_str::<Value>(&cleaned) {
-        return Ok(v);
-    }
-
-    // Try to find first json start and last json end and parse that slice.
-    if let Some(start) = cleaned.find('{').or_else(|| cleaned.find('[')) {
-        // find last occurrence of closing braces
-        if let Some(end_brace) = cleaned.rfind('}') {
-            if end_brace > start {
-                let candidate = &cleaned[start..=end_brace];
-                if let Ok(v) = serde_json::from_str::<Value>(candidate) {
-                    return Ok(v);
-                }
-            }
-        }
-        if let Some(end_bracket) = cleaned.rfind(']') {
-            if end_bracket > start {
-                let candidate = &cleaned[start..=end_bracket];
-                if let Ok(v) = serde_json::from_str::<Value>(candidate) {
-                    return Ok(v);
-                }
-            }
-        }
+/// Parses the JSON document of an ArDrive CLI output, skipping the ANSI codes, the BOM and the
+/// lines printed before the JSON (update banners, deprecation warnings) or after it.
+pub(super) fn try_parse_json_flex(raw: &str) -> Result<Value, serde_json::Error> {
+    let stripped = strip_ansi_codes(raw);
+    let cleaned = stripped.trim().trim_start_matches('\u{feff}').trim();
+
+    if let Ok(value) = serde_json::from_str::<Value>(cleaned) {
+        return Ok(value);
     }
 
-    // As a last resort, try parsing each non-empty line that looks like JSON
-    for line in cleaned.lines() {
-        let t = line.trim();
-        if t.is_empty() {
-            continue;
-        }
-        if t.starts_with('{') || t.starts_with('[') {
-            if let Ok(v) = serde_json::from_str::<Value>(t) {
-                return Ok(v);
+    // the document starts on a line beginning with `{` or `[`; banner lines can start with
+    // `[` too (e.g. "[WARN]"), so each candidate line is tried in turn
+    let mut offset = 0;
+    for line in cleaned.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            let start = offset + (line.len() - trimmed.len());
+            // parses the first document, ignoring what follows it
+            let mut documents =
+                serde_json::Deserializer::from_str(&cleaned[start..]).into_iter::<Value>();
+
+            if let Some(Ok(value)) = documents.next() {
+                return Ok(value);
             }
         }
+
+        offset += line.len();
     }
 
-    // give up and return the last parse error from attempting the whole cleaned string
-    serde_json::from_str::<Value>(&cleaned)
+    // returns the error of the whole output
+    serde_json::from_str::<Value>(cleaned)
+}
+
+// Number of characters of a CLI output shown in an error message.
+const OUTPUT_EXCERPT_CHARS: usize = 500;
+
+/// Returns the beginning of a CLI output for an error message.
+fn output_excerpt(output: &str) -> String {
+    let output = output.trim();
+    let length = output.chars().count();
+
+    if length <= OUTPUT_EXCERPT_CHARS {
+        output.to_string()
+    } else {
+        format!(
+            "{}... ({} more characters)",
+            output
+                .chars()
+                .take(OUTPUT_EXCERPT_CHARS)
+                .collect::<String>(),
+            length - OUTPUT_EXCERPT_CHARS
+        )
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -256,6 +503,71 @@ pub struct ArDriveFile {
     pub content_type: Option<String>,
     #[serde(rename = "dataContentType")]
     pub data_content_type: Option<String>,
+    /// ID of the file (or folder) entity.
+    #[serde(rename = "entityId", default)]
+    pub entity_id: Option<String>,
+    /// Path of the file relative to the root folder of the drive (e.g. `images/0.png`).
+    #[serde(default)]
+    pub path: Option<String>,
+    /// Confirmation status of the data transaction, when it was checked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<TxStatus>,
+}
+
+/// Paths of the folders of a drive, relative to its root folder.
+#[derive(Debug, Default)]
+pub struct FolderPaths(HashMap<String, String>);
+
+impl FolderPaths {
+    /// Resolves the paths of the folders of a drive listing by following their parent folders
+    /// up to the root folder (the folder without a parent). Files are ignored.
+    pub fn new(entities: &[ArDriveFile]) -> Self {
+        let folders: HashMap<&str, &ArDriveFile> = entities
+            .iter()
+            .filter(|entity| entity.entity_type.as_deref() == Some("folder"))
+            .filter_map(|folder| Some((folder.entity_id.as_deref()?, folder)))
+            .collect();
+
+        let mut paths = HashMap::new();
+
+        for (id, folder) in &folders {
+            let mut names = Vec::new();
+            let mut current = *folder;
+
+            // the depth bounds the walk if the parents form a cycle
+            while let Some(parent) = current.parent_folder_id.as_deref() {
+                names.push(current.name.as_deref().unwrap_or_default());
+                match folders.get(parent) {
+                    Some(parent) if names.len() <= folders.len() => current = parent,
+                    _ => break,
+                }
+            }
+
+            names.reverse();
+            paths.insert(id.to_string(), names.join("/"));
+        }
+
+        FolderPaths(paths)
+    }
+
+    /// Returns the path of a file or folder: the path of its parent folder followed by its
+    /// name, or `None` for the root folder.
+    pub fn path_of(&self, entity: &ArDriveFile) -> Option<String> {
+        let parent = entity.parent_folder_id.as_deref()?;
+        let name = entity.name.as_deref().unwrap_or_default();
+
+        Some(match self.0.get(parent) {
+            Some(folder) if !folder.is_empty() => format!("{folder}/{name}"),
+            _ => name.to_string(),
+        })
+    }
+
+    /// Sets the path of each file and folder.
+    pub fn set_paths(&self, entities: &mut [ArDriveFile]) {
+        for entity in entities {
+            entity.path = self.path_of(entity);
+        }
+    }
 }
 
 impl ArDriveDrive {
@@ -270,19 +582,178 @@ impl ArDriveDrive {
     }
 }
 
-// Placeholder implementations for ArDrive interactions.
-// Replace these with real SDK calls / HTTP requests as needed.
+/// Type of the ArFS entities of a drive listing.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EntityType {
+    #[default]
+    File,
+    Folder,
+}
 
-pub fn process_ardrive_upload(file: PathBuf, bucket: Option<String>) -> Result<()> {
-    info!(
-        "ArDrive: upload called: file={:?} bucket={:?}",
-        file, bucket
-    );
-    println!(
-        "(ardrive) Uploading {:?} to {:?} (placeholder)",
-        file, bucket
-    );
-    Ok(())
+impl FromStr for EntityType {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "file" => Ok(EntityType::File),
+            "folder" => Ok(EntityType::Folder),
+            _ => Err(anyhow!(
+                "Invalid entity type '{}', expected 'file' or 'folder'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for EntityType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EntityType::File => write!(f, "file"),
+            EntityType::Folder => write!(f, "folder"),
+        }
+    }
+}
+
+/// Filters and page of a drive file listing.
+#[derive(Debug, Clone, Default)]
+pub struct FileQuery {
+    /// Type of the listed entities (files by default, folders have no data).
+    pub entity_type: EntityType,
+    /// Only list the files of this folder.
+    pub parent_folder: Option<String>,
+    /// Only list the files under one of these folder paths (e.g. `images`), relative to the root
+    /// folder of the drive.
+    pub folder_paths: Vec<String>,
+    /// Only list the files with this extension (e.g. `json`), ignoring the case.
+    pub extension: Option<String>,
+    /// Only list the files with this data content type (e.g. `image/png` or `image/*`).
+    pub content_type: Option<String>,
+    /// Number of matching files to skip.
+    pub offset: usize,
+    /// Maximum number of files to list.
+    pub limit: Option<usize>,
+}
+
+impl FileQuery {
+    /// Returns `true` if the file passes the entity type, folder, folder path, extension and
+    /// content type filters.
+    pub fn matches(&self, file: &ArDriveFile) -> bool {
+        // entries without an entity type are files
+        let is_folder = file.entity_type.as_deref() == Some("folder");
+        if is_folder != (self.entity_type == EntityType::Folder) {
+            return false;
+        }
+
+        if let Some(folder) = &self.parent_folder {
+            if file.parent_folder_id.as_ref() != Some(folder) {
+                return false;
+            }
+        }
+
+        if !self.folder_paths.is_empty() {
+            let path = file.path.as_deref().unwrap_or_default();
+            if !self
+                .folder_paths
+                .iter()
+                .any(|folder| path.starts_with(&format!("{}/", folder.trim_matches('/'))))
+            {
+                return false;
+            }
+        }
+
+        if let Some(ext) = &self.extension {
+            let suffix = format!(".{}", ext.trim_start_matches('.').to_lowercase());
+            match &file.name {
+                Some(name) if name.to_lowercase().ends_with(&suffix) => (),
+                _ => return false,
+            }
+        }
+
+        match &self.content_type {
+            Some(content_type) => file
+                .data_content_type
+                .as_deref()
+                .map(|actual| content_type_matches(content_type, actual))
+                .unwrap_or(false),
+            None => true,
+        }
+    }
+
+    /// Returns `true` once `matched` files are enough to fill the page.
+    pub fn is_filled(&self, matched: usize) -> bool {
+        matches!(self.limit, Some(limit) if matched >= self.offset + limit)
+    }
+
+    /// Applies the filters and the page to a list of files.
+    pub fn apply(&self, files: Vec<ArDriveFile>) -> Vec<ArDriveFile> {
+        files
+            .into_iter()
+            .filter(|f| self.matches(f))
+            .skip(self.offset)
+            .take(self.limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+/// Returns `true` if the content type matches the pattern, which can end with a `*` wildcard
+/// subtype (e.g. `image/*`).
+fn content_type_matches(pattern: &str, content_type: &str) -> bool {
+    // ignores parameters such as "; charset=utf-8"
+    let content_type = content_type.split(';').next().unwrap_or_default().trim();
+
+    match pattern.strip_suffix("/*") {
+        Some(kind) => content_type
+            .split('/')
+            .next()
+            .map(|actual| actual.eq_ignore_ascii_case(kind))
+            .unwrap_or(false),
+        None => content_type.eq_ignore_ascii_case(pattern.trim()),
+    }
+}
+
+/// Output format of the ardrive list commands.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Human readable output.
+    #[default]
+    Table,
+    /// A single JSON document, suitable for scripts.
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(anyhow!(
+                "Invalid output format '{}', expected 'table' or 'json'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for OutputFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OutputFormat::Table => write!(f, "table"),
+            OutputFormat::Json => write!(f, "json"),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// Prints a status message: on stdout for the table output, on stderr for the JSON
+    /// output so that stdout only has the JSON document.
+    pub(super) fn status(&self, message: impl fmt::Display) {
+        match self {
+            OutputFormat::Table => report!("{}", message),
+            OutputFormat::Json => report_error!("{}", message),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -314,199 +785,1107 @@ mod tests {
         let res = extract_drives_from_value(&v).expect("should extract from map");
         assert_eq!(res.len(), 2);
     }
-}
 
-pub fn process_ardrive_list(bucket: Option<String>) -> Result<()> {
-    info!("ArDrive: list called: bucket={:?}", bucket);
-    println!("(ardrive) Listing contents of {:?} (placeholder)", bucket);
-    Ok(())
-}
+    #[test]
+    fn test_parse_json_with_banners() {
+        // update notice printed by ardrive-cli before the listing
+        let output = "\n\
+            ╭──────────────────────────────────────────╮\n\
+            │  Update available 2.0.4 → 2.1.0          │\n\
+            │  Run npm i -g ardrive-cli to update      │\n\
+            ╰──────────────────────────────────────────╯\n\
+            [\n  {\"driveId\": \"a\", \"name\": \"A\"}\n]\n\n";
+        let v = try_parse_json_flex(output).unwrap();
+        assert_eq!(extract_drives_from_value(&v).unwrap().len(), 1);
+
+        // warning starting with a bracket, and a trailing message
+        let output =
+            "[WARN] Node.js 16 is deprecated\n{\"drives\": [{\"driveId\": \"x\"}]}\nDone.\n";
+        let v = try_parse_json_flex(output).unwrap();
+        assert_eq!(v["drives"][0]["driveId"], "x");
+
+        // BOM, ANSI colors and CRLF line endings
+        let output =
+            "\u{feff}\u{1b}[33mWarning: low balance\u{1b}[0m\r\n[{\"name\": \"0.png\"}]\r\n";
+        let v = try_parse_json_flex(output).unwrap();
+        assert_eq!(v[0]["name"], "0.png");
+
+        assert!(try_parse_json_flex("Error: wallet not found\n").is_err());
+    }
 
-pub fn process_ardrive_info(id: String) -> Result<()> {
-    info!("ArDrive: info called: id={}", id);
-    println!("(ardrive) Info for id {} (placeholder)", id);
-    Ok(())
-}
+    #[test]
+    fn test_output_excerpt() {
+        assert_eq!(output_excerpt("  short output\n"), "short output");
 
-pub fn process_ardrive_delete(id: String) -> Result<()> {
-    info!("ArDrive: delete called: id={}", id);
-    println!("(ardrive) Delete id {} (placeholder)", id);
-    Ok(())
-}
+        let excerpt = output_excerpt(&"é".repeat(OUTPUT_EXCERPT_CHARS + 20));
+        assert!(excerpt.starts_with(&"é".repeat(OUTPUT_EXCERPT_CHARS)));
+        assert!(excerpt.ends_with("... (20 more characters)"));
+    }
 
-/// Store the provided ardrive wallet file contents into the user's config
-/// so other CLI calls can read it. We copy the file contents into
-/// ~/.config/sugar-cli/ardrive_wallet.json (creates dirs if needed).
-pub fn process_ardrive_set_wallet(wallet_file: std::path::PathBuf) -> Result<()> {
-    info!("ArDrive: set wallet called: {:?}", wallet_file);
-    // PathBuf is already imported at top-level
-    // use std::path::PathBuf;
+    fn drive_files(v: Value) -> Vec<ArDriveFile> {
+        serde_json::from_value(v).expect("should parse drive files")
+    }
 
-    let content = fs::read_to_string(&wallet_file).map_err(|e| {
-        anyhow::anyhow!(
-            "Failed to read wallet file {}: {}",
-            wallet_file.display(),
-            e
+    #[test]
+    fn test_append_drive_files_pairs_media_and_metadata() {
+        let mut cache = crate::cache::Cache::new();
+        let mut files = Vec::new();
+        for i in (0..10).rev() {
+            files.push(json!({"name": format!("{i}.json"), "dataTxId": format!("json{i}")}));
+            files.push(json!({"name": format!("{i}.png"), "dataTxId": format!("png{i}")}));
+        }
+        files.push(json!({"name": "collection.png", "dataTxId": "png-c"}));
+        files.push(json!({"name": "collection.json", "dataTxId": "json-c"}));
+        // not an asset file
+        files.push(json!({"name": "notes.txt", "dataTxId": "txt"}));
+
+        let added = append_drive_files(
+            arfs::DEFAULT_GATEWAY,
+            &mut cache,
+            &drive_files(json!(files)),
+            None,
         )
-    })?;
+        .unwrap();
+
+        assert_eq!(added.len(), 11);
+        assert_eq!(cache.items.len(), 11);
+        for i in 0..10 {
+            let item = &cache.items[&i.to_string()];
+            assert_eq!(item.name, i.to_string());
+            assert_eq!(
+                item.image_link,
+                get_arweave_url(arfs::DEFAULT_GATEWAY, &format!("png{i}"))
+            );
+            assert_eq!(
+                item.metadata_link,
+                get_arweave_url(arfs::DEFAULT_GATEWAY, &format!("json{i}"))
+            );
+        }
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            get_arweave_url(arfs::DEFAULT_GATEWAY, "json-c")
+        );
+    }
 
-    let home = std::env::var("HOME").map_err(|e| anyhow::anyhow!("HOME not set: {}", e))?;
-    let mut cfg_dir = PathBuf::from(home);
-    cfg_dir.push(".config");
-    cfg_dir.push("sugar-cli");
+    #[test]
+    fn test_append_drive_files_replaces_collection_item() {
+        let mut cache = crate::cache::Cache::new();
+        let collection = |tx: &str| {
+            drive_files(json!([
+                {"name": "0.png", "dataTxId": "a"},
+                {"name": "0.json", "dataTxId": "a-json"},
+                {"name": "collection.png", "dataTxId": format!("png-{tx}")},
+                {"name": "collection.json", "dataTxId": format!("json-{tx}")}
+            ]))
+        };
+        append_drive_files(arfs::DEFAULT_GATEWAY, &mut cache, &collection("c1"), None).unwrap();
+
+        // a new upload of the collection files replaces the -1 item
+        let added =
+            append_drive_files(arfs::DEFAULT_GATEWAY, &mut cache, &collection("c2"), None).unwrap();
+        assert_eq!(added, vec!["-1"]);
+        assert_eq!(cache.items.len(), 2);
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            get_arweave_url(arfs::DEFAULT_GATEWAY, "json-c2")
+        );
 
-    fs::create_dir_all(&cfg_dir)
-        .map_err(|e| anyhow::anyhow!("Failed to create config dir: {}", e))?;
+        // a deployed collection item is kept
+        cache.items.get_mut("-1").unwrap().on_chain = true;
+        let added =
+            append_drive_files(arfs::DEFAULT_GATEWAY, &mut cache, &collection("c3"), None).unwrap();
+        assert!(added.is_empty());
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            get_arweave_url(arfs::DEFAULT_GATEWAY, "json-c2")
+        );
+    }
 
-    let mut out = cfg_dir;
-    out.push("ardrive_wallet.json");
+    #[test]
+    fn test_append_drive_files_keeps_existing_items() {
+        let mut cache = crate::cache::Cache::new();
+        let files = drive_files(json!([
+            {"name": "0.png", "dataTxId": "a"},
+            {"name": "0.json", "dataTxId": "a-json"},
+            {"name": "1.png", "dataTxId": "b"},
+            {"name": "1.json", "dataTxId": "b-json"}
+        ]));
+        assert_eq!(
+            append_drive_files(arfs::DEFAULT_GATEWAY, &mut cache, &files, None)
+                .unwrap()
+                .len(),
+            2
+        );
+        cache.items.get_mut("0").unwrap().on_chain = true;
+
+        let files = drive_files(json!([
+            {"name": "0.png", "dataTxId": "a"},
+            {"name": "0.json", "dataTxId": "a-json"},
+            {"name": "art/2.png", "dataTxId": "d"},
+            {"name": "art/2.json", "dataTxId": "d-json"}
+        ]));
+        assert_eq!(
+            append_drive_files(arfs::DEFAULT_GATEWAY, &mut cache, &files, None).unwrap(),
+            vec!["2"]
+        );
 
-    let mut f = fs::File::create(&out).map_err(|e| {
-        anyhow::anyhow!(
-            "Failed to create wallet config file {}: {}",
-            out.display(),
-            e
-        )
-    })?;
-    f.write_all(content.as_bytes()).map_err(|e| {
-        anyhow::anyhow!(
-            "Failed to write wallet config file {}: {}",
-            out.display(),
-            e
-        )
-    })?;
+        assert_eq!(cache.items.len(), 3);
+        assert!(cache.items["0"].on_chain);
+        assert_eq!(cache.items["0"].image_hash, "a");
+        assert_eq!(cache.items["2"].metadata_hash, "d-json");
+    }
 
-    println!(
-        "✅ Stored ardrive wallet to {}. Other ardrive commands will use this wallet.",
-        out.display()
-    );
+    #[test]
+    fn test_append_drive_files_since() {
+        let mut cache = crate::cache::Cache::new();
+        let files = drive_files(json!([
+            {"name": "0.png", "dataTxId": "a", "lastModifiedDate": 1_600_000_000_000u64},
+            {"name": "0.json", "dataTxId": "b", "lastModifiedDate": 1_600_000_000_000u64},
+            {"name": "1.png", "dataTxId": "c", "lastModifiedDate": 1_600_000_000_000u64},
+            {"name": "1.json", "dataTxId": "d", "lastModifiedDate": 1_700_000_000_000u64},
+            {"name": "2.png", "dataTxId": "e"},
+            {"name": "2.json", "dataTxId": "f"}
+        ]));
+
+        assert_eq!(
+            append_drive_files(
+                arfs::DEFAULT_GATEWAY,
+                &mut cache,
+                &files,
+                Some(1_650_000_000)
+            )
+            .unwrap()
+            .len(),
+            2
+        );
+        assert_eq!(cache.items["0"].name, "1");
+        assert_eq!(cache.items["1"].name, "2");
+    }
 
-    println!(
-        "To export into your shell session run: export ARDRIVE_WALLET=$(cat {})",
-        out.display()
-    );
+    #[test]
+    fn test_append_drive_files_reports_orphans() {
+        let mut cache = crate::cache::Cache::new();
+        let files = drive_files(json!([
+            {"name": "0.png", "dataTxId": "a"},
+            {"name": "0.json", "dataTxId": "b"},
+            {"name": "1.png", "dataTxId": "c"},
+            {"name": "2.json", "dataTxId": "d"}
+        ]));
+
+        let error = append_drive_files(arfs::DEFAULT_GATEWAY, &mut cache, &files, None)
+            .unwrap_err()
+            .to_string();
+
+        assert!(error.contains("1.png (missing 1.json)"));
+        assert!(error.contains("2.json (missing image)"));
+        assert!(cache.items.is_empty());
+    }
 
-    Ok(())
-}
+    #[test]
+    fn test_rewrite_gateway_link() {
+        let tx_id = "bNbA3TEQVL60xlgCcqdz4ZPHFZ711cZ3hmkpGttDt_U";
 
-/// Resolve the ardrive wallet content from (in order):
-/// 1) explicit PathBuf passed by user (read file),
-/// 2) ARDRIVE_WALLET environment variable (expected to contain the JSON contents),
-/// 3) saved file at ~/.config/sugar-cli/ardrive_wallet.json
-fn resolve_ardrive_wallet_content(opt_wallet: Option<PathBuf>) -> anyhow::Result<String> {
-    if let Some(p) = opt_wallet {
-        let s = fs::read_to_string(&p)
-            .map_err(|e| anyhow::anyhow!("Failed reading wallet file {}: {}", p.display(), e))?;
-        return Ok(s);
+        let gateway = arfs::DEFAULT_GATEWAY;
+
+        assert_eq!(
+            rewrite_gateway_link(gateway, &format!("https://ar-io.net/{tx_id}")),
+            Some(get_arweave_url(gateway, tx_id))
+        );
+        assert_eq!(
+            rewrite_gateway_link(gateway, &format!("https://ar-io.net/{tx_id}?ext=png")),
+            Some(format!("{}?ext=png", get_arweave_url(gateway, tx_id)))
+        );
+        assert_eq!(
+            rewrite_gateway_link("https://ar-io.net", &get_arweave_url(gateway, tx_id)),
+            Some(format!("https://ar-io.net/{tx_id}"))
+        );
+        // already on the gateway, or not a transaction link
+        assert_eq!(
+            rewrite_gateway_link(gateway, &get_arweave_url(gateway, tx_id)),
+            None
+        );
+        assert_eq!(
+            rewrite_gateway_link(gateway, "https://example.com/images/0.png"),
+            None
+        );
+        assert_eq!(rewrite_gateway_link(gateway, "not a link"), None);
     }
 
-    if let Ok(env_val) = std::env::var("ARDRIVE_WALLET") {
-        if !env_val.trim().is_empty() {
-            return Ok(env_val);
-        }
+    #[test]
+    fn test_covered_bytes() {
+        assert_eq!(covered_bytes(0, 100, 1024), 0);
+        assert_eq!(covered_bytes(250, 100, 1024), 2560);
+        assert_eq!(covered_bytes(u128::MAX, 1, 1024), u64::MAX);
+        assert_eq!(covered_bytes(1, 0, 1024), u64::MAX);
     }
 
-    // fallback to saved path
-    if let Ok(home) = std::env::var("HOME") {
-        let mut cfg = PathBuf::from(home);
-        cfg.push(".config");
-        cfg.push("sugar-cli");
-        cfg.push("ardrive_wallet.json");
+    #[test]
+    fn test_output_format() {
+        assert_eq!(OutputFormat::default(), OutputFormat::Table);
+        assert_eq!("json".parse::<OutputFormat>().unwrap(), OutputFormat::Json);
+        assert_eq!(
+            "table".parse::<OutputFormat>().unwrap(),
+            OutputFormat::Table
+        );
+        assert!("yaml".parse::<OutputFormat>().is_err());
 
-        if cfg.exists() {
-            let s = fs::read_to_string(&cfg).map_err(|e| {
-                anyhow::anyhow!("Failed reading stored wallet {}: {}", cfg.display(), e)
-            })?;
-            return Ok(s);
+        for format in [OutputFormat::Table, OutputFormat::Json] {
+            assert_eq!(format.to_string().parse::<OutputFormat>().unwrap(), format);
         }
     }
 
-    Err(anyhow::anyhow!("No ardrive wallet provided: pass -w/--wallet, set ARDRIVE_WALLET env var, or run 'sugar ardrive set-wallet <file>' to store one."))
-}
+    #[test]
+    fn test_file_query_filters_and_pages() {
+        let files = || {
+            drive_files(json!([
+                {"name": "0.png", "parentFolderId": "root"},
+                {"name": "0.json", "parentFolderId": "root"},
+                {"name": "1.png", "parentFolderId": "art"},
+                {"name": "1.json", "parentFolderId": "art"},
+                {"name": "2.json", "parentFolderId": "art"}
+            ]))
+        };
+        let names = |files: Vec<ArDriveFile>| -> Vec<String> {
+            files.into_iter().filter_map(|f| f.name).collect()
+        };
 
-pub fn process_ardrive_list_drives(wallet: Option<PathBuf>, drive_id: String) -> Result<()> {
-    info!(
-        "ArDrive: list-drives called (wallet override: {:?}, drive_id: {})",
-        wallet, drive_id
-    );
+        let query = FileQuery {
+            parent_folder: Some("art".to_string()),
+            extension: Some("json".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query.apply(files())), vec!["1.json", "2.json"]);
 
-    let content = resolve_ardrive_wallet_content(wallet).map_err(|e| anyhow::anyhow!("{}", e))?;
+        let query = FileQuery {
+            offset: 1,
+            limit: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(names(query.apply(files())), vec!["0.json", "1.png"]);
+        assert!(!query.is_filled(2));
+        assert!(query.is_filled(3));
+
+        // a page past the end is empty
+        let query = FileQuery {
+            offset: 10,
+            limit: Some(5),
+            ..Default::default()
+        };
+        let page = query.apply(files());
+        assert!(page.is_empty());
+        assert_eq!(serde_json::to_string(&page).unwrap(), "[]");
+    }
 
-    // Create a temporary file for the wallet
-    let mut temp_dir = std::env::temp_dir();
-    temp_dir.push("sugar-cli-wallet.tmp.json");
+    #[test]
+    fn test_folder_paths() {
+        let mut entities = drive_files(json!([
+            {"name": "collection", "entityType": "folder", "entityId": "root"},
+            {"name": "images", "entityType": "folder", "entityId": "images", "parentFolderId": "root"},
+            {"name": "backup", "entityType": "folder", "entityId": "backup", "parentFolderId": "root"},
+            {"name": "old", "entityType": "folder", "entityId": "old", "parentFolderId": "backup"},
+            {"name": "0.png", "entityType": "file", "parentFolderId": "images"},
+            {"name": "0.png", "entityType": "file", "parentFolderId": "old"},
+            {"name": "readme.txt", "entityType": "file", "parentFolderId": "root"}
+        ]));
+        FolderPaths::new(&entities).set_paths(&mut entities);
+
+        let paths: Vec<Option<&str>> = entities.iter().map(|e| e.path.as_deref()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                None,
+                Some("images"),
+                Some("backup"),
+                Some("backup/old"),
+                Some("images/0.png"),
+                Some("backup/old/0.png"),
+                Some("readme.txt")
+            ]
+        );
 
-    let mut temp_file =
-        fs::File::create(&temp_dir).context("Failed to create temporary wallet file")?;
-    temp_file
-        .write_all(content.as_bytes())
-        .context("Failed to write wallet content to temporary file")?;
+        let query = FileQuery {
+            folder_paths: vec!["images/".to_string(), "backup".to_string()],
+            ..Default::default()
+        };
+        let paths: Vec<String> = query
+            .apply(entities)
+            .into_iter()
+            .filter_map(|f| f.path)
+            .collect();
+        assert_eq!(paths, vec!["images/0.png", "backup/old/0.png"]);
+    }
 
-    // Make sure the file is written and closed
-    drop(temp_file);
+    #[test]
+    fn test_file_query_entity_and_content_types() {
+        let files = || {
+            drive_files(json!([
+                {"name": "art", "entityType": "folder"},
+                {"name": "0.PNG", "entityType": "file", "dataContentType": "image/png"},
+                {"name": "0", "entityType": "file", "dataContentType": "application/json; charset=utf-8"},
+                {"name": "1.png", "entityType": "file", "dataContentType": "image/png"}
+            ]))
+        };
+        let names = |files: Vec<ArDriveFile>| -> Vec<String> {
+            files.into_iter().filter_map(|f| f.name).collect()
+        };
 
-    info!(
+        // folders are excluded by default
+        assert_eq!(
+            names(FileQuery::default().apply(files())),
+            vec!["0.PNG", "0", "1.png"]
+        );
+
+        let query = FileQuery {
+            entity_type: EntityType::Folder,
+            ..Default::default()
+        };
+        assert_eq!(names(query.apply(files())), vec!["art"]);
+
+        let query = FileQuery {
+            extension: Some("png".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query.apply(files())), vec!["0.PNG", "1.png"]);
+
+        let query = FileQuery {
+            content_type: Some("application/json".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query.apply(files())), vec!["0"]);
+
+        let query = FileQuery {
+            content_type: Some("image/*".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(names(query.apply(files())), vec!["0.PNG", "1.png"]);
+
+        assert_eq!("folder".parse::<EntityType>().unwrap(), EntityType::Folder);
+        assert!("drive".parse::<EntityType>().is_err());
+    }
+
+    fn status_error(status: u16) -> anyhow::Error {
+        ArDriveError::http_status("gateway", reqwest::StatusCode::from_u16(status).unwrap()).into()
+    }
+
+    #[test]
+    fn test_transient_errors() {
+        assert!(is_transient(&status_error(429)));
+        assert!(is_transient(&status_error(503)));
+        assert!(is_transient(&status_error(502).context("listing failed")));
+        assert!(!is_transient(&status_error(404)));
+        assert!(!is_transient(&status_error(401)));
+        assert!(!is_transient(&anyhow!(
+            "incorrect drive password for drive d"
+        )));
+
+        let err = cli_error(
+            "Error: Request failed with status code 503",
+            anyhow!("failed"),
+        );
+        assert!(is_transient(&err));
+        assert_eq!(err.to_string(), "failed");
+        assert!(!is_transient(&cli_error(
+            "Error: Entity with Id 5030 not found",
+            anyhow!("failed")
+        )));
+    }
+
+    #[test]
+    fn test_retries_transient_errors_only() {
+        let options = ArdriveOptions {
+            max_retries: 2,
+            retry_delay_ms: 1,
+            ..ArdriveOptions::default()
+        };
+
+        let mut attempts = 0;
+        let err = with_retry_blocking(&options, "listing", || -> Result<()> {
+            attempts += 1;
+            Err(status_error(429))
+        })
+        .unwrap_err();
+        assert_eq!(attempts, 3);
+        assert_eq!(
+            err.to_string(),
+            "listing failed after 3 attempts: gateway returned 429 Too Many Requests"
+        );
+
+        let mut attempts = 0;
+        let err = with_retry_blocking(&options, "listing", || -> Result<()> {
+            attempts += 1;
+            Err(status_error(404))
+        })
+        .unwrap_err();
+        assert_eq!(attempts, 1);
+        assert_eq!(err.to_string(), "gateway returned 404 Not Found");
+
+        let mut attempts = 0;
+        let value = with_retry_blocking(&options, "listing", || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(status_error(503))
+            } else {
+                Ok(attempts)
+            }
+        })
+        .unwrap();
+        assert_eq!(value, 3);
+    }
+
+    #[test]
+    fn test_quiet_hides_the_progress() {
+        let options = ArdriveOptions {
+            quiet: true,
+            ..ArdriveOptions::default()
+        };
+
+        let pb = files_progress_bar(&options, 3);
+        assert!(pb.is_hidden());
+        // the hidden bar still counts the files
+        pb.inc(2);
+        assert_eq!(pb.position(), 2);
+        assert!(progress_spinner(&options, "Listing...").is_hidden());
+    }
+
+    #[test]
+    fn test_is_downloaded_checks_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.json");
+
+        assert!(!is_downloaded(&path, Some(2)));
+
+        fs::write(&path, "{}").unwrap();
+        assert!(is_downloaded(&path, Some(2)));
+        assert!(is_downloaded(&path, None));
+        // a partial (or stale) file is downloaded again
+        assert!(!is_downloaded(&path, Some(1024)));
+        assert!(!is_downloaded(dir.path(), None));
+    }
+
+    #[test]
+    fn test_sha256_hex_matches_upload_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.json");
+        fs::write(&path, br#"{"name": "Item 0"}"#).unwrap();
+
+        assert_eq!(
+            sha256_hex(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            sha256_hex(&fs::read(&path).unwrap()),
+            crate::upload::assets::encode(&path.to_string_lossy()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_download_skips_existing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("0.png"), [0u8; 4]).unwrap();
+
+        let files = drive_files(json!([
+            {"name": "0.png", "dataTxId": "png0", "size": 4},
+            {"name": "../1.png", "size": 4},
+            {"name": "..", "dataTxId": "tx"}
+        ]));
+        let client = arfs::ArweaveClient::new(&ArdriveOptions::default());
+
+        let outcome = download_drive_file(&client, dir.path(), &files[0], false).await;
+        assert_eq!(outcome.unwrap(), DownloadOutcome::Skipped);
+        // names are reduced to their basename, and files without data fail
+        assert!(download_drive_file(&client, dir.path(), &files[1], false)
+            .await
+            .unwrap_err()
+            .to_string()
+            .contains("no data transaction"));
+        assert!(download_drive_file(&client, dir.path(), &files[2], false)
+            .await
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_temp_wallet_is_private_and_removed() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let wallet = write_temp_wallet("{}").unwrap();
+        let path = wallet.path().to_path_buf();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        drop(wallet);
+        assert!(!path.exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_stored_wallet_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wallet.json");
+
+        write_wallet_file(&path, "{}").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "{}");
+
+        // an existing wallet readable by others is restricted when replaced
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_wallet_file(&path, "[]").unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[]");
+    }
+
+    #[test]
+    fn test_redacted_wallet() {
+        let wallet = json!({
+            "kty": "RSA",
+            "n": "bW9kdWx1cw",
+            "e": "AQAB",
+            "d": "cHJpdmF0ZS1leHBvbmVudA",
+            "p": "cHJpbWUtcA",
+            "q": "cHJpbWUtcQ",
+        })
+        .to_string();
+
+        let lines = redacted_wallet(&wallet);
+        assert_eq!(lines[0], "Top-level keys (6): d, e, kty, n, p, q");
+        assert_eq!(
+            lines[1],
+            format!(
+                "Arweave address: {}",
+                arfs::wallet_address(&wallet).unwrap()
+            )
+        );
+        // no value of the wallet is shown, only its keys
+        for value in [
+            "RSA",
+            "bW9kdWx1cw",
+            "AQAB",
+            "cHJpdmF0ZS1leHBvbmVudA",
+            "cHJpbWUtcA",
+        ] {
+            assert!(lines.iter().all(|line| !line.contains(value)), "{value}");
+        }
+
+        // an invalid wallet is not echoed either
+        let lines = redacted_wallet("not-a-wallet secret");
+        assert_eq!(lines[0], "(wallet content is not a JSON object)");
+        assert!(lines[1].starts_with("Arweave address: unavailable"));
+        assert!(lines.iter().all(|line| !line.contains("secret")));
+
+        let lines = redacted_wallet(r#"{"d": "secret"}"#);
+        assert_eq!(lines[0], "Top-level keys (1): d");
+        assert!(lines[1].contains("missing the 'n' (modulus) field"));
+        assert!(lines.iter().all(|line| !line.contains("secret")));
+    }
+
+    // fake `ardrive` that fails if its wallet file disappears while it runs
+    #[cfg(unix)]
+    const ARDRIVE_SHIM: &str = r#"#!/bin/sh
+wallet=""
+while [ $# -gt 0 ]; do
+    if [ "$1" = "--wallet-file" ]; then wallet="$2"; fi
+    shift
+done
+[ -z "$wallet" ] && exit 0
+[ -f "$wallet" ] || { echo "missing wallet" >&2; exit 1; }
+sleep 1
+[ -f "$wallet" ] || { echo "wallet removed" >&2; exit 1; }
+echo '[{"entityType": "file", "name": "0.png", "dataTxId": "tx"}]'
+"#;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_concurrent_node_listings() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("sugar-ardrive-shim-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let shim = dir.join("ardrive");
+        fs::write(&shim, ARDRIVE_SHIM).unwrap();
+        fs::set_permissions(&shim, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let wallet = dir.join("wallet.json");
+        fs::write(&wallet, "{}").unwrap();
+
+        let results: Vec<Result<Vec<ArDriveFile>>> = std::thread::scope(|scope| {
+            let handles: Vec<_> = ["drive-a", "drive-b"]
+                .into_iter()
+                .map(|drive_id| {
+                    let (shim, wallet) = (&shim, wallet.clone());
+                    scope.spawn(move || {
+                        node_list_drive_files(
+                            &ArdriveOptions::default(),
+                            Some(shim),
+                            Some(wallet),
+                            drive_id,
+                            None,
+                        )
+                    })
+                })
+                .collect();
+
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        for result in results {
+            let files = result.unwrap();
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].name.as_deref(), Some("0.png"));
+        }
+    }
+}
+
+// Placeholder implementations for ArDrive interactions.
+// Replace these with real SDK calls / HTTP requests as needed.
+
+pub fn process_ardrive_list(bucket: Option<String>) -> Result<()> {
+    info!("ArDrive: list called: bucket={:?}", bucket);
+    report!("(ardrive) Listing contents of {:?} (placeholder)", bucket);
+    Ok(())
+}
+
+/// Prints the details of a drive, folder or file (by entity ID or metadata transaction ID), or
+/// of any Arweave transaction, failing if the gateway does not know the ID.
+pub async fn process_ardrive_info(
+    id: String,
+    format: OutputFormat,
+    options: &ArdriveOptions,
+) -> Result<()> {
+    info!("ArDrive: info called: id={}", id);
+
+    let id = id.trim();
+    let pb = progress_spinner(options, "Looking up the entity...");
+    let entity = arfs::entity_info(options, id).await;
+    pb.finish_and_clear();
+
+    let entity = entity?.ok_or_else(|| anyhow!("no ArFS entity found for {}", id))?;
+
+    match format {
+        OutputFormat::Json => report!("{}", serde_json::to_string_pretty(&entity)?),
+        OutputFormat::Table => print_entity(&options.gateway, &entity),
+    }
+
+    Ok(())
+}
+
+fn print_entity(gateway: &str, entity: &arfs::EntityInfo) {
+    let time = |seconds: Option<u64>| {
+        seconds
+            .and_then(|seconds| NaiveDateTime::from_timestamp_opt(seconds as i64, 0))
+            .map(|time| format!("{} UTC", time))
+    };
+
+    let fields = [
+        ("Type", Some(entity.kind.clone())),
+        ("ID", Some(entity.id.clone())),
+        ("Transaction", Some(entity.tx_id.clone())),
+        (
+            "Name",
+            entity
+                .name
+                .clone()
+                .or_else(|| entity.private.then(|| "<encrypted>".to_string())),
+        ),
+        ("Drive", entity.drive_id.clone()),
+        ("Parent folder", entity.parent_folder_id.clone()),
+        ("Root folder", entity.root_folder_id.clone()),
+        (
+            "Data",
+            entity
+                .data_tx_id
+                .as_deref()
+                .map(|tx_id| get_arweave_url(gateway, tx_id)),
+        ),
+        ("Size", entity.size.map(|size| format!("{} bytes", size))),
+        ("Content type", entity.content_type.clone()),
+        ("Owner", entity.owner.clone()),
+        (
+            "Block",
+            Some(
+                entity
+                    .block_height
+                    .map(|height| height.to_string())
+                    .unwrap_or_else(|| "pending".to_string()),
+            ),
+        ),
+        ("Block time", time(entity.block_timestamp)),
+        ("Created", time(entity.unix_time)),
+        (
+            "Last modified",
+            time(entity.last_modified_date.map(|millis| millis / 1000)),
+        ),
+    ];
+
+    for (label, value) in fields {
+        if let Some(value) = value {
+            report!("{:14} {}", format!("{}:", label), value);
+        }
+    }
+}
+
+/// Store the provided ardrive wallet file contents into the user's config
+/// so other CLI calls can read it. We copy the file contents into
+/// ~/.config/sugar-cli/ardrive_wallet.json, or ~/.config/sugar-cli/wallets/<profile>.json
+/// when a profile is selected (creates dirs if needed).
+pub fn process_ardrive_set_wallet(
+    wallet_file: std::path::PathBuf,
+    options: &ArdriveOptions,
+) -> Result<()> {
+    info!("ArDrive: set wallet called: {:?}", wallet_file);
+
+    let content = fs::read_to_string(&wallet_file).map_err(|e| {
+        anyhow::anyhow!(
+            "Failed to read wallet file {}: {}",
+            wallet_file.display(),
+            e
+        )
+    })?;
+
+    let profile = &options.profile;
+    let out = match profile {
+        Some(name) => profile_wallet_path(name),
+        None => default_wallet_path(),
+    }
+    .ok_or_else(|| anyhow::anyhow!("Failed to locate the home directory"))?;
+
+    if let Some(cfg_dir) = out.parent() {
+        fs::create_dir_all(cfg_dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create config dir: {}", e))?;
+    }
+
+    write_wallet_file(&out, &content)?;
+
+    match profile {
+        Some(name) => report!(
+            "✅ Stored ardrive wallet of profile '{}' to {}. Use --profile {} to select it.",
+            name,
+            out.display(),
+            name
+        ),
+        None => report!(
+            "✅ Stored ardrive wallet to {}. Other ardrive commands will use this wallet.",
+            out.display()
+        ),
+    }
+
+    report!(
+        "To export into your shell session run: export ARDRIVE_WALLET=$(cat {})",
+        out.display()
+    );
+
+    Ok(())
+}
+
+/// Resolve the ardrive wallet content from (in order):
+/// 1) explicit PathBuf passed by user (read file),
+/// 2) ARDRIVE_WALLET environment variable (expected to contain the JSON contents),
+/// 3) the profile selected with --profile, saved at ~/.config/sugar-cli/wallets/<profile>.json
+/// 4) saved file at ~/.config/sugar-cli/ardrive_wallet.json
+pub(crate) fn resolve_ardrive_wallet_content(
+    options: &ArdriveOptions,
+    opt_wallet: Option<PathBuf>,
+) -> anyhow::Result<String> {
+    resolve_ardrive_wallet(options, opt_wallet).map(|(content, _)| content)
+}
+
+/// Resolve the ardrive wallet content (see `resolve_ardrive_wallet_content`) and a
+/// description of where it was read from.
+fn resolve_ardrive_wallet(
+    options: &ArdriveOptions,
+    opt_wallet: Option<PathBuf>,
+) -> anyhow::Result<(String, String)> {
+    if let Some(p) = opt_wallet {
+        let s = fs::read_to_string(&p)
+            .map_err(|e| anyhow::anyhow!("Failed reading wallet file {}: {}", p.display(), e))?;
+        return Ok((s, format!("file: {}", p.display())));
+    }
+
+    if let Ok(env_val) = std::env::var("ARDRIVE_WALLET") {
+        if !env_val.trim().is_empty() {
+            return Ok((env_val, "environment variable ARDRIVE_WALLET".to_string()));
+        }
+    }
+
+    // a selected profile never falls back to the default wallet
+    if let Some(name) = &options.profile {
+        let cfg = profile_wallet_path(name)
+            .ok_or_else(|| anyhow::anyhow!("Failed to locate the home directory"))?;
+
+        if !cfg.exists() {
+            return Err(ArDriveError::WalletProfileNotFound(name.clone()).into());
+        }
+
+        let s = fs::read_to_string(&cfg).map_err(|e| {
+            anyhow::anyhow!("Failed reading stored wallet {}: {}", cfg.display(), e)
+        })?;
+        return Ok((s, format!("profile '{}': {}", name, cfg.display())));
+    }
+
+    // fallback to saved path
+    if let Some(cfg) = default_wallet_path() {
+        if cfg.exists() {
+            let s = fs::read_to_string(&cfg).map_err(|e| {
+                anyhow::anyhow!("Failed reading stored wallet {}: {}", cfg.display(), e)
+            })?;
+            return Ok((s, format!("stored file: {}", cfg.display())));
+        }
+    }
+
+    Err(ArDriveError::WalletNotConfigured.into())
+}
+
+/// Resolve the password of private drives from the explicit option or the
+/// ARDRIVE_DRIVE_PASSWORD environment variable.
+pub(crate) fn resolve_drive_password(opt_password: Option<String>) -> Option<String> {
+    opt_password.or_else(|| {
+        std::env::var("ARDRIVE_DRIVE_PASSWORD")
+            .ok()
+            .filter(|password| !password.is_empty())
+    })
+}
+
+/// Adds the gateway option to an ArDrive CLI command when it is not the default gateway.
+pub(super) fn add_gateway(options: &ArdriveOptions, cmd: &mut Command) {
+    if options.gateway != arfs::DEFAULT_GATEWAY {
+        cmd.arg("--gateway").arg(&options.gateway);
+    }
+}
+
+/// Adds the private drive options to an ArDrive CLI command.
+fn add_drive_password(cmd: &mut Command, password: Option<&str>) {
+    if let Some(password) = password {
+        cmd.arg("--private")
+            .arg("--unsafe-drive-password")
+            .arg(password);
+    }
+}
+
+/// Returns `true` if an ArDrive CLI listing with a drive password failed to decrypt the drive.
+fn is_password_error(password: Option<&str>, stderr: &str) -> bool {
+    let stderr = stderr.to_lowercase();
+    password.is_some() && (stderr.contains("decrypt") || stderr.contains("password"))
+}
+
+/// Writes a stored wallet (default or profile). The file is only readable by the user
+/// (0600 on Unix), also when it replaces an existing wallet.
+fn write_wallet_file(path: &Path, content: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create wallet config file {}", path.display()))?;
+
+    // the mode only applies to new files
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("Failed to restrict wallet config file {}", path.display()))?;
+    }
+
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("Failed to write wallet config file {}", path.display()))
+}
+
+/// Writes the wallet to a unique temporary file for the ArDrive CLI. The file is only
+/// readable by the user (0600 on Unix) and is deleted when the returned handle is dropped.
+pub(super) fn write_temp_wallet(content: &str) -> Result<NamedTempFile> {
+    let mut file = tempfile::Builder::new()
+        .prefix("sugar-cli-wallet-")
+        .suffix(".json")
+        .tempfile()
+        .context("Failed to create temporary wallet file")?;
+
+    file.write_all(content.as_bytes())
+        .and_then(|_| file.flush())
+        .context("Failed to write wallet content to temporary file")?;
+
+    Ok(file)
+}
+
+pub fn process_ardrive_list_drives(
+    wallet: Option<PathBuf>,
+    drive_id: String,
+    drive_password: Option<String>,
+    format: OutputFormat,
+    options: &ArdriveOptions,
+) -> Result<()> {
+    info!(
+        "ArDrive: list-drives called (wallet override: {:?}, drive_id: {})",
+        wallet, drive_id
+    );
+
+    let content = resolve_ardrive_wallet_content(options, wallet)?;
+    let drive_password = resolve_drive_password(drive_password);
+    let password = drive_password.as_deref();
+
+    // Create a temporary file for the wallet (removed when dropped, even on errors)
+    let wallet_file = write_temp_wallet(&content)?;
+    let wallet_path = wallet_file.path();
+
+    info!(
         "Using ardrive wallet ({} bytes) from temporary file",
         content.len()
     );
 
-    // Run ardrive list-drive with our temporary wallet file
-    // Prefer a local wrapper in node_modules/.bin; otherwise fall back to system `ardrive`.
-    let ardrive_local = find_local_ardrive();
-    let output = if let Some(ref ardrive_path) = ardrive_local {
-        let mut cmd = Command::new(ardrive_path);
-        cmd.arg("list-drive")
-            .arg("-d") // or --drive-id
-            .arg(&drive_id)
-            .arg("--wallet-file")
-            .arg(&temp_dir)
-            .env("NODE_ENV", "production");
-        cmd.output()
-            .context("Failed to execute local ardrive bin for list-drive")?
-    } else {
-        let mut cmd = Command::new("ardrive");
-        cmd.arg("list-drive")
-            .arg("-d")
-            .arg(&drive_id)
-            .arg("--wallet-file")
-            .arg(&temp_dir)
-            .env("NODE_ENV", "production");
-        cmd.output()
-            .context("Failed to execute system ardrive for list-drive")?
-    };
+    // Run ardrive list-drive with our temporary wallet file, retrying gateway errors
+    let stdout = with_retry_blocking(options, "ArDrive CLI list-drive", || {
+        let ardrive_local = find_local_ardrive();
+        let output = if let Some(ref ardrive_path) = ardrive_local {
+            let mut cmd = Command::new(ardrive_path);
+            cmd.arg("list-drive")
+                .arg("-d") // or --drive-id
+                .arg(&drive_id)
+                .arg("--wallet-file")
+                .arg(wallet_path)
+                .env("NODE_ENV", "production");
+            add_drive_password(&mut cmd, password);
+            add_gateway(options, &mut cmd);
+            cmd.output()
+                .context("Failed to execute local ardrive bin for list-drive")?
+        } else {
+            let mut cmd = Command::new("ardrive");
+            cmd.arg("list-drive")
+                .arg("-d")
+                .arg(&drive_id)
+                .arg("--wallet-file")
+                .arg(wallet_path)
+                .env("NODE_ENV", "production");
+            add_drive_password(&mut cmd, password);
+            add_gateway(options, &mut cmd);
+            cmd.output()
+                .context("Failed to execute system ardrive for list-drive")?
+        };
 
-    // Clean up the temporary file
-    if let Err(e) = fs::remove_file(&temp_dir) {
-        info!("Note: Failed to clean up temporary wallet file: {}", e);
-    }
+        // Return the command output or error
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
 
-    // Print the command output or error
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
-    let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        if output.status.success() {
+            if !stderr.is_empty() {
+                info!("ArDrive CLI stderr (non-fatal): {}", stderr);
+            }
+            Ok(stdout)
+        } else if is_password_error(password, &stderr) {
+            Err(anyhow!("incorrect drive password for drive {}", drive_id))
+        } else {
+            Err(cli_error(&stderr, anyhow::anyhow!(
+                "ArDrive CLI command failed:\nStdout: {}\nStderr: {}\nCommand: list-drive -d {} --wallet-file <wallet>",
+                stdout, stderr, drive_id
+            )))
+        }
+    })?;
 
-    if output.status.success() {
-        if !stderr.is_empty() {
-            info!("ArDrive CLI stderr (non-fatal): {}", stderr);
+    match format {
+        OutputFormat::Json => {
+            let entities = try_parse_json_flex(&stdout)
+                .context("Failed to parse the ArDrive CLI list-drive output")?;
+            report!("{}", serde_json::to_string_pretty(&entities)?);
         }
-        println!("{}", stdout);
-        Ok(())
-    } else {
-        Err(anyhow::anyhow!(
-            "ArDrive CLI command failed:\nStdout: {}\nStderr: {}\nCommand: list-drive -d {} --wallet-file <wallet>",
-            stdout, stderr, drive_id
-        ))
+        OutputFormat::Table => report!("{}", stdout),
     }
+
+    Ok(())
 }
 
-pub fn process_ardrive_list_all_drives(
+/// Lists all the drives of the wallet, reading the ArFS entities from Arweave (or using the
+/// ArDrive Node CLI when `use_node_cli` is set). Private drives are decrypted with the drive
+/// password (or the `ARDRIVE_DRIVE_PASSWORD` environment variable).
+pub async fn process_ardrive_list_all_drives(
     wallet: Option<PathBuf>,
     output_path: Option<PathBuf>,
+    drive_password: Option<String>,
+    format: OutputFormat,
+    use_node_cli: bool,
+    options: &ArdriveOptions,
 ) -> Result<Vec<ArDriveDrive>> {
     info!(
-        "ArDrive: list-all-drives called (wallet override: {:?}, output: {:?})",
-        wallet, output_path
+        "ArDrive: list-all-drives called (wallet override: {:?}, output: {:?}, node cli: {})",
+        wallet, output_path, use_node_cli
     );
 
+    let drive_password = resolve_drive_password(drive_password);
+
+    let drives = if use_node_cli {
+        with_retry_blocking(options, "ArDrive CLI list-all-drives", || {
+            node_list_all_drives(options, wallet.clone(), drive_password.as_deref())
+        })?
+    } else {
+        let content = resolve_ardrive_wallet_content(options, wallet)?;
+        arfs::list_drives(options, &content, drive_password.as_deref()).await?
+    };
+
+    match format {
+        OutputFormat::Json => report!(
+            "{}",
+            serde_json::to_string_pretty(&drives).context("Failed to serialize drives JSON")?
+        ),
+        OutputFormat::Table => print_drives_table(&drives),
+    }
+
+    // Optionally write to file
+    if let Some(path) = output_path {
+        fs::write(
+            &path,
+            serde_json::to_string_pretty(&drives).context("Failed to serialize drives JSON")?,
+        )
+        .context(format!(
+            "Failed to write drives to file: {}",
+            path.display()
+        ))?;
+        format.status(format!("✅ Drives written to {}", path.display()));
+    }
+
+    Ok(drives)
+}
+
+fn print_drives_table(drives: &[ArDriveDrive]) {
+    report!("Found {} drive(s)", drives.len());
+
+    if drives.is_empty() {
+        return;
+    }
+
+    report!(
+        "{:30} | {:36} | {:7} | {:36}",
+        "name",
+        "drive id",
+        "privacy",
+        "root folder"
+    );
+    report!("{:-<30} | {:-<36} | {:-<7} | {:-<36}", "", "", "", "");
+
+    for drive in drives {
+        report!(
+            "{:30} | {:36} | {:7} | {:36}",
+            drive.name.as_deref().unwrap_or("<encrypted>"),
+            drive.drive_id.as_deref().unwrap_or("-"),
+            drive.drive_privacy.as_deref().unwrap_or("-"),
+            drive.root_folder_id.as_deref().unwrap_or("-")
+        );
+    }
+}
+
+/// Lists the drives using the ArDrive Node CLI (`--use-node-cli`).
+fn node_list_all_drives(
+    options: &ArdriveOptions,
+    wallet: Option<PathBuf>,
+    password: Option<&str>,
+) -> Result<Vec<ArDriveDrive>> {
     // Prefer a local wrapper for version check if present
     let ardrive_local = find_local_ardrive();
     let ardrive_version = if let Some(local) = &ardrive_local {
@@ -522,20 +1901,11 @@ pub fn process_ardrive_list_all_drives(
         ));
     }
 
-    let content = resolve_ardrive_wallet_content(wallet).map_err(|e| anyhow::anyhow!("{}", e))?;
-
-    // Create a temporary file for the wallet
-    let mut temp_dir = std::env::temp_dir();
-    temp_dir.push("sugar-cli-wallet.tmp.json");
-
-    let mut temp_file =
-        fs::File::create(&temp_dir).context("Failed to create temporary wallet file")?;
-    temp_file
-        .write_all(content.as_bytes())
-        .context("Failed to write wallet content to temporary file")?;
+    let content = resolve_ardrive_wallet_content(options, wallet)?;
 
-    // Make sure the file is written and closed
-    drop(temp_file);
+    // Create a temporary file for the wallet (removed when dropped, even on errors)
+    let wallet_file = write_temp_wallet(&content)?;
+    let wallet_path = wallet_file.path();
 
     info!(
         "Using ardrive wallet ({} bytes) from temporary file",
@@ -571,14 +1941,16 @@ pub fn process_ardrive_list_all_drives(
         let mut cmd = Command::new(ardrive_path);
         cmd.arg("list-all-drives")
             .arg("--wallet-file")
-            .arg(&temp_dir)
+            .arg(wallet_path)
             .env("NODE_ENV", "production");
 
         info!(
             "Running local ardrive bin: {} list-all-drives --wallet-file {:?}",
             ardrive_path.display(),
-            temp_dir
+            wallet_path
         );
+        add_drive_password(&mut cmd, password);
+        add_gateway(options, &mut cmd);
         cmd.output()
             .context("Failed to execute local ardrive bin")?
     } else {
@@ -586,18 +1958,17 @@ pub fn process_ardrive_list_all_drives(
         let mut cmd = Command::new("ardrive");
         cmd.arg("list-all-drives")
             .arg("--wallet-file")
-            .arg(&temp_dir)
+            .arg(wallet_path)
             .env("NODE_ENV", "production");
 
         info!(
             "Running system ardrive: list-all-drives --wallet-file {:?}",
-            temp_dir
+            wallet_path
         );
+        add_drive_password(&mut cmd, password);
+        add_gateway(options, &mut cmd);
         cmd.output().context("Failed to execute system ardrive")?
-    }; // Clean up the temporary file
-    if let Err(e) = fs::remove_file(&temp_dir) {
-        info!("Note: Failed to clean up temporary wallet file: {}", e);
-    }
+    };
 
     // Parse the command output or error
     if output.status.success() {
@@ -610,21 +1981,18 @@ pub fn process_ardrive_list_all_drives(
             info!("ArDrive CLI stderr: {}", stderr);
         }
 
-        // Try to normalize potential line noise
-        let clean_stdout = stdout.trim().trim_start_matches('\u{feff}');
-
-        // Try to parse and provide detailed error information
-        let val: Value = match serde_json::from_str(clean_stdout) {
+        // Try to parse (skipping banners and warnings) and provide detailed error information
+        let val: Value = match try_parse_json_flex(&stdout) {
             Ok(v) => v,
             Err(e) => {
-                let error_msg = format!(
-                    "\nParse error: {}\nCommand output:\n{}\n\nNote: Make sure ardrive-cli is installed with `pnpm add ardrive-cli`",
-                    e, clean_stdout
-                );
-                return Err(anyhow!(
-                    "ArDrive CLI returned invalid output: {}",
-                    error_msg
-                ));
+                return Err(ArDriveError::ParseError {
+                    message: format!(
+                        "ArDrive CLI returned invalid output: \nParse error: {}\nCommand output:\n{}",
+                        e,
+                        output_excerpt(&stdout)
+                    ),
+                }
+                .into());
             }
         };
 
@@ -634,10 +2002,9 @@ pub fn process_ardrive_list_all_drives(
                 Actual JSON structure: {}\n\
                 Raw stdout: {}\n\
                 Stderr: {}",
-                serde_json::to_string_pretty(&val)
-                    .unwrap_or_else(|_| "<failed to pretty print>".to_string()),
-                stdout,
-                stderr
+                output_excerpt(&val.to_string()),
+                output_excerpt(&stdout),
+                output_excerpt(&stderr)
             )
         })?;
 
@@ -649,69 +2016,320 @@ pub fn process_ardrive_list_all_drives(
             drives.push(d);
         }
 
-        println!("Found {} drive(s)", drives.len());
-        println!(
-            "Drives: {}",
-            serde_json::to_string_pretty(&drives).unwrap_or_else(|_| stdout.to_string())
-        );
-
-        // Optionally write to file
-        if let Some(path) = output_path {
-            fs::write(
-                &path,
-                serde_json::to_string_pretty(&drives).context("Failed to serialize drives JSON")?,
-            )
-            .context(format!(
-                "Failed to write drives to file: {}",
-                path.display()
-            ))?;
-            println!("✅ Drives written to {}", path.display());
-        }
-
         Ok(drives)
+    } else if is_password_error(password, &String::from_utf8_lossy(&output.stderr)) {
+        Err(anyhow!(
+            "incorrect drive password for a private drive of the wallet"
+        ))
     } else {
         let stderr = String::from_utf8_lossy(&output.stderr);
         let stdout = String::from_utf8_lossy(&output.stdout);
-        Err(anyhow::anyhow!(
-            "ArDrive CLI command failed.\nStderr: {}\nStdout: {}\n\nIf you don't have ArDrive installed locally, run: `pnpm add ardrive-cli` in this project (then `pnpm install`).\nOr install globally: `pnpm add -g ardrive-cli`. Ensure Node.js is installed and the wrapper is executable.",
-            stderr, stdout
-        ))
+        Err(cli_error(&stderr, ArDriveError::SubprocessFailed {
+            code: output.status.code(),
+            message: format!(
+                "ArDrive CLI command failed.\nStderr: {}\nStdout: {}\n\nIf you don't have ArDrive installed locally, run: `pnpm add ardrive-cli` in this project (then `pnpm install`).\nOr install globally: `pnpm add -g ardrive-cli`. Ensure Node.js is installed and the wrapper is executable.",
+                stderr, stdout
+            ),
+        }
+        .into()))
     }
 }
 
-/// Debug helper: show which wallet the CLI resolved and print its JSON contents.
-/// Use with an optional wallet path override. This is intended for debugging only
-/// (the wallet contains private keys — don't paste the output publicly).
+pub struct ArdriveListDriveFilesArgs {
+    /// Optional wallet override.
+    pub wallet: Option<PathBuf>,
+    pub drive_id: String,
+    /// Path to write the file list (JSON) to.
+    pub output_path: Option<PathBuf>,
+    pub query: FileQuery,
+    /// Password of a private drive, defaults to the ARDRIVE_DRIVE_PASSWORD env var.
+    pub drive_password: Option<String>,
+    pub format: OutputFormat,
+    /// List the files with the ArDrive Node CLI instead of reading the ArFS entities.
+    pub use_node_cli: bool,
+    /// Query the confirmation status of the data transactions.
+    pub check_status: bool,
+    pub options: ArdriveOptions,
+}
+
 /// List all files in a specific drive. Returns a Vec of files with their names and Arweave URLs.
-/// Can filter by file extension using filter_ext (e.g. Some("json") for .json files only).
-pub fn process_ardrive_list_drive_files(
-    wallet: Option<PathBuf>,
-    drive_id: String,
-    output_path: Option<PathBuf>,
-    filter_ext: Option<&str>,
+/// The `query` restricts the listing to a folder, a file extension (e.g. `json` for .json
+/// files only) and a page of the matching files. The files are read from the ArFS entities on
+/// Arweave, or listed by the ArDrive Node CLI when `use_node_cli` is set. The files of a
+/// private drive are decrypted with the drive password (or `ARDRIVE_DRIVE_PASSWORD`). With
+/// `check_status`, the files are annotated with the confirmation status of their data
+/// transaction.
+pub async fn process_ardrive_list_drive_files(
+    args: ArdriveListDriveFilesArgs,
 ) -> Result<Vec<ArDriveFile>> {
+    let ArdriveListDriveFilesArgs {
+        wallet,
+        drive_id,
+        output_path,
+        query,
+        drive_password,
+        format,
+        use_node_cli,
+        check_status,
+        options,
+    } = args;
+
     info!(
-        "ArDrive: list-drive-files called for drive {} (wallet override: {:?}, filter: {:?})",
-        drive_id, wallet, filter_ext
+        "ArDrive: list-drive-files called for drive {} (wallet override: {:?}, query: {:?}, node cli: {}, check status: {})",
+        drive_id, wallet, query, use_node_cli, check_status
     );
 
-    let content = resolve_ardrive_wallet_content(wallet).map_err(|e| anyhow::anyhow!("{}", e))?;
+    check_drive_id(&drive_id)?;
+    let drive_password = resolve_drive_password(drive_password);
 
-    // Create a temporary file for the wallet
-    let mut temp_dir = std::env::temp_dir();
-    temp_dir.push("sugar-cli-wallet.tmp.json");
+    // the native listing shows its own progress
+    let spinner = if use_node_cli {
+        progress_spinner(&options, "Listing the drive files with the ArDrive CLI...")
+    } else {
+        ProgressBar::hidden()
+    };
 
-    let mut temp_file =
-        fs::File::create(&temp_dir).context("Failed to create temporary wallet file")?;
-    temp_file
-        .write_all(content.as_bytes())
-        .context("Failed to write wallet content to temporary file")?;
+    let ardrive = if use_node_cli {
+        find_local_ardrive()
+    } else {
+        None
+    };
+
+    let mut files = match drive_password {
+        Some(password) if use_node_cli => query.apply(with_retry_blocking(
+            &options,
+            "ArDrive CLI list-drive",
+            || {
+                node_list_drive_files(
+                    &options,
+                    ardrive.as_deref(),
+                    wallet.clone(),
+                    &drive_id,
+                    Some(&password),
+                )
+            },
+        )?),
+        Some(password) => {
+            let content = resolve_ardrive_wallet_content(&options, wallet)?;
+            arfs::list_drive_files(&options, &content, &drive_id, &query, Some(&password)).await?
+        }
+        // the listing cache only holds public drives
+        None if use_node_cli => {
+            let files = match listing::load(&options, &drive_id) {
+                Some(files) => files,
+                None => {
+                    let files = with_retry_blocking(&options, "ArDrive CLI list-drive", || {
+                        node_list_drive_files(
+                            &options,
+                            ardrive.as_deref(),
+                            wallet.clone(),
+                            &drive_id,
+                            None,
+                        )
+                    })?;
+                    listing::store(&options, &drive_id, &files);
+                    files
+                }
+            };
+            query.apply(files)
+        }
+        None => {
+            let content = resolve_ardrive_wallet_content(&options, wallet)?;
+            cached_drive_files(&options, &content, &drive_id, &query).await?
+        }
+    };
+
+    spinner.finish_and_clear();
+
+    if check_status {
+        check_tx_statuses(&options, &mut files).await?;
+
+        let (confirmed, pending, not_found) = count_statuses(&files);
+        format.status(format!(
+            "Transactions: {} confirmed, {} pending, {} not found",
+            confirmed, pending, not_found
+        ));
+    }
+
+    match format {
+        OutputFormat::Json => report!(
+            "{}",
+            serde_json::to_string_pretty(&files).context("Failed to format file list as JSON")?
+        ),
+        OutputFormat::Table if options.quiet => {
+            report!("Found {} files in drive {}", files.len(), drive_id)
+        }
+        OutputFormat::Table => print_files_table(&options.gateway, &files, &drive_id, query.offset),
+    }
+
+    if let Some(path) = output_path {
+        fs::write(
+            &path,
+            serde_json::to_string_pretty(&files).context("Failed to format file list as JSON")?,
+        )
+        .with_context(|| format!("Failed to write file list to {}", path.display()))?;
+        format.status(format!("✅ File list written to {}", path.display()));
+    }
+
+    Ok(files)
+}
+
+/// Lists the files of a public drive from Arweave, reusing the cached listing of the drive.
+pub(super) async fn cached_drive_files(
+    options: &ArdriveOptions,
+    wallet: &str,
+    drive_id: &str,
+    query: &FileQuery,
+) -> Result<Vec<ArDriveFile>> {
+    // the cached listings only have files
+    if query.entity_type != EntityType::File {
+        return arfs::list_drive_files(options, wallet, drive_id, query, None).await;
+    }
+
+    if let Some(files) = listing::load(options, drive_id) {
+        return Ok(query.apply(files));
+    }
+
+    // folder and paged listings stop early, only complete listings are cached
+    if query.parent_folder.is_some() || query.limit.is_some() {
+        return arfs::list_drive_files(options, wallet, drive_id, query, None).await;
+    }
+
+    let files =
+        arfs::list_drive_files(options, wallet, drive_id, &FileQuery::default(), None).await?;
+    listing::store(options, drive_id, &files);
+
+    Ok(query.apply(files))
+}
+
+fn print_files_table(gateway: &str, files: &[ArDriveFile], drive_id: &str, offset: usize) {
+    // Print summary and write output
+    report!("Found {} files in drive {}", files.len(), drive_id);
+
+    if !files.is_empty() {
+        // the status column is only shown when the statuses were checked
+        let checked = files.iter().any(|f| f.status.is_some());
+        let status_column = |status: &str| {
+            if checked {
+                format!(" | {:9}", status)
+            } else {
+                String::new()
+            }
+        };
+
+        report!("Detailed files:");
+        // Added an extra column for the Arweave link (derived from data tx or metadata tx)
+        report!(
+            "{:>3} | {:30} | {:>10}{} | {:>43} | {:>43} | {:64} | type",
+            "idx",
+            "path",
+            "size",
+            status_column("status"),
+            "data tx",
+            "meta tx",
+            "arweave"
+        );
+        report!(
+            "{:-<3} | {:-<30} | {:-<10}{} | {:-<43} | {:-<43} | {:-<64} | {:-<20}",
+            "",
+            "",
+            "",
+            status_column(&"-".repeat(9)),
+            "",
+            "",
+            "",
+            ""
+        );
+
+        for (i, f) in files.iter().enumerate() {
+            let name = f.name.as_deref().unwrap_or("<unnamed>");
+            // files listed before their folders were known only have a name
+            let path = f.path.as_deref().unwrap_or(name);
+            let size = f
+                .size
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| "?".to_string());
+            let data_tx = f.data_tx_id.as_deref().unwrap_or("");
+            let meta_tx = f.metadata_tx_id.as_deref().unwrap_or("");
+
+            // Derive an arweave URL from the preferred tx id (data tx preferred, then metadata)
+            let arweave_url = if !data_tx.is_empty() {
+                get_arweave_url(gateway, data_tx)
+            } else if !meta_tx.is_empty() {
+                get_arweave_url(gateway, meta_tx)
+            } else {
+                String::new()
+            };
+
+            // Detect content type from filename if not explicitly set
+            let ctype =
+                if let Some(ct) = f.content_type.as_deref().or(f.data_content_type.as_deref()) {
+                    ct.to_string()
+                } else {
+                    // Try to infer from extension
+                    if let Some(ext) = Path::new(name).extension().and_then(|e| e.to_str()) {
+                        match ext.to_lowercase().as_str() {
+                            "jpg" | "jpeg" => "image/jpeg",
+                            "png" => "image/png",
+                            "gif" => "image/gif",
+                            "json" => "application/json",
+                            _ => "application/octet-stream",
+                        }
+                        .to_string()
+                    } else {
+                        "application/octet-stream".to_string()
+                    }
+                };
+
+            // Format size with units if available
+            let size_fmt = if let Ok(size_num) = size.parse::<u64>() {
+                if size_num > 1024 * 1024 {
+                    format!("{:.1}M", size_num as f64 / (1024.0 * 1024.0))
+                } else if size_num > 1024 {
+                    format!("{:.1}K", size_num as f64 / 1024.0)
+                } else {
+                    format!("{}B", size_num)
+                }
+            } else {
+                size
+            };
+
+            report!(
+                "{:>3} | {:30} | {:>10}{} | {:>43} | {:>43} | {:64} | {}",
+                offset + i,
+                path,
+                size_fmt,
+                status_column(
+                    &f.status
+                        .map(|status| status.to_string())
+                        .unwrap_or_else(|| "-".to_string())
+                ),
+                if data_tx.is_empty() { "-" } else { data_tx },
+                if meta_tx.is_empty() { "-" } else { meta_tx },
+                arweave_url,
+                ctype
+            );
+        }
+    }
+}
+
+/// Lists the files of a drive using the ArDrive Node CLI (`--use-node-cli`): the local `ardrive`
+/// binary when given (see [`find_local_ardrive`]), otherwise the one on the PATH.
+fn node_list_drive_files(
+    options: &ArdriveOptions,
+    ardrive: Option<&Path>,
+    wallet: Option<PathBuf>,
+    drive_id: &str,
+    password: Option<&str>,
+) -> Result<Vec<ArDriveFile>> {
+    let content = resolve_ardrive_wallet_content(options, wallet)?;
 
-    drop(temp_file);
+    // Create a temporary file for the wallet (removed when dropped, even on errors)
+    let wallet_file = write_temp_wallet(&content)?;
+    let wallet_path = wallet_file.path();
 
     // Check for ardrive CLI and get its help output
-    let ardrive_local = find_local_ardrive();
-    let output = if let Some(ref ardrive_path) = ardrive_local {
+    let output = if let Some(ardrive_path) = ardrive {
         info!("Using local ardrive at: {}", ardrive_path.display());
 
         // Try getting help output first
@@ -730,19 +2348,23 @@ pub fn process_ardrive_list_drive_files(
         // Use the working `list-drive` command with --all to enumerate files
         cmd.arg("list-drive")
             .arg("-d")
-            .arg(&drive_id)
+            .arg(drive_id)
             .arg("--all")
             .arg("--wallet-file")
-            .arg(&temp_dir)
+            .arg(wallet_path)
             .env("NODE_ENV", "production");
 
         info!(
             "Executing: {} list-drive -d {} --all --wallet-file {}",
             ardrive_path.display(),
             drive_id,
-            temp_dir.display()
+            wallet_path.display()
         );
 
+        add_drive_password(&mut cmd, password);
+
+        add_gateway(options, &mut cmd);
+
         cmd.output().context("Failed to execute local ardrive")?
     } else {
         info!("Local ardrive not found, trying system ardrive");
@@ -759,26 +2381,25 @@ pub fn process_ardrive_list_drive_files(
         // Use `list-drive --all` to retrieve files for the drive
         cmd.arg("list-drive")
             .arg("-d")
-            .arg(&drive_id)
+            .arg(drive_id)
             .arg("--all")
             .arg("--wallet-file")
-            .arg(&temp_dir)
+            .arg(wallet_path)
             .env("NODE_ENV", "production");
 
         info!(
             "Executing: ardrive list-drive -d {} --all --wallet-file {}",
             drive_id,
-            temp_dir.display()
+            wallet_path.display()
         );
 
+        add_drive_password(&mut cmd, password);
+
+        add_gateway(options, &mut cmd);
+
         cmd.output().context("Failed to execute system ardrive")?
     };
 
-    // Clean up temp file
-    if let Err(e) = fs::remove_file(&temp_dir) {
-        info!("Note: Failed to clean up temporary wallet file: {}", e);
-    }
-
     // Process output
     let stdout = String::from_utf8_lossy(&output.stdout).to_string();
     let stderr = String::from_utf8_lossy(&output.stderr).to_string();
@@ -794,8 +2415,16 @@ pub fn process_ardrive_list_drive_files(
     }
 
     if !output.status.success() {
-        return Err(anyhow!(
-            "ArDrive CLI command failed.\n\
+        if is_password_error(password, &stderr) {
+            return Err(anyhow!("incorrect drive password for drive {}", drive_id));
+        }
+
+        return Err(cli_error(
+            &stderr,
+            ArDriveError::SubprocessFailed {
+                code: output.status.code(),
+                message: format!(
+                    "ArDrive CLI command failed.\n\
              Exit Code: {}\n\
              Command output:\n{}\n{}\n\n\
              Common fixes:\n\
@@ -803,22 +2432,27 @@ pub fn process_ardrive_list_drive_files(
              2. Check your wallet file\n\
              3. Verify drive ID: {}\n\
              4. Try: sugar ardrive show-wallet",
-            output.status.code().unwrap_or(-1),
-            stderr.trim(),
-            stdout.trim(),
-            drive_id
+                    output.status.code().unwrap_or(-1),
+                    stderr.trim(),
+                    stdout.trim(),
+                    drive_id
+                ),
+            }
+            .into(),
         ));
     }
 
     // Pre-process output
-    let clean_stdout = stdout.trim().trim_start_matches('\u{feff}');
+    let clean_stdout = strip_ansi_codes(&stdout);
+    let clean_stdout = clean_stdout.trim().trim_start_matches('\u{feff}');
 
     // First check for common error patterns
     if stdout.contains("Invalid entity ID") || stderr.contains("Invalid entity ID") {
-        return Err(anyhow!(
-            "Invalid drive ID '{}'. Please check that the drive ID exists and is accessible.",
-            drive_id
-        ));
+        return Err(ArDriveError::InvalidDriveId {
+            drive_id: drive_id.to_string(),
+            reason: "please check that the drive ID exists and is accessible".to_string(),
+        }
+        .into());
     } else if clean_stdout.is_empty() {
         return Err(anyhow!(
             "ArDrive CLI returned empty output.\n\
@@ -840,10 +2474,13 @@ pub fn process_ardrive_list_drive_files(
     // Try to detect common output patterns before parsing
     if clean_stdout.contains("Error:") || clean_stdout.contains("error:") {
         info!("Detected error message in output");
-        return Err(anyhow!("ArDrive CLI error in output:\n{}", clean_stdout));
+        return Err(anyhow!(
+            "ArDrive CLI error in output:\n{}",
+            output_excerpt(clean_stdout)
+        ));
     }
 
-    let val: Value = match serde_json::from_str(clean_stdout) {
+    let val: Value = match try_parse_json_flex(clean_stdout) {
         Ok(v) => {
             let pretty = serde_json::to_string_pretty(&v)
                 .unwrap_or_else(|_| "<failed to pretty print>".to_string());
@@ -866,32 +2503,34 @@ pub fn process_ardrive_list_drive_files(
             let context = {
                 let lines: Vec<_> = clean_stdout.lines().collect();
                 if let Some(problem_line) = lines.get(line_num.saturating_sub(1)) {
-                    format!(" (line {}: {})", line_num, problem_line)
+                    format!(" (line {}: {})", line_num, output_excerpt(problem_line))
                 } else {
                     String::new()
                 }
             };
 
-            return Err(anyhow!(
-                "ArDrive CLI returned invalid JSON{}.\n\
-                 Error: {}\n\
-                 Output: {}\n\n\
-                 This usually means:\n\
-                 1. The drive ID might be incorrect\n\
-                 2. There might be permission issues\n\
-                 3. The ArDrive CLI output format might have changed\n\
-                 Try: sugar ardrive show-wallet",
-                context,
-                e,
-                clean_stdout
-            ));
+            return Err(ArDriveError::ParseError {
+                message: format!(
+                    "ArDrive CLI returned invalid JSON{}.\n\
+                     Error: {}\n\
+                     Output: {}\n\n\
+                     This usually means:\n\
+                     1. The drive ID might be incorrect\n\
+                     2. There might be permission issues\n\
+                     3. The ArDrive CLI output format might have changed\n\
+                     Try: sugar ardrive show-wallet",
+                    context,
+                    e,
+                    output_excerpt(clean_stdout)
+                ),
+            }
+            .into());
         }
     };
 
     // Extract file list
     let items = extract_drives_from_value(&val).ok_or_else(|| {
-        let structure = serde_json::to_string_pretty(&val)
-            .unwrap_or_else(|_| "<cannot display JSON>".to_string());
+        let structure = output_excerpt(&val.to_string());
 
         anyhow!(
             "Unexpected response format.\n\
@@ -914,158 +2553,125 @@ pub fn process_ardrive_list_drive_files(
         files.push(file);
     }
 
-    // Apply extension filter if requested
-    if let Some(ext) = filter_ext {
-        files.retain(|f| {
-            f.name
-                .as_ref()
-                .map(|n| n.ends_with(&format!(".{}", ext)))
-                .unwrap_or(false)
-        });
-    }
+    // the listing has the folders of the drive
+    FolderPaths::new(&files).set_paths(&mut files);
 
-    // Print summary and write output
-    println!("Found {} files in drive {}", files.len(), drive_id);
+    Ok(files)
+}
 
-    println!("Detailed files:");
-    // Added an extra column for the Arweave link (derived from data tx or metadata tx)
-    println!(
-        "{:>3} | {:30} | {:>10} | {:>43} | {:>43} | {:64} | type",
-        "idx", "name", "size", "data tx", "meta tx", "arweave"
-    );
-    println!(
-        "{:-<3} | {:-<30} | {:-<10} | {:-<43} | {:-<43} | {:-<64} | {:-<20}",
-        "", "", "", "", "", "", ""
-    );
+/// Helper function to generate an Arweave URL (on the gateway) from a transaction ID
+pub fn get_arweave_url(gateway: &str, tx_id: &str) -> String {
+    format!("{}/{}", gateway, tx_id)
+}
 
-    for (i, f) in files.iter().enumerate() {
-        let name = f.name.as_deref().unwrap_or("<unnamed>");
-        let size = f
-            .size
-            .map(|s| s.to_string())
-            .unwrap_or_else(|| "?".to_string());
-        let data_tx = f.data_tx_id.as_deref().unwrap_or("");
-        let meta_tx = f.metadata_tx_id.as_deref().unwrap_or("");
-
-        // Derive an arweave URL from the preferred tx id (data tx preferred, then metadata)
-        let arweave_url = if !data_tx.is_empty() {
-            get_arweave_url(data_tx)
-        } else if !meta_tx.is_empty() {
-            get_arweave_url(meta_tx)
-        } else {
-            String::new()
-        };
+/// Returns the transaction ID of an Arweave transaction link (`<gateway>/<tx id>`).
+pub(super) fn arweave_tx_id(url: &reqwest::Url) -> Option<&str> {
+    let tx_id = url.path().trim_start_matches('/');
 
-        // Detect content type from filename if not explicitly set
-        let ctype = if let Some(ct) = f.content_type.as_deref().or(f.data_content_type.as_deref()) {
-            ct.to_string()
-        } else {
-            // Try to infer from extension
-            if let Some(ext) = Path::new(name).extension().and_then(|e| e.to_str()) {
-                match ext.to_lowercase().as_str() {
-                    "jpg" | "jpeg" => "image/jpeg",
-                    "png" => "image/png",
-                    "gif" => "image/gif",
-                    "json" => "application/json",
-                    _ => "application/octet-stream",
-                }
-                .to_string()
-            } else {
-                "application/octet-stream".to_string()
-            }
-        };
+    let is_tx_id = tx_id.len() == 43
+        && tx_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
 
-        // Format size with units if available
-        let size_fmt = if let Ok(size_num) = size.parse::<u64>() {
-            if size_num > 1024 * 1024 {
-                format!("{:.1}M", size_num as f64 / (1024.0 * 1024.0))
-            } else if size_num > 1024 {
-                format!("{:.1}K", size_num as f64 / 1024.0)
-            } else {
-                format!("{}B", size_num)
-            }
-        } else {
-            size
-        };
+    is_tx_id.then_some(tx_id)
+}
 
-        println!(
-            "{:>3} | {:30} | {:>10} | {:>43} | {:>43} | {:64} | {}",
-            i,
-            name,
-            size_fmt,
-            if data_tx.is_empty() { "-" } else { data_tx },
-            if meta_tx.is_empty() { "-" } else { meta_tx },
-            arweave_url,
-            ctype
-        );
-    }
+/// Returns the link of an Arweave transaction moved to the gateway, or `None` if the link is not
+/// a transaction link (`<gateway>/<tx id>`) or already uses the gateway.
+fn rewrite_gateway_link(gateway: &str, link: &str) -> Option<String> {
+    let url = reqwest::Url::parse(link).ok()?;
+    let tx_id = arweave_tx_id(&url)?;
 
-    if let Some(path) = output_path {
-        fs::write(
-            &path,
-            serde_json::to_string_pretty(&files).context("Failed to format file list as JSON")?,
-        )
-        .with_context(|| format!("Failed to write file list to {}", path.display()))?;
-        println!("✅ File list written to {}", path.display());
+    let rewritten = match url.query() {
+        Some(query) => format!("{}?{}", get_arweave_url(gateway, tx_id), query),
+        None => get_arweave_url(gateway, tx_id),
+    };
+
+    (rewritten != link).then_some(rewritten)
+}
+
+/// Moves the Arweave links of the cache items to the gateway, returning the number of updated
+/// items.
+fn rewrite_cache_gateway(gateway: &str, cache: &mut crate::cache::Cache) -> usize {
+    let mut updated = 0;
+
+    for item in cache.items.values_mut() {
+        let mut changed = false;
+
+        for link in [&mut item.image_link, &mut item.metadata_link]
+            .into_iter()
+            .chain(item.animation_link.as_mut())
+        {
+            if let Some(rewritten) = rewrite_gateway_link(gateway, link) {
+                *link = rewritten;
+                changed = true;
+            }
+        }
+
+        if changed {
+            updated += 1;
+        }
     }
 
-    Ok(files)
+    updated
 }
 
-/// Helper function to generate an Arweave URL from a transaction ID
-pub fn get_arweave_url(tx_id: &str) -> String {
-    format!("https://arweave.net/{}", tx_id)
+/// Redacted view of a wallet: the top-level keys and the Arweave address, never a key value.
+fn redacted_wallet(content: &str) -> Vec<String> {
+    let keys = match serde_json::from_str::<Value>(content) {
+        Ok(serde_json::Value::Object(map)) => format!(
+            "Top-level keys ({}): {}",
+            map.len(),
+            map.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+        _ => "(wallet content is not a JSON object)".to_string(),
+    };
+
+    let address = match arfs::wallet_address(content) {
+        Ok(address) => format!("Arweave address: {}", address),
+        Err(e) => format!("Arweave address: unavailable ({})", e),
+    };
+
+    vec![
+        keys,
+        address,
+        "Private key redacted, use --unsafe-full to print the full wallet.".to_string(),
+    ]
 }
 
-pub fn process_ardrive_show_wallet(wallet: Option<PathBuf>) -> Result<()> {
+/// Shows the ArDrive wallet in use. When `redact` is set, only the top-level keys, the size and
+/// the Arweave address of the wallet are printed; otherwise the full JSON (private key included).
+pub fn process_ardrive_show_wallet(
+    wallet: Option<PathBuf>,
+    redact: bool,
+    options: &ArdriveOptions,
+) -> Result<()> {
     info!(
-        "ArDrive: show-wallet called (wallet override: {:?})",
-        wallet
+        "ArDrive: show-wallet called (wallet override: {:?}, redact: {})",
+        wallet, redact
     );
 
-    // Resolve source (prefer explicit path, then env var, then stored file)
-    let mut source = String::new();
-    let content: String;
+    // Resolve source (prefer explicit path, then env var, then profile, then stored file)
+    let (content, source) = resolve_ardrive_wallet(options, wallet)?;
 
-    if let Some(p) = wallet {
-        content = fs::read_to_string(&p)
-            .map_err(|e| anyhow::anyhow!("Failed reading wallet file {}: {}", p.display(), e))?;
-        source = format!("file: {}", p.display());
-    } else if let Ok(env_val) = std::env::var("ARDRIVE_WALLET") {
-        if !env_val.trim().is_empty() {
-            content = env_val;
-            source = "environment variable ARDRIVE_WALLET".to_string();
-        } else {
-            content = String::new();
-        }
-    } else if let Ok(home) = std::env::var("HOME") {
-        let mut cfg = PathBuf::from(home);
-        cfg.push(".config");
-        cfg.push("sugar-cli");
-        cfg.push("ardrive_wallet.json");
+    report!("ArDrive wallet source: {}", source);
+    report!("Wallet size: {} bytes", content.len());
 
-        if cfg.exists() {
-            content = fs::read_to_string(&cfg).map_err(|e| {
-                anyhow::anyhow!("Failed reading stored wallet {}: {}", cfg.display(), e)
-            })?;
-            source = format!("stored file: {}", cfg.display());
-        } else {
-            return Err(anyhow::anyhow!("No ardrive wallet provided: pass -w/--wallet, set ARDRIVE_WALLET env var, or run 'sugar ardrive set-wallet <file>' to store one."));
+    if redact {
+        for line in redacted_wallet(&content) {
+            report!("{}", line);
         }
-    } else {
-        return Err(anyhow::anyhow!("No ardrive wallet provided: pass -w/--wallet, set ARDRIVE_WALLET env var, or run 'sugar ardrive set-wallet <file>' to store one."));
+        return Ok(());
     }
 
-    println!("ArDrive wallet source: {}", source);
-    println!("Wallet size: {} bytes", content.len());
-    println!("WARNING: wallet contains private keys — do not share output publicly.");
+    report!("WARNING: wallet contains private keys — do not share output publicly.");
 
     // Try to parse JSON and pretty-print
     match serde_json::from_str::<Value>(&content) {
         Ok(val) => {
             // If it's an object, list top-level keys
             if let serde_json::Value::Object(map) = &val {
-                println!(
+                report!(
                     "Top-level keys ({}): {}",
                     map.len(),
                     map.keys().cloned().collect::<Vec<_>>().join(", ")
@@ -1073,15 +2679,16 @@ pub fn process_ardrive_show_wallet(wallet: Option<PathBuf>) -> Result<()> {
             }
 
             match serde_json::to_string_pretty(&val) {
-                Ok(pretty) => println!("\n{}", pretty),
-                Err(e) => println!(
+                Ok(pretty) => report!("\n{}", pretty),
+                Err(e) => report!(
                     "(failed to pretty-print JSON: {})\nRaw contents:\n{}",
-                    e, content
+                    e,
+                    content
                 ),
             }
         }
         Err(_) => {
-            println!(
+            report!(
                 "(wallet content is not valid JSON)\nRaw contents:\n{}",
                 content
             );
@@ -1091,98 +2698,772 @@ pub fn process_ardrive_show_wallet(wallet: Option<PathBuf>) -> Result<()> {
     Ok(())
 }
 
-/// Generate a candy-machine-style cache file from files stored in an ArDrive drive.
-/// - `wallet` optional wallet override
-/// - `drive_id` the drive to list
-/// - `cache_file` path to write the cache JSON
-/// - `candy_machine` optional candy machine pubkey (will populate program.candyMachine)
-pub fn process_ardrive_generate_cache(
-    wallet: Option<PathBuf>,
-    drive_id: String,
-    cache_file: PathBuf,
-    candy_machine: Option<String>,
-) -> Result<()> {
-    use std::str::FromStr;
+/// Where the files of a generated cache come from.
+pub enum CacheSource {
+    /// List the files of the drive (using the ArDrive Node CLI when `use_node_cli` is set).
+    Drive {
+        drive_id: String,
+        /// Only list the files of this folder.
+        parent_folder: Option<String>,
+        /// Only list the files under these folder paths (e.g. `images`).
+        folder_paths: Vec<String>,
+        use_node_cli: bool,
+    },
+    /// Read the files from a file list, as written by `list-drive-files --output` or
+    /// `upload --output`.
+    FileList(PathBuf),
+}
 
-    use anchor_client::solana_sdk::pubkey::Pubkey;
+pub struct ArdriveGenerateCacheArgs {
+    /// Optional wallet override.
+    pub wallet: Option<PathBuf>,
+    /// The drive to list or the file list to read.
+    pub source: CacheSource,
+    /// Path to write the cache JSON to.
+    pub cache_file: PathBuf,
+    /// Optional candy machine pubkey (will populate program.candyMachine).
+    pub candy_machine: Option<String>,
+    /// Replace an existing cache file instead of appending to it (asks for confirmation).
+    pub overwrite: bool,
+    /// Only include files modified at or after this unix time (seconds).
+    pub since: Option<u64>,
+    /// Move the links of the existing items to the configured gateway.
+    pub rewrite_gateway: bool,
+    /// Use the transaction IDs as item hashes instead of downloading the files to hash them.
+    pub skip_hash: bool,
+    /// Check that the data transactions are confirmed, refusing transactions not found.
+    pub check_status: bool,
+    /// Write the cache even if some transactions are not found.
+    pub allow_pending: bool,
+    pub options: ArdriveOptions,
+}
 
-    use crate::cache::{Cache, CacheItem, CacheProgram};
+/// Generate a candy-machine-style cache file from files stored in an ArDrive drive.
+///
+/// Media and metadata files sharing a basename (e.g. `0.png` + `0.json`) form a single cache
+/// item, named after the name in the metadata. When the cache file already exists (and
+/// `overwrite` is not set), existing items and the program section are kept and only pairs
+/// whose links are not in the cache are appended. The hashes of the new items are the SHA-256
+/// of the downloaded files, as written by `sugar upload`, unless `skip_hash` is set.
+pub async fn process_ardrive_generate_cache(args: ArdriveGenerateCacheArgs) -> Result<()> {
+    use anchor_client::solana_sdk::pubkey::Pubkey;
 
-    info!(
-        "Generating cache for drive {} -> {}",
-        drive_id,
-        cache_file.display()
-    );
+    use crate::cache::{load_cache, Cache, CacheProgram};
+
+    let ArdriveGenerateCacheArgs {
+        wallet,
+        source,
+        cache_file,
+        candy_machine,
+        overwrite,
+        since,
+        rewrite_gateway,
+        skip_hash,
+        check_status,
+        allow_pending,
+        options,
+    } = args;
+
+    info!("Generating cache -> {}", cache_file.display());
+
+    let exists = cache_file.exists();
+
+    if exists
+        && overwrite
+        && !confirm(
+            Confirmation::new(format!(
+                "Overwrite the existing cache file {}? All of its items and program data will be lost.",
+                cache_file.display()
+            ))
+            .default(false),
+        )?
+    {
+        return Err(anyhow!("Cache generation aborted."));
+    }
 
-    // Reuse the existing listing function to fetch files
-    let files = process_ardrive_list_drive_files(wallet, drive_id, None, None)
-        .context("Failed to list drive files for cache generation")?;
+    let mut cache = if exists && !overwrite {
+        load_cache(&cache_file.to_string_lossy(), false)?
+    } else {
+        Cache::new()
+    };
 
-    let mut cache = Cache::new();
-    // If candy_machine provided, try to set program data
+    // If candy_machine provided, try to set program data (an existing program section is kept)
     if let Some(cm) = candy_machine {
         if let Ok(pk) = Pubkey::from_str(&cm) {
-            cache.program = CacheProgram::new_from_cm(&pk);
+            if cache.program.candy_machine.is_empty() {
+                cache.program = CacheProgram::new_from_cm(&pk);
+            } else if cache.program.candy_machine != cm {
+                report!(
+                    "Cache already refers to candy machine {}, ignoring {}",
+                    cache.program.candy_machine,
+                    cm
+                );
+            }
         } else {
             info!("Invalid candy machine pubkey provided, leaving program defaults");
         }
     }
 
-    // Fill items: key by numeric index (0-based as string) to be compatible with typical caches
-    for (i, f) in files.iter().enumerate() {
-        let key = i.to_string();
-        let name = f.name.clone().unwrap_or_else(|| key.clone());
-        let image_hash = f.data_tx_id.clone().unwrap_or_default();
-        let image_link = if !image_hash.is_empty() {
-            get_arweave_url(&image_hash)
+    let mut files = match source {
+        // Reuse the existing listing function to fetch files
+        CacheSource::Drive {
+            drive_id,
+            parent_folder,
+            folder_paths,
+            use_node_cli,
+        } => process_ardrive_list_drive_files(ArdriveListDriveFilesArgs {
+            wallet,
+            drive_id,
+            output_path: None,
+            query: FileQuery {
+                parent_folder,
+                folder_paths,
+                ..FileQuery::default()
+            },
+            drive_password: None,
+            format: OutputFormat::Table,
+            use_node_cli,
+            check_status: false,
+            options: options.clone(),
+        })
+        .await
+        .context("Failed to list drive files for cache generation")?,
+        CacheSource::FileList(path) => {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read file list {}", path.display()))?;
+            serde_json::from_str::<Vec<ArDriveFile>>(&content)
+                .with_context(|| format!("Invalid file list {}", path.display()))?
+        }
+    };
+
+    // the links of the existing items keep their gateway unless asked otherwise
+    if rewrite_gateway {
+        let updated = rewrite_cache_gateway(&options.gateway, &mut cache);
+        print_detail(
+            &options,
+            format!(
+                "Moved the links of {} cache item(s) to {}",
+                updated, options.gateway
+            ),
+        );
+    }
+
+    if check_status {
+        check_tx_statuses(&options, &mut files).await?;
+        check_cache_statuses(&options, &files, allow_pending)?;
+    }
+
+    let existing = cache.items.len();
+    let added = append_drive_files(&options.gateway, &mut cache, &files, since)?;
+    fetch_item_details(&options, &mut cache, &added, &files, !skip_hash).await?;
+
+    let report = cache.items.check_indices();
+    if !report.is_contiguous() {
+        report!(
+            "{} The cache items are not keyed 0..n ({}): fill the missing indices with \
+            'sugar import --append --start-index <INDEX>' before running 'deploy'.",
+            WARNING_EMOJI,
+            report
+        );
+    }
+
+    cache.file_path = cache_file.to_string_lossy().to_string();
+    // avoid borrowing `cache` immutably while calling a mutable method
+    let out_path = cache.file_path.clone();
+    cache
+        .write_to_file(&out_path)
+        .with_context(|| format!("Failed to write cache to {}", cache_file.display()))?;
+
+    report!(
+        "✅ Wrote cache to {} ({} existing, {} added)",
+        cache_file.display(),
+        existing,
+        added.len()
+    );
+    Ok(())
+}
+
+/// Reports the pending transactions and fails on the transactions not found, unless
+/// `allow_pending` is set.
+fn check_cache_statuses(
+    options: &ArdriveOptions,
+    files: &[ArDriveFile],
+    allow_pending: bool,
+) -> Result<()> {
+    let (confirmed, pending, not_found) = count_statuses(files);
+    print_detail(
+        options,
+        format!(
+            "Transactions: {} confirmed, {} pending, {} not found",
+            confirmed, pending, not_found
+        ),
+    );
+
+    let names = |status: TxStatus| -> Vec<String> {
+        files
+            .iter()
+            .filter(|f| f.status == Some(status))
+            .map(|f| {
+                f.path
+                    .clone()
+                    .or_else(|| f.name.clone())
+                    .unwrap_or_default()
+            })
+            .collect()
+    };
+
+    if pending > 0 {
+        report!(
+            "{}{}",
+            WARNING_EMOJI,
+            style(format!(
+                "Pending transactions (mints will fail until they are confirmed): {}",
+                names(TxStatus::Pending).join(", ")
+            ))
+            .yellow()
+        );
+    }
+
+    if not_found > 0 {
+        let message = format!(
+            "Transactions not found: {}",
+            names(TxStatus::NotFound).join(", ")
+        );
+        if !allow_pending {
+            return Err(anyhow!(
+                "{}\nRe-upload the files or pass --allow-pending to write the cache anyway",
+                message
+            ));
+        }
+        report!("{}{}", WARNING_EMOJI, style(message).yellow());
+    }
+
+    Ok(())
+}
+
+/// Pair the image and metadata files that share a numeric basename (e.g. `0.png` + `0.json`,
+/// or `collection.png` + `collection.json` for the collection item) and append the pairs whose
+/// links are not already in the cache, returning the keys of the items added. Fails listing the
+/// orphaned files (an image without its JSON or vice versa). New items are keyed by numeric index
+/// after the highest existing one and named after the file stem; the collection files always
+/// replace the `-1` item, unless it is deployed.
+fn append_drive_files(
+    gateway: &str,
+    cache: &mut crate::cache::Cache,
+    files: &[ArDriveFile],
+    since: Option<u64>,
+) -> Result<Vec<String>> {
+    use std::collections::{BTreeMap, HashSet};
+
+    use crate::cache::CacheItem;
+
+    // stem -> (media, metadata)
+    let mut pairs: BTreeMap<String, (Option<&ArDriveFile>, Option<&ArDriveFile>)> = BTreeMap::new();
+
+    for f in files {
+        let name = match &f.name {
+            Some(name) => basename(name),
+            None => continue,
+        };
+        let path = Path::new(&name);
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| name.clone());
+
+        if stem != "collection" && stem.parse::<u64>().is_err() {
+            info!("Ignoring '{}': not an asset file", name);
+            continue;
+        }
+
+        let is_json = path
+            .extension()
+            .map(|ext| ext.eq_ignore_ascii_case("json"))
+            .unwrap_or(false);
+
+        let pair = pairs.entry(stem).or_default();
+        if is_json {
+            pair.1.get_or_insert(f);
         } else {
-            String::new()
+            pair.0.get_or_insert(f);
+        }
+    }
+
+    let mut links: HashSet<String> = cache
+        .items
+        .values()
+        .flat_map(|item| [item.image_link.clone(), item.metadata_link.clone()])
+        .filter(|link| !link.is_empty())
+        .collect();
+
+    let mut next_index = cache
+        .items
+        .keys()
+        .filter_map(|key| key.parse::<u64>().ok())
+        .max()
+        .map_or(0, |index| index + 1);
+
+    let orphans: Vec<String> = pairs
+        .iter()
+        .filter_map(|(stem, pair)| match pair {
+            (Some(media), None) => Some(format!(
+                "{} (missing {}.json)",
+                media.name.as_deref().unwrap_or_default(),
+                stem
+            )),
+            (None, Some(metadata)) => Some(format!(
+                "{} (missing image)",
+                metadata.name.as_deref().unwrap_or_default()
+            )),
+            _ => None,
+        })
+        .collect();
+
+    if !orphans.is_empty() {
+        return Err(anyhow!(
+            "Found {} file(s) without a matching image or metadata file:\n  {}",
+            orphans.len(),
+            orphans.join("\n  ")
+        ));
+    }
+
+    // numeric stems in numeric order, then the collection
+    let mut stems: Vec<&String> = pairs.keys().collect();
+    stems.sort_by_key(|stem| stem.parse::<u64>().unwrap_or(u64::MAX));
+
+    let mut added = Vec::new();
+
+    for stem in stems {
+        let (media, metadata) = match pairs[stem] {
+            (Some(media), Some(metadata)) => (media, metadata),
+            _ => continue,
         };
-        let metadata_hash = f.metadata_tx_id.clone().unwrap_or_default();
-        let metadata_link = if !metadata_hash.is_empty() {
-            get_arweave_url(&metadata_hash)
+
+        if let Some(since) = since {
+            let modified = media.last_modified_date.max(metadata.last_modified_date);
+            if matches!(modified, Some(modified) if modified_seconds(modified) < since) {
+                continue;
+            }
+        }
+
+        let image_hash = media.data_tx_id.clone().unwrap_or_default();
+        let metadata_hash = metadata.data_tx_id.clone().unwrap_or_default();
+
+        if image_hash.is_empty() || metadata_hash.is_empty() {
+            info!("Skipping '{}': missing data transaction", stem);
+            continue;
+        }
+
+        let image_link = get_arweave_url(gateway, &image_hash);
+        let metadata_link = get_arweave_url(gateway, &metadata_hash);
+
+        if links.contains(&image_link) || links.contains(&metadata_link) {
+            continue;
+        }
+        links.insert(image_link.clone());
+        links.insert(metadata_link.clone());
+
+        // the collection is always the -1 item, replaced until it is deployed
+        let key = if stem == "collection" {
+            if cache.items.get("-1").map_or(false, |item| item.on_chain) {
+                info!("Skipping '{}': the collection item (-1) is deployed", stem);
+                continue;
+            }
+            "-1".to_string()
         } else {
-            String::new()
+            next_index += 1;
+            (next_index - 1).to_string()
         };
 
         let item = CacheItem {
-            name: name.clone(),
-            image_hash: image_hash.clone(),
+            name: stem.clone(),
+            image_hash,
             image_link,
-            metadata_hash: metadata_hash.clone(),
+            metadata_hash,
             metadata_link,
             on_chain: false,
             animation_hash: None,
             animation_link: None,
+            optimized_image_hash: None,
         };
 
-        cache.items.insert(key, item);
+        cache.items.insert(key.clone(), item);
+        added.push(key);
     }
 
-    cache.file_path = cache_file.to_string_lossy().to_string();
-    // avoid borrowing `cache` immutably while calling a mutable method
-    let out_path = cache.file_path.clone();
-    cache
-        .write_to_file(&out_path)
-        .with_context(|| format!("Failed to write cache to {}", cache_file.display()))?;
+    Ok(added)
+}
+
+/// Replace the (file stem) name of the items with the name in their metadata and, when `hash`
+/// is set, their hashes with the SHA-256 of the image and metadata files. Downloads shorter
+/// than the size of the drive file are retried. Items whose metadata cannot be downloaded keep
+/// their name, but every file must be downloaded when hashing.
+async fn fetch_item_details(
+    options: &ArdriveOptions,
+    cache: &mut crate::cache::Cache,
+    keys: &[String],
+    files: &[ArDriveFile],
+    hash: bool,
+) -> Result<()> {
+    use futures::{stream, StreamExt};
+
+    // sizes of the drive files by link
+    let sizes: HashMap<String, u64> = files
+        .iter()
+        .filter_map(|file| {
+            Some((
+                get_arweave_url(&options.gateway, file.data_tx_id.as_deref()?),
+                file.size?,
+            ))
+        })
+        .collect();
+
+    let client = arfs::ArweaveClient::new(options);
+    let pb = files_progress_bar(options, keys.len() as u64);
+    pb.set_message(if hash {
+        "Hashing items "
+    } else {
+        "Reading item names "
+    });
+
+    let details: Vec<(String, Result<ItemDetails>)> = stream::iter(keys)
+        .map(|key| {
+            let client = &client;
+            let sizes = &sizes;
+            let pb = &pb;
+            let item = &cache.items[key];
+            let (image_link, metadata_link) = (item.image_link.clone(), item.metadata_link.clone());
+            async move {
+                let details = fetch_details(client, &image_link, &metadata_link, sizes, hash).await;
+                pb.inc(1);
+                (key.clone(), details)
+            }
+        })
+        .buffered(10)
+        .collect()
+        .await;
+
+    pb.finish_and_clear();
+
+    let mut failed = Vec::new();
+
+    for (key, details) in details {
+        let item = match cache.items.get_mut(&key) {
+            Some(item) => item,
+            None => continue,
+        };
+
+        match details {
+            Ok(details) => {
+                if let Some(hashes) = details.hashes {
+                    (item.image_hash, item.metadata_hash) = hashes;
+                }
+                match details.name {
+                    Some(name) => item.name = name,
+                    None => print_detail(
+                        options,
+                        format!(
+                            "Could not read the name from the metadata of item {}, using '{}'",
+                            key, item.name
+                        ),
+                    ),
+                }
+            }
+            Err(err) if hash => failed.push(format!("{}: {:#}", key, err)),
+            Err(err) => {
+                info!("Failed to download the metadata of item {}: {:#}", key, err);
+                print_detail(
+                    options,
+                    format!(
+                        "Could not read the name from the metadata of item {}, using '{}'",
+                        key, item.name
+                    ),
+                );
+            }
+        }
+    }
+
+    if !failed.is_empty() {
+        for failure in &failed {
+            print_detail(options, format!("  {}", failure));
+        }
+        return Err(anyhow!(
+            "Failed to hash {} item(s), re-run the command to retry or use --skip-hash",
+            failed.len()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Name (from the metadata) and hashes (image, metadata) of a cache item.
+struct ItemDetails {
+    name: Option<String>,
+    hashes: Option<(String, String)>,
+}
+
+async fn fetch_details(
+    client: &arfs::ArweaveClient,
+    image_link: &str,
+    metadata_link: &str,
+    sizes: &HashMap<String, u64>,
+    hash: bool,
+) -> Result<ItemDetails> {
+    let metadata = arfs::get_file_bytes(
+        client,
+        metadata_link,
+        metadata_link,
+        sizes.get(metadata_link).copied(),
+    )
+    .await?;
+    let name = serde_json::from_slice::<Value>(&metadata)
+        .ok()
+        .and_then(|metadata| metadata["name"].as_str().map(String::from));
+
+    let hashes = if hash {
+        let image = arfs::get_file_bytes(
+            client,
+            image_link,
+            image_link,
+            sizes.get(image_link).copied(),
+        )
+        .await?;
+        Some((sha256_hex(&image), sha256_hex(&metadata)))
+    } else {
+        None
+    };
+
+    Ok(ItemDetails { name, hashes })
+}
+
+/// Returns the SHA-256 (hex) of the data, as the hashes of the items written by `sugar upload`.
+fn sha256_hex(data: &[u8]) -> String {
+    HEXLOWER.encode(&Sha256::digest(data))
+}
+
+fn basename(name: &str) -> String {
+    Path::new(name)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| name.to_string())
+}
+
+/// ArDrive reports `lastModifiedDate` in milliseconds; values that are too small to be
+/// milliseconds are taken as seconds.
+fn modified_seconds(last_modified_date: u64) -> u64 {
+    if last_modified_date >= 100_000_000_000 {
+        last_modified_date / 1000
+    } else {
+        last_modified_date
+    }
+}
+
+/// Size of the upload priced with the `/price` endpoint to estimate the storage a balance covers.
+const PRICE_REFERENCE_BYTES: u64 = 1024 * 1024;
 
-    println!(
-        "✅ Wrote cache with {} items to {}",
-        cache.items.len(),
-        cache_file.display()
+/// Shows the Arweave address of the ArDrive wallet, its AR balance and the estimated number of
+/// bytes the balance can pay for at the current storage price.
+pub async fn process_ardrive_balance(
+    wallet: Option<PathBuf>,
+    options: &ArdriveOptions,
+) -> Result<()> {
+    let content = resolve_ardrive_wallet_content(options, wallet)?;
+    let address = arfs::wallet_address(&content)?;
+    info!("ArDrive: balance called for {}", address);
+
+    let balance = arfs::wallet_balance(options, &address).await?;
+    let price = arfs::storage_price(options, PRICE_REFERENCE_BYTES).await?;
+
+    report!("Arweave address: {}", address);
+    report!(
+        "Balance: {} winston ({} AR)",
+        balance,
+        arfs::format_ar(balance)
     );
+    report!(
+        "Storage price: {} AR per {}",
+        arfs::format_ar(price),
+        HumanBytes(PRICE_REFERENCE_BYTES)
+    );
+    report!(
+        "Estimated storage: ~{}",
+        HumanBytes(covered_bytes(balance, price, PRICE_REFERENCE_BYTES))
+    );
+
     Ok(())
 }
 
+/// Number of bytes `balance` pays for when `reference_bytes` cost `price` winston.
+fn covered_bytes(balance: u128, price: u128, reference_bytes: u64) -> u64 {
+    if price == 0 {
+        return u64::MAX;
+    }
+
+    let bytes = balance.saturating_mul(reference_bytes as u128) / price;
+    u64::try_from(bytes).unwrap_or(u64::MAX)
+}
+
 /// Wipe (delete) an existing cache file. Useful when switching Candy Machines.
 pub fn process_ardrive_wipe_cache(cache_file: PathBuf) -> Result<()> {
     if cache_file.exists() {
         fs::remove_file(&cache_file)
             .with_context(|| format!("Failed to remove cache file {}", cache_file.display()))?;
-        println!("✅ Removed cache file {}", cache_file.display());
+        report!("✅ Removed cache file {}", cache_file.display());
     } else {
-        println!("No cache file found at {}", cache_file.display());
+        report!("No cache file found at {}", cache_file.display());
+    }
+
+    Ok(())
+}
+
+/// Default number of parallel downloads of `ardrive download`.
+pub const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 10;
+
+#[derive(Debug, PartialEq, Eq)]
+enum DownloadOutcome {
+    Downloaded,
+    Skipped,
+}
+
+/// Download the files of a drive to `output_dir`, writing each file under its original name.
+/// Files that already exist with the reported size are skipped unless `force` is set.
+#[allow(clippy::too_many_arguments)]
+pub async fn process_ardrive_download(
+    wallet: Option<PathBuf>,
+    drive_id: String,
+    output_dir: PathBuf,
+    filter: Option<String>,
+    concurrency: usize,
+    force: bool,
+    use_node_cli: bool,
+    options: &ArdriveOptions,
+) -> Result<()> {
+    use futures::{stream, StreamExt};
+
+    let query = FileQuery {
+        extension: filter,
+        ..FileQuery::default()
+    };
+    let files = process_ardrive_list_drive_files(ArdriveListDriveFilesArgs {
+        wallet,
+        drive_id,
+        output_path: None,
+        query,
+        drive_password: None,
+        format: OutputFormat::Table,
+        use_node_cli,
+        check_status: false,
+        options: options.clone(),
+    })
+    .await
+    .context("Failed to list drive files for download")?;
+
+    fs::create_dir_all(&output_dir)
+        .with_context(|| format!("Failed to create output directory {}", output_dir.display()))?;
+
+    let client = arfs::ArweaveClient::new(options);
+    let pb = files_progress_bar(options, files.len() as u64);
+
+    let results: Vec<(String, Result<DownloadOutcome>)> = stream::iter(&files)
+        .map(|file| {
+            let client = &client;
+            let output_dir = &output_dir;
+            let pb = &pb;
+            async move {
+                let name = file
+                    .name
+                    .as_deref()
+                    .map(basename)
+                    .unwrap_or_else(|| "<unnamed>".to_string());
+                let result = download_drive_file(client, output_dir, file, force).await;
+                pb.inc(1);
+                (name, result)
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+
+    pb.finish_and_clear();
+
+    let mut downloaded = 0;
+    let mut skipped = 0;
+    let mut failed = Vec::new();
+
+    for (name, result) in results {
+        match result {
+            Ok(DownloadOutcome::Downloaded) => downloaded += 1,
+            Ok(DownloadOutcome::Skipped) => skipped += 1,
+            Err(err) => failed.push(format!("{}: {}", name, err)),
+        }
+    }
+
+    report!(
+        "Downloaded {} files to {} ({} skipped, {} failed)",
+        downloaded,
+        output_dir.display(),
+        skipped,
+        failed.len()
+    );
+
+    if !failed.is_empty() {
+        for failure in &failed {
+            print_detail(options, format!("  {}", failure));
+        }
+        return Err(anyhow!(
+            "Failed to download {} file(s), re-run the command to retry.",
+            failed.len()
+        ));
     }
 
     Ok(())
 }
+
+/// Download the data of a drive file, checking its length against the reported size (truncated
+/// responses are retried). The data
+/// is written to a temporary file that is renamed once complete, so an interrupted download is
+/// never mistaken for a finished one.
+async fn download_drive_file(
+    client: &arfs::ArweaveClient,
+    output_dir: &Path,
+    file: &ArDriveFile,
+    force: bool,
+) -> Result<DownloadOutcome> {
+    let name = match file.name.as_deref().map(basename) {
+        Some(name) if !name.is_empty() && name != "." && name != ".." => name,
+        _ => return Err(anyhow!("file has no name")),
+    };
+    let data_tx = file
+        .data_tx_id
+        .as_deref()
+        .ok_or_else(|| anyhow!("file has no data transaction"))?;
+
+    let path = output_dir.join(&name);
+
+    if !force && is_downloaded(&path, file.size) {
+        return Ok(DownloadOutcome::Skipped);
+    }
+
+    let url = get_arweave_url(&client.options.gateway, data_tx);
+    let data = arfs::get_file_bytes(client, &url, &url, file.size).await?;
+    info!(
+        "Downloaded {} ({} bytes, SHA-256 {})",
+        name,
+        data.len(),
+        sha256_hex(&data)
+    );
+
+    let partial = output_dir.join(format!(".{}.part", name));
+    fs::write(&partial, &data).with_context(|| format!("Failed to write {}", partial.display()))?;
+    fs::rename(&partial, &path).with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(DownloadOutcome::Downloaded)
+}
+
+/// Returns `true` if `path` exists with the expected size (any size when it is not known).
+fn is_downloaded(path: &Path, size: Option<u64>) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => size.map(|s| s == metadata.len()).unwrap_or(true),
+        _ => false,
+    }
+}
@@ -0,0 +1,155 @@
+//! Named ArDrive wallet profiles, stored in `~/.config/sugar-cli/wallets/<name>.json`.
+//!
+//! The profile selected with `--profile` is written by `set-wallet` and used by the other
+//! ardrive commands when no wallet file is passed and `ARDRIVE_WALLET` is not set.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Result};
+use tracing::info;
+
+use super::{arfs, ArdriveOptions};
+
+/// Returns the wallet profile selected with `--profile`, failing on invalid names.
+pub fn resolve_wallet_profile(profile: Option<String>) -> Result<Option<String>> {
+    if let Some(name) = &profile {
+        validate_profile_name(name)?;
+        info!("Using ArDrive wallet profile {}", name);
+    }
+
+    Ok(profile)
+}
+
+/// Returns the path of the wallet stored without a profile.
+pub fn default_wallet_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("ardrive_wallet.json"))
+}
+
+/// Returns the path of the wallet of a profile.
+pub fn profile_wallet_path(name: &str) -> Option<PathBuf> {
+    profiles_dir().map(|dir| dir.join(format!("{name}.json")))
+}
+
+fn config_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config").join("sugar-cli"))
+}
+
+fn profiles_dir() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("wallets"))
+}
+
+fn validate_profile_name(name: &str) -> Result<()> {
+    // profile names end up in a path
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(anyhow!(
+            "Invalid wallet profile '{}': use letters, digits, '-' and '_' only",
+            name
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the profiles stored in the directory (name and wallet file), sorted by name.
+fn read_profiles(dir: &Path) -> Vec<(String, PathBuf)> {
+    let mut profiles: Vec<(String, PathBuf)> = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && path.extension().map_or(false, |ext| ext == "json"))
+        .filter_map(|path| {
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            validate_profile_name(&name).ok()?;
+            Some((name, path))
+        })
+        .collect();
+
+    profiles.sort();
+    profiles
+}
+
+/// Lists the stored wallets (the default wallet and the profiles) with their Arweave address.
+pub fn process_ardrive_list_wallets(options: &ArdriveOptions) -> Result<()> {
+    info!("ArDrive: list-wallets called");
+
+    let mut wallets: Vec<(String, PathBuf)> = default_wallet_path()
+        .filter(|path| path.exists())
+        .map(|path| ("(default)".to_string(), path))
+        .into_iter()
+        .collect();
+    if let Some(dir) = profiles_dir() {
+        wallets.extend(read_profiles(&dir));
+    }
+
+    if wallets.is_empty() {
+        report!(
+            "No stored ArDrive wallets, run 'sugar ardrive set-wallet <file> [--profile <name>]' to store one."
+        );
+        return Ok(());
+    }
+
+    let selected = options.profile.as_deref();
+
+    report!("  {:20} | {:43} | path", "profile", "address");
+    report!("  {:-<20} | {:-<43} | {:-<4}", "", "", "");
+
+    for (name, path) in wallets {
+        let address = fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|content| arfs::wallet_address(&content))
+            .unwrap_or_else(|err| format!("<invalid wallet: {}>", err));
+        let marker = if selected == Some(name.as_str()) {
+            "*"
+        } else {
+            " "
+        };
+
+        report!(
+            "{} {:20} | {:43} | {}",
+            marker,
+            name,
+            address,
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wallet_profiles() {
+        assert!(validate_profile_name("client-A_2").is_ok());
+        assert!(validate_profile_name("../wallet").is_err());
+        assert!(validate_profile_name("").is_err());
+        assert_eq!(
+            resolve_wallet_profile(Some("clientA".to_string())).unwrap(),
+            Some("clientA".to_string())
+        );
+        assert!(resolve_wallet_profile(Some("../wallet".to_string())).is_err());
+        assert_eq!(resolve_wallet_profile(None).unwrap(), None);
+
+        let dir = tempfile::tempdir().unwrap();
+        for file in ["clientB.json", "clientA.json", "notes.txt", "bad name.json"] {
+            fs::write(dir.path().join(file), "{}").unwrap();
+        }
+
+        let names: Vec<String> = read_profiles(dir.path())
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        assert_eq!(names, vec!["clientA", "clientB"]);
+
+        assert!(read_profiles(&dir.path().join("missing")).is_empty());
+    }
+}
@@ -0,0 +1,125 @@
+//! Confirmation status of the data transactions of drive files.
+//!
+//! Files uploaded a few minutes ago are listed by the gateway before their data transaction is
+//! mined, and a cache pointing at a pending (or dropped) transaction leads to broken mints.
+
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use futures::{stream, StreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    arfs::{self, ArweaveClient},
+    files_progress_bar, ArDriveFile, ArdriveOptions,
+};
+
+/// Number of transaction statuses queried in parallel.
+pub const STATUS_CONCURRENCY: usize = 16;
+
+/// Confirmation status of a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxStatus {
+    /// Mined in a block.
+    Confirmed,
+    /// Accepted by the gateway, not mined yet.
+    Pending,
+    /// Unknown to the gateway.
+    NotFound,
+}
+
+impl fmt::Display for TxStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TxStatus::Confirmed => write!(f, "confirmed"),
+            TxStatus::Pending => write!(f, "pending"),
+            TxStatus::NotFound => write!(f, "not_found"),
+        }
+    }
+}
+
+/// Returns the confirmation status of a transaction.
+pub async fn tx_status(client: &ArweaveClient, tx_id: &str) -> Result<TxStatus> {
+    match arfs::transaction_status(client, tx_id).await? {
+        StatusCode::OK => Ok(TxStatus::Confirmed),
+        StatusCode::ACCEPTED => Ok(TxStatus::Pending),
+        StatusCode::NOT_FOUND => Ok(TxStatus::NotFound),
+        status => Err(anyhow!(
+            "transaction status endpoint returned {} for {}",
+            status,
+            tx_id
+        )),
+    }
+}
+
+/// Queries the status of the data transaction of the files in parallel and sets their `status`
+/// (folders and files without a data transaction are left unchanged).
+pub async fn check_tx_statuses(options: &ArdriveOptions, files: &mut [ArDriveFile]) -> Result<()> {
+    let client = ArweaveClient::new(options);
+    let checked: Vec<&mut ArDriveFile> = files
+        .iter_mut()
+        .filter(|file| file.data_tx_id.is_some())
+        .collect();
+
+    let pb = files_progress_bar(options, checked.len() as u64);
+    pb.set_message("Checking the transaction statuses ");
+
+    let results: Vec<Result<()>> = stream::iter(checked)
+        .map(|file| {
+            let client = &client;
+            let pb = &pb;
+            async move {
+                let tx_id = file.data_tx_id.as_deref().unwrap_or_default();
+                file.status = Some(tx_status(client, tx_id).await?);
+                pb.inc(1);
+                Ok(())
+            }
+        })
+        .buffer_unordered(STATUS_CONCURRENCY)
+        .collect()
+        .await;
+
+    pb.finish_and_clear();
+
+    results.into_iter().collect()
+}
+
+/// Returns the number of confirmed, pending and not found files.
+pub fn count_statuses(files: &[ArDriveFile]) -> (usize, usize, usize) {
+    let count = |status: TxStatus| files.iter().filter(|f| f.status == Some(status)).count();
+
+    (
+        count(TxStatus::Confirmed),
+        count(TxStatus::Pending),
+        count(TxStatus::NotFound),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_tx_status_serialization() {
+        let files: Vec<ArDriveFile> = serde_json::from_value(json!([
+            {"name": "0.png", "status": "confirmed"},
+            {"name": "0.json", "status": "pending"},
+            {"name": "1.png", "status": "not_found"},
+            {"name": "1.json"}
+        ]))
+        .unwrap();
+
+        assert_eq!(count_statuses(&files), (1, 1, 1));
+        assert_eq!(TxStatus::NotFound.to_string(), "not_found");
+
+        let json = serde_json::to_value(&files[2]).unwrap();
+        assert_eq!(json["status"], "not_found");
+        // unchecked files have no status
+        let json = serde_json::to_value(&files[3]).unwrap();
+        assert!(json.get("status").is_none());
+    }
+}
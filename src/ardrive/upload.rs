@@ -0,0 +1,894 @@
+//! Uploads of local files and directories to an ArDrive drive.
+//!
+//! Transactions are signed and posted by the ArDrive Node CLI; the drive structure (root
+//! folder, existing folders and files) is read natively from Arweave so that an interrupted
+//! upload can be resumed without uploading the same files twice.
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::Mutex,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{anyhow, Context, Result};
+use futures::{stream, StreamExt};
+use reqwest::StatusCode;
+use serde_json::Value;
+use tracing::info;
+
+use super::{
+    add_gateway,
+    arfs::{self, format_ar, ArweaveClient},
+    cached_drive_files, check_drive_id, cli_error, files_progress_bar, find_local_ardrive,
+    get_arweave_url,
+    journal::{default_journal_path, journal_key, JournalEntry, UploadJournal},
+    listing, print_detail, resolve_ardrive_wallet_content, try_parse_json_flex,
+    with_retry_blocking, write_temp_wallet, ArDriveDrive, ArDriveError, ArDriveFile,
+    ArdriveOptions, FileQuery,
+};
+use crate::{constants::WARNING_EMOJI, output::style, upload::assets::encode};
+
+/// Default number of parallel uploads of `ardrive upload`.
+pub const DEFAULT_UPLOAD_CONCURRENCY: usize = 4;
+
+pub struct ArdriveUploadArgs {
+    pub wallet: Option<PathBuf>,
+    /// File or directory to upload.
+    pub path: PathBuf,
+    pub drive_id: String,
+    /// Folder to upload to, defaults to the root folder of the drive.
+    pub parent_folder: Option<String>,
+    pub concurrency: usize,
+    pub dry_run: bool,
+    pub manifest: bool,
+    /// JSON file to write the uploaded files to (same format as `list-drive-files --output`).
+    pub output: Option<PathBuf>,
+    /// Upload every file, even if it is already in the folder or in the upload journal.
+    pub force_reupload: bool,
+    /// Upload journal, defaults to `.ardrive-upload-state.json` next to the uploaded path.
+    pub journal: Option<PathBuf>,
+    pub options: ArdriveOptions,
+}
+
+/// Upload a file, or every file of a directory, to a drive. A directory is uploaded to a
+/// folder with the same name in the parent folder (created if needed); files already in that
+/// folder, or recorded in the upload journal with a confirmed transaction, are not uploaded
+/// again.
+pub async fn process_ardrive_upload(args: ArdriveUploadArgs) -> Result<Vec<ArDriveFile>> {
+    info!(
+        "ArDrive: upload called: path={:?} drive={} parent folder={:?}",
+        args.path, args.drive_id, args.parent_folder
+    );
+
+    check_drive_id(&args.drive_id)?;
+    let local_files = collect_files(&args.path)?;
+    let total_bytes = local_files.iter().map(|(_, size)| size).sum::<u64>();
+
+    print_detail(
+        &args.options,
+        format!(
+            "Found {} file(s) to upload ({} bytes)",
+            local_files.len(),
+            total_bytes
+        ),
+    );
+
+    if args.dry_run {
+        let price = arfs::storage_price(&args.options, total_bytes).await?;
+        report!(
+            "Estimated cost: {} AR ({} winston), nothing was uploaded",
+            format_ar(price),
+            price
+        );
+        return Ok(Vec::new());
+    }
+
+    let content = resolve_ardrive_wallet_content(&args.options, args.wallet)?;
+    // removed when dropped, after all uploads completed
+    let wallet_file = write_temp_wallet(&content)?;
+    let wallet_path = wallet_file.path().to_path_buf();
+
+    let parent_folder = match args.parent_folder {
+        Some(folder) => folder,
+        None => root_folder(&args.options, &content, &args.drive_id, None).await?,
+    };
+
+    let folder_id = if args.path.is_dir() {
+        let name = file_name(&args.path)?;
+        match arfs::find_folder(
+            &args.options,
+            &content,
+            &args.drive_id,
+            &parent_folder,
+            &name,
+        )
+        .await?
+        {
+            Some(folder_id) => {
+                print_detail(
+                    &args.options,
+                    format!("Using existing folder '{}' ({})", name, folder_id),
+                );
+                folder_id
+            }
+            None => {
+                let output = run_ardrive(
+                    &args.options,
+                    [
+                        OsStr::new("create-folder"),
+                        OsStr::new("--parent-folder-id"),
+                        OsStr::new(&parent_folder),
+                        OsStr::new("--folder-name"),
+                        OsStr::new(&name),
+                        OsStr::new("--wallet-file"),
+                        wallet_path.as_os_str(),
+                    ],
+                )?;
+                let folder_id = entity_field(created_entity(&output, "folder")?, "entityId")?;
+                print_detail(
+                    &args.options,
+                    format!("Created folder '{}' ({})", name, folder_id),
+                );
+                folder_id
+            }
+        }
+    } else {
+        parent_folder
+    };
+
+    let journal_path = args
+        .journal
+        .clone()
+        .unwrap_or_else(|| default_journal_path(&args.path));
+    let journal = match UploadJournal::load(&journal_path) {
+        Ok(journal) => journal,
+        // the journal is replaced by the new uploads
+        Err(err) if args.force_reupload => {
+            info!("Ignoring upload journal: {:#}", err);
+            UploadJournal::default()
+        }
+        Err(err) => return Err(err),
+    };
+
+    // files uploaded by a previous (interrupted) run
+    let plan = if args.force_reupload {
+        SyncPlan {
+            missing: local_files,
+            ..SyncPlan::default()
+        }
+    } else {
+        let remote_files =
+            folder_files(&args.options, &content, &args.drive_id, &folder_id).await?;
+        SyncPlan::new(local_files, remote_files)?
+    };
+
+    let mut uploaded = plan.synced;
+    let pending: Vec<PathBuf> = plan
+        .missing
+        .into_iter()
+        .chain(plan.modified)
+        .map(|(path, _)| path)
+        .collect();
+
+    // files uploaded by a previous run but not listed by the gateway yet
+    let (journaled, pending) = if args.force_reupload {
+        (Vec::new(), hash_files(pending)?)
+    } else {
+        journaled_files(
+            &args.options,
+            &journal,
+            &args.path,
+            &folder_id,
+            hash_files(pending)?,
+        )
+        .await?
+    };
+    uploaded.extend(journaled);
+
+    let skipped = uploaded.len();
+    if skipped > 0 {
+        print_detail(
+            &args.options,
+            format!("Skipping {} file(s) already in the folder", skipped),
+        );
+    }
+
+    let pb = files_progress_bar(&args.options, pending.len() as u64);
+    pb.set_message("Uploading ");
+
+    let journal = Mutex::new(journal);
+
+    let results: Vec<(PathBuf, Result<ArDriveFile>)> = stream::iter(pending)
+        .map(|(path, sha256)| {
+            let options = args.options.clone();
+            let wallet_path = wallet_path.clone();
+            let folder_id = folder_id.clone();
+            let uploaded_path = &args.path;
+            let journal_path = &journal_path;
+            let journal = &journal;
+            let pb = &pb;
+            async move {
+                let task_path = path.clone();
+                let task_folder_id = folder_id.clone();
+                let result = tokio::task::spawn_blocking(move || {
+                    upload_file(&options, &wallet_path, &task_path, &task_folder_id)
+                })
+                .await
+                .map_err(|e| anyhow!("upload task failed: {}", e))
+                .and_then(|result| result);
+
+                if let Ok(file) = &result {
+                    record_upload(
+                        journal,
+                        journal_path,
+                        journal_key(uploaded_path, &path),
+                        file,
+                        folder_id,
+                        sha256,
+                    );
+                }
+
+                pb.inc(1);
+                (path, result)
+            }
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect()
+        .await;
+
+    pb.finish_and_clear();
+
+    let mut failed = Vec::new();
+
+    for (path, result) in results {
+        match result {
+            Ok(file) => uploaded.push(file),
+            Err(err) => failed.push(format!("{}: {}", path.display(), err)),
+        }
+    }
+
+    let new_files = uploaded.len() - skipped;
+    if new_files > 0 {
+        listing::invalidate(&args.drive_id);
+    }
+
+    uploaded.sort_by(|a, b| a.name.cmp(&b.name));
+
+    print_detail(&args.options, "Uploaded files:");
+    for file in &uploaded {
+        print_detail(
+            &args.options,
+            format!(
+                "  {} -> {}",
+                file.name.as_deref().unwrap_or("<unnamed>"),
+                file.data_tx_id.as_deref().unwrap_or("-")
+            ),
+        );
+    }
+
+    report!(
+        "Uploaded {} file(s) ({} already in the folder, {} failed)",
+        new_files,
+        skipped,
+        failed.len()
+    );
+
+    if let Some(path) = &args.output {
+        fs::write(
+            path,
+            serde_json::to_string_pretty(&uploaded)
+                .context("Failed to format file list as JSON")?,
+        )
+        .with_context(|| format!("Failed to write file list to {}", path.display()))?;
+        print_detail(
+            &args.options,
+            format!("✅ File list written to {}", path.display()),
+        );
+    }
+
+    if !failed.is_empty() {
+        for failure in &failed {
+            print_detail(&args.options, format!("  {}", failure));
+        }
+        return Err(anyhow!(
+            "Failed to upload {} file(s), re-run the command to retry.",
+            failed.len()
+        ));
+    }
+
+    if args.manifest {
+        let output = run_ardrive(
+            &args.options,
+            [
+                OsStr::new("create-manifest"),
+                OsStr::new("--folder-id"),
+                OsStr::new(&folder_id),
+                OsStr::new("--wallet-file"),
+                wallet_path.as_os_str(),
+            ],
+        )?;
+        let manifest_tx = entity_field(created_entity(&output, "file")?, "dataTxId")?;
+        report!(
+            "✅ Manifest created, files are available under {}/<file name>",
+            get_arweave_url(&args.options.gateway, &manifest_tx)
+        );
+    }
+
+    Ok(uploaded)
+}
+
+pub struct ArdriveSyncArgs {
+    pub wallet: Option<PathBuf>,
+    pub assets_dir: PathBuf,
+    pub drive_id: String,
+    pub dry_run: bool,
+    pub options: ArdriveOptions,
+}
+
+/// Local and remote files of a directory uploaded to a drive folder, matched by name and size
+/// (ArFS does not record the hash of the files).
+#[derive(Debug, Default)]
+struct SyncPlan {
+    /// Files in the folder with the same size as the local file.
+    synced: Vec<ArDriveFile>,
+    /// Local files not in the folder.
+    missing: Vec<(PathBuf, u64)>,
+    /// Local files in the folder with a different size (uploaded as a new version).
+    modified: Vec<(PathBuf, u64)>,
+    /// Files in the folder that are not in the local directory.
+    remote_only: Vec<ArDriveFile>,
+}
+
+impl SyncPlan {
+    fn new(local_files: Vec<(PathBuf, u64)>, remote_files: Vec<ArDriveFile>) -> Result<Self> {
+        let mut remote: HashMap<String, ArDriveFile> = remote_files
+            .into_iter()
+            .filter_map(|file| file.name.clone().map(|name| (name, file)))
+            .collect();
+        let mut plan = SyncPlan::default();
+
+        for (path, size) in local_files {
+            match remote.remove(&file_name(&path)?) {
+                Some(file) if file.size == Some(size) => plan.synced.push(file),
+                Some(_) => plan.modified.push((path, size)),
+                None => plan.missing.push((path, size)),
+            }
+        }
+
+        plan.remote_only = remote.into_values().collect();
+        plan.remote_only.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(plan)
+    }
+
+    /// Number of bytes to upload.
+    fn upload_bytes(&self) -> u64 {
+        self.missing
+            .iter()
+            .chain(&self.modified)
+            .map(|(_, size)| size)
+            .sum()
+    }
+}
+
+/// Compare a local assets directory with its folder in the drive (a folder with the same name
+/// in the root folder, as created by `upload`) and upload the files that are missing or whose
+/// size changed. Files only in the drive are reported but not removed.
+pub async fn process_ardrive_sync(args: ArdriveSyncArgs) -> Result<()> {
+    info!(
+        "ArDrive: sync called: assets={:?} drive={} dry run={}",
+        args.assets_dir, args.drive_id, args.dry_run
+    );
+
+    check_drive_id(&args.drive_id)?;
+    if !args.assets_dir.is_dir() {
+        return Err(anyhow!(
+            "Assets directory {} not found",
+            args.assets_dir.display()
+        ));
+    }
+
+    let local_files = collect_files(&args.assets_dir)?;
+    let content = resolve_ardrive_wallet_content(&args.options, args.wallet.clone())?;
+
+    let root_folder = root_folder(&args.options, &content, &args.drive_id, None).await?;
+    let name = file_name(&args.assets_dir)?;
+    let remote_files =
+        match arfs::find_folder(&args.options, &content, &args.drive_id, &root_folder, &name)
+            .await?
+        {
+            Some(folder_id) => {
+                folder_files(&args.options, &content, &args.drive_id, &folder_id).await?
+            }
+            None => {
+                print_detail(
+                    &args.options,
+                    format!("Folder '{}' not found in drive {}", name, args.drive_id),
+                );
+                Vec::new()
+            }
+        };
+
+    let plan = SyncPlan::new(local_files, remote_files)?;
+
+    print_detail(
+        &args.options,
+        format!("In sync: {} file(s)", plan.synced.len()),
+    );
+    print_detail(
+        &args.options,
+        format!(
+            "Only local (to upload): {} file(s)",
+            plan.missing.len() + plan.modified.len()
+        ),
+    );
+    for (path, _) in &plan.missing {
+        print_detail(&args.options, format!("  {}", file_name(path)?));
+    }
+    for (path, _) in &plan.modified {
+        print_detail(
+            &args.options,
+            format!("  {} (size differs)", file_name(path)?),
+        );
+    }
+    if !plan.remote_only.is_empty() {
+        print_detail(
+            &args.options,
+            format!(
+                "{}{} {} file(s)",
+                WARNING_EMOJI,
+                style("Only in the drive:").yellow().bold(),
+                plan.remote_only.len()
+            ),
+        );
+        for file in &plan.remote_only {
+            print_detail(
+                &args.options,
+                format!("  {}", file.name.as_deref().unwrap_or("<unnamed>")),
+            );
+        }
+    }
+
+    if plan.missing.is_empty() && plan.modified.is_empty() {
+        report!("✅ Drive is in sync with {}", args.assets_dir.display());
+        return Ok(());
+    }
+
+    if args.dry_run {
+        let bytes = plan.upload_bytes();
+        let price = arfs::storage_price(&args.options, bytes).await?;
+        report!(
+            "Estimated cost: {} AR ({} winston) for {} bytes, nothing was uploaded",
+            format_ar(price),
+            price,
+            bytes
+        );
+        return Ok(());
+    }
+
+    // files already in the folder are skipped by the upload
+    process_ardrive_upload(ArdriveUploadArgs {
+        wallet: args.wallet,
+        path: args.assets_dir,
+        drive_id: args.drive_id,
+        parent_folder: Some(root_folder),
+        concurrency: DEFAULT_UPLOAD_CONCURRENCY,
+        dry_run: false,
+        manifest: false,
+        output: None,
+        force_reupload: false,
+        journal: None,
+        options: args.options,
+    })
+    .await?;
+
+    Ok(())
+}
+
+/// Returns the root folder of a drive; private drives are decrypted with `password`.
+pub(super) async fn root_folder(
+    options: &ArdriveOptions,
+    wallet: &str,
+    drive_id: &str,
+    password: Option<&str>,
+) -> Result<String> {
+    let drives = arfs::list_drives(options, wallet, password).await?;
+
+    ArDriveDrive::find_in_list(&drives, drive_id)
+        .and_then(|drive| drive.root_folder_id.clone())
+        .ok_or_else(|| {
+            ArDriveError::InvalidDriveId {
+                drive_id: drive_id.to_string(),
+                reason: "could not find its root folder (it must be a drive of the wallet, private drives need the drive password)".to_string(),
+            }
+            .into()
+        })
+}
+
+/// Returns the files of a folder of the drive.
+async fn folder_files(
+    options: &ArdriveOptions,
+    wallet: &str,
+    drive_id: &str,
+    folder_id: &str,
+) -> Result<Vec<ArDriveFile>> {
+    let query = FileQuery {
+        parent_folder: Some(folder_id.to_string()),
+        ..FileQuery::default()
+    };
+
+    cached_drive_files(options, wallet, drive_id, &query).await
+}
+
+/// Returns the SHA-256 of each file.
+fn hash_files(paths: Vec<PathBuf>) -> Result<Vec<(PathBuf, String)>> {
+    paths
+        .into_iter()
+        .map(|path| {
+            let sha256 = encode(&path.to_string_lossy())
+                .with_context(|| format!("Failed to hash {}", path.display()))?;
+            Ok((path, sha256))
+        })
+        .collect()
+}
+
+/// Splits the files to upload between the files of the journal that did not change and whose
+/// data transaction is confirmed, returned as uploaded files, and the files left to upload.
+async fn journaled_files(
+    options: &ArdriveOptions,
+    journal: &UploadJournal,
+    uploaded_path: &Path,
+    folder_id: &str,
+    files: Vec<(PathBuf, String)>,
+) -> Result<(Vec<ArDriveFile>, Vec<(PathBuf, String)>)> {
+    let client = ArweaveClient::new(options);
+
+    let checked: Vec<(PathBuf, String, Result<Option<ArDriveFile>>)> = stream::iter(files)
+        .map(|(path, sha256)| {
+            let client = &client;
+            async move {
+                let file =
+                    journaled_file(client, journal, uploaded_path, folder_id, &path, &sha256).await;
+                (path, sha256, file)
+            }
+        })
+        .buffer_unordered(DEFAULT_UPLOAD_CONCURRENCY * 4)
+        .collect()
+        .await;
+
+    let mut journaled = Vec::new();
+    let mut pending = Vec::new();
+
+    for (path, sha256, file) in checked {
+        match file? {
+            Some(file) => journaled.push(file),
+            None => pending.push((path, sha256)),
+        }
+    }
+
+    if !journaled.is_empty() {
+        print_detail(
+            options,
+            format!("Skipping {} file(s) of the upload journal", journaled.len()),
+        );
+    }
+
+    // uploads in the order of the files
+    pending.sort();
+
+    Ok((journaled, pending))
+}
+
+/// Returns the uploaded file if it is in the journal with the same content and a confirmed data
+/// transaction.
+async fn journaled_file(
+    client: &ArweaveClient,
+    journal: &UploadJournal,
+    uploaded_path: &Path,
+    folder_id: &str,
+    path: &Path,
+    sha256: &str,
+) -> Result<Option<ArDriveFile>> {
+    let bytes = fs::metadata(path)?.len();
+    let key = journal_key(uploaded_path, path);
+
+    let entry = match journal.uploaded(&key, folder_id, bytes, sha256) {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+
+    let status = arfs::transaction_status(client, &entry.data_tx).await?;
+    if status != StatusCode::OK {
+        info!(
+            "Uploading {} again: transaction {} is not confirmed ({})",
+            key, entry.data_tx, status
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(ArDriveFile {
+        entity_type: Some("file".to_string()),
+        name: Some(file_name(path)?),
+        data_tx_id: Some(entry.data_tx.clone()),
+        metadata_tx_id: entry.metadata_tx.clone(),
+        parent_folder_id: Some(folder_id.to_string()),
+        size: Some(bytes),
+        last_modified_date: None,
+        content_type: None,
+        data_content_type: None,
+        entity_id: None,
+        path: None,
+        status: None,
+    }))
+}
+
+/// Records an uploaded file in the journal, saved after each upload so that an interrupted
+/// upload loses at most the files being uploaded.
+fn record_upload(
+    journal: &Mutex<UploadJournal>,
+    journal_path: &Path,
+    key: String,
+    file: &ArDriveFile,
+    folder_id: String,
+    sha256: String,
+) {
+    let data_tx = match &file.data_tx_id {
+        Some(data_tx) => data_tx.clone(),
+        None => return,
+    };
+
+    let mut journal = match journal.lock() {
+        Ok(journal) => journal,
+        Err(_) => return,
+    };
+
+    journal.files.insert(
+        key,
+        JournalEntry {
+            data_tx,
+            metadata_tx: file.metadata_tx_id.clone(),
+            folder_id,
+            bytes: file.size.unwrap_or_default(),
+            sha256,
+        },
+    );
+
+    if let Err(err) = journal.save(journal_path) {
+        info!("Failed to save the upload journal: {:#}", err);
+    }
+}
+
+/// Uploads a file to the folder with the ArDrive Node CLI.
+fn upload_file(
+    options: &ArdriveOptions,
+    wallet_path: &Path,
+    path: &Path,
+    folder_id: &str,
+) -> Result<ArDriveFile> {
+    let output = run_ardrive(
+        options,
+        [
+            OsStr::new("upload-file"),
+            OsStr::new("--local-path"),
+            path.as_os_str(),
+            OsStr::new("--parent-folder-id"),
+            OsStr::new(folder_id),
+            OsStr::new("--wallet-file"),
+            wallet_path.as_os_str(),
+        ],
+    )?;
+    let entity = created_entity(&output, "file")?;
+    let metadata = fs::metadata(path)?;
+
+    Ok(ArDriveFile {
+        entity_type: Some("file".to_string()),
+        name: Some(file_name(path)?),
+        data_tx_id: Some(entity_field(entity, "dataTxId")?),
+        metadata_tx_id: entity["metadataTxId"].as_str().map(String::from),
+        parent_folder_id: Some(folder_id.to_string()),
+        size: Some(metadata.len()),
+        last_modified_date: metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_millis() as u64),
+        content_type: None,
+        data_content_type: None,
+        entity_id: entity["entityId"].as_str().map(String::from),
+        path: None,
+        status: None,
+    })
+}
+
+/// Runs the ArDrive Node CLI, returning its JSON output; commands failing on gateway errors
+/// are retried.
+pub(super) fn run_ardrive<I, S>(options: &ArdriveOptions, args: I) -> Result<Value>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<OsStr>,
+{
+    let args: Vec<S> = args.into_iter().collect();
+    let command = args
+        .first()
+        .map(|arg| arg.as_ref().to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let stdout = with_retry_blocking(options, &format!("ArDrive CLI {command}"), || {
+        let mut cmd =
+            Command::new(find_local_ardrive().unwrap_or_else(|| PathBuf::from("ardrive")));
+        cmd.args(&args).env("NODE_ENV", "production");
+        add_gateway(options, &mut cmd);
+
+        let output = cmd.output().map_err(|e| ArDriveError::SubprocessFailed {
+            code: None,
+            message: format!(
+                "Failed to run 'ardrive {command}': {e}. Is ArDrive CLI installed? Install with: pnpm add ardrive-cli or pnpm add -g ardrive-cli"
+            ),
+        })?;
+
+        let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(cli_error(
+                &stderr,
+                ArDriveError::SubprocessFailed {
+                    code: output.status.code(),
+                    message: format!(
+                        "ArDrive CLI command {} failed:\nStdout: {}\nStderr: {}",
+                        command, stdout, stderr
+                    ),
+                }
+                .into(),
+            ));
+        }
+
+        Ok(stdout)
+    })?;
+
+    try_parse_json_flex(&stdout).map_err(|e| {
+        ArDriveError::ParseError {
+            message: format!("Invalid ArDrive CLI {} output: {}\n{}", command, e, stdout),
+        }
+        .into()
+    })
+}
+
+/// Returns the entity of the specified type created by an ArDrive CLI command.
+pub(super) fn created_entity<'a>(output: &'a Value, entity_type: &str) -> Result<&'a Value> {
+    output["created"]
+        .as_array()
+        .and_then(|created| {
+            created
+                .iter()
+                .find(|entity| entity["type"].as_str() == Some(entity_type))
+        })
+        .ok_or_else(|| {
+            ArDriveError::ParseError {
+                message: format!("ArDrive CLI did not create a {}: {}", entity_type, output),
+            }
+            .into()
+        })
+}
+
+pub(super) fn entity_field(entity: &Value, field: &str) -> Result<String> {
+    entity[field].as_str().map(String::from).ok_or_else(|| {
+        ArDriveError::ParseError {
+            message: format!("ArDrive CLI output is missing '{}': {}", field, entity),
+        }
+        .into()
+    })
+}
+
+/// Returns the files to upload (and their sizes): the file itself or the (non-hidden) files
+/// of the directory, sorted by name. Sub-directories are not uploaded.
+pub(super) fn collect_files(path: &Path) -> Result<Vec<(PathBuf, u64)>> {
+    if path.is_file() {
+        return Ok(vec![(path.to_path_buf(), fs::metadata(path)?.len())]);
+    }
+
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(path)
+        .with_context(|| format!("Failed to read directory {}", path.display()))?
+    {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        let hidden = entry.file_name().to_string_lossy().starts_with('.');
+
+        if metadata.is_file() && !hidden {
+            files.push((entry.path(), metadata.len()));
+        }
+    }
+
+    if files.is_empty() {
+        return Err(anyhow!("No files to upload in {}", path.display()));
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+fn file_name(path: &Path) -> Result<String> {
+    path.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| anyhow!("Invalid path {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_collect_files_skips_hidden_files_and_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("1.json"), "{}").unwrap();
+        fs::write(dir.path().join("0.png"), [0u8; 8]).unwrap();
+        fs::write(dir.path().join(".DS_Store"), "").unwrap();
+        fs::create_dir(dir.path().join("nested")).unwrap();
+
+        let files: Vec<(String, u64)> = collect_files(dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|(path, size)| (file_name(&path).unwrap(), size))
+            .collect();
+        assert_eq!(
+            files,
+            vec![("0.png".to_string(), 8), ("1.json".to_string(), 2)]
+        );
+
+        let single = collect_files(&dir.path().join("0.png")).unwrap();
+        assert_eq!(single.len(), 1);
+
+        let empty = tempfile::tempdir().unwrap();
+        assert!(collect_files(empty.path()).is_err());
+    }
+
+    #[test]
+    fn test_sync_plan() {
+        let local = vec![
+            (PathBuf::from("assets/0.json"), 10),
+            (PathBuf::from("assets/0.png"), 100),
+            (PathBuf::from("assets/1.json"), 12),
+            (PathBuf::from("assets/1.png"), 100),
+        ];
+        let remote: Vec<ArDriveFile> = serde_json::from_value(json!([
+            {"name": "0.json", "size": 10, "dataTxId": "json0"},
+            {"name": "0.png", "size": 100, "dataTxId": "png0"},
+            {"name": "1.json", "size": 11, "dataTxId": "json1"},
+            {"name": "2.png", "size": 100, "dataTxId": "png2"}
+        ]))
+        .unwrap();
+
+        let plan = SyncPlan::new(local, remote).unwrap();
+
+        assert_eq!(plan.synced.len(), 2);
+        assert_eq!(plan.missing, vec![(PathBuf::from("assets/1.png"), 100)]);
+        assert_eq!(plan.modified, vec![(PathBuf::from("assets/1.json"), 12)]);
+        assert_eq!(plan.remote_only.len(), 1);
+        assert_eq!(plan.remote_only[0].name.as_deref(), Some("2.png"));
+        assert_eq!(plan.upload_bytes(), 112);
+    }
+
+    #[test]
+    fn test_created_entity() {
+        let output = json!({
+            "created": [
+                {"type": "file", "entityName": "0.png", "dataTxId": "data", "metadataTxId": "meta"},
+                {"type": "bundle", "bundleTxId": "bundle"}
+            ],
+            "tips": [],
+            "fees": {}
+        });
+
+        let file = created_entity(&output, "file").unwrap();
+        assert_eq!(entity_field(file, "dataTxId").unwrap(), "data");
+        assert!(entity_field(file, "entityId").is_err());
+        assert!(created_entity(&output, "folder").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+//! Verification that the Arweave links of a cache are live before deploying it.
+
+use std::{fs, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+use futures::{stream, StreamExt};
+use reqwest::{header::CONTENT_TYPE, StatusCode, Url};
+use serde::Serialize;
+use tracing::info;
+
+use super::{
+    arfs::ArweaveClient, arweave_tx_id, tx_status, with_retry, ArDriveError, ArdriveOptions,
+    TxStatus,
+};
+use crate::{
+    cache::{load_cache, CacheItem},
+    constants::{COMPLETE_EMOJI, ERROR_EMOJI},
+    output::style,
+    utils::progress_bar_with_style,
+};
+
+/// Default number of cache items checked in parallel by `ardrive verify-cache`.
+pub const DEFAULT_VERIFY_CONCURRENCY: usize = 16;
+
+pub struct ArdriveVerifyCacheArgs {
+    pub cache: String,
+    pub concurrency: usize,
+    /// Also check that the transactions are confirmed on Arweave.
+    pub check_confirmation: bool,
+    /// JSON file to write the failing items to.
+    pub output: Option<PathBuf>,
+    pub options: ArdriveOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LinkKind {
+    Metadata,
+    Image,
+}
+
+impl LinkKind {
+    fn name(&self) -> &'static str {
+        match self {
+            LinkKind::Metadata => "metadata",
+            LinkKind::Image => "image",
+        }
+    }
+
+    fn accepts(&self, content_type: &str) -> bool {
+        let content_type = content_type.to_lowercase();
+
+        match self {
+            LinkKind::Metadata => content_type.contains("json"),
+            LinkKind::Image => ["image/", "video/", "audio/", "model/"]
+                .iter()
+                .any(|prefix| content_type.starts_with(prefix)),
+        }
+    }
+}
+
+/// Verification result of a cache item.
+#[derive(Debug, Serialize)]
+struct ItemReport {
+    index: String,
+    name: String,
+    /// `None` if the link is live.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    image_error: Option<String>,
+}
+
+impl ItemReport {
+    fn passed(&self) -> bool {
+        self.metadata_error.is_none() && self.image_error.is_none()
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct VerifyReport<'a> {
+    items: usize,
+    /// Indices of the failing items.
+    failed: Vec<&'a str>,
+    failures: Vec<&'a ItemReport>,
+}
+
+/// Checks that the metadata and image links of every cache item are served by the gateway
+/// with the expected content type (and optionally that their transactions are confirmed),
+/// failing if any item is not.
+pub async fn process_ardrive_verify_cache(args: ArdriveVerifyCacheArgs) -> Result<()> {
+    let cache = load_cache(&args.cache, false)?;
+    info!(
+        "ArDrive: verify-cache called for {} ({} items)",
+        args.cache,
+        cache.items.len()
+    );
+
+    let client = ArweaveClient::new(&args.options);
+    let pb = progress_bar_with_style(cache.items.len() as u64);
+
+    let mut reports: Vec<ItemReport> = stream::iter(cache.items.iter())
+        .map(|(index, item)| {
+            let client = &client;
+            let pb = &pb;
+            let check_confirmation = args.check_confirmation;
+            async move {
+                let report = check_item(client, index, item, check_confirmation).await;
+                pb.inc(1);
+                report
+            }
+        })
+        .buffer_unordered(args.concurrency.max(1))
+        .collect()
+        .await;
+
+    pb.finish_and_clear();
+
+    // the collection item (-1) first, then the items in order
+    reports.sort_by_key(|report| report.index.parse::<i64>().unwrap_or(i64::MAX));
+
+    print_reports(&reports);
+
+    let failures: Vec<&ItemReport> = reports.iter().filter(|r| !r.passed()).collect();
+
+    if let Some(path) = &args.output {
+        let report = VerifyReport {
+            items: reports.len(),
+            failed: failures.iter().map(|r| r.index.as_str()).collect(),
+            failures: failures.clone(),
+        };
+        fs::write(path, serde_json::to_string_pretty(&report)?)
+            .with_context(|| format!("Failed to write report to {}", path.display()))?;
+        report!("Report written to {}", path.display());
+    }
+
+    if failures.is_empty() {
+        report!(
+            "\n{}All {} cache item(s) are live on Arweave",
+            COMPLETE_EMOJI,
+            reports.len()
+        );
+        Ok(())
+    } else {
+        report!(
+            "\n{}{}",
+            ERROR_EMOJI,
+            style(format!(
+                "{} of {} cache item(s) failed verification",
+                failures.len(),
+                reports.len()
+            ))
+            .red()
+        );
+        Err(anyhow!(
+            "Cache verification failed for item(s) {}",
+            failures
+                .iter()
+                .map(|r| r.index.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+    }
+}
+
+fn print_reports(reports: &[ItemReport]) {
+    let status = |error: &Option<String>| if error.is_some() { "FAILED" } else { "ok" };
+
+    report!(
+        "{:>5} | {:30} | {:8} | {:8}",
+        "index",
+        "name",
+        "metadata",
+        "image"
+    );
+    report!("{:-<5} | {:-<30} | {:-<8} | {:-<8}", "", "", "", "");
+
+    for report in reports {
+        let line = format!(
+            "{:>5} | {:30} | {:8} | {:8}",
+            report.index,
+            report.name,
+            status(&report.metadata_error),
+            status(&report.image_error)
+        );
+
+        if report.passed() {
+            report!("{}", line);
+        } else {
+            report!("{}", style(line).red());
+        }
+    }
+
+    for report in reports.iter().filter(|r| !r.passed()) {
+        for error in [&report.metadata_error, &report.image_error]
+            .into_iter()
+            .flatten()
+        {
+            report!("  {}: {}", report.index, error);
+        }
+    }
+}
+
+async fn check_item(
+    client: &ArweaveClient,
+    index: &str,
+    item: &CacheItem,
+    check_confirmation: bool,
+) -> ItemReport {
+    let check = |link: &str, kind: LinkKind| {
+        let link = link.to_string();
+        async move {
+            check_link(client, &link, kind, check_confirmation)
+                .await
+                .err()
+                .map(|e| format!("{} link {}: {:#}", kind.name(), link, e))
+        }
+    };
+
+    ItemReport {
+        index: index.to_string(),
+        name: item.name.clone(),
+        metadata_error: check(&item.metadata_link, LinkKind::Metadata).await,
+        image_error: check(&item.image_link, LinkKind::Image).await,
+    }
+}
+
+/// Checks that the link is served (status 200) with the expected content type.
+async fn check_link(
+    client: &ArweaveClient,
+    link: &str,
+    kind: LinkKind,
+    check_confirmation: bool,
+) -> Result<()> {
+    if link.is_empty() {
+        return Err(anyhow!("missing link"));
+    }
+
+    let url = Url::parse(link).map_err(|e| anyhow!("invalid link: {}", e))?;
+
+    let url = &url;
+    let content_type = with_retry(
+        &client.options,
+        &format!("HEAD {link}"),
+        move || async move {
+            let response = client
+                .http
+                .head(url.clone())
+                .send()
+                .await
+                .context("request failed")?;
+
+            let status = response.status();
+            if status != StatusCode::OK {
+                return Err(ArDriveError::http_status("gateway", status).into());
+            }
+
+            Ok(response
+                .headers()
+                .get(CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(String::from))
+        },
+    )
+    .await?;
+
+    match content_type {
+        Some(content_type) if kind.accepts(&content_type) => (),
+        Some(content_type) => return Err(anyhow!("unexpected content type {}", content_type)),
+        None => return Err(anyhow!("no content type")),
+    }
+
+    if check_confirmation {
+        let tx_id = arweave_tx_id(url).ok_or_else(|| anyhow!("not an Arweave transaction link"))?;
+        check_confirmed(client, tx_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the transaction is confirmed.
+async fn check_confirmed(client: &ArweaveClient, tx_id: &str) -> Result<()> {
+    match tx_status(client, tx_id).await? {
+        TxStatus::Confirmed => Ok(()),
+        TxStatus::Pending => Err(anyhow!("transaction {} is pending", tx_id)),
+        TxStatus::NotFound => Err(anyhow!("transaction {} not found", tx_id)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepted_content_types() {
+        assert!(LinkKind::Metadata.accepts("application/json"));
+        assert!(LinkKind::Metadata.accepts("application/json; charset=utf-8"));
+        assert!(!LinkKind::Metadata.accepts("text/html"));
+
+        assert!(LinkKind::Image.accepts("image/png"));
+        assert!(LinkKind::Image.accepts("video/MP4"));
+        assert!(!LinkKind::Image.accepts("text/html; charset=utf-8"));
+        assert!(!LinkKind::Image.accepts("application/json"));
+    }
+}
@@ -0,0 +1,258 @@
+use anchor_client::solana_sdk::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+use anyhow::{anyhow, Result};
+use borsh::BorshSerialize;
+use sha2::{Digest, Sha256};
+use solana_program::pubkey;
+
+/// Bubblegum program id.
+pub const BUBBLEGUM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+
+/// SPL account compression program id.
+pub const ACCOUNT_COMPRESSION_ID: Pubkey = pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+
+/// SPL noop (log wrapper) program id.
+pub const NOOP_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+/// Size of the concurrent merkle tree account header (account type, version and header v1).
+const TREE_HEADER_SIZE: usize = 2 + 54;
+
+/// Offset of the `total_mint_capacity` field in the `TreeConfig` account.
+const TREE_CONFIG_CAPACITY_OFFSET: usize = 8 + 32 + 32;
+
+/// Offset of the `num_minted` field in the `TreeConfig` account.
+const TREE_CONFIG_MINTED_OFFSET: usize = TREE_CONFIG_CAPACITY_OFFSET + 8;
+
+/// Valid (max depth, max buffer size) pairs supported by the account compression program,
+/// sorted by depth and buffer size.
+pub const VALID_TREE_SIZES: [(u32, u32); 34] = [
+    (3, 8),
+    (5, 8),
+    (6, 16),
+    (7, 16),
+    (8, 16),
+    (9, 16),
+    (10, 32),
+    (11, 32),
+    (12, 32),
+    (13, 32),
+    (14, 64),
+    (14, 256),
+    (14, 1024),
+    (14, 2048),
+    (15, 64),
+    (16, 64),
+    (17, 64),
+    (18, 64),
+    (19, 64),
+    (20, 64),
+    (20, 256),
+    (20, 1024),
+    (20, 2048),
+    (24, 64),
+    (24, 256),
+    (24, 512),
+    (24, 1024),
+    (24, 2048),
+    (26, 512),
+    (26, 1024),
+    (26, 2048),
+    (30, 512),
+    (30, 1024),
+    (30, 2048),
+];
+
+#[derive(BorshSerialize)]
+pub struct Collection {
+    pub verified: bool,
+    pub key: Pubkey,
+}
+
+#[derive(BorshSerialize)]
+pub struct Creator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+/// Metadata arguments of a compressed NFT (mirrors Bubblegum's `MetadataArgs`).
+#[derive(BorshSerialize)]
+pub struct MetadataArgs {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    pub seller_fee_basis_points: u16,
+    pub primary_sale_happened: bool,
+    pub is_mutable: bool,
+    pub edition_nonce: Option<u8>,
+    /// Token standard (`0` = NonFungible).
+    pub token_standard: Option<u8>,
+    pub collection: Option<Collection>,
+    /// Uses are not supported, always serialized as `None`.
+    pub uses: Option<u8>,
+    /// Token program version (`0` = Original).
+    pub token_program_version: u8,
+    pub creators: Vec<Creator>,
+}
+
+/// Utilization information of a merkle tree.
+pub struct TreeConfig {
+    pub total_mint_capacity: u64,
+    pub num_minted: u64,
+}
+
+impl TreeConfig {
+    pub fn from_account_data(data: &[u8]) -> Result<Self> {
+        if data.len() < TREE_CONFIG_MINTED_OFFSET + 8 {
+            return Err(anyhow!("Invalid tree config account data."));
+        }
+
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[offset..offset + 8]);
+            u64::from_le_bytes(bytes)
+        };
+
+        Ok(TreeConfig {
+            total_mint_capacity: read_u64(TREE_CONFIG_CAPACITY_OFFSET),
+            num_minted: read_u64(TREE_CONFIG_MINTED_OFFSET),
+        })
+    }
+}
+
+/// Anchor instruction discriminator.
+fn discriminator(name: &str) -> [u8; 8] {
+    let hash = Sha256::digest(format!("global:{name}").as_bytes());
+    let mut discriminator = [0u8; 8];
+    discriminator.copy_from_slice(&hash[..8]);
+    discriminator
+}
+
+pub fn find_tree_config_pda(merkle_tree: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[merkle_tree.as_ref()], &BUBBLEGUM_ID).0
+}
+
+pub fn find_bubblegum_signer_pda() -> Pubkey {
+    Pubkey::find_program_address(&[b"collection_cpi"], &BUBBLEGUM_ID).0
+}
+
+/// Return the asset id of the leaf at `leaf_index` (the same id reported by DAS).
+pub fn find_asset_id(merkle_tree: &Pubkey, leaf_index: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"asset", merkle_tree.as_ref(), &leaf_index.to_le_bytes()],
+        &BUBBLEGUM_ID,
+    )
+    .0
+}
+
+/// Return the smallest supported (max depth, max buffer size) pair that can hold `capacity` leaves.
+pub fn tree_size_for_capacity(capacity: u64) -> Result<(u32, u32)> {
+    VALID_TREE_SIZES
+        .into_iter()
+        .find(|(depth, _)| 2u64.pow(*depth) >= capacity)
+        .ok_or_else(|| anyhow!("Capacity {} exceeds the largest supported tree.", capacity))
+}
+
+/// Size of the concurrent merkle tree account.
+pub fn get_tree_account_size(max_depth: u32, max_buffer_size: u32, canopy_depth: u32) -> usize {
+    let depth = max_depth as usize;
+    // root + proof + index + padding
+    let change_log = 32 + 32 * depth + 4 + 4;
+    // proof + leaf + index + padding
+    let rightmost_path = 32 * depth + 32 + 4 + 4;
+    let tree = 8 + 8 + 8 + (max_buffer_size as usize) * change_log + rightmost_path;
+    let canopy = ((1usize << (canopy_depth + 1)) - 2) * 32;
+
+    TREE_HEADER_SIZE + tree + canopy
+}
+
+pub fn create_tree_instruction(
+    merkle_tree: &Pubkey,
+    payer: &Pubkey,
+    max_depth: u32,
+    max_buffer_size: u32,
+    public: bool,
+) -> Result<Instruction> {
+    let mut data = discriminator("create_tree").to_vec();
+    max_depth.serialize(&mut data)?;
+    max_buffer_size.serialize(&mut data)?;
+    Some(public).serialize(&mut data)?;
+
+    Ok(Instruction {
+        program_id: BUBBLEGUM_ID,
+        accounts: vec![
+            AccountMeta::new(find_tree_config_pda(merkle_tree), false),
+            AccountMeta::new(*merkle_tree, false),
+            AccountMeta::new(*payer, true),
+            AccountMeta::new_readonly(*payer, true),
+            AccountMeta::new_readonly(NOOP_ID, false),
+            AccountMeta::new_readonly(ACCOUNT_COMPRESSION_ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    })
+}
+
+pub struct MintToCollectionAccounts {
+    pub merkle_tree: Pubkey,
+    pub leaf_owner: Pubkey,
+    pub payer: Pubkey,
+    pub collection_mint: Pubkey,
+    pub collection_metadata: Pubkey,
+    pub collection_edition: Pubkey,
+}
+
+pub fn mint_to_collection_instruction(
+    accounts: &MintToCollectionAccounts,
+    metadata: MetadataArgs,
+) -> Result<Instruction> {
+    let mut data = discriminator("mint_to_collection_v1").to_vec();
+    metadata.serialize(&mut data)?;
+
+    Ok(Instruction {
+        program_id: BUBBLEGUM_ID,
+        accounts: vec![
+            AccountMeta::new(find_tree_config_pda(&accounts.merkle_tree), false),
+            AccountMeta::new_readonly(accounts.leaf_owner, false),
+            AccountMeta::new_readonly(accounts.leaf_owner, false),
+            AccountMeta::new(accounts.merkle_tree, false),
+            AccountMeta::new(accounts.payer, true),
+            AccountMeta::new_readonly(accounts.payer, true),
+            AccountMeta::new_readonly(accounts.payer, true),
+            // no collection authority record
+            AccountMeta::new_readonly(BUBBLEGUM_ID, false),
+            AccountMeta::new_readonly(accounts.collection_mint, false),
+            AccountMeta::new(accounts.collection_metadata, false),
+            AccountMeta::new_readonly(accounts.collection_edition, false),
+            AccountMeta::new_readonly(find_bubblegum_signer_pda(), false),
+            AccountMeta::new_readonly(NOOP_ID, false),
+            AccountMeta::new_readonly(ACCOUNT_COMPRESSION_ID, false),
+            AccountMeta::new_readonly(mpl_token_metadata::ID, false),
+            AccountMeta::new_readonly(system_program::ID, false),
+        ],
+        data,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree_size_for_capacity_picks_smallest_tree() {
+        assert_eq!(tree_size_for_capacity(8).unwrap(), (3, 8));
+        assert_eq!(tree_size_for_capacity(9).unwrap(), (5, 8));
+        assert_eq!(tree_size_for_capacity(50_000).unwrap(), (16, 64));
+        assert!(tree_size_for_capacity(u64::MAX).is_err());
+    }
+
+    #[test]
+    fn tree_account_size_matches_account_compression() {
+        assert_eq!(get_tree_account_size(3, 8, 0), 1_304);
+        assert_eq!(get_tree_account_size(14, 64, 0), 31_800);
+        assert_eq!(get_tree_account_size(14, 64, 2), 31_800 + 6 * 32);
+    }
+}
@@ -1,29 +1,49 @@
 use anchor_client::solana_sdk::{native_token::LAMPORTS_PER_SOL, signer::Signer};
 use bundlr_sdk::deep_hash::{deep_hash, DeepHashChunk};
-use console::style;
+#[cfg(feature = "cli")]
+use clap::Subcommand;
 use data_encoding::BASE64URL;
 
 use crate::{
-    candy_machine::CANDY_MACHINE_ID, cli::BundlrAction, common::*, config::*,
-    upload::methods::BundlrMethod, utils::*,
+    common::*,
+    config::*,
+    output::style,
+    upload::methods::{parse_u64, BundlrMethod},
+    utils::*,
 };
 
 // The minimum amount required for withdraw.
 const LIMIT: u64 = 5000;
 
+#[cfg_attr(feature = "cli", derive(Subcommand))]
+pub enum BundlrAction {
+    /// Retrieve the balance on bundlr
+    Balance,
+    /// Withdraw funds from bundlr
+    Withdraw,
+}
+
 pub struct BundlrArgs {
     pub keypair: Option<String>,
     pub rpc_url: Option<String>,
+    pub config: String,
+    pub bundlr_node: Option<BundlrNode>,
     pub action: BundlrAction,
 }
 
 pub async fn process_bundlr(args: BundlrArgs) -> Result<()> {
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
-    let client = setup_client(&sugar_config)?;
+
+    // the node of the flag, or of the config file when there is one
+    let bundlr_node = match args.bundlr_node {
+        Some(node) => Some(node),
+        None if Path::new(&args.config).exists() => get_config_data(&args.config)?.bundlr_node,
+        None => None,
+    };
 
     // retrieving balance
 
-    println!(
+    report!(
         "{} {}Retrieving balance",
         style(if let BundlrAction::Withdraw = args.action {
             "[1/2]"
@@ -38,27 +58,19 @@ pub async fn process_bundlr(args: BundlrArgs) -> Result<()> {
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
 
-    let program = client.program(CANDY_MACHINE_ID);
-    let solana_cluster: Cluster = get_cluster(program.rpc())?;
+    let bundlr_node = BundlrMethod::get_bundlr_node(&sugar_config, bundlr_node.as_ref())?;
 
     let http_client = reqwest::Client::new();
     let keypair = sugar_config.keypair;
     let address = keypair.pubkey().to_string();
-    let bundlr_node = match solana_cluster {
-        Cluster::Devnet => BUNDLR_DEVNET,
-        Cluster::Mainnet => BUNDLR_MAINNET,
-        Cluster::Unknown | Cluster::Localnet => {
-            return Err(anyhow!("Bundlr is only supported on devnet or mainnet"));
-        }
-    };
 
-    let balance = BundlrMethod::get_bundlr_balance(&http_client, &address, bundlr_node).await?;
+    let balance = BundlrMethod::get_bundlr_balance(&http_client, &address, &bundlr_node).await?;
 
     pb.finish_and_clear();
 
-    println!("\nFunding address:");
-    println!("  -> pubkey: {}", address);
-    println!(
+    report!("\nFunding address ({bundlr_node}):");
+    report!("  -> pubkey: {}", address);
+    report!(
         "  -> lamports: {} (◎ {})",
         balance,
         balance as f64 / LAMPORTS_PER_SOL as f64
@@ -67,14 +79,14 @@ pub async fn process_bundlr(args: BundlrArgs) -> Result<()> {
     // withdrawing funds
 
     if let BundlrAction::Withdraw = args.action {
-        println!(
+        report!(
             "\n{} {}Withdrawing funds",
             style("[2/2]").bold().dim(),
             WITHDRAW_EMOJI
         );
 
         if balance == 0 {
-            println!("\nNo funds to withdraw.");
+            report!("\nNo funds to withdraw.");
         } else if (balance - LIMIT) > 0 {
             let pb = spinner_with_style();
             pb.set_message("Connecting...");
@@ -83,14 +95,9 @@ pub async fn process_bundlr(args: BundlrArgs) -> Result<()> {
 
             // nonce
             let url = format!("{bundlr_node}/account/withdrawals/solana/?address={address}");
-            let nonce = if let Some(value) = http_client
-                .get(&url)
-                .send()
-                .await?
-                .json::<Value>()
-                .await?
-                .as_u64()
-            {
+            let response = http_client.get(&url).send().await?.json::<Value>().await?;
+            // Irys nodes return the nonce as a string, legacy nodes as a number
+            let nonce = if let Some(value) = parse_u64(&response) {
                 value
             } else {
                 pb.finish_and_clear();
@@ -121,18 +128,18 @@ pub async fn process_bundlr(args: BundlrArgs) -> Result<()> {
             pb.finish_and_clear();
 
             if response.status() == 200 {
-                println!("\nWithdraw completed.");
+                report!("\nWithdraw completed.");
             } else {
-                println!("\n{}", style("Withdraw failed.").red().bold());
+                report!("\n{}", style("Withdraw failed.").red().bold());
                 let error = response.text().await?;
                 return Err(anyhow!("Failed to complete withdraw ({})", error));
             }
         } else {
-            println!(
+            report!(
                 "\n{}",
                 style("Insufficient balance for withdraw:").red().bold()
             );
-            println!(
+            report!(
                 "  -> required balance > {} (◎ {})",
                 LIMIT,
                 LIMIT as f64 / LAMPORTS_PER_SOL as f64
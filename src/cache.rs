@@ -1,4 +1,6 @@
 use std::{
+    collections::{BTreeMap, HashSet},
+    fmt,
     ops::{Deref, DerefMut},
     path::Path,
 };
@@ -7,15 +9,28 @@ use anchor_client::solana_sdk::pubkey::Pubkey;
 use anyhow::Result;
 use mpl_candy_machine_core::ConfigLine;
 use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
 
-use crate::{common::*, pdas::find_candy_machine_creator_pda};
+use crate::{
+    candy_machine::CANDY_MACHINE_ID,
+    common::*,
+    output::style,
+    pdas::find_candy_machine_creator_pda,
+    utils::{DEVNET_HASH, MAINNET_HASH, TESTNET_HASH},
+};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Cache {
     pub program: CacheProgram,
     pub items: CacheItems,
+    /// Candy machines of the chunks of the items (`deploy --chunk`), by chunk index.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub chunks: BTreeMap<u32, CacheChunk>,
     #[serde(skip_deserializing, skip_serializing)]
     pub file_path: String,
+    /// Set when the cache is a view of a chunk (see [`load_cache_chunk`]).
+    #[serde(skip_deserializing, skip_serializing)]
+    pub chunk: Option<ChunkRange>,
 }
 
 impl Cache {
@@ -23,7 +38,9 @@ impl Cache {
         Cache {
             program: CacheProgram::new(),
             items: CacheItems::new(),
+            chunks: BTreeMap::new(),
             file_path: String::new(),
+            chunk: None,
         }
     }
 
@@ -34,9 +51,135 @@ impl Cache {
         Ok(())
     }
 
+    /// Writes the cache to its file. A chunk view is merged into the cache of the file.
     pub fn sync_file(&mut self) -> Result<()> {
         let file_path = self.file_path.clone();
-        self.write_to_file(Path::new(&file_path))
+
+        match self.chunk {
+            Some(range) => {
+                let mut cache = load_cache(&file_path, false)?;
+                cache.merge_chunk(range, self);
+                cache.write_to_file(Path::new(&file_path))
+            }
+            None => self.write_to_file(Path::new(&file_path)),
+        }
+    }
+
+    /// Cache of the items of a chunk, keyed `0..n` as their config lines in the candy machine
+    /// of the chunk (the collection item is shared by all chunks).
+    fn chunk_view(&self, range: ChunkRange) -> Cache {
+        let mut items = CacheItems::new();
+
+        if let Some(collection) = self.items.get("-1") {
+            items.insert("-1".to_string(), collection.clone());
+        }
+        for i in 0..range.items {
+            if let Some(item) = self.items.get(&(range.first_item + i).to_string()) {
+                items.insert(i.to_string(), item.clone());
+            }
+        }
+
+        let program = match self.chunks.get(&range.index) {
+            Some(chunk) => chunk.program.clone(),
+            None => {
+                // the candy machines of the chunks share the collection
+                let mut program = CacheProgram::new();
+                program.collection_mint = std::iter::once(&self.program)
+                    .chain(self.chunks.values().map(|chunk| &chunk.program))
+                    .map(|program| program.collection_mint.clone())
+                    .find(|mint| !mint.is_empty())
+                    .unwrap_or_default();
+                program
+            }
+        };
+
+        Cache {
+            program,
+            items,
+            chunks: BTreeMap::new(),
+            file_path: self.file_path.clone(),
+            chunk: Some(range),
+        }
+    }
+
+    /// Copies the items and program of a chunk view back to the cache.
+    fn merge_chunk(&mut self, range: ChunkRange, view: &Cache) {
+        for (key, item) in view.items.iter() {
+            let key = match key.parse::<u64>() {
+                Ok(i) if i < range.items => (range.first_item + i).to_string(),
+                _ if key == "-1" => key.clone(),
+                _ => continue,
+            };
+            self.items.insert(key, item.clone());
+        }
+
+        self.chunks.insert(
+            range.index,
+            CacheChunk {
+                first_item: range.first_item,
+                items: range.items,
+                program: view.program.clone(),
+            },
+        );
+    }
+
+    /// Checks that the RPC is connected to the cluster where the cache was created.
+    ///
+    /// Caches of a candy machine created before the cluster was recorded get the cluster of
+    /// the RPC, with a warning. Nothing is checked with `--allow-cluster-mismatch`
+    /// (`allow_mismatch`).
+    pub fn check_cluster(&mut self, rpc: &RpcClient, allow_mismatch: bool) -> Result<()> {
+        if allow_mismatch {
+            return Ok(());
+        }
+
+        let expected = match &self.program.genesis_hash {
+            Some(genesis_hash) => genesis_hash,
+            None if self.program.candy_machine.is_empty() => return Ok(()),
+            None => {
+                let current = rpc.get_genesis_hash()?.to_string();
+
+                report!(
+                    "{}{}",
+                    WARNING_EMOJI,
+                    style(format!(
+                        "The cache does not record the cluster of its candy machine, recording \
+                        {} (the cluster of the RPC)",
+                        cluster_name(&current)
+                    ))
+                    .yellow()
+                );
+
+                self.program.genesis_hash = Some(current);
+                if !self.file_path.is_empty() {
+                    self.sync_file()?;
+                }
+                return Ok(());
+            }
+        };
+
+        let current = rpc.get_genesis_hash()?.to_string();
+
+        if current != *expected {
+            return Err(CacheError::ClusterMismatch(
+                cluster_name(expected),
+                cluster_name(&current),
+            )
+            .into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name of the cluster with the genesis hash (or the hash itself when it is
+/// not a known cluster).
+pub fn cluster_name(genesis_hash: &str) -> String {
+    match genesis_hash {
+        DEVNET_HASH => "devnet".to_string(),
+        MAINNET_HASH => "mainnet-beta".to_string(),
+        TESTNET_HASH => "testnet".to_string(),
+        _ => genesis_hash.to_string(),
     }
 }
 
@@ -46,7 +189,24 @@ impl Default for Cache {
     }
 }
 
+/// Items of a chunk of the cache: `items` items from the index `first_item`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkRange {
+    pub index: u32,
+    pub first_item: u64,
+    pub items: u64,
+}
+
+/// Candy machine of a chunk of the cache items.
 #[derive(Debug, Deserialize, Serialize)]
+pub struct CacheChunk {
+    #[serde(rename = "firstItem")]
+    pub first_item: u64,
+    pub items: u64,
+    pub program: CacheProgram,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct CacheProgram {
     #[serde(rename = "candyMachine")]
     pub candy_machine: String,
@@ -56,6 +216,55 @@ pub struct CacheProgram {
     pub candy_machine_creator: String,
     #[serde(rename = "collectionMint")]
     pub collection_mint: String,
+    /// Merkle tree used for compressed (Bubblegum) mints.
+    #[serde(
+        rename = "merkleTree",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    pub merkle_tree: String,
+    /// Address lookup table of the v0 transactions (`--use-lut`).
+    #[serde(
+        rename = "lookupTable",
+        default,
+        skip_serializing_if = "String::is_empty"
+    )]
+    pub lookup_table: String,
+    /// Genesis hash of the cluster where the candy machine was created.
+    #[serde(
+        rename = "genesisHash",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub genesis_hash: Option<String>,
+    /// Slot of the candy machine creation transaction.
+    #[serde(
+        rename = "creationSlot",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub creation_slot: Option<u64>,
+    /// Signature of the candy machine creation transaction.
+    #[serde(
+        rename = "creationSignature",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub creation_signature: Option<String>,
+    /// Candy machine program used to create the candy machine.
+    #[serde(
+        rename = "candyMachineProgram",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub candy_machine_program: Option<String>,
+    /// Candy guard program used to create the candy guard.
+    #[serde(
+        rename = "candyGuardProgram",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub candy_guard_program: Option<String>,
 }
 
 impl CacheProgram {
@@ -65,6 +274,13 @@ impl CacheProgram {
             candy_guard: String::new(),
             candy_machine_creator: String::new(),
             collection_mint: String::new(),
+            merkle_tree: String::new(),
+            lookup_table: String::new(),
+            genesis_hash: None,
+            creation_slot: None,
+            creation_signature: None,
+            candy_machine_program: None,
+            candy_guard_program: None,
         }
     }
 
@@ -76,7 +292,54 @@ impl CacheProgram {
             candy_guard: String::new(),
             candy_machine_creator: candy_machine_creator_pda.to_string(),
             collection_mint: String::new(),
+            merkle_tree: String::new(),
+            lookup_table: String::new(),
+            genesis_hash: None,
+            creation_slot: None,
+            creation_signature: None,
+            candy_machine_program: None,
+            candy_guard_program: None,
+        }
+    }
+}
+
+impl CacheProgram {
+    /// Records the cluster, creation transaction and program of a new candy machine.
+    pub fn record_candy_machine_creation(
+        &mut self,
+        rpc: &RpcClient,
+        signature: &Signature,
+    ) -> Result<()> {
+        self.genesis_hash = Some(rpc.get_genesis_hash()?.to_string());
+        self.creation_signature = Some(signature.to_string());
+        // the slot is informative only, so a failure to retrieve it is not an error
+        self.creation_slot = rpc
+            .get_signature_statuses(&[*signature])
+            .ok()
+            .and_then(|statuses| statuses.value.into_iter().next().flatten())
+            .map(|status| status.slot);
+        self.candy_machine_program = Some(CANDY_MACHINE_ID.to_string());
+
+        Ok(())
+    }
+
+    /// Records the cluster and program of a candy machine created by a deploy that stopped
+    /// before recording it (its creation transaction is not known).
+    pub fn record_existing_candy_machine(&mut self, rpc: &RpcClient) -> Result<()> {
+        self.genesis_hash = Some(rpc.get_genesis_hash()?.to_string());
+        self.candy_machine_program = Some(CANDY_MACHINE_ID.to_string());
+
+        Ok(())
+    }
+
+    /// Records the program of a new candy guard.
+    pub fn record_candy_guard_creation(&mut self, rpc: &RpcClient) -> Result<()> {
+        if self.genesis_hash.is_none() {
+            self.genesis_hash = Some(rpc.get_genesis_hash()?.to_string());
         }
+        self.candy_guard_program = Some(mpl_candy_guard::ID.to_string());
+
+        Ok(())
     }
 }
 
@@ -106,6 +369,63 @@ impl CacheItems {
     pub fn new() -> Self {
         CacheItems(IndexMap::new())
     }
+
+    /// Checks that the keys of the items are the indices `0..n` (the `-1` collection item
+    /// aside), as `deploy` expects.
+    pub fn check_indices(&self) -> IndexReport {
+        let mut report = IndexReport::default();
+        let mut indices = HashSet::new();
+
+        for key in self.keys().filter(|key| *key != "-1") {
+            match key.parse::<u64>() {
+                Ok(index) if indices.insert(index) => (),
+                Ok(_) => report.duplicates.push(key.clone()),
+                Err(_) => report.invalid.push(key.clone()),
+            }
+        }
+
+        if let Some(last) = indices.iter().max() {
+            report.gaps = (0..*last).filter(|i| !indices.contains(i)).collect();
+        }
+
+        report
+    }
+
+    /// Rekeys the items `0..n` in the order of their indices (items with the same index keep
+    /// their order), with the collection item first. Fails with non-numeric keys, or when an
+    /// item already written to the candy machine would change index.
+    pub fn renumber(&mut self) -> Result<()> {
+        let mut keys = Vec::with_capacity(self.len());
+
+        for key in self.keys().filter(|key| *key != "-1") {
+            let index = key.parse::<u64>().map_err(|_| {
+                anyhow!("Cannot renumber the cache items: '{}' is not an index", key)
+            })?;
+            keys.push((index, key.clone()));
+        }
+        keys.sort_by_key(|(index, _)| *index);
+
+        for (new_index, (index, key)) in keys.iter().enumerate() {
+            if self[key].on_chain && *index != new_index as u64 {
+                return Err(anyhow!(
+                    "Cannot renumber the cache items: item {} is already written to the candy \
+                    machine",
+                    key
+                ));
+            }
+        }
+
+        let mut items = IndexMap::with_capacity(self.len());
+        if let Some(collection) = self.get("-1") {
+            items.insert("-1".to_string(), collection.clone());
+        }
+        for (new_index, (_, key)) in keys.iter().enumerate() {
+            items.insert(new_index.to_string(), self[key].clone());
+        }
+        self.0 = items;
+
+        Ok(())
+    }
 }
 impl Default for CacheItems {
     fn default() -> Self {
@@ -113,6 +433,65 @@ impl Default for CacheItems {
     }
 }
 
+/// Problems with the keys of the cache items.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IndexReport {
+    /// Indices without an item below the highest index.
+    pub gaps: Vec<u64>,
+    /// Keys of the same index as a previous key (e.g. `01` after `1`).
+    pub duplicates: Vec<String>,
+    /// Keys that are not an index.
+    pub invalid: Vec<String>,
+}
+
+impl IndexReport {
+    pub fn is_contiguous(&self) -> bool {
+        self.gaps.is_empty() && self.duplicates.is_empty() && self.invalid.is_empty()
+    }
+}
+
+impl fmt::Display for IndexReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut problems = Vec::new();
+
+        if !self.gaps.is_empty() {
+            problems.push(format!("missing indices: {}", format_indices(&self.gaps)));
+        }
+        if !self.duplicates.is_empty() {
+            problems.push(format!("duplicate keys: {}", self.duplicates.join(", ")));
+        }
+        if !self.invalid.is_empty() {
+            problems.push(format!("non-numeric keys: {}", self.invalid.join(", ")));
+        }
+
+        write!(f, "{}", problems.join("; "))
+    }
+}
+
+/// Formats the indices as ranges (e.g. `0-3, 7`).
+pub fn format_indices(indices: &[u64]) -> String {
+    let mut ranges: Vec<(u64, u64)> = Vec::new();
+
+    for &index in indices {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == index => *end = index,
+            _ => ranges.push((index, index)),
+        }
+    }
+
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct CacheItem {
     pub name: String,
@@ -128,6 +507,10 @@ pub struct CacheItem {
     pub animation_hash: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub animation_link: Option<String>,
+    /// Hash of the optimized copy of the image uploaded instead of the image
+    /// (`upload --optimize-images`); `image_hash` is the hash of the source image.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub optimized_image_hash: Option<String>,
 }
 
 impl CacheItem {
@@ -183,3 +566,280 @@ pub fn load_cache(cache_file_path: &str, create: bool) -> Result<Cache> {
         Ok(cache)
     }
 }
+
+/// Loads the view of a chunk of the cache, the items `[chunk * size, (chunk + 1) * size)`
+/// with the candy machine of the chunk. The `size` is needed to add a chunk, and must match
+/// the recorded one otherwise. Saving the view updates the chunk in the cache file.
+pub fn load_cache_chunk(cache_file_path: &str, chunk: u32, size: Option<u64>) -> Result<Cache> {
+    let cache = load_cache(cache_file_path, false)?;
+    let range = chunk_range(&cache, chunk, size)?;
+
+    Ok(cache.chunk_view(range))
+}
+
+/// Loads the cache, or the view of a chunk of it (see [`load_cache_chunk`]).
+pub fn load_cache_or_chunk(cache_file_path: &str, chunk: Option<u32>) -> Result<Cache> {
+    match chunk {
+        Some(chunk) => load_cache_chunk(cache_file_path, chunk, None),
+        None => load_cache(cache_file_path, false),
+    }
+}
+
+fn chunk_range(cache: &Cache, chunk: u32, size: Option<u64>) -> Result<ChunkRange> {
+    let invalid = |reason: String| CacheError::InvalidChunk(chunk, reason);
+
+    let recorded = cache.chunks.get(&chunk).map(|recorded| ChunkRange {
+        index: chunk,
+        first_item: recorded.first_item,
+        items: recorded.items,
+    });
+
+    let size = match (size, recorded) {
+        (Some(0), _) => return Err(invalid("the chunk size must be at least 1".to_string()).into()),
+        (Some(size), _) => size,
+        (None, Some(recorded)) => return Ok(recorded),
+        (None, None) => return Err(CacheError::ChunkNotFound(chunk).into()),
+    };
+
+    if !cache.program.candy_machine.is_empty() {
+        return Err(invalid(format!(
+            "the cache has a candy machine for all its items ({})",
+            cache.program.candy_machine
+        ))
+        .into());
+    }
+
+    let total = cache.items.keys().filter(|key| *key != "-1").count() as u64;
+    let first_item = chunk as u64 * size;
+
+    if first_item >= total {
+        return Err(invalid(format!(
+            "it starts at item {} but the cache has {} item(s)",
+            first_item, total
+        ))
+        .into());
+    }
+
+    let range = ChunkRange {
+        index: chunk,
+        first_item,
+        items: size.min(total - first_item),
+    };
+
+    if let Some(recorded) = recorded {
+        if recorded != range {
+            return Err(invalid(format!(
+                "it was deployed with the items {}-{}, not {}-{} (check --chunk-size)",
+                recorded.first_item,
+                recorded.first_item + recorded.items - 1,
+                range.first_item,
+                range.first_item + range.items - 1
+            ))
+            .into());
+        }
+    }
+
+    let end = range.first_item + range.items;
+    for (index, other) in &cache.chunks {
+        if *index != chunk
+            && other.first_item < end
+            && range.first_item < other.first_item + other.items
+        {
+            return Err(invalid(format!("its items overlap the items of chunk {}", index)).into());
+        }
+    }
+
+    Ok(range)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_client::{rpc_client::Mocks, rpc_request::RpcRequest};
+
+    use super::*;
+
+    fn items(keys: &[&str]) -> CacheItems {
+        CacheItems(
+            keys.iter()
+                .map(|key| {
+                    let item = CacheItem {
+                        name: format!("Item {}", key),
+                        image_hash: String::new(),
+                        image_link: String::new(),
+                        metadata_hash: String::new(),
+                        metadata_link: format!("https://arweave.net/{}", key),
+                        on_chain: false,
+                        animation_hash: None,
+                        animation_link: None,
+                        optimized_image_hash: None,
+                    };
+                    (key.to_string(), item)
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_check_indices() {
+        assert!(items(&["-1", "0", "1", "2"])
+            .check_indices()
+            .is_contiguous());
+        assert!(items(&["1", "0"]).check_indices().is_contiguous());
+        assert!(items(&[]).check_indices().is_contiguous());
+
+        let report = items(&["-1", "0", "1", "3", "6"]).check_indices();
+        assert_eq!(report.gaps, vec![2, 4, 5]);
+        assert_eq!(report.to_string(), "missing indices: 2, 4-5");
+
+        let report = items(&["0", "1", "01", "x", "2"]).check_indices();
+        assert_eq!(report.duplicates, vec!["01"]);
+        assert_eq!(report.invalid, vec!["x"]);
+        assert!(report.gaps.is_empty());
+        assert_eq!(
+            report.to_string(),
+            "duplicate keys: 01; non-numeric keys: x"
+        );
+    }
+
+    fn genesis_rpc(genesis_hash: &str) -> RpcClient {
+        let mut mocks = Mocks::new();
+        mocks.insert(RpcRequest::GetGenesisHash, json!(genesis_hash));
+        RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks)
+    }
+
+    #[test]
+    fn test_check_cluster() {
+        let mut cache = Cache::new();
+        cache.program.genesis_hash = Some(DEVNET_HASH.to_string());
+
+        assert!(cache
+            .check_cluster(&genesis_rpc(DEVNET_HASH), false)
+            .is_ok());
+        assert_eq!(
+            cache
+                .check_cluster(&genesis_rpc(MAINNET_HASH), false)
+                .unwrap_err()
+                .to_string(),
+            "Cache was created on devnet but the RPC is connected to mainnet-beta. Use \
+            --allow-cluster-mismatch to override."
+        );
+        assert!(cache
+            .check_cluster(&genesis_rpc(MAINNET_HASH), true)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_check_cluster_records_old_caches() {
+        // nothing to record before the candy machine is created
+        let mut cache = Cache::new();
+        cache
+            .check_cluster(&genesis_rpc(MAINNET_HASH), false)
+            .unwrap();
+        assert_eq!(cache.program.genesis_hash, None);
+
+        cache.program.candy_machine = Pubkey::new_unique().to_string();
+        cache
+            .check_cluster(&genesis_rpc(MAINNET_HASH), false)
+            .unwrap();
+        assert_eq!(cache.program.genesis_hash.as_deref(), Some(MAINNET_HASH));
+
+        assert!(cache
+            .check_cluster(&genesis_rpc(DEVNET_HASH), false)
+            .is_err());
+    }
+
+    #[test]
+    fn test_renumber() {
+        let mut cache_items = items(&["3", "-1", "0", "01", "7"]);
+        cache_items.renumber().unwrap();
+
+        let keys: Vec<&str> = cache_items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["-1", "0", "1", "2", "3"]);
+        let names: Vec<&str> = cache_items
+            .values()
+            .map(|item| item.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["Item -1", "Item 0", "Item 01", "Item 3", "Item 7"]
+        );
+
+        assert!(items(&["0", "x"]).renumber().is_err());
+
+        // an item written to the candy machine keeps its index
+        let mut cache_items = items(&["0", "2"]);
+        cache_items["2"].on_chain = true;
+        assert!(cache_items.renumber().is_err());
+        assert!(cache_items.contains_key("2"));
+    }
+
+    #[test]
+    fn test_chunk_range() {
+        let mut cache = Cache::new();
+        cache.items = items(&["-1", "0", "1", "2", "3", "4", "5", "6"]);
+
+        let range = chunk_range(&cache, 2, Some(3)).unwrap();
+        assert_eq!(
+            range,
+            ChunkRange {
+                index: 2,
+                first_item: 6,
+                items: 1
+            }
+        );
+
+        assert!(chunk_range(&cache, 3, Some(3)).is_err());
+        assert!(chunk_range(&cache, 0, Some(0)).is_err());
+        assert!(matches!(
+            chunk_range(&cache, 0, None)
+                .unwrap_err()
+                .downcast_ref::<CacheError>(),
+            Some(CacheError::ChunkNotFound(0))
+        ));
+
+        cache.chunks.insert(
+            0,
+            CacheChunk {
+                first_item: 0,
+                items: 4,
+                program: CacheProgram::new(),
+            },
+        );
+        assert_eq!(
+            chunk_range(&cache, 0, None).unwrap(),
+            chunk_range(&cache, 0, Some(4)).unwrap()
+        );
+        // a different size
+        assert!(chunk_range(&cache, 0, Some(3)).is_err());
+        // the items 3-5 overlap the chunk 0
+        assert!(chunk_range(&cache, 1, Some(3)).is_err());
+        assert!(chunk_range(&cache, 1, Some(4)).is_ok());
+    }
+
+    #[test]
+    fn test_chunk_view_is_merged_back() {
+        let mut cache = Cache::new();
+        cache.items = items(&["-1", "0", "1", "2", "3", "4"]);
+        cache.program.collection_mint = "collection".to_string();
+
+        let range = chunk_range(&cache, 1, Some(2)).unwrap();
+        let mut view = cache.chunk_view(range);
+
+        let keys: Vec<&str> = view.items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["-1", "0", "1"]);
+        assert_eq!(view.items["0"].name, "Item 2");
+        assert_eq!(view.program.collection_mint, "collection");
+
+        view.program.candy_machine = "candy machine".to_string();
+        view.items["1"].on_chain = true;
+        cache.merge_chunk(range, &view);
+
+        assert!(cache.items["3"].on_chain);
+        assert!(!cache.items["1"].on_chain);
+        assert_eq!(cache.chunks[&1].first_item, 2);
+        assert_eq!(cache.chunks[&1].items, 2);
+        assert_eq!(cache.chunks[&1].program.candy_machine, "candy machine");
+        // the top-level program is left as it was
+        assert!(cache.program.candy_machine.is_empty());
+    }
+}
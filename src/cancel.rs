@@ -0,0 +1,172 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::Duration,
+};
+
+use anyhow::Result;
+use thiserror::Error;
+
+/// Exit code of a command that was cancelled after saving its progress: the work done is
+/// recorded (cache, results files) and re-running the command resumes it.
+pub const EXIT_PARTIAL: i32 = 3;
+
+/// Time given to a cancelled command to save its progress before the process exits.
+pub const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+static CANCELLABLE: AtomicBool = AtomicBool::new(false);
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+static REASON: Mutex<Option<CancelReason>> = Mutex::new(None);
+
+static INTERRUPTED: Mutex<Option<Arc<AtomicBool>>> = Mutex::new(None);
+
+/// Reason for cancelling a command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelReason {
+    /// Ctrl-C was pressed.
+    Interrupted,
+    /// The command timeout (in seconds) expired.
+    TimedOut(u64),
+}
+
+impl fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CancelReason::Interrupted => write!(f, "Operation interrupted"),
+            CancelReason::TimedOut(seconds) => write!(f, "Timed out after {seconds} seconds"),
+        }
+    }
+}
+
+/// Error returned by a command that stopped early because it was cancelled.
+#[derive(Debug, Error)]
+#[error("{reason}, the progress was saved and re-running the command resumes it")]
+pub struct Cancelled {
+    pub reason: CancelReason,
+}
+
+/// Outcome of a cancellation request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CancelStatus {
+    /// The running loop stops at its next check and saves its progress.
+    Pending,
+    /// The command cannot stop by itself (or was already cancelled), the process has to exit.
+    Abort,
+}
+
+/// Installs the Ctrl-C handler and, with a timeout, the command timer.
+///
+/// `interrupted` is the flag shared with the upload and deploy loops: they clear it while
+/// they can be stopped and check it between items. Other loops mark themselves with
+/// [`cancellable`] and check [`is_cancelled`]. Cancelling outside of these loops calls
+/// `on_abort` immediately, as does a second Ctrl-C; a command that does not stop within
+/// [`CANCEL_GRACE_PERIOD`] also gets `on_abort`, which is expected to exit the process.
+pub fn install_cancel_handler(
+    interrupted: Arc<AtomicBool>,
+    timeout: Option<u64>,
+    on_abort: fn(CancelReason),
+) -> Result<()> {
+    *INTERRUPTED.lock().unwrap() = Some(interrupted);
+
+    ctrlc::set_handler(move || handle_cancel(CancelReason::Interrupted, on_abort))?;
+
+    if let Some(timeout) = timeout {
+        thread::spawn(move || {
+            thread::sleep(Duration::from_secs(timeout));
+            handle_cancel(CancelReason::TimedOut(timeout), on_abort);
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_cancel(reason: CancelReason, on_abort: fn(CancelReason)) {
+    match cancel(reason) {
+        CancelStatus::Abort => on_abort(reason),
+        CancelStatus::Pending => {
+            // the command might be stuck waiting on the RPC
+            thread::spawn(move || {
+                thread::sleep(CANCEL_GRACE_PERIOD);
+                on_abort(reason);
+            });
+        }
+    }
+}
+
+/// Requests the cancellation of the running command.
+pub fn cancel(reason: CancelReason) -> CancelStatus {
+    let interrupted = INTERRUPTED.lock().unwrap().clone();
+    // upload and deploy loops clear the flag while running
+    let in_loop = interrupted
+        .as_ref()
+        .map(|flag| !flag.load(Ordering::SeqCst))
+        .unwrap_or(false);
+
+    if CANCELLED.load(Ordering::SeqCst) || !(in_loop || CANCELLABLE.load(Ordering::SeqCst)) {
+        // we really need to exit
+        return CancelStatus::Abort;
+    }
+
+    REASON.lock().unwrap().get_or_insert(reason);
+    CANCELLED.store(true, Ordering::SeqCst);
+
+    if let Some(interrupted) = interrupted {
+        // signal that we want to exit
+        interrupted.store(true, Ordering::SeqCst);
+    }
+
+    CancelStatus::Pending
+}
+
+/// Returns `true` if the command was cancelled.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Returns the error for a cancelled command.
+pub fn cancelled() -> Cancelled {
+    Cancelled {
+        reason: REASON.lock().unwrap().unwrap_or(CancelReason::Interrupted),
+    }
+}
+
+/// Returns an error if the command was cancelled.
+pub fn check_cancelled() -> std::result::Result<(), Cancelled> {
+    if is_cancelled() {
+        Err(cancelled())
+    } else {
+        Ok(())
+    }
+}
+
+/// Marks the command as cancellable until the returned guard is dropped.
+pub fn cancellable() -> CancellableGuard {
+    CANCELLABLE.store(true, Ordering::SeqCst);
+    CancellableGuard
+}
+
+pub struct CancellableGuard;
+
+impl Drop for CancellableGuard {
+    fn drop(&mut self) {
+        CANCELLABLE.store(false, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancel_outside_of_a_loop_aborts() {
+        // nothing can stop by itself, the caller has to exit (and nothing is recorded)
+        assert_eq!(cancel(CancelReason::Interrupted), CancelStatus::Abort);
+        assert!(!is_cancelled());
+    }
+}
@@ -2,9 +2,14 @@ use anchor_client::{solana_sdk::pubkey::Pubkey, ClientError};
 use anchor_lang::AnchorDeserialize;
 use anyhow::{anyhow, Result};
 pub use mpl_candy_machine_core::ID as CANDY_MACHINE_ID;
-use mpl_candy_machine_core::{CandyMachine, CandyMachineData};
+use mpl_candy_machine_core::{
+    constants::HIDDEN_SECTION, CandyMachine, CandyMachineData, ConfigLineSettings,
+};
 
-use crate::{config::data::SugarConfig, pdas::get_metadata_pda, setup::setup_client};
+use crate::{
+    config::data::SugarConfig, constants::STRING_LEN_SIZE, pdas::get_metadata_pda,
+    setup::setup_client,
+};
 
 // To test a custom candy machine program, comment the mpl_candy_machine::ID line
 // above and use the following lines to declare the id to use:
@@ -60,3 +65,48 @@ pub fn load_candy_machine(
 
     Ok((candy_machine, rule_set))
 }
+
+/// Reads a candy machine account, returning its state and whether each of its config lines
+/// was written (empty when the candy machine has hidden settings).
+pub fn get_config_line_state(
+    sugar_config: &SugarConfig,
+    candy_machine_id: &Pubkey,
+) -> Result<(CandyMachine, Vec<bool>)> {
+    let client = setup_client(sugar_config)?;
+    let program = client.program(CANDY_MACHINE_ID);
+    // retrieves the account data
+    let data = program.rpc().get_account_data(candy_machine_id)?;
+    let candy_machine = CandyMachine::deserialize(&mut &data[8..])?;
+
+    let loaded = match &candy_machine.data.config_line_settings {
+        Some(settings) => loaded_config_lines(&data, candy_machine.data.items_available, settings)?,
+        None => Vec::new(),
+    };
+
+    Ok((candy_machine, loaded))
+}
+
+/// Decodes the bitmask that follows the config lines of a raw candy machine account, which
+/// records the lines that were written.
+pub fn loaded_config_lines(
+    data: &[u8],
+    items_available: u64,
+    settings: &ConfigLineSettings,
+) -> Result<Vec<bool>> {
+    let line_size = (settings.name_length + settings.uri_length) as usize;
+
+    let lines_start = HIDDEN_SECTION + STRING_LEN_SIZE;
+    let bitmask_start = lines_start + (items_available as usize) * line_size;
+    let bitmask_end = bitmask_start + (items_available / 8 + 1) as usize;
+
+    if data.len() < bitmask_end {
+        return Err(anyhow!(
+            "Candy machine account is too small for {} config line(s)",
+            items_available
+        ));
+    }
+
+    Ok((0..items_available)
+        .map(|i| data[bitmask_start + (i / 8) as usize] & (0x80 >> (i % 8)) != 0)
+        .collect())
+}
@@ -0,0 +1,3 @@
+pub mod token_accounts;
+
+pub use token_accounts::*;
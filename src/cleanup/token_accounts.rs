@@ -0,0 +1,539 @@
+use std::{collections::HashSet, fmt};
+
+use anchor_client::solana_sdk::{
+    account::Account, compute_budget::ComputeBudgetInstruction, instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL, program_pack::Pack,
+};
+use mpl_candy_guard::state::{CandyGuardData, GuardSet, DATA_OFFSET};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::{
+    instruction::{burn, close_account},
+    state::{Account as SplAccount, Mint},
+};
+
+use crate::{
+    account_fetcher::AccountFetcher,
+    cache::load_cache,
+    candy_machine::CANDY_MACHINE_ID,
+    common::*,
+    freeze::find_freeze_pda,
+    guard::find_wrapping_candy_guard,
+    output::style,
+    priority_fee::{resolve_priority_fee, PriorityFee},
+    utils::*,
+};
+
+/// Number of token accounts closed by each transaction.
+const ACCOUNTS_PER_TX: usize = 8;
+
+/// Compute units requested by each cleanup transaction.
+const COMPUTE_UNITS: u32 = 100_000;
+
+pub struct CleanupTokenAccountsArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub owner: Option<String>,
+    pub mint: Option<String>,
+    pub close_empty: bool,
+    pub burn_dust: bool,
+    pub max_dust: Option<u64>,
+    pub dry_run: bool,
+    pub priority_fee: PriorityFee,
+    pub max_total_priority_sol: Option<f64>,
+    pub allow_cluster_mismatch: bool,
+}
+
+/// Action taken on a token account.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CleanupAction {
+    /// Close an empty account.
+    Close,
+    /// Burn the (dust) balance and close the account.
+    BurnAndClose,
+}
+
+impl fmt::Display for CleanupAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CleanupAction::Close => write!(f, "close"),
+            CleanupAction::BurnAndClose => write!(f, "burn & close"),
+        }
+    }
+}
+
+/// Which token accounts are cleaned up.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CleanupOptions {
+    pub close_empty: bool,
+    pub burn_dust: bool,
+    pub max_dust: u64,
+}
+
+struct Candidate {
+    address: Pubkey,
+    account: SplAccount,
+    lamports: u64,
+    action: CleanupAction,
+}
+
+pub fn process_cleanup_token_accounts(args: CleanupTokenAccountsArgs) -> Result<()> {
+    if args.burn_dust && args.max_dust.is_none() {
+        return Err(anyhow!("--burn-dust requires --max-dust <AMOUNT>"));
+    }
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(CANDY_MACHINE_ID);
+    let rpc = program.rpc();
+
+    let owner = match &args.owner {
+        Some(owner) => Pubkey::from_str(owner)
+            .map_err(|_| anyhow!("Failed to parse owner address: {}", owner))?,
+        None => sugar_config.keypair.pubkey(),
+    };
+    let mint = args
+        .mint
+        .as_ref()
+        .map(|mint| Pubkey::from_str(mint).map_err(|_| anyhow!("Failed to parse mint: {}", mint)))
+        .transpose()?;
+
+    let options = CleanupOptions {
+        close_empty: args.close_empty,
+        burn_dust: args.burn_dust,
+        max_dust: args.max_dust.unwrap_or_default(),
+    };
+    // without an action, the accounts that would be closed with --close-empty are listed
+    let dry_run = args.dry_run || !(options.close_empty || options.burn_dust);
+    let options = if options.close_empty || options.burn_dust {
+        options
+    } else {
+        CleanupOptions {
+            close_empty: true,
+            ..options
+        }
+    };
+
+    if !dry_run && owner != sugar_config.keypair.pubkey() {
+        return Err(anyhow!(
+            "Token accounts of {} can only be closed by the owner, use --dry-run to list them",
+            owner
+        ));
+    }
+
+    report!(
+        "{} {}Loading token accounts",
+        style("[1/3]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let accounts = get_owner_token_accounts(&rpc, &owner, mint.as_ref())?;
+
+    // mint decimals are only needed to protect NFTs from the dust burn
+    let fetcher = AccountFetcher::new(rpc);
+    let decimals = if options.burn_dust {
+        let mints: Vec<Pubkey> = accounts
+            .iter()
+            .map(|(_, account, _)| account.mint)
+            .collect();
+        fetcher
+            .get_multiple_accounts(&mints)?
+            .into_iter()
+            .map(|account| account.and_then(|a| Mint::unpack(&a.data).ok().map(|m| m.decimals)))
+            .collect()
+    } else {
+        vec![None; accounts.len()]
+    };
+
+    pb.finish_with_message(format!("Found {} token account(s)", accounts.len()));
+
+    report!(
+        "\n{} {}Checking candy machine accounts",
+        style("[2/3]").bold().dim(),
+        GUARD_EMOJI
+    );
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let protected =
+        get_protected_token_accounts(&program, &args.cache, args.allow_cluster_mismatch)?;
+
+    pb.finish_with_message(format!(
+        "{} account(s) in use by the candy machine",
+        protected.len()
+    ));
+
+    let candidates: Vec<Candidate> = accounts
+        .into_iter()
+        .zip(decimals)
+        .filter(|((address, _, _), _)| !protected.contains(address))
+        .filter_map(|((address, account, lamports), decimals)| {
+            get_cleanup_action(&account, decimals, &owner, &options).map(|action| Candidate {
+                address,
+                account,
+                lamports,
+                action,
+            })
+        })
+        .collect();
+
+    report!(
+        "\n{} {}{}",
+        style("[3/3]").bold().dim(),
+        COMPUTER_EMOJI,
+        if dry_run {
+            "Listing token accounts (dry run)"
+        } else {
+            "Closing token accounts"
+        }
+    );
+
+    if candidates.is_empty() {
+        report!("\nNo token accounts to clean up.");
+        return Ok(());
+    }
+
+    let reclaimable: u64 = candidates.iter().map(|c| c.lamports).sum();
+
+    if dry_run {
+        for candidate in &candidates {
+            report!(
+                "{} {} (mint {}, amount {}): {}",
+                style("=>").dim(),
+                candidate.address,
+                candidate.account.mint,
+                candidate.account.amount,
+                candidate.action
+            );
+        }
+        report!(
+            "\n{} token account(s) holding {} SOL of rent",
+            candidates.len(),
+            reclaimable as f64 / LAMPORTS_PER_SOL as f64
+        );
+        return Ok(());
+    }
+
+    let batches: Vec<&[Candidate]> = candidates.chunks(ACCOUNTS_PER_TX).collect();
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        COMPUTE_UNITS,
+        batches.len() as u64,
+        args.max_total_priority_sol,
+    )?;
+
+    let pb = progress_bar_with_style(candidates.len() as u64);
+    pb.set_message("Closing accounts... ");
+
+    let keypair = &sugar_config.keypair;
+    let rpc = program.rpc();
+    let mut closed = 0;
+    let mut reclaimed = 0;
+    let mut errors = Vec::new();
+
+    for batch in batches {
+        let mut instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_UNITS),
+            ComputeBudgetInstruction::set_compute_unit_price(priority_fee),
+        ];
+        for candidate in batch {
+            instructions.extend(cleanup_instructions(candidate, &owner)?);
+        }
+
+        let result = rpc.get_latest_blockhash().and_then(|blockhash| {
+            let tx = Transaction::new_signed_with_payer(
+                &instructions,
+                Some(&keypair.pubkey()),
+                &[keypair],
+                blockhash,
+            );
+            rpc.send_and_confirm_transaction(&tx)
+        });
+
+        match result {
+            Ok(_) => {
+                closed += batch.len();
+                reclaimed += batch.iter().map(|c| c.lamports).sum::<u64>();
+            }
+            Err(err) => {
+                error!("Failed to close token accounts: {:?}", err);
+                errors.push(err);
+            }
+        }
+
+        pb.inc(batch.len() as u64);
+    }
+
+    if errors.is_empty() {
+        pb.finish();
+    } else {
+        pb.abandon_with_message(format!("{}", style("Cleanup failed ").red().bold()));
+    }
+
+    report!(
+        "\n{}Closed {} token account(s), reclaimed {} SOL",
+        MONEY_BAG_EMOJI,
+        closed,
+        reclaimed as f64 / LAMPORTS_PER_SOL as f64
+    );
+
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "{} of {} token account(s) could not be closed: {}",
+            candidates.len() - closed,
+            candidates.len(),
+            errors[0]
+        ));
+    }
+
+    Ok(())
+}
+
+/// Returns the action to take on the token account, if any. Frozen accounts and accounts
+/// that the owner cannot close are left untouched; dust is never burned from accounts of
+/// mints without decimals (or unknown decimals) to protect NFTs.
+pub fn get_cleanup_action(
+    account: &SplAccount,
+    decimals: Option<u8>,
+    owner: &Pubkey,
+    options: &CleanupOptions,
+) -> Option<CleanupAction> {
+    if account.is_frozen() {
+        return None;
+    }
+
+    if let Some(close_authority) = Option::<Pubkey>::from(account.close_authority) {
+        if close_authority != *owner {
+            return None;
+        }
+    }
+
+    if account.amount == 0 {
+        return options.close_empty.then_some(CleanupAction::Close);
+    }
+
+    let fungible = matches!(decimals, Some(decimals) if decimals > 0);
+
+    if options.burn_dust && fungible && !account.is_native() && account.amount <= options.max_dust {
+        Some(CleanupAction::BurnAndClose)
+    } else {
+        None
+    }
+}
+
+/// Returns the token accounts used by the candy guard of the cache's candy machine: the
+/// freeze escrow accounts and the payment destinations of the token payment guards.
+pub fn get_protected_token_accounts<C: std::ops::Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    cache_file: &str,
+    allow_cluster_mismatch: bool,
+) -> Result<HashSet<Pubkey>> {
+    let mut protected = HashSet::new();
+
+    if !Path::new(cache_file).exists() {
+        return Ok(protected);
+    }
+
+    let mut cache = load_cache(cache_file, false)?;
+    cache.check_cluster(&program.rpc(), allow_cluster_mismatch)?;
+
+    let candy_machine = match Pubkey::from_str(&cache.program.candy_machine) {
+        Ok(candy_machine) => candy_machine,
+        Err(_) => return Ok(protected),
+    };
+
+    let candy_guard = match Pubkey::from_str(&cache.program.candy_guard) {
+        Ok(candy_guard) => candy_guard,
+        Err(_) => match find_wrapping_candy_guard(program, &candy_machine) {
+            Ok(candy_guard) => candy_guard,
+            Err(_) => return Ok(protected),
+        },
+    };
+
+    let account_data = match program.rpc().get_account_data(&candy_guard) {
+        Ok(account_data) => account_data,
+        Err(_) => return Ok(protected),
+    };
+    let candy_guard_data = CandyGuardData::load(&account_data[DATA_OFFSET..])?;
+
+    let mut guard_sets: Vec<&GuardSet> = vec![&candy_guard_data.default];
+    if let Some(groups) = &candy_guard_data.groups {
+        guard_sets.extend(groups.iter().map(|group| &group.guards));
+    }
+
+    for guards in guard_sets {
+        if let Some(guard) = &guards.freeze_token_payment {
+            let (freeze_escrow, _) =
+                find_freeze_pda(&candy_guard, &candy_machine, &guard.destination_ata);
+            protected.insert(guard.destination_ata);
+            protected.insert(get_associated_token_address(&freeze_escrow, &guard.mint));
+        }
+        if let Some(guard) = &guards.token_payment {
+            protected.insert(guard.destination_ata);
+        }
+    }
+
+    Ok(protected)
+}
+
+fn cleanup_instructions(candidate: &Candidate, owner: &Pubkey) -> Result<Vec<Instruction>> {
+    let mut instructions = Vec::new();
+
+    if candidate.action == CleanupAction::BurnAndClose {
+        instructions.push(burn(
+            &spl_token::ID,
+            &candidate.address,
+            &candidate.account.mint,
+            owner,
+            &[],
+            candidate.account.amount,
+        )?);
+    }
+
+    instructions.push(close_account(
+        &spl_token::ID,
+        &candidate.address,
+        owner,
+        owner,
+        &[],
+    )?);
+
+    Ok(instructions)
+}
+
+// returns the token accounts (address, account, lamports) of the owner
+fn get_owner_token_accounts(
+    client: &RpcClient,
+    owner: &Pubkey,
+    mint: Option<&Pubkey>,
+) -> Result<Vec<(Pubkey, SplAccount, u64)>> {
+    let mut filters = vec![
+        RpcFilterType::DataSize(SplAccount::LEN as u64),
+        // owner is stored after the mint
+        RpcFilterType::Memcmp(Memcmp::new_base58_encoded(32, owner.as_ref())),
+    ];
+    if let Some(mint) = mint {
+        filters.push(RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            0,
+            mint.as_ref(),
+        )));
+    }
+
+    let config = RpcProgramAccountsConfig {
+        filters: Some(filters),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            data_slice: None,
+            commitment: Some(CommitmentConfig::confirmed()),
+            min_context_slot: None,
+        },
+        with_context: None,
+    };
+
+    let accounts = client.get_program_accounts_with_config(&spl_token::ID, config)?;
+
+    accounts
+        .into_iter()
+        .map(|(address, Account { data, lamports, .. })| {
+            let account = SplAccount::unpack(&data)
+                .map_err(|_| anyhow!("Failed to unpack token account {}", address))?;
+            Ok((address, account, lamports))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::program_option::COption;
+    use spl_token::state::AccountState;
+
+    use super::*;
+
+    fn token_account(amount: u64) -> SplAccount {
+        SplAccount {
+            mint: Pubkey::new_unique(),
+            owner: Pubkey::new_unique(),
+            amount,
+            state: AccountState::Initialized,
+            ..SplAccount::default()
+        }
+    }
+
+    const OPTIONS: CleanupOptions = CleanupOptions {
+        close_empty: true,
+        burn_dust: true,
+        max_dust: 10,
+    };
+
+    #[test]
+    fn closes_empty_accounts() {
+        let owner = Pubkey::new_unique();
+        let account = token_account(0);
+
+        assert_eq!(
+            get_cleanup_action(&account, None, &owner, &OPTIONS),
+            Some(CleanupAction::Close)
+        );
+
+        let options = CleanupOptions {
+            close_empty: false,
+            ..OPTIONS
+        };
+        assert_eq!(get_cleanup_action(&account, None, &owner, &options), None);
+    }
+
+    #[test]
+    fn burns_dust_of_fungible_tokens_only() {
+        let owner = Pubkey::new_unique();
+
+        assert_eq!(
+            get_cleanup_action(&token_account(10), Some(6), &owner, &OPTIONS),
+            Some(CleanupAction::BurnAndClose)
+        );
+        assert_eq!(
+            get_cleanup_action(&token_account(11), Some(6), &owner, &OPTIONS),
+            None
+        );
+        // NFTs and unknown mints are never burned
+        assert_eq!(
+            get_cleanup_action(&token_account(1), Some(0), &owner, &OPTIONS),
+            None
+        );
+        assert_eq!(
+            get_cleanup_action(&token_account(1), None, &owner, &OPTIONS),
+            None
+        );
+    }
+
+    #[test]
+    fn skips_frozen_and_delegated_close_authority() {
+        let owner = Pubkey::new_unique();
+
+        let mut frozen = token_account(0);
+        frozen.state = AccountState::Frozen;
+        assert_eq!(get_cleanup_action(&frozen, None, &owner, &OPTIONS), None);
+
+        let mut other_authority = token_account(0);
+        other_authority.close_authority = COption::Some(Pubkey::new_unique());
+        assert_eq!(
+            get_cleanup_action(&other_authority, None, &owner, &OPTIONS),
+            None
+        );
+
+        other_authority.close_authority = COption::Some(owner);
+        assert_eq!(
+            get_cleanup_action(&other_authority, None, &owner, &OPTIONS),
+            Some(CleanupAction::Close)
+        );
+    }
+}
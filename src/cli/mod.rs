@@ -1,11 +1,30 @@
+pub mod create_config;
+pub mod terminal;
+
 use clap::{Parser, Subcommand};
 
 use crate::{
-    config::TokenStandard,
+    ardrive::{
+        listing::DEFAULT_LISTING_TTL_MINUTES, EntityType, OutputFormat,
+        DEFAULT_DOWNLOAD_CONCURRENCY, DEFAULT_MAX_RETRIES, DEFAULT_RETRY_DELAY_MS,
+        DEFAULT_UPLOAD_CONCURRENCY, DEFAULT_VERIFY_CONCURRENCY,
+    },
+    bundlr::BundlrAction,
+    config::{BundlrNode, TokenStandard},
     constants::{
         DEFAULT_AIRDROP_LIST, DEFAULT_AIRDROP_LIST_HELP, DEFAULT_ASSETS, DEFAULT_CACHE,
-        DEFAULT_CONFIG, DEFAULT_PRIORITY_FEE,
+        DEFAULT_CONFIG, DEFAULT_IPFS_GATEWAY, DEFAULT_PRIORITY_FEE, DEFAULT_RECEIPT_TIMEOUT_SECS,
+        DEFAULT_UPLOAD_ERROR_REPORT,
     },
+    deploy::{DEFAULT_DEPLOY_PARALLELISM, DEFAULT_MAX_RESUBMITS},
+    estimate::{DEFAULT_NAME_LENGTH, DEFAULT_URI_LENGTH},
+    export_metadata::DEFAULT_EXPORT_PARALLEL,
+    import_nfts::{
+        ImportFormat, NameTemplate, DEFAULT_FLUSH_EVERY, DEFAULT_HASHLIST_BATCH_SIZE,
+        DEFAULT_IMPORT_CONCURRENCY, DEFAULT_NAME_TEMPLATE,
+    },
+    priority_fee::PriorityFee,
+    upload::{UploadScope, DEFAULT_UPLOAD_FLUSH_EVERY},
 };
 
 #[derive(Parser)]
@@ -15,10 +34,40 @@ pub struct Cli {
     #[clap(short, long, global = true)]
     pub log_level: Option<String>,
 
+    /// Skip the check that the RPC is connected to the cluster recorded in the cache
+    #[clap(long, global = true, alias = "ignore-cluster-check")]
+    pub allow_cluster_mismatch: bool,
+
+    /// Write newline-delimited JSON progress events to a file, FIFO, unix socket or "-" (stdout)
+    #[clap(long, global = true, value_name = "PATH|-")]
+    pub progress_stream: Option<String>,
+
+    /// Stop the command after the given number of seconds, saving its progress (exits with
+    /// code 3). Named `--command-timeout` because several commands already have a `--timeout`
+    /// for the RPC requests of the mint list
+    #[clap(long, global = true, value_name = "SECONDS")]
+    pub command_timeout: Option<u64>,
+
+    /// Compute unit limit of the transactions [default: set by each command]
+    #[clap(long, global = true, value_name = "N")]
+    pub compute_unit_limit: Option<u32>,
+
+    /// Simulate each transaction before sending it, setting its compute unit limit to the
+    /// units consumed plus 10%; nothing is sent when a simulation fails
+    #[clap(long, global = true)]
+    pub simulate: bool,
+
     #[clap(subcommand)]
     pub command: Commands,
 }
 
+impl Cli {
+    /// Returns `true` if the command only prints its summary line (`ardrive --quiet`).
+    pub fn quiet(&self) -> bool {
+        matches!(self.command, Commands::Ardrive { quiet: true, .. })
+    }
+}
+
 #[derive(Subcommand)]
 pub enum Commands {
     /// Interact with the bundlr network
@@ -31,10 +80,31 @@ pub enum Commands {
         #[clap(short, long)]
         rpc_url: Option<String>,
 
+        /// Path to the config file, read for the bundlrNode when present
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Bundlr (Irys) node: node1, node2, devnet or the URL of a node
+        /// [default: bundlrNode of the config file, or the node of the cluster]
+        #[clap(long, value_name = "NODE")]
+        bundlr_node: Option<BundlrNode>,
+
         #[clap(subcommand)]
         action: BundlrAction,
     },
 
+    /// Manage the cache file
+    Cache {
+        #[clap(subcommand)]
+        command: CacheSubcommands,
+    },
+
+    /// Close unused token accounts to reclaim their rent
+    Cleanup {
+        #[clap(subcommand)]
+        command: CleanupCommand,
+    },
+
     /// Manage the collection on the candy machine
     Collection {
         #[clap(subcommand)]
@@ -61,9 +131,13 @@ pub enum Commands {
         #[clap(short, long)]
         rpc_url: Option<String>,
 
-        /// Priority fee value
-        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
-        priority_fee: u64,
+        /// Priority fee per compute unit ("500", "500cu") or per transaction ("0.0001sol-per-tx")
+        #[clap(short, long, default_value_t = PriorityFee::default())]
+        priority_fee: PriorityFee,
+
+        /// Maximum total priority fee (in SOL), lowers the priority fee to stay within it
+        #[clap(long)]
+        max_total_priority_sol: Option<f64>,
 
         /// Path to the cache file, defaults to "cache.json"
         #[clap(long, default_value = DEFAULT_CACHE)]
@@ -72,6 +146,98 @@ pub enum Commands {
         /// The optional collection address where the candymachine will mint the tokens to
         #[clap(long)]
         collection_mint: Option<String>,
+
+        /// Report what the deploy would change on-chain (transactions, fees and rent) without
+        /// sending any transaction
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Deploy even if the config number does not match the cache
+        #[clap(long)]
+        force: bool,
+
+        /// Number of config lines per transaction [default: as many as fit in a transaction]
+        #[clap(long, value_name = "N")]
+        lines_per_tx: Option<usize>,
+
+        /// Number of config line transactions sent in parallel (waiting for confirmation)
+        #[clap(long, default_value_t = DEFAULT_DEPLOY_PARALLELISM, value_name = "N")]
+        parallelism: usize,
+
+        /// Number of times a config line transaction whose blockhash expired before it landed
+        /// is re-signed and sent again
+        #[clap(long, default_value_t = DEFAULT_MAX_RESUBMITS, value_name = "N")]
+        max_resubmits: u32,
+
+        /// Write the transactions unsigned (base64) to this directory instead of sending them
+        #[clap(long, value_name = "DIR")]
+        unsigned_tx_out: Option<String>,
+
+        /// Authority (and fee payer) of the unsigned transactions [default: the keypair]
+        #[clap(long, value_name = "PUBKEY", requires = "unsigned_tx_out")]
+        authority: Option<String>,
+
+        /// Durable nonce account of the unsigned transactions, one per transaction (can be
+        /// repeated)
+        #[clap(
+            long,
+            value_name = "PUBKEY",
+            multiple_occurrences = true,
+            requires = "unsigned_tx_out"
+        )]
+        nonce_account: Vec<String>,
+
+        /// Send the config lines in v0 transactions with the lookup table of the cache
+        #[clap(long, conflicts_with = "unsigned_tx_out")]
+        use_lut: bool,
+
+        /// Deploy the items of a chunk of the cache in their own candy machine
+        #[clap(long, value_name = "INDEX", conflicts_with = "unsigned_tx_out")]
+        chunk: Option<u32>,
+
+        /// Number of items per chunk, needed to deploy a new chunk (the items of the chunk N
+        /// are [N * SIZE, (N + 1) * SIZE))
+        #[clap(long, value_name = "SIZE", requires = "chunk")]
+        chunk_size: Option<u64>,
+
+        /// Compare the config lines of the candy machine with the cache after writing them,
+        /// marking the items that do not match to be written again
+        #[clap(long, conflicts_with_all = &["dry_run", "unsigned_tx_out"])]
+        verify: bool,
+
+        /// Keypair file of the account of a new candy machine, created when it does not exist
+        /// [default: saved next to the cache until the candy machine is recorded]
+        #[clap(long, value_name = "FILE", conflicts_with = "unsigned_tx_out")]
+        candy_machine_keypair: Option<String>,
+    },
+
+    /// Check the environment for common configuration problems
+    Doctor {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Path to the config file, defaults to "config.json"
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Print the results as JSON
+        #[clap(long)]
+        json: bool,
+    },
+
+    /// Estimate the costs of a deployment
+    Estimate {
+        #[clap(subcommand)]
+        command: EstimateCommand,
     },
 
     /// Manage freeze guard actions
@@ -134,17 +300,204 @@ pub enum Commands {
         /// Skip collection validate prompt
         #[clap(long)]
         skip_collection_prompt: bool,
+
+        /// Maximum number of files uploaded in parallel [default: limit of the upload method]
+        #[clap(long, value_name = "N")]
+        concurrency: Option<usize>,
+
+        /// Fund the Bundlr balance before the upload when it is not enough
+        #[clap(long)]
+        auto_fund: bool,
+
+        /// Maximum amount (in lamports) funded with --auto-fund
+        #[clap(long, value_name = "LAMPORTS")]
+        max_fund_lamports: Option<u64>,
+
+        /// Bundlr (Irys) node of the upload: node1, node2, devnet or the URL of a node
+        /// [default: bundlrNode of the config file, or the node of the cluster]
+        #[clap(long, value_name = "NODE")]
+        bundlr_node: Option<BundlrNode>,
+
+        /// Maximum number of requests per second sent to the storage (lowered while the
+        /// storage throttles the upload) [default: no limit]
+        #[clap(long, value_name = "N")]
+        requests_per_second: Option<f64>,
+
+        /// Assign the assets to the cache indices of a seeded shuffle, keeping the file names;
+        /// the mapping is written to shuffle-map.json
+        #[clap(long)]
+        shuffle: bool,
+
+        /// Seed of the shuffle [default: seed of the previous shuffle-map.json, or random]
+        #[clap(long, value_name = "SEED", requires = "shuffle")]
+        shuffle_seed: Option<u64>,
+
+        /// Read the assets of the subdirectories too (following symlinks); the file names
+        /// must be unique across the directories
+        #[clap(long)]
+        recursive: bool,
+
+        /// Number of uploaded files between two writes of the cache (0 writes it at the end only)
+        #[clap(long, default_value_t = DEFAULT_UPLOAD_FLUSH_EVERY, value_name = "N")]
+        flush_every: usize,
     },
 
-    /// Import existing NFTs metadata links into a Sugar cache
+    /// Import existing NFTs metadata links, or the config lines of a candy machine, into a
+    /// Sugar cache
     Import {
-        /// Path to the text file containing Arweave metadata URLs.
-        #[clap(short, long, value_name = "FILE")]
-        import: std::path::PathBuf,
+        /// Path to the text file containing Arweave metadata URLs, or to a CSV, JSON or JSON
+        /// Lines file.
+        #[clap(
+            short,
+            long,
+            value_name = "FILE",
+            required_unless_present_any = &["from_candy_machine", "hashlist"]
+        )]
+        import: Option<std::path::PathBuf>,
+
+        /// Import the mints of a hashlist (a JSON array of mint addresses), reading the name
+        /// and metadata link of each mint from its metadata account
+        #[clap(
+            long,
+            value_name = "FILE",
+            conflicts_with_all = &["import", "from_candy_machine"]
+        )]
+        hashlist: Option<std::path::PathBuf>,
+
+        /// Number of metadata accounts read per RPC request with --hashlist (at most 100)
+        #[clap(long, default_value_t = DEFAULT_HASHLIST_BATCH_SIZE, value_name = "N")]
+        batch_size: usize,
+
+        /// Rebuild the cache from the config lines of a deployed candy machine
+        #[clap(
+            long,
+            value_name = "ADDRESS",
+            conflicts_with_all = &["import", "append", "start_index"]
+        )]
+        from_candy_machine: Option<String>,
+
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        /// (with --from-candy-machine)
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url (with --from-candy-machine or --hashlist)
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Format of the file (txt, csv, json or jsonl), detected from its extension by default
+        #[clap(long, value_name = "FORMAT")]
+        format: Option<ImportFormat>,
+
+        /// Columns of the cache fields in a CSV file, as headers or 1-based column numbers
+        /// (e.g. "name=title,metadata_link=uri,image_link=3")
+        #[clap(long, value_name = "MAP")]
+        column_map: Option<String>,
 
         /// Path to the output cache file (e.g. ./cache.json)
         #[clap(short, long, default_value = "cache.json", value_name = "CACHE")]
         output: std::path::PathBuf,
+
+        /// Number of metadata files fetched in parallel
+        #[clap(long, default_value_t = DEFAULT_IMPORT_CONCURRENCY, value_name = "N")]
+        concurrency: usize,
+
+        /// Import the links without fetching the metadata (items are named "NFT #<n>" and have
+        /// no image link); CSV files are always imported without fetching the metadata
+        #[clap(long)]
+        offline: bool,
+
+        /// Import the links that appear more than once
+        #[clap(long)]
+        allow_duplicates: bool,
+
+        /// Leave out the links that are not https URLs instead of aborting
+        #[clap(long)]
+        skip_invalid: bool,
+
+        /// Validate the links and print a report without writing the cache
+        #[clap(long, conflicts_with = "from_candy_machine")]
+        dry_run: bool,
+
+        /// Name of the items without a name in their metadata, with the {index} (0-based) and
+        /// {index1} (1-based) placeholders, zero-padded with e.g. {index:04}
+        #[clap(long, default_value = DEFAULT_NAME_TEMPLATE, value_name = "TEMPLATE")]
+        name_template: NameTemplate,
+
+        /// Name every item from the name template, even when its metadata has a name
+        #[clap(long)]
+        force_template: bool,
+
+        /// Download the metadata of every imported item and check it as `sugar validate` does
+        /// (name and symbol lengths, seller fee basis points, files and creator shares)
+        #[clap(long, conflicts_with_all = &["from_candy_machine", "dry_run"])]
+        verify: bool,
+
+        /// Keep the cache and exit successfully when --verify finds invalid items
+        #[clap(long, requires = "verify")]
+        continue_on_error: bool,
+
+        /// Write the hash of the cache in the hidden settings of the config file, as `sugar hash`
+        /// does
+        #[clap(long, conflicts_with_all = &["from_candy_machine", "dry_run"])]
+        update_hash: bool,
+
+        /// Path to the config file of --update-hash, defaults to "config.json"
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Number of fetched items between two writes of the cache (0 writes it at the end only)
+        #[clap(long, default_value_t = DEFAULT_FLUSH_EVERY, value_name = "N")]
+        flush_every: usize,
+
+        /// Gateway used to fetch `ipfs://` metadata links
+        #[clap(long, default_value = DEFAULT_IPFS_GATEWAY, value_name = "URL")]
+        ipfs_gateway: String,
+
+        /// Start over instead of resuming the partial cache of an interrupted import
+        #[clap(long, conflicts_with = "append")]
+        fresh: bool,
+
+        /// Add the items to the existing cache, keeping its program section (candy machine)
+        #[clap(long)]
+        append: bool,
+
+        /// Replace the existing items with the same index when appending
+        #[clap(long, requires = "append")]
+        overwrite_items: bool,
+
+        /// Index of the first imported item (defaults to the next unused index of the cache)
+        #[clap(long, value_name = "INDEX")]
+        start_index: Option<usize>,
+
+        /// Rekey the cache items 0..n when the import leaves missing indices, instead of
+        /// aborting
+        #[clap(long)]
+        renumber: bool,
+
+        /// Split the items in caches of at most N items each, keyed from 0 (e.g. one per candy
+        /// machine)
+        #[clap(
+            long,
+            value_name = "N",
+            conflicts_with_all = &["from_candy_machine", "append", "start_index", "update_hash"]
+        )]
+        chunk_size: Option<usize>,
+
+        /// File name stem of the caches of --chunk-size, written as <PREFIX>-<n>.json next to
+        /// the output cache (defaults to the stem of the output cache)
+        #[clap(long, value_name = "PREFIX", requires = "chunk_size")]
+        chunk_prefix: Option<String>,
+
+        /// Replace an existing cache without asking for confirmation
+        #[clap(long, conflicts_with = "append")]
+        force: bool,
+    },
+
+    /// Manage address lookup tables for v0 transactions
+    Lut {
+        #[clap(subcommand)]
+        command: LutCommand,
     },
 
     /// Mint one NFT from candy machine
@@ -161,9 +514,13 @@ pub enum Commands {
         #[clap(long, default_value = DEFAULT_CACHE)]
         cache: String,
 
-        /// Priority fee value
-        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
-        priority_fee: u64,
+        /// Priority fee per compute unit ("500", "500cu") or per transaction ("0.0001sol-per-tx")
+        #[clap(short, long, default_value_t = PriorityFee::default())]
+        priority_fee: PriorityFee,
+
+        /// Maximum total priority fee (in SOL), lowers the priority fee to stay within it
+        #[clap(long)]
+        max_total_priority_sol: Option<f64>,
 
         /// Amount of NFTs to be minted in bulk
         #[clap(short, long)]
@@ -176,6 +533,25 @@ pub enum Commands {
         /// Address of candy machine to mint from.
         #[clap(long)]
         candy_machine: Option<String>,
+
+        /// Mint compressed NFTs (Bubblegum) into the cache merkle tree
+        #[clap(long)]
+        compressed: bool,
+
+        /// Address of the merkle tree to mint into (compressed mode only)
+        #[clap(long, requires = "compressed")]
+        merkle_tree: Option<String>,
+
+        /// Path to the config file, defaults to "config.json" (compressed mode only)
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+        /// Send v0 transactions with the lookup table of the cache
+        #[clap(long, conflicts_with = "compressed")]
+        use_lut: bool,
+
+        /// Mint from the candy machine of a chunk of the cache (see deploy --chunk)
+        #[clap(long, value_name = "INDEX", conflicts_with_all = &["candy_machine", "compressed"])]
+        chunk: Option<u32>,
     },
     /// Airdrop NFTs from candy machine
     Airdrop {
@@ -191,9 +567,13 @@ pub enum Commands {
         #[clap(long, default_value = DEFAULT_CACHE)]
         cache: String,
 
-        /// Priority fee value
-        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
-        priority_fee: u64,
+        /// Priority fee per compute unit ("500", "500cu") or per transaction ("0.0001sol-per-tx")
+        #[clap(short, long, default_value_t = PriorityFee::default())]
+        priority_fee: PriorityFee,
+
+        /// Maximum total priority fee (in SOL), lowers the priority fee to stay within it
+        #[clap(long)]
+        max_total_priority_sol: Option<f64>,
 
         /// Address of candy machine to mint from.
         #[clap(long)]
@@ -202,6 +582,21 @@ pub enum Commands {
         /// List of airdrop targets.
         #[clap(long, default_value = DEFAULT_AIRDROP_LIST, help = DEFAULT_AIRDROP_LIST_HELP)]
         airdrop_list: String,
+
+        /// Mint compressed NFTs (Bubblegum) into the cache merkle tree
+        #[clap(long)]
+        compressed: bool,
+
+        /// Address of the merkle tree to mint into (compressed mode only)
+        #[clap(long, requires = "compressed")]
+        merkle_tree: Option<String>,
+
+        /// Path to the config file, defaults to "config.json" (compressed mode only)
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+        /// Send v0 transactions with the lookup table of the cache
+        #[clap(long, conflicts_with = "compressed")]
+        use_lut: bool,
     },
 
     /// Reveal the NFTs from a hidden settings candy machine
@@ -229,6 +624,20 @@ pub enum Commands {
         /// Address to transfer the update authority to
         #[clap(short, long)]
         new_update_authority: Option<String>,
+
+        /// Priority fee per compute unit ("500", "500cu") or per transaction ("0.0001sol-per-tx")
+        #[clap(short, long, default_value_t = PriorityFee::default())]
+        priority_fee: PriorityFee,
+
+        /// Maximum total priority fee (in SOL), lowers the priority fee to stay within it
+        #[clap(long)]
+        max_total_priority_sol: Option<f64>,
+    },
+
+    /// Manage the royalties of minted NFTs
+    Royalties {
+        #[clap(subcommand)]
+        command: RoyaltiesCommand,
     },
 
     /// Show the on-chain config of an existing candy machine
@@ -251,6 +660,37 @@ pub enum Commands {
         /// Display a list of unminted indices
         #[clap(long)]
         unminted: bool,
+
+        /// Display the transaction history of the candy machine (and its candy guard)
+        #[clap(long, conflicts_with = "unminted")]
+        history: bool,
+
+        /// Maximum number of (most recent) transactions to retrieve
+        #[clap(long, requires = "history")]
+        limit: Option<usize>,
+
+        /// Only retrieve transactions after a signature or date
+        #[clap(long, requires = "history")]
+        since: Option<String>,
+
+        /// Path to save the transaction history as JSON
+        #[clap(long, requires = "history")]
+        output: Option<String>,
+    },
+
+    /// Send the transactions written with --unsigned-tx-out, once signed
+    SendSigned {
+        /// Directory of the signed transactions
+        #[clap(long, value_name = "DIR")]
+        dir: String,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
     },
 
     /// Sign one or all NFTs from candy machine
@@ -274,6 +714,17 @@ pub enum Commands {
         /// Candy machine id.
         #[clap(long)]
         candy_machine_id: Option<String>,
+
+        /// Report the NFTs to sign and the ones already signed without sending any
+        /// transaction
+        #[clap(long, conflicts_with = "mint")]
+        dry_run: bool,
+    },
+
+    /// Manage merkle trees for compressed NFTs
+    Tree {
+        #[clap(subcommand)]
+        command: TreeCommand,
     },
 
     /// Upload assets to storage and creates the cache config
@@ -301,10 +752,137 @@ pub enum Commands {
         /// Path to the cache file
         #[clap(long, default_value = DEFAULT_CACHE)]
         cache: String,
+
+        /// Maximum number of files uploaded in parallel [default: limit of the upload method]
+        #[clap(long, value_name = "N")]
+        concurrency: Option<usize>,
+
+        /// Only upload the items of the cache missing a link, skipping the complete items
+        /// without checking their files for changes
+        #[clap(long)]
+        only_missing: bool,
+
+        /// Upload all files, even the ones that did not change since the last upload
+        #[clap(long, conflicts_with = "only_missing")]
+        force: bool,
+
+        /// Print the size and estimated cost of the upload without uploading
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Types of files to upload (images, metadata, animations or all); the metadata is
+        /// uploaded with the image and animation links of the cache
+        #[clap(long, default_value_t = UploadScope::default(), conflicts_with = "only_missing")]
+        scope: UploadScope,
+
+        /// Path of the report of the files that failed to upload (removed after a successful
+        /// upload)
+        #[clap(long, value_name = "PATH", default_value = DEFAULT_UPLOAD_ERROR_REPORT)]
+        error_report: String,
+
+        /// Upload the assets of a manifest of remote files (a JSON array of
+        /// {index, image_url, metadata_url}) instead of the assets directory; the remote files
+        /// are sent to the storage without being saved to disk (the images over the streaming
+        /// threshold are streamed from their URL)
+        #[clap(long, value_name = "FILE", conflicts_with_all = &["only_missing", "scope"])]
+        from_manifest: Option<String>,
+
+        /// Fund the Bundlr balance before the upload when it is not enough
+        #[clap(long)]
+        auto_fund: bool,
+
+        /// Maximum amount (in lamports) funded with --auto-fund
+        #[clap(long, value_name = "LAMPORTS")]
+        max_fund_lamports: Option<u64>,
+
+        /// Bundlr (Irys) node of the upload: node1, node2, devnet or the URL of a node
+        /// [default: bundlrNode of the config file, or the node of the cluster]
+        #[clap(long, value_name = "NODE")]
+        bundlr_node: Option<BundlrNode>,
+
+        /// Maximum number of requests per second sent to the storage (lowered while the
+        /// storage throttles the upload) [default: no limit]
+        #[clap(long, value_name = "N")]
+        requests_per_second: Option<f64>,
+
+        /// Assign the assets to the cache indices of a seeded shuffle, keeping the file names;
+        /// the mapping is written to shuffle-map.json
+        #[clap(long, conflicts_with_all = &["only_missing", "from_manifest"])]
+        shuffle: bool,
+
+        /// Seed of the shuffle [default: seed of the previous shuffle-map.json, or random]
+        #[clap(long, value_name = "SEED", requires = "shuffle")]
+        shuffle_seed: Option<u64>,
+
+        /// Write a JSON summary of the upload (file counts, bytes, time) to the path, also
+        /// when the upload fails
+        #[clap(long, value_name = "PATH")]
+        summary: Option<String>,
+        /// Read the assets of the subdirectories too (following symlinks); the file names
+        /// must be unique across the directories
+        #[clap(long)]
+        recursive: bool,
+
+        /// Upload optimized copies of the PNG and JPEG images above the size threshold
+        /// (imageOptimization of the config file), written to a work directory
+        #[clap(long, conflicts_with = "from_manifest")]
+        optimize_images: bool,
+
+        /// Wait for the confirmation of the Bundlr transactions after the upload; the files
+        /// not confirmed are marked as not uploaded in the cache
+        #[clap(long)]
+        verify_receipts: bool,
+
+        /// Time to wait for the confirmation of a transaction with --verify-receipts
+        #[clap(
+            long,
+            value_name = "SECONDS",
+            default_value_t = DEFAULT_RECEIPT_TIMEOUT_SECS,
+            requires = "verify_receipts"
+        )]
+        receipt_timeout: u64,
+
+        /// Number of uploaded files between two writes of the cache (0 writes it at the end only)
+        #[clap(long, default_value_t = DEFAULT_UPLOAD_FLUSH_EVERY, value_name = "N")]
+        flush_every: usize,
     },
 
     /// Interact with ArDrive storage service
     Ardrive {
+        /// Maximum number of retries of a request failing with a gateway error (429, 5xx, timeout)
+        #[clap(long, global = true, default_value_t = DEFAULT_MAX_RETRIES)]
+        max_retries: usize,
+
+        /// Delay before the first retry in milliseconds, doubled (with jitter) on each retry
+        #[clap(long, global = true, default_value_t = DEFAULT_RETRY_DELAY_MS)]
+        retry_delay_ms: u64,
+
+        /// List the drive files again instead of using the cached listing
+        #[clap(long, global = true)]
+        refresh: bool,
+
+        /// Minutes a drive listing is cached for (0 to disable the cache)
+        #[clap(
+            long,
+            global = true,
+            value_name = "MINUTES",
+            default_value_t = DEFAULT_LISTING_TTL_MINUTES
+        )]
+        listing_ttl: u64,
+
+        /// Arweave gateway URL [default: SUGAR_ARWEAVE_GATEWAY env var or https://arweave.net]
+        #[clap(long, global = true, value_name = "URL")]
+        gateway: Option<String>,
+
+        /// Hide the progress bars and detailed output, printing only the summary line
+        #[clap(short, long, global = true)]
+        quiet: bool,
+
+        /// Wallet profile to use (or to store with set-wallet) when no wallet file is given and
+        /// ARDRIVE_WALLET is not set
+        #[clap(long, global = true, value_name = "NAME")]
+        profile: Option<String>,
+
         #[clap(subcommand)]
         command: ArdriveCommand,
     },
@@ -322,6 +900,23 @@ pub enum Commands {
         /// Skip collection prompt
         #[clap(long)]
         skip_collection_prompt: bool,
+
+        /// Path to the config file, checked against the assets when present
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Path to the cache file, checked against the assets when present
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Print the assets consistency report as JSON
+        #[clap(long)]
+        json: bool,
+
+        /// Read the assets of the subdirectories too (following symlinks); the file names
+        /// must be unique across the directories
+        #[clap(long)]
+        recursive: bool,
     },
 
     /// Verify uploaded data
@@ -337,6 +932,14 @@ pub enum Commands {
         /// Path to the cache file, defaults to "cache.json"
         #[clap(long, default_value = DEFAULT_CACHE)]
         cache: String,
+
+        /// Verify the candy machine of a chunk of the cache (see deploy --chunk)
+        #[clap(long, value_name = "INDEX")]
+        chunk: Option<u32>,
+
+        /// Gateway used to resolve `ipfs://` metadata links
+        #[clap(long, default_value = DEFAULT_IPFS_GATEWAY, value_name = "URL")]
+        ipfs_gateway: String,
     },
 
     /// Withdraw funds a from candy machine account closing it
@@ -366,27 +969,63 @@ pub enum Commands {
         /// Defaults to keypair.pubkey.
         #[clap(long)]
         authority: Option<String>,
-    },
-}
 
-#[derive(Subcommand)]
-pub enum BundlrAction {
-    /// Retrieve the balance on bundlr
-    Balance,
-    /// Withdraw funds from bundlr
-    Withdraw,
+        /// Path to the cache file, defaults to "cache.json" (checked against the cluster of
+        /// the RPC when it exists)
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Withdraw from the candy machine of a chunk of the cache (see deploy --chunk)
+        #[clap(long, value_name = "INDEX", conflicts_with = "candy_machine")]
+        chunk: Option<u32>,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum ArdriveCommand {
-    /// Upload a file to ArDrive
+    /// Upload a file, or the files of a directory, to an ArDrive drive
     Upload {
-        /// Path to the file to upload
-        file: std::path::PathBuf,
+        /// Path to the file or directory to upload
+        path: std::path::PathBuf,
 
-        /// Optional bucket name
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// ID of the drive to upload to (required)
         #[clap(short, long)]
-        bucket: Option<String>,
+        drive_id: String,
+
+        /// Folder to upload to, defaults to the root folder of the drive (a directory is
+        /// uploaded to a folder with the same name inside it)
+        #[clap(long, value_name = "FOLDER_ID")]
+        parent_folder: Option<String>,
+
+        /// Maximum number of parallel uploads
+        #[clap(long, default_value_t = DEFAULT_UPLOAD_CONCURRENCY)]
+        concurrency: usize,
+
+        /// Print the estimated cost without uploading
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Create an ArFS manifest so the files are available under a single base path
+        #[clap(long)]
+        manifest: bool,
+
+        /// Optional JSON output file path to save the uploaded file list
+        #[clap(short, long, value_name = "OUTPUT")]
+        output: Option<std::path::PathBuf>,
+
+        /// Upload every file again, ignoring the upload journal and the files already in the
+        /// folder
+        #[clap(long)]
+        force_reupload: bool,
+
+        /// Upload journal recording the uploaded files, defaults to .ardrive-upload-state.json
+        /// next to the uploaded path
+        #[clap(long, value_name = "FILE")]
+        journal: Option<std::path::PathBuf>,
     },
 
     /// List contents of a bucket
@@ -396,72 +1035,443 @@ pub enum ArdriveCommand {
         bucket: Option<String>,
     },
 
-    /// Show info about an item
+    /// Show the details of a drive, folder or file, or of an Arweave transaction
     Info {
-        /// Item id
+        /// Drive, folder or file ID, or Arweave transaction ID
         id: String,
+
+        /// Output format (table or json); json prints a single JSON document to stdout
+        #[clap(long, default_value_t = OutputFormat::default())]
+        format: OutputFormat,
     },
 
-    /// Delete an item
+    /// Delete (hide) files and folders: a new revision of their metadata marks them hidden,
+    /// Arweave data being permanent
     Delete {
-        /// Item id
-        id: String,
+        /// IDs of the files and folders
+        #[clap(required_unless_present = "ids_file")]
+        ids: Vec<String>,
+
+        /// File with one file or folder ID per line
+        #[clap(long, value_name = "FILE")]
+        ids_file: Option<std::path::PathBuf>,
+
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// Password of a private drive (defaults to the ARDRIVE_DRIVE_PASSWORD env var)
+        #[clap(long, value_name = "PASSWORD")]
+        drive_password: Option<String>,
+
+        /// Hide several files and folders without asking for confirmation
+        #[clap(short, long)]
+        yes: bool,
+
+        /// List the files and folders that would be hidden without sending anything
+        #[clap(long)]
+        dry_run: bool,
     },
-    /// Export (set) an ArDrive wallet file for CLI usage
+    /// Export (set) an ArDrive wallet file for CLI usage, as the default wallet or as the
+    /// wallet of the --profile
     #[clap(alias = "export")]
     SetWallet {
         /// Path to the ardrive wallet JSON file
         wallet: std::path::PathBuf,
     },
-    /// List contents of a specific ArDrive drive
-    ListDrives {
+    /// List the stored ArDrive wallets (default and profiles) with their Arweave address
+    ListWallets,
+    /// Show the ArDrive wallet in use (redacted unless --unsafe-full is set)
+    ShowWallet {
         /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
         #[clap(short, long, value_name = "WALLET")]
         wallet: Option<std::path::PathBuf>,
 
-        /// ID of the drive to list (required)
-        #[clap(short, long)]
-        drive_id: String,
+        /// Print the full wallet JSON, including the private key
+        #[clap(long)]
+        unsafe_full: bool,
     },
-    /// List files in a specific ArDrive drive
-    ListDriveFiles {
+    /// Show the Arweave address and AR balance of the ArDrive wallet
+    Balance {
         /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
         #[clap(short, long, value_name = "WALLET")]
         wallet: Option<std::path::PathBuf>,
+    },
+    /// Estimate the cost of uploading an assets directory to ArDrive
+    Cost {
+        /// Path to the directory with the assets
+        #[clap(default_value = DEFAULT_ASSETS)]
+        assets_dir: std::path::PathBuf,
 
-        /// ID of the drive to list (required)
-        #[clap(short, long)]
-        drive_id: String,
+        /// Do not fetch the AR price to show the approximate cost in USD
+        #[clap(long)]
+        no_fiat: bool,
+    },
+    /// Create a drive, printing its ID and the ID of its root folder
+    CreateDrive {
+        /// Name of the drive
+        name: String,
 
-        /// Optional JSON output file path to save the file list
-        #[clap(short, long, value_name = "OUTPUT")]
-        output: Option<std::path::PathBuf>,
+        /// Create a private drive, encrypted with the drive password
+        #[clap(long)]
+        private: bool,
+
+        /// Password of the private drive [default: ARDRIVE_DRIVE_PASSWORD env var]
+        #[clap(long, value_name = "PASSWORD")]
+        drive_password: Option<String>,
 
-        /// Optional file extension filter (e.g. json)
-        #[clap(short = 'e', long, value_name = "EXT")]
-        filter: Option<String>,
-    },
-    /// List all drives (detailed) accessible by the wallet
-    ListAllDrives {
         /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
         #[clap(short, long, value_name = "WALLET")]
         wallet: Option<std::path::PathBuf>,
 
-        /// Optional JSON output file path to save the drive list
-        #[clap(short, long, value_name = "OUTPUT")]
-        output: Option<std::path::PathBuf>,
+        /// Output format (table or json); json prints a single JSON document to stdout
+        #[clap(long, default_value_t = OutputFormat::default())]
+        format: OutputFormat,
     },
-}
+    /// Create a folder in an ArDrive drive, printing its ID
+    CreateFolder {
+        /// Name of the folder
+        name: String,
 
-#[derive(Subcommand)]
-pub enum ConfigSubcommands {
-    /// Interactive process to create a config file
-    Create {
-        /// Path to the config file
+        /// ID of the drive to create the folder in (required)
         #[clap(short, long)]
-        config: Option<String>,
+        drive_id: String,
 
-        /// RPC Url
+        /// Folder to create the folder in, defaults to the root folder of the drive
+        #[clap(long, value_name = "FOLDER_ID")]
+        parent_folder: Option<String>,
+
+        /// Password of a private drive [default: ARDRIVE_DRIVE_PASSWORD env var]
+        #[clap(long, value_name = "PASSWORD")]
+        drive_password: Option<String>,
+
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// Output format (table or json); json prints a single JSON document to stdout
+        #[clap(long, default_value_t = OutputFormat::default())]
+        format: OutputFormat,
+    },
+    /// Delete a cache file (e.g. when switching candy machines)
+    WipeCache {
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: std::path::PathBuf,
+    },
+    /// List contents of a specific ArDrive drive
+    ListDrives {
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// ID of the drive to list (required)
+        #[clap(short, long)]
+        drive_id: String,
+
+        /// Password of a private drive [default: ARDRIVE_DRIVE_PASSWORD env var]
+        #[clap(long, value_name = "PASSWORD")]
+        drive_password: Option<String>,
+
+        /// Output format (table or json); json prints a single JSON document to stdout
+        #[clap(long, default_value_t = OutputFormat::default())]
+        format: OutputFormat,
+    },
+    /// List files in a specific ArDrive drive
+    ListDriveFiles {
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// ID of the drive to list (required)
+        #[clap(short, long)]
+        drive_id: String,
+
+        /// Optional JSON output file path to save the file list
+        #[clap(short, long, value_name = "OUTPUT")]
+        output: Option<std::path::PathBuf>,
+
+        /// Optional file extension filter (e.g. json), case-insensitive
+        #[clap(short = 'e', long, value_name = "EXT")]
+        filter: Option<String>,
+
+        /// Type of the entities to list (file or folder)
+        #[clap(long, default_value_t = EntityType::default())]
+        entity_type: EntityType,
+
+        /// Only list the files with this content type (e.g. image/png or image/*)
+        #[clap(long, value_name = "MIME")]
+        content_type: Option<String>,
+
+        /// Only list the files of this folder
+        #[clap(long, value_name = "FOLDER_ID")]
+        parent_folder: Option<String>,
+
+        /// Maximum number of files to list
+        #[clap(long)]
+        limit: Option<usize>,
+
+        /// Number of files to skip before listing
+        #[clap(long, default_value = "0")]
+        offset: usize,
+
+        /// Password of a private drive [default: ARDRIVE_DRIVE_PASSWORD env var]
+        #[clap(long, value_name = "PASSWORD")]
+        drive_password: Option<String>,
+
+        /// Output format (table or json); json prints a single JSON document to stdout
+        #[clap(long, default_value_t = OutputFormat::default())]
+        format: OutputFormat,
+
+        /// List the files with the ArDrive Node CLI instead of reading them from Arweave
+        #[clap(long)]
+        use_node_cli: bool,
+
+        /// Check whether the data transactions are confirmed, pending or not found
+        #[clap(long)]
+        check_status: bool,
+    },
+    /// Generate a cache file from the media and metadata pairs of an ArDrive drive, appending to
+    /// an existing cache
+    GenerateCache {
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// ID of the drive to list
+        #[clap(short, long, required_unless_present = "files")]
+        drive_id: Option<String>,
+
+        /// Read the files from a file list (written by list-drive-files or upload --output)
+        /// instead of listing the drive
+        #[clap(long, value_name = "FILE", conflicts_with = "drive_id")]
+        files: Option<std::path::PathBuf>,
+
+        /// Only use the files of this folder of the drive
+        #[clap(long, value_name = "FOLDER_ID", requires = "drive_id")]
+        parent_folder: Option<String>,
+
+        /// Only use the files under this folder path of the drive (e.g. "images"), can be
+        /// repeated
+        #[clap(
+            long,
+            value_name = "PATH",
+            multiple_occurrences = true,
+            requires = "drive_id"
+        )]
+        folder_path: Vec<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: std::path::PathBuf,
+
+        /// Candy machine address to record in a new cache file
+        #[clap(long)]
+        candy_machine: Option<String>,
+
+        /// Keep the existing cache items and add only new files (default)
+        #[clap(long, conflicts_with = "overwrite")]
+        append: bool,
+
+        /// Replace the existing cache file (requires confirmation)
+        #[clap(long)]
+        overwrite: bool,
+
+        /// Only include files modified at or after this unix time (in seconds)
+        #[clap(long, value_name = "UNIX_TIME")]
+        since: Option<u64>,
+
+        /// Move the links of the existing cache items to the configured gateway
+        #[clap(long)]
+        rewrite_gateway: bool,
+
+        /// Use the transaction IDs as item hashes instead of downloading the files to hash them
+        #[clap(long)]
+        skip_hash: bool,
+
+        /// List the files with the ArDrive Node CLI instead of reading them from Arweave
+        #[clap(long)]
+        use_node_cli: bool,
+
+        /// Check that the data transactions are confirmed before writing the cache
+        #[clap(long)]
+        check_status: bool,
+
+        /// Write the cache even if some transactions are not found (with --check-status)
+        #[clap(long, requires = "check_status")]
+        allow_pending: bool,
+    },
+    /// Upload the files of an assets directory missing from its folder in an ArDrive drive
+    Sync {
+        /// Path to the directory with the assets to sync
+        #[clap(default_value = DEFAULT_ASSETS)]
+        assets_dir: std::path::PathBuf,
+
+        /// ID of the drive to sync with (required)
+        #[clap(short, long)]
+        drive_id: String,
+
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// Print the files to upload and the estimated cost without uploading
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Check that the metadata and image links of every cache item are live on Arweave
+    VerifyCache {
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Maximum number of items checked in parallel
+        #[clap(long, default_value_t = DEFAULT_VERIFY_CONCURRENCY)]
+        concurrency: usize,
+
+        /// Also check that the transactions are confirmed
+        #[clap(long)]
+        check_confirmation: bool,
+
+        /// JSON file to write the failing items to
+        #[clap(short, long, value_name = "OUTPUT")]
+        output: Option<std::path::PathBuf>,
+    },
+    /// Download the files of an ArDrive drive to a local directory
+    Download {
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// ID of the drive to download (required)
+        #[clap(short, long)]
+        drive_id: String,
+
+        /// Directory to write the files to
+        #[clap(short, long, default_value = DEFAULT_ASSETS)]
+        output_dir: std::path::PathBuf,
+
+        /// Optional file extension filter (e.g. json)
+        #[clap(short = 'e', long, value_name = "EXT")]
+        filter: Option<String>,
+
+        /// Maximum number of parallel downloads
+        #[clap(long, default_value_t = DEFAULT_DOWNLOAD_CONCURRENCY)]
+        concurrency: usize,
+
+        /// Download the files that already exist with the same size
+        #[clap(long)]
+        force: bool,
+
+        /// List the files with the ArDrive Node CLI instead of reading them from Arweave
+        #[clap(long)]
+        use_node_cli: bool,
+    },
+    /// List all drives (detailed) accessible by the wallet
+    ListAllDrives {
+        /// Optional path to the ardrive wallet JSON file (overrides stored wallet)
+        #[clap(short, long, value_name = "WALLET")]
+        wallet: Option<std::path::PathBuf>,
+
+        /// Optional JSON output file path to save the drive list
+        #[clap(short, long, value_name = "OUTPUT")]
+        output: Option<std::path::PathBuf>,
+
+        /// Password of a private drive [default: ARDRIVE_DRIVE_PASSWORD env var]
+        #[clap(long, value_name = "PASSWORD")]
+        drive_password: Option<String>,
+
+        /// Output format (table or json); json prints a single JSON document to stdout
+        #[clap(long, default_value_t = OutputFormat::default())]
+        format: OutputFormat,
+
+        /// List the drives with the ArDrive Node CLI instead of reading them from Arweave
+        #[clap(long)]
+        use_node_cli: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CacheSubcommands {
+    /// Download the metadata (and images) of the cache items to a local directory
+    ExportMetadata {
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Directory to write the files to
+        #[clap(short, long, default_value = "assets_restored")]
+        output: String,
+
+        /// Also download the images referenced by the cache items
+        #[clap(long)]
+        with_images: bool,
+
+        /// Maximum number of concurrent downloads
+        #[clap(long, default_value_t = DEFAULT_EXPORT_PARALLEL)]
+        parallel: usize,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum CleanupCommand {
+    /// Close empty (and dust) token accounts of the owner, e.g. after a token payment drop
+    TokenAccounts {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Owner of the token accounts [default: keypair address]
+        #[clap(long)]
+        owner: Option<String>,
+
+        /// Only clean up token accounts of this mint
+        #[clap(long)]
+        mint: Option<String>,
+
+        /// Close the empty token accounts
+        #[clap(long)]
+        close_empty: bool,
+
+        /// Burn balances up to --max-dust and close the token accounts
+        #[clap(long, requires = "max_dust")]
+        burn_dust: bool,
+
+        /// Maximum balance (in base units) burned with --burn-dust
+        #[clap(long)]
+        max_dust: Option<u64>,
+
+        /// List the token accounts without closing them
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Priority fee per compute unit ("500", "500cu") or per transaction ("0.0001sol-per-tx")
+        #[clap(short, long, default_value_t = PriorityFee::default())]
+        priority_fee: PriorityFee,
+
+        /// Maximum total priority fee (in SOL), lowers the priority fee to stay within it
+        #[clap(long)]
+        max_total_priority_sol: Option<f64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum ConfigSubcommands {
+    /// Interactive process to create a config file
+    Create {
+        /// Path to the config file
+        #[clap(short, long)]
+        config: Option<String>,
+
+        /// RPC Url
         #[clap(short, long)]
         rpc_url: Option<String>,
 
@@ -474,6 +1484,11 @@ pub enum ConfigSubcommands {
         assets_dir: String,
     },
     /// Update the candy machine config on-chain
+    ///
+    /// The number of items can only grow for a candy machine with hidden settings: Candy Machine
+    /// v3 has no instruction to resize the account of its config lines. To add items, deploy a
+    /// new candy machine (remove 'candyMachine' from the cache file and run 'sugar deploy') and
+    /// close the current one with 'sugar withdraw'.
     Update {
         /// Path to the config file, defaults to "config.json"
         #[clap(short, long, default_value = DEFAULT_CONFIG)]
@@ -502,6 +1517,24 @@ pub enum ConfigSubcommands {
         /// Address of candy machine to update.
         #[clap(long)]
         candy_machine: Option<String>,
+
+        /// Write the transactions unsigned (base64) to this directory instead of sending them
+        #[clap(long, value_name = "DIR")]
+        unsigned_tx_out: Option<String>,
+
+        /// Authority (and fee payer) of the unsigned transactions [default: the keypair]
+        #[clap(long, value_name = "PUBKEY", requires = "unsigned_tx_out")]
+        authority: Option<String>,
+
+        /// Durable nonce account of the unsigned transactions, one per transaction (can be
+        /// repeated)
+        #[clap(
+            long,
+            value_name = "PUBKEY",
+            multiple_occurrences = true,
+            requires = "unsigned_tx_out"
+        )]
+        nonce_account: Vec<String>,
     },
     /// Set specific candy machine config values
     Set {
@@ -563,9 +1596,94 @@ pub enum CollectionSubcommands {
         #[clap(long)]
         candy_machine: Option<String>,
 
+        /// Write the transactions unsigned (base64) to this directory instead of sending them
+        #[clap(long, value_name = "DIR")]
+        unsigned_tx_out: Option<String>,
+
+        /// Authority (and fee payer) of the unsigned transactions [default: the keypair]
+        #[clap(long, value_name = "PUBKEY", requires = "unsigned_tx_out")]
+        authority: Option<String>,
+
+        /// Durable nonce account of the unsigned transactions, one per transaction (can be
+        /// repeated)
+        #[clap(
+            long,
+            value_name = "PUBKEY",
+            multiple_occurrences = true,
+            requires = "unsigned_tx_out"
+        )]
+        nonce_account: Vec<String>,
+
         /// Address of collection mint to set the candy machine to.
         collection_mint: String,
     },
+
+    /// Audit (and repair) the collection of minted NFTs
+    Audit {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Path to a JSON list of mint addresses to audit
+        #[clap(long, conflicts_with = "candy_machine")]
+        mint_list: Option<String>,
+
+        /// Address of candy machine whose NFTs are audited
+        #[clap(long)]
+        candy_machine: Option<String>,
+
+        /// Set and verify the target collection on nonconforming NFTs; the status of each NFT
+        /// is written to sugar-collection-audit-cache.json and the NFTs repaired or conforming
+        /// are skipped when the repair is run again
+        #[clap(long, requires = "target")]
+        repair: bool,
+
+        /// Address of the collection mint the NFTs should belong to
+        #[clap(long)]
+        target: Option<String>,
+
+        /// RPC timeout to retrieve the mint list (in seconds)
+        #[clap(short, long)]
+        timeout: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum EstimateCommand {
+    /// Estimate the rent of the candy machine (and candy guard) accounts
+    Rent {
+        /// RPC Url, uses the default rent when no RPC is available
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the config file, the candy guard is estimated when it has guards
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Number of items of the candy machine
+        #[clap(short, long)]
+        number: u64,
+
+        /// Maximum length of the item names
+        #[clap(long, default_value_t = DEFAULT_NAME_LENGTH)]
+        name_length: u32,
+
+        /// Maximum length of the item uris
+        #[clap(long, default_value_t = DEFAULT_URI_LENGTH)]
+        uri_length: u32,
+
+        /// Estimate a candy machine with hidden settings
+        #[clap(long)]
+        hidden_settings: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -599,8 +1717,21 @@ pub enum GuardCommand {
         /// Address of the candy guard.
         #[clap(long)]
         candy_guard: Option<String>,
+
+        /// Create and configure the candy guard without wrapping the candy machine
+        #[clap(long)]
+        no_wrap: bool,
+
+        /// Print the changes to the on-chain guards without sending them
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation when guards or groups would be removed
+        #[clap(short, long)]
+        yes: bool,
     },
     /// Remove a candy guard from a candy machine
+    #[clap(visible_alias = "unwrap")]
     Remove {
         /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
         #[clap(short, long)]
@@ -626,6 +1757,32 @@ pub enum GuardCommand {
         #[clap(long)]
         candy_guard: Option<String>,
     },
+    /// Set an existing candy guard as the mint authority of a candy machine
+    Wrap {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Priority fee value
+        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
+        priority_fee: u64,
+
+        /// Address of the candy machine.
+        #[clap(long)]
+        candy_machine: Option<String>,
+
+        /// Address of the candy guard.
+        #[clap(long)]
+        candy_guard: Option<String>,
+    },
     /// Show the on-chain config of an existing candy guard
     Show {
         /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
@@ -640,6 +1797,10 @@ pub enum GuardCommand {
         #[clap(long, default_value = DEFAULT_CACHE)]
         cache: String,
 
+        /// Address of the candy machine (used to find its candy guard).
+        #[clap(long)]
+        candy_machine: Option<String>,
+
         /// Address of the candy guard.
         #[clap(long)]
         candy_guard: Option<String>,
@@ -666,9 +1827,81 @@ pub enum GuardCommand {
         #[clap(short, long, default_value = DEFAULT_CONFIG)]
         config: String,
 
+        /// Address of the candy machine (used to find its candy guard).
+        #[clap(long)]
+        candy_machine: Option<String>,
+
+        /// Address of the candy guard.
+        #[clap(long)]
+        candy_guard: Option<String>,
+
+        /// Print the changes to the on-chain guards without sending them
+        #[clap(long)]
+        dry_run: bool,
+
+        /// Skip the confirmation when guards or groups would be removed
+        #[clap(short, long)]
+        yes: bool,
+
+        /// Write the transactions unsigned (base64) to this directory instead of sending them
+        #[clap(long, value_name = "DIR")]
+        unsigned_tx_out: Option<String>,
+
+        /// Authority (and fee payer) of the unsigned transactions [default: the keypair]
+        #[clap(long, value_name = "PUBKEY", requires = "unsigned_tx_out")]
+        authority: Option<String>,
+
+        /// Durable nonce account of the unsigned transactions, one per transaction (can be
+        /// repeated)
+        #[clap(
+            long,
+            value_name = "PUBKEY",
+            multiple_occurrences = true,
+            requires = "unsigned_tx_out"
+        )]
+        nonce_account: Vec<String>,
+    },
+    /// Schedule the mint by setting the start and end date guards
+    Schedule {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Priority fee value
+        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
+        priority_fee: u64,
+
+        /// Address of the candy machine (used to find its candy guard).
+        #[clap(long)]
+        candy_machine: Option<String>,
+
         /// Address of the candy guard.
         #[clap(long)]
         candy_guard: Option<String>,
+
+        /// Start date (e.g. "2023-05-01T17:00:00Z", "now" or "+1h")
+        #[clap(long)]
+        start: String,
+
+        /// End date, or a duration after the start date (e.g. "2h")
+        #[clap(long)]
+        end: String,
+
+        /// Label of the group to schedule, defaults to the default guard set
+        #[clap(long)]
+        label: Option<String>,
+
+        /// Wait until the start date, confirming the on-chain dates right before it
+        #[clap(long, alias = "execute-at-start")]
+        wait: bool,
     },
     /// Withdraw funds from a candy guard account closing it
     Withdraw {
@@ -688,12 +1921,166 @@ pub enum GuardCommand {
         #[clap(long, default_value = DEFAULT_CACHE)]
         cache: String,
 
+        /// Address of the candy machine (used to find its candy guard).
+        #[clap(long)]
+        candy_machine: Option<String>,
+
         /// Address of the candy guard.
         #[clap(long)]
         candy_guard: Option<String>,
     },
 }
 
+#[derive(Subcommand)]
+pub enum RoyaltiesCommand {
+    /// Update the seller fee basis points (and rule set) of minted NFTs
+    Set {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// New seller fee basis points
+        #[clap(long)]
+        basis_points: u16,
+
+        /// New rule set (programmable NFTs only)
+        #[clap(long)]
+        rule_set: Option<String>,
+
+        /// Path to a JSON list of mint addresses to update
+        #[clap(long, conflicts_with = "candy_machine")]
+        mint_list: Option<String>,
+
+        /// Address of candy machine whose NFTs are updated
+        #[clap(long)]
+        candy_machine: Option<String>,
+
+        /// Show the items that would be updated without sending transactions
+        #[clap(long)]
+        dry_run: bool,
+
+        /// RPC timeout to retrieve the mint list (in seconds)
+        #[clap(short, long)]
+        timeout: Option<u64>,
+    },
+    /// Show the distribution of royalty values across minted NFTs
+    Show {
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Path to the config file, defaults to "config.json"
+        #[clap(short, long, default_value = DEFAULT_CONFIG)]
+        config: String,
+
+        /// Path to a JSON list of mint addresses
+        #[clap(long, conflicts_with = "candy_machine")]
+        mint_list: Option<String>,
+
+        /// Address of candy machine whose NFTs are shown
+        #[clap(long)]
+        candy_machine: Option<String>,
+
+        /// RPC timeout to retrieve the mint list (in seconds)
+        #[clap(short, long)]
+        timeout: Option<u64>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TreeCommand {
+    /// Create a merkle tree for compressed NFTs and store it in the cache
+    Create {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Priority fee value
+        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
+        priority_fee: u64,
+
+        /// Number of NFTs the tree must be able to hold
+        #[clap(long)]
+        capacity: u64,
+
+        /// Max buffer size (concurrent changes per slot), defaults to the smallest valid size
+        #[clap(long)]
+        max_buffer_size: Option<u32>,
+
+        /// Number of tree levels cached on-chain
+        #[clap(long, default_value_t = 0)]
+        canopy_depth: u32,
+
+        /// Address of the collection mint, overrides the one from the cache
+        #[clap(long)]
+        collection_mint: Option<String>,
+    },
+    /// Show the utilization of a merkle tree
+    Show {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Address of the merkle tree
+        #[clap(long)]
+        merkle_tree: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum LutCommand {
+    /// Create an address lookup table with the candy machine accounts and store it in the cache
+    Create {
+        /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+        #[clap(short, long)]
+        keypair: Option<String>,
+
+        /// RPC Url
+        #[clap(short, long)]
+        rpc_url: Option<String>,
+
+        /// Path to the cache file, defaults to "cache.json"
+        #[clap(long, default_value = DEFAULT_CACHE)]
+        cache: String,
+
+        /// Priority fee value
+        #[clap(short, long, default_value_t = DEFAULT_PRIORITY_FEE)]
+        priority_fee: u64,
+
+        /// Address of candy machine, overrides the one from the cache
+        #[clap(long)]
+        candy_machine: Option<String>,
+    },
+}
+
 #[derive(Subcommand)]
 pub enum FreezeCommand {
     /// Initialize the freeze escrow account.
@@ -834,3 +2221,19 @@ pub enum FreezeCommand {
         token: bool,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quiet_is_set_by_the_ardrive_flag_only() {
+        let parse = |args: &[&str]| Cli::try_parse_from(args).unwrap();
+
+        assert!(parse(&["sugar", "ardrive", "--quiet", "list-wallets"]).quiet());
+        // global flag of the ardrive command
+        assert!(parse(&["sugar", "ardrive", "list-wallets", "-q"]).quiet());
+        assert!(!parse(&["sugar", "ardrive", "list-wallets"]).quiet());
+        assert!(!parse(&["sugar", "verify"]).quiet());
+    }
+}
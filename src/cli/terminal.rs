@@ -0,0 +1,47 @@
+use anyhow::Result;
+use console::{style, Style};
+use dialoguer::{theme::ColorfulTheme, Confirm};
+
+use crate::output::{Confirmation, Output, PromptTheme, Terminal};
+
+/// Prints the output of the commands and asks their confirmations on the terminal.
+pub struct CliTerminal;
+
+impl Terminal for CliTerminal {
+    fn output(&self, output: Output) {
+        match output {
+            Output::Line(line) => println!("{line}"),
+            Output::Text(text) => print!("{text}"),
+            Output::ErrorLine(line) => eprintln!("{line}"),
+        }
+    }
+
+    fn confirm(&self, confirmation: &Confirmation) -> Result<bool> {
+        let theme = match confirmation.theme {
+            PromptTheme::Default => get_dialoguer_theme(),
+            PromptTheme::Warning => ColorfulTheme {
+                success_prefix: style("✔".to_string()).yellow().force_styling(true),
+                values_style: Style::new().yellow(),
+                ..get_dialoguer_theme()
+            },
+        };
+
+        let mut prompt = Confirm::with_theme(&theme);
+        prompt.with_prompt(&confirmation.prompt);
+        if let Some(default) = confirmation.default {
+            prompt.default(default);
+        }
+
+        Ok(prompt.interact()?)
+    }
+}
+
+/// Theme of the Sugar prompts.
+pub fn get_dialoguer_theme() -> ColorfulTheme {
+    ColorfulTheme {
+        prompt_style: Style::new(),
+        checked_item_prefix: style("✔".to_string()).green().force_styling(true),
+        unchecked_item_prefix: style("✔".to_string()).black().force_styling(true),
+        ..Default::default()
+    }
+}
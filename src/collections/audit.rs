@@ -0,0 +1,689 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use borsh::BorshDeserialize;
+use mpl_token_metadata::{
+    instruction::{
+        set_and_verify_collection, set_and_verify_sized_collection_item, unverify_collection,
+        unverify_sized_collection_item,
+    },
+    state::{Collection, Metadata},
+    ID as TOKEN_METADATA_PROGRAM_ID,
+};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use tokio::sync::Semaphore;
+
+use crate::{
+    account_fetcher::AccountFetcher,
+    common::*,
+    mint_list::get_nft_metadata_pubkeys,
+    output::style,
+    pdas::{find_master_edition_pda, find_metadata_pda},
+    setup::get_rpc_url,
+    show::print_with_style,
+    utils::*,
+};
+
+/// Report of the last audit with a target collection, written to the working directory. The
+/// items repaired or conforming are skipped when the repair is run again.
+pub const COLLECTION_AUDIT_CACHE: &str = "sugar-collection-audit-cache.json";
+
+pub struct AuditCollectionArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub mint_list: Option<String>,
+    pub candy_machine: Option<String>,
+    pub repair: bool,
+    pub target: Option<String>,
+    pub timeout: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+struct RepairValues {
+    metadata_pubkey: Pubkey,
+    metadata: Metadata,
+    // the collection the item is currently verified in
+    unverify: Option<CollectionInfo>,
+}
+
+#[derive(Clone, Debug)]
+struct CollectionInfo {
+    mint: Pubkey,
+    sized: bool,
+}
+
+/// Items grouped by the repair they need to be verified in the target collection.
+#[derive(Debug, Default)]
+struct RepairPlan {
+    /// Items already verified in the target collection.
+    conforming: Vec<Pubkey>,
+    repair: Vec<RepairValues>,
+    /// Items that cannot be repaired with the keypair, with the reason.
+    unrepairable: Vec<(Pubkey, String)>,
+}
+
+/// Report of an audit: the status of each item for the target collection.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct AuditReport {
+    target: String,
+    items: Vec<AuditItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct AuditItem {
+    mint: String,
+    status: AuditStatus,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum AuditStatus {
+    Repaired,
+    Conforming,
+    /// Not repaired by the audit (not run with `--repair`).
+    ToRepair,
+    Unrepairable(String),
+    Failed(String),
+}
+
+impl AuditStatus {
+    fn is_complete(&self) -> bool {
+        matches!(self, Self::Repaired | Self::Conforming)
+    }
+}
+
+pub async fn process_audit_collection(args: AuditCollectionArgs) -> Result<()> {
+    let target = match (&args.target, args.repair) {
+        (Some(target), _) => Some(
+            Pubkey::from_str(target)
+                .map_err(|_| anyhow!("Failed to parse target collection mint: {}", target))?,
+        ),
+        (None, true) => {
+            return Err(anyhow!(
+                "Missing --target <COLLECTION_MINT> to repair the collection"
+            ))
+        }
+        (None, false) => None,
+    };
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url.clone())?;
+    let authority = sugar_config.keypair.pubkey();
+
+    report!(
+        "{} {}Loading NFTs",
+        style("[1/3]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Loading...");
+
+    let mut metadata_pubkeys = get_nft_metadata_pubkeys(
+        args.rpc_url.clone(),
+        &args.cache,
+        args.mint_list,
+        args.candy_machine,
+        args.timeout,
+    )?;
+
+    if metadata_pubkeys.is_empty() {
+        spinner.finish_and_clear();
+        return Err(anyhow!("No NFTs found to audit."));
+    }
+
+    // the items completed by a previous repair of the same target are not audited again
+    let mut completed = Vec::new();
+    if let (Some(target), true) = (target, args.repair) {
+        let report = load_audit_report(COLLECTION_AUDIT_CACHE)?;
+        let (items, skipped) = completed_items(&report, &target, &metadata_pubkeys);
+        completed = items;
+        metadata_pubkeys.retain(|pubkey| !skipped.contains(pubkey));
+    }
+
+    let client = Arc::new(RpcClient::new(get_rpc_url(args.rpc_url)?));
+    let fetcher = AccountFetcher::new(client.clone());
+    let metadata = get_metadata_accounts(&fetcher, &metadata_pubkeys)?;
+
+    spinner.finish_with_message(format!("Found {} NFTs", metadata.len()));
+
+    if !completed.is_empty() {
+        report!(
+            "Skipped {} item(s) repaired or conforming in a previous run (see {})",
+            completed.len(),
+            COLLECTION_AUDIT_CACHE
+        );
+    }
+
+    report!(
+        "\n{} {}Collection summary",
+        style("[2/3]").bold().dim(),
+        COLLECTION_EMOJI
+    );
+
+    report!();
+    for ((collection, verified), count) in &group_collections(&metadata) {
+        print_with_style(
+            "",
+            collection,
+            format!(
+                "{} item(s){}",
+                count,
+                if *verified { ", verified" } else { "" }
+            ),
+        );
+    }
+
+    let target = match target {
+        Some(target) => target,
+        None => return Ok(()),
+    };
+
+    // metadata of the target collection and of the collections items are verified in
+    let mut collection_mints: Vec<Pubkey> = metadata
+        .iter()
+        .filter_map(|m| m.collection.as_ref())
+        .filter(|c| c.verified)
+        .map(|c| c.key)
+        .collect();
+    collection_mints.push(target);
+    collection_mints.sort();
+    collection_mints.dedup();
+
+    let collection_metadata_pubkeys: Vec<Pubkey> =
+        collection_mints.iter().map(find_metadata_pda).collect();
+    let collections: HashMap<Pubkey, Metadata> = collection_mints
+        .iter()
+        .zip(fetcher.get_multiple_accounts(&collection_metadata_pubkeys)?)
+        .filter_map(|(mint, account)| {
+            account
+                .and_then(|a| Metadata::deserialize(&mut a.data.as_slice()).ok())
+                .map(|m| (*mint, m))
+        })
+        .collect();
+
+    let target_collection = collections
+        .get(&target)
+        .ok_or_else(|| anyhow!("Collection metadata not found for mint {}", target))?;
+
+    if target_collection.update_authority != authority {
+        return Err(anyhow!(
+            "Keypair {} is not the update authority of the collection {} ({}).",
+            authority,
+            target,
+            target_collection.update_authority
+        ));
+    }
+
+    let target_info = CollectionInfo {
+        mint: target,
+        sized: target_collection.collection_details.is_some(),
+    };
+
+    let plan = plan_repairs(metadata, &target, &collections, &authority);
+
+    report!();
+    print_with_style(
+        "",
+        "conforming",
+        format!("{} item(s)", plan.conforming.len()),
+    );
+    print_with_style("", "to repair", format!("{} item(s)", plan.repair.len()));
+
+    if !plan.unrepairable.is_empty() {
+        report!(
+            "{}{} {}",
+            WARNING_EMOJI,
+            style("Unrepairable:").yellow().bold(),
+            plan.unrepairable.len()
+        );
+        for (mint, reason) in &plan.unrepairable {
+            report!("  {}: {}", mint, reason);
+        }
+    }
+
+    // items of the report, starting with the ones completed in a previous run
+    let mut items = completed;
+    items.extend(plan.conforming.iter().map(|mint| AuditItem {
+        mint: mint.to_string(),
+        status: AuditStatus::Conforming,
+    }));
+    items.extend(plan.unrepairable.iter().map(|(mint, reason)| AuditItem {
+        mint: mint.to_string(),
+        status: AuditStatus::Unrepairable(reason.clone()),
+    }));
+
+    if !args.repair || plan.repair.is_empty() {
+        items.extend(plan.repair.iter().map(|item| AuditItem {
+            mint: item.metadata.mint.to_string(),
+            status: AuditStatus::ToRepair,
+        }));
+        write_audit_report(&target, items)?;
+
+        if args.repair {
+            report!("\nNo items to repair.");
+        }
+        return Ok(());
+    }
+
+    report!(
+        "\n{} {}Repairing collection",
+        style("[3/3]").bold().dim(),
+        UPLOAD_EMOJI
+    );
+
+    let keypair = Arc::new(sugar_config.keypair);
+    let target_info = Arc::new(target_info);
+    let sem = Arc::new(Semaphore::new(100));
+    let repair_results = Arc::new(Mutex::new(Vec::new()));
+    let mut tx_tasks = Vec::new();
+
+    let pb = progress_bar_with_style(plan.repair.len() as u64);
+    pb.set_message("Repairing NFTs... ");
+
+    for item in plan.repair {
+        let permit = Arc::clone(&sem).acquire_owned().await.unwrap();
+        let client = client.clone();
+        let keypair = keypair.clone();
+        let target_info = target_info.clone();
+        let repair_results = repair_results.clone();
+        let pb = pb.clone();
+
+        tx_tasks.push(tokio::spawn(async move {
+            // Move permit into the closure so it is dropped when the task is dropped.
+            let _permit = permit;
+            let mint = item.metadata.mint.to_string();
+
+            let status = match repair_collection(client, keypair, &target_info, item).await {
+                Ok(()) => AuditStatus::Repaired,
+                Err(e) => AuditStatus::Failed(e.to_string()),
+            };
+            repair_results
+                .lock()
+                .unwrap()
+                .push(AuditItem { mint, status });
+
+            pb.inc(1);
+        }));
+    }
+
+    for task in tx_tasks {
+        task.await.unwrap();
+    }
+    pb.finish();
+
+    let results = std::mem::take(&mut *repair_results.lock().unwrap());
+    let failed = results
+        .iter()
+        .filter(|item| matches!(item.status, AuditStatus::Failed(_)))
+        .count();
+
+    report!(
+        "\n{} {}/{} item(s) repaired",
+        style("Repaired:").bold(),
+        results.len() - failed,
+        results.len()
+    );
+
+    items.extend(results);
+    write_audit_report(&target, items)?;
+
+    if failed > 0 {
+        report!(
+            "{}Some repairs failed. See the collection audit cache file for details. \
+            Re-run the command.",
+            WARNING_EMOJI
+        );
+    } else {
+        report!("{}Collection repaired!", CONFETTI_EMOJI);
+    }
+
+    Ok(())
+}
+
+/// Number of items by collection and verification status.
+fn group_collections(metadata: &[Metadata]) -> BTreeMap<(String, bool), usize> {
+    let mut groups = BTreeMap::new();
+
+    for m in metadata {
+        let key = match &m.collection {
+            Some(Collection { key, verified }) => (key.to_string(), *verified),
+            None => ("none".to_string(), false),
+        };
+        *groups.entry(key).or_default() += 1;
+    }
+
+    groups
+}
+
+/// Groups the items by the repair they need to be verified in the `target` collection with
+/// the `authority` keypair.
+fn plan_repairs(
+    metadata: Vec<Metadata>,
+    target: &Pubkey,
+    collections: &HashMap<Pubkey, Metadata>,
+    authority: &Pubkey,
+) -> RepairPlan {
+    let mut plan = RepairPlan::default();
+
+    for m in metadata {
+        let unverify = match &m.collection {
+            Some(Collection {
+                key,
+                verified: true,
+            }) if key == target => {
+                plan.conforming.push(m.mint);
+                continue;
+            }
+            Some(Collection {
+                key,
+                verified: true,
+            }) => match collections.get(key) {
+                Some(collection) if collection.update_authority == *authority => {
+                    Some(CollectionInfo {
+                        mint: *key,
+                        sized: collection.collection_details.is_some(),
+                    })
+                }
+                _ => {
+                    plan.unrepairable.push((
+                        m.mint,
+                        format!(
+                            "verified in collection {} controlled by another authority",
+                            key
+                        ),
+                    ));
+                    continue;
+                }
+            },
+            _ => None,
+        };
+
+        if !m.is_mutable {
+            plan.unrepairable
+                .push((m.mint, "immutable metadata".to_string()));
+        } else if m.update_authority != *authority {
+            plan.unrepairable.push((
+                m.mint,
+                format!("different update authority ({})", m.update_authority),
+            ));
+        } else {
+            plan.repair.push(RepairValues {
+                metadata_pubkey: find_metadata_pda(&m.mint),
+                metadata: m,
+                unverify,
+            });
+        }
+    }
+
+    plan
+}
+
+/// Loads the report of a previous audit, if there is one.
+fn load_audit_report(path: &str) -> Result<AuditReport> {
+    match fs::read_to_string(path) {
+        Ok(content) => serde_json::from_str(&content)
+            .map_err(|e| anyhow!("Failed to parse collection audit report '{}': {}", path, e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(AuditReport::default()),
+        Err(e) => Err(anyhow!(
+            "Failed to read collection audit report '{}': {}",
+            path,
+            e
+        )),
+    }
+}
+
+/// Items of the report completed for the `target` collection among the metadata accounts,
+/// with their metadata accounts.
+fn completed_items(
+    report: &AuditReport,
+    target: &Pubkey,
+    metadata_pubkeys: &[Pubkey],
+) -> (Vec<AuditItem>, HashSet<Pubkey>) {
+    let mut completed = Vec::new();
+    let mut skipped = HashSet::new();
+
+    if report.target != target.to_string() {
+        return (completed, skipped);
+    }
+
+    let metadata_pubkeys: HashSet<&Pubkey> = metadata_pubkeys.iter().collect();
+
+    for item in report.items.iter().filter(|item| item.status.is_complete()) {
+        let metadata_pubkey = match Pubkey::from_str(&item.mint) {
+            Ok(mint) => find_metadata_pda(&mint),
+            Err(_) => continue,
+        };
+
+        if metadata_pubkeys.contains(&metadata_pubkey) {
+            completed.push(item.clone());
+            skipped.insert(metadata_pubkey);
+        }
+    }
+
+    (completed, skipped)
+}
+
+fn write_audit_report(target: &Pubkey, items: Vec<AuditItem>) -> Result<()> {
+    let report = AuditReport {
+        target: target.to_string(),
+        items,
+    };
+    let f = File::create(COLLECTION_AUDIT_CACHE)
+        .map_err(|e| anyhow!("Failed to create sugar collection audit cache file: {e}"))?;
+    serde_json::to_writer_pretty(f, &report)?;
+
+    Ok(())
+}
+
+async fn repair_collection(
+    client: Arc<RpcClient>,
+    collection_authority: Arc<Keypair>,
+    target: &CollectionInfo,
+    value: RepairValues,
+) -> Result<()> {
+    let authority = collection_authority.pubkey();
+    let mut instructions = Vec::new();
+
+    if let Some(current) = value.unverify {
+        let metadata = find_metadata_pda(&current.mint);
+        let edition = find_master_edition_pda(&current.mint);
+
+        instructions.push(if current.sized {
+            unverify_sized_collection_item(
+                TOKEN_METADATA_PROGRAM_ID,
+                value.metadata_pubkey,
+                authority,
+                authority,
+                current.mint,
+                metadata,
+                edition,
+                None,
+            )
+        } else {
+            unverify_collection(
+                TOKEN_METADATA_PROGRAM_ID,
+                value.metadata_pubkey,
+                authority,
+                current.mint,
+                metadata,
+                edition,
+                None,
+            )
+        });
+    }
+
+    let set_and_verify = if target.sized {
+        set_and_verify_sized_collection_item
+    } else {
+        set_and_verify_collection
+    };
+
+    instructions.push(set_and_verify(
+        TOKEN_METADATA_PROGRAM_ID,
+        value.metadata_pubkey,
+        authority,
+        authority,
+        value.metadata.update_authority,
+        target.mint,
+        find_metadata_pda(&target.mint),
+        find_master_edition_pda(&target.mint),
+        None,
+    ));
+
+    let recent_blockhash = client.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(
+        &instructions,
+        Some(&authority),
+        &[&*collection_authority],
+        recent_blockhash,
+    );
+
+    client.send_and_confirm_transaction(&tx)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_token_metadata::state::CollectionDetails;
+
+    use super::*;
+
+    fn nft(collection: Option<(Pubkey, bool)>, is_mutable: bool, authority: Pubkey) -> Metadata {
+        Metadata {
+            mint: Pubkey::new_unique(),
+            update_authority: authority,
+            is_mutable,
+            collection: collection.map(|(key, verified)| Collection { verified, key }),
+            ..Default::default()
+        }
+    }
+
+    fn collection(authority: Pubkey, sized: bool) -> Metadata {
+        Metadata {
+            update_authority: authority,
+            collection_details: sized.then_some(CollectionDetails::V1 { size: 10 }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_group_collections() {
+        let authority = Pubkey::new_unique();
+        let target = Pubkey::new_unique();
+
+        let groups = group_collections(&[
+            nft(Some((target, true)), true, authority),
+            nft(Some((target, true)), true, authority),
+            nft(Some((target, false)), true, authority),
+            nft(None, true, authority),
+        ]);
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[&(target.to_string(), true)], 2);
+        assert_eq!(groups[&(target.to_string(), false)], 1);
+        assert_eq!(groups[&("none".to_string(), false)], 1);
+    }
+
+    #[test]
+    fn test_plan_repairs() {
+        let authority = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let target = Pubkey::new_unique();
+        let own_collection = Pubkey::new_unique();
+        let other_collection = Pubkey::new_unique();
+
+        let collections = HashMap::from([
+            (target, collection(authority, true)),
+            (own_collection, collection(authority, false)),
+            (other_collection, collection(other, false)),
+        ]);
+
+        let metadata = vec![
+            nft(Some((target, true)), true, authority),
+            nft(Some((target, false)), true, authority),
+            nft(None, true, authority),
+            nft(Some((own_collection, true)), true, authority),
+            nft(Some((other_collection, true)), true, authority),
+            nft(None, false, authority),
+            nft(None, true, other),
+        ];
+        let mints: Vec<Pubkey> = metadata.iter().map(|m| m.mint).collect();
+
+        let plan = plan_repairs(metadata, &target, &collections, &authority);
+
+        assert_eq!(plan.conforming, vec![mints[0]]);
+
+        let repair: Vec<(Pubkey, Option<Pubkey>)> = plan
+            .repair
+            .iter()
+            .map(|item| (item.metadata.mint, item.unverify.as_ref().map(|c| c.mint)))
+            .collect();
+        // an unverified item or an item without collection is only verified, an item of
+        // another collection of the authority is unverified first
+        assert_eq!(
+            repair,
+            vec![
+                (mints[1], None),
+                (mints[2], None),
+                (mints[3], Some(own_collection))
+            ]
+        );
+        assert!(!plan.repair[2].unverify.as_ref().unwrap().sized);
+
+        let unrepairable: Vec<&Pubkey> = plan.unrepairable.iter().map(|(mint, _)| mint).collect();
+        assert_eq!(unrepairable, vec![&mints[4], &mints[5], &mints[6]]);
+        assert!(plan.unrepairable[0].1.contains("another authority"));
+        assert_eq!(plan.unrepairable[1].1, "immutable metadata");
+        assert!(plan.unrepairable[2].1.contains(&other.to_string()));
+    }
+
+    #[test]
+    fn test_completed_items() {
+        let target = Pubkey::new_unique();
+        let mints: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let item = |mint: &Pubkey, status| AuditItem {
+            mint: mint.to_string(),
+            status,
+        };
+
+        let report = AuditReport {
+            target: target.to_string(),
+            items: vec![
+                item(&mints[0], AuditStatus::Repaired),
+                item(&mints[1], AuditStatus::Conforming),
+                item(&mints[2], AuditStatus::Failed("timeout".to_string())),
+                item(
+                    &mints[3],
+                    AuditStatus::Unrepairable("immutable metadata".to_string()),
+                ),
+                // not in the NFTs of this run
+                item(&Pubkey::new_unique(), AuditStatus::Repaired),
+            ],
+        };
+        let metadata_pubkeys: Vec<Pubkey> = mints.iter().map(find_metadata_pda).collect();
+
+        let (completed, skipped) = completed_items(&report, &target, &metadata_pubkeys);
+        assert_eq!(completed, report.items[..2].to_vec());
+        assert_eq!(
+            skipped,
+            HashSet::from([metadata_pubkeys[0], metadata_pubkeys[1]])
+        );
+
+        // the report of another target collection is ignored
+        let (completed, skipped) =
+            completed_items(&report, &Pubkey::new_unique(), &metadata_pubkeys);
+        assert!(completed.is_empty() && skipped.is_empty());
+
+        // the report round-trips
+        let json = serde_json::to_string(&report).unwrap();
+        let parsed: AuditReport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.items, report.items);
+        assert!(json.contains(r#""status":"repaired""#), "{json}");
+    }
+}
@@ -1,3 +1,5 @@
+pub mod audit;
 pub mod set;
 
+pub use audit::*;
 pub use set::*;
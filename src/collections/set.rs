@@ -1,10 +1,10 @@
 use std::{ops::Deref, str::FromStr};
 
 use anchor_client::solana_sdk::{
-    compute_budget::ComputeBudgetInstruction, pubkey::Pubkey, system_program,
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+    system_program,
 };
 use anyhow::Result;
-use console::style;
 use mpl_candy_machine_core::{
     accounts as nft_accounts, instruction as nft_instruction, AccountVersion,
 };
@@ -19,8 +19,11 @@ use crate::{
     cache::load_cache,
     candy_machine::{CANDY_MACHINE_ID, *},
     common::*,
+    compute_budget::{send_transaction, ComputeBudget},
     config::get_config_data,
     hash::hash_and_update,
+    offline::{unsigned_setup, CacheUpdate, UnsignedTxArgs, UnsignedTxWriter},
+    output::style,
     pdas::*,
     update::{process_update, UpdateArgs},
     utils::{assert_correct_authority, spinner_with_style},
@@ -34,10 +37,17 @@ pub struct SetCollectionArgs {
     pub config: String,
     pub candy_machine: Option<String>,
     pub priority_fee: u64,
+    pub unsigned_tx: Option<UnsignedTxArgs>,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
-    let sugar_config = sugar_setup(args.keypair.clone(), args.rpc_url.clone())?;
+    let (sugar_config, authority) = unsigned_setup(
+        args.keypair.clone(),
+        args.rpc_url.clone(),
+        args.unsigned_tx.as_ref(),
+    )?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(CANDY_MACHINE_ID);
     let mut cache = Cache::new();
@@ -47,6 +57,7 @@ pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
         Some(ref candy_machine_id) => candy_machine_id.to_owned(),
         None => {
             cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
             cache.program.candy_machine.clone()
         }
     };
@@ -72,12 +83,12 @@ pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
         }
     };
 
-    println!(
+    report!(
         "{} {}Loading candy machine",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
-    println!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+    report!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -91,28 +102,65 @@ pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
 
     pb.finish_with_message("Done");
 
-    assert_correct_authority(
-        &sugar_config.keypair.pubkey(),
-        &candy_machine_state.authority,
-    )?;
+    assert_correct_authority(&authority, &candy_machine_state.authority)?;
 
-    println!(
+    report!(
         "\n{} {}Setting collection mint for candy machine",
         style("[2/2]").bold().dim(),
         COLLECTION_EMOJI
     );
 
-    let pb = spinner_with_style();
-    pb.set_message("Sending set collection transaction...");
-
-    let set_signature = set_collection(
+    let instructions = set_collection_instructions(
         &program,
+        authority,
         &candy_pubkey,
         &candy_machine_state,
         &collection_mint_pubkey,
         &collection_metadata_info,
         &collection_edition_info,
-        &args,
+        args.priority_fee,
+    )?;
+
+    if let Some(unsigned_tx) = &args.unsigned_tx {
+        // the cache is updated by send-signed, once the transaction is confirmed
+        let cache_update = if args.candy_machine.is_none() {
+            CacheUpdate::CollectionSet {
+                mint: collection_mint_pubkey.to_string(),
+            }
+        } else {
+            CacheUpdate::None
+        };
+
+        let mut writer = UnsignedTxWriter::new("collection set", unsigned_tx, authority)?;
+        writer.add(
+            &program.rpc(),
+            "set-collection",
+            "Set the candy machine collection",
+            &instructions,
+            &[],
+            cache_update,
+        )?;
+        writer.finish()?;
+
+        if candy_machine_state.data.hidden_settings.is_some() {
+            report!(
+                "\nCandy machine has hidden settings: once the transaction is sent, run 'sugar \
+                hash' and 'sugar config update --unsigned-tx-out' to update the hash value."
+            );
+        }
+
+        return Ok(());
+    }
+
+    let pb = spinner_with_style();
+    pb.set_message("Sending set collection transaction...");
+
+    let set_signature = send_transaction(
+        &program.rpc(),
+        &sugar_config.keypair,
+        &instructions,
+        &[],
+        &args.compute_budget,
     )?;
 
     pb.finish_with_message(format!(
@@ -133,13 +181,13 @@ pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
             let mut config_data = get_config_data(&args.config)?;
             let hidden_settings = config_data.hidden_settings.as_ref().unwrap().clone();
 
-            println!(
+            report!(
                 "\n{} {}",
                 style("Hidden settings hash:").bold(),
                 hash_and_update(hidden_settings, &args.config, &mut config_data, &args.cache,)?
             );
 
-            println!(
+            report!(
                 "\nCandy machine has hidden settings and cache file was updated. Updating hash value...\n"
             );
 
@@ -151,6 +199,9 @@ pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
                 config: args.config,
                 candy_machine: Some(candy_machine_id),
                 priority_fee: args.priority_fee,
+                unsigned_tx: None,
+                compute_budget: args.compute_budget,
+                allow_cluster_mismatch: args.allow_cluster_mismatch,
             };
 
             process_update(update_args)?;
@@ -160,17 +211,19 @@ pub fn process_set_collection(args: SetCollectionArgs) -> Result<()> {
     Ok(())
 }
 
-pub fn set_collection<C: Deref<Target = impl Signer> + Clone>(
+/// Instructions of the transaction setting the collection of a candy machine, signed by its
+/// `authority`.
+#[allow(clippy::too_many_arguments)]
+pub fn set_collection_instructions<C: Deref<Target = impl Signer> + Clone>(
     program: &Program<C>,
+    authority: Pubkey,
     candy_pubkey: &Pubkey,
     candy_machine_state: &CandyMachine,
     new_collection_mint_pubkey: &Pubkey,
     new_collection_metadata_info: &PdaInfo<Metadata>,
     new_collection_edition_info: &PdaInfo<MasterEditionV2>,
-    args: &SetCollectionArgs,
-) -> Result<Signature> {
-    let payer = program.payer();
-
+    priority_fee: u64,
+) -> Result<Vec<Instruction>> {
     let (authority_pda, _) = find_candy_machine_creator_pda(candy_pubkey);
 
     let (new_collection_metadata_pubkey, new_collection_metadata) = new_collection_metadata_info;
@@ -184,10 +237,10 @@ pub fn set_collection<C: Deref<Target = impl Signer> + Clone>(
     )
     .0;
 
-    if new_collection_metadata.update_authority != payer {
+    if new_collection_metadata.update_authority != authority {
         return Err(anyhow!(CustomCandyError::AuthorityMismatch(
             new_collection_metadata.update_authority.to_string(),
-            payer.to_string()
+            authority.to_string()
         )));
     }
 
@@ -220,16 +273,16 @@ pub fn set_collection<C: Deref<Target = impl Signer> + Clone>(
     let collection_update_authority = collection_metadata.update_authority;
     let collection_metadata = find_metadata_pda(&collection_mint);
 
-    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
+    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
 
-    let builder = program
+    let instructions = program
         .request()
         .instruction(priority_fee)
         .accounts(nft_accounts::SetCollectionV2 {
             candy_machine: *candy_pubkey,
-            authority: payer,
+            authority,
             authority_pda,
-            payer,
+            payer: authority,
             collection_mint,
             collection_metadata,
             collection_update_authority,
@@ -245,9 +298,8 @@ pub fn set_collection<C: Deref<Target = impl Signer> + Clone>(
             authorization_rules_program: None,
             authorization_rules: None,
         })
-        .args(nft_instruction::SetCollectionV2);
-
-    let sig = builder.send()?;
+        .args(nft_instruction::SetCollectionV2)
+        .instructions()?;
 
-    Ok(sig)
+    Ok(instructions)
 }
@@ -0,0 +1,426 @@
+//! Compute budget of the transactions sent by the commands (`--compute-unit-limit` and
+//! `--simulate`), sent as legacy or v0 transactions (`--use-lut`).
+
+use anchor_client::solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, VersionedMessage},
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
+use solana_client::rpc_client::{RpcClient, SerializableTransaction};
+
+use crate::{common::*, output::style, priority_fee::DEFAULT_COMPUTE_UNITS};
+
+/// Maximum compute unit limit of a transaction.
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+/// Size of the compute unit limit instruction in a transaction that already sets the compute
+/// unit price: the program index (1), the accounts (1) and the data (1 + 5).
+pub const COMPUTE_UNIT_LIMIT_INSTRUCTION_SIZE: usize = 8;
+
+/// Compute budget of the transactions sent by a command.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ComputeBudget {
+    /// Compute unit limit of the transactions, replacing the limit set by the command
+    /// (`--compute-unit-limit`).
+    pub compute_unit_limit: Option<u32>,
+    /// Simulate each transaction before sending it, setting its compute unit limit to the
+    /// units consumed (`--simulate`).
+    pub simulate: bool,
+}
+
+impl ComputeBudget {
+    /// Compute unit limit of the transactions of a command that requests `requested` units,
+    /// used to price their priority fee. `--compute-unit-limit` replaces the limit set by the
+    /// command, while the limit of `--simulate` is never above the requested one.
+    pub fn compute_unit_limit(&self, requested: u32) -> u32 {
+        self.compute_unit_limit.unwrap_or(requested)
+    }
+
+    /// Size taken by the compute unit limit instruction that is added to the transactions
+    /// sent by sugar with `--compute-unit-limit` or `--simulate`.
+    pub fn compute_unit_limit_size(&self) -> usize {
+        if self.compute_unit_limit.is_some() || self.simulate {
+            COMPUTE_UNIT_LIMIT_INSTRUCTION_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Same as [`compute_unit_limit_size`](Self::compute_unit_limit_size) for the
+    /// transactions that are not sent by sugar, which only get the instruction with
+    /// `--compute-unit-limit`.
+    pub fn unsigned_compute_unit_limit_size(&self) -> usize {
+        if self.compute_unit_limit.is_some() {
+            COMPUTE_UNIT_LIMIT_INSTRUCTION_SIZE
+        } else {
+            0
+        }
+    }
+
+    /// Instructions with the compute unit limit of `--compute-unit-limit`, if set.
+    /// Transactions that are not sent by sugar (e.g. written unsigned) are not simulated.
+    pub fn apply_compute_unit_limit(&self, instructions: &[Instruction]) -> Vec<Instruction> {
+        match self.compute_unit_limit {
+            Some(limit) => with_compute_unit_limit(limit, instructions),
+            None => instructions.to_vec(),
+        }
+    }
+}
+
+/// Compute unit limit of a transaction that consumed `consumed` units in the simulation,
+/// with a 10% margin.
+pub fn simulated_limit(consumed: u64) -> u32 {
+    let limit = (consumed * 11 + 9) / 10;
+    limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+}
+
+/// Whether an instruction sets the compute unit limit of its transaction.
+fn is_compute_unit_limit(instruction: &Instruction) -> bool {
+    let limit = ComputeBudgetInstruction::set_compute_unit_limit(0);
+    instruction.program_id == limit.program_id && instruction.data.first() == limit.data.first()
+}
+
+/// Compute units requested by the instructions: the limit they set or, when they set none, the
+/// default limit of each instruction.
+fn requested_compute_units(instructions: &[Instruction]) -> u32 {
+    let limit = ComputeBudgetInstruction::set_compute_unit_limit(0);
+
+    if let Some(instruction) = instructions.iter().find(|ix| is_compute_unit_limit(ix)) {
+        if let Some(units) = instruction
+            .data
+            .get(1..5)
+            .and_then(|bytes| <[u8; 4]>::try_from(bytes).ok())
+        {
+            return u32::from_le_bytes(units);
+        }
+    }
+
+    let count = instructions
+        .iter()
+        .filter(|instruction| instruction.program_id != limit.program_id)
+        .count() as u32;
+    DEFAULT_COMPUTE_UNITS
+        .saturating_mul(count.max(1))
+        .min(MAX_COMPUTE_UNIT_LIMIT)
+}
+
+/// Instructions with a compute unit limit instruction replacing the ones of `instructions`.
+fn with_compute_unit_limit(limit: u32, instructions: &[Instruction]) -> Vec<Instruction> {
+    let mut limited = vec![ComputeBudgetInstruction::set_compute_unit_limit(limit)];
+    limited.extend(
+        instructions
+            .iter()
+            .filter(|instruction| !is_compute_unit_limit(instruction))
+            .cloned(),
+    );
+    limited
+}
+
+fn sign(
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+    blockhash: Hash,
+) -> Transaction {
+    let mut all_signers = vec![payer];
+    all_signers.extend_from_slice(signers);
+
+    Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &all_signers, blockhash)
+}
+
+/// Signs a v0 transaction loading the accounts of the instructions found in the lookup table
+/// from it.
+fn sign_versioned(
+    instructions: &[Instruction],
+    payer: &Keypair,
+    signers: &[&Keypair],
+    lookup_table: &AddressLookupTableAccount,
+    blockhash: Hash,
+) -> Result<VersionedTransaction> {
+    let message = v0::Message::try_compile(
+        &payer.pubkey(),
+        instructions,
+        &[lookup_table.clone()],
+        blockhash,
+    )?;
+
+    let mut all_signers: Vec<&dyn Signer> = vec![payer];
+    all_signers.extend(signers.iter().map(|signer| *signer as &dyn Signer));
+
+    Ok(VersionedTransaction::try_new(
+        VersionedMessage::V0(message),
+        &all_signers,
+    )?)
+}
+
+/// Simulates a transaction, returning the compute units it consumed. When it fails, the
+/// program logs are printed.
+fn simulate<T: SerializableTransaction>(rpc: &RpcClient, transaction: &T) -> Result<u64> {
+    let result = rpc.simulate_transaction(transaction)?.value;
+
+    if let Some(err) = result.err {
+        report!("{}", style("Transaction simulation failed:").red().bold());
+        for log in result.logs.unwrap_or_default() {
+            report!("  {}", log);
+        }
+        return Err(anyhow!(
+            "Transaction simulation failed, nothing was sent: {}",
+            err
+        ));
+    }
+
+    result.units_consumed.ok_or_else(|| {
+        anyhow!("The RPC node did not report the compute units consumed by the simulation")
+    })
+}
+
+/// Signs the transaction of the instructions with `sign`, setting its compute unit limit.
+///
+/// With `--simulate`, the transaction is simulated first and its compute unit limit is set to
+/// the units consumed (plus a margin), never above the units requested by the instructions so
+/// that the priority fee paid stays within the one planned by the command;
+/// `--compute-unit-limit` overrides the limit. Otherwise the instructions are sent as they are.
+fn prepare<T: SerializableTransaction>(
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    compute_budget: &ComputeBudget,
+    sign: impl Fn(&[Instruction], Hash) -> Result<T>,
+) -> Result<T> {
+    let requested = compute_budget.compute_unit_limit;
+    let blockhash = rpc.get_latest_blockhash()?;
+
+    if !compute_budget.simulate {
+        return match requested {
+            Some(limit) => sign(&with_compute_unit_limit(limit, instructions), blockhash),
+            None => sign(instructions, blockhash),
+        };
+    }
+
+    let planned = compute_budget.compute_unit_limit(requested_compute_units(instructions));
+    let simulated = sign(&with_compute_unit_limit(planned, instructions), blockhash)?;
+    let consumed = simulate(rpc, &simulated)?;
+    let limit = requested.unwrap_or_else(|| simulated_limit(consumed).min(planned));
+
+    report!(
+        "{} {} compute units consumed, limit set to {}",
+        style("Simulation:").bold(),
+        consumed,
+        limit
+    );
+
+    sign(&with_compute_unit_limit(limit, instructions), blockhash)
+}
+
+/// Builds and signs the transaction of the instructions, paid by `payer` and signed by the
+/// `signers` too (see [`prepare`] for its compute unit limit).
+pub fn prepare_transaction(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    compute_budget: &ComputeBudget,
+) -> Result<Transaction> {
+    prepare(
+        rpc,
+        instructions,
+        compute_budget,
+        |instructions, blockhash| Ok(sign(instructions, payer, signers, blockhash)),
+    )
+}
+
+/// Same as [`prepare_transaction`], building a v0 transaction that references the
+/// `lookup_table`.
+pub fn prepare_versioned_transaction(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    lookup_table: &AddressLookupTableAccount,
+    compute_budget: &ComputeBudget,
+) -> Result<VersionedTransaction> {
+    prepare(
+        rpc,
+        instructions,
+        compute_budget,
+        |instructions, blockhash| {
+            sign_versioned(instructions, payer, signers, lookup_table, blockhash)
+        },
+    )
+}
+
+/// Sends the transaction of the instructions and waits for its confirmation (see
+/// [`prepare_transaction`]).
+pub fn send_transaction(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    compute_budget: &ComputeBudget,
+) -> Result<Signature> {
+    let transaction = prepare_transaction(rpc, payer, instructions, signers, compute_budget)?;
+    Ok(rpc.send_and_confirm_transaction(&transaction)?)
+}
+
+/// Same as [`send_transaction`], sending a v0 transaction when there is a lookup table.
+pub fn send_transaction_with_lookup_table(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+    lookup_table: Option<&AddressLookupTableAccount>,
+    compute_budget: &ComputeBudget,
+) -> Result<Signature> {
+    match lookup_table {
+        Some(lookup_table) => {
+            let transaction = prepare_versioned_transaction(
+                rpc,
+                payer,
+                instructions,
+                signers,
+                lookup_table,
+                compute_budget,
+            )?;
+            Ok(rpc.send_and_confirm_transaction(&transaction)?)
+        }
+        None => send_transaction(rpc, payer, instructions, signers, compute_budget),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_client::solana_sdk::instruction::AccountMeta;
+
+    use super::*;
+    use crate::priority_fee::{resolve_priority_fee, total_priority_lamports, PriorityFee};
+
+    #[test]
+    fn simulated_limit_adds_a_margin() {
+        assert_eq!(simulated_limit(100_000), 110_000);
+        // rounded up
+        assert_eq!(simulated_limit(12_345), 13_580);
+        assert_eq!(simulated_limit(1_300_000), MAX_COMPUTE_UNIT_LIMIT);
+    }
+
+    #[test]
+    fn replaces_the_compute_unit_limit() {
+        let price = ComputeBudgetInstruction::set_compute_unit_price(500);
+        let instructions = vec![
+            ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+            price.clone(),
+        ];
+
+        let limited = with_compute_unit_limit(150_000, &instructions);
+
+        assert_eq!(
+            limited,
+            vec![
+                ComputeBudgetInstruction::set_compute_unit_limit(150_000),
+                price
+            ]
+        );
+        assert!(!is_compute_unit_limit(&limited[1]));
+    }
+
+    #[test]
+    fn requested_compute_units_of_the_instructions() {
+        let price = ComputeBudgetInstruction::set_compute_unit_price(500);
+        let instruction = Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]);
+
+        assert_eq!(
+            requested_compute_units(&[
+                ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+                price.clone(),
+                instruction.clone(),
+            ]),
+            400_000
+        );
+        // the compute budget instructions do not request units
+        assert_eq!(
+            requested_compute_units(&[price, instruction.clone()]),
+            DEFAULT_COMPUTE_UNITS
+        );
+        let instructions: Vec<_> = std::iter::repeat(instruction).take(10).collect();
+        assert_eq!(
+            requested_compute_units(&instructions),
+            MAX_COMPUTE_UNIT_LIMIT
+        );
+    }
+
+    #[test]
+    fn prices_the_fee_for_the_compute_unit_limit() {
+        let compute_budget = ComputeBudget {
+            compute_unit_limit: Some(MAX_COMPUTE_UNIT_LIMIT),
+            simulate: false,
+        };
+        let limit = compute_budget.compute_unit_limit(DEFAULT_COMPUTE_UNITS);
+        assert_eq!(limit, MAX_COMPUTE_UNIT_LIMIT);
+        assert_eq!(
+            ComputeBudget::default().compute_unit_limit(DEFAULT_COMPUTE_UNITS),
+            DEFAULT_COMPUTE_UNITS
+        );
+
+        // 0.0001 SOL per transaction over 1.4M units, not 200k
+        let price = PriorityFee::LamportsPerTx(100_000).micro_lamports_per_cu(limit);
+        assert_eq!(price, 71_428);
+        assert_eq!(total_priority_lamports(price, limit, 1), 100_000);
+
+        // 500 micro-lamports per unit is 700 lamports per transaction at 1.4M units: the
+        // price is lowered to keep 10 transactions within 1000 lamports
+        let price = resolve_priority_fee(
+            PriorityFee::MicroLamportsPerCu(500),
+            limit,
+            10,
+            Some(0.000001),
+        )
+        .unwrap();
+        assert_eq!(price, 71);
+        assert!(total_priority_lamports(price, limit, 10) <= 1_000);
+    }
+
+    #[test]
+    fn versioned_transaction_loads_accounts_from_the_lookup_table() {
+        let payer = Keypair::new();
+        let signer = Keypair::new();
+        let writable = Pubkey::new_unique();
+        let readonly = Pubkey::new_unique();
+        let instruction = Instruction::new_with_bytes(
+            Pubkey::new_unique(),
+            &[],
+            vec![
+                AccountMeta::new(signer.pubkey(), true),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+            ],
+        );
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            // signers are never loaded from a lookup table
+            addresses: vec![readonly, signer.pubkey(), writable],
+        };
+
+        let transaction = sign_versioned(
+            &[instruction],
+            &payer,
+            &[&signer],
+            &lookup_table,
+            Hash::default(),
+        )
+        .unwrap();
+
+        assert!(transaction.verify_with_results().iter().all(|valid| *valid));
+        match &transaction.message {
+            VersionedMessage::V0(message) => {
+                assert_eq!(message.account_keys.len(), 3);
+                assert_eq!(message.address_table_lookups.len(), 1);
+                assert_eq!(message.address_table_lookups[0].writable_indexes, vec![2]);
+                assert_eq!(message.address_table_lookups[0].readonly_indexes, vec![0]);
+            }
+            VersionedMessage::Legacy(_) => panic!("expected a v0 message"),
+        }
+    }
+}
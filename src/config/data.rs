@@ -8,10 +8,17 @@ use anchor_client::solana_sdk::{
 };
 pub use anyhow::{anyhow, Result};
 use chrono::prelude::*;
+use indexmap::IndexMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use super::CandyGuardData;
-use crate::config::errors::*;
+use crate::{
+    config::errors::*,
+    constants::{
+        BUNDLR_DEVNET, BUNDLR_MAINNET, BUNDLR_NODE2, DEFAULT_OPTIMIZED_IMAGES_DIR,
+        DEFAULT_OPTIMIZE_JPEG_QUALITY, DEFAULT_OPTIMIZE_THRESHOLD_KB,
+    },
+};
 
 pub struct SugarConfig {
     pub keypair: Keypair,
@@ -53,11 +60,30 @@ pub struct ConfigData {
     /// Upload method to use
     pub upload_method: UploadMethod,
 
+    /// Maximum size (in MB) of a file to upload
+    pub max_file_size_mb: Option<u64>,
+
+    /// Size (in MB) above which a file is streamed from disk during the upload (50 MB by
+    /// default)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub streaming_threshold_mb: Option<u64>,
+
+    /// Settings of the image optimization of `upload --optimize-images`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_optimization: Option<ImageOptimization>,
+
     // Token auth rules account (for pNFTs).
     #[serde(deserialize_with = "to_option_pubkey")]
     #[serde(serialize_with = "to_option_string")]
     pub rule_set: Option<Pubkey>,
 
+    // Bundlr specific configuration
+    pub bundlr_config: Option<BundlrConfig>,
+
+    /// Bundlr (Irys) node of the uploads, the node of the cluster when not set
+    #[serde(default, alias = "irysNode", skip_serializing_if = "Option::is_none")]
+    pub bundlr_node: Option<BundlrNode>,
+
     // AWS specific configuration
     pub aws_config: Option<AwsConfig>,
 
@@ -76,6 +102,10 @@ pub struct ConfigData {
     // Pinata specific configuration
     pub pinata_config: Option<PinataConfig>,
 
+    // HTTP PUT (self-hosted storage) specific configuration
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub http_put_config: Option<HttpPutConfig>,
+
     /// Hidden setttings
     pub hidden_settings: Option<HiddenSettings>,
 
@@ -85,12 +115,139 @@ pub struct ConfigData {
     pub max_edition_supply: Option<u64>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BundlrConfig {
+    /// Fund the Bundlr balance before the upload when it is not enough.
+    pub auto_fund: bool,
+    /// Margin (in percent) added to the missing amount when funding.
+    pub fund_margin_percent: u32,
+    /// Maximum amount (in lamports) funded before the upload.
+    pub max_fund_lamports: Option<u64>,
+}
+
+impl Default for BundlrConfig {
+    fn default() -> Self {
+        Self {
+            auto_fund: false,
+            fund_margin_percent: 30,
+            max_fund_lamports: None,
+        }
+    }
+}
+
+/// Settings of the re-encoding of the images before the upload (`--optimize-images`).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ImageOptimization {
+    /// Size (in KB) above which an image is optimized.
+    pub threshold_kb: u64,
+    /// Quality (1-100) of the re-encoded JPEG images.
+    pub jpeg_quality: u8,
+    /// Maximum width and height of the images, larger images are scaled down.
+    pub max_dimension: Option<u32>,
+    /// Directory of the optimized copies of the images.
+    pub work_dir: String,
+}
+
+impl Default for ImageOptimization {
+    fn default() -> Self {
+        Self {
+            threshold_kb: DEFAULT_OPTIMIZE_THRESHOLD_KB,
+            jpeg_quality: DEFAULT_OPTIMIZE_JPEG_QUALITY,
+            max_dimension: None,
+            work_dir: DEFAULT_OPTIMIZED_IMAGES_DIR.to_string(),
+        }
+    }
+}
+
+/// Bundlr (Irys) node: `node1`, `node2`, `devnet` or the URL of a node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum BundlrNode {
+    Node1,
+    Node2,
+    Devnet,
+    Url(String),
+}
+
+impl BundlrNode {
+    pub fn url(&self) -> &str {
+        match self {
+            BundlrNode::Node1 => BUNDLR_MAINNET,
+            BundlrNode::Node2 => BUNDLR_NODE2,
+            BundlrNode::Devnet => BUNDLR_DEVNET,
+            BundlrNode::Url(url) => url,
+        }
+    }
+
+    /// Cluster of the funds of the node, unknown for a URL.
+    pub fn cluster(&self) -> Option<Cluster> {
+        match self {
+            BundlrNode::Node1 | BundlrNode::Node2 => Some(Cluster::Mainnet),
+            BundlrNode::Devnet => Some(Cluster::Devnet),
+            BundlrNode::Url(_) => None,
+        }
+    }
+}
+
+impl FromStr for BundlrNode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "node1" => Ok(BundlrNode::Node1),
+            "node2" => Ok(BundlrNode::Node2),
+            "devnet" => Ok(BundlrNode::Devnet),
+            url if url.starts_with("https://") || url.starts_with("http://") => {
+                Ok(BundlrNode::Url(url.trim_end_matches('/').to_string()))
+            }
+            _ => Err(ConfigError::InvalidBundlrNode(s.to_string()).into()),
+        }
+    }
+}
+
+impl TryFrom<String> for BundlrNode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        BundlrNode::from_str(&value)
+    }
+}
+
+impl Display for BundlrNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BundlrNode::Node1 => write!(f, "node1"),
+            BundlrNode::Node2 => write!(f, "node2"),
+            BundlrNode::Devnet => write!(f, "devnet"),
+            BundlrNode::Url(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+impl From<BundlrNode> for String {
+    fn from(node: BundlrNode) -> Self {
+        node.to_string()
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AwsConfig {
     pub bucket: String,
     pub profile: String,
+    /// Key prefix of the uploaded files.
+    #[serde(alias = "prefix")]
     pub directory: String,
+    /// Base URL of the links written to the cache (e.g. a CloudFront distribution).
+    #[serde(alias = "publicBaseUrl")]
     pub domain: Option<String>,
+    /// Region of the bucket, read from the profile when not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    /// Endpoint of an S3-compatible storage (e.g. MinIO or R2).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
 }
 
 impl AwsConfig {
@@ -105,6 +262,8 @@ impl AwsConfig {
             profile,
             directory,
             domain,
+            region: None,
+            endpoint: None,
         }
     }
 }
@@ -112,19 +271,62 @@ impl AwsConfig {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PinataConfig {
-    pub jwt: String,
+    /// Pinata JWT, read from the `PINATA_JWT` environment variable when not set.
+    pub jwt: Option<String>,
     pub api_gateway: String,
-    pub content_gateway: String,
+    /// Gateway of the links written to the cache, `ipfs://` links when not set.
+    pub content_gateway: Option<String>,
     pub parallel_limit: Option<u16>,
+    /// Version of the CIDs of the pinned files.
+    #[serde(default)]
+    pub cid_version: CidVersion,
 }
 
 impl PinataConfig {
-    pub fn new(jwt: String, api_gateway: String, content_gateway: String) -> PinataConfig {
+    pub fn new(
+        jwt: Option<String>,
+        api_gateway: String,
+        content_gateway: Option<String>,
+    ) -> PinataConfig {
         PinataConfig {
             jwt,
             api_gateway,
             content_gateway,
             parallel_limit: None,
+            cid_version: CidVersion::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpPutConfig {
+    /// URL of the PUT request of a file, where `{name}` is replaced by the file name (e.g.
+    /// `https://cdn.example.com/assets/{name}`).
+    pub url: String,
+    /// Headers of the requests, where `${VAR}` is replaced by the environment variable `VAR`.
+    #[serde(default)]
+    pub headers: IndexMap<String, String>,
+    /// Base URL of the links written to the cache (`<publicBaseUrl>/<name>`), the URL of the
+    /// PUT request when not set.
+    pub public_base_url: Option<String>,
+    pub parallel_limit: Option<u16>,
+}
+
+/// Version of the CIDs of the pinned files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CidVersion {
+    #[default]
+    V0,
+    V1,
+}
+
+impl CidVersion {
+    pub fn number(&self) -> u8 {
+        match self {
+            CidVersion::V0 => 0,
+            CidVersion::V1 => 1,
         }
     }
 }
@@ -220,14 +422,23 @@ impl HiddenSettings {
 pub enum UploadMethod {
     #[default]
     Bundlr,
-    #[serde(rename = "aws")]
+    /// AWS S3 or an S3-compatible storage.
+    #[serde(rename = "aws", alias = "s3")]
     AWS,
     NftStorage,
     #[serde(rename = "shdw")]
     SHDW,
+    /// IPFS pinning through the Pinata API.
+    #[serde(alias = "ipfs")]
     Pinata,
     #[serde(rename = "sdrive")]
     Sdrive,
+    /// PUT requests to a self-hosted storage.
+    #[serde(alias = "httpPut")]
+    HttpPut,
+    /// Local storage that only generates links, used by the integration tests.
+    #[cfg(feature = "integration-tests")]
+    Mock,
 }
 
 impl Display for UploadMethod {
@@ -331,3 +542,46 @@ impl FromStr for TokenStandard {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config_with_upload_method(method: &str) -> ConfigData {
+        serde_json::from_str(&format!(
+            r#"{{
+                "number": 2,
+                "symbol": "",
+                "sellerFeeBasisPoints": 0,
+                "isMutable": true,
+                "isSequential": false,
+                "creators": [
+                    {{ "address": "ENsRsTm9GtfvPKJwg9HhHNUFqoRSUp2TYdfyqDHeNJAn", "share": 100 }}
+                ],
+                "uploadMethod": "{method}",
+                "ruleSet": null
+            }}"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn parses_upload_method_aliases() {
+        assert_eq!(
+            config_with_upload_method("pinata").upload_method,
+            UploadMethod::Pinata
+        );
+        assert_eq!(
+            config_with_upload_method("ipfs").upload_method,
+            UploadMethod::Pinata
+        );
+        assert_eq!(
+            config_with_upload_method("s3").upload_method,
+            UploadMethod::AWS
+        );
+
+        // the aliases are written back with the name of the method
+        let value = serde_json::to_value(config_with_upload_method("ipfs")).unwrap();
+        assert_eq!(value["uploadMethod"], "pinata");
+    }
+}
@@ -22,4 +22,7 @@ pub enum ConfigError {
 
     #[error("Invalid token standard '{0}'")]
     InvalidTokenStandard(String),
+
+    #[error("Invalid Bundlr node '{0}', expected 'node1', 'node2', 'devnet' or a URL")]
+    InvalidBundlrNode(String),
 }
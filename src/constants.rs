@@ -1,8 +1,9 @@
-use console::Emoji;
 pub use mpl_token_metadata::state::{
     MAX_CREATOR_LEN, MAX_CREATOR_LIMIT, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH,
 };
 
+use crate::output::Emoji;
+
 /// Metaplex program id.
 pub const METAPLEX_PROGRAM_ID: &str = "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s";
 
@@ -30,6 +31,28 @@ pub const DEFAULT_ASSETS: &str = "assets";
 /// Default path for cache file.
 pub const DEFAULT_CACHE: &str = "cache.json";
 
+/// Default path for the report of the files that failed to upload.
+pub const DEFAULT_UPLOAD_ERROR_REPORT: &str = "upload-errors.json";
+
+/// Default size (in MB) above which a file is streamed from disk during the upload.
+pub const DEFAULT_STREAMING_THRESHOLD_MB: u64 = 50;
+
+/// Default size (in KB) above which an image is optimized with `--optimize-images`.
+pub const DEFAULT_OPTIMIZE_THRESHOLD_KB: u64 = 1024;
+
+/// Default quality of the JPEG images optimized with `--optimize-images`.
+pub const DEFAULT_OPTIMIZE_JPEG_QUALITY: u8 = 85;
+
+/// Default directory of the images optimized with `--optimize-images`.
+pub const DEFAULT_OPTIMIZED_IMAGES_DIR: &str = ".sugar/optimized-images";
+
+/// Default time (in seconds) to wait for the confirmation of a Bundlr transaction with
+/// `--verify-receipts`.
+pub const DEFAULT_RECEIPT_TIMEOUT_SECS: u64 = 300;
+
+/// Default path for the map of the assets of a shuffled upload.
+pub const DEFAULT_SHUFFLE_MAP: &str = "shuffle-map.json";
+
 /// Default path for airdrop list
 pub const DEFAULT_AIRDROP_LIST: &str = "airdrop_list.json";
 pub const DEFAULT_AIRDROP_LIST_HELP: &str = "Path to airdrop targets list, format: \n{\n\"address1\": number_of_tokens,\n\"address2\": number_of_tokens\n}\n";
@@ -40,11 +63,17 @@ pub const DEFAULT_CONFIG: &str = "config.json";
 /// Default path for keypair file.
 pub const DEFAULT_KEYPATH: &str = "~/.config/solana/id.json";
 
-/// Bundlr devnet endpoint.
-pub const BUNDLR_DEVNET: &str = "https://devnet.bundlr.network";
+/// Gateway used to read `ipfs://` links.
+pub const DEFAULT_IPFS_GATEWAY: &str = "https://nftstorage.link";
+
+/// Bundlr (Irys) devnet endpoint.
+pub const BUNDLR_DEVNET: &str = "https://devnet.irys.xyz";
 
-/// Bundlr mainnet endpoint.
-pub const BUNDLR_MAINNET: &str = "https://node1.bundlr.network";
+/// Bundlr (Irys) mainnet endpoint.
+pub const BUNDLR_MAINNET: &str = "https://node1.irys.xyz";
+
+/// Bundlr (Irys) second mainnet endpoint.
+pub const BUNDLR_NODE2: &str = "https://node2.irys.xyz";
 
 pub const CIVIC_NETWORK: &str = "ignREusXmGrscGNUesoU9mxfds9AiYTezUKex2PsZV6";
 
@@ -94,6 +123,8 @@ pub const WRAP_EMOJI: Emoji<'_, '_> = Emoji("📦 ", "");
 
 pub const UNWRAP_EMOJI: Emoji<'_, '_> = Emoji("🔩 ", "");
 
+pub const TREE_EMOJI: Emoji<'_, '_> = Emoji("🌳 ", "");
+
 pub const MAX_FREEZE_DAYS: u8 = 31;
 
 pub const COMPUTE_UNITS: u32 = 400_000;
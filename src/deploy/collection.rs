@@ -1,4 +1,8 @@
-use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
+use std::ops::Deref;
+
+use anchor_client::solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, instruction::Instruction, pubkey::Pubkey,
+};
 use anyhow::Result;
 use mpl_token_metadata::{
     instruction::{create_master_edition_v3, create_metadata_accounts_v3},
@@ -15,30 +19,64 @@ use spl_token::{
 use crate::{
     candy_machine::CANDY_MACHINE_ID,
     common::*,
+    compute_budget::{send_transaction, ComputeBudget},
     config::ConfigData,
-    deploy::DeployArgs,
+    deploy::errors::DeployError,
     pdas::{find_master_edition_pda, find_metadata_pda},
     setup::SugarClient,
 };
 
 pub fn create_collection(
     client: &SugarClient,
+    keypair: &Keypair,
     _candy_machine: Pubkey,
     cache: &mut Cache,
     config_data: &ConfigData,
-    args: &DeployArgs,
+    priority_fee: u64,
+    compute_budget: &ComputeBudget,
 ) -> Result<(Signature, Pubkey)> {
     let program = client.program(CANDY_MACHINE_ID);
-    let payer = program.payer();
 
     let collection_mint = Keypair::new();
     let collection_item: &mut CacheItem = match cache.items.get_mut("-1") {
         Some(item) => item,
-        None => {
-            return Err(anyhow!("Trying to create and set collection when collection item info isn't in cache! This shouldn't happen!"));
-        }
+        None => return Err(DeployError::MissingCollectionItem.into()),
     };
 
+    let instructions = create_collection_instructions(
+        &program,
+        program.payer(),
+        &collection_mint.pubkey(),
+        collection_item,
+        config_data,
+        priority_fee,
+    )?;
+
+    let sig = send_transaction(
+        &program.rpc(),
+        keypair,
+        &instructions,
+        &[&collection_mint],
+        compute_budget,
+    )?;
+
+    collection_item.on_chain = true;
+    cache.program.collection_mint = collection_mint.pubkey().to_string();
+    cache.sync_file()?;
+
+    Ok((sig, collection_mint.pubkey()))
+}
+
+/// Instructions of the transaction minting the collection NFT of the cache item, paid by
+/// `payer` (the update authority of the collection).
+pub fn create_collection_instructions<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    payer: Pubkey,
+    collection_mint: &Pubkey,
+    collection_item: &CacheItem,
+    config_data: &ConfigData,
+    priority_fee: u64,
+) -> Result<Vec<Instruction>> {
     // Allocate memory for the account
     let min_rent = program
         .rpc()
@@ -47,31 +85,26 @@ pub fn create_collection(
     // Create mint account
     let create_mint_account_ix = system_instruction::create_account(
         &payer,
-        &collection_mint.pubkey(),
+        collection_mint,
         min_rent,
         MINT_LAYOUT,
         &TOKEN_PROGRAM_ID,
     );
 
     // Initialize mint ix
-    let init_mint_ix = initialize_mint(
-        &TOKEN_PROGRAM_ID,
-        &collection_mint.pubkey(),
-        &payer,
-        Some(&payer),
-        0,
-    )?;
+    let init_mint_ix =
+        initialize_mint(&TOKEN_PROGRAM_ID, collection_mint, &payer, Some(&payer), 0)?;
 
-    let ata_pubkey = get_associated_token_address(&payer, &collection_mint.pubkey());
+    let ata_pubkey = get_associated_token_address(&payer, collection_mint);
 
     // Create associated account instruction
     let create_assoc_account_ix =
-        create_associated_token_account(&payer, &payer, &collection_mint.pubkey(), &spl_token::ID);
+        create_associated_token_account(&payer, &payer, collection_mint, &spl_token::ID);
 
     // Mint to instruction
     let mint_to_ix = mint_to(
         &TOKEN_PROGRAM_ID,
-        &collection_mint.pubkey(),
+        collection_mint,
         &ata_pubkey,
         &payer,
         &[],
@@ -83,12 +116,12 @@ pub fn create_collection(
         verified: true,
         share: 100,
     };
-    let collection_metadata_pubkey = find_metadata_pda(&collection_mint.pubkey());
+    let collection_metadata_pubkey = find_metadata_pda(collection_mint);
 
     let create_metadata_account_ix = create_metadata_accounts_v3(
         mpl_token_metadata::ID,
         collection_metadata_pubkey,
-        collection_mint.pubkey(),
+        *collection_mint,
         payer,
         payer,
         payer,
@@ -104,36 +137,27 @@ pub fn create_collection(
         Some(CollectionDetails::V1 { size: 0 }),
     );
 
-    let collection_edition_pubkey = find_master_edition_pda(&collection_mint.pubkey());
+    let collection_edition_pubkey = find_master_edition_pda(collection_mint);
 
     let create_master_edition_ix = create_master_edition_v3(
         mpl_token_metadata::ID,
         collection_edition_pubkey,
-        collection_mint.pubkey(),
+        *collection_mint,
         payer,
         payer,
         collection_metadata_pubkey,
         payer,
         Some(0),
     );
-    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
-
-    let builder = program
-        .request()
-        .instruction(priority_fee)
-        .instruction(create_mint_account_ix)
-        .instruction(init_mint_ix)
-        .instruction(create_assoc_account_ix)
-        .instruction(mint_to_ix)
-        .signer(&collection_mint)
-        .instruction(create_metadata_account_ix)
-        .instruction(create_master_edition_ix);
-
-    let sig = builder.send()?;
-
-    collection_item.on_chain = true;
-    cache.program.collection_mint = collection_mint.pubkey().to_string();
-    cache.sync_file()?;
-
-    Ok((sig, collection_mint.pubkey()))
+    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
+
+    Ok(vec![
+        priority_fee,
+        create_mint_account_ix,
+        init_mint_ix,
+        create_assoc_account_ix,
+        mint_to_ix,
+        create_metadata_account_ix,
+        create_master_edition_ix,
+    ])
 }
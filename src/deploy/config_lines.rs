@@ -1,51 +1,215 @@
 use std::{
-    cmp,
+    collections::VecDeque,
+    fmt,
+    ops::Deref,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use anchor_client::solana_sdk::{
-    compute_budget::ComputeBudgetInstruction, pubkey::Pubkey, signature::Keypair,
+    address_lookup_table_account::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    hash::Hash,
+    instruction::Instruction,
+    packet::PACKET_DATA_SIZE,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
 };
 use anyhow::Result;
-use console::style;
-use futures::future::select_all;
+use async_trait::async_trait;
+use futures::future::join_all;
 use mpl_candy_machine_core::{
     accounts as nft_accounts, instruction as nft_instruction, CandyMachineData, ConfigLine,
 };
 pub use mpl_token_metadata::state::{
     MAX_CREATOR_LIMIT, MAX_NAME_LENGTH, MAX_SYMBOL_LENGTH, MAX_URI_LENGTH,
 };
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::{sleep, timeout},
+};
 
 use crate::{
-    cache::*, candy_machine::CANDY_MACHINE_ID, common::*, config::data::*, deploy::errors::*,
-    setup::setup_client, utils::*,
+    cache::*,
+    cancel,
+    candy_machine::CANDY_MACHINE_ID,
+    common::*,
+    compute_budget::{prepare_transaction, prepare_versioned_transaction, ComputeBudget},
+    config::data::*,
+    deploy::errors::*,
+    output::style,
+    progress_stream,
+    setup::setup_client,
+    utils::*,
 };
 
+/// Size of a transaction adding config lines, without the config lines: one signature (65),
+/// the message header (3), four account keys (129), the recent blockhash (32), the compute
+/// budget instruction (1 + 12) and the `add_config_lines` instruction without the lines (22).
+const TRANSACTION_OVERHEAD: usize = 264;
+
 /// The maximum config line bytes per transaction.
-const MAX_TRANSACTION_BYTES: usize = 966;
+pub const MAX_TRANSACTION_BYTES: usize = PACKET_DATA_SIZE - TRANSACTION_OVERHEAD;
+
+/// Size added by sending the config lines in a v0 transaction with a lookup table: the version
+/// prefix (1) and the table lookup (1 + 35), less the candy machine key loaded from it (32).
+pub const LOOKUP_TABLE_OVERHEAD: usize = 5;
 
-/// The maximum number of config lines per transaction.
+/// The maximum number of config lines per transaction when the number is not set with
+/// `--lines-per-tx`.
 const MAX_TRANSACTION_LINES: usize = 17;
 
+/// Default number of config line transactions in flight (sent and not confirmed yet).
+pub const DEFAULT_DEPLOY_PARALLELISM: usize = 4;
+
+/// Number of times a failed transaction is sent again.
+const MAX_TX_RETRIES: u32 = 3;
+
+/// Default number of times a transaction whose blockhash expired before it landed is re-signed
+/// and sent again.
+pub const DEFAULT_MAX_RESUBMITS: u32 = 5;
+
+/// Time between two checks of the signatures in flight.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Maximum number of signatures of a `getSignatureStatuses` request.
+const MAX_SIGNATURE_STATUSES: usize = 256;
+
+/// Status of a config line transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TxStatus {
+    Confirmed,
+    /// Processed and not confirmed yet.
+    Pending,
+    /// Not seen by the cluster: the transaction can still land while its blockhash is valid.
+    NotFound,
+    Failed(String),
+}
+
 pub struct TxInfo {
     candy_pubkey: Pubkey,
     payer: Keypair,
     chunk: Vec<(u32, ConfigLine)>,
 }
 
-/// Determine the config lines that need to be uploaded.
+impl Clone for TxInfo {
+    fn clone(&self) -> Self {
+        TxInfo {
+            candy_pubkey: self.candy_pubkey,
+            // neither Keypair nor ConfigLine implement clone
+            payer: Keypair::from_bytes(&self.payer.to_bytes()).expect("valid keypair bytes"),
+            chunk: self
+                .chunk
+                .iter()
+                .map(|(index, line)| {
+                    (
+                        *index,
+                        ConfigLine {
+                            name: line.name.clone(),
+                            uri: line.uri.clone(),
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Config lines written to the candy machine and recorded in the cache, before the cache was
+/// corrected.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reconciliation {
+    pub on_chain: usize,
+    pub in_cache: usize,
+    pub corrected: usize,
+}
+
+impl fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "chain has {} line(s), cache thought {}; corrected {} item(s)",
+            self.on_chain, self.in_cache, self.corrected
+        )
+    }
+}
+
+/// Updates the `on_chain` flags of the cache items to match the config lines written to the
+/// candy machine (`loaded`), so that only the missing lines are sent: a deploy that stopped
+/// between sending a transaction and saving the cache leaves them out of sync.
+pub fn reconcile_config_lines(
+    cache: &mut Cache,
+    num_items: u64,
+    loaded: &[bool],
+) -> Reconciliation {
+    let mut reconciliation = Reconciliation {
+        on_chain: loaded.iter().filter(|loaded| **loaded).count(),
+        ..Default::default()
+    };
+
+    for i in 0..num_items {
+        if let Some(item) = cache.items.get_mut(&i.to_string()) {
+            let on_chain = loaded.get(i as usize).copied().unwrap_or(false);
+
+            if item.on_chain {
+                reconciliation.in_cache += 1;
+            }
+            if item.on_chain != on_chain {
+                item.on_chain = on_chain;
+                reconciliation.corrected += 1;
+            }
+        }
+    }
+
+    reconciliation
+}
+
+/// Serialized size of a config line in the `add_config_lines` instruction.
+pub fn config_line_size(config_line: &ConfigLine) -> usize {
+    (2 * STRING_LEN_SIZE) + config_line.name.len() + config_line.uri.len()
+}
+
+/// Determine the config lines that need to be uploaded, packed in transactions by their
+/// serialized size. With `lines_per_tx`, the transactions have that number of lines instead
+/// (failing when they do not fit in a transaction).
 pub fn generate_config_lines(
     num_items: u64,
     cache_items: &CacheItems,
     data: &CandyMachineData,
+    lines_per_tx: Option<usize>,
+) -> Result<Vec<Vec<(u32, ConfigLine)>>> {
+    generate_config_lines_within(
+        num_items,
+        cache_items,
+        data,
+        lines_per_tx,
+        MAX_TRANSACTION_BYTES,
+    )
+}
+
+/// Same as [`generate_config_lines`], with at most `max_bytes` of config lines per
+/// transaction (e.g. to leave room for other instructions).
+pub fn generate_config_lines_within(
+    num_items: u64,
+    cache_items: &CacheItems,
+    data: &CandyMachineData,
+    lines_per_tx: Option<usize>,
+    max_bytes: usize,
 ) -> Result<Vec<Vec<(u32, ConfigLine)>>> {
     let mut config_lines: Vec<Vec<(u32, ConfigLine)>> = Vec::new();
     let mut current: Vec<(u32, ConfigLine)> = Vec::new();
     let mut tx_size = 0;
 
+    if lines_per_tx == Some(0) {
+        return Err(anyhow!(
+            "The number of config lines per transaction must be at least 1"
+        ));
+    }
+
     let config_line_settings = if let Some(config_line_settings) = &data.config_line_settings {
         config_line_settings
     } else {
@@ -80,15 +244,37 @@ pub fn generate_config_lines(
                 uri: item.metadata_link[uri_offset..].to_string(),
             };
 
-            let size = (2 * STRING_LEN_SIZE) + data.get_config_line_size();
+            let size = config_line_size(&config_line);
 
-            if (tx_size + size) > MAX_TRANSACTION_BYTES || current.len() == MAX_TRANSACTION_LINES {
+            let full = match lines_per_tx {
+                Some(lines) => current.len() == lines,
+                None => (tx_size + size) > max_bytes || current.len() == MAX_TRANSACTION_LINES,
+            };
+
+            if full && !current.is_empty() {
                 // we need a separate tx to not break the size limit
                 config_lines.push(current);
                 current = Vec::new();
                 tx_size = 0;
             }
 
+            if tx_size + size > max_bytes {
+                let message = match current.first() {
+                    Some((first, _)) => format!(
+                        "Config lines {}-{} do not fit in a transaction ({} bytes over the \
+                        limit), lower --lines-per-tx",
+                        first,
+                        i,
+                        tx_size + size - max_bytes
+                    ),
+                    None => format!(
+                        "Config line {} does not fit in a transaction ({} bytes)",
+                        i, size
+                    ),
+                };
+                return Err(DeployError::AddConfigLineFailed(message).into());
+            }
+
             tx_size += size;
             current.push((i as u32, config_line));
         }
@@ -101,7 +287,225 @@ pub fn generate_config_lines(
     Ok(config_lines)
 }
 
-/// Send the config lines to the candy machine program.
+/// Sends the config line transactions and checks the status of their signatures.
+#[async_trait]
+pub trait ConfigLineSender: Send + Sync + 'static {
+    /// Sends the transaction of a chunk without waiting for its confirmation, returning its
+    /// signature and recent blockhash.
+    async fn send(&self, tx: &TxInfo) -> Result<(Signature, Hash)>;
+
+    /// Returns the status of each signature, in the same order.
+    async fn statuses(&self, signatures: &[Signature]) -> Result<Vec<TxStatus>>;
+
+    /// Whether a transaction with the `blockhash` can still land.
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool>;
+}
+
+/// Sends the `add_config_lines` transactions to the cluster.
+pub struct ClusterSender {
+    sugar_config: Arc<SugarConfig>,
+    priority_fee: u64,
+    lookup_table: Option<AddressLookupTableAccount>,
+    compute_budget: ComputeBudget,
+}
+
+// the RPC client is blocking: its requests run on the blocking thread pool, so that the
+// transactions are sent concurrently without blocking the runtime
+#[async_trait]
+impl ConfigLineSender for ClusterSender {
+    async fn send(&self, tx: &TxInfo) -> Result<(Signature, Hash)> {
+        let sugar_config = self.sugar_config.clone();
+        let priority_fee = self.priority_fee;
+        let lookup_table = self.lookup_table.clone();
+        let compute_budget = self.compute_budget;
+        let tx = tx.clone();
+
+        tokio::task::spawn_blocking(move || {
+            send_transaction(
+                &sugar_config,
+                priority_fee,
+                lookup_table.as_ref(),
+                &compute_budget,
+                &tx,
+            )
+        })
+        .await?
+    }
+
+    async fn statuses(&self, signatures: &[Signature]) -> Result<Vec<TxStatus>> {
+        let sugar_config = self.sugar_config.clone();
+        let signatures = signatures.to_vec();
+
+        let statuses = tokio::task::spawn_blocking(move || -> Result<_> {
+            let client = setup_client(&sugar_config)?;
+            Ok(client
+                .program(CANDY_MACHINE_ID)
+                .rpc()
+                .get_signature_statuses(&signatures)?
+                .value)
+        })
+        .await??;
+
+        Ok(statuses
+            .into_iter()
+            .map(|status| match status {
+                Some(status) => match status.err {
+                    Some(err) => TxStatus::Failed(err.to_string()),
+                    None if status.satisfies_commitment(CommitmentConfig::confirmed()) => {
+                        TxStatus::Confirmed
+                    }
+                    None => TxStatus::Pending,
+                },
+                None => TxStatus::NotFound,
+            })
+            .collect())
+    }
+
+    async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+        let sugar_config = self.sugar_config.clone();
+        let blockhash = *blockhash;
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let client = setup_client(&sugar_config)?;
+
+            Ok(client
+                .program(CANDY_MACHINE_ID)
+                .rpc()
+                .is_blockhash_valid(&blockhash, CommitmentConfig::processed())?)
+        })
+        .await?
+    }
+}
+
+/// Signs and sends the transaction of a chunk, returning its signature and recent blockhash.
+fn send_transaction(
+    sugar_config: &SugarConfig,
+    priority_fee: u64,
+    lookup_table: Option<&AddressLookupTableAccount>,
+    compute_budget: &ComputeBudget,
+    tx: &TxInfo,
+) -> Result<(Signature, Hash)> {
+    let client = setup_client(sugar_config)?;
+    let program = client.program(CANDY_MACHINE_ID);
+
+    let instructions = add_config_lines_instructions(
+        &program,
+        tx.candy_pubkey,
+        program.payer(),
+        &tx.chunk,
+        priority_fee,
+    )?;
+
+    let rpc = program.rpc();
+
+    Ok(match lookup_table {
+        Some(lookup_table) => {
+            let transaction = prepare_versioned_transaction(
+                &rpc,
+                &tx.payer,
+                &instructions,
+                &[],
+                lookup_table,
+                compute_budget,
+            )?;
+            (
+                rpc.send_transaction(&transaction)?,
+                *transaction.message.recent_blockhash(),
+            )
+        }
+        None => {
+            let transaction =
+                prepare_transaction(&rpc, &tx.payer, &instructions, &[], compute_budget)?;
+            (
+                rpc.send_transaction(&transaction)?,
+                transaction.message.recent_blockhash,
+            )
+        }
+    })
+}
+
+/// Instructions of the transaction writing a chunk of config lines, signed by the candy
+/// machine `authority`.
+pub fn add_config_lines_instructions<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    candy_pubkey: Pubkey,
+    authority: Pubkey,
+    chunk: &[(u32, ConfigLine)],
+    priority_fee: u64,
+) -> Result<Vec<Instruction>> {
+    // configLine does not implement clone, so we have to do this
+    let config_lines: Vec<ConfigLine> = chunk
+        .iter()
+        .map(|(_, line)| ConfigLine {
+            name: line.name.clone(),
+            uri: line.uri.clone(),
+        })
+        .collect();
+
+    let instructions = program
+        .request()
+        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
+            priority_fee,
+        ))
+        .accounts(nft_accounts::AddConfigLines {
+            candy_machine: candy_pubkey,
+            authority,
+        })
+        .args(nft_instruction::AddConfigLines {
+            index: chunk[0].0,
+            config_lines,
+        })
+        .instructions()?;
+
+    Ok(instructions)
+}
+
+/// Options of the sending of the config line transactions.
+struct SendOptions {
+    /// Maximum number of transactions in flight (sent and not confirmed yet).
+    parallelism: usize,
+    /// Number of times a failed transaction is sent again.
+    max_retries: u32,
+    /// Number of times a transaction whose blockhash expired is re-signed and sent again.
+    max_resubmits: u32,
+    /// Time between two checks of the signatures in flight.
+    poll_interval: Duration,
+}
+
+/// Number of times transactions were sent again.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Resends {
+    /// After an error.
+    retries: u32,
+    /// After their blockhash expired.
+    resubmits: u32,
+}
+
+/// Why a transaction is sent again.
+#[derive(Debug, Clone, Copy)]
+enum Resend {
+    Retry,
+    Resubmit,
+}
+
+/// A transaction sent and not confirmed yet.
+struct InFlight {
+    tx: TxInfo,
+    signature: Signature,
+    blockhash: Hash,
+    resends: Resends,
+}
+
+/// How a transaction in flight completed.
+enum Outcome {
+    Confirmed,
+    Failed(String),
+    Expired,
+}
+
+/// Send the config lines to the candy machine program, in v0 transactions when there is a
+/// lookup table.
+#[allow(clippy::too_many_arguments)]
 pub async fn upload_config_lines(
     sugar_config: Arc<SugarConfig>,
     candy_pubkey: Pubkey,
@@ -109,16 +513,14 @@ pub async fn upload_config_lines(
     config_lines: Vec<Vec<(u32, ConfigLine)>>,
     interrupted: Arc<AtomicBool>,
     priority_fee: u64,
+    parallelism: usize,
+    max_resubmits: u32,
+    lookup_table: Option<AddressLookupTableAccount>,
+    compute_budget: ComputeBudget,
 ) -> Result<Vec<DeployError>> {
-    println!(
-        "Sending config line(s) in {} transaction(s): (Ctrl+C to abort)",
-        config_lines.len()
-    );
-
-    let pb = progress_bar_with_style(config_lines.len() as u64);
-
-    debug!("Num of config line chunks: {:?}", config_lines.len());
-    info!("Uploading config lines in chunks...");
+    if parallelism == 0 {
+        return Err(anyhow!("The parallelism must be at least 1"));
+    }
 
     let mut transactions = Vec::new();
 
@@ -133,76 +535,172 @@ pub async fn upload_config_lines(
         });
     }
 
-    let mut handles = Vec::new();
+    let sender = Arc::new(ClusterSender {
+        sugar_config,
+        priority_fee,
+        lookup_table,
+        compute_budget,
+    });
+    let options = SendOptions {
+        parallelism,
+        max_retries: MAX_TX_RETRIES,
+        max_resubmits,
+        poll_interval: POLL_INTERVAL,
+    };
 
-    for tx in transactions.drain(0..cmp::min(transactions.len(), PARALLEL_LIMIT)) {
-        let config = sugar_config.clone();
-        handles.push(tokio::spawn(async move {
-            add_config_lines(config, tx, priority_fee).await
-        }));
-    }
+    send_config_lines(cache, transactions, interrupted, sender, options).await
+}
+
+/// Sends the transactions keeping up to `parallelism` of them in flight, while a separate task
+/// confirms their signatures. The config lines of a transaction are recorded in the cache only
+/// once it is confirmed; failed transactions are sent again up to `max_retries` times, and
+/// transactions whose blockhash expired before they landed are re-signed and sent again up to
+/// `max_resubmits` times.
+///
+/// When interrupted, the cache is saved with the config lines confirmed so far and a
+/// [`Cancelled`](crate::cancel::Cancelled) error is returned.
+async fn send_config_lines<S: ConfigLineSender>(
+    cache: &mut Cache,
+    transactions: Vec<TxInfo>,
+    interrupted: Arc<AtomicBool>,
+    sender: Arc<S>,
+    options: SendOptions,
+) -> Result<Vec<DeployError>> {
+    report!(
+        "Sending config line(s) in {} transaction(s): (Ctrl+C to abort)",
+        transactions.len()
+    );
+
+    let pb = progress_bar_with_style(transactions.len() as u64);
+
+    debug!("Num of config line chunks: {:?}", transactions.len());
+    info!("Uploading config lines in chunks...");
 
+    let (sent, to_confirm) = mpsc::unbounded_channel();
+    let (outcomes, mut confirmed) = mpsc::unbounded_channel();
+    let confirmer = tokio::spawn(confirm_signatures(
+        sender.clone(),
+        to_confirm,
+        outcomes,
+        options.poll_interval,
+    ));
+
+    let mut queue: VecDeque<(TxInfo, Resends)> = transactions
+        .into_iter()
+        .map(|tx| (tx, Resends::default()))
+        .collect();
+    let mut in_flight = 0;
+    let mut completed = 0;
     let mut errors = Vec::new();
+    let mut summary = Resends::default();
 
-    while !interrupted.load(Ordering::SeqCst) && !handles.is_empty() {
-        match select_all(handles).await {
-            (Ok(res), _index, remaining) => {
-                // independently if the upload was successful or not
-                // we continue to try the remaining ones
-                handles = remaining;
+    while !interrupted.load(Ordering::SeqCst) && (!queue.is_empty() || in_flight > 0) {
+        // sends the next transactions together, up to the number of transactions in flight
+        let mut batch = Vec::new();
+        while in_flight + batch.len() < options.parallelism && !interrupted.load(Ordering::SeqCst) {
+            match queue.pop_front() {
+                Some(next) => batch.push(next),
+                None => break,
+            }
+        }
 
-                if res.is_ok() {
-                    let indices = res?;
+        let results = join_all(batch.iter().map(|(tx, _)| sender.send(tx))).await;
 
-                    for index in indices {
-                        let item = cache.items.get_mut(&index.to_string()).unwrap();
-                        item.on_chain = true;
-                    }
-                    // updates the progress bar
-                    pb.inc(1);
-                } else {
-                    // user will need to retry the upload
-                    errors.push(DeployError::AddConfigLineFailed(format!(
-                        "Transaction error: {:?}",
-                        res.err().unwrap()
-                    )));
+        for ((tx, resends), result) in batch.into_iter().zip(results) {
+            match result {
+                Ok((signature, blockhash)) => {
+                    debug!("Sent config lines {} in {}", chunk_range(&tx), signature);
+                    sent.send(InFlight {
+                        tx,
+                        signature,
+                        blockhash,
+                        resends,
+                    })
+                    .map_err(|_| anyhow!("The confirmation of the config lines stopped"))?;
+                    in_flight += 1;
                 }
-            }
-            (Err(err), _index, remaining) => {
-                // user will need to retry the upload
-                errors.push(DeployError::AddConfigLineFailed(format!(
-                    "Transaction error: {:?}",
-                    err
-                )));
-                // ignoring all errors
-                handles = remaining;
+                Err(err) => errors.extend(resend_or_fail(
+                    &mut queue,
+                    &mut summary,
+                    tx,
+                    resends,
+                    Resend::Retry,
+                    &options,
+                    err.to_string(),
+                )),
             }
         }
 
-        if !transactions.is_empty() {
-            // if we are half way through, let spawn more transactions
-            if (PARALLEL_LIMIT - handles.len()) > (PARALLEL_LIMIT / 2) {
-                // saves the progress to the cache file
-                cache.sync_file()?;
-
-                for tx in transactions.drain(0..cmp::min(transactions.len(), PARALLEL_LIMIT / 2)) {
-                    let config = sugar_config.clone();
-                    handles.push(tokio::spawn(async move {
-                        add_config_lines(config, tx, priority_fee).await
-                    }));
+        if in_flight == 0 {
+            continue;
+        }
+
+        // waits for the next transaction to complete, checking the interruption flag
+        let (done, outcome) = match timeout(options.poll_interval, confirmed.recv()).await {
+            Ok(Some(result)) => result,
+            Ok(None) => return Err(anyhow!("The confirmation of the config lines stopped")),
+            Err(_) => continue,
+        };
+        in_flight -= 1;
+
+        match outcome {
+            Outcome::Confirmed => {
+                for (index, _) in &done.tx.chunk {
+                    let item = cache.items.get_mut(&index.to_string()).ok_or_else(|| {
+                        DeployError::AddConfigLineFailed(format!(
+                            "Missing cache item {} of confirmed config lines {}",
+                            index,
+                            chunk_range(&done.tx)
+                        ))
+                    })?;
+                    item.on_chain = true;
+                    progress_stream::item_completed(Some(index.to_string()), Ok::<_, String>(()));
+                }
+                // updates the progress bar
+                pb.inc(1);
+
+                completed += 1;
+                if completed % options.parallelism == 0 {
+                    // saves the progress to the cache file
+                    cache.sync_file()?;
                 }
             }
+            Outcome::Failed(reason) => errors.extend(resend_or_fail(
+                &mut queue,
+                &mut summary,
+                done.tx,
+                done.resends,
+                Resend::Retry,
+                &options,
+                format!("{} ({})", reason, done.signature),
+            )),
+            Outcome::Expired => errors.extend(resend_or_fail(
+                &mut queue,
+                &mut summary,
+                done.tx,
+                done.resends,
+                Resend::Resubmit,
+                &options,
+                format!(
+                    "Blockhash expired before the transaction landed ({})",
+                    done.signature
+                ),
+            )),
         }
     }
 
-    if !errors.is_empty() {
+    // the transactions still in flight are not recorded
+    drop(sent);
+    confirmer.abort();
+
+    // makes sure the cache file is updated with the confirmed config lines
+    cache.sync_file()?;
+
+    if !queue.is_empty() || in_flight > 0 {
+        pb.abandon_with_message(format!("{}", style("Deploy interrupted ").red().bold()));
+        return Err(cancel::cancelled().into());
+    } else if !errors.is_empty() {
         pb.abandon_with_message(format!("{}", style("Deploy failed ").red().bold()));
-    } else if !transactions.is_empty() {
-        pb.abandon_with_message(format!("{}", style("Upload aborted ").red().bold()));
-        return Err(DeployError::AddConfigLineFailed(
-            "Not all config lines were deployed.".to_string(),
-        )
-        .into());
     } else {
         pb.finish_with_message(format!(
             "{}",
@@ -210,49 +708,785 @@ pub async fn upload_config_lines(
         ));
     }
 
-    // makes sure the cache file is updated
-    cache.sync_file()?;
+    report!(
+        "{} {} expired transaction(s) re-signed and sent again, {} failed transaction(s) \
+        retried",
+        style("Resubmissions:").bold(),
+        summary.resubmits,
+        summary.retries
+    );
 
     Ok(errors)
 }
 
-/// Send the `add_config_lines` instruction to the candy machine program.
-pub async fn add_config_lines(
-    config: Arc<SugarConfig>,
-    tx_info: TxInfo,
-    priority_fee: u64,
-) -> Result<Vec<u32>> {
-    let client = setup_client(&config)?;
-    let program = client.program(CANDY_MACHINE_ID);
+/// Puts a transaction back in the queue, or returns the error once it was sent again the
+/// maximum number of times for the `resend` reason (the user will need to deploy again).
+fn resend_or_fail(
+    queue: &mut VecDeque<(TxInfo, Resends)>,
+    summary: &mut Resends,
+    tx: TxInfo,
+    mut resends: Resends,
+    resend: Resend,
+    options: &SendOptions,
+    reason: String,
+) -> Option<DeployError> {
+    let (count, total, limit) = match resend {
+        Resend::Retry => (
+            &mut resends.retries,
+            &mut summary.retries,
+            options.max_retries,
+        ),
+        Resend::Resubmit => (
+            &mut resends.resubmits,
+            &mut summary.resubmits,
+            options.max_resubmits,
+        ),
+    };
 
-    // this will be used to update the cache
-    let mut indices: Vec<u32> = Vec::new();
+    if *count < limit {
+        *count += 1;
+        *total += 1;
+        debug!(
+            "Sending config lines {} again ({:?}): {}",
+            chunk_range(&tx),
+            resend,
+            reason
+        );
+        queue.push_back((tx, resends));
+        None
+    } else {
+        let error = DeployError::AddConfigLineFailed(format!(
+            "Transaction error (config lines {}, {} attempts): {}",
+            chunk_range(&tx),
+            1 + resends.retries + resends.resubmits,
+            reason
+        ));
+        progress_stream::item_completed(None, Err(&error));
+        Some(error)
+    }
+}
 
-    // configLine does not implement clone, so we have to do this
-    let mut config_lines: Vec<ConfigLine> = Vec::new();
-    // start index
-    let start_index = tx_info.chunk[0].0;
+/// Checks the signatures of the transactions in flight, reporting each transaction once it is
+/// confirmed, failed or expired (not seen by the cluster once its blockhash is no longer
+/// valid).
+async fn confirm_signatures<S: ConfigLineSender>(
+    sender: Arc<S>,
+    mut to_confirm: UnboundedReceiver<InFlight>,
+    outcomes: UnboundedSender<(InFlight, Outcome)>,
+    poll_interval: Duration,
+) {
+    let mut pending: Vec<InFlight> = Vec::new();
+
+    loop {
+        if pending.is_empty() {
+            match to_confirm.recv().await {
+                Some(tx) => pending.push(tx),
+                None => return,
+            }
+        }
+        while let Ok(tx) = to_confirm.try_recv() {
+            pending.push(tx);
+        }
+
+        let signatures: Vec<Signature> = pending.iter().map(|tx| tx.signature).collect();
+        let mut statuses = Vec::with_capacity(signatures.len());
+
+        for chunk in signatures.chunks(MAX_SIGNATURE_STATUSES) {
+            match sender.statuses(chunk).await {
+                Ok(chunk_statuses) => {
+                    let mut chunk_statuses = chunk_statuses;
+                    // signatures without a status were not seen
+                    chunk_statuses.resize(chunk.len(), TxStatus::NotFound);
+                    statuses.extend(chunk_statuses);
+                }
+                Err(err) => {
+                    debug!("Failed to get signature statuses: {:#}", err);
+                    statuses.extend(chunk.iter().map(|_| TxStatus::Pending));
+                }
+            }
+        }
+
+        let mut still_pending = Vec::new();
+        // the transactions sent together share their blockhash, which is checked once
+        let mut expired_blockhashes = HashMap::new();
+
+        for (tx, status) in pending.into_iter().zip(statuses) {
+            let outcome = match status {
+                TxStatus::Confirmed => Outcome::Confirmed,
+                TxStatus::Failed(err) => Outcome::Failed(err),
+                // a transaction landing right before its blockhash expires might be sent
+                // again, which writes the same config lines
+                TxStatus::NotFound => {
+                    if is_expired(&*sender, &mut expired_blockhashes, &tx.blockhash).await {
+                        Outcome::Expired
+                    } else {
+                        still_pending.push(tx);
+                        continue;
+                    }
+                }
+                TxStatus::Pending => {
+                    still_pending.push(tx);
+                    continue;
+                }
+            };
+
+            if outcomes.send((tx, outcome)).is_err() {
+                return;
+            }
+        }
+
+        pending = still_pending;
 
-    for (index, line) in tx_info.chunk {
-        indices.push(index);
-        config_lines.push(line);
+        if !pending.is_empty() {
+            sleep(poll_interval).await;
+        }
     }
+}
 
-    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
+/// Whether a blockhash expired, checking it once per poll. It is considered valid when the
+/// check fails.
+async fn is_expired<S: ConfigLineSender>(
+    sender: &S,
+    checked: &mut HashMap<Hash, bool>,
+    blockhash: &Hash,
+) -> bool {
+    if let Some(expired) = checked.get(blockhash) {
+        return *expired;
+    }
 
-    let _sig = program
-        .request()
-        .instruction(priority_fee)
-        .accounts(nft_accounts::AddConfigLines {
-            candy_machine: tx_info.candy_pubkey,
-            authority: program.payer(),
-        })
-        .args(nft_instruction::AddConfigLines {
-            index: start_index,
-            config_lines,
-        })
-        .signer(&tx_info.payer)
-        .send()?;
+    let expired = match sender.is_blockhash_valid(blockhash).await {
+        Ok(valid) => !valid,
+        Err(err) => {
+            debug!("Failed to check blockhash {}: {:#}", blockhash, err);
+            false
+        }
+    };
+    checked.insert(*blockhash, expired);
+
+    expired
+}
+
+/// Checks that the config lines written to the candy machine (`loaded`) are the ones the cache
+/// records on-chain, after they were sent.
+pub fn check_written_config_lines(
+    cache: &Cache,
+    num_items: u64,
+    loaded: &[bool],
+) -> Result<(), DeployError> {
+    let on_chain = loaded
+        .iter()
+        .take(num_items as usize)
+        .filter(|loaded| **loaded)
+        .count();
+    let in_cache = (0..num_items)
+        .filter_map(|i| cache.items.get(&i.to_string()))
+        .filter(|item| item.on_chain)
+        .count();
+
+    if on_chain == in_cache {
+        Ok(())
+    } else {
+        Err(DeployError::ConfigLineCountMismatch(on_chain, in_cache))
+    }
+}
+
+/// Range of the config line indices of a transaction, e.g. `10-14`.
+fn chunk_range(tx: &TxInfo) -> String {
+    match (tx.chunk.first(), tx.chunk.last()) {
+        (Some((first, _)), Some((last, _))) if first != last => format!("{first}-{last}"),
+        (Some((first, _)), _) => first.to_string(),
+        _ => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashSet,
+        fs,
+        sync::{atomic::AtomicUsize, Mutex},
+    };
+
+    use std::time::Instant;
+
+    use anchor_client::solana_sdk::message::{v0, Message, VersionedMessage};
+    use anchor_lang::{InstructionData, ToAccountMetas};
+    use mpl_candy_machine_core::ConfigLineSettings;
+
+    use super::*;
+    use crate::compute_budget::COMPUTE_UNIT_LIMIT_INSTRUCTION_SIZE;
+
+    fn write_cache(path: &Path, items: u32) -> Result<Cache> {
+        let mut cache = Cache::new();
+        for i in 0..items {
+            cache.items.insert(
+                i.to_string(),
+                CacheItem {
+                    name: format!("#{i}"),
+                    image_hash: String::new(),
+                    image_link: format!("https://arweave.net/{i}.png"),
+                    metadata_hash: String::new(),
+                    metadata_link: format!("https://arweave.net/{i}.json"),
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+        }
+        cache.file_path = path.to_string_lossy().to_string();
+        cache.sync_file()?;
+        Ok(cache)
+    }
+
+    fn cache_items(uri_lengths: &[usize]) -> CacheItems {
+        let mut cache = Cache::new();
+        for (i, length) in uri_lengths.iter().enumerate() {
+            cache.items.insert(
+                i.to_string(),
+                CacheItem {
+                    name: "Item".to_string(),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: "u".repeat(*length),
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+        }
+        cache.items
+    }
+
+    fn candy_machine_data() -> CandyMachineData {
+        CandyMachineData {
+            items_available: 0,
+            symbol: String::new(),
+            seller_fee_basis_points: 0,
+            max_supply: 0,
+            is_mutable: true,
+            creators: Vec::new(),
+            config_line_settings: Some(ConfigLineSettings {
+                prefix_name: String::new(),
+                name_length: MAX_NAME_LENGTH as u32,
+                prefix_uri: String::new(),
+                uri_length: MAX_URI_LENGTH as u32,
+                is_sequential: false,
+            }),
+            hidden_settings: None,
+        }
+    }
 
-    Ok(indices)
+    fn config_line_instructions(
+        payer: &Pubkey,
+        candy_machine: &Pubkey,
+        chunk: Vec<(u32, ConfigLine)>,
+    ) -> Vec<Instruction> {
+        vec![
+            ComputeBudgetInstruction::set_compute_unit_price(u64::MAX),
+            Instruction {
+                program_id: CANDY_MACHINE_ID,
+                accounts: nft_accounts::AddConfigLines {
+                    candy_machine: *candy_machine,
+                    authority: *payer,
+                }
+                .to_account_metas(None),
+                data: nft_instruction::AddConfigLines {
+                    index: 0,
+                    config_lines: chunk.into_iter().map(|(_, line)| line).collect(),
+                }
+                .data(),
+            },
+        ]
+    }
+
+    fn chunk_sizes(uri_lengths: &[usize], lines_per_tx: Option<usize>) -> Result<Vec<usize>> {
+        let chunks = generate_config_lines(
+            uri_lengths.len() as u64,
+            &cache_items(uri_lengths),
+            &candy_machine_data(),
+            lines_per_tx,
+        )?;
+        Ok(chunks.iter().map(|chunk| chunk.len()).collect())
+    }
+
+    #[test]
+    fn transaction_overhead_matches_serialized_transaction() {
+        // 4 config lines of 212 bytes and one of 120 bytes add up to the limit
+        let chunks = generate_config_lines(
+            5,
+            &cache_items(&[200, 200, 200, 200, 108]),
+            &candy_machine_data(),
+            None,
+        )
+        .unwrap();
+        let chunk = chunks.into_iter().next().unwrap();
+        assert_eq!(
+            chunk
+                .iter()
+                .map(|(_, line)| config_line_size(line))
+                .sum::<usize>(),
+            MAX_TRANSACTION_BYTES
+        );
+
+        let payer = Pubkey::new_unique();
+        let message = Message::new(
+            &config_line_instructions(&payer, &Pubkey::new_unique(), chunk),
+            Some(&payer),
+        );
+
+        // signature count and signature
+        assert_eq!(1 + 64 + message.serialize().len(), PACKET_DATA_SIZE);
+    }
+
+    #[test]
+    fn compute_unit_limit_fits_in_the_transaction() {
+        // 4 config lines of 212 bytes and one of 112 bytes fill the room left by the limit
+        let max_bytes = MAX_TRANSACTION_BYTES - COMPUTE_UNIT_LIMIT_INSTRUCTION_SIZE;
+        let chunks = generate_config_lines_within(
+            5,
+            &cache_items(&[200, 200, 200, 200, 100]),
+            &candy_machine_data(),
+            None,
+            max_bytes,
+        )
+        .unwrap();
+        let chunk = chunks.into_iter().next().unwrap();
+        assert_eq!(chunk.len(), 5);
+
+        let payer = Pubkey::new_unique();
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_limit(u32::MAX)];
+        instructions.extend(config_line_instructions(
+            &payer,
+            &Pubkey::new_unique(),
+            chunk,
+        ));
+        let message = Message::new(&instructions, Some(&payer));
+
+        assert!(1 + 64 + message.serialize().len() <= PACKET_DATA_SIZE);
+    }
+
+    #[test]
+    fn lookup_table_overhead_matches_serialized_transaction() {
+        let chunks = generate_config_lines(
+            5,
+            &cache_items(&[200, 200, 200, 200, 108]),
+            &candy_machine_data(),
+            None,
+        )
+        .unwrap();
+        let chunk = chunks.into_iter().next().unwrap();
+
+        let payer = Pubkey::new_unique();
+        let candy_machine = Pubkey::new_unique();
+        let lookup_table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![candy_machine],
+        };
+        let message = v0::Message::try_compile(
+            &payer,
+            &config_line_instructions(&payer, &candy_machine, chunk),
+            &[lookup_table],
+            Hash::default(),
+        )
+        .unwrap();
+
+        assert_eq!(message.address_table_lookups.len(), 1);
+        assert_eq!(
+            1 + 64 + VersionedMessage::V0(message).serialize().len(),
+            PACKET_DATA_SIZE + LOOKUP_TABLE_OVERHEAD
+        );
+    }
+
+    #[test]
+    fn config_lines_are_packed_up_to_the_transaction_limit() {
+        assert_eq!(
+            chunk_sizes(&[200, 200, 200, 200, 108], None).unwrap(),
+            vec![5]
+        );
+        // one byte over the limit
+        assert_eq!(
+            chunk_sizes(&[200, 200, 200, 200, 109], None).unwrap(),
+            vec![4, 1]
+        );
+    }
+
+    #[test]
+    fn long_uris_are_split_in_transactions_that_fit() {
+        let chunks =
+            generate_config_lines(20, &cache_items(&[200; 20]), &candy_machine_data(), None)
+                .unwrap();
+
+        assert_eq!(chunks.len(), 5);
+        for chunk in &chunks {
+            let size: usize = chunk.iter().map(|(_, line)| config_line_size(line)).sum();
+            assert!(size <= MAX_TRANSACTION_BYTES);
+        }
+    }
+
+    #[test]
+    fn short_uris_use_fewer_transactions() {
+        let long = chunk_sizes(&[200; 34], None).unwrap();
+        let short = chunk_sizes(&[43; 34], None).unwrap();
+
+        assert!(short.len() < long.len());
+        assert_eq!(short, vec![MAX_TRANSACTION_LINES, MAX_TRANSACTION_LINES]);
+    }
+
+    #[test]
+    fn lines_per_tx_override() {
+        assert_eq!(chunk_sizes(&[43; 10], Some(3)).unwrap(), vec![3, 3, 3, 1]);
+        assert_eq!(chunk_sizes(&[10; 20], Some(20)).unwrap(), vec![20]);
+
+        // 5 lines of 212 bytes do not fit in a transaction
+        let error = chunk_sizes(&[200; 5], Some(5)).unwrap_err();
+        assert!(error.to_string().contains("lower --lines-per-tx"));
+        assert!(chunk_sizes(&[43; 1], Some(0)).is_err());
+    }
+
+    #[test]
+    fn cache_is_reconciled_with_the_written_config_lines() {
+        let mut cache = Cache::new();
+        cache.items = cache_items(&[10; 6]);
+        // the deploy stopped before saving the lines 2-3 and the line 5 was never written
+        for i in ["0", "1", "5"] {
+            cache.items.get_mut(i).unwrap().on_chain = true;
+        }
+        let loaded = [true, true, true, true, false, false];
+
+        let reconciliation = reconcile_config_lines(&mut cache, 6, &loaded);
+
+        assert_eq!(
+            reconciliation,
+            Reconciliation {
+                on_chain: 4,
+                in_cache: 3,
+                corrected: 3,
+            }
+        );
+        assert_eq!(
+            reconciliation.to_string(),
+            "chain has 4 line(s), cache thought 3; corrected 3 item(s)"
+        );
+
+        let on_chain: Vec<bool> = cache.items.values().map(|item| item.on_chain).collect();
+        assert_eq!(on_chain, loaded);
+
+        let chunks = generate_config_lines(6, &cache.items, &candy_machine_data(), None).unwrap();
+        let indices: Vec<Vec<u32>> = chunks
+            .iter()
+            .map(|chunk| chunk.iter().map(|(index, _)| *index).collect())
+            .collect();
+        assert_eq!(indices, vec![vec![4, 5]]);
+    }
+
+    /// Cluster where a transaction lands unless `drops` returns true for it (given the number
+    /// of transactions sent before it). Each transaction gets a new blockhash, valid for
+    /// `validity` after it was sent.
+    struct MockCluster<F> {
+        drops: F,
+        validity: Duration,
+        sent: AtomicUsize,
+        landed: Mutex<HashMap<Signature, Vec<u32>>>,
+        blockhashes: Mutex<HashMap<Hash, Instant>>,
+        max_signatures: AtomicUsize,
+        sending: AtomicUsize,
+        max_sending: AtomicUsize,
+    }
+
+    impl<F: Fn(usize, &TxInfo) -> bool + Send + Sync + 'static> MockCluster<F> {
+        fn new(validity: Duration, drops: F) -> Arc<Self> {
+            Arc::new(Self {
+                drops,
+                validity,
+                sent: AtomicUsize::new(0),
+                landed: Mutex::new(HashMap::new()),
+                blockhashes: Mutex::new(HashMap::new()),
+                max_signatures: AtomicUsize::new(0),
+                sending: AtomicUsize::new(0),
+                max_sending: AtomicUsize::new(0),
+            })
+        }
+
+        /// Indices of the config lines written on-chain.
+        fn written(&self) -> HashSet<u32> {
+            self.landed
+                .lock()
+                .unwrap()
+                .values()
+                .flatten()
+                .copied()
+                .collect()
+        }
+    }
+
+    #[async_trait]
+    impl<F: Fn(usize, &TxInfo) -> bool + Send + Sync + 'static> ConfigLineSender for MockCluster<F> {
+        async fn send(&self, tx: &TxInfo) -> Result<(Signature, Hash)> {
+            // sending takes a while, so that concurrent sends overlap
+            let sending = self.sending.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_sending.fetch_max(sending, Ordering::SeqCst);
+            sleep(Duration::from_millis(1)).await;
+            self.sending.fetch_sub(1, Ordering::SeqCst);
+
+            let count = self.sent.fetch_add(1, Ordering::SeqCst);
+            let signature = Signature::new_unique();
+            let blockhash = Hash::new_unique();
+
+            if !(self.drops)(count, tx) {
+                let indices = tx.chunk.iter().map(|(index, _)| *index).collect();
+                self.landed.lock().unwrap().insert(signature, indices);
+            }
+            self.blockhashes
+                .lock()
+                .unwrap()
+                .insert(blockhash, Instant::now());
+
+            Ok((signature, blockhash))
+        }
+
+        async fn statuses(&self, signatures: &[Signature]) -> Result<Vec<TxStatus>> {
+            self.max_signatures
+                .fetch_max(signatures.len(), Ordering::SeqCst);
+            let landed = self.landed.lock().unwrap();
+
+            Ok(signatures
+                .iter()
+                .map(|signature| {
+                    if landed.contains_key(signature) {
+                        TxStatus::Confirmed
+                    } else {
+                        TxStatus::NotFound
+                    }
+                })
+                .collect())
+        }
+
+        async fn is_blockhash_valid(&self, blockhash: &Hash) -> Result<bool> {
+            let blockhashes = self.blockhashes.lock().unwrap();
+            Ok(blockhashes[blockhash].elapsed() < self.validity)
+        }
+    }
+
+    fn cache_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("sugar-deploy-{}-{}.json", name, std::process::id()))
+    }
+
+    fn transactions(items: u32) -> Vec<TxInfo> {
+        (0..items)
+            .map(|i| TxInfo {
+                candy_pubkey: Pubkey::new_unique(),
+                payer: Keypair::new(),
+                chunk: vec![(
+                    i,
+                    ConfigLine {
+                        name: format!("#{i}"),
+                        uri: format!("{i}.json"),
+                    },
+                )],
+            })
+            .collect()
+    }
+
+    fn options() -> SendOptions {
+        SendOptions {
+            parallelism: 4,
+            max_retries: 3,
+            max_resubmits: 3,
+            poll_interval: Duration::from_millis(5),
+        }
+    }
+
+    /// Indices of the config lines recorded on-chain in the cache file.
+    fn saved_on_chain(path: &Path) -> Result<HashSet<u32>> {
+        let saved = load_cache(&path.to_string_lossy(), false)?;
+        Ok(saved
+            .items
+            .iter()
+            .filter(|(_, item)| item.on_chain)
+            .map(|(key, _)| key.parse().unwrap())
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn dropped_transactions_are_sent_again() -> Result<()> {
+        let path = cache_path("dropped");
+        let mut cache = write_cache(&path, 100)?;
+
+        // one transaction out of ten never lands
+        let cluster = MockCluster::new(Duration::from_millis(50), |count, _: &TxInfo| {
+            count % 10 == 9
+        });
+        let errors = send_config_lines(
+            &mut cache,
+            transactions(100),
+            Arc::new(AtomicBool::new(false)),
+            cluster.clone(),
+            options(),
+        )
+        .await?;
+
+        assert!(errors.is_empty());
+        assert!(cluster.sent.load(Ordering::SeqCst) > 100);
+        assert!(cluster.max_signatures.load(Ordering::SeqCst) <= 4);
+
+        let written = cluster.written();
+        assert_eq!(written, (0..100).collect());
+        assert_eq!(saved_on_chain(&path)?, written);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn transactions_that_never_land_are_not_recorded() -> Result<()> {
+        let path = cache_path("never-land");
+        let mut cache = write_cache(&path, 20)?;
+
+        // the config line 7 is never written
+        let cluster = MockCluster::new(Duration::from_millis(20), |_, tx: &TxInfo| {
+            tx.chunk[0].0 == 7
+        });
+        let errors = send_config_lines(
+            &mut cache,
+            transactions(20),
+            Arc::new(AtomicBool::new(false)),
+            cluster.clone(),
+            options(),
+        )
+        .await?;
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].to_string().contains("config lines 7, 4 attempts"));
+
+        let saved = saved_on_chain(&path)?;
+        assert!(!saved.contains(&7));
+        assert!(saved.is_subset(&cluster.written()));
+        assert_eq!(saved.len(), 19);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn expired_transactions_are_resubmitted_up_to_max_resubmits() -> Result<()> {
+        let path = cache_path("expired");
+        let mut cache = write_cache(&path, 10)?;
+
+        // the config line 3 is never written
+        let cluster = MockCluster::new(Duration::from_millis(20), |_, tx: &TxInfo| {
+            tx.chunk[0].0 == 3
+        });
+        let errors = send_config_lines(
+            &mut cache,
+            transactions(10),
+            Arc::new(AtomicBool::new(false)),
+            cluster.clone(),
+            SendOptions {
+                max_resubmits: 1,
+                ..options()
+            },
+        )
+        .await?;
+
+        assert_eq!(errors.len(), 1);
+        let error = errors[0].to_string();
+        assert!(error.contains("config lines 3, 2 attempts"));
+        assert!(error.contains("Blockhash expired"));
+        assert_eq!(cluster.sent.load(Ordering::SeqCst), 11);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn transactions_are_sent_concurrently() -> Result<()> {
+        let path = cache_path("concurrent");
+        let mut cache = write_cache(&path, 16)?;
+
+        let cluster = MockCluster::new(Duration::from_secs(60), |_, _: &TxInfo| false);
+        let errors = send_config_lines(
+            &mut cache,
+            transactions(16),
+            Arc::new(AtomicBool::new(false)),
+            cluster.clone(),
+            options(),
+        )
+        .await?;
+
+        assert!(errors.is_empty());
+        let max_sending = cluster.max_sending.load(Ordering::SeqCst);
+        assert!(max_sending > 1 && max_sending <= 4);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn missing_cache_item_is_an_error() -> Result<()> {
+        let path = cache_path("missing-item");
+        // the transaction of the config line 3 has no cache item
+        let mut cache = write_cache(&path, 3)?;
+
+        let cluster = MockCluster::new(Duration::from_secs(60), |_, _: &TxInfo| false);
+        let error = send_config_lines(
+            &mut cache,
+            transactions(4),
+            Arc::new(AtomicBool::new(false)),
+            cluster,
+            options(),
+        )
+        .await
+        .expect_err("the cache item 3 is missing");
+
+        assert!(error.to_string().contains("Missing cache item 3"));
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn written_config_lines_are_checked_against_the_cache() {
+        let mut cache = Cache::new();
+        cache.items = cache_items(&[10; 4]);
+        for i in ["0", "1", "2"] {
+            cache.items.get_mut(i).unwrap().on_chain = true;
+        }
+
+        assert!(check_written_config_lines(&cache, 4, &[true, true, true, false]).is_ok());
+
+        let error = check_written_config_lines(&cache, 4, &[true, true, false, false]).unwrap_err();
+        assert!(matches!(error, DeployError::ConfigLineCountMismatch(2, 3)));
+    }
+
+    #[tokio::test]
+    async fn interrupted_deploy_saves_confirmed_config_lines() -> Result<()> {
+        let path = cache_path("interrupted");
+        let mut cache = write_cache(&path, 4)?;
+
+        // the first two transactions confirm and the remaining ones never confirm
+        let cluster = MockCluster::new(Duration::from_secs(60), |_, tx: &TxInfo| tx.chunk[0].0 > 1);
+
+        let interrupted = Arc::new(AtomicBool::new(false));
+        let flag = interrupted.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            flag.store(true, Ordering::SeqCst);
+        });
+
+        let result =
+            send_config_lines(&mut cache, transactions(4), interrupted, cluster, options()).await;
+
+        let error = result.expect_err("deploy should be cancelled");
+        assert!(error.downcast_ref::<cancel::Cancelled>().is_some());
+
+        let saved = load_cache(&path.to_string_lossy(), false)?;
+        let on_chain: Vec<bool> = saved.items.values().map(|item| item.on_chain).collect();
+        assert_eq!(on_chain, vec![true, true, false, false]);
+
+        fs::remove_file(path)?;
+        Ok(())
+    }
 }
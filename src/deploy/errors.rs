@@ -12,4 +12,19 @@ pub enum DeployError {
         "Your current wallet balance of {0} SOL is not enough. {1} SOL is needed to deploy the candy machine."
     )]
     BalanceTooLow(String, String),
+    #[error(
+        "Missing collection item \"-1\" in the cache: add collection.json and a collection image \
+        to the assets directory and run 'sugar upload', or deploy with --collection-mint <MINT>"
+    )]
+    MissingCollectionItem,
+    #[error(
+        "Collection item \"-1\" of the cache is missing its {0} link: run 'sugar upload' to \
+        upload the collection files"
+    )]
+    IncompleteCollectionItem(String),
+    #[error(
+        "The candy machine has {0} config line(s) written but the cache records {1}: run \
+        'sugar deploy' again to write the missing config lines"
+    )]
+    ConfigLineCountMismatch(usize, usize),
 }
@@ -2,6 +2,7 @@ use std::ops::Deref;
 
 use anchor_client::solana_sdk::{
     compute_budget::ComputeBudgetInstruction,
+    instruction::Instruction,
     pubkey::Pubkey,
     signature::{Keypair, Signature, Signer},
     system_instruction, system_program,
@@ -22,18 +23,14 @@ use solana_program::native_token::LAMPORTS_PER_SOL;
 
 use crate::{
     common::*,
+    compute_budget::{send_transaction, ComputeBudget},
     config::data::*,
     deploy::errors::*,
     pdas::{find_candy_machine_creator_pda, find_master_edition_pda, find_metadata_pda},
-    setup::SugarClient,
 };
 
 /// Create the candy machine data struct.
-pub fn create_candy_machine_data(
-    _client: &SugarClient,
-    config: &ConfigData,
-    cache: &Cache,
-) -> Result<CandyMachineData> {
+pub fn create_candy_machine_data(config: &ConfigData, cache: &Cache) -> Result<CandyMachineData> {
     let mut creators: Vec<CandyCreator> = Vec::new();
     let mut share = 0u32;
 
@@ -132,22 +129,59 @@ pub fn create_candy_machine_data(
 }
 
 /// Send the `initialize_candy_machine` instruction to the candy machine program.
+#[allow(clippy::too_many_arguments)]
 pub fn initialize_candy_machine<C: Deref<Target = impl Signer> + Clone>(
     config_data: &ConfigData,
+    keypair: &Keypair,
     candy_account: &Keypair,
     candy_machine_data: CandyMachineData,
     collection_mint: Pubkey,
     collection_update_authority: Pubkey,
     program: Program<C>,
     priority_fee: &u64,
+    compute_budget: &ComputeBudget,
 ) -> Result<Signature> {
-    let payer = program.payer();
+    let instructions = initialize_candy_machine_instructions(
+        config_data,
+        program.payer(),
+        &candy_account.pubkey(),
+        candy_machine_data,
+        collection_mint,
+        collection_update_authority,
+        &program,
+        *priority_fee,
+    )?;
+
+    let sig = send_transaction(
+        &program.rpc(),
+        keypair,
+        &instructions,
+        &[candy_account],
+        compute_budget,
+    )?;
+
+    Ok(sig)
+}
+
+/// Instructions of the transaction creating and initializing the candy machine account,
+/// paid by `payer` (the authority of the candy machine).
+#[allow(clippy::too_many_arguments)]
+pub fn initialize_candy_machine_instructions<C: Deref<Target = impl Signer> + Clone>(
+    config_data: &ConfigData,
+    payer: Pubkey,
+    candy_account: &Pubkey,
+    candy_machine_data: CandyMachineData,
+    collection_mint: Pubkey,
+    collection_update_authority: Pubkey,
+    program: &Program<C>,
+    priority_fee: u64,
+) -> Result<Vec<Instruction>> {
     let candy_account_size = candy_machine_data.get_space_for_candy()?;
 
     info!(
         "Initializing candy machine with account size of: {} and address of: {}",
         candy_account_size,
-        candy_account.pubkey().to_string()
+        candy_account.to_string()
     );
 
     let lamports = program
@@ -166,7 +200,7 @@ pub fn initialize_candy_machine<C: Deref<Target = impl Signer> + Clone>(
 
     // required PDAs
 
-    let (authority_pda, _) = find_candy_machine_creator_pda(&candy_account.pubkey());
+    let (authority_pda, _) = find_candy_machine_creator_pda(candy_account);
 
     let collection_metadata = find_metadata_pda(&collection_mint);
     let collection_master_edition = find_master_edition_pda(&collection_mint);
@@ -177,21 +211,20 @@ pub fn initialize_candy_machine<C: Deref<Target = impl Signer> + Clone>(
         &authority_pda,
     );
 
-    let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(*priority_fee);
+    let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
 
-    let tx = program
+    let instructions = program
         .request()
         .instruction(priority_fee_ix)
         .instruction(system_instruction::create_account(
             &payer,
-            &candy_account.pubkey(),
+            candy_account,
             lamports,
             candy_account_size as u64,
             &program.id(),
         ))
-        .signer(candy_account)
         .accounts(nft_accounts::InitializeV2 {
-            candy_machine: candy_account.pubkey(),
+            candy_machine: *candy_account,
             authority: payer,
             authority_pda,
             payer,
@@ -212,9 +245,8 @@ pub fn initialize_candy_machine<C: Deref<Target = impl Signer> + Clone>(
             token_standard: <crate::config::data::TokenStandard as std::convert::Into<
                 TokenStandard,
             >>::into(config_data.token_standard) as u8,
-        });
-
-    let sig = tx.send()?;
+        })
+        .instructions()?;
 
-    Ok(sig)
+    Ok(instructions)
 }
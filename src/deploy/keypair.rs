@@ -0,0 +1,169 @@
+use std::fs;
+
+use anchor_client::solana_sdk::signature::{read_keypair_file, write_keypair_file};
+use solana_client::rpc_client::RpcClient;
+
+use crate::{candy_machine::CANDY_MACHINE_ID, common::*, output::style};
+
+/// Keypair of the account of a new candy machine, saved to a file before the account is
+/// created so that a deploy that stopped after its creation continues with the same account.
+pub struct CandyMachineKeypair {
+    pub keypair: Keypair,
+    /// File written by sugar, removed once the candy machine is recorded in the cache.
+    generated: Option<PathBuf>,
+}
+
+impl CandyMachineKeypair {
+    /// Removes the keypair file written by sugar (a file given with `--candy-machine-keypair`
+    /// is kept). The candy machine is recorded in the cache, so a failure is only reported.
+    pub fn remove_generated(&self) {
+        if let Some(path) = &self.generated {
+            if let Err(err) = fs::remove_file(path) {
+                warn!("Failed to remove {}: {}", path.display(), err);
+            }
+        }
+    }
+}
+
+/// Default file of the candy machine keypair, next to the cache file (one per chunk).
+pub fn default_candy_machine_keypair_path(cache: &Cache) -> PathBuf {
+    let cache_path = Path::new(&cache.file_path);
+    let stem = cache_path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "cache".to_string());
+
+    let name = match cache.chunk {
+        Some(range) => format!("{}-chunk-{}-candy-machine-keypair.json", stem, range.index),
+        None => format!("{}-candy-machine-keypair.json", stem),
+    };
+
+    cache_path.with_file_name(name)
+}
+
+/// Loads the candy machine keypair from `path` (or the default file of the cache), creating
+/// the file with a new keypair when it does not exist.
+pub fn candy_machine_keypair(cache: &Cache, path: Option<&str>) -> Result<CandyMachineKeypair> {
+    let (path, default) = match path {
+        Some(path) => (PathBuf::from(path), false),
+        None => (default_candy_machine_keypair_path(cache), true),
+    };
+
+    if path.exists() {
+        let keypair = read_keypair_file(&path)
+            .map_err(|e| anyhow!("Failed to read keypair {}: {}", path.display(), e))?;
+
+        report!(
+            "{} {} ({})",
+            style("Candy machine keypair:").bold(),
+            keypair.pubkey(),
+            path.display()
+        );
+
+        return Ok(CandyMachineKeypair {
+            keypair,
+            generated: default.then_some(path),
+        });
+    }
+
+    let keypair = Keypair::new();
+    write_keypair_file(&keypair, &path)
+        .map_err(|e| anyhow!("Failed to write keypair {}: {}", path.display(), e))?;
+
+    report!(
+        "{} {} saved to {} (reused if the deploy stops before the candy machine is recorded \
+        in the cache)",
+        style("Candy machine keypair:").bold(),
+        keypair.pubkey(),
+        path.display()
+    );
+
+    Ok(CandyMachineKeypair {
+        keypair,
+        generated: default.then_some(path),
+    })
+}
+
+/// Returns the candy machine at `candy_pubkey` when its account is already initialized, e.g.
+/// by a deploy that stopped before recording it in the cache. Fails when the account is used
+/// by something else or the candy machine has another authority.
+pub fn initialized_candy_machine(
+    rpc: &RpcClient,
+    candy_pubkey: &Pubkey,
+    authority: &Pubkey,
+) -> Result<Option<CandyMachine>> {
+    let account = match rpc
+        .get_account_with_commitment(candy_pubkey, rpc.commitment())?
+        .value
+    {
+        Some(account) => account,
+        None => return Ok(None),
+    };
+
+    if account.owner != CANDY_MACHINE_ID {
+        return Err(anyhow!(
+            "The account {} of the candy machine keypair is already in use (owned by {})",
+            candy_pubkey,
+            account.owner
+        ));
+    }
+
+    let candy_machine = CandyMachine::try_deserialize(&mut account.data.as_slice())
+        .map_err(|_| anyhow!("The account {} is not a candy machine", candy_pubkey))?;
+
+    if candy_machine.authority != *authority {
+        return Err(anyhow!(
+            "The candy machine {} has the authority {}, not {}",
+            candy_pubkey,
+            candy_machine.authority,
+            authority
+        ));
+    }
+
+    Ok(Some(candy_machine))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::ChunkRange;
+
+    #[test]
+    fn candy_machine_keypair_is_reused() -> Result<()> {
+        let dir = std::env::temp_dir().join(format!("sugar-cm-keypair-{}", std::process::id()));
+        fs::create_dir_all(&dir)?;
+
+        let mut cache = Cache::new();
+        cache.file_path = dir.join("cache.json").to_string_lossy().to_string();
+
+        let path = default_candy_machine_keypair_path(&cache);
+        assert_eq!(path, dir.join("cache-candy-machine-keypair.json"));
+
+        let first = candy_machine_keypair(&cache, None)?;
+        assert!(path.exists());
+        let second = candy_machine_keypair(&cache, None)?;
+        assert_eq!(first.keypair.pubkey(), second.keypair.pubkey());
+
+        second.remove_generated();
+        assert!(!path.exists());
+
+        // a given file is kept
+        let given = dir.join("given.json");
+        let keypair = candy_machine_keypair(&cache, Some(&given.to_string_lossy()))?;
+        keypair.remove_generated();
+        assert!(given.exists());
+
+        cache.chunk = Some(ChunkRange {
+            index: 2,
+            first_item: 10,
+            items: 5,
+        });
+        assert_eq!(
+            default_candy_machine_keypair_path(&cache),
+            dir.join("cache-chunk-2-candy-machine-keypair.json")
+        );
+
+        fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
@@ -2,10 +2,16 @@ pub mod collection;
 pub mod config_lines;
 pub mod errors;
 pub mod initialize;
+pub mod keypair;
+pub mod plan;
 pub mod process;
+pub mod unsigned;
 
 pub use collection::*;
 pub use config_lines::*;
 pub use errors::*;
 pub use initialize::*;
+pub use keypair::*;
+pub use plan::*;
 pub use process::*;
+pub use unsigned::*;
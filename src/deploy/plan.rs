@@ -0,0 +1,378 @@
+use anchor_client::solana_sdk::native_token::LAMPORTS_PER_SOL;
+use mpl_candy_machine_core::CandyMachineData;
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    candy_machine::get_config_line_state,
+    common::*,
+    config::data::{ConfigData, SugarConfig},
+    deploy::{create_candy_machine_data, generate_config_lines, reconcile_config_lines},
+    estimate::{estimate_rent, print_rent_estimate, RentEstimate},
+    output::style,
+    priority_fee::total_priority_lamports,
+    validate::ConsistencyReport,
+};
+
+/// Fee of each signature of a transaction (in lamports).
+const LAMPORTS_PER_SIGNATURE: u64 = 5_000;
+
+/// What a deploy does with the candy machine.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CandyMachineAction {
+    Create,
+    Update(Pubkey),
+}
+
+/// The changes of a deploy, computed from the on-chain state without sending any
+/// transaction (`deploy --dry-run`).
+#[derive(Debug, PartialEq, Eq)]
+pub struct DeployPlan {
+    pub candy_machine: CandyMachineAction,
+    /// Whether the collection NFT of the cache is minted.
+    pub create_collection: bool,
+    /// Existing collection set on the new candy machine.
+    pub collection_mint: Option<String>,
+    pub config_lines: usize,
+    pub config_line_transactions: usize,
+    /// Whether the hash of the hidden settings is updated.
+    pub update_hash: bool,
+    /// Rent of the new candy machine (and candy guard) accounts.
+    pub rent: Option<RentEstimate>,
+    /// Differences between the config, the cache and the candy machine.
+    pub mismatches: Vec<String>,
+}
+
+impl DeployPlan {
+    /// Number of transactions and signatures of the deploy.
+    pub fn transactions(&self) -> (u64, u64) {
+        let mut transactions = 0;
+        let mut signatures = 0;
+
+        // the collection mint and the candy machine account sign their creation
+        if self.create_collection {
+            transactions += 1;
+            signatures += 2;
+        }
+        if self.candy_machine == CandyMachineAction::Create {
+            transactions += 1;
+            signatures += 2;
+        }
+        if self.update_hash {
+            transactions += 1;
+            signatures += 1;
+        }
+
+        transactions += self.config_line_transactions as u64;
+        signatures += self.config_line_transactions as u64;
+
+        (transactions, signatures)
+    }
+
+    /// Base and priority fees (in lamports) of the transactions at the compute unit `price`,
+    /// each with a limit of `compute_units`.
+    pub fn fees(&self, price: u64, compute_units: u32) -> (u64, u64) {
+        let (transactions, signatures) = self.transactions();
+
+        (
+            signatures * LAMPORTS_PER_SIGNATURE,
+            total_priority_lamports(price, compute_units, transactions),
+        )
+    }
+}
+
+/// Plans a deploy of the cache: the candy machine is created when the cache has none,
+/// otherwise its config lines are compared with the cache (which is corrected in memory only).
+#[allow(clippy::too_many_arguments)]
+pub fn plan_deploy(
+    sugar_config: &SugarConfig,
+    rpc: &RpcClient,
+    cache: &mut Cache,
+    config_data: &ConfigData,
+    collection_mint: Option<&str>,
+    lines_per_tx: Option<usize>,
+    consistency: &ConsistencyReport,
+    force: bool,
+) -> Result<DeployPlan> {
+    let hidden = config_data.hidden_settings.is_some();
+    let num_items = config_data.number;
+    let mut mismatches = Vec::new();
+
+    if !consistency.is_consistent() {
+        mismatches.push(format!(
+            "Config number does not match the cache{}: {}",
+            if hidden || force {
+                ""
+            } else {
+                " (the deploy requires --force)"
+            },
+            consistency.to_string().trim_end().replace('\n', ", ")
+        ));
+    }
+
+    if cache.program.candy_machine.is_empty() {
+        let create_collection = cache
+            .items
+            .get("-1")
+            .map(|item| !item.on_chain)
+            .unwrap_or(false);
+        let collection_mint = if create_collection {
+            None
+        } else {
+            Some(
+                collection_mint
+                    .map(String::from)
+                    .unwrap_or_else(|| cache.program.collection_mint.clone()),
+            )
+        };
+
+        let candy_data = create_candy_machine_data(config_data, cache)?;
+        let config_lines = if hidden {
+            Vec::new()
+        } else {
+            generate_config_lines(num_items, &cache.items, &candy_data, lines_per_tx)?
+        };
+
+        Ok(DeployPlan {
+            candy_machine: CandyMachineAction::Create,
+            create_collection,
+            collection_mint,
+            config_lines: config_lines.iter().map(Vec::len).sum(),
+            config_line_transactions: config_lines.len(),
+            update_hash: hidden,
+            rent: Some(estimate_rent(
+                Some(rpc),
+                &candy_data,
+                config_data.guards.as_ref(),
+            )?),
+            mismatches,
+        })
+    } else {
+        let candy_pubkey = Pubkey::from_str(&cache.program.candy_machine).map_err(|_| {
+            CacheError::InvalidCandyMachineAddress(cache.program.candy_machine.clone())
+        })?;
+        let (candy_machine, loaded) =
+            get_config_line_state(sugar_config, &candy_pubkey).map_err(|_| {
+                anyhow!(
+                    "Candy machine from cache does't exist on chain: {}",
+                    candy_pubkey
+                )
+            })?;
+
+        let reconciliation = reconcile_config_lines(cache, num_items, &loaded);
+        if reconciliation.corrected > 0 {
+            mismatches.push(format!(
+                "Cache out of sync with the candy machine: {reconciliation}"
+            ));
+        }
+        mismatches.extend(candy_machine_mismatches(
+            config_data,
+            cache,
+            &candy_machine.data,
+            &candy_machine.collection_mint,
+        ));
+
+        let config_lines = if hidden || candy_machine.data.config_line_settings.is_none() {
+            Vec::new()
+        } else {
+            generate_config_lines(num_items, &cache.items, &candy_machine.data, lines_per_tx)?
+        };
+
+        Ok(DeployPlan {
+            candy_machine: CandyMachineAction::Update(candy_pubkey),
+            create_collection: false,
+            collection_mint: None,
+            config_lines: config_lines.iter().map(Vec::len).sum(),
+            config_line_transactions: config_lines.len(),
+            update_hash: hidden,
+            rent: None,
+            mismatches,
+        })
+    }
+}
+
+/// Differences between the config (and cache) and an existing candy machine that a deploy
+/// does not change.
+fn candy_machine_mismatches(
+    config_data: &ConfigData,
+    cache: &Cache,
+    data: &CandyMachineData,
+    collection_mint: &Pubkey,
+) -> Vec<String> {
+    let mut mismatches = Vec::new();
+
+    if data.items_available != config_data.number {
+        mismatches.push(format!(
+            "The candy machine has {} item(s), the config number is {}",
+            data.items_available, config_data.number
+        ));
+    }
+
+    match (
+        config_data.hidden_settings.is_some(),
+        data.hidden_settings.is_some(),
+    ) {
+        (true, false) => mismatches.push(
+            "The config has hidden settings, the candy machine uses config lines".to_string(),
+        ),
+        (false, true) => mismatches
+            .push("The candy machine has hidden settings, the config does not".to_string()),
+        _ => (),
+    }
+
+    if !cache.program.collection_mint.is_empty()
+        && cache.program.collection_mint != collection_mint.to_string()
+    {
+        mismatches.push(format!(
+            "The cache collection mint {} is not the candy machine's {} (use 'sugar collection \
+            set' to change it)",
+            cache.program.collection_mint, collection_mint
+        ));
+    }
+
+    mismatches
+}
+
+pub fn print_deploy_plan(plan: &DeployPlan, price: u64, compute_units: u32) {
+    let sol = |lamports: u64| lamports as f64 / LAMPORTS_PER_SOL as f64;
+
+    report!();
+    match &plan.candy_machine {
+        CandyMachineAction::Create => report!(
+            "{} create a new candy machine",
+            style("Candy machine:").bold()
+        ),
+        CandyMachineAction::Update(pubkey) => {
+            report!("{} update {}", style("Candy machine:").bold(), pubkey)
+        }
+    }
+
+    let collection = match (&plan.candy_machine, &plan.collection_mint) {
+        (CandyMachineAction::Update(_), _) => "unchanged".to_string(),
+        (CandyMachineAction::Create, Some(mint)) => format!("set to {}", mint),
+        (CandyMachineAction::Create, None) => "set to a new collection NFT".to_string(),
+    };
+    report!("{} {}", style("Collection mint:").bold(), collection);
+
+    if plan.update_hash {
+        report!(
+            "{} hidden settings, the hash is updated",
+            style("Config lines:").bold()
+        );
+    } else {
+        report!(
+            "{} {} to write in {} transaction(s)",
+            style("Config lines:").bold(),
+            plan.config_lines,
+            plan.config_line_transactions
+        );
+    }
+
+    let (transactions, signatures) = plan.transactions();
+    let (base_fee, priority_fee) = plan.fees(price, compute_units);
+
+    report!(
+        "{} {} ({} signature(s))",
+        style("Transactions:").bold(),
+        transactions,
+        signatures
+    );
+    report!("{} {} SOL", style("Base fee:").bold(), sol(base_fee));
+    report!(
+        "{} {} SOL ({} micro-lamports per compute unit)",
+        style("Priority fee:").bold(),
+        sol(priority_fee),
+        price
+    );
+    report!(
+        "{} {} SOL",
+        style("Total fees:").bold(),
+        sol(base_fee + priority_fee)
+    );
+
+    if let Some(rent) = &plan.rent {
+        print_rent_estimate(rent);
+    }
+
+    if plan.mismatches.is_empty() {
+        report!("\nConfig, cache and candy machine are consistent.");
+    } else {
+        report!();
+        for mismatch in &plan.mismatches {
+            report!("{}{}", WARNING_EMOJI, style(mismatch).yellow());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{estimate::estimate_candy_machine_data, priority_fee::DEFAULT_COMPUTE_UNITS};
+
+    fn plan(candy_machine: CandyMachineAction) -> DeployPlan {
+        DeployPlan {
+            candy_machine,
+            create_collection: false,
+            collection_mint: None,
+            config_lines: 0,
+            config_line_transactions: 0,
+            update_hash: false,
+            rent: None,
+            mismatches: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn fees_of_a_new_candy_machine() {
+        let plan = DeployPlan {
+            create_collection: true,
+            config_lines: 40,
+            config_line_transactions: 3,
+            ..plan(CandyMachineAction::Create)
+        };
+
+        assert_eq!(plan.transactions(), (5, 7));
+        // 500 micro-lamports per compute unit for 200k units is 100 lamports per transaction
+        assert_eq!(plan.fees(500, DEFAULT_COMPUTE_UNITS), (35_000, 500));
+        // with --compute-unit-limit 1400000, 700 lamports per transaction
+        assert_eq!(plan.fees(500, 1_400_000), (35_000, 3_500));
+    }
+
+    #[test]
+    fn fees_of_a_hidden_settings_update() {
+        let plan = DeployPlan {
+            update_hash: true,
+            ..plan(CandyMachineAction::Update(Pubkey::new_unique()))
+        };
+
+        assert_eq!(plan.transactions(), (1, 1));
+        assert_eq!(plan.fees(0, DEFAULT_COMPUTE_UNITS), (5_000, 0));
+    }
+
+    #[test]
+    fn reports_candy_machine_mismatches() {
+        let config_data = ConfigData {
+            number: 10,
+            ..ConfigData::default()
+        };
+        let collection_mint = Pubkey::new_unique();
+        let mut cache = Cache::new();
+        cache.program.collection_mint = collection_mint.to_string();
+
+        let data = estimate_candy_machine_data(10, 32, 200, false);
+        assert!(candy_machine_mismatches(&config_data, &cache, &data, &collection_mint).is_empty());
+
+        let data = estimate_candy_machine_data(12, 32, 200, true);
+        let mismatches =
+            candy_machine_mismatches(&config_data, &cache, &data, &Pubkey::new_unique());
+
+        assert_eq!(mismatches.len(), 3);
+        assert_eq!(
+            mismatches[0],
+            "The candy machine has 12 item(s), the config number is 10"
+        );
+        assert_eq!(
+            mismatches[1],
+            "The candy machine has hidden settings, the config does not"
+        );
+    }
+}
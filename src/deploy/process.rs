@@ -9,29 +9,43 @@ use std::{
 };
 
 use anchor_client::solana_sdk::{
-    pubkey::Pubkey,
-    signature::{Keypair, Signer},
+    native_token::LAMPORTS_PER_SOL, pubkey::Pubkey, signature::Signer,
 };
 use anyhow::Result;
 use borsh::BorshDeserialize;
-use console::style;
 use mpl_token_metadata::state::Metadata;
+use solana_client::rpc_client::RpcClient;
 
 use crate::{
     cache::*,
-    candy_machine::{get_candy_machine_state, CANDY_MACHINE_ID},
+    candy_machine::{get_candy_machine_state, get_config_line_state, CANDY_MACHINE_ID},
     common::*,
+    compute_budget::ComputeBudget,
     config::parser::get_config_data,
     deploy::{
+        add_unsigned_deploy_transactions, candy_machine_keypair, check_written_config_lines,
         create_candy_machine_data, create_collection, errors::*, generate_config_lines,
-        initialize_candy_machine, upload_config_lines,
+        generate_config_lines_within, initialize_candy_machine, initialized_candy_machine,
+        plan_deploy, print_deploy_plan, reconcile_config_lines, upload_config_lines,
+        LOOKUP_TABLE_OVERHEAD, MAX_TRANSACTION_BYTES,
     },
     hash::hash_and_update,
+    lut::cache_lookup_table,
+    offline::{unsigned_setup, UnsignedTxArgs, UnsignedTxWriter},
+    output::style,
     pdas::find_metadata_pda,
-    setup::{setup_client, sugar_setup},
+    priority_fee::{
+        apply_priority_budget, resolve_priority_fee, PriorityFee, DEFAULT_COMPUTE_UNITS,
+    },
+    progress_stream,
+    setup::setup_client,
     update::{process_update, UpdateArgs},
     utils::*,
-    validate::parser::{check_name, check_seller_fee_basis_points, check_symbol, check_url},
+    validate::{
+        check_consistency,
+        parser::{check_name, check_seller_fee_basis_points, check_symbol, check_url},
+    },
+    verify::{check_config_lines, decode_config_lines},
 };
 
 pub struct DeployArgs {
@@ -41,16 +55,39 @@ pub struct DeployArgs {
     pub rpc_url: Option<String>,
     pub interrupted: Arc<AtomicBool>,
     pub collection_mint: Option<String>,
-    pub priority_fee: u64,
+    pub priority_fee: PriorityFee,
+    pub max_total_priority_sol: Option<f64>,
+    pub dry_run: bool,
+    pub force: bool,
+    pub lines_per_tx: Option<usize>,
+    pub parallelism: usize,
+    pub max_resubmits: u32,
+    pub unsigned_tx: Option<UnsignedTxArgs>,
+    pub use_lut: bool,
+    pub chunk: Option<u32>,
+    pub chunk_size: Option<u64>,
+    pub verify: bool,
+    pub candy_machine_keypair: Option<String>,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub async fn process_deploy(args: DeployArgs) -> Result<()> {
     // loads the cache file (this needs to have been created by
-    // the upload command)
-    let mut cache = load_cache(&args.cache, false)?;
+    // the upload command), or the items of a chunk with their own candy machine
+    let mut cache = match args.chunk {
+        Some(chunk) => load_cache_chunk(&args.cache, chunk, args.chunk_size)?,
+        None => load_cache(&args.cache, false)?,
+    };
+
+    if args.chunk.is_none() && !cache.chunks.is_empty() {
+        return Err(anyhow!(
+            "The cache items are deployed in chunks, use --chunk to deploy one of them"
+        ));
+    }
 
     if cache.items.is_empty() {
-        println!(
+        report!(
             "{}",
             style("No cache items found - run 'upload' to create the cache file first.")
                 .red()
@@ -72,17 +109,56 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
         }
 
         if item.metadata_link.is_empty() {
-            return Err(DeployError::MissingMetadataLink(index.to_string()).into());
+            return Err(if index == "-1" {
+                DeployError::IncompleteCollectionItem("metadata".to_string())
+            } else {
+                DeployError::MissingMetadataLink(index.to_string())
+            }
+            .into());
         } else {
             check_url(&item.metadata_link)?;
         }
     }
 
-    let sugar_config = Arc::new(sugar_setup(args.keypair.clone(), args.rpc_url.clone())?);
+    // a new candy machine needs the collection item, unless an existing collection is given
+    if cache.program.candy_machine.is_empty()
+        && args.collection_mint.is_none()
+        && !cache.items.contains_key("-1")
+    {
+        return Err(DeployError::MissingCollectionItem.into());
+    }
+
+    let (sugar_config, authority) = unsigned_setup(
+        args.keypair.clone(),
+        args.rpc_url.clone(),
+        args.unsigned_tx.as_ref(),
+    )?;
+    let sugar_config = Arc::new(sugar_config);
     let client = setup_client(&sugar_config)?;
     let mut config_data = get_config_data(&args.config)?;
 
-    let candy_machine_address = cache.program.candy_machine.clone();
+    if let Some(range) = cache.chunk {
+        if config_data.hidden_settings.is_some() {
+            return Err(anyhow!("Chunks cannot be deployed with hidden settings"));
+        }
+        // the candy machine of a chunk has the items of the chunk
+        config_data.number = range.items;
+
+        report!(
+            "{} {} (items {}-{})",
+            style("Chunk:").bold(),
+            range.index,
+            range.first_item,
+            range.first_item + range.items - 1
+        );
+    }
+
+    cache.check_cluster(
+        &client.program(CANDY_MACHINE_ID).rpc(),
+        args.allow_cluster_mismatch,
+    )?;
+
+    let mut candy_machine_address = cache.program.candy_machine.clone();
 
     // checks the candy machine data
 
@@ -90,28 +166,31 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
     let hidden = config_data.hidden_settings.is_some();
     let collection_in_cache = cache.items.get("-1").is_some();
 
-    let cache_items_sans_collection = (cache.items.len() - collection_in_cache as usize) as u64;
-
-    if !hidden && num_items != cache_items_sans_collection {
-        return Err(anyhow!(
-            "Number of items ({}) do not match cache items ({}). 
-            Item number in the config should only include asset files, not the collection file.",
-            num_items,
-            cache_items_sans_collection
-        ));
-    } else if hidden && num_items != cache_items_sans_collection {
-        println!(
-            "{}",
-            style(format!(
-                "Warning: Number of items ({}) do not match cache items ({}). 
-                {} items are missing. Revealing will not work correctly.",
-                num_items,
-                cache_items_sans_collection,
-                num_items.saturating_sub(cache_items_sans_collection)
-            ))
-            .yellow()
-            .bold()
-        );
+    let report = check_consistency(None, Some(num_items), Some(&cache), false)?;
+
+    // a dry run reports the mismatch with the rest of the plan instead
+    if !report.is_consistent() && !args.dry_run {
+        if hidden || args.force {
+            report!(
+                "{}{}\n{}",
+                WARNING_EMOJI,
+                style(if hidden {
+                    "Config number does not match the cache, revealing will not work correctly:"
+                } else {
+                    "Config number does not match the cache, deploying anyway (--force):"
+                })
+                .yellow()
+                .bold(),
+                report
+            );
+        } else {
+            return Err(anyhow!(
+                "Config number does not match the cache (use --force to deploy anyway). \
+                Item number in the config should only include asset files, not the collection \
+                file.\n{}",
+                report
+            ));
+        }
     }
 
     check_symbol(&config_data.symbol)?;
@@ -123,9 +202,134 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
         0
     } - (hidden as u8);
 
-    let candy_pubkey = if candy_machine_address.is_empty() {
-        let candy_keypair = Keypair::new();
-        let candy_pubkey = candy_keypair.pubkey();
+    // reports what the deploy would change on-chain without sending any transaction
+
+    if args.dry_run {
+        report!(
+            "\n{} {}Planning deploy (dry run)",
+            style("[1/1]").bold().dim(),
+            LOOKING_GLASS_EMOJI
+        );
+
+        let rpc = client.program(CANDY_MACHINE_ID).rpc();
+        let plan = plan_deploy(
+            &sugar_config,
+            &rpc,
+            &mut cache,
+            &config_data,
+            args.collection_mint.as_deref(),
+            args.lines_per_tx,
+            &report,
+            args.force,
+        )?;
+
+        let (transactions, _) = plan.transactions();
+        let compute_units = args
+            .compute_budget
+            .compute_unit_limit(DEFAULT_COMPUTE_UNITS);
+        let mut price = args.priority_fee.micro_lamports_per_cu(compute_units);
+
+        if let Some(max_total_priority_sol) = args.max_total_priority_sol {
+            let max_lamports = (max_total_priority_sol * LAMPORTS_PER_SOL as f64) as u64;
+            price = apply_priority_budget(price, compute_units, transactions, max_lamports);
+        }
+
+        print_deploy_plan(&plan, price, compute_units);
+
+        return Ok(());
+    }
+
+    // the keypair of a new candy machine is saved before its creation: when the account is
+    // already initialized, the deploy continues with it instead of creating another one
+    let candy_keypair = if candy_machine_address.is_empty() && args.unsigned_tx.is_none() {
+        let candy_keypair = candy_machine_keypair(&cache, args.candy_machine_keypair.as_deref())?;
+        let candy_pubkey = candy_keypair.keypair.pubkey();
+        let rpc = client.program(CANDY_MACHINE_ID).rpc();
+
+        match initialized_candy_machine(&rpc, &candy_pubkey, &sugar_config.keypair.pubkey())? {
+            Some(candy_machine) => {
+                report!(
+                    "\n{}{}",
+                    WARNING_EMOJI,
+                    style(format!(
+                        "Candy machine {} is already initialized, continuing with it",
+                        candy_pubkey
+                    ))
+                    .yellow()
+                );
+
+                cache.program = CacheProgram::new_from_cm(&candy_pubkey);
+                cache.program.collection_mint = candy_machine.collection_mint.to_string();
+                cache.program.record_existing_candy_machine(&rpc)?;
+                // the collection NFT of the cache is created before the candy machine
+                if args.collection_mint.is_none() {
+                    if let Some(item) = cache.items.get_mut("-1") {
+                        item.on_chain = true;
+                    }
+                }
+                cache.sync_file()?;
+                candy_keypair.remove_generated();
+
+                candy_machine_address = candy_pubkey.to_string();
+                None
+            }
+            None => Some(candy_keypair),
+        }
+    } else {
+        None
+    };
+
+    // estimates the number of transactions to report the priority fee cost
+
+    let setup_transactions = if candy_machine_address.is_empty() {
+        1 + collection_in_cache as u64
+    } else {
+        0
+    };
+    let item_transactions = if hidden {
+        1
+    } else {
+        let candy_data = create_candy_machine_data(&config_data, &cache)?;
+        generate_config_lines(num_items, &cache.items, &candy_data, args.lines_per_tx)?.len() as u64
+    };
+
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        args.compute_budget
+            .compute_unit_limit(DEFAULT_COMPUTE_UNITS),
+        setup_transactions + item_transactions,
+        args.max_total_priority_sol,
+    )?;
+
+    // writes the transactions for an external signer instead of sending them
+
+    if let Some(unsigned_tx) = &args.unsigned_tx {
+        let mut writer = UnsignedTxWriter::new("deploy", unsigned_tx, authority)?;
+
+        add_unsigned_deploy_transactions(
+            &client,
+            &sugar_config,
+            &mut writer,
+            &mut cache,
+            &config_data,
+            args.collection_mint,
+            args.lines_per_tx,
+            priority_fee,
+        )?;
+        writer.finish()?;
+
+        if hidden {
+            report!(
+                "\nCandy machine has hidden settings: once the transactions are sent, run 'sugar \
+                hash' and 'sugar config update --unsigned-tx-out' to update the hash value."
+            );
+        }
+
+        return Ok(());
+    }
+
+    let candy_pubkey = if let Some(candy_keypair) = candy_keypair {
+        let candy_pubkey = candy_keypair.keypair.pubkey();
 
         // collection_item could be missing when args.collection_mint is provided
         let collection_item = if let Some(collection_item) = cache.items.get_mut("-1") {
@@ -133,15 +337,16 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
         } else {
             match args.collection_mint {
                 Some(_) => None, // existing collection provided
-                None => return Err(anyhow!("Missing collection item in cache")),
+                None => return Err(DeployError::MissingCollectionItem.into()),
             }
         };
 
-        println!(
+        report!(
             "\n{} {}Creating collection NFT for candy machine",
             style(format!("[1/{}]", total_steps)).bold().dim(),
             COLLECTION_EMOJI
         );
+        progress_stream::phase_started("create_collection", 1, total_steps as usize);
 
         let collection_minted = match collection_item {
             Some(item) => item.on_chain,
@@ -153,17 +358,24 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
             .unwrap_or_else(|| cache.program.collection_mint.clone());
 
         let collection_mint = if collection_minted {
-            println!("\nCollection mint already deployed.");
+            report!("\nCollection mint already deployed.");
             Pubkey::from_str(&collection_str)?
         } else {
             let pb = spinner_with_style();
             pb.set_message("Creating NFT...");
 
-            let (_, collection_mint) =
-                create_collection(&client, candy_pubkey, &mut cache, &config_data, &args)?;
+            let (_, collection_mint) = create_collection(
+                &client,
+                &sugar_config.keypair,
+                candy_pubkey,
+                &mut cache,
+                &config_data,
+                priority_fee,
+                &args.compute_budget,
+            )?;
 
             pb.finish_and_clear();
-            println!(
+            report!(
                 "{} {}",
                 style("Collection mint ID:").bold(),
                 collection_mint
@@ -172,17 +384,20 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
             collection_mint
         };
 
-        println!(
+        progress_stream::phase_finished();
+
+        report!(
             "{} {}Creating candy machine",
             style(format!("\n[2/{}]", total_steps)).bold().dim(),
             CANDY_EMOJI
         );
+        progress_stream::phase_started("create_candy_machine", 2, total_steps as usize);
         info!("Candy machine address is empty, creating new candy machine...");
 
         let spinner = spinner_with_style();
         spinner.set_message("Creating candy machine...");
 
-        let candy_data = create_candy_machine_data(&client, &config_data, &cache)?;
+        let candy_data = create_candy_machine_data(&config_data, &cache)?;
         let program = client.program(CANDY_MACHINE_ID);
 
         // all good, let's create the candy machine
@@ -193,12 +408,14 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
 
         let sig = initialize_candy_machine(
             &config_data,
-            &candy_keypair,
+            &sugar_config.keypair,
+            &candy_keypair.keypair,
             candy_data,
             collection_mint,
             metadata.update_authority,
             program,
-            &args.priority_fee,
+            &priority_fee,
+            &args.compute_budget,
         )?;
         info!("Candy machine initialized with sig: {}", sig);
         info!(
@@ -208,17 +425,23 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
 
         cache.program = CacheProgram::new_from_cm(&candy_pubkey);
         cache.program.collection_mint = collection_mint.to_string();
+        cache
+            .program
+            .record_candy_machine_creation(&client.program(CANDY_MACHINE_ID).rpc(), &sig)?;
         cache.sync_file()?;
+        candy_keypair.remove_generated();
 
         spinner.finish_and_clear();
+        progress_stream::phase_finished();
 
         candy_pubkey
     } else {
-        println!(
+        report!(
             "{} {}Loading candy machine",
             style(format!("[1/{}]", total_steps)).bold().dim(),
             CANDY_EMOJI
         );
+        progress_stream::phase_started("load_candy_machine", 1, total_steps as usize);
 
         let candy_pubkey = match Pubkey::from_str(&candy_machine_address) {
             Ok(pubkey) => pubkey,
@@ -235,11 +458,12 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
         };
 
         if get_candy_machine_state(&Arc::clone(&sugar_config), &candy_pubkey).is_err() {
-            println!(
+            report!(
                 "\n{} Candy machine {} not found on-chain",
-                WARNING_EMOJI, candy_machine_address
+                WARNING_EMOJI,
+                candy_machine_address
             );
-            println!(
+            report!(
                 "\nThis can happen if you are trying to re-deploy a candy machine from \
                     a previously used cache file. If this is the case, re-run the deploy command \
                     with the option '--new'.",
@@ -251,10 +475,12 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
             ));
         }
 
+        progress_stream::phase_finished();
+
         candy_pubkey
     };
 
-    println!("{} {}", style("Candy machine ID:").bold(), candy_pubkey);
+    report!("{} {}", style("Candy machine ID:").bold(), candy_pubkey);
 
     // Hidden Settings check needs to be the last action in this command, so we can
     // update the hash with the final cache state.
@@ -264,21 +490,63 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
         } else {
             0
         };
-        println!(
+        report!(
             "\n{} {}Writing config lines",
             style(format!("[{}/{}]", step_num, total_steps))
                 .bold()
                 .dim(),
             PAPER_EMOJI
         );
+        progress_stream::phase_started(
+            "write_config_lines",
+            step_num as usize,
+            total_steps as usize,
+        );
 
-        let cndy_state = get_candy_machine_state(&sugar_config, &candy_pubkey)?;
+        let (cndy_state, loaded) = get_config_line_state(&sugar_config, &candy_pubkey)?;
         let cndy_data = cndy_state.data;
 
-        let config_lines = generate_config_lines(num_items, &cache.items, &cndy_data)?;
+        // the config lines written to the candy machine are the source of truth, since the
+        // cache might not have been saved after the last transactions
+        let reconciliation = reconcile_config_lines(&mut cache, num_items, &loaded);
+
+        if reconciliation.corrected > 0 {
+            cache.sync_file()?;
+            report!(
+                "{}{}",
+                WARNING_EMOJI,
+                style(format!(
+                    "Cache out of sync with the candy machine: {reconciliation}"
+                ))
+                .yellow()
+            );
+        } else {
+            report!("Cache in sync with the candy machine: {reconciliation}");
+        }
+
+        let lookup_table = if args.use_lut {
+            cache_lookup_table(&client.program(CANDY_MACHINE_ID).rpc(), &cache)?
+        } else {
+            None
+        };
+        // the lookup table and the compute unit limit take room from the config lines
+        let max_bytes = if lookup_table.is_some() {
+            MAX_TRANSACTION_BYTES - LOOKUP_TABLE_OVERHEAD
+        } else {
+            MAX_TRANSACTION_BYTES
+        } - args.compute_budget.compute_unit_limit_size();
+
+        let config_lines = generate_config_lines_within(
+            num_items,
+            &cache.items,
+            &cndy_data,
+            args.lines_per_tx,
+            max_bytes,
+        )?;
+        progress_stream::totals(config_lines.iter().map(|chunk| chunk.len() as u64).sum());
 
         if config_lines.is_empty() {
-            println!("\nAll config lines deployed.");
+            report!("\nAll config lines deployed.");
         } else {
             // clear the interruption handler value ahead of the upload
             args.interrupted.store(false, Ordering::SeqCst);
@@ -289,7 +557,11 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
                 &mut cache,
                 config_lines,
                 args.interrupted,
-                args.priority_fee,
+                priority_fee,
+                args.parallelism,
+                args.max_resubmits,
+                lookup_table,
+                args.compute_budget,
             )
             .await?;
 
@@ -314,18 +586,33 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
 
                 return Err(DeployError::AddConfigLineFailed(message).into());
             }
+
+            // the confirmed transactions are checked against the candy machine, in case the
+            // cache was updated with config lines that were not written
+            let (_, loaded) = get_config_line_state(&sugar_config, &candy_pubkey)?;
+            check_written_config_lines(&cache, num_items, &loaded)?;
         }
+
+        if args.verify {
+            verify_config_lines(
+                &client.program(CANDY_MACHINE_ID).rpc(),
+                &candy_pubkey,
+                &mut cache,
+            )?;
+        }
+
+        progress_stream::phase_finished();
     } else {
         // If hidden settings are enabled, update the hash value with the new cache file.
-        println!("\nCandy machine with hidden settings deployed.");
+        report!("\nCandy machine with hidden settings deployed.");
         let hidden_settings = config_data.hidden_settings.as_ref().unwrap().clone();
 
-        println!(
+        report!(
             "\nHidden settings hash: {}",
             hash_and_update(hidden_settings, &args.config, &mut config_data, &args.cache,)?
         );
 
-        println!("\nUpdating candy machine state with new hash value:\n");
+        report!("\nUpdating candy machine state with new hash value:\n");
         let update_args = UpdateArgs {
             keypair: args.keypair,
             rpc_url: args.rpc_url,
@@ -333,7 +620,10 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
             new_authority: None,
             config: args.config,
             candy_machine: Some(candy_pubkey.to_string()),
-            priority_fee: args.priority_fee,
+            priority_fee,
+            unsigned_tx: None,
+            compute_budget: args.compute_budget,
+            allow_cluster_mismatch: args.allow_cluster_mismatch,
         };
 
         process_update(update_args)?;
@@ -341,3 +631,43 @@ pub async fn process_deploy(args: DeployArgs) -> Result<()> {
 
     Ok(())
 }
+
+/// Compares the config lines of the candy machine with the cache items (`--verify`). The
+/// mismatched items are marked as not on-chain, so that the next deploy writes them again.
+fn verify_config_lines(rpc: &RpcClient, candy_pubkey: &Pubkey, cache: &mut Cache) -> Result<()> {
+    let pb = spinner_with_style();
+    pb.set_message("Verifying config lines...");
+
+    let data = rpc.get_account_data(candy_pubkey)?;
+    let candy_machine = CandyMachine::try_deserialize(&mut data.as_slice())?;
+    let on_chain_items = decode_config_lines(&data, &candy_machine.data)?;
+    let mismatches = check_config_lines(&mut cache.items, &on_chain_items);
+
+    if mismatches.is_empty() {
+        pb.finish_with_message(format!(
+            "{} {} config line(s) match the cache",
+            style("Verification successful:").green().bold(),
+            on_chain_items.len()
+        ));
+        return Ok(());
+    }
+
+    pb.abandon_with_message(format!("{}", style("Verification failed").red().bold()));
+    cache.sync_file()?;
+
+    report!(
+        "
+Config lines that do not match the cache:"
+    );
+    for (index, mismatch) in &mismatches {
+        report!("- Item {}: {}", index, mismatch);
+    }
+
+    let indices: Vec<u64> = mismatches.iter().map(|(index, _)| *index as u64).collect();
+    Err(anyhow!(
+        "{} config line(s) do not match the cache (items {}), re-run 'sugar deploy' to write \
+        them again",
+        mismatches.len(),
+        format_indices(&indices)
+    ))
+}
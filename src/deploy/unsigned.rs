@@ -0,0 +1,176 @@
+use borsh::BorshDeserialize;
+use mpl_token_metadata::state::Metadata;
+
+use crate::{
+    candy_machine::{get_config_line_state, CANDY_MACHINE_ID},
+    common::*,
+    config::data::{ConfigData, SugarConfig},
+    deploy::{
+        add_config_lines_instructions, create_candy_machine_data, create_collection_instructions,
+        errors::*, generate_config_lines_within, initialize_candy_machine_instructions,
+        reconcile_config_lines, MAX_TRANSACTION_BYTES,
+    },
+    offline::{CacheUpdate, UnsignedTxWriter, NONCE_INSTRUCTION_SIZE},
+    output::style,
+    pdas::find_metadata_pda,
+    setup::SugarClient,
+};
+
+/// Adds the transactions of a deploy to the unsigned transactions: the collection NFT and the
+/// candy machine when they are not created yet, then the missing config lines. The cache is
+/// only updated by `send-signed`, once the transactions are confirmed.
+#[allow(clippy::too_many_arguments)]
+pub fn add_unsigned_deploy_transactions(
+    client: &SugarClient,
+    sugar_config: &SugarConfig,
+    writer: &mut UnsignedTxWriter,
+    cache: &mut Cache,
+    config_data: &ConfigData,
+    collection_mint: Option<String>,
+    lines_per_tx: Option<usize>,
+    priority_fee: u64,
+) -> Result<()> {
+    let program = client.program(CANDY_MACHINE_ID);
+    let rpc = program.rpc();
+    let authority = writer.authority();
+    let num_items = config_data.number;
+
+    let (candy_pubkey, candy_data) = if cache.program.candy_machine.is_empty() {
+        let candy_keypair = Keypair::new();
+
+        let collection_minted = match (cache.items.get("-1"), &collection_mint) {
+            (Some(item), _) => item.on_chain,
+            (None, Some(_)) => true,
+            (None, None) => return Err(DeployError::MissingCollectionItem.into()),
+        };
+
+        let (collection_mint, collection_update_authority) = if collection_minted {
+            let collection_str =
+                collection_mint.unwrap_or_else(|| cache.program.collection_mint.clone());
+            let collection_mint = Pubkey::from_str(&collection_str)?;
+
+            let data = rpc.get_account_data(&find_metadata_pda(&collection_mint))?;
+            let metadata = Metadata::deserialize(&mut data.as_slice())?;
+
+            (collection_mint, metadata.update_authority)
+        } else {
+            let mint_keypair = Keypair::new();
+            let mint = mint_keypair.pubkey();
+
+            let instructions = create_collection_instructions(
+                &program,
+                authority,
+                &mint,
+                &cache.items["-1"],
+                config_data,
+                priority_fee,
+            )?;
+            writer.add(
+                &rpc,
+                "create-collection",
+                "Create the collection NFT",
+                &instructions,
+                &[&mint_keypair],
+                CacheUpdate::CollectionMinted {
+                    mint: mint.to_string(),
+                },
+            )?;
+
+            (mint, authority)
+        };
+
+        let candy_data = create_candy_machine_data(config_data, cache)?;
+        let instructions = initialize_candy_machine_instructions(
+            config_data,
+            authority,
+            &candy_keypair.pubkey(),
+            create_candy_machine_data(config_data, cache)?,
+            collection_mint,
+            collection_update_authority,
+            &program,
+            priority_fee,
+        )?;
+        writer.add(
+            &rpc,
+            "create-candy-machine",
+            "Create the candy machine",
+            &instructions,
+            &[&candy_keypair],
+            CacheUpdate::CandyMachineCreated {
+                candy_machine: candy_keypair.pubkey().to_string(),
+                collection_mint: collection_mint.to_string(),
+            },
+        )?;
+
+        report!(
+            "{} {}",
+            style("Candy machine ID:").bold(),
+            candy_keypair.pubkey()
+        );
+
+        (candy_keypair.pubkey(), candy_data)
+    } else {
+        let candy_pubkey = Pubkey::from_str(&cache.program.candy_machine).map_err(|_| {
+            CacheError::InvalidCandyMachineAddress(cache.program.candy_machine.clone())
+        })?;
+
+        if config_data.hidden_settings.is_some() {
+            return Ok(());
+        }
+
+        let (candy_machine, loaded) = get_config_line_state(sugar_config, &candy_pubkey)?;
+        let reconciliation = reconcile_config_lines(cache, num_items, &loaded);
+
+        if reconciliation.corrected > 0 {
+            cache.sync_file()?;
+            report!(
+                "{}{}",
+                WARNING_EMOJI,
+                style(format!(
+                    "Cache out of sync with the candy machine: {reconciliation}"
+                ))
+                .yellow()
+            );
+        }
+
+        (candy_pubkey, candy_machine.data)
+    };
+
+    if config_data.hidden_settings.is_some() {
+        return Ok(());
+    }
+
+    // the nonce instruction and the compute unit limit take room from the config lines
+    let max_bytes = if writer.uses_nonces() {
+        MAX_TRANSACTION_BYTES - NONCE_INSTRUCTION_SIZE
+    } else {
+        MAX_TRANSACTION_BYTES
+    } - writer.compute_unit_limit_size();
+    let config_lines = generate_config_lines_within(
+        num_items,
+        &cache.items,
+        &candy_data,
+        lines_per_tx,
+        max_bytes,
+    )?;
+
+    for chunk in config_lines {
+        let first = chunk[0].0;
+        let last = chunk[chunk.len() - 1].0;
+
+        let instructions =
+            add_config_lines_instructions(&program, candy_pubkey, authority, &chunk, priority_fee)?;
+        writer.add(
+            &rpc,
+            &format!("config-lines-{first}-{last}"),
+            &format!("Write config lines {first}-{last}"),
+            &instructions,
+            &[],
+            CacheUpdate::ConfigLinesWritten {
+                indices: chunk.iter().map(|(index, _)| *index).collect(),
+            },
+        )?;
+    }
+
+    Ok(())
+}
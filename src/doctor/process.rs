@@ -0,0 +1,607 @@
+use std::fmt;
+
+use anchor_client::solana_sdk::{native_token::LAMPORTS_PER_SOL, signature::read_keypair_file};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    ardrive::{resolve_ardrive_wallet_content, ArdriveOptions},
+    cache::load_cache,
+    common::*,
+    config::{get_config_data, Cluster, ConfigData, SolanaConfig, UploadMethod},
+    output::style,
+    parse::{parse_solana_config, solana_config_path},
+    utils::*,
+};
+
+/// Minimum balance (in lamports) below which a warning is reported.
+const LOW_BALANCE: u64 = LAMPORTS_PER_SOL / 10;
+
+pub struct DoctorArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub config: String,
+    pub json: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+impl fmt::Display for CheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheckStatus::Pass => write!(f, "{}", style("PASS").green().bold()),
+            CheckStatus::Warn => write!(f, "{}", style("WARN").yellow().bold()),
+            CheckStatus::Fail => write!(f, "{}", style("FAIL").red().bold()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl CheckResult {
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        CheckResult {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Runs the environment checks and returns their results. With `json`, the results are printed
+/// as JSON and failed checks are left to the caller (the document must be the only output);
+/// otherwise they are printed as a list and a failed check is an error.
+pub async fn process_doctor(args: DoctorArgs) -> Result<Vec<CheckResult>> {
+    let mut checks = Vec::new();
+
+    // the Solana CLI config is optional when both keypair and rpc url are specified
+    let solana_config = if solana_config_path().map(|p| p.exists()).unwrap_or(false) {
+        parse_solana_config()?
+    } else {
+        None
+    };
+
+    let keypair = check_keypair(&args.keypair, &solana_config, &mut checks);
+    let rpc_url = args
+        .rpc_url
+        .clone()
+        .or_else(|| solana_config.as_ref().map(|c| c.json_rpc_url.clone()));
+    let cluster = check_rpc(&rpc_url, keypair.as_ref(), &mut checks);
+
+    check_cache(&args.cache, &mut checks);
+    let config_data = check_config(&args.config, &mut checks);
+
+    if let Some(config_data) = &config_data {
+        check_storage(config_data, cluster, &mut checks).await;
+    }
+
+    check_ardrive(&mut checks);
+
+    let failed = checks
+        .iter()
+        .filter(|c| c.status == CheckStatus::Fail)
+        .count();
+
+    if args.json {
+        report!("{}", serde_json::to_string_pretty(&checks)?);
+        return Ok(checks);
+    }
+
+    report!(
+        "{} {}Running environment checks\n",
+        style("[1/1]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    for check in &checks {
+        report!(
+            "{} {}: {}",
+            check.status,
+            style(check.name).bold(),
+            check.message
+        );
+        if let Some(hint) = &check.hint {
+            report!("     {} {}", RIGHT_ARROW_EMOJI, style(hint).dim());
+        }
+    }
+
+    if failed > 0 {
+        return Err(anyhow!("{} check(s) failed.", failed));
+    }
+
+    Ok(checks)
+}
+
+fn check_keypair(
+    keypair: &Option<String>,
+    solana_config: &Option<SolanaConfig>,
+    checks: &mut Vec<CheckResult>,
+) -> Option<Keypair> {
+    let path = match (keypair, solana_config) {
+        (Some(path), _) => path.clone(),
+        (None, Some(config)) => config.keypair_path.clone(),
+        (None, None) => shellexpand::tilde(DEFAULT_KEYPATH).to_string(),
+    };
+
+    match read_keypair_file(&path) {
+        Ok(keypair) => {
+            checks.push(CheckResult::pass(
+                "keypair",
+                format!("{} ({})", keypair.pubkey(), path),
+            ));
+            Some(keypair)
+        }
+        Err(e) => {
+            checks.push(CheckResult::fail(
+                "keypair",
+                format!("Failed to read keypair file {}: {}", path, e),
+                "Pass --keypair or run 'solana config set --keypair <path>'",
+            ));
+            None
+        }
+    }
+}
+
+fn check_rpc(
+    rpc_url: &Option<String>,
+    keypair: Option<&Keypair>,
+    checks: &mut Vec<CheckResult>,
+) -> Option<Cluster> {
+    let rpc_url = match rpc_url {
+        Some(rpc_url) => rpc_url,
+        None => {
+            checks.push(CheckResult::fail(
+                "rpc",
+                "No RPC URL found",
+                "Pass --rpc-url or run 'solana config set --url <url>'",
+            ));
+            return None;
+        }
+    };
+
+    let cluster = match get_cluster(RpcClient::new(rpc_url)) {
+        Ok(cluster) => cluster,
+        Err(e) => {
+            checks.push(CheckResult::fail(
+                "rpc",
+                format!("RPC {} unreachable: {}", rpc_url, e),
+                "Check the RPC URL and your network connection",
+            ));
+            return None;
+        }
+    };
+
+    let cluster = if rpc_url.ends_with("8899") {
+        Cluster::Localnet
+    } else {
+        cluster
+    };
+
+    if matches!(cluster, Cluster::Unknown) {
+        checks.push(CheckResult::warn(
+            "rpc",
+            format!("{} is reachable but the cluster is unknown", rpc_url),
+            "Only devnet, mainnet-beta and localnet are fully supported",
+        ));
+    } else {
+        checks.push(CheckResult::pass(
+            "rpc",
+            format!("{} ({})", rpc_url, cluster),
+        ));
+    }
+
+    if let Some(keypair) = keypair {
+        match RpcClient::new(rpc_url).get_balance(&keypair.pubkey()) {
+            Ok(balance) if balance < LOW_BALANCE => checks.push(CheckResult::warn(
+                "balance",
+                format!("{:.4} SOL", balance as f64 / LAMPORTS_PER_SOL as f64),
+                "The balance is low, deploying and minting might fail",
+            )),
+            Ok(balance) => checks.push(CheckResult::pass(
+                "balance",
+                format!("{:.4} SOL", balance as f64 / LAMPORTS_PER_SOL as f64),
+            )),
+            Err(e) => checks.push(CheckResult::fail(
+                "balance",
+                format!("Failed to fetch balance: {}", e),
+                "Check the RPC URL and your network connection",
+            )),
+        }
+    }
+
+    Some(cluster)
+}
+
+fn check_cache(cache: &str, checks: &mut Vec<CheckResult>) {
+    if !Path::new(cache).exists() {
+        checks.push(CheckResult::warn(
+            "cache",
+            format!("Cache file '{}' not found", cache),
+            "Run 'sugar upload' to create the cache file",
+        ));
+        return;
+    }
+
+    match load_cache(cache, false) {
+        Ok(cache_data) => {
+            let incomplete = cache_data
+                .items
+                .values()
+                .filter(|item| item.name.is_empty() || item.metadata_link.is_empty())
+                .count();
+
+            if incomplete > 0 {
+                checks.push(CheckResult::warn(
+                    "cache",
+                    format!("{} item(s) missing a name or metadata link", incomplete),
+                    "Run 'sugar upload' again to complete the cache",
+                ));
+            } else {
+                checks.push(CheckResult::pass(
+                    "cache",
+                    format!("{} item(s)", cache_data.items.len()),
+                ));
+            }
+        }
+        Err(e) => checks.push(CheckResult::fail(
+            "cache",
+            format!("Failed to parse cache file: {}", e),
+            "Fix or delete the cache file and run 'sugar upload' again",
+        )),
+    }
+}
+
+fn check_config(config: &str, checks: &mut Vec<CheckResult>) -> Option<ConfigData> {
+    match get_config_data(config) {
+        Ok(config_data) => {
+            checks.push(CheckResult::pass(
+                "config",
+                format!(
+                    "{} item(s), upload method {}",
+                    config_data.number, config_data.upload_method
+                ),
+            ));
+            Some(config_data)
+        }
+        Err(e) => {
+            checks.push(CheckResult::fail(
+                "config",
+                format!("Failed to parse config file '{}': {}", config, e),
+                "Run 'sugar config create' to create a config file",
+            ));
+            None
+        }
+    }
+}
+
+async fn check_storage(
+    config_data: &ConfigData,
+    cluster: Option<Cluster>,
+    checks: &mut Vec<CheckResult>,
+) {
+    let missing = |name: &str| {
+        CheckResult::fail(
+            "storage",
+            format!("Missing {} in config file", name),
+            format!(
+                "Add '{}' to the config file or change the upload method",
+                name
+            ),
+        )
+    };
+
+    let result = match config_data.upload_method {
+        UploadMethod::Bundlr => {
+            let node = match (&config_data.bundlr_node, cluster) {
+                (Some(node), _) => node.url(),
+                (None, Some(Cluster::Mainnet)) => BUNDLR_MAINNET,
+                (None, _) => BUNDLR_DEVNET,
+            };
+
+            match HttpClient::new().get(format!("{}/info", node)).send().await {
+                Ok(response) if response.status().is_success() => {
+                    CheckResult::pass("storage", format!("Bundlr node {} reachable", node))
+                }
+                Ok(response) => CheckResult::fail(
+                    "storage",
+                    format!("Bundlr node {} returned {}", node, response.status()),
+                    "The Bundlr node might be down, try again later",
+                ),
+                Err(e) => CheckResult::fail(
+                    "storage",
+                    format!("Bundlr node {} unreachable: {}", node, e),
+                    "Check your network connection",
+                ),
+            }
+        }
+        UploadMethod::AWS => match &config_data.aws_config {
+            Some(aws_config) => {
+                let from_env = std::env::var("AWS_ACCESS_KEY_ID").is_ok();
+                let credentials =
+                    dirs::home_dir().map(|home| home.join(".aws").join("credentials"));
+                let has_profile = credentials
+                    .and_then(|path| std::fs::read_to_string(path).ok())
+                    .map(|contents| contents.contains(&format!("[{}]", aws_config.profile)))
+                    .unwrap_or(false);
+
+                if from_env {
+                    CheckResult::pass("storage", format!("S3 bucket '{}'", aws_config.bucket))
+                } else if has_profile {
+                    CheckResult::pass("storage", format!("AWS profile '{}'", aws_config.profile))
+                } else {
+                    CheckResult::fail(
+                        "storage",
+                        format!(
+                            "AWS profile '{}' not found in ~/.aws/credentials",
+                            aws_config.profile
+                        ),
+                        "Set AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY or run 'aws configure \
+                        --profile <profile>'",
+                    )
+                }
+            }
+            None => missing("awsConfig"),
+        },
+        UploadMethod::NftStorage => match &config_data.nft_storage_auth_token {
+            Some(_) => CheckResult::pass("storage", "NFT.Storage auth token present"),
+            None => missing("nftStorageAuthToken"),
+        },
+        UploadMethod::SHDW => match &config_data.shdw_storage_account {
+            Some(account) => CheckResult::pass("storage", format!("Shadow Drive {}", account)),
+            None => missing("shdwStorageAccount"),
+        },
+        UploadMethod::Pinata => match &config_data.pinata_config {
+            Some(pinata_config) => {
+                if pinata_config.jwt.is_some() || std::env::var("PINATA_JWT").is_ok() {
+                    CheckResult::pass("storage", "Pinata config present")
+                } else {
+                    CheckResult::fail(
+                        "storage",
+                        "No Pinata JWT found",
+                        "Set 'pinataConfig.jwt' in the config file or PINATA_JWT",
+                    )
+                }
+            }
+            None => missing("pinataConfig"),
+        },
+        UploadMethod::Sdrive => match &config_data.sdrive_api_key {
+            Some(_) => CheckResult::pass("storage", "SDrive API key present"),
+            None => missing("sdriveApiKey"),
+        },
+        UploadMethod::HttpPut => match &config_data.http_put_config {
+            Some(config) => CheckResult::pass("storage", format!("HTTP PUT to {}", config.url)),
+            None => missing("httpPutConfig"),
+        },
+        #[cfg(feature = "integration-tests")]
+        UploadMethod::Mock => CheckResult::pass("storage", "Mock storage"),
+    };
+
+    checks.push(result);
+}
+
+fn check_ardrive(checks: &mut Vec<CheckResult>) {
+    // the ArDrive wallet is only needed by the 'ardrive' commands (default profile)
+    match resolve_ardrive_wallet_content(&ArdriveOptions::default(), None) {
+        Ok(_) => checks.push(CheckResult::pass("ardrive", "ArDrive wallet resolved")),
+        Err(_) => checks.push(CheckResult::warn(
+            "ardrive",
+            "No ArDrive wallet found",
+            "Only needed for 'sugar ardrive': run 'sugar ardrive set-wallet <file>'",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_client::solana_sdk::signature::write_keypair_file;
+
+    use super::*;
+    use crate::{config::BundlrNode, upload::methods::test_utils::serve};
+
+    fn statuses(checks: &[CheckResult]) -> Vec<(&'static str, CheckStatus)> {
+        checks.iter().map(|c| (c.name, c.status)).collect()
+    }
+
+    fn cache_item(name: &str, metadata_link: &str) -> CacheItem {
+        CacheItem {
+            name: name.to_string(),
+            image_hash: String::new(),
+            image_link: String::new(),
+            metadata_hash: String::new(),
+            metadata_link: metadata_link.to_string(),
+            on_chain: false,
+            animation_hash: None,
+            animation_link: None,
+            optimized_image_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_check_keypair() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keypair.json");
+        let keypair = Keypair::new();
+        write_keypair_file(&keypair, &path).unwrap();
+
+        let mut checks = Vec::new();
+        let found = check_keypair(
+            &Some(path.to_string_lossy().to_string()),
+            &None,
+            &mut checks,
+        );
+        assert_eq!(found.unwrap().pubkey(), keypair.pubkey());
+
+        // the keypair of the Solana config, when none is specified
+        let solana_config = SolanaConfig {
+            json_rpc_url: String::new(),
+            keypair_path: dir
+                .path()
+                .join("missing.json")
+                .to_string_lossy()
+                .to_string(),
+            commitment: String::new(),
+        };
+        assert!(check_keypair(&None, &Some(solana_config), &mut checks).is_none());
+
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("keypair", CheckStatus::Pass),
+                ("keypair", CheckStatus::Fail)
+            ]
+        );
+        assert!(checks[1].message.contains("missing.json"));
+    }
+
+    #[test]
+    fn test_check_rpc() {
+        let mut checks = Vec::new();
+        assert!(check_rpc(&None, None, &mut checks).is_none());
+
+        let (rpc_url, _) = serve(|request| {
+            let body = String::from_utf8_lossy(&request.body);
+            let result = if body.contains("getGenesisHash") {
+                format!("\"{}\"", DEVNET_HASH)
+            } else {
+                r#"{"context": {"slot": 1}, "value": 1000}"#.to_string()
+            };
+            (
+                200,
+                format!(r#"{{"jsonrpc": "2.0", "result": {}, "id": 1}}"#, result),
+            )
+        });
+        let keypair = Keypair::new();
+        let cluster = check_rpc(&Some(rpc_url), Some(&keypair), &mut checks);
+        assert!(matches!(cluster, Some(Cluster::Devnet)));
+
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("rpc", CheckStatus::Fail),
+                ("rpc", CheckStatus::Pass),
+                // 1000 lamports is below the low balance
+                ("balance", CheckStatus::Warn),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let cache_file = path.to_str().unwrap();
+        let mut checks = Vec::new();
+
+        check_cache(cache_file, &mut checks);
+
+        let mut cache = Cache::new();
+        cache.items.insert(
+            "0".to_string(),
+            cache_item("0", "https://example.com/0.json"),
+        );
+        cache.write_to_file(&path).unwrap();
+        check_cache(cache_file, &mut checks);
+
+        cache.items.insert("1".to_string(), cache_item("1", ""));
+        cache.write_to_file(&path).unwrap();
+        check_cache(cache_file, &mut checks);
+
+        std::fs::write(&path, "{").unwrap();
+        check_cache(cache_file, &mut checks);
+
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("cache", CheckStatus::Warn),
+                ("cache", CheckStatus::Pass),
+                ("cache", CheckStatus::Warn),
+                ("cache", CheckStatus::Fail),
+            ]
+        );
+        assert_eq!(checks[1].message, "1 item(s)");
+        assert_eq!(
+            checks[2].message,
+            "1 item(s) missing a name or metadata link"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_storage() {
+        let mut checks = Vec::new();
+
+        let config_data = ConfigData {
+            upload_method: UploadMethod::NftStorage,
+            ..Default::default()
+        };
+        check_storage(&config_data, None, &mut checks).await;
+        assert!(checks[0].message.contains("nftStorageAuthToken"));
+
+        let config_data = ConfigData {
+            upload_method: UploadMethod::NftStorage,
+            nft_storage_auth_token: Some("token".to_string()),
+            ..Default::default()
+        };
+        check_storage(&config_data, None, &mut checks).await;
+
+        // the Bundlr node is queried, a node that is down fails the check
+        let (node, requests) = serve(|request| {
+            if request.path == "/up/info" {
+                (200, "{}".to_string())
+            } else {
+                (503, String::new())
+            }
+        });
+        for path in ["up", "down"] {
+            let config_data = ConfigData {
+                upload_method: UploadMethod::Bundlr,
+                bundlr_node: Some(BundlrNode::Url(format!("{}/{}", node, path))),
+                ..Default::default()
+            };
+            check_storage(&config_data, Some(Cluster::Devnet), &mut checks).await;
+        }
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        assert_eq!(
+            statuses(&checks),
+            vec![
+                ("storage", CheckStatus::Fail),
+                ("storage", CheckStatus::Pass),
+                ("storage", CheckStatus::Pass),
+                ("storage", CheckStatus::Fail),
+            ]
+        );
+    }
+}
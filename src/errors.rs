@@ -30,6 +30,15 @@ pub enum CacheError {
 
     #[error("Invalid cache state found.")]
     InvalidState,
+
+    #[error("Cache was created on {0} but the RPC is connected to {1}. Use --allow-cluster-mismatch to override.")]
+    ClusterMismatch(String, String),
+
+    #[error("Chunk {0} is not in the cache. Run `sugar deploy --chunk {0} --chunk-size <N>` to deploy it.")]
+    ChunkNotFound(u32),
+
+    #[error("Invalid chunk {0}: {1}")]
+    InvalidChunk(u32, String),
 }
 
 #[derive(Debug, Error)]
@@ -0,0 +1,3 @@
+pub mod rent;
+
+pub use rent::*;
@@ -0,0 +1,250 @@
+use anchor_client::solana_sdk::{native_token::LAMPORTS_PER_SOL, rent::Rent};
+use mpl_candy_guard::state::DATA_OFFSET;
+use mpl_candy_machine_core::{CandyMachineData, ConfigLineSettings, HiddenSettings};
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    common::*,
+    config::{get_config_data, CandyGuardData},
+    deploy::{MAX_NAME_LENGTH, MAX_URI_LENGTH},
+    output::style,
+    parse::{parse_solana_config, solana_config_path},
+    utils::*,
+};
+
+/// Default name length used by the estimate.
+pub const DEFAULT_NAME_LENGTH: u32 = MAX_NAME_LENGTH as u32;
+
+/// Default uri length used by the estimate.
+pub const DEFAULT_URI_LENGTH: u32 = MAX_URI_LENGTH as u32;
+
+pub struct EstimateRentArgs {
+    pub rpc_url: Option<String>,
+    pub config: String,
+    pub number: u64,
+    pub name_length: u32,
+    pub uri_length: u32,
+    pub hidden_settings: bool,
+}
+
+/// Account sizes (in bytes) and rent-exempt minimums (in lamports) of a deployment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RentEstimate {
+    pub candy_machine_size: usize,
+    pub candy_machine_rent: u64,
+    pub candy_guard: Option<(usize, u64)>,
+}
+
+pub fn process_estimate_rent(args: EstimateRentArgs) -> Result<()> {
+    report!(
+        "{} {}Estimating rent",
+        style("[1/1]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    // the guards are only included when a config file is present
+    let guards = if Path::new(&args.config).exists() {
+        get_config_data(&args.config)?.guards
+    } else {
+        None
+    };
+
+    let rpc_url = match args.rpc_url {
+        Some(rpc_url) => Some(rpc_url),
+        None if solana_config_path().map(|p| p.exists()).unwrap_or(false) => {
+            parse_solana_config()?.map(|config| config.json_rpc_url)
+        }
+        None => None,
+    };
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let rpc = rpc_url.map(RpcClient::new);
+    let data = estimate_candy_machine_data(
+        args.number,
+        args.name_length,
+        args.uri_length,
+        args.hidden_settings,
+    );
+    let estimate = estimate_rent(rpc.as_ref(), &data, guards.as_ref())?;
+
+    pb.finish_and_clear();
+
+    print_rent_estimate(&estimate);
+
+    Ok(())
+}
+
+/// Returns a candy machine data with the layout of `number` items.
+pub fn estimate_candy_machine_data(
+    number: u64,
+    name_length: u32,
+    uri_length: u32,
+    hidden_settings: bool,
+) -> CandyMachineData {
+    let (config_line_settings, hidden_settings) = if hidden_settings {
+        (
+            None,
+            Some(HiddenSettings {
+                name: String::new(),
+                uri: String::new(),
+                hash: [0; 32],
+            }),
+        )
+    } else {
+        (
+            Some(ConfigLineSettings {
+                prefix_name: String::new(),
+                name_length,
+                prefix_uri: String::new(),
+                uri_length,
+                is_sequential: false,
+            }),
+            None,
+        )
+    };
+
+    CandyMachineData {
+        items_available: number,
+        symbol: String::new(),
+        seller_fee_basis_points: 0,
+        max_supply: 0,
+        is_mutable: true,
+        creators: Vec::new(),
+        config_line_settings,
+        hidden_settings,
+    }
+}
+
+/// Computes the account sizes and rent of the candy machine (and candy guard) accounts.
+///
+/// The rent-exempt minimum is retrieved from the RPC when available, falling back to the
+/// default rent otherwise. This is shared by 'estimate rent' and 'deploy --dry-run'.
+pub fn estimate_rent(
+    rpc: Option<&RpcClient>,
+    data: &CandyMachineData,
+    guards: Option<&CandyGuardData>,
+) -> Result<RentEstimate> {
+    let candy_machine_size = data.get_space_for_candy()?;
+
+    let candy_guard = if let Some(guards) = guards {
+        let size = DATA_OFFSET + guards.to_guard_format()?.size();
+        Some((size, get_rent_exemption(rpc, size)))
+    } else {
+        None
+    };
+
+    Ok(RentEstimate {
+        candy_machine_size,
+        candy_machine_rent: get_rent_exemption(rpc, candy_machine_size),
+        candy_guard,
+    })
+}
+
+/// Returns the rent-exempt minimum (in lamports) for an account of `size` bytes.
+pub fn get_rent_exemption(rpc: Option<&RpcClient>, size: usize) -> u64 {
+    if let Some(rpc) = rpc {
+        match rpc.get_minimum_balance_for_rent_exemption(size) {
+            Ok(lamports) => return lamports,
+            Err(err) => warn!(
+                "Failed to retrieve rent from the RPC, using default: {}",
+                err
+            ),
+        }
+    }
+
+    Rent::default().minimum_balance(size)
+}
+
+pub fn print_rent_estimate(estimate: &RentEstimate) {
+    let sol = |lamports: u64| lamports as f64 / LAMPORTS_PER_SOL as f64;
+
+    report!(
+        "\n{} {} bytes",
+        style("Candy machine account size:").bold(),
+        estimate.candy_machine_size
+    );
+    report!(
+        "{} {} SOL",
+        style("Candy machine rent:").bold(),
+        sol(estimate.candy_machine_rent)
+    );
+
+    let mut total = estimate.candy_machine_rent;
+
+    if let Some((size, rent)) = estimate.candy_guard {
+        report!(
+            "{} {} bytes",
+            style("Candy guard account size:").bold(),
+            size
+        );
+        report!("{} {} SOL", style("Candy guard rent:").bold(), sol(rent));
+        total += rent;
+    }
+
+    report!("{} {} SOL", style("Total rent:").bold(), sol(total));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::ConfigData, deploy::create_candy_machine_data};
+
+    fn test_cache(number: u64, name_length: usize, uri_length: usize) -> Cache {
+        let mut cache = Cache::new();
+
+        for i in 0..number {
+            // alternating first characters so that there is no common prefix
+            let first = if i % 2 == 0 { 'a' } else { 'b' };
+            cache.items.insert(
+                i.to_string(),
+                CacheItem {
+                    name: format!("{}{:0>width$}", first, i, width = name_length - 1),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: format!("{}{:0>width$}", first, i, width = uri_length - 1),
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+        }
+
+        cache
+    }
+
+    #[test]
+    fn estimate_matches_deploy_dry_run() {
+        let config = ConfigData {
+            number: 100,
+            creators: vec![crate::config::Creator {
+                address: Pubkey::new_unique(),
+                share: 100,
+            }],
+            ..ConfigData::default()
+        };
+        let cache = test_cache(config.number, 32, 200);
+
+        let deployed = create_candy_machine_data(&config, &cache).unwrap();
+        let estimated = estimate_candy_machine_data(config.number, 32, 200, false);
+
+        assert_eq!(
+            estimate_rent(None, &deployed, None).unwrap(),
+            estimate_rent(None, &estimated, None).unwrap()
+        );
+    }
+
+    #[test]
+    fn hidden_settings_size_does_not_depend_on_items() {
+        let small = estimate_candy_machine_data(10, 32, 200, true);
+        let large = estimate_candy_machine_data(10_000, 32, 200, true);
+
+        assert_eq!(
+            small.get_space_for_candy().unwrap(),
+            large.get_space_for_candy().unwrap()
+        );
+    }
+}
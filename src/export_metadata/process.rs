@@ -0,0 +1,479 @@
+use std::{
+    collections::HashSet,
+    ffi::OsStr,
+    fs,
+    sync::{Arc, Mutex},
+};
+
+use futures::future::join_all;
+use reqwest::StatusCode;
+use tokio::sync::Semaphore;
+
+use crate::{
+    cache::load_cache,
+    common::*,
+    output::style,
+    upload::{encode, ANIMATION_CONTENT_TYPES, IMAGE_CONTENT_TYPES},
+    utils::*,
+};
+
+/// Default number of concurrent downloads.
+pub const DEFAULT_EXPORT_PARALLEL: usize = 10;
+
+// Maximum number of times to retry each individual download.
+const MAX_RETRY: u8 = 3;
+
+// Extensions of the image types that cannot be uploaded but can be linked by the metadata.
+const OTHER_CONTENT_TYPES: [(&str, &str); 3] = [
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("avif", "image/avif"),
+];
+
+pub struct ExportMetadataArgs {
+    pub cache: String,
+    pub output: String,
+    pub with_images: bool,
+    pub parallel: usize,
+}
+
+#[derive(Default)]
+struct ExportSummary {
+    fetched: usize,
+    skipped: usize,
+    failed: Vec<String>,
+    mismatched: Vec<String>,
+}
+
+pub async fn process_export_metadata(args: ExportMetadataArgs) -> Result<()> {
+    report!(
+        "{} {}Loading cache",
+        style("[1/2]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let cache = load_cache(&args.cache, false)?;
+
+    if cache.items.is_empty() {
+        return Err(anyhow!("Cache file '{}' has no items.", args.cache));
+    }
+
+    let output = PathBuf::from(&args.output);
+    fs::create_dir_all(&output)
+        .map_err(|e| anyhow!("Failed to create output directory '{}': {}", args.output, e))?;
+
+    report!("{} {}", style("Items:").bold(), cache.items.len());
+
+    report!(
+        "\n{} {}Downloading metadata{}",
+        style("[2/2]").bold().dim(),
+        ASSETS_EMOJI,
+        if args.with_images { " and images" } else { "" }
+    );
+
+    // the files of an interrupted export are kept
+    let exported = Arc::new(read_exported(&output)?);
+    let http_client = HttpClient::new();
+    let sem = Arc::new(Semaphore::new(args.parallel.max(1)));
+    let summary = Arc::new(Mutex::new(ExportSummary::default()));
+
+    let pb = progress_bar_with_style(cache.items.len() as u64);
+    let mut tasks = Vec::new();
+
+    for (key, item) in cache.items.iter() {
+        let name = if key == "-1" {
+            "collection".to_string()
+        } else {
+            key.clone()
+        };
+        let item = item.clone();
+        let output = output.clone();
+        let exported = exported.clone();
+        let http_client = http_client.clone();
+        let sem = sem.clone();
+        let summary = summary.clone();
+        let pb = pb.clone();
+        let with_images = args.with_images;
+
+        tasks.push(async move {
+            // the permit limits the number of concurrent requests
+            let _permit = sem.acquire_owned().await.unwrap();
+            let result =
+                export_item(&http_client, &output, &exported, &name, &item, with_images).await;
+            let mut summary = summary.lock().unwrap();
+
+            match result {
+                Ok(ItemExport::Fetched { image_matches }) => {
+                    summary.fetched += 1;
+                    if !image_matches {
+                        summary.mismatched.push(name);
+                    }
+                }
+                Ok(ItemExport::Skipped) => summary.skipped += 1,
+                Err(err) => {
+                    error!("Failed to export item {}: {:?}", name, err);
+                    summary.failed.push(format!("{}: {}", name, err));
+                }
+            }
+
+            pb.inc(1);
+        });
+    }
+
+    join_all(tasks).await;
+    pb.finish();
+
+    let summary = summary.lock().unwrap();
+
+    report!("\n{} {}", style("Fetched:").bold(), summary.fetched);
+    report!(
+        "{} {}",
+        style("Skipped (already exported):").bold(),
+        summary.skipped
+    );
+
+    if !summary.mismatched.is_empty() {
+        report!(
+            "{}{} {}",
+            WARNING_EMOJI,
+            style("Image hash mismatches:").yellow().bold(),
+            summary.mismatched.join(", ")
+        );
+    }
+
+    if !summary.failed.is_empty() {
+        report!(
+            "{}{} {}",
+            WARNING_EMOJI,
+            style("Failed:").red().bold(),
+            summary.failed.len()
+        );
+        for failure in &summary.failed {
+            report!("  {}", failure);
+        }
+        return Err(anyhow!(
+            "Failed to export {} item(s), re-run the command to retry.",
+            summary.failed.len()
+        ));
+    }
+
+    Ok(())
+}
+
+enum ItemExport {
+    Fetched { image_matches: bool },
+    Skipped,
+}
+
+/// Files of the output directory when the export started.
+#[derive(Debug, Default)]
+struct ExportedFiles {
+    /// Names of the items with a metadata file.
+    metadata: HashSet<String>,
+    /// Image files by item name.
+    images: HashMap<String, PathBuf>,
+}
+
+/// Reads the output directory once, ignoring the partial files of an interrupted export.
+fn read_exported(output: &Path) -> Result<ExportedFiles> {
+    let mut exported = ExportedFiles::default();
+
+    for entry in fs::read_dir(output)? {
+        let path = entry?.path();
+        let (stem, extension) = match (
+            path.file_stem().and_then(OsStr::to_str),
+            path.extension().and_then(OsStr::to_str),
+        ) {
+            (Some(stem), Some(extension)) if !stem.starts_with('.') => (stem, extension),
+            _ => continue,
+        };
+
+        if extension == "json" {
+            exported.metadata.insert(stem.to_string());
+        } else {
+            exported.images.insert(stem.to_string(), path.clone());
+        }
+    }
+
+    Ok(exported)
+}
+
+async fn export_item(
+    http_client: &HttpClient,
+    output: &Path,
+    exported: &ExportedFiles,
+    name: &str,
+    item: &CacheItem,
+    with_images: bool,
+) -> Result<ItemExport> {
+    if item.metadata_link.is_empty() {
+        return Err(anyhow!("missing metadata link"));
+    }
+
+    let metadata_exported = exported.metadata.contains(name);
+    let image_path = exported.images.get(name).cloned();
+
+    // existing files are kept so the command can be resumed
+    if metadata_exported && (!with_images || image_path.is_some()) {
+        return Ok(ItemExport::Skipped);
+    }
+
+    if !metadata_exported {
+        // the metadata hash in the cache is the one of the local file before the
+        // upload rewrote its links, so the downloaded metadata is not compared
+        let (metadata, _) = download(http_client, &item.metadata_link).await?;
+        write_file(&output.join(format!("{name}.json")), &metadata)?;
+    }
+
+    let mut image_matches = true;
+
+    if with_images {
+        let image_path = match image_path {
+            Some(image_path) => image_path,
+            None => {
+                if item.image_link.is_empty() {
+                    return Err(anyhow!("missing image link"));
+                }
+
+                let (image, content_type) = download(http_client, &item.image_link).await?;
+                let extension = file_extension(content_type.as_deref(), &item.image_link)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "unknown file type of {} ({})",
+                            item.image_link,
+                            content_type.as_deref().unwrap_or("no content type")
+                        )
+                    })?;
+                let image_path = output.join(format!("{name}.{extension}"));
+                write_file(&image_path, &image)?;
+                image_path
+            }
+        };
+
+        if !item.image_hash.is_empty() {
+            image_matches = encode(&path_to_string(&image_path)?)? == item.image_hash;
+        }
+    }
+
+    Ok(ItemExport::Fetched { image_matches })
+}
+
+/// Writes the file to a partial file renamed once complete, so that an interrupted export
+/// does not leave a truncated file that would be skipped when the export is resumed.
+fn write_file(path: &Path, data: &[u8]) -> Result<()> {
+    let name = path
+        .file_name()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| anyhow!("Invalid file name {}", path.display()))?;
+    let partial = path.with_file_name(format!(".{name}.part"));
+
+    fs::write(&partial, data)
+        .map_err(|e| anyhow!("Failed to write '{}': {}", partial.display(), e))?;
+    fs::rename(&partial, path).map_err(|e| anyhow!("Failed to write '{}': {}", path.display(), e))
+}
+
+/// Extension of a downloaded file: from its content type, or from the extension of its URL
+/// (or of the `ext` query parameter of the Arweave links) when the content type is not a
+/// known media type.
+fn file_extension(content_type: Option<&str>, url: &str) -> Option<String> {
+    let mime = content_type
+        .and_then(|value| value.split(';').next())
+        .map(|value| value.trim().to_lowercase());
+
+    let known = IMAGE_CONTENT_TYPES
+        .iter()
+        .chain(ANIMATION_CONTENT_TYPES.iter())
+        .chain(OTHER_CONTENT_TYPES.iter())
+        .find(|(_, content_type)| mime.as_deref() == Some(*content_type))
+        .map(|(extension, _)| extension.to_string());
+
+    known.or_else(|| {
+        let url = url::Url::parse(url).ok()?;
+        let extension = url
+            .query_pairs()
+            .find(|(key, _)| key == "ext")
+            .map(|(_, value)| value.to_string())
+            .or_else(|| {
+                Path::new(url.path())
+                    .extension()
+                    .and_then(OsStr::to_str)
+                    .map(String::from)
+            })?;
+
+        (!extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric()))
+            .then(|| extension.to_lowercase())
+    })
+}
+
+/// Download the content of `url`, returning the bytes and the content type. Client errors
+/// (other than throttling) are not retried.
+async fn download(http_client: &HttpClient, url: &str) -> Result<(Vec<u8>, Option<String>)> {
+    let mut last_error = anyhow!("no attempts made");
+
+    for attempt in 0..MAX_RETRY {
+        if attempt > 0 {
+            // backs off before retrying (also respects rate limits)
+            tokio::time::sleep(std::time::Duration::from_secs(2u64.pow(attempt as u32))).await;
+        }
+
+        let response = match http_client.get(url).send().await {
+            Ok(response) => response,
+            Err(err) => {
+                last_error = anyhow!("request to {} failed: {}", url, err);
+                continue;
+            }
+        };
+
+        let status = response.status();
+        if !status.is_success() {
+            last_error = anyhow!("request to {} returned {}", url, status);
+
+            // a missing file will not be found on retry
+            if status.is_client_error()
+                && status != StatusCode::TOO_MANY_REQUESTS
+                && status != StatusCode::REQUEST_TIMEOUT
+            {
+                break;
+            }
+            continue;
+        }
+
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from);
+
+        return Ok((response.bytes().await?.to_vec(), content_type));
+    }
+
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::methods::test_utils::serve;
+
+    fn item(base_url: &str, image_hash: &str) -> CacheItem {
+        CacheItem {
+            name: "Item 0".to_string(),
+            image_hash: image_hash.to_string(),
+            image_link: format!("{base_url}/image.png"),
+            metadata_hash: String::new(),
+            metadata_link: format!("{base_url}/metadata"),
+            on_chain: true,
+            animation_hash: None,
+            animation_link: None,
+            optimized_image_hash: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_skip_and_resume() {
+        let (base_url, requests) = serve(|_| (200, "image".to_string()));
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path();
+        let http_client = HttpClient::new();
+
+        // the metadata was exported, the image download was interrupted
+        fs::write(output.join("0.json"), "{}").unwrap();
+        fs::write(output.join(".0.png.part"), "ima").unwrap();
+        fs::write(output.join("1.json"), "{}").unwrap();
+        fs::write(output.join("1.jpg"), "image").unwrap();
+
+        let exported = read_exported(output).unwrap();
+        assert_eq!(exported.metadata.len(), 2);
+        assert_eq!(exported.images.keys().collect::<Vec<_>>(), vec!["1"]);
+
+        let result = export_item(
+            &http_client,
+            output,
+            &exported,
+            "1",
+            &item(&base_url, ""),
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(result, ItemExport::Skipped));
+        assert!(requests.lock().unwrap().is_empty());
+
+        let image = output.join("1.jpg");
+        let image_hash = encode(image.to_str().unwrap()).unwrap();
+        let result = export_item(
+            &http_client,
+            output,
+            &exported,
+            "0",
+            &item(&base_url, &image_hash),
+            true,
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            result,
+            ItemExport::Fetched {
+                image_matches: true
+            }
+        ));
+
+        // only the image is downloaded, without leaving the partial file
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/image.png");
+        assert_eq!(fs::read(output.join("0.png")).unwrap(), b"image");
+        assert!(!output.join(".0.png.part").exists());
+        assert_eq!(fs::read(output.join("0.json")).unwrap(), b"{}");
+    }
+
+    #[tokio::test]
+    async fn test_missing_file_is_not_retried() {
+        let (base_url, requests) = serve(|_| (404, String::new()));
+        let dir = tempfile::tempdir().unwrap();
+
+        let error = export_item(
+            &HttpClient::new(),
+            dir.path(),
+            &ExportedFiles::default(),
+            "0",
+            &item(&base_url, ""),
+            false,
+        )
+        .await
+        .err()
+        .unwrap();
+
+        assert!(error.to_string().contains("404"), "{error}");
+        assert_eq!(requests.lock().unwrap().len(), 1);
+        assert!(!dir.path().join("0.json").exists());
+    }
+
+    #[test]
+    fn test_file_extension() {
+        let url = "https://arweave.net/abc";
+        assert_eq!(file_extension(Some("image/jpeg"), url).unwrap(), "jpg");
+        assert_eq!(
+            file_extension(Some("image/png; charset=binary"), url).unwrap(),
+            "png"
+        );
+        assert_eq!(file_extension(Some("video/mp4"), url).unwrap(), "mp4");
+        assert_eq!(file_extension(Some("image/svg+xml"), url).unwrap(), "svg");
+
+        // the extension of the URL when the content type is not a media type
+        assert_eq!(
+            file_extension(
+                Some("application/octet-stream"),
+                "https://gateway.irys.xyz/abc?ext=gif"
+            )
+            .unwrap(),
+            "gif"
+        );
+        assert_eq!(
+            file_extension(None, "https://cdn.example.com/0.WEBP?v=2").unwrap(),
+            "webp"
+        );
+        assert_eq!(file_extension(Some("application/octet-stream"), url), None);
+        assert_eq!(file_extension(None, url), None);
+    }
+}
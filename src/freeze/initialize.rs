@@ -20,6 +20,8 @@ pub struct InitializeArgs {
     pub label: Option<String>,
     pub period: u64,
     pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_initialize(args: InitializeArgs) -> Result<()> {
@@ -27,31 +29,29 @@ pub fn process_initialize(args: InitializeArgs) -> Result<()> {
     let client = setup_client(&sugar_config)?;
     let program = client.program(mpl_candy_guard::ID);
 
-    // candy guard id specified takes precedence over the one from the cache
-    let candy_guard_id = match args.candy_guard {
-        Some(ref candy_guard_id) => candy_guard_id.to_owned(),
-        None => {
-            let cache = load_cache(&args.cache, false)?;
-            cache.program.candy_guard
-        }
-    };
-
     // candy machine id specified takes precedence over the one from the cache
     let candy_machine_id = match args.candy_machine {
         Some(ref candy_machine_id) => candy_machine_id.to_owned(),
         None => {
-            let cache = load_cache(&args.cache, false)?;
+            let mut cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
             cache.program.candy_machine
         }
     };
 
-    let candy_guard = Pubkey::from_str(&candy_guard_id)
-        .map_err(|_| anyhow!("Failed to parse candy guard id: {}", &candy_guard_id))?;
-
     let candy_machine = Pubkey::from_str(&candy_machine_id)
-        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", &candy_guard_id))?;
+        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", &candy_machine_id))?;
 
-    println!(
+    // candy guard id specified takes precedence over the candy machine's
+    let candy_guard = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
+
+    report!(
         "{} {}Loading freeze guard information",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
@@ -70,7 +70,7 @@ pub fn process_initialize(args: InitializeArgs) -> Result<()> {
 
     pb.finish_with_message("Done");
 
-    println!(
+    report!(
         "\n{} {}Initializing freeze escrow",
         style("[2/2]").bold().dim(),
         MONEY_BAG_EMOJI
@@ -81,6 +81,7 @@ pub fn process_initialize(args: InitializeArgs) -> Result<()> {
 
     let signature = initialize(
         &program,
+        &sugar_config.keypair,
         &candy_guard,
         &candy_machine,
         &destination_address,
@@ -88,6 +89,7 @@ pub fn process_initialize(args: InitializeArgs) -> Result<()> {
         args.period,
         mint,
         args.priority_fee,
+        &args.compute_budget,
     )?;
 
     pb.finish_with_message(format!("{} {}", style("Signature:").bold(), signature));
@@ -97,6 +99,7 @@ pub fn process_initialize(args: InitializeArgs) -> Result<()> {
 
 pub fn initialize<C: Deref<Target = impl Signer> + Clone>(
     program: &Program<C>,
+    keypair: &Keypair,
     candy_guard_id: &Pubkey,
     candy_machine_id: &Pubkey,
     destination: &Pubkey,
@@ -104,6 +107,7 @@ pub fn initialize<C: Deref<Target = impl Signer> + Clone>(
     period: u64,
     mint: Option<Pubkey>,
     priority_fee: u64,
+    compute_budget: &ComputeBudget,
 ) -> Result<Signature> {
     let mut remaining_accounts = Vec::with_capacity(4);
     let (freeze_pda, _) = find_freeze_pda(candy_guard_id, candy_machine_id, destination);
@@ -160,7 +164,7 @@ pub fn initialize<C: Deref<Target = impl Signer> + Clone>(
 
     let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
 
-    let builder = program
+    let instructions = program
         .request()
         .instruction(priority_fee_ix)
         .accounts(RouteAccount {
@@ -175,8 +179,9 @@ pub fn initialize<C: Deref<Target = impl Signer> + Clone>(
                 guard: freeze_guard,
             },
             label: label.to_owned(),
-        });
-    let sig = builder.send()?;
+        })
+        .instructions()?;
+    let sig = send_transaction(&program.rpc(), keypair, &instructions, &[], compute_budget)?;
 
     Ok(sig)
 }
@@ -5,7 +5,6 @@ use std::{
 
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anyhow::Result;
-use console::style;
 use mpl_candy_guard::{
     guards::FreezeEscrow,
     state::{CandyGuardData, DATA_OFFSET},
@@ -20,7 +19,10 @@ use tokio::sync::Semaphore;
 use crate::{
     cache::load_cache,
     common::*,
+    compute_budget::{send_transaction, ComputeBudget},
     config::{get_config_data, Cluster, ConfigData, SugarConfig},
+    guard::resolve_candy_guard,
+    output::style,
     pdas::*,
     setup::get_rpc_url,
     utils::{
@@ -14,7 +14,10 @@ use mpl_token_metadata::{
 };
 
 use super::*;
-use crate::config::TokenStandard;
+use crate::{
+    account_fetcher::AccountFetcher, cancel, config::TokenStandard, progress_stream,
+    utils::get_metadata_accounts,
+};
 
 pub struct ThawArgs {
     pub keypair: Option<String>,
@@ -31,6 +34,8 @@ pub struct ThawArgs {
     pub timeout: Option<u64>,
     pub token: bool,
     pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -89,40 +94,39 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
     let sugar_config = sugar_setup(args.keypair.clone(), args.rpc_url.clone())?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(mpl_candy_guard::ID);
-    let rpc_url = get_rpc_url(args.rpc_url.clone());
+    let rpc_url = get_rpc_url(args.rpc_url.clone())?;
     let rpc_client = RpcClient::new(&rpc_url);
 
-    // candy guard id specified takes precedence over the one from the cache
-    let candy_guard_id = match args.candy_guard {
-        Some(ref candy_guard_id) => candy_guard_id.to_owned(),
-        None => {
-            let cache = load_cache(&args.cache, false)?;
-            cache.program.candy_guard
-        }
-    };
-
     // candy machine id specified takes precedence over the one from the cache
     let candy_machine_id = match args.candy_machine {
         Some(ref candy_machine_id) => candy_machine_id.to_owned(),
         None => {
-            let cache = load_cache(&args.cache, false)?;
+            let mut cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
             cache.program.candy_machine
         }
     };
 
-    let candy_guard = Pubkey::from_str(&candy_guard_id)
-        .map_err(|_| anyhow!("Failed to parse candy guard id: {}", &candy_guard_id))?;
-
     let candy_machine = Pubkey::from_str(&candy_machine_id)
-        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", &candy_guard_id))?;
+        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", &candy_machine_id))?;
+
+    // candy guard id specified takes precedence over the candy machine's
+    let candy_guard = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
 
     let total_steps = if args.all { 4 } else { 2 };
 
-    println!(
+    report!(
         "{} {}Loading freeze escrow information",
         style(format!("[1/{}]", total_steps)).bold().dim(),
         LOOKING_GLASS_EMOJI
     );
+    progress_stream::phase_started("load_freeze_escrow", 1, total_steps);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -175,13 +179,16 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
     }
 
     pb.finish_with_message("Done");
+    progress_stream::phase_finished();
 
     if !args.all {
-        println!(
+        report!(
             "\n{} {}Thawing NFT",
             style(format!("[2/{}]", total_steps)).bold().dim(),
             MONEY_BAG_EMOJI
         );
+        progress_stream::phase_started("thaw_nft", 2, total_steps);
+        progress_stream::totals(1);
 
         let nft_mint = if let Some(nft_mint) = &args.nft_mint {
             nft_mint.to_owned()
@@ -275,7 +282,8 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
         };
 
         if !locked {
-            println!("\n NFT is already thawed.");
+            report!("\n NFT is already thawed.");
+            progress_stream::phase_finished();
             return Ok(());
         }
 
@@ -299,7 +307,15 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
             &args.label,
             freeze_guard,
             &args.priority_fee,
-        )?;
+            &args.compute_budget,
+        )
+        .map_err(|err| {
+            progress_stream::item_completed(Some(nft_mint.clone()), Err(&err));
+            err
+        })?;
+
+        progress_stream::item_completed(Some(nft_mint), Ok::<_, String>(()));
+        progress_stream::phase_finished();
 
         pb.finish_with_message(format!(
             "{} {}",
@@ -310,25 +326,27 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
     }
 
     // Thaw all frozen NFTs.
-    println!(
+    report!(
         "\n{} {}Getting minted NFTs for candy machine {}",
         style(format!("[2/{}]", total_steps)).bold().dim(),
         LOOKING_GLASS_EMOJI,
         candy_machine_id
     );
+    progress_stream::phase_started("find_nfts", 2, total_steps);
 
     let pb = spinner_with_style();
     pb.set_message("Searching...");
 
     let solana_cluster: Cluster = get_cluster(program.rpc())?;
-    let rpc_url = get_rpc_url(args.rpc_url);
-    let client = RpcClient::new_with_timeout(
+    let rpc_url = get_rpc_url(args.rpc_url)?;
+    let client = RpcClient::new_with_timeout_and_commitment(
         &rpc_url,
         Duration::from_secs(if let Some(timeout) = args.timeout {
             timeout
         } else {
             DEFAULT_TIMEOUT
         }),
+        CommitmentConfig::confirmed(),
     );
 
     let solana_cluster = if rpc_url.ends_with("8899") {
@@ -381,9 +399,13 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
         serde_json::to_writer_pretty(mint_pubkeys_cache, &mint_list)?;
     }
 
+    progress_stream::totals(mint_pubkeys.len() as u64);
+    progress_stream::phase_finished();
+
     // padding
-    println!();
+    report!();
 
+    progress_stream::phase_started("load_nft_information", 3, total_steps);
     let pb = progress_bar_with_style(mint_pubkeys.len() as u64);
     pb.set_message("Getting NFT information....");
 
@@ -394,7 +416,7 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
     let mut thaw_tasks = Vec::new();
     let errors = Arc::new(Mutex::new(Vec::new()));
     let thaw_errors = Arc::new(Mutex::new(Vec::new()));
-    let thaw_nfts = Arc::new(Mutex::new(Vec::new()));
+    let token_accounts = Arc::new(Mutex::new(Vec::new()));
     let failed_thaws = Arc::new(Mutex::new(Vec::new()));
 
     let mint_pubkeys_len = mint_pubkeys.len();
@@ -404,7 +426,7 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
         let client = client.clone();
         let pb = pb.clone();
         let errors = errors.clone();
-        let thaw_nfts = thaw_nfts.clone();
+        let token_accounts = token_accounts.clone();
 
         tasks.push(tokio::spawn(async move {
             let _permit = permit;
@@ -415,13 +437,13 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
             let params = json!([mint.to_string(), { "commitment": "confirmed" }]);
             let result: JRpcResponse = client.send(request, params).unwrap();
 
-            let token_accounts: Vec<TokenAccount> = result
+            let holders: Vec<TokenAccount> = result
                 .value
                 .into_iter()
                 .filter(|account| account.amount.parse::<u64>().unwrap() == 1)
                 .collect();
 
-            if token_accounts.len() != 1 {
+            if holders.len() != 1 {
                 errors.lock().unwrap().push(anyhow!(
                     "Mint account {} had more than one token account with 1 token",
                     mint
@@ -429,70 +451,10 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
                 return;
             }
 
-            let token_account = Pubkey::from_str(&token_accounts[0].address).unwrap();
-            let account = client
-                .get_account_with_commitment(&token_account, CommitmentConfig::confirmed())
-                .unwrap()
-                .value
-                .unwrap();
-            let account_data = SplAccount::unpack(&account.data).unwrap();
-            let owner = account_data.owner;
-
-            // Only thaw frozen accounts.
-            if account_data.is_frozen() {
-                // We need to determine whether we have a NFT or pNFT.
-                let token_record_pubkey = find_token_record_account(&mint, &token_account).0;
-                let (locked, token_standard, rule_set) = if let Some(token_record) = client
-                    .get_account_with_commitment(
-                        &token_record_pubkey,
-                        CommitmentConfig::confirmed(),
-                    )
-                    .unwrap()
-                    .value
-                {
-                    let token_record = TokenRecord::safe_deserialize(&token_record.data).unwrap();
-
-                    if token_record.is_locked() {
-                        let metadata_pubkey = find_metadata_pda(&mint);
-                        let metadata_account = client
-                            .get_account_with_commitment(
-                                &metadata_pubkey,
-                                CommitmentConfig::confirmed(),
-                            )
-                            .unwrap()
-                            .value
-                            .unwrap();
-                        let metadata =
-                            Metadata::deserialize(&mut metadata_account.data.as_slice()).unwrap();
-
-                        let rule_set = if let Some(ProgrammableConfig::V1 { rule_set }) =
-                            metadata.programmable_config
-                        {
-                            rule_set
-                        } else {
-                            None
-                        };
-
-                        (true, TokenStandard::ProgrammableNonFungible, rule_set)
-                    } else {
-                        (false, TokenStandard::ProgrammableNonFungible, None)
-                    }
-                } else {
-                    (true, TokenStandard::NonFungible, None)
-                };
+            let token_account = Pubkey::from_str(&holders[0].address).unwrap();
+            token_accounts.lock().unwrap().push((mint, token_account));
 
-                if locked {
-                    thaw_nfts.lock().unwrap().push(ThawNft {
-                        mint,
-                        token_account,
-                        owner,
-                        token_standard,
-                        rule_set,
-                    });
-                }
-
-                pb.inc(1);
-            }
+            pb.inc(1);
         }));
     }
 
@@ -502,8 +464,98 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
             .ok();
     }
 
+    // the token accounts and token records are loaded in batches
+    let fetcher = AccountFetcher::new(client.clone());
+    let token_accounts = token_accounts.lock().unwrap().clone();
+
+    let mut pubkeys = Vec::with_capacity(token_accounts.len() * 2);
+    for (mint, token_account) in &token_accounts {
+        pubkeys.push(*token_account);
+        pubkeys.push(find_token_record_account(mint, token_account).0);
+    }
+    fetcher.prefetch(&pubkeys)?;
+
+    let mut thaw_nfts = Vec::new();
+
+    for (mint, token_account) in token_accounts {
+        let account_data = match fetcher.get_account(&token_account)? {
+            Some(account) => match SplAccount::unpack(&account.data) {
+                Ok(account_data) => account_data,
+                Err(err) => {
+                    errors.lock().unwrap().push(anyhow!(
+                        "Failed to unpack token account {}: {}",
+                        token_account,
+                        err
+                    ));
+                    continue;
+                }
+            },
+            None => {
+                errors
+                    .lock()
+                    .unwrap()
+                    .push(anyhow!("Token account {} not found", token_account));
+                continue;
+            }
+        };
+
+        // Only thaw frozen accounts.
+        if !account_data.is_frozen() {
+            continue;
+        }
+
+        // We need to determine whether we have a NFT or pNFT.
+        let token_record_pubkey = find_token_record_account(&mint, &token_account).0;
+        let (locked, token_standard) = match fetcher.get_account(&token_record_pubkey)? {
+            Some(token_record) => match TokenRecord::safe_deserialize(&token_record.data) {
+                Ok(token_record) => (
+                    token_record.is_locked(),
+                    TokenStandard::ProgrammableNonFungible,
+                ),
+                Err(err) => {
+                    errors.lock().unwrap().push(anyhow!(
+                        "Failed to deserialize token record {}: {}",
+                        token_record_pubkey,
+                        err
+                    ));
+                    continue;
+                }
+            },
+            None => (true, TokenStandard::NonFungible),
+        };
+
+        if locked {
+            thaw_nfts.push(ThawNft {
+                mint,
+                token_account,
+                owner: account_data.owner,
+                token_standard,
+                rule_set: None,
+            });
+        }
+    }
+
+    // the rule set of locked pNFTs is read from their metadata
+    let is_programmable =
+        |nft: &&mut ThawNft| matches!(nft.token_standard, TokenStandard::ProgrammableNonFungible);
+    let metadata_pubkeys: Vec<Pubkey> = thaw_nfts
+        .iter_mut()
+        .filter(is_programmable)
+        .map(|nft| find_metadata_pda(&nft.mint))
+        .collect();
+    let metadata = get_metadata_accounts(&fetcher, &metadata_pubkeys)?;
+
+    for (nft, metadata) in thaw_nfts.iter_mut().filter(is_programmable).zip(metadata) {
+        nft.rule_set =
+            if let Some(ProgrammableConfig::V1 { rule_set }) = metadata.programmable_config {
+                rule_set
+            } else {
+                None
+            };
+    }
+
     if !errors.lock().unwrap().is_empty() {
-        println!(
+        report!(
             "{} {}/{} {}",
             style("Found :").bold(),
             errors.lock().unwrap().len(),
@@ -516,17 +568,29 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
         "{}",
         style("Finished fetching NFT information ").green().bold()
     ));
+    fetcher.print_summary();
+    progress_stream::phase_finished();
 
     let config = Arc::new(sugar_config);
 
     // padding
-    println!();
+    report!();
 
-    let nfts = thaw_nfts.lock().unwrap().clone();
+    let nfts = thaw_nfts;
+    progress_stream::phase_started("thaw_nfts", 4, total_steps);
+    progress_stream::totals(nfts.len() as u64);
     let thaw_pb = progress_bar_with_style(nfts.len() as u64);
     thaw_pb.set_message("Thawing NFTs....");
 
+    let mut cancelled_thaws = Vec::new();
+    let _cancellable = cancel::cancellable();
+
     for nft in nfts.into_iter() {
+        if cancel::is_cancelled() {
+            cancelled_thaws.push(nft);
+            continue;
+        }
+
         let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
         let thaw_pb = thaw_pb.clone();
         let failed_thaws = failed_thaws.clone();
@@ -538,7 +602,7 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
         thaw_tasks.push(tokio::spawn(async move {
             let _permit = permit;
 
-            let _signature = thaw_nft(
+            let result = thaw_nft(
                 config,
                 &candy_guard,
                 &candy_machine,
@@ -547,8 +611,14 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
                 &label,
                 guard,
                 &args.priority_fee,
-            )
-            .map_err(|e| {
+                &args.compute_budget,
+            );
+            progress_stream::item_completed(
+                Some(nft.mint.to_string()),
+                result.as_ref().map(|_| ()),
+            );
+
+            let _signature = result.map_err(|e| {
                 failed_thaws.lock().unwrap().push(FailedThaw {
                     nft: nft.clone(),
                     error: e.to_string(),
@@ -566,6 +636,28 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
         }
     }
 
+    if cancel::is_cancelled() {
+        thaw_pb.abandon_with_message(format!(
+            "{} {}/{} {}",
+            style("Thawed").yellow().bold(),
+            thaw_pb.position() as usize - failed_thaws.lock().unwrap().len(),
+            thaw_pb.length(),
+            style("NFTs").yellow().bold()
+        ));
+
+        let error = cancel::cancelled();
+        let mut remaining = failed_thaws.lock().unwrap().clone();
+        remaining.extend(cancelled_thaws.into_iter().map(|nft| FailedThaw {
+            nft,
+            error: error.reason.to_string(),
+        }));
+
+        let remaining_items_cache = File::create("remaining_thaw_items_cache.json")?;
+        serde_json::to_writer_pretty(remaining_items_cache, &remaining)?;
+
+        return Err(error.into());
+    }
+
     if !thaw_errors.lock().unwrap().is_empty() || !failed_thaws.lock().unwrap().is_empty() {
         thaw_pb.abandon_with_message(format!(
             "{}",
@@ -582,6 +674,7 @@ pub async fn process_thaw(args: ThawArgs) -> Result<()> {
             "{}",
             style("All NFTs thawed successfully ").green().bold()
         ));
+        progress_stream::phase_finished();
     }
 
     let remaining_nfts = Arc::try_unwrap(failed_thaws).unwrap().into_inner().unwrap();
@@ -603,6 +696,7 @@ fn thaw_nft(
     label: &Option<String>,
     freeze_guard: GuardType,
     priority_fee: &u64,
+    compute_budget: &ComputeBudget,
 ) -> Result<Signature> {
     let client = setup_client(&config)?;
     let program = client.program(mpl_candy_guard::ID);
@@ -703,7 +797,7 @@ fn thaw_nft(
 
     let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(*priority_fee);
 
-    let builder = program
+    let instructions = program
         .request()
         .instruction(priority_fee_ix)
         .accounts(RouteAccount {
@@ -718,8 +812,15 @@ fn thaw_nft(
                 guard: freeze_guard,
             },
             label: label.to_owned(),
-        });
-    let sig = builder.send()?;
+        })
+        .instructions()?;
+    let sig = send_transaction(
+        &program.rpc(),
+        &config.keypair,
+        &instructions,
+        &[],
+        compute_budget,
+    )?;
 
     Ok(sig)
 }
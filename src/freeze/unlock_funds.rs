@@ -17,6 +17,8 @@ pub struct UnlockFundsArgs {
     pub label: Option<String>,
     pub token: bool,
     pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_unlock_funds(args: UnlockFundsArgs) -> Result<()> {
@@ -24,31 +26,29 @@ pub fn process_unlock_funds(args: UnlockFundsArgs) -> Result<()> {
     let client = setup_client(&sugar_config)?;
     let program = client.program(mpl_candy_guard::ID);
 
-    // candy guard id specified takes precedence over the one from the cache
-    let candy_guard_id = match args.candy_guard {
-        Some(ref candy_guard_id) => candy_guard_id.to_owned(),
-        None => {
-            let cache = load_cache(&args.cache, false)?;
-            cache.program.candy_guard
-        }
-    };
-
     // candy machine id specified takes precedence over the one from the cache
     let candy_machine_id = match args.candy_machine {
         Some(ref candy_machine_id) => candy_machine_id.to_owned(),
         None => {
-            let cache = load_cache(&args.cache, false)?;
+            let mut cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
             cache.program.candy_machine
         }
     };
 
-    let candy_guard = Pubkey::from_str(&candy_guard_id)
-        .map_err(|_| anyhow!("Failed to parse candy guard id: {}", &candy_guard_id))?;
-
     let candy_machine = Pubkey::from_str(&candy_machine_id)
-        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", &candy_guard_id))?;
+        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", &candy_machine_id))?;
 
-    println!(
+    // candy guard id specified takes precedence over the candy machine's
+    let candy_guard = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
+
+    report!(
         "{} {}Loading freeze escrow information",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
@@ -106,7 +106,7 @@ pub fn process_unlock_funds(args: UnlockFundsArgs) -> Result<()> {
 
     pb.finish_with_message("Done");
 
-    println!(
+    report!(
         "\n{} {}Unlocking treasury funds",
         style("[2/2]").bold().dim(),
         MONEY_BAG_EMOJI
@@ -117,12 +117,14 @@ pub fn process_unlock_funds(args: UnlockFundsArgs) -> Result<()> {
 
     let signature = unlock_funds(
         &program,
+        &sugar_config.keypair,
         &candy_guard,
         &candy_machine,
         &destination_address,
         &args.label,
         freeze_guard,
         &args.priority_fee,
+        &args.compute_budget,
     )?;
 
     pb.finish_with_message(format!(
@@ -134,14 +136,17 @@ pub fn process_unlock_funds(args: UnlockFundsArgs) -> Result<()> {
     Ok(())
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn unlock_funds<C: Deref<Target = impl Signer> + Clone>(
     program: &Program<C>,
+    keypair: &Keypair,
     candy_guard_id: &Pubkey,
     candy_machine_id: &Pubkey,
     destination: &Pubkey,
     label: &Option<String>,
     freeze_guard: GuardType,
     priority_fee: &u64,
+    compute_budget: &ComputeBudget,
 ) -> Result<Signature> {
     let mut remaining_accounts = Vec::with_capacity(4);
     let (freeze_pda, _) = find_freeze_pda(candy_guard_id, candy_machine_id, destination);
@@ -203,7 +208,7 @@ pub fn unlock_funds<C: Deref<Target = impl Signer> + Clone>(
 
     let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(*priority_fee);
 
-    let builder = program
+    let instructions = program
         .request()
         .instruction(priority_fee_ix)
         .accounts(RouteAccount {
@@ -218,8 +223,9 @@ pub fn unlock_funds<C: Deref<Target = impl Signer> + Clone>(
                 guard: freeze_guard,
             },
             label: label.to_owned(),
-        });
-    let sig = builder.send()?;
+        })
+        .instructions()?;
+    let sig = send_transaction(&program.rpc(), keypair, &instructions, &[], compute_budget)?;
 
     Ok(sig)
 }
@@ -2,14 +2,21 @@ use std::str::FromStr;
 
 use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
 use anyhow::Result;
-use console::style;
 use mpl_candy_guard::{
-    accounts::{Initialize as InitializeAccount, Update as UpdateAccount, Wrap as WrapAccount},
-    instruction::{Initialize, Update, Wrap},
+    accounts::{Initialize as InitializeAccount, Update as UpdateAccount},
+    instruction::{Initialize, Update},
 };
 use mpl_candy_machine_core::constants::EMPTY_STR;
 
-use crate::{cache::load_cache, candy_machine::*, common::*, config::get_config_data, utils::*};
+use crate::{
+    cache::load_cache,
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    config::{get_config_data, CandyGuardData},
+    guard::{diff_guards, print_guard_diff, review_guard_update, wrap_candy_machine},
+    output::style,
+    utils::*,
+};
 
 pub struct GuardAddArgs {
     pub keypair: Option<String>,
@@ -19,43 +26,59 @@ pub struct GuardAddArgs {
     pub candy_machine: Option<String>,
     pub candy_guard: Option<String>,
     pub priority_fee: u64,
+    pub no_wrap: bool,
+    pub dry_run: bool,
+    pub yes: bool,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_guard_add(args: GuardAddArgs) -> Result<()> {
-    println!("[1/3] {}Looking up candy machine", LOOKING_GLASS_EMOJI);
+    // the wrap step is skipped when the guard is only created and configured
+    let steps = if args.no_wrap { 2 } else { 3 };
+
+    report!(
+        "[1/{steps}] {}Looking up candy machine",
+        LOOKING_GLASS_EMOJI
+    );
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
 
     // the candy machine id specified takes precedence over the one from the cache
 
-    let (candy_machine_id, cache) = if let Some(candy_machine) = args.candy_machine {
+    let (candy_machine_id, mut cache) = if let Some(candy_machine) = args.candy_machine {
         (candy_machine, None)
     } else {
-        let cache = load_cache(&args.cache, false)?;
+        let cache = load_cache(&args.cache, args.no_wrap)?;
         (cache.program.candy_machine.clone(), Some(cache))
     };
 
-    if candy_machine_id.is_empty() {
-        return Err(anyhow!("Missing candy machine id."));
-    }
-
-    let candy_machine_id = match Pubkey::from_str(&candy_machine_id) {
-        Ok(candy_machine_id) => candy_machine_id,
-        Err(_) => {
-            let error = anyhow!("Failed to parse candy machine id: {}", candy_machine_id);
-            error!("{:?}", error);
-            return Err(error);
+    let candy_machine_id = if candy_machine_id.is_empty() {
+        if !args.no_wrap {
+            return Err(anyhow!("Missing candy machine id."));
+        }
+        None
+    } else {
+        match Pubkey::from_str(&candy_machine_id) {
+            Ok(candy_machine_id) => Some(candy_machine_id),
+            Err(_) => {
+                let error = anyhow!("Failed to parse candy machine id: {}", candy_machine_id);
+                error!("{:?}", error);
+                return Err(error);
+            }
         }
     };
 
     pb.finish_and_clear();
 
-    println!(
-        "\n{} {}",
-        style("Candy machine ID:").bold(),
-        candy_machine_id
-    );
+    if let Some(candy_machine_id) = candy_machine_id {
+        report!(
+            "\n{} {}",
+            style("Candy machine ID:").bold(),
+            candy_machine_id
+        );
+    }
 
     // decide whether to create a new candy guard or use an existing one
 
@@ -73,8 +96,26 @@ pub fn process_guard_add(args: GuardAddArgs) -> Result<()> {
     let payer = sugar_config.keypair;
     let program = client.program(mpl_candy_guard::ID);
 
+    if let Some(ref mut cache) = cache {
+        cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
+    }
+
+    if candy_guard_id.is_empty() && args.dry_run {
+        // a new candy guard would be created with all the guards of the config file
+        let data = if let Some(guards) = &config_data.guards {
+            guards.to_guard_format()?
+        } else {
+            return Err(anyhow!("Missing guards configuration."));
+        };
+        let empty = CandyGuardData::default().to_guard_format()?;
+
+        report!();
+        print_guard_diff(&diff_guards(&empty, &data));
+        return Ok(());
+    }
+
     let candy_guard = if candy_guard_id.is_empty() {
-        println!("\n[2/3] {}Initializing a candy guard", GUARD_EMOJI);
+        report!("\n[2/{steps}] {}Initializing a candy guard", GUARD_EMOJI);
         let pb = spinner_with_style();
         pb.set_message("Initializing...");
 
@@ -95,7 +136,7 @@ pub fn process_guard_add(args: GuardAddArgs) -> Result<()> {
 
         let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
-        let tx = program
+        let instructions = program
             .request()
             .instruction(priority_fee)
             .accounts(InitializeAccount {
@@ -108,16 +149,22 @@ pub fn process_guard_add(args: GuardAddArgs) -> Result<()> {
             .args(Initialize {
                 data: serialized_data,
             })
-            .signer(&base);
+            .instructions()?;
 
-        let sig = tx.send()?;
+        let sig = send_transaction(
+            &program.rpc(),
+            &payer,
+            &instructions,
+            &[&base],
+            &args.compute_budget,
+        )?;
 
         pb.finish_and_clear();
-        println!("{} {}", style("Signature:").bold(), sig);
+        report!("{} {}", style("Signature:").bold(), sig);
 
         candy_guard
     } else {
-        println!("\n[2/3] {}Loading candy guard", COMPUTER_EMOJI);
+        report!("\n[2/{steps}] {}Loading candy guard", COMPUTER_EMOJI);
 
         let candy_guard_id = match Pubkey::from_str(&candy_guard_id) {
             Ok(candy_guard_id) => candy_guard_id,
@@ -140,13 +187,26 @@ pub fn process_guard_add(args: GuardAddArgs) -> Result<()> {
             return Err(anyhow!("Missing guards configuration."));
         };
 
+        pb.finish_and_clear();
+
+        // the whole guard account is overwritten, so the changes are reviewed first
+        if !review_guard_update(&program, &candy_guard_id, &data, args.dry_run, args.yes)? {
+            if !args.dry_run {
+                report!("Update cancelled.");
+            }
+            return Ok(());
+        }
+
+        let pb = spinner_with_style();
+        pb.set_message("Updating...");
+
         let mut serialized_data = vec![0; data.size()];
         data.save(&mut serialized_data)?;
 
         let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
         // synchronizes the guards config with the on-chain account
-        let tx = program
+        let instructions = program
             .request()
             .instruction(priority_fee)
             .accounts(UpdateAccount {
@@ -157,51 +217,61 @@ pub fn process_guard_add(args: GuardAddArgs) -> Result<()> {
             })
             .args(Update {
                 data: serialized_data,
-            });
+            })
+            .instructions()?;
 
-        tx.send()?;
+        send_transaction(
+            &program.rpc(),
+            &payer,
+            &instructions,
+            &[],
+            &args.compute_budget,
+        )?;
 
         pb.finish_with_message("Done");
 
         candy_guard_id
     };
 
-    println!("\n{} {}", style("Candy guard ID:").bold(), candy_guard);
+    report!("\n{} {}", style("Candy guard ID:").bold(), candy_guard);
 
     // wraps the candy machine
 
-    println!("\n[3/3] {}Wrapping", WRAP_EMOJI);
-
-    let pb = spinner_with_style();
-    pb.set_message("Connecting...");
-
-    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
+    if let Some(candy_machine_id) = candy_machine_id.filter(|_| !args.no_wrap) {
+        report!("\n[3/3] {}Wrapping", WRAP_EMOJI);
 
-    let tx = program
-        .request()
-        .instruction(priority_fee)
-        .accounts(WrapAccount {
-            candy_guard,
-            authority: payer.pubkey(),
-            candy_machine: candy_machine_id,
-            candy_machine_program: CANDY_MACHINE_ID,
-            candy_machine_authority: payer.pubkey(),
-        })
-        .args(Wrap {});
+        let pb = spinner_with_style();
+        pb.set_message("Connecting...");
 
-    let sig = tx.send()?;
+        let sig = wrap_candy_machine(
+            &program,
+            &payer,
+            &candy_guard,
+            &candy_machine_id,
+            args.priority_fee,
+            &args.compute_budget,
+        )?;
 
-    pb.finish_and_clear();
-    println!("{} {}", style("Signature:").bold(), sig);
+        pb.finish_and_clear();
+        report!("{} {}", style("Signature:").bold(), sig);
 
-    println!("\nThe candy guard is now the mint authority of the candy machine.");
+        report!("\nThe candy guard is now the mint authority of the candy machine.");
+    } else {
+        report!(
+            "\nThe candy guard was not wrapped, run 'sugar guard wrap' to set it as the \
+            mint authority of the candy machine."
+        );
+    }
 
     // if we created a new candy guard from the candy machine on the cache file,
     // we store the reference of the candy guard on the cache
 
     if cache.is_some() {
-        let mut cache = load_cache(&args.cache, false)?;
+        let mut cache = load_cache(&args.cache, args.no_wrap)?;
         cache.program.candy_guard = candy_guard.to_string();
+        if candy_guard_id.is_empty() {
+            cache.program.record_candy_guard_creation(&program.rpc())?;
+        }
         cache.sync_file()?;
     }
 
@@ -0,0 +1,334 @@
+use anyhow::Result;
+use mpl_candy_guard::{
+    guards::GuardSet,
+    state::{CandyGuardData, DATA_OFFSET},
+};
+
+use crate::{
+    common::*,
+    output::{confirm, style, Confirmation},
+};
+
+/// Kind of change to a guard (or group).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+/// Change to a single guard of a guard set.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GuardChange {
+    pub guard: &'static str,
+    pub kind: ChangeKind,
+    pub current: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Structural diff between the on-chain guards and the guards of the config file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuardDiff {
+    pub default: Vec<GuardChange>,
+    pub groups_added: Vec<String>,
+    pub groups_removed: Vec<String>,
+    pub groups_changed: Vec<(String, Vec<GuardChange>)>,
+}
+
+impl GuardDiff {
+    pub fn is_empty(&self) -> bool {
+        self.default.is_empty()
+            && self.groups_added.is_empty()
+            && self.groups_removed.is_empty()
+            && self.groups_changed.is_empty()
+    }
+
+    /// Returns `true` if a guard or a group would be removed.
+    pub fn has_removals(&self) -> bool {
+        !self.groups_removed.is_empty()
+            || self
+                .default
+                .iter()
+                .chain(self.groups_changed.iter().flat_map(|(_, changes)| changes))
+                .any(|change| change.kind == ChangeKind::Removed)
+    }
+}
+
+// returns the (config name, value) of every known guard
+macro_rules! guard_values {
+    ($guards:expr, $($field:ident => $name:literal),* $(,)?) => {
+        vec![$(($name, $guards.$field.as_ref().map(|guard| format!("{:?}", guard)))),*]
+    };
+}
+
+fn guard_values(guards: &GuardSet) -> Vec<(&'static str, Option<String>)> {
+    guard_values!(
+        guards,
+        bot_tax => "botTax",
+        sol_payment => "solPayment",
+        token_payment => "tokenPayment",
+        start_date => "startDate",
+        third_party_signer => "thirdPartySigner",
+        token_gate => "tokenGate",
+        gatekeeper => "gatekeeper",
+        end_date => "endDate",
+        allow_list => "allowList",
+        mint_limit => "mintLimit",
+        nft_payment => "nftPayment",
+        redeemed_amount => "redeemedAmount",
+        address_gate => "addressGate",
+        nft_gate => "nftGate",
+        nft_burn => "nftBurn",
+        token_burn => "tokenBurn",
+        freeze_sol_payment => "freezeSolPayment",
+        freeze_token_payment => "freezeTokenPayment",
+        program_gate => "programGate",
+        allocation => "allocation",
+        token2022_payment => "token2022Payment",
+    )
+}
+
+/// Computes the changes between two guard sets.
+pub fn diff_guard_set(current: &GuardSet, new: &GuardSet) -> Vec<GuardChange> {
+    guard_values(current)
+        .into_iter()
+        .zip(guard_values(new))
+        .filter_map(|((guard, current), (_, new))| {
+            let kind = match (&current, &new) {
+                (None, Some(_)) => ChangeKind::Added,
+                (Some(_), None) => ChangeKind::Removed,
+                (Some(c), Some(n)) if c != n => ChangeKind::Changed,
+                _ => return None,
+            };
+            Some(GuardChange {
+                guard,
+                kind,
+                current,
+                new,
+            })
+        })
+        .collect()
+}
+
+/// Computes the changes between the current (on-chain) and the new guards.
+pub fn diff_guards(current: &CandyGuardData, new: &CandyGuardData) -> GuardDiff {
+    let current_groups = current.groups.as_deref().unwrap_or_default();
+    let new_groups = new.groups.as_deref().unwrap_or_default();
+
+    let mut diff = GuardDiff {
+        default: diff_guard_set(&current.default, &new.default),
+        ..Default::default()
+    };
+
+    for group in current_groups {
+        match new_groups.iter().find(|g| g.label == group.label) {
+            Some(new_group) => {
+                let changes = diff_guard_set(&group.guards, &new_group.guards);
+                if !changes.is_empty() {
+                    diff.groups_changed.push((group.label.clone(), changes));
+                }
+            }
+            None => diff.groups_removed.push(group.label.clone()),
+        }
+    }
+
+    diff.groups_added = new_groups
+        .iter()
+        .filter(|group| !current_groups.iter().any(|g| g.label == group.label))
+        .map(|group| group.label.clone())
+        .collect();
+
+    diff
+}
+
+pub fn print_guard_diff(diff: &GuardDiff) {
+    if diff.is_empty() {
+        report!("{}", style("No changes to the on-chain guards.").dim());
+        return;
+    }
+
+    let print_changes = |indent: &str, changes: &[GuardChange]| {
+        for change in changes {
+            match change.kind {
+                ChangeKind::Added => report!(
+                    "{indent}{} {}: {}",
+                    style("+").green().bold(),
+                    change.guard,
+                    change.new.as_deref().unwrap_or_default()
+                ),
+                ChangeKind::Removed => report!(
+                    "{indent}{}",
+                    style(format!(
+                        "- {}: {}",
+                        change.guard,
+                        change.current.as_deref().unwrap_or_default()
+                    ))
+                    .red()
+                    .bold()
+                ),
+                ChangeKind::Changed => {
+                    report!("{indent}{} {}", style("~").yellow().bold(), change.guard);
+                    report!(
+                        "{indent}    {} {}",
+                        style("from").dim(),
+                        change.current.as_deref().unwrap_or_default()
+                    );
+                    report!(
+                        "{indent}    {}   {}",
+                        style("to").dim(),
+                        change.new.as_deref().unwrap_or_default()
+                    );
+                }
+            }
+        }
+    };
+
+    if !diff.default.is_empty() {
+        report!("{}", style("default").bold());
+        print_changes("  ", &diff.default);
+    }
+
+    for (label, changes) in &diff.groups_changed {
+        report!("{}", style(format!("group '{label}'")).bold());
+        print_changes("  ", changes);
+    }
+
+    for label in &diff.groups_added {
+        report!("{} group '{}'", style("+").green().bold(), label);
+    }
+
+    for label in &diff.groups_removed {
+        report!("{}", style(format!("- group '{label}'")).red().bold());
+    }
+}
+
+/// Prints the diff between the on-chain guards and the new guards, returning whether the
+/// update should proceed: not in a dry run, and only after confirmation (unless `yes` is
+/// set) when guards or groups would be removed.
+pub fn review_guard_update<C: std::ops::Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    candy_guard_id: &Pubkey,
+    new: &CandyGuardData,
+    dry_run: bool,
+    yes: bool,
+) -> Result<bool> {
+    let account_data = program.rpc().get_account_data(candy_guard_id)?;
+    let current = CandyGuardData::load(&account_data[DATA_OFFSET..])?;
+
+    let diff = diff_guards(&current, new);
+    report!();
+    print_guard_diff(&diff);
+    report!();
+
+    if dry_run {
+        return Ok(false);
+    }
+
+    if diff.has_removals() && !yes {
+        return confirm(
+            Confirmation::new("The update removes guards or groups, do you want to continue?")
+                .default(false),
+        );
+    }
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::guard_data::{
+        CandyGuardData as ConfigData, EndDate, Group, GuardSet as ConfigGuardSet, StartDate,
+    };
+
+    fn guards(start: Option<&str>, end: Option<&str>) -> ConfigGuardSet {
+        ConfigGuardSet {
+            start_date: start.map(|date| StartDate {
+                date: date.to_string(),
+            }),
+            end_date: end.map(|date| EndDate {
+                date: date.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn data(default: ConfigGuardSet, groups: Vec<(&str, ConfigGuardSet)>) -> CandyGuardData {
+        ConfigData {
+            default,
+            groups: (!groups.is_empty()).then(|| {
+                groups
+                    .into_iter()
+                    .map(|(label, guards)| Group {
+                        label: label.to_string(),
+                        guards,
+                    })
+                    .collect()
+            }),
+        }
+        .to_guard_format()
+        .unwrap()
+    }
+
+    const START: &str = "2023-05-01T17:00:00Z";
+    const END: &str = "2023-05-01T19:00:00Z";
+
+    #[test]
+    fn detects_added_guards() {
+        let current = data(guards(None, None), vec![]);
+        let new = data(guards(Some(START), None), vec![]);
+
+        let diff = diff_guards(&current, &new);
+
+        assert_eq!(diff.default.len(), 1);
+        assert_eq!(diff.default[0].guard, "startDate");
+        assert_eq!(diff.default[0].kind, ChangeKind::Added);
+        assert!(!diff.has_removals());
+    }
+
+    #[test]
+    fn detects_removed_guards_and_groups() {
+        let current = data(
+            guards(Some(START), Some(END)),
+            vec![("OG", guards(Some(START), None))],
+        );
+        let new = data(guards(Some(START), None), vec![]);
+
+        let diff = diff_guards(&current, &new);
+
+        assert_eq!(diff.default.len(), 1);
+        assert_eq!(diff.default[0].guard, "endDate");
+        assert_eq!(diff.default[0].kind, ChangeKind::Removed);
+        assert_eq!(diff.groups_removed, vec!["OG".to_string()]);
+        assert!(diff.has_removals());
+    }
+
+    #[test]
+    fn detects_changed_guards_and_added_groups() {
+        let current = data(
+            guards(Some(START), None),
+            vec![("OG", guards(Some(START), None))],
+        );
+        let new = data(
+            guards(Some(END), None),
+            vec![
+                ("OG", guards(Some(START), None)),
+                ("WL", guards(None, Some(END))),
+            ],
+        );
+
+        let diff = diff_guards(&current, &new);
+
+        assert_eq!(diff.default.len(), 1);
+        assert_eq!(diff.default[0].kind, ChangeKind::Changed);
+        assert!(diff.groups_changed.is_empty());
+        assert_eq!(diff.groups_added, vec!["WL".to_string()]);
+        assert!(!diff.has_removals());
+    }
+
+    #[test]
+    fn identical_guards_have_no_diff() {
+        let current = data(guards(Some(START), Some(END)), vec![]);
+        assert!(diff_guards(&current, &current).is_empty());
+    }
+}
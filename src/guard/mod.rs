@@ -1,11 +1,19 @@
 pub mod add;
+pub mod diff;
 pub mod remove;
+pub mod resolve;
+pub mod schedule;
 pub mod show;
 pub mod update;
 pub mod withdraw;
+pub mod wrap;
 
 pub use add::*;
+pub use diff::*;
 pub use remove::*;
+pub use resolve::*;
+pub use schedule::*;
 pub use show::*;
 pub use update::*;
 pub use withdraw::*;
+pub use wrap::*;
@@ -2,10 +2,17 @@ use std::str::FromStr;
 
 use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
 use anyhow::Result;
-use console::style;
 use mpl_candy_guard::{accounts::Unwrap as UnwrapAccount, instruction::Unwrap};
 
-use crate::{cache::load_cache, candy_machine::*, common::*, utils::*};
+use crate::{
+    cache::load_cache,
+    candy_machine::*,
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    guard::{print_mint_authority, resolve_candy_guard},
+    output::style,
+    utils::*,
+};
 
 pub struct GuardRemoveArgs {
     pub keypair: Option<String>,
@@ -14,17 +21,24 @@ pub struct GuardRemoveArgs {
     pub candy_machine: Option<String>,
     pub candy_guard: Option<String>,
     pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_guard_remove(args: GuardRemoveArgs) -> Result<()> {
-    println!("[1/1] {}Unwrapping", UNWRAP_EMOJI);
+    report!("[1/1] {}Unwrapping", UNWRAP_EMOJI);
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(mpl_candy_guard::ID);
 
     // the candy machine id specified takes precedence over the one from the cache
 
-    let candy_machine_id = if let Some(candy_machine) = args.candy_machine {
-        candy_machine
+    let candy_machine_id = if let Some(candy_machine) = &args.candy_machine {
+        candy_machine.to_owned()
     } else {
-        let cache = load_cache(&args.cache, false)?;
+        let mut cache = load_cache(&args.cache, false)?;
+        cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
         cache.program.candy_machine
     };
 
@@ -37,36 +51,27 @@ pub fn process_guard_remove(args: GuardRemoveArgs) -> Result<()> {
         }
     };
 
-    // the candy guard id specified takes precedence over the one from the cache
+    // the candy guard id specified takes precedence over the candy machine's
 
-    let candy_guard_id = if let Some(candy_guard) = args.candy_guard {
-        candy_guard
-    } else {
-        let cache = load_cache(&args.cache, false)?;
-        cache.program.candy_guard
-    };
-
-    let candy_guard_id = match Pubkey::from_str(&candy_guard_id) {
-        Ok(candy_guard_id) => candy_guard_id,
-        Err(_) => {
-            let error = anyhow!("Failed to parse candy guard id: {}", candy_guard_id);
-            error!("{:?}", error);
-            return Err(error);
-        }
-    };
+    let candy_guard_id = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        Some(candy_machine_id.to_string().as_str()),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
 
     // remove the candy guard as mint authority
 
-    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
-    let client = setup_client(&sugar_config)?;
-    let program = client.program(mpl_candy_guard::ID);
-    let payer = sugar_config.keypair;
+    print_mint_authority(&sugar_config, &candy_machine_id, "Mint authority (before):")?;
+
+    let payer = &sugar_config.keypair;
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
     let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
-    let tx = program
+    let instructions = program
         .request()
         .instruction(priority_fee)
         .accounts(UnwrapAccount {
@@ -76,15 +81,24 @@ pub fn process_guard_remove(args: GuardRemoveArgs) -> Result<()> {
             candy_machine_authority: payer.pubkey(),
             candy_machine_program: CANDY_MACHINE_ID,
         })
-        .args(Unwrap {});
+        .args(Unwrap {})
+        .instructions()?;
 
-    let sig = tx.send()?;
+    let sig = send_transaction(
+        &program.rpc(),
+        payer,
+        &instructions,
+        &[],
+        &args.compute_budget,
+    )?;
 
     pb.finish_and_clear();
-    println!("{} {}", style("Signature:").bold(), sig);
+    report!("{} {}", style("Signature:").bold(), sig);
+
+    print_mint_authority(&sugar_config, &candy_machine_id, "Mint authority (after):")?;
 
-    println!("\nThe candy guard is no longer the mint authority of the candy machine.");
-    println!(
+    report!("\nThe candy guard is no longer the mint authority of the candy machine.");
+    report!(
         "  -> New mint authority: {}",
         style(format!("{}", payer.pubkey())).bold()
     );
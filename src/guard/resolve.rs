@@ -0,0 +1,291 @@
+use std::{ops::Deref, str::FromStr};
+
+use anchor_client::solana_sdk::pubkey::Pubkey;
+use anyhow::Result;
+use mpl_candy_guard::state::CandyGuard;
+
+use crate::{cache::load_cache, candy_machine::CANDY_MACHINE_ID, common::*};
+
+/// Resolves the candy guard address of a guard command.
+///
+/// The candy guard specified takes precedence. Otherwise, the candy guard is read from the
+/// mint authority of the candy machine specified, falling back to the candy guard in the
+/// cache and then to the mint authority of the candy machine in the cache. A candy guard
+/// read from the cache's candy machine is recorded in the cache.
+pub fn resolve_candy_guard<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    candy_guard: Option<&str>,
+    candy_machine: Option<&str>,
+    cache_file: &str,
+    allow_cluster_mismatch: bool,
+) -> Result<Pubkey> {
+    resolve_candy_guard_with(
+        candy_guard,
+        candy_machine,
+        cache_file,
+        |cache| cache.check_cluster(&program.rpc(), allow_cluster_mismatch),
+        |candy_machine| find_wrapping_candy_guard(program, candy_machine),
+    )
+}
+
+/// [`resolve_candy_guard`] with the cluster check of the cache and the lookup of the candy
+/// guard wrapping a candy machine given as functions.
+fn resolve_candy_guard_with(
+    candy_guard: Option<&str>,
+    candy_machine: Option<&str>,
+    cache_file: &str,
+    check_cluster: impl FnOnce(&mut Cache) -> Result<()>,
+    find_candy_guard: impl FnOnce(&Pubkey) -> Result<Pubkey>,
+) -> Result<Pubkey> {
+    if let Some(candy_guard) = candy_guard {
+        return parse_candy_guard_id(candy_guard);
+    }
+
+    if let Some(candy_machine) = candy_machine {
+        let candy_machine = parse_candy_machine_id(candy_machine)?;
+        let candy_guard = find_candy_guard(&candy_machine)?;
+
+        // only updates a cache that refers to the same candy machine
+        if Path::new(cache_file).exists() {
+            let mut cache = load_cache(cache_file, false)?;
+            if cache.program.candy_machine == candy_machine.to_string() {
+                record_candy_guard(&mut cache, &candy_guard)?;
+            }
+        }
+
+        return Ok(candy_guard);
+    }
+
+    let mut cache = load_cache(cache_file, false)?;
+    check_cluster(&mut cache)?;
+
+    if !cache.program.candy_guard.is_empty() {
+        return parse_candy_guard_id(&cache.program.candy_guard);
+    }
+
+    if cache.program.candy_machine.is_empty() {
+        return Err(anyhow!(
+            "Missing candy guard id, specify --candy-guard or --candy-machine."
+        ));
+    }
+
+    let candy_machine = parse_candy_machine_id(&cache.program.candy_machine)?;
+    let candy_guard = find_candy_guard(&candy_machine)?;
+    record_candy_guard(&mut cache, &candy_guard)?;
+
+    Ok(candy_guard)
+}
+
+/// Returns the candy guard set as the mint authority of the candy machine.
+pub fn find_wrapping_candy_guard<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    candy_machine: &Pubkey,
+) -> Result<Pubkey> {
+    let account = program
+        .rpc()
+        .get_account(candy_machine)
+        .map_err(|_| anyhow!("Candy machine {} not found", candy_machine))?;
+
+    if account.owner != CANDY_MACHINE_ID {
+        return Err(anyhow!("Account {} is not a candy machine", candy_machine));
+    }
+
+    let candy_machine_state = CandyMachine::try_deserialize(&mut account.data.as_slice())?;
+    let mint_authority = candy_machine_state.mint_authority;
+
+    let no_guard = || {
+        anyhow!(
+            "Candy machine {} has no candy guard attached (mint authority: {}).",
+            candy_machine,
+            mint_authority
+        )
+    };
+
+    let account = program
+        .rpc()
+        .get_account_with_commitment(&mint_authority, program.rpc().commitment())?
+        .value
+        .ok_or_else(no_guard)?;
+
+    if account.owner != mpl_candy_guard::ID {
+        return Err(no_guard());
+    }
+
+    let candy_guard =
+        CandyGuard::try_deserialize(&mut account.data.as_slice()).map_err(|_| no_guard())?;
+    let (candy_guard_pda, _) = Pubkey::find_program_address(
+        &[b"candy_guard", candy_guard.base.as_ref()],
+        &mpl_candy_guard::ID,
+    );
+
+    if candy_guard_pda != mint_authority {
+        return Err(no_guard());
+    }
+
+    Ok(mint_authority)
+}
+
+fn record_candy_guard(cache: &mut Cache, candy_guard: &Pubkey) -> Result<()> {
+    if cache.program.candy_guard != candy_guard.to_string() {
+        cache.program.candy_guard = candy_guard.to_string();
+        cache.sync_file()?;
+    }
+
+    Ok(())
+}
+
+fn parse_candy_guard_id(candy_guard_id: &str) -> Result<Pubkey> {
+    Pubkey::from_str(candy_guard_id).map_err(|_| {
+        let error = anyhow!("Failed to parse candy guard id: {}", candy_guard_id);
+        error!("{:?}", error);
+        error
+    })
+}
+
+fn parse_candy_machine_id(candy_machine_id: &str) -> Result<Pubkey> {
+    Pubkey::from_str(candy_machine_id).map_err(|_| {
+        let error = anyhow!("Failed to parse candy machine id: {}", candy_machine_id);
+        error!("{:?}", error);
+        error
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_cache(path: &Path, candy_machine: &str, candy_guard: &str) {
+        let mut cache = Cache::new();
+        cache.program.candy_machine = candy_machine.to_string();
+        cache.program.candy_guard = candy_guard.to_string();
+        cache.write_to_file(path).unwrap();
+    }
+
+    fn cached_guard(path: &Path) -> String {
+        load_cache(path.to_str().unwrap(), false)
+            .unwrap()
+            .program
+            .candy_guard
+    }
+
+    fn no_lookup(candy_machine: &Pubkey) -> Result<Pubkey> {
+        Err(anyhow!("unexpected lookup of {}", candy_machine))
+    }
+
+    #[test]
+    fn test_resolve_candy_guard_precedence() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let cache_file = path.to_str().unwrap();
+        let cache_machine = Pubkey::new_unique();
+        let cache_guard = Pubkey::new_unique();
+        let candy_machine = Pubkey::new_unique();
+        let candy_guard = Pubkey::new_unique();
+        write_cache(&path, &cache_machine.to_string(), &cache_guard.to_string());
+
+        // the candy guard specified: neither the cache nor the chain is read
+        let resolved = resolve_candy_guard_with(
+            Some(candy_guard.to_string().as_str()),
+            Some(candy_machine.to_string().as_str()),
+            "missing.json",
+            |_| Err(anyhow!("unexpected cluster check")),
+            no_lookup,
+        )
+        .unwrap();
+        assert_eq!(resolved, candy_guard);
+
+        // the candy machine specified: its mint authority, over the candy guard of the cache
+        let resolved = resolve_candy_guard_with(
+            None,
+            Some(candy_machine.to_string().as_str()),
+            cache_file,
+            |_| Err(anyhow!("unexpected cluster check")),
+            |id| {
+                assert_eq!(*id, candy_machine);
+                Ok(candy_guard)
+            },
+        )
+        .unwrap();
+        assert_eq!(resolved, candy_guard);
+        // the cache refers to another candy machine, it is not updated
+        assert_eq!(cached_guard(&path), cache_guard.to_string());
+
+        // nothing specified: the candy guard of the cache, after the cluster check
+        let mut checked = false;
+        let resolved = resolve_candy_guard_with(
+            None,
+            None,
+            cache_file,
+            |_| {
+                checked = true;
+                Ok(())
+            },
+            no_lookup,
+        )
+        .unwrap();
+        assert_eq!(resolved, cache_guard);
+        assert!(checked);
+
+        let error = resolve_candy_guard_with(
+            None,
+            None,
+            cache_file,
+            |_| Err(anyhow!("cluster mismatch")),
+            no_lookup,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "cluster mismatch");
+    }
+
+    #[test]
+    fn test_resolve_candy_guard_records_cache() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cache.json");
+        let cache_file = path.to_str().unwrap();
+        let candy_machine = Pubkey::new_unique();
+        let candy_guard = Pubkey::new_unique();
+
+        // no candy guard in the cache: the mint authority of the cache's candy machine,
+        // recorded in the cache
+        write_cache(&path, &candy_machine.to_string(), "");
+        let resolved = resolve_candy_guard_with(
+            None,
+            None,
+            cache_file,
+            |_| Ok(()),
+            |id| {
+                assert_eq!(*id, candy_machine);
+                Ok(candy_guard)
+            },
+        )
+        .unwrap();
+        assert_eq!(resolved, candy_guard);
+        assert_eq!(cached_guard(&path), candy_guard.to_string());
+
+        // the candy machine specified is the cache's: the cache is updated
+        let other_guard = Pubkey::new_unique();
+        let resolved = resolve_candy_guard_with(
+            None,
+            Some(candy_machine.to_string().as_str()),
+            cache_file,
+            |_| Ok(()),
+            |_| Ok(other_guard),
+        )
+        .unwrap();
+        assert_eq!(resolved, other_guard);
+        assert_eq!(cached_guard(&path), other_guard.to_string());
+
+        // neither a candy guard nor a candy machine
+        write_cache(&path, "", "");
+        let error =
+            resolve_candy_guard_with(None, None, cache_file, |_| Ok(()), no_lookup).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("--candy-guard or --candy-machine"));
+
+        assert!(
+            resolve_candy_guard_with(Some("guard"), None, cache_file, |_| Ok(()), no_lookup)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,247 @@
+use std::{thread::sleep, time::Duration as StdDuration};
+
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use mpl_candy_guard::{
+    accounts::Update as UpdateAccount,
+    guards::{EndDate, StartDate},
+    instruction::Update,
+    state::{CandyGuardData, GuardSet, DATA_OFFSET},
+};
+
+use crate::{
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    guard::resolve_candy_guard,
+    output::style,
+    parse::{format_countdown, format_date, parse_date},
+    utils::*,
+};
+
+/// How long before the start time the on-chain dates are confirmed.
+const CONFIRM_BEFORE_START: i64 = 30;
+
+pub struct GuardScheduleArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub candy_machine: Option<String>,
+    pub candy_guard: Option<String>,
+    pub start: String,
+    pub end: String,
+    pub label: Option<String>,
+    pub wait: bool,
+    pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
+}
+
+pub fn process_guard_schedule(args: GuardScheduleArgs) -> Result<()> {
+    // the end date can be relative to the start date (e.g. +2h)
+    let start = parse_date(&args.start, Utc::now())?;
+    let end = parse_date(&args.end, start)?;
+
+    if end <= start {
+        return Err(anyhow!(
+            "End date ({}) must be after the start date ({})",
+            format_date(&end),
+            format_date(&start)
+        ));
+    }
+
+    report!("{} {}", style("Start:").bold(), format_date(&start));
+    report!("{} {}", style("End:").bold(), format_date(&end));
+    report!(
+        "{} {}",
+        style("Group:").bold(),
+        args.label.as_deref().unwrap_or("default")
+    );
+
+    report!(
+        "\n{} {}Loading candy guard",
+        style("[1/2]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(mpl_candy_guard::ID);
+
+    // the candy guard id specified takes precedence over the candy machine's
+    let candy_guard_id = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+    let data = load_candy_guard_data(&program, &candy_guard_id)?;
+    pb.finish_with_message("Done");
+
+    report!("{} {}", style("Candy guard ID:").bold(), candy_guard_id);
+
+    report!(
+        "\n{} {}Scheduling mint",
+        style("[2/2]").bold().dim(),
+        COMPUTER_EMOJI
+    );
+
+    let schedule = Schedule {
+        label: args.label,
+        start: start.timestamp(),
+        end: end.timestamp(),
+    };
+
+    let sig = send_schedule(
+        &program,
+        &candy_guard_id,
+        &sugar_config.keypair,
+        data,
+        &schedule,
+        args.priority_fee,
+        &args.compute_budget,
+    )?;
+    report!("{} {}", style("Signature:").bold(), sig);
+
+    if !args.wait {
+        return Ok(());
+    }
+
+    report!();
+    let pb = spinner_with_style();
+    let mut confirmed = false;
+
+    loop {
+        let remaining = start - Utc::now();
+
+        if remaining <= Duration::zero() {
+            break;
+        }
+
+        // re-sends the dates if the guard was changed in the meantime
+        if !confirmed && remaining <= Duration::seconds(CONFIRM_BEFORE_START) {
+            pb.set_message("Confirming on-chain dates...");
+            let mut data = load_candy_guard_data(&program, &candy_guard_id)?;
+
+            if schedule.matches(&mut data)? {
+                confirmed = true;
+            } else {
+                pb.println(format!(
+                    "{}Scheduled dates changed on-chain, re-sending",
+                    WARNING_EMOJI
+                ));
+                let sig = send_schedule(
+                    &program,
+                    &candy_guard_id,
+                    &sugar_config.keypair,
+                    data,
+                    &schedule,
+                    args.priority_fee,
+                    &args.compute_budget,
+                )?;
+                pb.println(format!("{} {}", style("Signature:").bold(), sig));
+                sleep(StdDuration::from_secs(1));
+            }
+            continue;
+        }
+
+        pb.set_message(format!(
+            "Mint opens in {}",
+            style(format_countdown(remaining)).bold()
+        ));
+        sleep(StdDuration::from_secs(1));
+    }
+
+    pb.finish_and_clear();
+    report!("{}Mint open, closing at {}", CANDY_EMOJI, format_date(&end));
+
+    Ok(())
+}
+
+struct Schedule {
+    label: Option<String>,
+    start: i64,
+    end: i64,
+}
+
+impl Schedule {
+    fn apply(&self, data: &mut CandyGuardData) -> Result<()> {
+        let guards = self.guard_set(data)?;
+        guards.start_date = Some(StartDate { date: self.start });
+        guards.end_date = Some(EndDate { date: self.end });
+        Ok(())
+    }
+
+    fn matches(&self, data: &mut CandyGuardData) -> Result<bool> {
+        let guards = self.guard_set(data)?;
+
+        Ok(
+            matches!(&guards.start_date, Some(d) if d.date == self.start)
+                && matches!(&guards.end_date, Some(d) if d.date == self.end),
+        )
+    }
+
+    fn guard_set<'a>(&self, data: &'a mut CandyGuardData) -> Result<&'a mut GuardSet> {
+        match &self.label {
+            None => Ok(&mut data.default),
+            Some(label) => data
+                .groups
+                .as_mut()
+                .and_then(|groups| groups.iter_mut().find(|group| &group.label == label))
+                .map(|group| &mut group.guards)
+                .ok_or_else(|| anyhow!("Group '{}' not found in the candy guard", label)),
+        }
+    }
+}
+
+fn load_candy_guard_data<C: std::ops::Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    candy_guard_id: &Pubkey,
+) -> Result<CandyGuardData> {
+    let account_data = program.rpc().get_account_data(candy_guard_id)?;
+    Ok(*CandyGuardData::load(&account_data[DATA_OFFSET..])?)
+}
+
+fn send_schedule<C: std::ops::Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    candy_guard_id: &Pubkey,
+    payer: &Keypair,
+    mut data: CandyGuardData,
+    schedule: &Schedule,
+    priority_fee: u64,
+    compute_budget: &ComputeBudget,
+) -> Result<Signature> {
+    schedule.apply(&mut data)?;
+
+    let mut serialized_data = vec![0; data.size()];
+    data.save(&mut serialized_data)?;
+
+    let pb = spinner_with_style();
+    pb.set_message("Sending update...");
+
+    let instructions = program
+        .request()
+        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
+            priority_fee,
+        ))
+        .accounts(UpdateAccount {
+            candy_guard: *candy_guard_id,
+            authority: payer.pubkey(),
+            payer: payer.pubkey(),
+            system_program: system_program::ID,
+        })
+        .args(Update {
+            data: serialized_data,
+        })
+        .instructions()?;
+
+    let sig = send_transaction(&program.rpc(), payer, &instructions, &[], compute_budget)?;
+
+    pb.finish_and_clear();
+
+    Ok(sig)
+}
@@ -1,51 +1,38 @@
-use std::str::FromStr;
-
-use anchor_client::solana_sdk::pubkey::Pubkey;
 use anyhow::Result;
 use chrono::NaiveDateTime;
-use console::style;
 use mpl_candy_guard::state::{CandyGuard, CandyGuardData, GuardSet, DATA_OFFSET};
 use mpl_candy_machine_core::constants::EMPTY_STR;
 use solana_program::native_token::LAMPORTS_PER_SOL;
 
-use crate::{cache::load_cache, common::*, show::print_with_style, utils::*};
+use crate::{
+    common::*, guard::resolve_candy_guard, output::style, show::print_with_style, utils::*,
+};
 
 pub struct GuardShowArgs {
     pub keypair: Option<String>,
     pub rpc_url: Option<String>,
     pub cache: String,
+    pub candy_machine: Option<String>,
     pub candy_guard: Option<String>,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_guard_show(args: GuardShowArgs) -> Result<()> {
-    println!("[1/1] {}Loading candy guard", LOOKING_GLASS_EMOJI);
-
-    // the candy guard id specified takes precedence over the one from the cache
-
-    let candy_guard_id = if let Some(candy_guard) = args.candy_guard {
-        candy_guard
-    } else {
-        let cache = load_cache(&args.cache, false)?;
-        cache.program.candy_guard
-    };
-
-    if candy_guard_id.is_empty() {
-        return Err(anyhow!("Missing candy guard id."));
-    }
-
-    let candy_guard_id = match Pubkey::from_str(&candy_guard_id) {
-        Ok(candy_guard_id) => candy_guard_id,
-        Err(_) => {
-            let error = anyhow!("Failed to parse candy guard id: {}", candy_guard_id);
-            error!("{:?}", error);
-            return Err(error);
-        }
-    };
+    report!("[1/1] {}Loading candy guard", LOOKING_GLASS_EMOJI);
 
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(mpl_candy_guard::ID);
 
+    // the candy guard id specified takes precedence over the candy machine's
+    let candy_guard_id = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
+
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
 
@@ -56,7 +43,7 @@ pub fn process_guard_show(args: GuardShowArgs) -> Result<()> {
 
     pb.finish_with_message("Done");
 
-    println!(
+    report!(
         "\n{}{} {}",
         GUARD_EMOJI,
         style("Candy Guard ID:").dim(),
@@ -65,7 +52,7 @@ pub fn process_guard_show(args: GuardShowArgs) -> Result<()> {
 
     // candy guard configuration
 
-    println!(" {}", style(":").dim());
+    report!(" {}", style(":").dim());
     print_with_style("", "base", account.base.to_string());
     print_with_style("", "bump", account.bump.to_string());
     print_with_style("", "authority", account.authority.to_string());
@@ -77,13 +64,13 @@ pub fn process_guard_show(args: GuardShowArgs) -> Result<()> {
 
     // groups
     if let Some(groups) = candy_guard_data.groups {
-        println!("     {}", style(":").dim());
+        report!("     {}", style(":").dim());
         print_with_style("    ", "groups", EMPTY_STR.to_string());
 
         for (index, group) in groups.iter().enumerate() {
             if index > 0 {
                 // padding between groups
-                println!("          {}", style(":").dim());
+                report!("          {}", style(":").dim());
             }
             print_with_style("         ", "label", &group.label);
             print_guard_set(
@@ -1,54 +1,52 @@
-use std::str::FromStr;
-
-use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
 use anyhow::Result;
-use console::style;
 use mpl_candy_guard::{accounts::Update as UpdateAccount, instruction::Update};
 
-use crate::{cache::load_cache, common::*, config::get_config_data, utils::*};
+use crate::{
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    config::get_config_data,
+    guard::{resolve_candy_guard, review_guard_update},
+    offline::{unsigned_setup, CacheUpdate, UnsignedTxArgs, UnsignedTxWriter},
+    output::style,
+    utils::*,
+};
 
 pub struct GuardUpdateArgs {
     pub keypair: Option<String>,
     pub rpc_url: Option<String>,
     pub cache: String,
     pub config: String,
+    pub candy_machine: Option<String>,
     pub candy_guard: Option<String>,
     pub priority_fee: u64,
+    pub dry_run: bool,
+    pub yes: bool,
+    pub unsigned_tx: Option<UnsignedTxArgs>,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_guard_update(args: GuardUpdateArgs) -> Result<()> {
-    println!(
+    report!(
         "{} {}Loading candy guard",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
 
-    // the candy guard id specified takes precedence over the one from the cache
-
-    let candy_guard_id = if let Some(candy_guard) = args.candy_guard {
-        candy_guard
-    } else {
-        let cache = load_cache(&args.cache, false)?;
-        cache.program.candy_guard
-    };
-
-    if candy_guard_id.is_empty() {
-        return Err(anyhow!("Missing candy guard id."));
-    }
-
-    let candy_guard_id = match Pubkey::from_str(&candy_guard_id) {
-        Ok(candy_guard_id) => candy_guard_id,
-        Err(_) => {
-            let error = anyhow!("Failed to parse candy guard id: {}", candy_guard_id);
-            error!("{:?}", error);
-            return Err(error);
-        }
-    };
-
-    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let (sugar_config, authority) =
+        unsigned_setup(args.keypair, args.rpc_url, args.unsigned_tx.as_ref())?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(mpl_candy_guard::ID);
-    let payer = sugar_config.keypair;
+
+    // the candy guard id specified takes precedence over the candy machine's
+    let candy_guard_id = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -56,9 +54,9 @@ pub fn process_guard_update(args: GuardUpdateArgs) -> Result<()> {
     let _account = program.rpc().get_account(&candy_guard_id)?;
     pb.finish_with_message("Done");
 
-    println!("{} {}", style("Candy guard ID:").bold(), candy_guard_id);
+    report!("{} {}", style("Candy guard ID:").bold(), candy_guard_id);
 
-    println!(
+    report!(
         "\n{} {}Updating configuration",
         style("[2/2]").bold().dim(),
         COMPUTER_EMOJI
@@ -71,6 +69,13 @@ pub fn process_guard_update(args: GuardUpdateArgs) -> Result<()> {
         return Err(anyhow!("Missing guards configuration."));
     };
 
+    if !review_guard_update(&program, &candy_guard_id, &data, args.dry_run, args.yes)? {
+        if !args.dry_run {
+            report!("Update cancelled.");
+        }
+        return Ok(());
+    }
+
     let mut serialized_data = vec![0; data.size()];
     data.save(&mut serialized_data)?;
 
@@ -79,23 +84,45 @@ pub fn process_guard_update(args: GuardUpdateArgs) -> Result<()> {
 
     let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
-    let tx = program
+    let instructions = program
         .request()
         .instruction(priority_fee)
         .accounts(UpdateAccount {
             candy_guard: candy_guard_id,
-            authority: payer.pubkey(),
-            payer: payer.pubkey(),
+            authority,
+            payer: authority,
             system_program: system_program::ID,
         })
         .args(Update {
             data: serialized_data,
-        });
-
-    let sig = tx.send()?;
-
-    pb.finish_and_clear();
-    println!("{} {}", style("Signature:").bold(), sig);
+        })
+        .instructions()?;
+
+    if let Some(unsigned_tx) = &args.unsigned_tx {
+        pb.finish_and_clear();
+
+        let mut writer = UnsignedTxWriter::new("guard update", unsigned_tx, authority)?;
+        writer.add(
+            &program.rpc(),
+            "update-candy-guard",
+            "Update the candy guard",
+            &instructions,
+            &[],
+            CacheUpdate::None,
+        )?;
+        writer.finish()?;
+    } else {
+        let sig = send_transaction(
+            &program.rpc(),
+            &sugar_config.keypair,
+            &instructions,
+            &[],
+            &args.compute_budget,
+        )?;
+
+        pb.finish_and_clear();
+        report!("{} {}", style("Signature:").bold(), sig);
+    }
 
     Ok(())
 }
@@ -1,49 +1,44 @@
-use std::str::FromStr;
-
-use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
 use anyhow::Result;
-use console::style;
 use mpl_candy_guard::{accounts::Withdraw as WithdrawAccount, instruction::Withdraw};
 use solana_program::native_token::LAMPORTS_PER_SOL;
 
-use crate::{cache::load_cache, common::*, utils::*};
+use crate::{
+    cache::load_cache,
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    guard::resolve_candy_guard,
+    output::style,
+    utils::*,
+};
 
 pub struct GuardWithdrawArgs {
     pub keypair: Option<String>,
     pub rpc_url: Option<String>,
     pub cache: String,
+    pub candy_machine: Option<String>,
     pub candy_guard: Option<String>,
     pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_guard_withdraw(args: GuardWithdrawArgs) -> Result<()> {
-    println!("[1/2] {}Loading candy guard", LOOKING_GLASS_EMOJI);
-
-    // the candy guard id specified takes precedence over the one from the cache
-
-    let (candy_guard_id, cache) = if let Some(candy_guard) = args.candy_guard {
-        (candy_guard, None)
-    } else {
-        let cache = load_cache(&args.cache, false)?;
-        (cache.program.candy_guard.clone(), Some(cache))
-    };
-
-    if candy_guard_id.is_empty() {
-        return Err(anyhow!("Missing candy guard id."));
-    }
-
-    let candy_guard_id = match Pubkey::from_str(&candy_guard_id) {
-        Ok(candy_guard_id) => candy_guard_id,
-        Err(_) => {
-            let error = anyhow!("Failed to parse candy guard id: {}", candy_guard_id);
-            error!("{:?}", error);
-            return Err(error);
-        }
-    };
+    report!("[1/2] {}Loading candy guard", LOOKING_GLASS_EMOJI);
 
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(mpl_candy_guard::ID);
+
+    // the candy guard id specified takes precedence over the candy machine's
+    let candy_guard_id = resolve_candy_guard(
+        &program,
+        args.candy_guard.as_deref(),
+        args.candy_machine.as_deref(),
+        &args.cache,
+        args.allow_cluster_mismatch,
+    )?;
+
     let payer = sugar_config.keypair;
 
     let pb = spinner_with_style();
@@ -53,28 +48,35 @@ pub fn process_guard_withdraw(args: GuardWithdrawArgs) -> Result<()> {
 
     pb.finish_with_message("Done");
 
-    println!("\n[2/2] {}Retrieving funds", WITHDRAW_EMOJI);
+    report!("\n[2/2] {}Retrieving funds", WITHDRAW_EMOJI);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
 
     let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
-    let tx = program
+    let instructions = program
         .request()
         .instruction(priority_fee)
         .accounts(WithdrawAccount {
             candy_guard: candy_guard_id,
             authority: payer.pubkey(),
         })
-        .args(Withdraw {});
+        .args(Withdraw {})
+        .instructions()?;
 
-    let sig = tx.send()?;
+    let sig = send_transaction(
+        &program.rpc(),
+        &payer,
+        &instructions,
+        &[],
+        &args.compute_budget,
+    )?;
 
     pb.finish_and_clear();
-    println!("{} {}", style("Signature:").bold(), sig);
+    report!("{} {}", style("Signature:").bold(), sig);
 
-    println!(
+    report!(
         "\nReceived ◎ {} from rent fee.",
         (account.lamports as f64) / (LAMPORTS_PER_SOL as f64)
     );
@@ -82,10 +84,12 @@ pub fn process_guard_withdraw(args: GuardWithdrawArgs) -> Result<()> {
     // if we closed the candy guard from the cache file, remove
     // its reference
 
-    if cache.is_some() {
+    if Path::new(&args.cache).exists() {
         let mut cache = load_cache(&args.cache, false)?;
-        cache.program.candy_guard = String::new();
-        cache.sync_file()?;
+        if cache.program.candy_guard == candy_guard_id.to_string() {
+            cache.program.candy_guard = String::new();
+            cache.sync_file()?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,231 @@
+use std::{ops::Deref, str::FromStr};
+
+use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
+use anyhow::Result;
+use mpl_candy_guard::{accounts::Wrap as WrapAccount, instruction::Wrap};
+
+use crate::{
+    cache::load_cache,
+    candy_machine::*,
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    config::SugarConfig,
+    output::style,
+    utils::*,
+};
+
+pub struct GuardWrapArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub candy_machine: Option<String>,
+    pub candy_guard: Option<String>,
+    pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
+}
+
+pub fn process_guard_wrap(args: GuardWrapArgs) -> Result<()> {
+    report!("[1/1] {}Wrapping", WRAP_EMOJI);
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(mpl_candy_guard::ID);
+
+    let (candy_machine_id, candy_guard_id) =
+        resolve_wrap_ids(args.candy_machine, args.candy_guard, || {
+            let cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
+            Ok(cache)
+        })?;
+
+    let candy_machine = get_candy_machine_state(&sugar_config, &candy_machine_id)?;
+    report!(
+        "{} {}",
+        style("Mint authority (before):").bold(),
+        candy_machine.mint_authority
+    );
+
+    if is_wrapped(&candy_machine.mint_authority, &candy_guard_id) {
+        report!(
+            "\nThe candy machine is already wrapped: the candy guard {} is its mint authority.",
+            candy_guard_id
+        );
+        return Ok(());
+    }
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let sig = wrap_candy_machine(
+        &program,
+        &sugar_config.keypair,
+        &candy_guard_id,
+        &candy_machine_id,
+        args.priority_fee,
+        &args.compute_budget,
+    )?;
+
+    pb.finish_and_clear();
+    report!("{} {}", style("Signature:").bold(), sig);
+
+    print_mint_authority(&sugar_config, &candy_machine_id, "Mint authority (after):")?;
+
+    report!("\nThe candy guard is now the mint authority of the candy machine.");
+
+    Ok(())
+}
+
+/// Returns the ids of the candy machine and candy guard to wrap. The ids specified take
+/// precedence over the ones from the cache, which is loaded (once) only when an id is missing.
+fn resolve_wrap_ids(
+    candy_machine: Option<String>,
+    candy_guard: Option<String>,
+    read_cache: impl FnOnce() -> Result<Cache>,
+) -> Result<(Pubkey, Pubkey)> {
+    let (candy_machine_id, candy_guard_id) = match (candy_machine, candy_guard) {
+        (Some(candy_machine), Some(candy_guard)) => (candy_machine, candy_guard),
+        (candy_machine, candy_guard) => {
+            let cache = read_cache()?;
+            (
+                candy_machine.unwrap_or(cache.program.candy_machine),
+                candy_guard.unwrap_or(cache.program.candy_guard),
+            )
+        }
+    };
+
+    let candy_machine_id = match Pubkey::from_str(&candy_machine_id) {
+        Ok(candy_machine_id) => candy_machine_id,
+        Err(_) => {
+            let error = anyhow!("Failed to parse candy machine id: {}", candy_machine_id);
+            error!("{:?}", error);
+            return Err(error);
+        }
+    };
+
+    if candy_guard_id.is_empty() {
+        return Err(anyhow!(
+            "Missing candy guard id, run 'sugar guard add --no-wrap' to create one."
+        ));
+    }
+
+    let candy_guard_id = match Pubkey::from_str(&candy_guard_id) {
+        Ok(candy_guard_id) => candy_guard_id,
+        Err(_) => {
+            let error = anyhow!("Failed to parse candy guard id: {}", candy_guard_id);
+            error!("{:?}", error);
+            return Err(error);
+        }
+    };
+
+    Ok((candy_machine_id, candy_guard_id))
+}
+
+/// Checks if the candy guard is already the mint authority of the candy machine.
+fn is_wrapped(mint_authority: &Pubkey, candy_guard: &Pubkey) -> bool {
+    mint_authority == candy_guard
+}
+
+/// Sets the candy guard as the mint authority of the candy machine.
+pub fn wrap_candy_machine<C: Deref<Target = impl Signer> + Clone>(
+    program: &Program<C>,
+    keypair: &Keypair,
+    candy_guard: &Pubkey,
+    candy_machine: &Pubkey,
+    priority_fee: u64,
+    compute_budget: &ComputeBudget,
+) -> Result<Signature> {
+    let payer = program.payer();
+    let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
+
+    let instructions = program
+        .request()
+        .instruction(priority_fee)
+        .accounts(WrapAccount {
+            candy_guard: *candy_guard,
+            authority: payer,
+            candy_machine: *candy_machine,
+            candy_machine_program: CANDY_MACHINE_ID,
+            candy_machine_authority: payer,
+        })
+        .args(Wrap {})
+        .instructions()?;
+
+    send_transaction(&program.rpc(), keypair, &instructions, &[], compute_budget)
+}
+
+pub fn print_mint_authority(
+    sugar_config: &SugarConfig,
+    candy_machine_id: &Pubkey,
+    label: &str,
+) -> Result<()> {
+    let candy_machine = get_candy_machine_state(sugar_config, candy_machine_id)?;
+    report!("{} {}", style(label).bold(), candy_machine.mint_authority);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    fn cache_with(candy_machine: &Pubkey, candy_guard: &str) -> Cache {
+        let mut cache = Cache::new();
+        cache.program.candy_machine = candy_machine.to_string();
+        cache.program.candy_guard = candy_guard.to_string();
+        cache
+    }
+
+    #[test]
+    fn test_resolve_wrap_ids() {
+        let candy_machine = Pubkey::new_unique();
+        let candy_guard = Pubkey::new_unique();
+        let other_guard = Pubkey::new_unique();
+        let loads = Cell::new(0);
+        let load = |cache_guard: String| {
+            let loads = &loads;
+            move || {
+                loads.set(loads.get() + 1);
+                Ok(cache_with(&candy_machine, &cache_guard))
+            }
+        };
+
+        // both ids specified: the cache is not read
+        let ids = resolve_wrap_ids(
+            Some(candy_machine.to_string()),
+            Some(candy_guard.to_string()),
+            || Err(anyhow!("no cache")),
+        )
+        .unwrap();
+        assert_eq!(ids, (candy_machine, candy_guard));
+
+        // the missing ids come from a single read of the cache
+        let ids = resolve_wrap_ids(None, None, load(candy_guard.to_string())).unwrap();
+        assert_eq!(ids, (candy_machine, candy_guard));
+        assert_eq!(loads.get(), 1);
+
+        // the id specified takes precedence over the cache's
+        let ids = resolve_wrap_ids(
+            None,
+            Some(other_guard.to_string()),
+            load(candy_guard.to_string()),
+        )
+        .unwrap();
+        assert_eq!(ids, (candy_machine, other_guard));
+        assert_eq!(loads.get(), 2);
+
+        let error = resolve_wrap_ids(None, None, load(String::new())).unwrap_err();
+        assert!(error.to_string().contains("sugar guard add --no-wrap"));
+        assert!(resolve_wrap_ids(None, Some("guard".to_string()), load(String::new())).is_err());
+    }
+
+    #[test]
+    fn test_is_wrapped() {
+        let candy_guard = Pubkey::new_unique();
+
+        assert!(is_wrapped(&candy_guard, &candy_guard));
+        assert!(!is_wrapped(&Pubkey::new_unique(), &candy_guard));
+    }
+}
@@ -3,12 +3,12 @@ use std::{
     io::{BufReader, Read},
 };
 
-use console::style;
 use sha2::{Digest, Sha256};
 
 use crate::{
     common::*,
     config::{get_config_data, ConfigData, HiddenSettings},
+    output::style,
 };
 
 pub struct HashArgs {
@@ -20,9 +20,6 @@ pub struct HashArgs {
 pub fn process_hash(args: HashArgs) -> Result<()> {
     let mut config_data = get_config_data(&args.config)?;
 
-    // We use std::process::exit to exit the program without going to the main handling which prints
-    // "Command successful".
-
     if let Some(hash) = args.compare {
         let mut hasher = Sha256::new();
 
@@ -36,23 +33,18 @@ pub fn process_hash(args: HashArgs) -> Result<()> {
         let hash_base58 = bs58::encode(&hasher.finalize()).into_string();
         let expected_hash = hash_base58.chars().take(32).collect::<String>();
         if hash != expected_hash {
-            println!(
-                "{} {}",
-                ERROR_EMOJI,
-                style("Hashes do not match!").red().bold()
-            );
-            std::process::exit(0);
+            return Err(anyhow!("Hashes do not match!"));
         }
-        println!(
+        report!(
             "{} {}",
             COMPLETE_EMOJI,
             style("Hashes match!").blue().bold()
         );
-        std::process::exit(0);
+        return Ok(());
     }
 
     if let Some(ref hidden_settings) = config_data.hidden_settings {
-        println!(
+        report!(
             "hash: {}",
             hash_and_update(
                 hidden_settings.clone(),
@@ -61,12 +53,12 @@ pub fn process_hash(args: HashArgs) -> Result<()> {
                 &args.cache,
             )?
         );
-        println!(
+        report!(
             "{} {}",
             COMPLETE_EMOJI,
             style("Config file updated with hash!").blue().bold()
         );
-        std::process::exit(0);
+        Ok(())
     } else {
         Err(anyhow!("No hidden settings found in config file."))
     }
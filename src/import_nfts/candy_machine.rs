@@ -0,0 +1,233 @@
+//! Rebuilds the cache of a deployed candy machine from its on-chain config lines.
+
+use borsh::BorshDeserialize;
+use mpl_candy_machine_core::{constants::HIDDEN_SECTION, ConfigLineSettings};
+use mpl_token_metadata::state::Metadata;
+
+use crate::{
+    cache::{format_indices, CacheItems, CacheProgram},
+    candy_machine::{loaded_config_lines, CANDY_MACHINE_ID},
+    common::*,
+    config::SugarConfig,
+    guard::find_wrapping_candy_guard,
+    output::style,
+    pdas::find_metadata_pda,
+};
+
+/// Reads the program section and the items of a cache from a candy machine account.
+///
+/// The items are keyed by the index of their config line and marked as on-chain; lines that
+/// were never written have no item (their indices are reported). The collection NFT is
+/// added as the `-1` item when its metadata can be read.
+pub fn read_candy_machine(
+    sugar_config: &SugarConfig,
+    candy_machine: &str,
+) -> Result<(CacheProgram, CacheItems)> {
+    let candy_machine_id = Pubkey::from_str(candy_machine)
+        .map_err(|_| CacheError::InvalidCandyMachineAddress(candy_machine.to_string()))?;
+
+    let client = setup_client(sugar_config)?;
+    let program = client.program(CANDY_MACHINE_ID);
+
+    let account = program
+        .rpc()
+        .get_account(&candy_machine_id)
+        .map_err(|_| anyhow!("Candy machine {} not found", candy_machine_id))?;
+
+    if account.owner != CANDY_MACHINE_ID {
+        return Err(anyhow!(
+            "Account {} is not a candy machine",
+            candy_machine_id
+        ));
+    }
+
+    let state = CandyMachine::try_deserialize(&mut account.data.as_slice())?;
+
+    if state.data.hidden_settings.is_some() {
+        return Err(anyhow!(
+            "Candy machine {} uses hidden settings: its items are not stored on-chain, so a \
+            cache can't be rebuilt from it. Import the metadata links of the items instead \
+            (sugar import -i <FILE>).",
+            candy_machine_id
+        ));
+    }
+
+    let config_line_settings = state
+        .data
+        .config_line_settings
+        .as_ref()
+        .ok_or_else(|| anyhow!("Could not determine candy machine config line settings"))?;
+
+    let (mut items, missing) = read_config_lines(
+        &account.data,
+        state.data.items_available,
+        config_line_settings,
+    )?;
+
+    if items.is_empty() {
+        return Err(anyhow!(
+            "Candy machine {} has no config lines written yet",
+            candy_machine_id
+        ));
+    }
+
+    if !missing.is_empty() {
+        report!(
+            "{} {} of {} config line(s) were never written and have no cache item: {}",
+            WARNING_EMOJI,
+            missing.len(),
+            state.data.items_available,
+            style(format_indices(&missing)).dim()
+        );
+        report!(
+            "Add their metadata links with 'sugar import --append --start-index <INDEX>' \
+            before re-running 'deploy'."
+        );
+    }
+
+    let mut cache_program = CacheProgram::new_from_cm(&candy_machine_id);
+    cache_program.collection_mint = state.collection_mint.to_string();
+    cache_program.candy_machine_program = Some(CANDY_MACHINE_ID.to_string());
+    cache_program.genesis_hash = Some(program.rpc().get_genesis_hash()?.to_string());
+
+    if let Ok(candy_guard) = find_wrapping_candy_guard(&program, &candy_machine_id) {
+        cache_program.candy_guard = candy_guard.to_string();
+        cache_program.candy_guard_program = Some(mpl_candy_guard::ID.to_string());
+    }
+
+    // the collection item is informative only (its image is not on-chain)
+    let collection_item = program
+        .rpc()
+        .get_account_data(&find_metadata_pda(&state.collection_mint))
+        .map_err(anyhow::Error::from)
+        .and_then(|data| Ok(Metadata::deserialize(&mut data.as_slice())?));
+
+    match collection_item {
+        Ok(metadata) => {
+            let mut with_collection = CacheItems::new();
+            with_collection.insert(
+                "-1".to_string(),
+                CacheItem {
+                    name: metadata.data.name.trim_matches(char::from(0)).to_string(),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: metadata.data.uri.trim_matches(char::from(0)).to_string(),
+                    on_chain: true,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+            with_collection.extend(items.0);
+            items = with_collection;
+        }
+        Err(err) => warn!(
+            "Failed to read the collection metadata of {}: {}",
+            state.collection_mint, err
+        ),
+    }
+
+    Ok((cache_program, items))
+}
+
+/// Decodes the written config lines of the raw candy machine account, returning the items
+/// and the indices of the lines that were never written.
+fn read_config_lines(
+    data: &[u8],
+    items_available: u64,
+    settings: &ConfigLineSettings,
+) -> Result<(CacheItems, Vec<u64>)> {
+    let name_length = settings.name_length as usize;
+    let uri_length = settings.uri_length as usize;
+    let line_size = name_length + uri_length;
+
+    let lines_start = HIDDEN_SECTION + STRING_LEN_SIZE;
+    let loaded = loaded_config_lines(data, items_available, settings)?;
+
+    let mut items = CacheItems::new();
+    let mut missing = Vec::new();
+
+    for i in 0..items_available {
+        if !loaded[i as usize] {
+            missing.push(i);
+            continue;
+        }
+
+        let name_start = lines_start + line_size * (i as usize);
+        let uri_start = name_start + name_length;
+
+        let decode = |start: usize, length: usize, what: &str| -> Result<String> {
+            Ok(String::from_utf8(data[start..start + length].to_vec())
+                .map_err(|_| anyhow!("Failed to decode {} of item {}", what, i))?
+                .trim_matches(char::from(0))
+                .to_string())
+        };
+
+        let name = settings.prefix_name.clone() + decode(name_start, name_length, "name")?.as_str();
+        let uri = settings.prefix_uri.clone() + decode(uri_start, uri_length, "uri")?.as_str();
+
+        items.insert(
+            i.to_string(),
+            CacheItem {
+                name: replace_id(&name, i),
+                image_hash: String::new(),
+                image_link: String::new(),
+                metadata_hash: String::new(),
+                metadata_link: replace_id(&uri, i),
+                on_chain: true,
+                animation_hash: None,
+                animation_link: None,
+                optimized_image_hash: None,
+            },
+        );
+    }
+
+    Ok((items, missing))
+}
+
+/// Replaces the `$ID$` and `$ID+1$` variables of a prefix, as the candy machine does on mint.
+fn replace_id(value: &str, index: u64) -> String {
+    value
+        .replace("$ID+1$", &(index + 1).to_string())
+        .replace("$ID$", &index.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_partially_written_config_lines() {
+        let settings = ConfigLineSettings {
+            prefix_name: "Numbers #$ID+1$".to_string(),
+            name_length: 0,
+            prefix_uri: "https://arweave.net/".to_string(),
+            uri_length: 4,
+            is_sequential: false,
+        };
+
+        let items_available = 10u64;
+        let lines_start = HIDDEN_SECTION + STRING_LEN_SIZE;
+        let bitmask_start = lines_start + 4 * items_available as usize;
+        let mut data = vec![0u8; bitmask_start + 2];
+
+        // lines 0-2 and 9 are written
+        for i in [0usize, 1, 2, 9] {
+            let uri = format!("m{:03}", i);
+            data[lines_start + 4 * i..lines_start + 4 * i + 4].copy_from_slice(uri.as_bytes());
+            data[bitmask_start + i / 8] |= 0x80 >> (i % 8);
+        }
+
+        let (items, missing) = read_config_lines(&data, items_available, &settings).unwrap();
+
+        let keys: Vec<&str> = items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["0", "1", "2", "9"]);
+        assert_eq!(items["1"].name, "Numbers #2");
+        assert_eq!(items["9"].metadata_link, "https://arweave.net/m009");
+        assert!(items["9"].on_chain);
+        assert_eq!(format_indices(&missing), "3-8");
+
+        assert!(read_config_lines(&data[..bitmask_start], items_available, &settings).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+//! CSV input of `sugar import`, e.g. a hashlist or indexer export:
+//!
+//! ```csv
+//! index,name,metadata_uri,image_uri
+//! 0,"Numbers #1",https://arweave.net/<tx>,https://arweave.net/<tx>
+//! ```
+//!
+//! The columns are found by their header (see [`ColumnMap`]); a file without a header row
+//! uses the positions of the columns instead. Fields can be quoted (`""` escapes a quote
+//! inside a quoted field) and lines can end with `\r\n`.
+
+use std::{fmt, path::Path, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use crate::cache::CacheItem;
+
+/// Format of the file of `sugar import`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// One metadata link per line.
+    Txt,
+    /// CSV with (at least) a metadata link column.
+    Csv,
+    /// JSON array of metadata links or objects.
+    Json,
+    /// JSON Lines of metadata links or objects.
+    Jsonl,
+}
+
+impl ImportFormat {
+    /// Returns the format of the file from its extension (`.csv`, `.json` or `.jsonl`), text
+    /// otherwise.
+    pub fn from_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+
+        match extension.as_deref() {
+            Some("csv") => ImportFormat::Csv,
+            Some("json") => ImportFormat::Json,
+            Some("jsonl") => ImportFormat::Jsonl,
+            _ => ImportFormat::Txt,
+        }
+    }
+}
+
+impl FromStr for ImportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "txt" => Ok(ImportFormat::Txt),
+            "csv" => Ok(ImportFormat::Csv),
+            "json" => Ok(ImportFormat::Json),
+            "jsonl" => Ok(ImportFormat::Jsonl),
+            _ => Err(anyhow!(
+                "Invalid import format '{}', expected 'txt', 'csv', 'json' or 'jsonl'",
+                value
+            )),
+        }
+    }
+}
+
+impl fmt::Display for ImportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ImportFormat::Txt => write!(f, "txt"),
+            ImportFormat::Csv => write!(f, "csv"),
+            ImportFormat::Json => write!(f, "json"),
+            ImportFormat::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+// cache fields that can be read from a column, with their default headers
+const FIELDS: [(&str, &[&str]); 4] = [
+    ("name", &["name"]),
+    (
+        "metadata_link",
+        &[
+            "metadata_uri",
+            "metadata_link",
+            "metadata_url",
+            "metadata",
+            "uri",
+        ],
+    ),
+    (
+        "image_link",
+        &["image_uri", "image_link", "image_url", "image"],
+    ),
+    (
+        "animation_link",
+        &["animation_uri", "animation_link", "animation_url"],
+    ),
+];
+
+/// Columns of the cache fields (0-based).
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ColumnMap {
+    pub name: Option<usize>,
+    pub metadata_link: Option<usize>,
+    pub image_link: Option<usize>,
+    pub animation_link: Option<usize>,
+}
+
+impl ColumnMap {
+    /// Finds the columns of the cache fields in the header. `overrides` maps cache fields to
+    /// a header or a 1-based column number (e.g. `name=title,metadata_link=3`).
+    pub fn from_header(header: &[String], overrides: &[(String, String)]) -> Result<Self> {
+        let mut map = ColumnMap::default();
+
+        for (field, defaults) in FIELDS {
+            let column = match overrides.iter().find(|(name, _)| name == field) {
+                Some((_, column)) => Some(find_column(header, column).ok_or_else(|| {
+                    anyhow!(
+                        "Column '{}' of {} not found in the CSV header",
+                        column,
+                        field
+                    )
+                })?),
+                None => defaults
+                    .iter()
+                    .find_map(|default| find_column(header, default)),
+            };
+            map.set(field, column);
+        }
+
+        Ok(map)
+    }
+
+    /// Columns of a file without a header: `[index,]name,metadata_uri[,image_uri]` depending
+    /// on the number of columns (a single column is the metadata link), with the column
+    /// numbers of `overrides` taking precedence.
+    pub fn positional(width: usize, overrides: &[(String, String)]) -> Result<Self> {
+        let mut map = match width {
+            0 | 1 => ColumnMap {
+                metadata_link: Some(0),
+                ..Default::default()
+            },
+            2 => ColumnMap {
+                name: Some(0),
+                metadata_link: Some(1),
+                ..Default::default()
+            },
+            3 => ColumnMap {
+                name: Some(0),
+                metadata_link: Some(1),
+                image_link: Some(2),
+                ..Default::default()
+            },
+            _ => ColumnMap {
+                name: Some(1),
+                metadata_link: Some(2),
+                image_link: Some(3),
+                ..Default::default()
+            },
+        };
+
+        for (field, column) in overrides {
+            let column = column
+                .parse::<usize>()
+                .ok()
+                .filter(|column| *column > 0)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "The CSV file has no header: map {} to a column number, not '{}'",
+                        field,
+                        column
+                    )
+                })?;
+            map.set(field, Some(column - 1));
+        }
+
+        Ok(map)
+    }
+
+    fn set(&mut self, field: &str, column: Option<usize>) {
+        match field {
+            "name" => self.name = column,
+            "metadata_link" => self.metadata_link = column,
+            "image_link" => self.image_link = column,
+            _ => self.animation_link = column,
+        }
+    }
+}
+
+/// Parses a `--column-map` value: comma-separated `field=column` pairs.
+pub fn parse_column_map(value: &str) -> Result<Vec<(String, String)>> {
+    value
+        .split(',')
+        .filter(|pair| !pair.trim().is_empty())
+        .map(|pair| {
+            let (field, column) = pair.split_once('=').ok_or_else(|| {
+                anyhow!("Invalid column mapping '{}', expected field=column", pair)
+            })?;
+            let field = field.trim();
+
+            if !FIELDS.iter().any(|(name, _)| *name == field) {
+                return Err(anyhow!(
+                    "Unknown cache field '{}', expected one of: {}",
+                    field,
+                    FIELDS.map(|(name, _)| name).join(", ")
+                ));
+            }
+
+            Ok((field.to_string(), column.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Reads the cache items of a CSV file in the order of the rows, with the line number of each
+/// row (items without a name column have an empty name).
+pub fn read_csv_items(
+    content: &str,
+    column_map: &[(String, String)],
+) -> Result<Vec<(usize, CacheItem)>> {
+    let mut rows = parse_csv(content)?.into_iter().peekable();
+
+    let has_header = rows
+        .peek()
+        .map(|(_, row)| !row.iter().any(|field| is_link(field)))
+        .unwrap_or(false);
+
+    let columns = if has_header {
+        let (_, header) = rows.next().unwrap_or_default();
+        ColumnMap::from_header(&header, column_map)?
+    } else {
+        let width = rows.peek().map(|(_, row)| row.len()).unwrap_or_default();
+        ColumnMap::positional(width, column_map)?
+    };
+
+    let metadata_column = columns
+        .metadata_link
+        .ok_or_else(|| anyhow!("No metadata link column in the CSV header (use --column-map)"))?;
+
+    let mut items = Vec::new();
+
+    for (line, row) in rows {
+        let field = |column: Option<usize>| {
+            column
+                .and_then(|column| row.get(column))
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty())
+        };
+
+        let metadata_link = field(Some(metadata_column))
+            .ok_or_else(|| anyhow!("Missing metadata link in line {} of the CSV file", line))?;
+
+        items.push((
+            line,
+            CacheItem {
+                name: field(columns.name).unwrap_or_default().to_string(),
+                image_hash: String::new(),
+                image_link: field(columns.image_link).unwrap_or_default().to_string(),
+                metadata_hash: String::new(),
+                metadata_link: metadata_link.to_string(),
+                on_chain: false,
+                animation_hash: None,
+                animation_link: field(columns.animation_link).map(String::from),
+                optimized_image_hash: None,
+            },
+        ));
+    }
+
+    Ok(items)
+}
+
+/// Splits CSV content into rows of fields with the line number where they start, skipping
+/// blank lines.
+fn parse_csv(content: &str) -> Result<Vec<(usize, Vec<String>)>> {
+    let content = content.trim_start_matches('\u{feff}');
+
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut row_line = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' => quoted = true,
+            ',' => row.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\n' => {
+                row.push(std::mem::take(&mut field));
+                push_row(&mut rows, row_line, std::mem::take(&mut row));
+                line += 1;
+                row_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if quoted {
+        return Err(anyhow!(
+            "Unterminated quoted field in line {} of the CSV file",
+            row_line
+        ));
+    }
+
+    row.push(field);
+    push_row(&mut rows, row_line, row);
+
+    Ok(rows)
+}
+
+fn push_row(rows: &mut Vec<(usize, Vec<String>)>, line: usize, row: Vec<String>) {
+    if row.iter().any(|field| !field.trim().is_empty()) {
+        rows.push((line, row));
+    }
+}
+
+fn find_column(header: &[String], column: &str) -> Option<usize> {
+    header
+        .iter()
+        .position(|name| name.trim().eq_ignore_ascii_case(column))
+        .or_else(|| {
+            // 1-based column number
+            column
+                .parse::<usize>()
+                .ok()
+                .filter(|number| *number > 0 && *number <= header.len())
+                .map(|number| number - 1)
+        })
+}
+
+fn is_link(value: &str) -> bool {
+    let value = value.trim();
+    ["http://", "https://", "ar://", "ipfs://"]
+        .iter()
+        .any(|scheme| value.starts_with(scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_with_header() {
+        let content = "index,name,metadata_uri,image_uri\r\n\
+            0,\"Numbers #1, \"\"blue\"\"\",https://arweave.net/m0,https://arweave.net/i0\r\n\
+            \r\n\
+            1,Numbers #2,https://arweave.net/m1,https://arweave.net/i1\r\n";
+
+        let items = read_csv_items(content, &[]).unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].1.name, "Numbers #1, \"blue\"");
+        assert_eq!(items[0].1.metadata_link, "https://arweave.net/m0");
+        assert_eq!(items[0].1.image_link, "https://arweave.net/i0");
+        assert_eq!(items[1].1.image_link, "https://arweave.net/i1");
+        assert!(items[1].1.animation_link.is_none());
+        // the blank line is skipped, not the line numbers
+        assert_eq!((items[0].0, items[1].0), (2, 4));
+    }
+
+    #[test]
+    fn test_csv_column_map_and_positional_fallback() {
+        let content = "title,uri,preview\nOne,https://arweave.net/m0,https://arweave.net/i0\n";
+        let map = parse_column_map("name=title, image_link=3").unwrap();
+        let items = read_csv_items(content, &map).unwrap();
+        assert_eq!(items[0].1.name, "One");
+        assert_eq!(items[0].1.metadata_link, "https://arweave.net/m0");
+        assert_eq!(items[0].1.image_link, "https://arweave.net/i0");
+
+        assert!(parse_column_map("symbol=2").is_err());
+        assert!(read_csv_items(content, &parse_column_map("name=missing").unwrap()).is_err());
+
+        // no header: index,name,metadata,image
+        let content = "0,One,https://arweave.net/m0,https://arweave.net/i0\n\
+            1,Two,https://arweave.net/m1,https://arweave.net/i1";
+        let items = read_csv_items(content, &[]).unwrap();
+        assert_eq!(items[1].1.name, "Two");
+        assert_eq!(items[1].1.metadata_link, "https://arweave.net/m1");
+
+        // a single column of links
+        let items = read_csv_items("https://arweave.net/m0\n", &[]).unwrap();
+        assert_eq!(items[0].1.name, "");
+        assert_eq!(items[0].1.image_link, "");
+
+        assert!(read_csv_items("\"https://arweave.net/m0\n", &[]).is_err());
+        assert_eq!(
+            ImportFormat::from_path(Path::new("hashlist.CSV")),
+            ImportFormat::Csv
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("links.txt")),
+            ImportFormat::Txt
+        );
+        assert_eq!(
+            ImportFormat::from_path(Path::new("links.jsonl")),
+            ImportFormat::Jsonl
+        );
+    }
+}
@@ -0,0 +1,210 @@
+//! Import of a hashlist (a JSON array of mint addresses): the cache items are read from the
+//! metadata accounts of the mints.
+
+use std::{fmt, thread, time::Duration};
+
+use borsh::BorshDeserialize;
+use mpl_token_metadata::state::Metadata;
+
+use crate::{
+    account_fetcher::{AccountSource, MAX_MULTIPLE_ACCOUNTS},
+    common::*,
+    pdas::find_metadata_pda,
+    utils::progress_bar_with_style,
+};
+
+/// Default number of metadata accounts of a `getMultipleAccounts` request.
+pub const DEFAULT_HASHLIST_BATCH_SIZE: usize = MAX_MULTIPLE_ACCOUNTS;
+
+// number of retries of a failed request, with a delay doubled on each retry
+const MAX_RETRY: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// A mint of the hashlist without a readable metadata account.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MissingMetadata {
+    /// Position of the mint in the hashlist, from 1.
+    pub position: usize,
+    pub mint: Pubkey,
+    pub reason: String,
+}
+
+/// Items read from a hashlist, with the position of their mint, and the mints without
+/// metadata.
+pub type HashlistItems = (Vec<(usize, CacheItem)>, Vec<MissingMetadata>);
+
+/// Reads the name and URI of the metadata account of each mint, `batch_size` accounts per
+/// request, returning the items (on-chain, numbered by the position of their mint) and the
+/// mints without metadata. Failed requests are retried; a request that keeps failing aborts.
+pub fn read_hashlist<S: AccountSource>(
+    source: &S,
+    mints: &[Pubkey],
+    batch_size: usize,
+) -> Result<HashlistItems> {
+    if batch_size == 0 || batch_size > MAX_MULTIPLE_ACCOUNTS {
+        return Err(anyhow!(
+            "Invalid batch size {}: must be between 1 and {}",
+            batch_size,
+            MAX_MULTIPLE_ACCOUNTS
+        ));
+    }
+
+    let pb = progress_bar_with_style(mints.len() as u64);
+    let mut items = Vec::with_capacity(mints.len());
+    let mut missing = Vec::new();
+
+    for (batch, chunk) in mints.chunks(batch_size).enumerate() {
+        let pdas: Vec<Pubkey> = chunk.iter().map(find_metadata_pda).collect();
+        let accounts = with_retry(|| source.get_multiple_accounts(&pdas))?;
+
+        for (offset, (mint, account)) in chunk.iter().zip(accounts).enumerate() {
+            let position = batch * batch_size + offset + 1;
+
+            let metadata = match account {
+                Some(account) => Metadata::deserialize(&mut account.data.as_slice())
+                    .map_err(|_| "invalid metadata account"),
+                None => Err("no metadata account"),
+            };
+
+            match metadata {
+                Ok(metadata) => items.push((
+                    position,
+                    CacheItem {
+                        name: metadata.data.name.trim_matches(char::from(0)).to_string(),
+                        image_hash: String::new(),
+                        image_link: String::new(),
+                        metadata_hash: String::new(),
+                        metadata_link: metadata.data.uri.trim_matches(char::from(0)).to_string(),
+                        on_chain: true,
+                        animation_hash: None,
+                        animation_link: None,
+                        optimized_image_hash: None,
+                    },
+                )),
+                Err(reason) => missing.push(MissingMetadata {
+                    position,
+                    mint: *mint,
+                    reason: reason.to_string(),
+                }),
+            }
+        }
+
+        pb.inc(chunk.len() as u64);
+    }
+
+    pb.finish_and_clear();
+
+    Ok((items, missing))
+}
+
+/// Lists the mints without metadata, one per line.
+pub fn format_missing(missing: &[MissingMetadata]) -> String {
+    missing
+        .iter()
+        .map(|m| format!("  mint {} ({}): {}", m.position, m.mint, m.reason))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Retries an RPC request with an exponential backoff (rate limited requests are retried too).
+fn with_retry<T, E: fmt::Display>(mut request: impl FnMut() -> Result<T, E>) -> Result<T> {
+    let mut attempt = 0;
+
+    loop {
+        match request() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < MAX_RETRY => {
+                warn!("RPC request failed (attempt {}): {}", attempt + 1, err);
+                thread::sleep(RETRY_DELAY * 2u32.pow(attempt));
+                attempt += 1;
+            }
+            Err(err) => return Err(anyhow!("RPC request failed: {}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use anchor_client::solana_sdk::account::Account;
+    use borsh::BorshSerialize;
+    use mpl_token_metadata::state::Data;
+
+    use super::*;
+
+    /// Serves the metadata accounts, failing the first request.
+    struct MockSource {
+        accounts: HashMap<Pubkey, Account>,
+        requests: AtomicUsize,
+    }
+
+    impl AccountSource for MockSource {
+        fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            if self.requests.fetch_add(1, Ordering::Relaxed) == 0 {
+                return Err(anyhow!("429 Too Many Requests"));
+            }
+            Ok(pubkeys
+                .iter()
+                .map(|pubkey| self.accounts.get(pubkey).cloned())
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_read_hashlist() {
+        let mints: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let mut accounts = HashMap::new();
+
+        // mint 2 has no metadata account and mint 4 an invalid one
+        for (i, mint) in mints.iter().enumerate() {
+            let data = match i {
+                2 => continue,
+                4 => vec![1, 2, 3],
+                _ => Metadata {
+                    mint: *mint,
+                    data: Data {
+                        name: format!("Numbers #{}\0\0\0", i + 1),
+                        uri: format!("https://arweave.net/m{}\0\0", i),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                }
+                .try_to_vec()
+                .unwrap(),
+            };
+            accounts.insert(
+                find_metadata_pda(mint),
+                Account {
+                    data,
+                    ..Account::default()
+                },
+            );
+        }
+
+        let source = MockSource {
+            accounts,
+            requests: AtomicUsize::new(0),
+        };
+        let (items, missing) = read_hashlist(&source, &mints, 2).unwrap();
+
+        // one failed request, then 3 batches
+        assert_eq!(source.requests.load(Ordering::Relaxed), 4);
+
+        let positions: Vec<usize> = items.iter().map(|(position, _)| *position).collect();
+        assert_eq!(positions, vec![1, 2, 4]);
+        assert_eq!(items[2].1.name, "Numbers #4");
+        assert_eq!(items[2].1.metadata_link, "https://arweave.net/m3");
+        assert!(items[2].1.on_chain);
+
+        assert_eq!(missing.len(), 2);
+        assert_eq!(missing[0].position, 3);
+        assert_eq!(missing[0].mint, mints[2]);
+        assert_eq!(missing[1].reason, "invalid metadata account");
+
+        assert!(read_hashlist(&source, &mints, 101).is_err());
+    }
+}
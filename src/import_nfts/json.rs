@@ -0,0 +1,169 @@
+//! JSON input of `sugar import`: an array (or JSON Lines) of metadata links, or of objects
+//! with a metadata link and optionally a name and an image:
+//!
+//! ```json
+//! [{"uri": "https://arweave.net/<tx>", "name": "Numbers #1", "image": "https://arweave.net/<tx>"}]
+//! ```
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+use crate::cache::CacheItem;
+
+// keys of the metadata link in an object, by priority
+const LINK_KEYS: [&str; 3] = ["uri", "url", "metadata_link"];
+
+/// Shape of the elements of a JSON input, set by the first element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    Link,
+    Object,
+}
+
+/// Reads the cache items of a JSON array in order, numbered from 1 by their position in the
+/// array (items without a name have an empty name).
+pub fn read_json_items(content: &str) -> Result<Vec<(usize, CacheItem)>> {
+    let value: Value = serde_json::from_str(content.trim_start_matches('\u{feff}'))
+        .map_err(|e| anyhow!("Failed to parse the JSON input: {}", e))?;
+
+    let elements = value
+        .as_array()
+        .ok_or_else(|| anyhow!("The JSON input must be an array of links or objects"))?;
+
+    let mut shape = None;
+
+    elements
+        .iter()
+        .enumerate()
+        .map(|(index, element)| {
+            let item = read_element(element, &mut shape)
+                .map_err(|e| anyhow!("Invalid element {} of the JSON array: {}", index, e))?;
+            Ok((index + 1, item))
+        })
+        .collect()
+}
+
+/// Reads the cache items of a JSON Lines file in order, with the line number of each item
+/// (blank lines are skipped).
+pub fn read_jsonl_items(content: &str) -> Result<Vec<(usize, CacheItem)>> {
+    let mut shape = None;
+    let mut items = Vec::new();
+
+    for (line_index, line) in content.trim_start_matches('\u{feff}').lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let item = serde_json::from_str(line)
+            .map_err(anyhow::Error::from)
+            .and_then(|element| read_element(&element, &mut shape))
+            .map_err(|e| {
+                anyhow!(
+                    "Invalid line {} of the JSON Lines input: {}",
+                    line_index + 1,
+                    e
+                )
+            })?;
+        items.push((line_index + 1, item));
+    }
+
+    Ok(items)
+}
+
+/// Reads a link or an object, checking that it has the same shape as the previous elements.
+fn read_element(element: &Value, shape: &mut Option<Shape>) -> Result<CacheItem> {
+    let element_shape = match element {
+        Value::String(_) => Shape::Link,
+        Value::Object(_) => Shape::Object,
+        other => {
+            return Err(anyhow!(
+                "expected a metadata link or an object, found {}",
+                other
+            ))
+        }
+    };
+
+    match shape {
+        Some(shape) if *shape != element_shape => {
+            return Err(anyhow!(
+                "mixed links and objects (found {}, expected {})",
+                describe(element_shape),
+                describe(*shape)
+            ))
+        }
+        Some(_) => (),
+        None => *shape = Some(element_shape),
+    }
+
+    let string = |key: &str| -> Result<Option<String>> {
+        match element.get(key) {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.trim().to_string())),
+            Some(other) => Err(anyhow!("'{}' must be a string, found {}", key, other)),
+        }
+    };
+
+    let (metadata_link, name, image_link) = match element {
+        Value::String(link) => (link.trim().to_string(), None, None),
+        _ => {
+            let mut metadata_link = None;
+            for key in LINK_KEYS {
+                if let Some(link) = string(key)? {
+                    metadata_link = Some(link);
+                    break;
+                }
+            }
+            let metadata_link = metadata_link.ok_or_else(|| {
+                anyhow!("missing metadata link (one of {})", LINK_KEYS.join(", "))
+            })?;
+
+            (metadata_link, string("name")?, string("image")?)
+        }
+    };
+
+    if metadata_link.is_empty() {
+        return Err(anyhow!("empty metadata link"));
+    }
+
+    Ok(CacheItem {
+        name: name.unwrap_or_default(),
+        image_hash: String::new(),
+        image_link: image_link.unwrap_or_default(),
+        metadata_hash: String::new(),
+        metadata_link,
+        on_chain: false,
+        animation_hash: None,
+        animation_link: None,
+        optimized_image_hash: None,
+    })
+}
+
+fn describe(shape: Shape) -> &'static str {
+    match shape {
+        Shape::Link => "a link",
+        Shape::Object => "an object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_errors() {
+        let err =
+            read_json_items(r#"["https://arweave.net/m0", {"uri": "https://arweave.net/m1"}]"#)
+                .unwrap_err();
+        assert!(err.to_string().contains("element 1"), "{}", err);
+
+        let err =
+            read_json_items(r#"[{"uri": "https://arweave.net/m0"}, {"name": "Two"}]"#).unwrap_err();
+        assert!(err.to_string().contains("element 1"), "{}", err);
+        assert!(read_json_items(r#"[42]"#).is_err());
+        assert!(read_json_items(r#"{"uri": "https://arweave.net/m0"}"#).is_err());
+
+        let err = read_jsonl_items("\"https://arweave.net/m0\"\n\n[1]\n").unwrap_err();
+        assert!(err.to_string().contains("line 3"), "{}", err);
+    }
+}
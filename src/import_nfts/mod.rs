@@ -1,27 +1,216 @@
 use std::path::PathBuf;
 
 use anyhow::Result;
+#[cfg(feature = "cli")]
 use clap::Args;
 
-pub use crate::import_nfts::process::process_import;
+#[cfg(feature = "cli")]
+use crate::constants::{DEFAULT_CONFIG, DEFAULT_IPFS_GATEWAY};
 
+pub use crate::import_nfts::{
+    csv::ImportFormat,
+    hashlist::DEFAULT_HASHLIST_BATCH_SIZE,
+    name_template::{NameTemplate, DEFAULT_NAME_TEMPLATE},
+    process::{process_import, DEFAULT_FLUSH_EVERY, DEFAULT_IMPORT_CONCURRENCY},
+};
+
+pub mod candy_machine;
+pub mod csv;
+pub mod hashlist;
+pub mod json;
+pub mod name_template;
 pub mod process;
+pub mod validate;
+pub mod verify;
 
 /// Arguments for importing existing NFTs metadata links into a Sugar cache.
-#[derive(Debug, Args)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "cli", derive(Args))]
 pub struct ImportNFTsArgs {
-    /// Path to the text file containing Arweave metadata URLs.
-    #[clap(short, long, value_name = "FILE")]
-    pub import: PathBuf,
+    /// Path to the text file containing Arweave metadata URLs, or to a CSV, JSON or JSON Lines
+    /// file.
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            short,
+            long,
+            value_name = "FILE",
+            required_unless_present_any = &["from_candy_machine", "hashlist"]
+        )
+    )]
+    pub import: Option<PathBuf>,
+
+    /// Import the mints of a hashlist (a JSON array of mint addresses), reading the name and
+    /// metadata link of each mint from its metadata account.
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, value_name = "FILE", conflicts_with_all = &["import", "from_candy_machine"])
+    )]
+    pub hashlist: Option<PathBuf>,
+
+    /// Number of metadata accounts read per RPC request with --hashlist (at most 100).
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, default_value_t = DEFAULT_HASHLIST_BATCH_SIZE, value_name = "N")
+    )]
+    pub batch_size: usize,
+
+    /// Rebuild the cache from the config lines of a deployed candy machine.
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            value_name = "ADDRESS",
+            conflicts_with_all = &["import", "append", "start_index"]
+        )
+    )]
+    pub from_candy_machine: Option<String>,
+
+    /// Path to the keypair file, uses Sol config or defaults to "~/.config/solana/id.json"
+    /// (with --from-candy-machine).
+    #[cfg_attr(feature = "cli", clap(short, long))]
+    pub keypair: Option<String>,
+
+    /// RPC Url (with --from-candy-machine or --hashlist).
+    #[cfg_attr(feature = "cli", clap(short, long))]
+    pub rpc_url: Option<String>,
+
+    /// Format of the file (txt, csv, json or jsonl), detected from its extension by default.
+    #[cfg_attr(feature = "cli", clap(long, value_name = "FORMAT"))]
+    pub format: Option<ImportFormat>,
+
+    /// Columns of the cache fields in a CSV file, as headers or 1-based column numbers (e.g.
+    /// "name=title,metadata_link=uri,image_link=3").
+    #[cfg_attr(feature = "cli", clap(long, value_name = "MAP"))]
+    pub column_map: Option<String>,
 
     /// Path to the output cache file (e.g. ./cache.json)
-    #[clap(short, long, default_value = "cache.json", value_name = "CACHE")]
+    #[cfg_attr(
+        feature = "cli",
+        clap(short, long, default_value = "cache.json", value_name = "CACHE")
+    )]
     pub output: PathBuf,
+
+    /// Number of metadata files fetched in parallel.
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, default_value_t = DEFAULT_IMPORT_CONCURRENCY, value_name = "N")
+    )]
+    pub concurrency: usize,
+
+    /// Import the links without fetching the metadata (items are named "NFT #<n>" and have no
+    /// image link). CSV files are always imported without fetching the metadata.
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub offline: bool,
+
+    /// Import the links that appear more than once.
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub allow_duplicates: bool,
+
+    /// Leave out the links that are not https URLs instead of aborting.
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub skip_invalid: bool,
+
+    /// Validate the links and print a report without writing the cache.
+    #[cfg_attr(feature = "cli", clap(long, conflicts_with = "from_candy_machine"))]
+    pub dry_run: bool,
+
+    /// Name of the items without a name in their metadata, with the {index} (0-based) and
+    /// {index1} (1-based) placeholders, zero-padded with e.g. {index:04}.
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, default_value = DEFAULT_NAME_TEMPLATE, value_name = "TEMPLATE")
+    )]
+    pub name_template: NameTemplate,
+
+    /// Name every item from the name template, even when its metadata has a name.
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub force_template: bool,
+
+    /// Download the metadata of every imported item and check it as `sugar validate` does
+    /// (name and symbol lengths, seller fee basis points, files and creator shares).
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, conflicts_with_all = &["from_candy_machine", "dry_run"])
+    )]
+    pub verify: bool,
+
+    /// Keep the cache and exit successfully when --verify finds invalid items.
+    #[cfg_attr(feature = "cli", clap(long, requires = "verify"))]
+    pub continue_on_error: bool,
+
+    /// Write the hash of the cache in the hidden settings of the config file, as `sugar hash`
+    /// does.
+    #[cfg_attr(feature = "cli", clap(long, conflicts_with_all = &["from_candy_machine", "dry_run"]))]
+    pub update_hash: bool,
+
+    /// Path to the config file of --update-hash.
+    #[cfg_attr(
+        feature = "cli",
+        clap(short, long, default_value = DEFAULT_CONFIG, value_name = "CONFIG")
+    )]
+    pub config: String,
+
+    /// Number of fetched items between two writes of the cache (0 writes it at the end only).
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, default_value_t = DEFAULT_FLUSH_EVERY, value_name = "N")
+    )]
+    pub flush_every: usize,
+
+    /// Gateway used to fetch `ipfs://` metadata links.
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, default_value = DEFAULT_IPFS_GATEWAY, value_name = "URL")
+    )]
+    pub ipfs_gateway: String,
+
+    /// Start over instead of resuming the partial cache of an interrupted import.
+    #[cfg_attr(feature = "cli", clap(long, conflicts_with = "append"))]
+    pub fresh: bool,
+
+    /// Add the items to the existing cache, keeping its program section (candy machine).
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub append: bool,
+
+    /// Replace the existing items with the same index when appending.
+    #[cfg_attr(feature = "cli", clap(long, requires = "append"))]
+    pub overwrite_items: bool,
+
+    /// Index of the first imported item (defaults to the next unused index of the cache).
+    #[cfg_attr(feature = "cli", clap(long, value_name = "INDEX"))]
+    pub start_index: Option<usize>,
+
+    /// Rekey the cache items 0..n when the import leaves missing indices, instead of aborting.
+    #[cfg_attr(feature = "cli", clap(long))]
+    pub renumber: bool,
+
+    /// Split the items in caches of at most N items each, keyed from 0 (e.g. one per candy
+    /// machine).
+    #[cfg_attr(
+        feature = "cli",
+        clap(
+            long,
+            value_name = "N",
+            conflicts_with_all = &["from_candy_machine", "append", "start_index", "update_hash"]
+        )
+    )]
+    pub chunk_size: Option<usize>,
+
+    /// File name stem of the caches of --chunk-size, written as <PREFIX>-<n>.json next to the
+    /// output cache (defaults to the stem of the output cache).
+    #[cfg_attr(
+        feature = "cli",
+        clap(long, value_name = "PREFIX", requires = "chunk_size")
+    )]
+    pub chunk_prefix: Option<String>,
+
+    /// Replace an existing cache without asking for confirmation.
+    #[cfg_attr(feature = "cli", clap(long, conflicts_with = "append"))]
+    pub force: bool,
 }
 
 /// Entry point for handling `sugar import` command.
 pub async fn process_import_nfts_cmd(args: ImportNFTsArgs) -> Result<()> {
-    // `process_import` is synchronous; call it and convert the result into anyhow::Result
-    process_import(&args.import, &args.output)?;
-    Ok(())
+    process_import(args).await
 }
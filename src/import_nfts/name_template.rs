@@ -0,0 +1,175 @@
+use std::{fmt, ops::Range, str::FromStr};
+
+use anyhow::{anyhow, Result};
+
+use crate::constants::MAX_NAME_LENGTH;
+
+/// Name template of the items that have no name in the metadata.
+pub const DEFAULT_NAME_TEMPLATE: &str = "NFT #{index1}";
+
+/// Template of the names of imported items, e.g. `Degen Ducks #{index:04}`.
+///
+/// `{index}` is replaced by the cache index of the item and `{index1}` by the index plus one;
+/// a width (`{index:4}`) pads the number with spaces, and `{index:04}` with zeros.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    template: String,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Index {
+        one_based: bool,
+        width: usize,
+        zero_padded: bool,
+    },
+}
+
+impl NameTemplate {
+    pub fn render(&self, index: usize) -> String {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Text(text) => text.clone(),
+                Part::Index {
+                    one_based,
+                    width,
+                    zero_padded,
+                } => {
+                    let number = if *one_based { index + 1 } else { index };
+                    if *zero_padded {
+                        format!("{:0width$}", number, width = width)
+                    } else {
+                        format!("{:width$}", number, width = width)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Checks that the names of the indices fit the on-chain name limit (the candy machine
+    /// would truncate them).
+    pub fn check_length(&self, indices: Range<usize>) -> Result<()> {
+        // the names only grow with the index
+        match indices.last().map(|index| self.render(index)) {
+            Some(name) if name.len() > MAX_NAME_LENGTH => Err(anyhow!(
+                "Name template '{}' renders names longer than {} bytes (e.g. '{}')",
+                self.template,
+                MAX_NAME_LENGTH,
+                name
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        DEFAULT_NAME_TEMPLATE
+            .parse()
+            .expect("Invalid default name template")
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(Part::Text(rest[..start].to_string()));
+            }
+
+            let end = rest[start..].find('}').ok_or_else(|| {
+                anyhow!("Unclosed placeholder in the name template '{}'", template)
+            })? + start;
+            parts.push(parse_placeholder(&rest[start + 1..end], template)?);
+            rest = &rest[end + 1..];
+        }
+
+        if !rest.is_empty() {
+            parts.push(Part::Text(rest.to_string()));
+        }
+
+        Ok(NameTemplate {
+            template: template.to_string(),
+            parts,
+        })
+    }
+}
+
+impl fmt::Display for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.template)
+    }
+}
+
+fn parse_placeholder(placeholder: &str, template: &str) -> Result<Part> {
+    let (name, format) = match placeholder.split_once(':') {
+        Some((name, format)) => (name, Some(format)),
+        None => (placeholder, None),
+    };
+
+    let one_based = match name {
+        "index" => false,
+        "index1" => true,
+        _ => {
+            return Err(anyhow!(
+                "Unknown placeholder '{{{}}}' in the name template '{}', expected {{index}} or \
+                {{index1}}",
+                placeholder,
+                template
+            ))
+        }
+    };
+
+    let (width, zero_padded) = match format {
+        Some(format) => {
+            let width = format.parse::<usize>().map_err(|_| {
+                anyhow!(
+                    "Invalid width '{}' in the name template '{}' (e.g. {{index:04}})",
+                    format,
+                    template
+                )
+            })?;
+            (width, format.starts_with('0'))
+        }
+        None => (0, false),
+    };
+
+    Ok(Part::Index {
+        one_based,
+        width,
+        zero_padded,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name_template() {
+        let template: NameTemplate = "Degen Ducks #{index:04}".parse().unwrap();
+        assert_eq!(template.render(0), "Degen Ducks #0000");
+        assert_eq!(template.render(12345), "Degen Ducks #12345");
+
+        let template: NameTemplate = "{index1} of 100 ({index:3})".parse().unwrap();
+        assert_eq!(template.render(9), "10 of 100 (  9)");
+
+        assert_eq!(NameTemplate::default().render(0), "NFT #1");
+
+        assert!("Ducks #{id}".parse::<NameTemplate>().is_err());
+        assert!("Ducks #{index".parse::<NameTemplate>().is_err());
+        assert!("Ducks #{index:x4}".parse::<NameTemplate>().is_err());
+
+        let template: NameTemplate = "A Very Long Collection Name #{index1}".parse().unwrap();
+        assert!(template.check_length(0..100).is_ok());
+        assert!(template.check_length(0..1000).is_err());
+    }
+}
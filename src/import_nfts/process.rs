@@ -1,70 +1,1435 @@
 use std::{
-    collections::HashMap,
-    fs::File,
+    collections::HashSet,
+    fs::{self, File},
     io::{BufRead, BufReader},
+    ops::Range,
     path::Path,
 };
 
 use anyhow::{anyhow, Result};
+use futures::{stream, StreamExt};
+use reqwest::Client;
+use serde_json::Value;
+use solana_client::rpc_client::RpcClient;
 
-use crate::cache::{Cache, CacheItem, CacheItems, CacheProgram};
+use super::{
+    candy_machine::read_candy_machine,
+    csv::{parse_column_map, read_csv_items, ImportFormat},
+    hashlist::{format_missing, read_hashlist, HashlistItems},
+    json::{read_json_items, read_jsonl_items},
+    name_template::NameTemplate,
+    validate::{check_links, format_problems},
+    verify::{check_metadata, ItemFailure, VerifyReport},
+    ImportNFTsArgs,
+};
+use crate::{
+    cache::{load_cache, Cache, CacheItem, CacheItems},
+    config::{get_config_data, ConfigData, HiddenSettings},
+    constants::WARNING_EMOJI,
+    hash::hash_and_update,
+    mint_list::load_mint_list,
+    output::{confirm, Confirmation},
+    parse::path_to_string,
+    setup::{get_rpc_url, sugar_setup},
+    utils::{progress_bar_with_style, resolve_ipfs_link},
+};
 
-/// Processes a list of Arweave metadata links and generates a sugar-style cache.json
-pub fn process_import(input_file: &Path, output_file: &Path) -> Result<()> {
-    // Open the input file
-    let file = File::open(input_file).map_err(|e| anyhow!("Failed to open input file: {}", e))?;
-    let reader = BufReader::new(file);
+/// Default number of metadata files fetched in parallel.
+pub const DEFAULT_IMPORT_CONCURRENCY: usize = 10;
 
-    // Temporary map to collect CacheItems
-    let mut items_map: HashMap<String, CacheItem> = HashMap::new();
+/// Default number of fetched items between two writes of the cache.
+pub const DEFAULT_FLUSH_EVERY: usize = 100;
 
-    for (index, line_result) in reader.lines().enumerate() {
-        let metadata_link =
-            line_result.map_err(|e| anyhow!("Failed to read line {}: {}", index + 1, e))?;
-        if metadata_link.trim().is_empty() {
-            continue;
+// maximum number of links of a {a..b} placeholder
+const MAX_RANGE_LINKS: u64 = 100_000;
+
+/// Processes a list of Arweave metadata links, or a CSV or JSON file, and generates a
+/// sugar-style cache.json.
+///
+/// The items are keyed `0..n` in the order of the links in the file; blank lines are skipped
+/// and do not leave gaps in the indices (the config lines of a candy machine are contiguous).
+/// The links must be distinct https URLs: the import aborts with the lines of the invalid and
+/// duplicate links, unless `skip_invalid` (the invalid links are left out) or
+/// `allow_duplicates` is set. With `dry_run`, only the validation report is printed.
+///
+/// The names and media links of a CSV file are read from its columns. For a list of links (or
+/// a JSON file), unless `offline` is set, the metadata of the items without a name or an
+/// image is fetched (`concurrency` at a time) to fill their name, image and animation links.
+/// The cache is written every `flush_every` fetched items, and once more with the fetched
+/// items when some links could not be read, so that running the command again resumes the
+/// import (unless `fresh` is set). Items without a name (or every item, with
+/// `force_template`) are then named from the `name_template` and their cache index.
+///
+/// With `verify`, the metadata of every imported item is downloaded once the cache is written
+/// and checked with the validators of `sugar validate`; the command fails with the index and
+/// the reason of each invalid item, unless `continue_on_error` is set.
+///
+/// With `append`, the items are added to the existing cache from its next unused index (or
+/// `start_index`), keeping its program section; otherwise an existing cache is only replaced
+/// after a confirmation or with `force`.
+///
+/// With `chunk_size`, the items are split in caches of at most `chunk_size` items each, keyed
+/// from 0 and named `<chunk_prefix>-<n>.json` (the stem of the output cache by default) next
+/// to the output cache; an input that fits in one chunk is written to the output cache.
+///
+/// With `hashlist`, the items are read from the metadata accounts of the mints of the hashlist
+/// (`batch_size` accounts per RPC request) instead of a file; the mints without metadata are
+/// reported once the cache is written.
+///
+/// With `from_candy_machine`, the cache is rebuilt from the config lines of the candy machine
+/// instead, pointing at the candy machine and its collection.
+pub async fn process_import(args: ImportNFTsArgs) -> Result<()> {
+    let output_file = args.output.as_path();
+
+    if let Some(candy_machine) = &args.from_candy_machine {
+        if !args.force && output_file.exists() {
+            confirm_replace(&load_cache(&path_to_string(output_file)?, false)?)?;
         }
 
-        let name = format!("NFT #{}", index + 1);
+        let sugar_config = sugar_setup(args.keypair.clone(), args.rpc_url.clone())?;
+        let (program, items) = read_candy_machine(&sugar_config, candy_machine)?;
+
+        let mut cache = Cache {
+            program,
+            items,
+            file_path: output_file.to_string_lossy().to_string(),
+            ..Cache::new()
+        };
+        cache
+            .write_to_file(output_file)
+            .map_err(|e| anyhow!("Failed to write cache file: {}", e))?;
+
+        report!(
+            "✅ Rebuilt the cache of candy machine {} ({} items): {:?}",
+            candy_machine,
+            cache.items.len(),
+            output_file
+        );
+
+        return Ok(());
+    }
 
-        items_map.insert(
-            index.to_string(),
-            CacheItem {
-                name,
-                image_hash: String::new(),
-                image_link: String::new(),
-                metadata_hash: String::new(),
-                metadata_link,
-                on_chain: false,
-                animation_hash: None,
-                animation_link: None,
+    // checks the config before anything is fetched
+    let hidden_settings = if args.update_hash {
+        Some(read_hidden_settings(&args.config)?)
+    } else {
+        None
+    };
+
+    let (mut numbered_items, format, missing) = match &args.hashlist {
+        Some(hashlist) => {
+            // the images of the items are fetched as for a list of links
+            let (items, missing) = read_hashlist_items(&args, hashlist)?;
+            (items, ImportFormat::Txt, missing)
+        }
+        None => {
+            let (items, format) = read_input_items(&args)?;
+            (items, format, Vec::new())
+        }
+    };
+
+    let report = check_links(&numbered_items);
+
+    if args.dry_run {
+        report!("{}", report.summary());
+        if !report.invalid.is_empty() {
+            report!("\nInvalid link(s):\n{}", format_problems(&report.invalid));
+        }
+        if !report.duplicates.is_empty() {
+            report!(
+                "\nDuplicate link(s):\n{}",
+                format_problems(&report.duplicates)
+            );
+        }
+        report!("\nDry run: no cache was written");
+        return Ok(());
+    }
+
+    let mut problems = Vec::new();
+    if !report.invalid.is_empty() && !args.skip_invalid {
+        problems.push(format!(
+            "Invalid link(s):\n{}",
+            format_problems(&report.invalid)
+        ));
+    }
+    if !report.duplicates.is_empty() && !args.allow_duplicates {
+        problems.push(format!(
+            "Duplicate link(s):\n{}",
+            format_problems(&report.duplicates)
+        ));
+    }
+    if !problems.is_empty() {
+        return Err(anyhow!(
+            "{}\n\n{}\n\nUse --skip-invalid to leave out the invalid links and \
+            --allow-duplicates to import the duplicates.",
+            report.summary(),
+            problems.join("\n\n")
+        ));
+    }
+
+    if !report.invalid.is_empty() {
+        let invalid_lines: HashSet<usize> = report.invalid.iter().map(|p| p.line).collect();
+        numbered_items.retain(|(line, _)| !invalid_lines.contains(line));
+        report!(
+            "{} Skipped {} invalid link(s):\n{}",
+            WARNING_EMOJI,
+            report.invalid.len(),
+            format_problems(&report.invalid)
+        );
+    }
+
+    match args.chunk_size {
+        Some(chunk_size) if numbered_items.len() > chunk_size => {
+            import_chunks(&args, format, numbered_items, chunk_size).await?
+        }
+        _ => import_items(&args, format, key_items(numbered_items)).await?,
+    }
+
+    if !missing.is_empty() {
+        report!(
+            "{} {} mint(s) of the hashlist have no metadata and were not imported:\n{}",
+            WARNING_EMOJI,
+            missing.len(),
+            format_missing(&missing)
+        );
+    }
+
+    if let Some((mut config_data, hidden_settings)) = hidden_settings {
+        let old_hash = hidden_settings.hash.clone();
+        let hash = hash_and_update(
+            hidden_settings,
+            &args.config,
+            &mut config_data,
+            &path_to_string(output_file)?,
+        )?;
+
+        report!(
+            "Updated the hidden settings hash of {}: {} -> {}",
+            args.config,
+            if old_hash.is_empty() {
+                "(none)"
+            } else {
+                &old_hash
             },
+            hash
         );
     }
 
-    // Convert HashMap into CacheItems (IndexMap wrapper)
-    let mut cache_items = CacheItems::new();
-    for (k, v) in items_map {
-        cache_items.insert(k, v);
+    Ok(())
+}
+
+/// Writes the items in caches of at most `chunk_size` items, keyed from 0, printing the lines
+/// of the input of each cache.
+async fn import_chunks(
+    args: &ImportNFTsArgs,
+    format: ImportFormat,
+    numbered_items: Vec<(usize, CacheItem)>,
+    chunk_size: usize,
+) -> Result<()> {
+    if chunk_size == 0 {
+        return Err(anyhow!("--chunk-size must be at least 1"));
+    }
+
+    let prefix = match &args.chunk_prefix {
+        Some(prefix) => prefix.clone(),
+        None => args
+            .output
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_else(|| "cache".to_string()),
+    };
+    let directory = args.output.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut summary = Vec::new();
+
+    for (n, chunk) in numbered_items.chunks(chunk_size).enumerate() {
+        let output = directory.join(format!("{}-{}.json", prefix, n));
+        let first_line = chunk[0].0;
+        let last_line = chunk[chunk.len() - 1].0;
+
+        let chunk_args = ImportNFTsArgs {
+            output: output.clone(),
+            ..args.clone()
+        };
+        import_items(&chunk_args, format, key_items(chunk.to_vec())).await?;
+
+        summary.push(format!(
+            "  lines {}-{} -> {:?} ({} items)",
+            first_line,
+            last_line,
+            output,
+            chunk.len()
+        ));
+    }
+
+    report!(
+        "\nSplit {} items in {} caches:\n{}",
+        numbered_items.len(),
+        summary.len(),
+        summary.join("\n")
+    );
+
+    Ok(())
+}
+
+/// Reads the items of the input file with their line numbers (or element numbers of a JSON
+/// array), and the format of the file.
+fn read_input_items(args: &ImportNFTsArgs) -> Result<(Vec<(usize, CacheItem)>, ImportFormat)> {
+    let input_file = args.import.as_deref().ok_or_else(|| {
+        anyhow!("Missing input file: pass -i/--import, --hashlist or --from-candy-machine")
+    })?;
+    let format = args
+        .format
+        .unwrap_or_else(|| ImportFormat::from_path(input_file));
+
+    if args.column_map.is_some() && format != ImportFormat::Csv {
+        return Err(anyhow!("--column-map only applies to CSV files"));
     }
 
-    // Build the final Cache (mutable for writing)
-    let mut cache = Cache {
-        program: CacheProgram::new(),
-        items: cache_items,
-        file_path: output_file.to_string_lossy().to_string(),
+    let items = match format {
+        ImportFormat::Txt => {
+            let file =
+                File::open(input_file).map_err(|e| anyhow!("Failed to open input file: {}", e))?;
+            read_links(BufReader::new(file))?
+        }
+        _ => {
+            let content = fs::read_to_string(input_file)
+                .map_err(|e| anyhow!("Failed to read input file: {}", e))?;
+            match format {
+                ImportFormat::Csv => {
+                    let column_map =
+                        parse_column_map(args.column_map.as_deref().unwrap_or_default())?;
+                    read_csv_items(&content, &column_map)?
+                }
+                ImportFormat::Json => read_json_items(&content)?,
+                _ => read_jsonl_items(&content)?,
+            }
+        }
+    };
+
+    Ok((items, format))
+}
+
+/// Reads the items of the mints of a hashlist from their metadata accounts, with the position
+/// of their mint, and the mints without metadata.
+fn read_hashlist_items(args: &ImportNFTsArgs, hashlist: &Path) -> Result<HashlistItems> {
+    let mints = load_mint_list(&path_to_string(hashlist)?)?;
+    let client = RpcClient::new(get_rpc_url(args.rpc_url.clone())?);
+
+    report!(
+        "Reading the metadata accounts of {} mint(s)...",
+        mints.len()
+    );
+    read_hashlist(&client, &mints, args.batch_size)
+}
+
+/// Reads the config file of `update_hash`, which must have hidden settings.
+fn read_hidden_settings(config_file: &str) -> Result<(ConfigData, HiddenSettings)> {
+    let config_data = get_config_data(config_file)?;
+
+    let hidden_settings = config_data.hidden_settings.clone().ok_or_else(|| {
+        anyhow!(
+            "No hidden settings found in {}: create a config with hidden settings with \
+            'sugar config create' to use --update-hash",
+            config_file
+        )
+    })?;
+
+    Ok((config_data, hidden_settings))
+}
+
+/// Writes the items (keyed `0..n`) to the output cache, fetching their metadata unless they
+/// come from a CSV file or `offline` is set. A partial cache of the same items left by an
+/// interrupted import is resumed.
+async fn import_items(
+    args: &ImportNFTsArgs,
+    format: ImportFormat,
+    items: CacheItems,
+) -> Result<()> {
+    let output_file = args.output.as_path();
+    let fetch = format != ImportFormat::Csv && !args.offline;
+    let imported = items.len();
+
+    let partial = if fetch && !args.fresh && output_file.exists() {
+        let cache = load_cache(&path_to_string(output_file)?, false)?;
+        find_partial_start(&cache.items, &items, args.start_index).map(|start| (cache, start))
+    } else {
+        None
     };
+    let resumed = partial.is_some();
+
+    let (mut cache, start) = match partial {
+        Some((cache, start)) => {
+            let remaining = (start..start + imported)
+                .filter(|index| cache.items[&index.to_string()].name.is_empty())
+                .count();
+            report!(
+                "Resuming the partial import in {:?}: {} of {} item(s) left to fetch",
+                output_file,
+                remaining,
+                imported
+            );
+            (cache, start)
+        }
+        None => {
+            let cache = if args.append {
+                load_cache(&path_to_string(output_file)?, true)?
+            } else {
+                if !args.force && output_file.exists() {
+                    confirm_replace(&load_cache(&path_to_string(output_file)?, false)?)?;
+                }
+                Cache {
+                    file_path: output_file.to_string_lossy().to_string(),
+                    ..Cache::new()
+                }
+            };
+            let start = first_index(&cache.items, args.start_index);
+            (cache, start)
+        }
+    };
+
+    // the items of a partial cache are the imported items, with the metadata fetched so far
+    let start = if resumed {
+        start
+    } else {
+        let existing_gaps = cache.items.check_indices().gaps;
+        merge_items(&mut cache.items, items, Some(start), args.overwrite_items)?;
+        check_indices(&mut cache.items, start, &existing_gaps, args.renumber)?
+    };
+
+    let indices = start..start + imported;
+    args.name_template.check_length(indices.clone())?;
+
+    if fetch {
+        fetch_metadata(
+            &mut cache,
+            indices.clone(),
+            args.concurrency,
+            args.flush_every,
+            &args.ipfs_gateway,
+        )
+        .await?;
+    }
+
+    name_items(
+        &mut cache.items,
+        indices,
+        &args.name_template,
+        args.force_template,
+    );
 
     // Write cache to file
     cache
         .write_to_file(output_file)
         .map_err(|e| anyhow!("Failed to write cache file: {}", e))?;
 
-    println!(
-        "✅ Imported {} NFTs into cache: {:?}",
+    report!(
+        "✅ Imported {} NFTs into cache ({} items): {:?}",
+        imported,
         cache.items.len(),
         output_file
     );
 
+    if args.verify {
+        let report = verify_items(
+            &cache.items,
+            start..start + imported,
+            args.concurrency,
+            &args.ipfs_gateway,
+        )
+        .await;
+
+        report!("\n{}", report.summary());
+        if !report.failures.is_empty() {
+            let failures = format!("Invalid item(s):\n{}", report.format_failures());
+            if !args.continue_on_error {
+                return Err(anyhow!(
+                    "{}\n\n{}\n\nThe cache was written: fix the metadata of the items and \
+                    import again, or use --continue-on-error to keep it as is.",
+                    report.summary(),
+                    failures
+                ));
+            }
+            report!("{} {}", WARNING_EMOJI, failures);
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the cache items are keyed `0..n` after importing items from index `start`,
+/// returning the index of the first imported item. With `renumber`, the items are rekeyed
+/// `0..n`; otherwise the import fails, unless the only missing indices were already missing
+/// in the cache (e.g. the unwritten config lines of a rebuilt cache).
+fn check_indices(
+    cache_items: &mut CacheItems,
+    start: usize,
+    existing_gaps: &[u64],
+    renumber: bool,
+) -> Result<usize> {
+    let report = cache_items.check_indices();
+
+    if report.is_contiguous() {
+        return Ok(start);
+    }
+
+    if renumber {
+        // the imported items stay consecutive, after the items of lower indices
+        let new_start = cache_items
+            .keys()
+            .filter_map(|key| key.parse::<usize>().ok())
+            .filter(|index| *index < start)
+            .count();
+        cache_items.renumber()?;
+
+        report!(
+            "Renumbered the cache items 0-{} ({})",
+            cache_items.keys().filter(|key| *key != "-1").count() - 1,
+            report
+        );
+        return Ok(new_start);
+    }
+
+    if report.duplicates.is_empty()
+        && report.invalid.is_empty()
+        && report.gaps.iter().all(|gap| existing_gaps.contains(gap))
+    {
+        report!(
+            "{} The cache still has {}: fill them with --append --start-index <INDEX> before \
+            running 'deploy'",
+            WARNING_EMOJI,
+            report
+        );
+        return Ok(start);
+    }
+
+    Err(anyhow!(
+        "The cache items must be keyed 0..n for 'deploy' ({}). Use --renumber to rekey them, \
+        or --start-index to fill the missing indices.",
+        report
+    ))
+}
+
+/// Returns the first index of the items in a partial cache: the cache has the links of the
+/// items at consecutive indices (from `start_index`, if set) and some of them have no name yet.
+fn find_partial_start(
+    cache_items: &CacheItems,
+    items: &CacheItems,
+    start_index: Option<usize>,
+) -> Option<usize> {
+    let links: Vec<&str> = items
+        .values()
+        .map(|item| item.metadata_link.as_str())
+        .collect();
+    let first_link = *links.first()?;
+
+    let candidates: Vec<usize> = match start_index {
+        Some(start) => vec![start],
+        None => cache_items
+            .iter()
+            .filter(|(_, item)| item.metadata_link == first_link)
+            .filter_map(|(key, _)| key.parse::<usize>().ok())
+            .collect(),
+    };
+
+    candidates.into_iter().find(|start| {
+        let range: Vec<Option<&CacheItem>> = (*start..*start + links.len())
+            .map(|index| cache_items.get(&index.to_string()))
+            .collect();
+
+        range
+            .iter()
+            .zip(&links)
+            .all(|(item, link)| item.map(|item| item.metadata_link.as_str()) == Some(*link))
+            && range.iter().flatten().any(|item| item.name.is_empty())
+    })
+}
+
+/// Asks for a confirmation before replacing a cache that has items or a candy machine.
+fn confirm_replace(existing: &Cache) -> Result<()> {
+    if existing.items.is_empty() && existing.program.candy_machine.is_empty() {
+        return Ok(());
+    }
+
+    let replace = confirm(
+        Confirmation::new(format!(
+            "{} already has {} item(s){}, replace it?",
+            existing.file_path,
+            existing.items.len(),
+            if existing.program.candy_machine.is_empty() {
+                String::new()
+            } else {
+                format!(" and candy machine {}", existing.program.candy_machine)
+            }
+        ))
+        .default(false),
+    )?;
+
+    if replace {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Import aborted: use --append to add the items to the cache or --force to replace it"
+        ))
+    }
+}
+
+/// Adds the imported items (keyed `0..n`) to the cache items from `start_index`, or from the
+/// next unused numeric index. Existing items are only replaced with `overwrite`.
+fn merge_items(
+    cache_items: &mut CacheItems,
+    items: CacheItems,
+    start_index: Option<usize>,
+    overwrite: bool,
+) -> Result<()> {
+    let start = first_index(cache_items, start_index);
+
+    let keys: Vec<String> = (start..start + items.len())
+        .map(|i| i.to_string())
+        .collect();
+
+    if !overwrite {
+        let existing: Vec<&str> = keys
+            .iter()
+            .filter(|key| cache_items.contains_key(*key))
+            .map(String::as_str)
+            .collect();
+        if !existing.is_empty() {
+            return Err(anyhow!(
+                "The cache already has items {} (use --overwrite-items to replace them)",
+                existing.join(", ")
+            ));
+        }
+    }
+
+    for (key, item) in keys.into_iter().zip(items.0.into_values()) {
+        cache_items.insert(key, item);
+    }
+
+    // keeps the items in index order when they were slotted before existing ones
+    cache_items.sort_by(
+        |a, _, b, _| match (a.parse::<usize>(), b.parse::<usize>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            _ => a.cmp(b),
+        },
+    );
+
+    Ok(())
+}
+
+/// Returns `start_index`, or the next unused numeric index of the cache items.
+fn first_index(cache_items: &CacheItems, start_index: Option<usize>) -> usize {
+    start_index.unwrap_or_else(|| {
+        cache_items
+            .keys()
+            .filter_map(|key| key.parse::<usize>().ok())
+            .max()
+            .map_or(0, |last| last + 1)
+    })
+}
+
+/// Names the items of the indices that have no name, or all of them with `force`, from the
+/// template and their index.
+fn name_items(
+    cache_items: &mut CacheItems,
+    indices: Range<usize>,
+    template: &NameTemplate,
+    force: bool,
+) {
+    for index in indices {
+        if let Some(item) = cache_items.get_mut(&index.to_string()) {
+            if force || item.name.is_empty() {
+                item.name = template.render(index);
+            }
+        }
+    }
+}
+
+/// Reads the metadata links, one per line, into unnamed items with their line number.
+///
+/// Lines starting with `#` are comments, and so is the end of a line after ` #`. A `{a..b}`
+/// placeholder expands a line into one link per number from `a` to `b` (inclusive), padded
+/// to the width of `a` when it has leading zeros (e.g. `{000..099}`).
+fn read_links(reader: impl BufRead) -> Result<Vec<(usize, CacheItem)>> {
+    let mut items = Vec::new();
+
+    for (line_index, line_result) in reader.lines().enumerate() {
+        let line =
+            line_result.map_err(|e| anyhow!("Failed to read line {}: {}", line_index + 1, e))?;
+        let metadata_link = strip_comment(&line);
+        if metadata_link.is_empty() {
+            continue;
+        }
+
+        let links = expand_range(metadata_link)
+            .map_err(|e| anyhow!("Invalid range in line {}: {}", line_index + 1, e))?;
+
+        for link in links {
+            items.push((
+                line_index + 1,
+                CacheItem {
+                    name: String::new(),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: link,
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            ));
+        }
+    }
+
+    Ok(items)
+}
+
+/// Returns the line without its comment, trimmed.
+fn strip_comment(line: &str) -> &str {
+    let line = line.trim();
+
+    if line.starts_with('#') {
+        return "";
+    }
+
+    // a '#' right after the link is a URL fragment
+    match line.find(" #").or_else(|| line.find("\t#")) {
+        Some(position) => line[..position].trim_end(),
+        None => line,
+    }
+}
+
+/// Expands the `{a..b}` placeholder of a link, if it has one.
+fn expand_range(link: &str) -> Result<Vec<String>> {
+    let start = match link.find('{') {
+        Some(start) => start,
+        None if link.contains('}') => return Err(anyhow!("unmatched '}}'")),
+        None => return Ok(vec![link.to_string()]),
+    };
+
+    let end = link[start..]
+        .find('}')
+        .map(|end| start + end)
+        .ok_or_else(|| anyhow!("unclosed '{{'"))?;
+
+    let (prefix, suffix) = (&link[..start], &link[end + 1..]);
+    if suffix.contains('{') || suffix.contains('}') {
+        return Err(anyhow!("only one {{a..b}} placeholder is allowed per line"));
+    }
+
+    let range = &link[start + 1..end];
+    let (first, last) = range
+        .split_once("..")
+        .ok_or_else(|| anyhow!("expected {{a..b}}, found {{{}}}", range))?;
+
+    let parse = |value: &str| {
+        value
+            .parse::<u64>()
+            .map_err(|_| anyhow!("'{}' is not a number in {{{}}}", value, range))
+    };
+    let (from, to) = (parse(first)?, parse(last)?);
+
+    if from > to {
+        return Err(anyhow!(
+            "{{{}}} is empty (the start is after the end)",
+            range
+        ));
+    }
+    if to - from >= MAX_RANGE_LINKS {
+        return Err(anyhow!(
+            "{{{}}} expands to more than {} links",
+            range,
+            MAX_RANGE_LINKS
+        ));
+    }
+
+    let width = if first.len() > 1 && first.starts_with('0') {
+        first.len()
+    } else {
+        0
+    };
+
+    Ok((from..=to)
+        .map(|number| format!("{}{:0width$}{}", prefix, number, suffix, width = width))
+        .collect())
+}
+
+/// Keys the items `0..n` in file order.
+fn key_items(numbered_items: Vec<(usize, CacheItem)>) -> CacheItems {
+    CacheItems(
+        numbered_items
+            .into_iter()
+            .enumerate()
+            .map(|(index, (_, item))| (index.to_string(), item))
+            .collect(),
+    )
+}
+
+/// Fetches the metadata of the items of the indices without a name or an image and fills
+/// their missing name, image and animation links, writing the cache every `flush_every`
+/// fetched items. Fails with the list of the links that could not be read, after writing the
+/// cache with the items that were fetched.
+async fn fetch_metadata(
+    cache: &mut Cache,
+    indices: Range<usize>,
+    concurrency: usize,
+    flush_every: usize,
+    ipfs_gateway: &str,
+) -> Result<()> {
+    let pending: Vec<(String, String)> = indices
+        .map(|index| index.to_string())
+        .filter_map(|key| {
+            cache
+                .items
+                .get(&key)
+                .filter(|item| item.name.is_empty() || item.image_link.is_empty())
+                .map(|item| (key.clone(), item.metadata_link.clone()))
+        })
+        .collect();
+
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let client = Client::new();
+    let pb = progress_bar_with_style(pending.len() as u64);
+
+    let mut results = stream::iter(pending)
+        .map(|(key, link)| {
+            let client = &client;
+            async move {
+                let metadata = fetch_json(client, &resolve_ipfs_link(&link, ipfs_gateway)).await;
+                (key, link, metadata)
+            }
+        })
+        .buffered(concurrency.max(1));
+
+    let mut fetched = 0;
+    let mut unreachable = Vec::new();
+
+    while let Some((key, link, result)) = results.next().await {
+        pb.inc(1);
+
+        match result {
+            Ok(metadata) => {
+                if let Some(item) = cache.items.get_mut(&key) {
+                    apply_metadata(item, &metadata);
+                }
+                fetched += 1;
+                if flush_every > 0 && fetched % flush_every == 0 {
+                    cache.sync_file()?;
+                }
+            }
+            Err(err) => unreachable.push(format!("{}: {}", link, err)),
+        }
+    }
+
+    pb.finish_and_clear();
+
+    if !unreachable.is_empty() {
+        cache.sync_file()?;
+
+        return Err(anyhow!(
+            "Failed to fetch {} metadata link(s) (run the command again to resume the import, or \
+            use --offline to import the links only):\n  {}",
+            unreachable.len(),
+            unreachable.join("\n  ")
+        ));
+    }
+
     Ok(())
 }
+
+/// Downloads the metadata of the items of the indices (`concurrency` at a time) and checks it,
+/// reporting the items that could not be fetched or are not valid.
+async fn verify_items(
+    cache_items: &CacheItems,
+    indices: Range<usize>,
+    concurrency: usize,
+    ipfs_gateway: &str,
+) -> VerifyReport {
+    let links: Vec<(usize, String)> = indices
+        .filter_map(|index| {
+            cache_items
+                .get(&index.to_string())
+                .map(|item| (index, item.metadata_link.clone()))
+        })
+        .collect();
+
+    let client = Client::new();
+    let pb = progress_bar_with_style(links.len() as u64);
+
+    let mut report = VerifyReport {
+        total: links.len(),
+        ..Default::default()
+    };
+
+    let mut results = stream::iter(links)
+        .map(|(index, link)| {
+            let client = &client;
+            async move {
+                let result = match fetch_json(client, &resolve_ipfs_link(&link, ipfs_gateway)).await
+                {
+                    Ok(document) => check_metadata(document),
+                    Err(err) => Err(format!("failed to fetch: {}", err)),
+                };
+                (index, link, result)
+            }
+        })
+        .buffered(concurrency.max(1));
+
+    while let Some((index, link, result)) = results.next().await {
+        pb.inc(1);
+        if let Err(reason) = result {
+            report.failures.push(ItemFailure {
+                index,
+                link,
+                reason,
+            });
+        }
+    }
+
+    pb.finish_and_clear();
+
+    report
+}
+
+async fn fetch_json(client: &Client, url: &str) -> Result<Value> {
+    let response = client.get(url).send().await?.error_for_status()?;
+    Ok(response.json::<Value>().await?)
+}
+
+/// Sets the name, image and animation links of the item from its metadata, keeping the
+/// fields that are already set.
+fn apply_metadata(item: &mut CacheItem, metadata: &Value) {
+    if let (true, Some(name)) = (item.name.is_empty(), metadata["name"].as_str()) {
+        item.name = name.to_string();
+    }
+    if let (true, Some(image)) = (item.image_link.is_empty(), metadata["image"].as_str()) {
+        item.image_link = image.to_string();
+    }
+    if let (None, Some(animation)) = (&item.animation_link, metadata["animation_url"].as_str()) {
+        item.animation_link = Some(animation.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        io::Write,
+        net::TcpListener,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+        thread,
+    };
+
+    use serde_json::json;
+    use sha2::{Digest, Sha256};
+
+    use super::*;
+    use crate::{
+        constants::{DEFAULT_CONFIG, DEFAULT_IPFS_GATEWAY},
+        import_nfts::DEFAULT_HASHLIST_BATCH_SIZE,
+    };
+
+    fn import_args(import: Option<PathBuf>, output: PathBuf) -> ImportNFTsArgs {
+        ImportNFTsArgs {
+            import,
+            from_candy_machine: None,
+            keypair: None,
+            rpc_url: None,
+            output,
+            format: None,
+            column_map: None,
+            concurrency: DEFAULT_IMPORT_CONCURRENCY,
+            offline: true,
+            allow_duplicates: false,
+            skip_invalid: false,
+            dry_run: false,
+            name_template: NameTemplate::default(),
+            force_template: false,
+            hashlist: None,
+            batch_size: DEFAULT_HASHLIST_BATCH_SIZE,
+            verify: false,
+            continue_on_error: false,
+            update_hash: false,
+            config: DEFAULT_CONFIG.to_string(),
+            flush_every: DEFAULT_FLUSH_EVERY,
+            ipfs_gateway: DEFAULT_IPFS_GATEWAY.to_string(),
+            fresh: false,
+            append: false,
+            overwrite_items: false,
+            start_index: None,
+            renumber: false,
+            chunk_size: None,
+            chunk_prefix: None,
+            force: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_hash() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("links.txt");
+        let output = dir.path().join("cache.json");
+        let config = dir.path().join("config.json");
+        std::fs::write(&input, "https://arweave.net/m0\nhttps://arweave.net/m1\n").unwrap();
+
+        let write_config = |hidden_settings: Option<HiddenSettings>| {
+            let config_data = ConfigData {
+                number: 2,
+                hidden_settings,
+                ..Default::default()
+            };
+            std::fs::write(&config, serde_json::to_string(&config_data).unwrap()).unwrap();
+        };
+        let args = || ImportNFTsArgs {
+            update_hash: true,
+            config: path_to_string(&config).unwrap(),
+            force: true,
+            ..import_args(Some(input.clone()), output.clone())
+        };
+
+        // no hidden settings: nothing is written
+        write_config(None);
+        let err = process_import(args()).await.unwrap_err();
+        assert!(err.to_string().contains("sugar config create"), "{}", err);
+        assert!(!output.exists());
+
+        write_config(Some(HiddenSettings::new(
+            "Numbers #$ID+1$".to_string(),
+            "https://arweave.net/hidden".to_string(),
+            String::new(),
+        )));
+        process_import(args()).await.unwrap();
+
+        let config_data = get_config_data(&path_to_string(&config).unwrap()).unwrap();
+        let hash = config_data.hidden_settings.unwrap().hash;
+        assert_eq!(hash.len(), 32);
+
+        let mut hasher = Sha256::new();
+        hasher.update(std::fs::read(&output).unwrap());
+        let expected = bs58::encode(&hasher.finalize()).into_string();
+        assert_eq!(hash, expected[..32]);
+    }
+
+    /// Serves the metadata `{"name": "Item <n>", ...}` at `/m<n>` (or a 500 error for the
+    /// `failing` paths), counting the requests of each path.
+    fn serve_metadata(
+        failing: Arc<Mutex<HashSet<String>>>,
+        hits: Arc<Mutex<HashMap<String, usize>>>,
+    ) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                }
+
+                let path = request_line
+                    .split_whitespace()
+                    .nth(1)
+                    .unwrap_or_default()
+                    .to_string();
+                *hits.lock().unwrap().entry(path.clone()).or_default() += 1;
+
+                let (status, body) = if failing.lock().unwrap().contains(&path) {
+                    ("500 Internal Server Error", String::new())
+                } else {
+                    let n = path.trim_start_matches("/m");
+                    (
+                        "200 OK",
+                        json!({
+                            "name": format!("Item {}", n),
+                            "image": format!("https://arweave.net/i{}", n)
+                        })
+                        .to_string(),
+                    )
+                };
+
+                write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                    Connection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                )
+                .unwrap();
+            }
+        });
+
+        port
+    }
+
+    #[tokio::test]
+    async fn test_import_keeps_line_order() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("links.txt");
+        let output = dir.path().join("cache.json");
+
+        let links: Vec<String> = (0..1000)
+            .map(|i| format!("https://arweave.net/metadata-{i}"))
+            .collect();
+        std::fs::write(&input, links.join("\n")).unwrap();
+
+        process_import(import_args(Some(input), output.clone()))
+            .await
+            .unwrap();
+
+        let json = std::fs::read_to_string(&output).unwrap();
+        let mut last = 0;
+        for i in 0..1000 {
+            let position = json
+                .find(&format!("\"{i}\": {{"))
+                .unwrap_or_else(|| panic!("missing item {i}"));
+            assert!(i == 0 || position > last, "item {i} is out of order");
+            last = position;
+        }
+    }
+
+    fn links(content: &str) -> CacheItems {
+        key_items(read_links(content.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_blank_lines_leave_no_index_gaps() {
+        let mut items = links("https://a\n\n  \nhttps://b\nhttps://c\n");
+        items["2"].name = "Three".to_string();
+        name_items(&mut items, 0..3, &NameTemplate::default(), false);
+
+        let keys: Vec<&str> = items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["0", "1", "2"]);
+        assert_eq!(items["1"].metadata_link, "https://b");
+        assert_eq!(items["1"].name, "NFT #2");
+        assert_eq!(items["2"].name, "Three");
+    }
+
+    #[test]
+    fn test_comments_and_ranges() {
+        let content = "# collection links\r\n\
+            https://arweave.net/a#meta  # fragment kept, comment dropped\r\n\
+            \r\n\
+            \t# indented comment\n\
+            https://arweave.net/{8..10}.json\r\n\
+            https://arweave.net/{000..001}?v=1 # padded\n";
+
+        let items = read_links(content.as_bytes()).unwrap();
+        let lines: Vec<usize> = items.iter().map(|(line, _)| *line).collect();
+        let links: Vec<&str> = items
+            .iter()
+            .map(|(_, item)| item.metadata_link.as_str())
+            .collect();
+
+        assert_eq!(lines, vec![2, 5, 5, 5, 6, 6]);
+        assert_eq!(
+            links,
+            vec![
+                "https://arweave.net/a#meta",
+                "https://arweave.net/8.json",
+                "https://arweave.net/9.json",
+                "https://arweave.net/10.json",
+                "https://arweave.net/000?v=1",
+                "https://arweave.net/001?v=1",
+            ]
+        );
+
+        // a single-number range
+        assert_eq!(
+            expand_range("https://a/{7..7}").unwrap(),
+            vec!["https://a/7"]
+        );
+
+        for invalid in [
+            "https://a/{3..1}",
+            "https://a/{1..x}",
+            "https://a/{1-3}",
+            "https://a/{1..3",
+            "https://a/1..3}",
+            "https://a/{1..2}/{1..2}",
+            "https://a/{0..100000}",
+        ] {
+            assert!(expand_range(invalid).is_err(), "{}", invalid);
+        }
+
+        let err = read_links("https://a/0\nhttps://a/{2..1}\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn test_merge_items() {
+        let mut cache_items = links("https://a\nhttps://b\n");
+
+        // appended after the last index
+        merge_items(&mut cache_items, links("https://c\n"), None, false).unwrap();
+        assert_eq!(cache_items["2"].metadata_link, "https://c");
+
+        // existing items are kept unless overwritten
+        let items = || links("https://x\nhttps://y\n");
+        assert!(merge_items(&mut cache_items, items(), Some(1), false).is_err());
+        assert_eq!(cache_items["1"].metadata_link, "https://b");
+
+        merge_items(&mut cache_items, items(), Some(2), true).unwrap();
+        let keys: Vec<&str> = cache_items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["0", "1", "2", "3"]);
+        assert_eq!(cache_items["3"].metadata_link, "https://y");
+
+        // slotted before the existing items
+        let mut cache_items = links("https://a\n");
+        let item = cache_items["0"].clone();
+        cache_items.insert("10".to_string(), item);
+        cache_items.shift_remove("0");
+        merge_items(&mut cache_items, items(), Some(0), false).unwrap();
+        let keys: Vec<&str> = cache_items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["0", "1", "10"]);
+    }
+
+    #[test]
+    fn test_check_indices_after_merge() {
+        // imported at index 5 of an empty cache
+        let mut cache_items = CacheItems::new();
+        merge_items(
+            &mut cache_items,
+            links("https://a\nhttps://b\n"),
+            Some(5),
+            false,
+        )
+        .unwrap();
+        assert!(check_indices(&mut cache_items, 5, &[], false).is_err());
+
+        assert_eq!(check_indices(&mut cache_items, 5, &[], true).unwrap(), 0);
+        let keys: Vec<&str> = cache_items.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["0", "1"]);
+
+        // the missing indices of the cache are left for a later import
+        let mut cache_items = links("https://a\n");
+        let item = cache_items["0"].clone();
+        cache_items.insert("3".to_string(), item);
+        let existing_gaps = cache_items.check_indices().gaps;
+        merge_items(&mut cache_items, links("https://b\n"), Some(1), false).unwrap();
+        assert_eq!(
+            check_indices(&mut cache_items, 1, &existing_gaps, false).unwrap(),
+            1
+        );
+        assert_eq!(cache_items.check_indices().gaps, vec![2]);
+    }
+
+    #[test]
+    fn test_apply_metadata() {
+        let mut item = CacheItem {
+            name: String::new(),
+            image_hash: String::new(),
+            image_link: String::new(),
+            metadata_hash: String::new(),
+            metadata_link: "https://arweave.net/metadata".to_string(),
+            on_chain: false,
+            animation_hash: None,
+            animation_link: None,
+            optimized_image_hash: None,
+        };
+
+        apply_metadata(&mut item, &json!({ "symbol": "NB" }));
+        assert_eq!(item.name, "");
+        assert!(item.animation_link.is_none());
+
+        apply_metadata(
+            &mut item,
+            &json!({
+                "name": "Numbers #1",
+                "image": "https://arweave.net/image",
+                "animation_url": "https://arweave.net/animation"
+            }),
+        );
+        assert_eq!(item.name, "Numbers #1");
+        assert_eq!(item.image_link, "https://arweave.net/image");
+        assert_eq!(
+            item.animation_link.as_deref(),
+            Some("https://arweave.net/animation")
+        );
+
+        // the fields that are set are kept
+        apply_metadata(&mut item, &json!({ "name": "Other" }));
+        assert_eq!(item.name, "Numbers #1");
+    }
+
+    #[test]
+    fn test_resolve_ipfs_link() {
+        let gateway = "https://nftstorage.link/";
+
+        assert_eq!(
+            resolve_ipfs_link("ipfs://bafybeib/0.json", gateway),
+            "https://nftstorage.link/ipfs/bafybeib/0.json"
+        );
+        assert_eq!(
+            resolve_ipfs_link("ipfs://ipfs/bafybeib", gateway),
+            "https://nftstorage.link/ipfs/bafybeib"
+        );
+        assert_eq!(
+            resolve_ipfs_link("https://arweave.net/metadata", gateway),
+            "https://arweave.net/metadata"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resume_interrupted_import() {
+        let failing = Arc::new(Mutex::new(HashSet::from(["/m5".to_string()])));
+        let hits = Arc::new(Mutex::new(HashMap::new()));
+        let port = serve_metadata(failing.clone(), hits.clone());
+
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("cache.json");
+        let content: Vec<String> = (0..10)
+            .map(|i| format!("http://127.0.0.1:{}/m{}", port, i))
+            .collect();
+        let content = content.join("\n");
+
+        let args = ImportNFTsArgs {
+            offline: false,
+            concurrency: 2,
+            flush_every: 3,
+            ..import_args(None, output.clone())
+        };
+
+        // the metadata of item 5 cannot be fetched
+        assert!(import_items(&args, ImportFormat::Txt, links(&content))
+            .await
+            .is_err());
+
+        let partial = load_cache(&path_to_string(&output).unwrap(), false).unwrap();
+        assert_eq!(partial.items.len(), 10);
+        assert_eq!(partial.items["4"].name, "Item 4");
+        assert!(partial.items["5"].name.is_empty());
+
+        // the second run only fetches item 5
+        failing.lock().unwrap().clear();
+        import_items(&args, ImportFormat::Txt, links(&content))
+            .await
+            .unwrap();
+
+        let cache = load_cache(&path_to_string(&output).unwrap(), false).unwrap();
+        for i in 0..10 {
+            assert_eq!(cache.items[&i.to_string()].name, format!("Item {}", i));
+        }
+
+        let hits = hits.lock().unwrap();
+        for i in 0..10 {
+            let expected = if i == 5 { 2 } else { 1 };
+            assert_eq!(
+                hits[&format!("/m{}", i)],
+                expected,
+                "requests of item {}",
+                i
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let input = dir.path().join("links.txt");
+        let output = dir.path().join("cache.json");
+
+        let links: Vec<String> = (0..5)
+            .map(|i| format!("https://arweave.net/m{i}"))
+            .collect();
+        std::fs::write(&input, links.join("\n")).unwrap();
+
+        let args = |chunk_size| ImportNFTsArgs {
+            chunk_size: Some(chunk_size),
+            ..import_args(Some(input.clone()), output.clone())
+        };
+
+        process_import(args(2)).await.unwrap();
+        assert!(!output.exists());
+
+        for (n, expected) in [(0, vec![0, 1]), (1, vec![2, 3]), (2, vec![4])] {
+            let chunk = dir.path().join(format!("cache-{}.json", n));
+            let cache = load_cache(&path_to_string(&chunk).unwrap(), false).unwrap();
+
+            let keys: Vec<String> = (0..expected.len()).map(|i| i.to_string()).collect();
+            assert_eq!(cache.items.keys().cloned().collect::<Vec<String>>(), keys);
+            let links: Vec<String> = expected
+                .iter()
+                .map(|i| format!("https://arweave.net/m{i}"))
+                .collect();
+            assert_eq!(
+                cache
+                    .items
+                    .values()
+                    .map(|item| item.metadata_link.clone())
+                    .collect::<Vec<String>>(),
+                links
+            );
+        }
+
+        // a chunk larger than the input writes the output cache
+        process_import(args(10)).await.unwrap();
+        let cache = load_cache(&path_to_string(&output).unwrap(), false).unwrap();
+        assert_eq!(cache.items.len(), 5);
+        assert!(!dir.path().join("cache-3.json").exists());
+    }
+
+    #[tokio::test]
+    async fn test_import_json_shapes() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let inputs = [
+            (
+                "links.json",
+                r#"["https://arweave.net/m0", "https://arweave.net/m1"]"#,
+            ),
+            (
+                "objects.json",
+                r#"[
+                    {"uri": "https://arweave.net/m0", "name": "Numbers #1", "image": "https://arweave.net/i0"},
+                    {"url": "https://arweave.net/m1"}
+                ]"#,
+            ),
+            (
+                "objects.jsonl",
+                "{\"metadata_link\": \"https://arweave.net/m0\", \"name\": \"Numbers #1\", \
+                \"image\": \"https://arweave.net/i0\"}\n\n{\"uri\": \"https://arweave.net/m1\"}\n",
+            ),
+        ];
+
+        for (file_name, content) in inputs {
+            let input = dir.path().join(file_name);
+            let output = dir.path().join(format!("{}.cache.json", file_name));
+            std::fs::write(&input, content).unwrap();
+
+            process_import(import_args(Some(input), output.clone()))
+                .await
+                .unwrap();
+
+            let cache = load_cache(&path_to_string(&output).unwrap(), false).unwrap();
+            let links: Vec<&str> = cache
+                .items
+                .values()
+                .map(|item| item.metadata_link.as_str())
+                .collect();
+            assert_eq!(
+                links,
+                vec!["https://arweave.net/m0", "https://arweave.net/m1"]
+            );
+            assert_eq!(cache.items["1"].name, "NFT #2");
+
+            if file_name.starts_with("objects") {
+                assert_eq!(cache.items["0"].name, "Numbers #1");
+                assert_eq!(cache.items["0"].image_link, "https://arweave.net/i0");
+            }
+        }
+    }
+}
@@ -0,0 +1,151 @@
+//! Validation of the metadata links of an import, before anything is fetched or written.
+
+use std::collections::HashMap;
+
+use url::Url;
+
+use crate::cache::CacheItem;
+
+/// A link rejected by the validation, with the line of the input file it was read from.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LinkProblem {
+    pub line: usize,
+    pub link: String,
+    pub reason: String,
+}
+
+/// Result of the validation of the links of an import.
+#[derive(Debug, Default)]
+pub struct LinkReport {
+    pub total: usize,
+    pub invalid: Vec<LinkProblem>,
+    /// Repeated links (the first occurrence of a link is not a duplicate).
+    pub duplicates: Vec<LinkProblem>,
+}
+
+impl LinkReport {
+    /// Number of distinct links.
+    pub fn unique(&self) -> usize {
+        self.total - self.duplicates.len()
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "{} link(s): {} unique, {} invalid, {} duplicate(s)",
+            self.total,
+            self.unique(),
+            self.invalid.len(),
+            self.duplicates.len()
+        )
+    }
+}
+
+/// Checks that every metadata link is an https URL with a path, and finds the duplicates.
+/// `items` are the items of the input file with their line numbers.
+pub fn check_links(items: &[(usize, CacheItem)]) -> LinkReport {
+    let mut report = LinkReport {
+        total: items.len(),
+        ..Default::default()
+    };
+    let mut first_lines: HashMap<&str, usize> = HashMap::new();
+
+    for (line, item) in items {
+        let link = item.metadata_link.as_str();
+
+        if let Err(reason) = validate_link(link) {
+            report.invalid.push(LinkProblem {
+                line: *line,
+                link: link.to_string(),
+                reason,
+            });
+        }
+
+        match first_lines.get(link) {
+            Some(first) => report.duplicates.push(LinkProblem {
+                line: *line,
+                link: link.to_string(),
+                reason: format!("duplicate of line {}", first),
+            }),
+            None => {
+                first_lines.insert(link, *line);
+            }
+        }
+    }
+
+    report
+}
+
+/// Lists the problems, one per line.
+pub fn format_problems(problems: &[LinkProblem]) -> String {
+    problems
+        .iter()
+        .map(|problem| {
+            format!(
+                "  line {}: {} ({})",
+                problem.line, problem.link, problem.reason
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn validate_link(link: &str) -> Result<(), String> {
+    let url = Url::parse(link).map_err(|err| format!("not a URL: {}", err))?;
+
+    if url.scheme() != "https" {
+        return Err(format!("scheme {}, expected https", url.scheme()));
+    }
+
+    if url.path().trim_matches('/').is_empty() {
+        return Err("empty path".to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(link: &str) -> CacheItem {
+        CacheItem {
+            name: String::new(),
+            image_hash: String::new(),
+            image_link: String::new(),
+            metadata_hash: String::new(),
+            metadata_link: link.to_string(),
+            on_chain: false,
+            animation_hash: None,
+            animation_link: None,
+            optimized_image_hash: None,
+        }
+    }
+
+    #[test]
+    fn test_check_links() {
+        let items = vec![
+            (1, item("https://arweave.net/tx0")),
+            (2, item("http://arweave.net/tx1")),
+            (4, item("https://arweave.net/tx0")),
+            (5, item("arweave.net/tx2")),
+            (6, item("https://arweave.net/")),
+            (7, item("https://arweave.net/tx0")),
+        ];
+
+        let report = check_links(&items);
+        assert_eq!(report.total, 6);
+        assert_eq!(report.unique(), 4);
+
+        let invalid: Vec<usize> = report.invalid.iter().map(|p| p.line).collect();
+        assert_eq!(invalid, vec![2, 5, 6]);
+        assert_eq!(report.invalid[0].reason, "scheme http, expected https");
+
+        let duplicates: Vec<usize> = report.duplicates.iter().map(|p| p.line).collect();
+        assert_eq!(duplicates, vec![4, 7]);
+        assert_eq!(report.duplicates[1].reason, "duplicate of line 1");
+        assert_eq!(
+            report.summary(),
+            "6 link(s): 4 unique, 3 invalid, 2 duplicate(s)"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Verification of the metadata documents of imported items, with the validators of
+//! `sugar validate`.
+
+use serde_json::Value;
+
+use crate::validate::{parser, Metadata};
+
+/// An item whose metadata could not be fetched or is not valid Metaplex metadata.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ItemFailure {
+    pub index: usize,
+    pub link: String,
+    pub reason: String,
+}
+
+/// Result of the verification of the imported items.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub total: usize,
+    pub failures: Vec<ItemFailure>,
+}
+
+impl VerifyReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "Verified {} item(s): {} valid, {} invalid",
+            self.total,
+            self.total - self.failures.len(),
+            self.failures.len()
+        )
+    }
+
+    /// Lists the failures by index, one per line.
+    pub fn format_failures(&self) -> String {
+        self.failures
+            .iter()
+            .map(|failure| {
+                format!(
+                    "  index {}: {} ({})",
+                    failure.index, failure.link, failure.reason
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Checks that a metadata document has the fields of the Metaplex standard and that they fit
+/// the on-chain limits: name and symbol lengths, seller fee basis points, at least one file
+/// and creator shares adding up to 100.
+pub fn check_metadata(document: Value) -> Result<(), String> {
+    let metadata: Metadata =
+        serde_json::from_value(document).map_err(|err| format!("invalid metadata: {}", err))?;
+
+    parser::check_name(&metadata.name).map_err(|err| err.to_string())?;
+    if let Some(symbol) = &metadata.symbol {
+        parser::check_symbol(symbol).map_err(|err| err.to_string())?;
+    }
+    if let Some(seller_fee_basis_points) = metadata.seller_fee_basis_points {
+        parser::check_seller_fee_basis_points(seller_fee_basis_points)
+            .map_err(|err| err.to_string())?;
+    }
+    parser::check_url(&metadata.image).map_err(|err| err.to_string())?;
+
+    if metadata.properties.files.is_empty() {
+        return Err("properties.files is empty".to_string());
+    }
+
+    if let Some(creators) = &metadata.properties.creators {
+        parser::check_creators_shares(creators).map_err(|err| err.to_string())?;
+        parser::check_creators_addresses(creators).map_err(|err| err.to_string())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_check_metadata() {
+        let metadata = |name: &str, files: Value, shares: [u16; 2]| {
+            json!({
+                "name": name,
+                "symbol": "NB",
+                "description": "Collection of numbers",
+                "seller_fee_basis_points": 500,
+                "image": "0.png",
+                "properties": {
+                    "files": files,
+                    "creators": [
+                        {"address": "BPr18DCdtzASf1YVbUVZ4dZ7mA6jpMYZSUP3YuiMgGeD", "share": shares[0]},
+                        {"address": "9WzDXwBbmkg8ZTbNMqUxvQRAyrZzDsGYdLVL9zYtAWWM", "share": shares[1]}
+                    ]
+                }
+            })
+        };
+        let files = json!([{"uri": "0.png", "type": "image/png"}]);
+
+        assert!(check_metadata(metadata("Numbers #1", files.clone(), [60, 40])).is_ok());
+
+        let reason = check_metadata(metadata("Numbers #1", json!([]), [60, 40])).unwrap_err();
+        assert_eq!(reason, "properties.files is empty");
+
+        let reason = check_metadata(metadata("Numbers #1", files.clone(), [60, 30])).unwrap_err();
+        assert!(reason.contains("share"), "{}", reason);
+
+        let long_name = "N".repeat(40);
+        assert!(check_metadata(metadata(&long_name, files, [60, 40])).is_err());
+
+        let reason = check_metadata(json!({ "name": "Numbers #1" })).unwrap_err();
+        assert!(reason.starts_with("invalid metadata"), "{}", reason);
+    }
+}
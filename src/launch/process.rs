@@ -1,15 +1,23 @@
 use std::sync::{atomic::AtomicBool, Arc};
 
-use anyhow::Result;
-use console::{style, Style};
-use dialoguer::{theme::ColorfulTheme, Confirm};
+use anyhow::{Error, Result};
 
+#[cfg(feature = "cli")]
 use crate::{
-    common::LAUNCH_EMOJI,
-    config::parser::get_config_data,
-    create_config::{process_create_config, CreateConfigArgs},
-    deploy::{process_deploy, DeployArgs},
-    upload::{process_upload, UploadArgs},
+    cli::create_config::{process_create_config, CreateConfigArgs},
+    output::{confirm, Confirmation},
+};
+use crate::{
+    common::{
+        DEFAULT_IPFS_GATEWAY, DEFAULT_RECEIPT_TIMEOUT_SECS, DEFAULT_UPLOAD_ERROR_REPORT,
+        LAUNCH_EMOJI,
+    },
+    compute_budget::ComputeBudget,
+    config::{parser::get_config_data, BundlrNode},
+    deploy::{process_deploy, DeployArgs, DEFAULT_DEPLOY_PARALLELISM, DEFAULT_MAX_RESUBMITS},
+    output::style,
+    priority_fee::PriorityFee,
+    upload::{process_upload, UploadArgs, UploadScope},
     validate::{process_validate, ValidateArgs},
     verify::{process_verify, VerifyArgs},
 };
@@ -24,49 +32,42 @@ pub struct LaunchArgs {
     pub skip_collection_prompt: bool,
     pub interrupted: Arc<AtomicBool>,
     pub priority_fee: u64,
+    pub concurrency: Option<usize>,
+    pub auto_fund: bool,
+    pub max_fund_lamports: Option<u64>,
+    pub bundlr_node: Option<BundlrNode>,
+    pub requests_per_second: Option<f64>,
+    pub shuffle: bool,
+    pub shuffle_seed: Option<u64>,
+    pub recursive: bool,
+    pub flush_every: usize,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub async fn process_launch(args: LaunchArgs) -> Result<()> {
-    println!("Starting Sugar launch... {}", LAUNCH_EMOJI);
-
-    let theme = ColorfulTheme {
-        prompt_style: Style::new(),
-        ..Default::default()
-    };
+    report!("Starting Sugar launch... {}", LAUNCH_EMOJI);
 
     if let Err(err) = get_config_data(&args.config) {
-        // padding
-        println!();
-        if Confirm::with_theme(&theme)
-            .with_prompt("Could not load config file. Would you like to create a new config file?")
-            .interact()?
-        {
-            println!("\n{} sugar create-config\n", style(">>>").magenta());
-
-            let create_config_args = CreateConfigArgs {
-                config: Some(args.config.clone()),
-                keypair: args.keypair.clone(),
-                rpc_url: args.rpc_url.clone(),
-                assets_dir: args.assets_dir.clone(),
-            };
-
-            process_create_config(create_config_args)?;
-        } else {
-            return Err(err.into());
-        }
+        create_missing_config(&args, err.into())?;
     }
 
-    println!("\n{} sugar validate\n", style(">>>").magenta());
+    report!("\n{} sugar validate\n", style(">>>").magenta());
 
     let validate_args = ValidateArgs {
         assets_dir: args.assets_dir.clone(),
         strict: args.strict,
         skip_collection_prompt: args.skip_collection_prompt,
+        config: Some(args.config.clone()),
+        // the cache is checked by the deploy step
+        cache: None,
+        json: false,
+        recursive: args.recursive,
     };
 
     process_validate(validate_args)?;
 
-    println!("\n{} sugar upload\n", style(">>>").magenta());
+    report!("\n{} sugar upload\n", style(">>>").magenta());
 
     let upload_args = UploadArgs {
         assets_dir: args.assets_dir.clone(),
@@ -76,11 +77,30 @@ pub async fn process_launch(args: LaunchArgs) -> Result<()> {
         cache: args.cache.clone(),
         interrupted: args.interrupted.clone(),
         priority_fee: args.priority_fee,
+        concurrency: args.concurrency,
+        only_missing: false,
+        force: false,
+        dry_run: false,
+        auto_fund: args.auto_fund,
+        max_fund_lamports: args.max_fund_lamports,
+        bundlr_node: args.bundlr_node.clone(),
+        requests_per_second: args.requests_per_second,
+        scope: UploadScope::All,
+        error_report: DEFAULT_UPLOAD_ERROR_REPORT.to_string(),
+        from_manifest: None,
+        shuffle: args.shuffle,
+        shuffle_seed: args.shuffle_seed,
+        summary: None,
+        recursive: args.recursive,
+        optimize_images: false,
+        verify_receipts: false,
+        receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+        flush_every: args.flush_every,
     };
 
     process_upload(upload_args).await?;
 
-    println!("\n{} sugar deploy\n", style(">>>").magenta());
+    report!("\n{} sugar deploy\n", style(">>>").magenta());
 
     let deploy_args = DeployArgs {
         config: args.config.clone(),
@@ -89,20 +109,65 @@ pub async fn process_launch(args: LaunchArgs) -> Result<()> {
         cache: args.cache.clone(),
         interrupted: args.interrupted.clone(),
         collection_mint: None,
-        priority_fee: args.priority_fee,
+        priority_fee: PriorityFee::MicroLamportsPerCu(args.priority_fee),
+        max_total_priority_sol: None,
+        dry_run: false,
+        force: false,
+        lines_per_tx: None,
+        parallelism: DEFAULT_DEPLOY_PARALLELISM,
+        max_resubmits: DEFAULT_MAX_RESUBMITS,
+        unsigned_tx: None,
+        use_lut: false,
+        chunk: None,
+        chunk_size: None,
+        verify: false,
+        candy_machine_keypair: None,
+        compute_budget: args.compute_budget,
+        allow_cluster_mismatch: args.allow_cluster_mismatch,
     };
 
     process_deploy(deploy_args).await?;
 
-    println!("\n{} sugar verify\n", style(">>>").magenta());
+    report!("\n{} sugar verify\n", style(">>>").magenta());
 
     let verify_args = VerifyArgs {
         keypair: args.keypair.clone(),
         rpc_url: args.rpc_url.clone(),
         cache: args.cache.clone(),
+        chunk: None,
+        ipfs_gateway: DEFAULT_IPFS_GATEWAY.to_string(),
     };
 
-    process_verify(verify_args)?;
+    process_verify(verify_args).await?;
 
     Ok(())
 }
+
+// offers to create the config file with the create-config prompts
+#[cfg(feature = "cli")]
+fn create_missing_config(args: &LaunchArgs, err: Error) -> Result<()> {
+    // padding
+    report!();
+    if confirm(Confirmation::new(
+        "Could not load config file. Would you like to create a new config file?",
+    ))? {
+        report!("\n{} sugar create-config\n", style(">>>").magenta());
+
+        let create_config_args = CreateConfigArgs {
+            config: Some(args.config.clone()),
+            keypair: args.keypair.clone(),
+            rpc_url: args.rpc_url.clone(),
+            assets_dir: args.assets_dir.clone(),
+        };
+
+        process_create_config(create_config_args)
+    } else {
+        Err(err)
+    }
+}
+
+// the create-config prompts are part of the CLI
+#[cfg(not(feature = "cli"))]
+fn create_missing_config(_args: &LaunchArgs, err: Error) -> Result<()> {
+    Err(err)
+}
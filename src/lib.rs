@@ -1,29 +1,52 @@
+// first, so that its report! macros are available in all the modules
+#[macro_use]
+pub mod output;
+
+pub mod account_fetcher;
 pub mod airdrop;
+pub mod api;
 pub mod ardrive;
+pub mod bubblegum;
 pub mod bundlr;
 pub mod cache;
+pub mod cancel;
 pub mod candy_machine;
+pub mod cleanup;
+#[cfg(feature = "cli")]
 pub mod cli;
 pub mod collections;
 pub mod common;
+pub mod compute_budget;
 pub mod config;
 pub mod constants;
 pub mod create_config;
 pub mod deploy;
+pub mod doctor;
 pub mod errors;
+pub mod estimate;
+pub mod export_metadata;
 pub mod freeze;
 pub mod guard;
 pub mod hash;
 pub mod import_nfts;
 pub mod launch;
+pub mod lut;
 pub mod mint;
+pub mod mint_list;
+pub mod offline;
 pub mod parse;
 pub mod pdas;
+pub mod priority_fee;
 pub mod program_errors;
+pub mod progress_stream;
 pub mod reveal;
+pub mod royalties;
 pub mod setup;
 pub mod show;
 pub mod sign;
+#[cfg(feature = "integration-tests")]
+pub mod test_validator;
+pub mod tree;
 pub mod update;
 pub mod upload;
 pub mod utils;
@@ -0,0 +1,209 @@
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+use solana_address_lookup_table_program::instruction::{create_lookup_table, extend_lookup_table};
+use spl_token::ID as TOKEN_PROGRAM_ID;
+
+use crate::{
+    cache::load_cache,
+    candy_machine::{get_candy_machine_state, CANDY_MACHINE_ID},
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    mint::collection_delegate_record,
+    output::style,
+    pdas::*,
+    utils::*,
+};
+
+/// Number of addresses added to the lookup table per transaction.
+const ADDRESSES_PER_TX: usize = 20;
+
+pub struct LutCreateArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub candy_machine: Option<String>,
+    pub priority_fee: u64,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
+}
+
+/// Addresses of the lookup table: the accounts of the mint transactions that do not sign them.
+pub fn lookup_table_addresses(
+    candy_machine: &Pubkey,
+    collection_mint: &Pubkey,
+    collection_delegate_record: &Pubkey,
+    collection_update_authority: &Pubkey,
+    candy_guard: Option<&Pubkey>,
+) -> Result<Vec<Pubkey>> {
+    let mut addresses = vec![
+        *candy_machine,
+        find_candy_machine_creator_pda(candy_machine).0,
+        CANDY_MACHINE_ID,
+        *collection_mint,
+        find_metadata_pda(collection_mint),
+        find_master_edition_pda(collection_mint),
+        *collection_delegate_record,
+        *collection_update_authority,
+        Pubkey::from_str(METAPLEX_PROGRAM_ID)?,
+        TOKEN_PROGRAM_ID,
+        spl_associated_token_account::ID,
+        system_program::id(),
+        sysvar::instructions::ID,
+        sysvar::slot_hashes::ID,
+    ];
+
+    if let Some(candy_guard) = candy_guard {
+        addresses.push(*candy_guard);
+        addresses.push(mpl_candy_guard::ID);
+    }
+
+    // the collection update authority might be one of the other accounts
+    let mut unique = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        if !unique.contains(&address) {
+            unique.push(address);
+        }
+    }
+
+    Ok(unique)
+}
+
+pub fn process_lut_create(args: LutCreateArgs) -> Result<()> {
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(CANDY_MACHINE_ID);
+    let rpc = program.rpc();
+    let payer = program.payer();
+
+    let mut cache = load_cache(&args.cache, false)?;
+    cache.check_cluster(&rpc, args.allow_cluster_mismatch)?;
+
+    if !cache.program.lookup_table.is_empty() {
+        return Err(anyhow!(
+            "Cache already has a lookup table: {}",
+            cache.program.lookup_table
+        ));
+    }
+
+    // the candy machine id specified takes precedence over the one from the cache
+
+    let candy_machine_id = args
+        .candy_machine
+        .unwrap_or_else(|| cache.program.candy_machine.clone());
+    let candy_pubkey = Pubkey::from_str(&candy_machine_id)
+        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", candy_machine_id))?;
+    let candy_guard = if cache.program.candy_guard.is_empty() {
+        None
+    } else {
+        Some(Pubkey::from_str(&cache.program.candy_guard).map_err(|_| {
+            anyhow!(
+                "Failed to parse candy guard id: {}",
+                cache.program.candy_guard
+            )
+        })?)
+    };
+
+    report!(
+        "{} {}Creating address lookup table",
+        style("[1/1]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+    report!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+
+    let pb = spinner_with_style();
+    pb.set_message("Sending lookup table transactions...");
+
+    let candy_machine_state = get_candy_machine_state(&sugar_config, &candy_pubkey)?;
+    let collection_mint = candy_machine_state.collection_mint;
+    let (_, collection_metadata) = get_metadata_pda(&collection_mint, &program)?;
+    let collection_update_authority = collection_metadata.update_authority;
+
+    let addresses = lookup_table_addresses(
+        &candy_pubkey,
+        &collection_mint,
+        &collection_delegate_record(
+            &candy_machine_state,
+            &candy_pubkey,
+            &collection_update_authority,
+        ),
+        &collection_update_authority,
+        candy_guard.as_ref(),
+    )?;
+
+    // the table address is derived from a recent slot
+    let recent_slot = rpc.get_slot_with_commitment(CommitmentConfig::finalized())?;
+    let (create_instruction, lookup_table) = create_lookup_table(payer, payer, recent_slot);
+
+    for (index, chunk) in addresses.chunks(ADDRESSES_PER_TX).enumerate() {
+        let mut instructions = vec![ComputeBudgetInstruction::set_compute_unit_price(
+            args.priority_fee,
+        )];
+        if index == 0 {
+            instructions.push(create_instruction.clone());
+        }
+        instructions.push(extend_lookup_table(
+            lookup_table,
+            payer,
+            Some(payer),
+            chunk.to_vec(),
+        ));
+
+        let signature = send_transaction(
+            &rpc,
+            &sugar_config.keypair,
+            &instructions,
+            &[],
+            &args.compute_budget,
+        )?;
+        info!("Lookup table transaction: {}", signature);
+    }
+
+    cache.program.lookup_table = lookup_table.to_string();
+    cache.sync_file()?;
+
+    pb.finish_and_clear();
+
+    report!("{} {}", style("Lookup table:").bold(), lookup_table);
+    report!("{} {}", style("Addresses:").bold(), addresses.len());
+    report!(
+        "\nThe lookup table can be used from the next slot with the --use-lut option of deploy, \
+        mint and airdrop."
+    );
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_table_addresses_are_unique() {
+        let candy_machine = Pubkey::new_unique();
+        let collection_mint = Pubkey::new_unique();
+        let delegate_record = Pubkey::new_unique();
+        let candy_guard = Pubkey::new_unique();
+
+        let addresses = lookup_table_addresses(
+            &candy_machine,
+            &collection_mint,
+            &delegate_record,
+            &Pubkey::new_unique(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(addresses.len(), 14);
+
+        // the candy machine as collection update authority is only added once
+        let addresses = lookup_table_addresses(
+            &candy_machine,
+            &collection_mint,
+            &delegate_record,
+            &candy_machine,
+            Some(&candy_guard),
+        )
+        .unwrap();
+        assert_eq!(addresses.len(), 15);
+        assert!(addresses.contains(&candy_guard));
+        assert!(addresses.contains(&find_metadata_pda(&collection_mint)));
+    }
+}
@@ -0,0 +1,5 @@
+pub mod create;
+pub mod table;
+
+pub use create::*;
+pub use table::*;
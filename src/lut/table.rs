@@ -0,0 +1,56 @@
+use anchor_client::solana_sdk::address_lookup_table_account::AddressLookupTableAccount;
+use solana_address_lookup_table_program::state::AddressLookupTable;
+use solana_client::rpc_client::RpcClient;
+
+use crate::{common::*, output::style};
+
+/// Reads the addresses of a lookup table.
+pub fn get_lookup_table(rpc: &RpcClient, address: &Pubkey) -> Result<AddressLookupTableAccount> {
+    let data = rpc
+        .get_account_data(address)
+        .map_err(|_| anyhow!("Lookup table not found: {}", address))?;
+    let table = AddressLookupTable::deserialize(&data)
+        .map_err(|e| anyhow!("Failed to read lookup table {}: {}", address, e))?;
+
+    Ok(AddressLookupTableAccount {
+        key: *address,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+/// Lookup table of the cache used by `--use-lut`. The transactions are sent as legacy
+/// transactions when the cache has none.
+pub fn cache_lookup_table(
+    rpc: &RpcClient,
+    cache: &Cache,
+) -> Result<Option<AddressLookupTableAccount>> {
+    if cache.program.lookup_table.is_empty() {
+        report!(
+            "{}{}",
+            WARNING_EMOJI,
+            style(
+                "No lookup table in the cache (run 'sugar lut create'), sending legacy \
+                transactions"
+            )
+            .yellow()
+        );
+        return Ok(None);
+    }
+
+    let address = Pubkey::from_str(&cache.program.lookup_table).map_err(|_| {
+        anyhow!(
+            "Failed to parse lookup table address: {}",
+            cache.program.lookup_table
+        )
+    })?;
+    let lookup_table = get_lookup_table(rpc, &address)?;
+
+    report!(
+        "{} {} ({} addresses)",
+        style("Lookup table:").bold(),
+        address,
+        lookup_table.addresses.len()
+    );
+
+    Ok(Some(lookup_table))
+}
@@ -2,10 +2,7 @@ use std::{
     fs::OpenOptions,
     path::PathBuf,
     str::FromStr,
-    sync::{
-        atomic::{AtomicBool, Ordering},
-        Arc,
-    },
+    sync::{atomic::AtomicBool, Arc},
 };
 
 use anyhow::{anyhow, Result};
@@ -14,36 +11,62 @@ use console::style;
 use sugar_cli::{
     airdrop::{process_airdrop, AirdropArgs},
     ardrive::{
-        process_ardrive_delete, process_ardrive_info, process_ardrive_list,
-        process_ardrive_list_all_drives, process_ardrive_list_drive_files,
-        process_ardrive_list_drives, process_ardrive_set_wallet, process_ardrive_upload,
+        ardrive_exit_code, arfs::resolve_gateway, process_ardrive_balance, process_ardrive_cost,
+        process_ardrive_create_drive, process_ardrive_create_folder, process_ardrive_delete,
+        process_ardrive_download, process_ardrive_generate_cache, process_ardrive_info,
+        process_ardrive_list, process_ardrive_list_all_drives, process_ardrive_list_drive_files,
+        process_ardrive_list_drives, process_ardrive_list_wallets, process_ardrive_set_wallet,
+        process_ardrive_show_wallet, process_ardrive_sync, process_ardrive_upload,
+        process_ardrive_verify_cache, process_ardrive_wipe_cache, resolve_wallet_profile,
+        ArdriveCostArgs, ArdriveCreateDriveArgs, ArdriveCreateFolderArgs, ArdriveDeleteArgs,
+        ArdriveGenerateCacheArgs, ArdriveListDriveFilesArgs, ArdriveOptions, ArdriveSyncArgs,
+        ArdriveUploadArgs, ArdriveVerifyCacheArgs, CacheSource, FileQuery, OutputFormat,
     },
     bundlr::{process_bundlr, BundlrArgs},
+    cancel::{install_cancel_handler, CancelReason, Cancelled, EXIT_PARTIAL},
+    cleanup::{process_cleanup_token_accounts, CleanupTokenAccountsArgs},
     cli::{
-        ArdriveCommand, Cli, CollectionSubcommands, Commands, ConfigSubcommands, FreezeCommand,
-        GuardCommand,
+        create_config::{process_create_config, CreateConfigArgs},
+        terminal::CliTerminal,
+        ArdriveCommand, CacheSubcommands, CleanupCommand, Cli, CollectionSubcommands, Commands,
+        ConfigSubcommands, EstimateCommand, FreezeCommand, GuardCommand, LutCommand,
+        RoyaltiesCommand, TreeCommand,
+    },
+    collections::{
+        process_audit_collection, process_set_collection, AuditCollectionArgs, SetCollectionArgs,
     },
-    collections::{process_set_collection, SetCollectionArgs},
-    constants::{COMPLETE_EMOJI, ERROR_EMOJI},
-    create_config::{process_create_config, CreateConfigArgs},
+    compute_budget::ComputeBudget,
+    constants::{COMPLETE_EMOJI, ERROR_EMOJI, WARNING_EMOJI},
     deploy::{process_deploy, DeployArgs},
+    doctor::{process_doctor, CheckStatus, DoctorArgs},
+    estimate::{process_estimate_rent, EstimateRentArgs},
+    export_metadata::{process_export_metadata, ExportMetadataArgs},
     freeze::{
         process_initialize, process_thaw, process_unlock_funds, InitializeArgs, ThawArgs,
         UnlockFundsArgs,
     },
     guard::{
-        process_guard_add, process_guard_remove, process_guard_show, process_guard_update,
-        process_guard_withdraw, GuardAddArgs, GuardRemoveArgs, GuardShowArgs, GuardUpdateArgs,
-        GuardWithdrawArgs,
+        process_guard_add, process_guard_remove, process_guard_schedule, process_guard_show,
+        process_guard_update, process_guard_withdraw, process_guard_wrap, GuardAddArgs,
+        GuardRemoveArgs, GuardScheduleArgs, GuardShowArgs, GuardUpdateArgs, GuardWithdrawArgs,
+        GuardWrapArgs,
     },
     hash::{process_hash, HashArgs},
     import_nfts::{process_import_nfts_cmd, ImportNFTsArgs},
     launch::{process_launch, LaunchArgs},
+    lut::{process_lut_create, LutCreateArgs},
     mint::{process_mint, MintArgs},
+    offline::{process_send_signed, SendSignedArgs, UnsignedTxArgs},
+    output::set_terminal,
     parse::parse_sugar_errors,
+    progress_stream::{close_progress_stream, emit, init_progress_stream, ProgressEvent},
     reveal::{process_reveal, RevealArgs},
+    royalties::{
+        process_royalties_set, process_royalties_show, RoyaltiesSetArgs, RoyaltiesShowArgs,
+    },
     show::{process_show, ShowArgs},
     sign::{process_sign, SignArgs},
+    tree::{process_tree_create, process_tree_show, TreeCreateArgs, TreeShowArgs},
     update::{process_set_token_stardard, process_update, SetTokenStandardArgs, UpdateArgs},
     upload::{process_upload, UploadArgs},
     validate::{process_validate, ValidateArgs},
@@ -87,33 +110,117 @@ fn setup_logging(level: Option<EnvFilter>) -> Result<()> {
 
 #[tokio::main]
 async fn main() {
-    match run().await {
+    // the library only reports its output, the CLI prints it
+    set_terminal(CliTerminal);
+
+    let cli = Cli::parse();
+    // quiet commands only print their summary line
+    let quiet = cli.quiet();
+
+    match run(cli).await {
         Ok(()) => {
+            emit(ProgressEvent::Finished { success: true });
+            close_progress_stream();
+
+            if !quiet {
+                println!(
+                    "\n{}{}",
+                    COMPLETE_EMOJI,
+                    style("Command successful.").green().bold().dim()
+                );
+            }
+        }
+        Err(err) if err.downcast_ref::<Cancelled>().is_some() => {
+            emit(ProgressEvent::Error {
+                message: err.to_string(),
+            });
+            emit(ProgressEvent::Finished { success: false });
+            close_progress_stream();
+
             println!(
-                "\n{}{}",
-                COMPLETE_EMOJI,
-                style("Command successful.").green().bold().dim()
+                "\n{}{} {}",
+                WARNING_EMOJI,
+                style("Command stopped (partial):").yellow(),
+                err,
             );
+            // the work done so far was saved, re-running the command resumes it
+            std::process::exit(EXIT_PARTIAL);
         }
         Err(err) => {
             let parsed_err = parse_sugar_errors(&err.to_string());
 
+            emit(ProgressEvent::Error {
+                message: parsed_err.clone(),
+            });
+            emit(ProgressEvent::Finished { success: false });
+            close_progress_stream();
+
             println!(
                 "\n{}{} {}",
                 ERROR_EMOJI,
                 style("Error running command (re-run needed):").red(),
                 parsed_err,
             );
-            // finished the program with an error code to the OS
-            std::process::exit(1);
+            // finished the program with an error code to the OS (ardrive errors have their own)
+            std::process::exit(ardrive_exit_code(&err).unwrap_or(1));
         }
     }
 }
 
-async fn run() -> Result<()> {
+/// Exits without the status line when the output is JSON, so that stdout only has the JSON
+/// document.
+fn finish_output(format: OutputFormat) {
+    if format == OutputFormat::Json {
+        finish_json(true);
+    }
+}
+
+/// Exits after a JSON document, with an error code when `success` is false.
+fn finish_json(success: bool) {
+    emit(ProgressEvent::Finished { success });
+    close_progress_stream();
+    std::process::exit(if success { 0 } else { 1 });
+}
+
+/// Exits a command that could not stop by itself after being cancelled.
+fn abort(reason: CancelReason) {
+    println!(
+        "\n\n{}{} {}.",
+        ERROR_EMOJI,
+        style("Error running command (re-run needed):").red(),
+        reason
+    );
+    // the progress saved before the cancel is kept, re-running the command resumes it
+    std::process::exit(EXIT_PARTIAL);
+}
+
+/// Options of the unsigned transactions, when written with `--unsigned-tx-out`.
+fn unsigned_tx_args(
+    out_dir: Option<String>,
+    authority: Option<String>,
+    nonce_accounts: Vec<String>,
+    compute_budget: ComputeBudget,
+) -> Option<UnsignedTxArgs> {
+    out_dir.map(|out_dir| UnsignedTxArgs {
+        out_dir,
+        authority,
+        nonce_accounts,
+        compute_budget,
+    })
+}
+
+async fn run(cli: Cli) -> Result<()> {
     solana_logger::setup_with_default("solana=off");
 
-    let cli = Cli::parse();
+    let allow_cluster_mismatch = cli.allow_cluster_mismatch;
+    let compute_budget = ComputeBudget {
+        compute_unit_limit: cli.compute_unit_limit,
+        simulate: cli.simulate,
+    };
+
+    if let Some(path) = &cli.progress_stream {
+        init_progress_stream(path)?;
+    }
 
     let log_level_error: Result<()> = Err(anyhow!(
         "Invalid log level: {:?}.\n Valid levels are: trace, debug, info, warn, error.",
@@ -133,37 +240,69 @@ async fn run() -> Result<()> {
     tracing::info!("Lend me some sugar, I am your neighbor.");
 
     let interrupted = Arc::new(AtomicBool::new(true));
-    let ctrl_handler = interrupted.clone();
-
-    ctrlc::set_handler(move || {
-        if ctrl_handler.load(Ordering::SeqCst) {
-            // we really need to exit
-            println!(
-                "\n\n{}{} Operation aborted.",
-                ERROR_EMOJI,
-                style("Error running command (re-run needed):").red(),
-            );
-            // finished the program with an error code to the OS
-            std::process::exit(1);
-        }
-        // signal that we want to exit
-        ctrl_handler.store(true, Ordering::SeqCst);
-    })
-    .expect("Error setting Ctrl-C handler");
+    install_cancel_handler(interrupted.clone(), cli.command_timeout, abort)?;
 
     match cli.command {
         Commands::Bundlr {
             keypair,
             rpc_url,
+            config,
+            bundlr_node,
             action,
         } => {
             process_bundlr(BundlrArgs {
                 keypair,
                 rpc_url,
+                config,
+                bundlr_node,
                 action,
             })
             .await?
         }
+        Commands::Cache { command } => match command {
+            CacheSubcommands::ExportMetadata {
+                cache,
+                output,
+                with_images,
+                parallel,
+            } => {
+                process_export_metadata(ExportMetadataArgs {
+                    cache,
+                    output,
+                    with_images,
+                    parallel,
+                })
+                .await?
+            }
+        },
+        Commands::Cleanup { command } => match command {
+            CleanupCommand::TokenAccounts {
+                keypair,
+                rpc_url,
+                cache,
+                owner,
+                mint,
+                close_empty,
+                burn_dust,
+                max_dust,
+                dry_run,
+                priority_fee,
+                max_total_priority_sol,
+            } => process_cleanup_token_accounts(CleanupTokenAccountsArgs {
+                keypair,
+                rpc_url,
+                cache,
+                owner,
+                mint,
+                close_empty,
+                burn_dust,
+                max_dust,
+                dry_run,
+                priority_fee,
+                max_total_priority_sol,
+                allow_cluster_mismatch,
+            })?,
+        },
         Commands::Collection { command } => match command {
             CollectionSubcommands::Set {
                 keypair,
@@ -173,6 +312,9 @@ async fn run() -> Result<()> {
                 candy_machine,
                 collection_mint,
                 priority_fee,
+                unsigned_tx_out,
+                authority,
+                nonce_account,
             } => process_set_collection(SetCollectionArgs {
                 collection_mint,
                 keypair,
@@ -181,7 +323,37 @@ async fn run() -> Result<()> {
                 config,
                 candy_machine,
                 priority_fee,
+                compute_budget,
+                unsigned_tx: unsigned_tx_args(
+                    unsigned_tx_out,
+                    authority,
+                    nonce_account,
+                    compute_budget,
+                ),
+                allow_cluster_mismatch,
             })?,
+            CollectionSubcommands::Audit {
+                keypair,
+                rpc_url,
+                cache,
+                mint_list,
+                candy_machine,
+                repair,
+                target,
+                timeout,
+            } => {
+                process_audit_collection(AuditCollectionArgs {
+                    keypair,
+                    rpc_url,
+                    cache,
+                    mint_list,
+                    candy_machine,
+                    repair,
+                    target,
+                    timeout,
+                })
+                .await?
+            }
         },
         Commands::Config { command } => match command {
             ConfigSubcommands::Create {
@@ -203,6 +375,9 @@ async fn run() -> Result<()> {
                 new_authority,
                 candy_machine,
                 priority_fee,
+                unsigned_tx_out,
+                authority,
+                nonce_account,
             } => process_update(UpdateArgs {
                 config,
                 keypair,
@@ -211,6 +386,14 @@ async fn run() -> Result<()> {
                 new_authority,
                 candy_machine,
                 priority_fee,
+                compute_budget,
+                unsigned_tx: unsigned_tx_args(
+                    unsigned_tx_out,
+                    authority,
+                    nonce_account,
+                    compute_budget,
+                ),
+                allow_cluster_mismatch,
             })?,
             ConfigSubcommands::Set {
                 keypair,
@@ -228,6 +411,7 @@ async fn run() -> Result<()> {
                 candy_machine,
                 rule_set,
                 priority_fee,
+                allow_cluster_mismatch,
             })?,
         },
         Commands::Deploy {
@@ -237,6 +421,20 @@ async fn run() -> Result<()> {
             cache,
             collection_mint,
             priority_fee,
+            max_total_priority_sol,
+            dry_run,
+            force,
+            lines_per_tx,
+            parallelism,
+            max_resubmits,
+            unsigned_tx_out,
+            authority,
+            nonce_account,
+            use_lut,
+            chunk,
+            chunk_size,
+            verify,
+            candy_machine_keypair,
         } => {
             process_deploy(DeployArgs {
                 config,
@@ -246,9 +444,66 @@ async fn run() -> Result<()> {
                 interrupted: interrupted.clone(),
                 collection_mint,
                 priority_fee,
+                compute_budget,
+                max_total_priority_sol,
+                dry_run,
+                force,
+                lines_per_tx,
+                parallelism,
+                max_resubmits,
+                unsigned_tx: unsigned_tx_args(
+                    unsigned_tx_out,
+                    authority,
+                    nonce_account,
+                    compute_budget,
+                ),
+                use_lut,
+                chunk,
+                chunk_size,
+                verify,
+                candy_machine_keypair,
+                allow_cluster_mismatch,
             })
             .await?
         }
+        Commands::Doctor {
+            keypair,
+            rpc_url,
+            cache,
+            config,
+            json,
+        } => {
+            let checks = process_doctor(DoctorArgs {
+                keypair,
+                rpc_url,
+                cache,
+                config,
+                json,
+            })
+            .await?;
+
+            if json {
+                let failed = checks.iter().any(|c| c.status == CheckStatus::Fail);
+                finish_json(!failed);
+            }
+        }
+        Commands::Estimate { command } => match command {
+            EstimateCommand::Rent {
+                rpc_url,
+                config,
+                number,
+                name_length,
+                uri_length,
+                hidden_settings,
+            } => process_estimate_rent(EstimateRentArgs {
+                rpc_url,
+                config,
+                number,
+                name_length,
+                uri_length,
+                hidden_settings,
+            })?,
+        },
         Commands::Freeze { command } => match command {
             FreezeCommand::Initialize {
                 keypair,
@@ -270,6 +525,8 @@ async fn run() -> Result<()> {
                 label,
                 period,
                 priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
             })?,
             FreezeCommand::Thaw {
                 keypair,
@@ -302,6 +559,8 @@ async fn run() -> Result<()> {
                     timeout,
                     token,
                     priority_fee,
+                    compute_budget,
+                    allow_cluster_mismatch,
                 })
                 .await?
             }
@@ -327,6 +586,8 @@ async fn run() -> Result<()> {
                 label,
                 token,
                 priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
             })?,
         },
         Commands::Guard { command } => match command {
@@ -338,6 +599,9 @@ async fn run() -> Result<()> {
                 candy_machine,
                 candy_guard,
                 priority_fee,
+                no_wrap,
+                dry_run,
+                yes,
             } => process_guard_add(GuardAddArgs {
                 keypair,
                 rpc_url,
@@ -346,6 +610,11 @@ async fn run() -> Result<()> {
                 candy_machine,
                 candy_guard,
                 priority_fee,
+                compute_budget,
+                no_wrap,
+                dry_run,
+                yes,
+                allow_cluster_mismatch,
             })?,
             GuardCommand::Remove {
                 keypair,
@@ -361,45 +630,113 @@ async fn run() -> Result<()> {
                 candy_machine,
                 candy_guard,
                 priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
+            })?,
+            GuardCommand::Wrap {
+                keypair,
+                rpc_url,
+                cache,
+                candy_machine,
+                candy_guard,
+                priority_fee,
+            } => process_guard_wrap(GuardWrapArgs {
+                keypair,
+                rpc_url,
+                cache,
+                candy_machine,
+                candy_guard,
+                priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
             })?,
             GuardCommand::Show {
                 keypair,
                 rpc_url,
                 cache,
+                candy_machine,
                 candy_guard,
             } => process_guard_show(GuardShowArgs {
                 keypair,
                 rpc_url,
                 cache,
+                candy_machine,
                 candy_guard,
+                allow_cluster_mismatch,
             })?,
             GuardCommand::Update {
                 keypair,
                 rpc_url,
                 cache,
                 config,
+                candy_machine,
                 candy_guard,
                 priority_fee,
+                dry_run,
+                yes,
+                unsigned_tx_out,
+                authority,
+                nonce_account,
             } => process_guard_update(GuardUpdateArgs {
                 keypair,
                 rpc_url,
                 cache,
                 config,
+                candy_machine,
                 candy_guard,
                 priority_fee,
+                compute_budget,
+                dry_run,
+                yes,
+                unsigned_tx: unsigned_tx_args(
+                    unsigned_tx_out,
+                    authority,
+                    nonce_account,
+                    compute_budget,
+                ),
+                allow_cluster_mismatch,
+            })?,
+            GuardCommand::Schedule {
+                keypair,
+                rpc_url,
+                cache,
+                priority_fee,
+                candy_machine,
+                candy_guard,
+                start,
+                end,
+                label,
+                wait,
+            } => process_guard_schedule(GuardScheduleArgs {
+                keypair,
+                rpc_url,
+                cache,
+                candy_machine,
+                candy_guard,
+                start,
+                end,
+                label,
+                wait,
+                priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
             })?,
             GuardCommand::Withdraw {
                 keypair,
                 rpc_url,
                 cache,
+                candy_machine,
                 candy_guard,
                 priority_fee,
             } => process_guard_withdraw(GuardWithdrawArgs {
                 keypair,
                 rpc_url,
                 cache,
+                candy_machine,
                 candy_guard,
                 priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
             })?,
         },
         Commands::Hash {
@@ -420,6 +757,15 @@ async fn run() -> Result<()> {
             strict,
             skip_collection_prompt,
             priority_fee,
+            concurrency,
+            auto_fund,
+            max_fund_lamports,
+            bundlr_node,
+            requests_per_second,
+            shuffle,
+            shuffle_seed,
+            recursive,
+            flush_every,
         } => {
             process_launch(LaunchArgs {
                 assets_dir,
@@ -431,11 +777,85 @@ async fn run() -> Result<()> {
                 skip_collection_prompt,
                 interrupted: interrupted.clone(),
                 priority_fee,
+                compute_budget,
+                concurrency,
+                auto_fund,
+                max_fund_lamports,
+                bundlr_node,
+                requests_per_second,
+                shuffle,
+                shuffle_seed,
+                recursive,
+                flush_every,
+                allow_cluster_mismatch,
             })
             .await?
         }
-        Commands::Import { import, output } => {
-            process_import_nfts_cmd(ImportNFTsArgs { import, output }).await?;
+        Commands::Import {
+            import,
+            hashlist,
+            batch_size,
+            from_candy_machine,
+            keypair,
+            rpc_url,
+            format,
+            column_map,
+            output,
+            concurrency,
+            offline,
+            allow_duplicates,
+            skip_invalid,
+            dry_run,
+            name_template,
+            force_template,
+            verify,
+            continue_on_error,
+            update_hash,
+            config,
+            flush_every,
+            ipfs_gateway,
+            fresh,
+            append,
+            overwrite_items,
+            start_index,
+            renumber,
+            chunk_size,
+            chunk_prefix,
+            force,
+        } => {
+            process_import_nfts_cmd(ImportNFTsArgs {
+                import,
+                hashlist,
+                batch_size,
+                from_candy_machine,
+                keypair,
+                rpc_url,
+                format,
+                column_map,
+                output,
+                concurrency,
+                offline,
+                allow_duplicates,
+                skip_invalid,
+                dry_run,
+                name_template,
+                force_template,
+                verify,
+                continue_on_error,
+                update_hash,
+                config,
+                flush_every,
+                ipfs_gateway,
+                fresh,
+                append,
+                overwrite_items,
+                start_index,
+                renumber,
+                chunk_size,
+                chunk_prefix,
+                force,
+            })
+            .await?;
         }
         Commands::Mint {
             keypair,
@@ -445,6 +865,12 @@ async fn run() -> Result<()> {
             receiver,
             candy_machine,
             priority_fee,
+            max_total_priority_sol,
+            compressed,
+            merkle_tree,
+            config,
+            use_lut,
+            chunk,
         } => {
             process_mint(MintArgs {
                 keypair,
@@ -454,6 +880,14 @@ async fn run() -> Result<()> {
                 receiver,
                 candy_machine,
                 priority_fee,
+                compute_budget,
+                max_total_priority_sol,
+                compressed,
+                merkle_tree,
+                config,
+                use_lut,
+                chunk,
+                allow_cluster_mismatch,
             })
             .await?
         }
@@ -464,6 +898,11 @@ async fn run() -> Result<()> {
             candy_machine,
             airdrop_list,
             priority_fee,
+            max_total_priority_sol,
+            compressed,
+            merkle_tree,
+            config,
+            use_lut,
         } => {
             process_airdrop(AirdropArgs {
                 keypair,
@@ -472,6 +911,13 @@ async fn run() -> Result<()> {
                 candy_machine,
                 airdrop_list,
                 priority_fee,
+                compute_budget,
+                max_total_priority_sol,
+                compressed,
+                merkle_tree,
+                config,
+                use_lut,
+                allow_cluster_mismatch,
             })
             .await?
         }
@@ -482,6 +928,8 @@ async fn run() -> Result<()> {
             config,
             timeout,
             new_update_authority,
+            priority_fee,
+            max_total_priority_sol,
         } => {
             process_reveal(RevealArgs {
                 keypair,
@@ -490,22 +938,126 @@ async fn run() -> Result<()> {
                 config,
                 timeout,
                 new_update_authority,
+                priority_fee,
+                max_total_priority_sol,
+                allow_cluster_mismatch,
             })
             .await?
         }
+        Commands::Royalties { command } => match command {
+            RoyaltiesCommand::Set {
+                keypair,
+                rpc_url,
+                cache,
+                basis_points,
+                rule_set,
+                mint_list,
+                candy_machine,
+                dry_run,
+                timeout,
+            } => {
+                process_royalties_set(RoyaltiesSetArgs {
+                    keypair,
+                    rpc_url,
+                    cache,
+                    basis_points,
+                    rule_set,
+                    mint_list,
+                    candy_machine,
+                    dry_run,
+                    timeout,
+                })
+                .await?
+            }
+            RoyaltiesCommand::Show {
+                rpc_url,
+                cache,
+                config,
+                mint_list,
+                candy_machine,
+                timeout,
+            } => {
+                process_royalties_show(RoyaltiesShowArgs {
+                    rpc_url,
+                    cache,
+                    config,
+                    mint_list,
+                    candy_machine,
+                    timeout,
+                })
+                .await?
+            }
+        },
         Commands::Show {
             keypair,
             rpc_url,
             cache,
             candy_machine,
             unminted,
+            history,
+            limit,
+            since,
+            output,
         } => process_show(ShowArgs {
             keypair,
             rpc_url,
             cache,
             candy_machine,
             unminted,
+            history,
+            limit,
+            since,
+            output,
         })?,
+        Commands::Lut { command } => match command {
+            LutCommand::Create {
+                keypair,
+                rpc_url,
+                cache,
+                priority_fee,
+                candy_machine,
+            } => process_lut_create(LutCreateArgs {
+                keypair,
+                rpc_url,
+                cache,
+                candy_machine,
+                priority_fee,
+                compute_budget,
+                allow_cluster_mismatch,
+            })?,
+        },
+        Commands::Tree { command } => match command {
+            TreeCommand::Create {
+                keypair,
+                rpc_url,
+                cache,
+                priority_fee,
+                capacity,
+                max_buffer_size,
+                canopy_depth,
+                collection_mint,
+            } => process_tree_create(TreeCreateArgs {
+                keypair,
+                rpc_url,
+                cache,
+                capacity,
+                max_buffer_size,
+                canopy_depth,
+                collection_mint,
+                priority_fee,
+            })?,
+            TreeCommand::Show {
+                keypair,
+                rpc_url,
+                cache,
+                merkle_tree,
+            } => process_tree_show(TreeShowArgs {
+                keypair,
+                rpc_url,
+                cache,
+                merkle_tree,
+            })?,
+        },
         Commands::Upload {
             assets_dir,
             config,
@@ -513,6 +1065,25 @@ async fn run() -> Result<()> {
             rpc_url,
             cache,
             priority_fee,
+            concurrency,
+            only_missing,
+            force,
+            dry_run,
+            scope,
+            error_report,
+            from_manifest,
+            auto_fund,
+            max_fund_lamports,
+            bundlr_node,
+            requests_per_second,
+            shuffle,
+            shuffle_seed,
+            summary,
+            recursive,
+            optimize_images,
+            verify_receipts,
+            receipt_timeout,
+            flush_every,
         } => {
             process_upload(UploadArgs {
                 assets_dir,
@@ -522,58 +1093,370 @@ async fn run() -> Result<()> {
                 cache,
                 interrupted: interrupted.clone(),
                 priority_fee,
+                concurrency,
+                only_missing,
+                force,
+                dry_run,
+                scope,
+                error_report,
+                from_manifest,
+                auto_fund,
+                max_fund_lamports,
+                bundlr_node,
+                requests_per_second,
+                shuffle,
+                shuffle_seed,
+                summary,
+                recursive,
+                optimize_images,
+                verify_receipts,
+                receipt_timeout,
+                flush_every,
             })
             .await?
         }
-        Commands::Ardrive { command } => match command {
-            ArdriveCommand::Upload { file, bucket } => {
-                process_ardrive_upload(file, bucket)?;
-            }
-            ArdriveCommand::SetWallet { wallet } => {
-                process_ardrive_set_wallet(wallet)?;
-            }
-            ArdriveCommand::ListDrives { wallet, drive_id } => {
-                process_ardrive_list_drives(wallet, drive_id)?;
-            }
-            ArdriveCommand::ListDriveFiles {
-                wallet,
-                drive_id,
-                output,
-                filter,
-            } => {
-                process_ardrive_list_drive_files(wallet, drive_id, output, filter.as_deref())?;
-            }
-            ArdriveCommand::ListAllDrives { wallet, output } => {
-                process_ardrive_list_all_drives(wallet, output)?;
-            }
-            ArdriveCommand::List { bucket } => {
-                process_ardrive_list(bucket)?;
-            }
-            ArdriveCommand::Info { id } => {
-                process_ardrive_info(id)?;
-            }
-            ArdriveCommand::Delete { id } => {
-                process_ardrive_delete(id)?;
+        Commands::Ardrive {
+            max_retries,
+            retry_delay_ms,
+            refresh,
+            listing_ttl,
+            gateway,
+            quiet,
+            profile,
+            command,
+        } => {
+            let options = ArdriveOptions {
+                max_retries,
+                retry_delay_ms,
+                refresh,
+                listing_ttl_minutes: listing_ttl,
+                gateway: resolve_gateway(gateway)?,
+                quiet,
+                profile: resolve_wallet_profile(profile)?,
+            };
+
+            match command {
+                ArdriveCommand::Upload {
+                    path,
+                    wallet,
+                    drive_id,
+                    parent_folder,
+                    concurrency,
+                    dry_run,
+                    manifest,
+                    output,
+                    force_reupload,
+                    journal,
+                } => {
+                    process_ardrive_upload(ArdriveUploadArgs {
+                        wallet,
+                        path,
+                        drive_id,
+                        parent_folder,
+                        concurrency,
+                        dry_run,
+                        manifest,
+                        output,
+                        force_reupload,
+                        journal,
+                        options,
+                    })
+                    .await?;
+                }
+                ArdriveCommand::SetWallet { wallet } => {
+                    process_ardrive_set_wallet(wallet, &options)?;
+                }
+                ArdriveCommand::ListWallets => {
+                    process_ardrive_list_wallets(&options)?;
+                }
+                ArdriveCommand::ShowWallet {
+                    wallet,
+                    unsafe_full,
+                } => {
+                    process_ardrive_show_wallet(wallet, !unsafe_full, &options)?;
+                }
+                ArdriveCommand::Balance { wallet } => {
+                    process_ardrive_balance(wallet, &options).await?;
+                }
+                ArdriveCommand::Cost {
+                    assets_dir,
+                    no_fiat,
+                } => {
+                    process_ardrive_cost(ArdriveCostArgs {
+                        assets_dir,
+                        fiat: !no_fiat,
+                        options,
+                    })
+                    .await?;
+                }
+                ArdriveCommand::CreateDrive {
+                    name,
+                    private,
+                    drive_password,
+                    wallet,
+                    format,
+                } => {
+                    process_ardrive_create_drive(ArdriveCreateDriveArgs {
+                        wallet,
+                        name,
+                        private,
+                        drive_password,
+                        format,
+                        options,
+                    })
+                    .await?;
+                    finish_output(format);
+                }
+                ArdriveCommand::CreateFolder {
+                    name,
+                    drive_id,
+                    parent_folder,
+                    drive_password,
+                    wallet,
+                    format,
+                } => {
+                    process_ardrive_create_folder(ArdriveCreateFolderArgs {
+                        wallet,
+                        drive_id,
+                        name,
+                        parent_folder,
+                        drive_password,
+                        format,
+                        options,
+                    })
+                    .await?;
+                    finish_output(format);
+                }
+                ArdriveCommand::WipeCache { cache } => {
+                    process_ardrive_wipe_cache(cache)?;
+                }
+                ArdriveCommand::ListDrives {
+                    wallet,
+                    drive_id,
+                    drive_password,
+                    format,
+                } => {
+                    process_ardrive_list_drives(
+                        wallet,
+                        drive_id,
+                        drive_password,
+                        format,
+                        &options,
+                    )?;
+                    finish_output(format);
+                }
+                ArdriveCommand::ListDriveFiles {
+                    wallet,
+                    drive_id,
+                    output,
+                    filter,
+                    entity_type,
+                    content_type,
+                    parent_folder,
+                    limit,
+                    offset,
+                    drive_password,
+                    format,
+                    use_node_cli,
+                    check_status,
+                } => {
+                    process_ardrive_list_drive_files(ArdriveListDriveFilesArgs {
+                        wallet,
+                        drive_id,
+                        output_path: output,
+                        query: FileQuery {
+                            entity_type,
+                            parent_folder,
+                            folder_paths: Vec::new(),
+                            extension: filter,
+                            content_type,
+                            offset,
+                            limit,
+                        },
+                        drive_password,
+                        format,
+                        use_node_cli,
+                        check_status,
+                        options,
+                    })
+                    .await?;
+                    finish_output(format);
+                }
+                ArdriveCommand::GenerateCache {
+                    wallet,
+                    drive_id,
+                    files,
+                    parent_folder,
+                    folder_path,
+                    cache,
+                    candy_machine,
+                    // appending is the default when the cache file exists
+                    append: _,
+                    overwrite,
+                    since,
+                    rewrite_gateway,
+                    skip_hash,
+                    use_node_cli,
+                    check_status,
+                    allow_pending,
+                } => {
+                    let source = match (files, drive_id) {
+                        (Some(files), _) => CacheSource::FileList(files),
+                        (None, Some(drive_id)) => CacheSource::Drive {
+                            drive_id,
+                            parent_folder,
+                            folder_paths: folder_path,
+                            use_node_cli,
+                        },
+                        (None, None) => {
+                            return Err(anyhow!("A drive id or a file list is required"))
+                        }
+                    };
+                    process_ardrive_generate_cache(ArdriveGenerateCacheArgs {
+                        wallet,
+                        source,
+                        cache_file: cache,
+                        candy_machine,
+                        overwrite,
+                        since,
+                        rewrite_gateway,
+                        skip_hash,
+                        check_status,
+                        allow_pending,
+                        options,
+                    })
+                    .await?;
+                }
+                ArdriveCommand::Sync {
+                    assets_dir,
+                    drive_id,
+                    wallet,
+                    dry_run,
+                } => {
+                    process_ardrive_sync(ArdriveSyncArgs {
+                        wallet,
+                        assets_dir,
+                        drive_id,
+                        dry_run,
+                        options,
+                    })
+                    .await?;
+                }
+                ArdriveCommand::VerifyCache {
+                    cache,
+                    concurrency,
+                    check_confirmation,
+                    output,
+                } => {
+                    process_ardrive_verify_cache(ArdriveVerifyCacheArgs {
+                        cache,
+                        concurrency,
+                        check_confirmation,
+                        output,
+                        options,
+                    })
+                    .await?;
+                }
+                ArdriveCommand::Download {
+                    wallet,
+                    drive_id,
+                    output_dir,
+                    filter,
+                    concurrency,
+                    force,
+                    use_node_cli,
+                } => {
+                    process_ardrive_download(
+                        wallet,
+                        drive_id,
+                        output_dir,
+                        filter,
+                        concurrency,
+                        force,
+                        use_node_cli,
+                        &options,
+                    )
+                    .await?;
+                }
+                ArdriveCommand::ListAllDrives {
+                    wallet,
+                    output,
+                    drive_password,
+                    format,
+                    use_node_cli,
+                } => {
+                    process_ardrive_list_all_drives(
+                        wallet,
+                        output,
+                        drive_password,
+                        format,
+                        use_node_cli,
+                        &options,
+                    )
+                    .await?;
+                    finish_output(format);
+                }
+                ArdriveCommand::List { bucket } => {
+                    process_ardrive_list(bucket)?;
+                }
+                ArdriveCommand::Info { id, format } => {
+                    process_ardrive_info(id, format, &options).await?;
+                    finish_output(format);
+                }
+                ArdriveCommand::Delete {
+                    ids,
+                    ids_file,
+                    wallet,
+                    drive_password,
+                    yes,
+                    dry_run,
+                } => {
+                    process_ardrive_delete(ArdriveDeleteArgs {
+                        wallet,
+                        ids,
+                        ids_file,
+                        drive_password,
+                        yes,
+                        dry_run,
+                        options,
+                    })
+                    .await?;
+                }
             }
-        },
+        }
         Commands::Validate {
             assets_dir,
             strict,
             skip_collection_prompt,
+            config,
+            cache,
+            json,
+            recursive,
         } => process_validate(ValidateArgs {
             assets_dir,
             strict,
             skip_collection_prompt,
+            config: Some(config),
+            cache: Some(cache),
+            json,
+            recursive,
         })?,
         Commands::Verify {
             keypair,
             rpc_url,
             cache,
-        } => process_verify(VerifyArgs {
-            keypair,
-            rpc_url,
-            cache,
-        })?,
+            chunk,
+            ipfs_gateway,
+        } => {
+            process_verify(VerifyArgs {
+                keypair,
+                rpc_url,
+                cache,
+                chunk,
+                ipfs_gateway,
+            })
+            .await?
+        }
         Commands::Withdraw {
             candy_machine,
             keypair,
@@ -581,6 +1464,8 @@ async fn run() -> Result<()> {
             list,
             authority,
             priority_fee,
+            cache,
+            chunk,
         } => process_withdraw(WithdrawArgs {
             candy_machine,
             keypair,
@@ -588,6 +1473,19 @@ async fn run() -> Result<()> {
             list,
             authority,
             priority_fee,
+            cache,
+            chunk,
+            allow_cluster_mismatch,
+        })?,
+        Commands::SendSigned {
+            dir,
+            rpc_url,
+            cache,
+        } => process_send_signed(SendSignedArgs {
+            dir,
+            rpc_url,
+            cache,
+            allow_cluster_mismatch,
         })?,
         Commands::Sign {
             keypair,
@@ -595,6 +1493,7 @@ async fn run() -> Result<()> {
             cache,
             mint,
             candy_machine_id,
+            dry_run,
         } => {
             process_sign(SignArgs {
                 keypair,
@@ -602,6 +1501,8 @@ async fn run() -> Result<()> {
                 cache,
                 mint,
                 candy_machine_id,
+                dry_run,
+                allow_cluster_mismatch,
             })
             .await?
         }
@@ -0,0 +1,284 @@
+use std::ops::Deref;
+
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+use borsh::BorshDeserialize;
+use mpl_token_metadata::state::Metadata;
+
+use crate::{
+    bubblegum::{
+        find_asset_id, mint_to_collection_instruction, Collection, Creator, MetadataArgs,
+        MintToCollectionAccounts, TreeConfig, BUBBLEGUM_ID,
+    },
+    cache::load_cache,
+    common::*,
+    compute_budget::{send_transaction, ComputeBudget},
+    config::{get_config_data, ConfigData},
+    mint::MintArgs,
+    output::style,
+    pdas::{find_master_edition_pda, find_metadata_pda},
+    priority_fee::resolve_priority_fee,
+    tree::{get_tree_config, parse_merkle_tree, print_tree_info},
+    utils::*,
+};
+
+/// Everything needed to mint cache items as compressed NFTs into a merkle tree.
+pub struct CompressedMinter {
+    pub merkle_tree: Pubkey,
+    pub collection_mint: Pubkey,
+    config_data: ConfigData,
+    /// Cache items in leaf order (the collection item is excluded).
+    items: Vec<(String, CacheItem)>,
+}
+
+pub struct CompressedMintResult {
+    pub signature: Signature,
+    /// Asset id of the minted leaf (the id used by DAS).
+    pub asset_id: Pubkey,
+    /// Key of the cache item minted.
+    pub cache_key: String,
+}
+
+impl CompressedMinter {
+    /// Loads the minter from the cache and config files; the merkle tree specified
+    /// takes precedence over the one from the cache.
+    pub fn load(cache: &Cache, config: &str, merkle_tree: Option<String>) -> Result<Self> {
+        let merkle_tree = match merkle_tree {
+            Some(merkle_tree) => merkle_tree,
+            None => cache.program.merkle_tree.clone(),
+        };
+        let merkle_tree = parse_merkle_tree(&merkle_tree)?;
+
+        let collection_mint = Pubkey::from_str(&cache.program.collection_mint).map_err(|_| {
+            anyhow!(
+                "Missing or invalid collection mint in cache: '{}'",
+                cache.program.collection_mint
+            )
+        })?;
+
+        let config_data = get_config_data(config)?;
+
+        let items = cache
+            .items
+            .iter()
+            .filter(|(key, _)| key.as_str() != "-1")
+            .map(|(key, item)| (key.clone(), item.clone()))
+            .collect();
+
+        Ok(CompressedMinter {
+            merkle_tree,
+            collection_mint,
+            config_data,
+            items,
+        })
+    }
+
+    /// Number of items that can still be minted, limited by both the tree and the cache.
+    pub fn available(&self, tree_config: &TreeConfig) -> u64 {
+        let remaining_leaves = tree_config
+            .total_mint_capacity
+            .saturating_sub(tree_config.num_minted);
+        let remaining_items = (self.items.len() as u64).saturating_sub(tree_config.num_minted);
+
+        remaining_leaves.min(remaining_items)
+    }
+
+    pub fn verify_collection_authority<C: Deref<Target = impl Signer> + Clone>(
+        &self,
+        program: &Program<C>,
+    ) -> Result<()> {
+        let data = program
+            .rpc()
+            .get_account_data(&find_metadata_pda(&self.collection_mint))?;
+        let metadata = Metadata::deserialize(&mut data.as_slice())?;
+
+        if metadata.update_authority != program.payer() {
+            return Err(anyhow!(
+                "Payer is not the collection update authority ({}), mint disallowed.",
+                metadata.update_authority
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Mints the next cache item (the item at the index of the next leaf) to `receiver`.
+    pub fn mint<C: Deref<Target = impl Signer> + Clone>(
+        &self,
+        program: &Program<C>,
+        keypair: &Keypair,
+        receiver: Pubkey,
+        priority_fee: u64,
+        compute_budget: &ComputeBudget,
+    ) -> Result<CompressedMintResult> {
+        let tree_config = get_tree_config(&program.rpc(), &self.merkle_tree)?;
+        let leaf_index = tree_config.num_minted;
+
+        if leaf_index >= tree_config.total_mint_capacity {
+            return Err(anyhow!("Merkle tree {} is full.", self.merkle_tree));
+        }
+
+        let (cache_key, item) = self.items.get(leaf_index as usize).ok_or_else(|| {
+            anyhow!(
+                "No cache item available for leaf {} (cache has {} items).",
+                leaf_index,
+                self.items.len()
+            )
+        })?;
+
+        if item.metadata_link.is_empty() {
+            return Err(anyhow!(
+                "Cache item {} is missing its metadata link, run 'upload' first.",
+                cache_key
+            ));
+        }
+
+        let payer = program.payer();
+
+        let metadata = MetadataArgs {
+            name: item.name.clone(),
+            symbol: self.config_data.symbol.clone(),
+            uri: item.metadata_link.clone(),
+            seller_fee_basis_points: self.config_data.seller_fee_basis_points,
+            primary_sale_happened: false,
+            is_mutable: self.config_data.is_mutable,
+            edition_nonce: None,
+            token_standard: Some(0),
+            collection: Some(Collection {
+                verified: false,
+                key: self.collection_mint,
+            }),
+            uses: None,
+            token_program_version: 0,
+            creators: self
+                .config_data
+                .creators
+                .iter()
+                .map(|creator| Creator {
+                    address: creator.address,
+                    verified: false,
+                    share: creator.share,
+                })
+                .collect(),
+        };
+
+        let accounts = MintToCollectionAccounts {
+            merkle_tree: self.merkle_tree,
+            leaf_owner: receiver,
+            payer,
+            collection_mint: self.collection_mint,
+            collection_metadata: find_metadata_pda(&self.collection_mint),
+            collection_edition: find_master_edition_pda(&self.collection_mint),
+        };
+
+        let instructions = [
+            ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_UNITS),
+            ComputeBudgetInstruction::set_compute_unit_price(priority_fee),
+            mint_to_collection_instruction(&accounts, metadata)?,
+        ];
+
+        let signature =
+            send_transaction(&program.rpc(), keypair, &instructions, &[], compute_budget)?;
+
+        info!("Minted compressed NFT! TxId: {}", signature);
+
+        Ok(CompressedMintResult {
+            signature,
+            asset_id: find_asset_id(&self.merkle_tree, leaf_index),
+            cache_key: cache_key.clone(),
+        })
+    }
+}
+
+/// Marks the minted cache item as on-chain and syncs the cache file.
+pub fn record_compressed_mint(cache: &mut Cache, result: &CompressedMintResult) -> Result<()> {
+    if let Some(item) = cache.items.get_mut(&result.cache_key) {
+        item.on_chain = true;
+    }
+    cache.sync_file()
+}
+
+pub async fn process_mint_compressed(args: MintArgs) -> Result<()> {
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(BUBBLEGUM_ID);
+
+    let mut cache = load_cache(&args.cache, false)?;
+    cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
+
+    report!(
+        "{} {}Loading merkle tree",
+        style("[1/2]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let minter = CompressedMinter::load(&cache, &args.config, args.merkle_tree)?;
+    minter.verify_collection_authority(&program)?;
+    let tree_config = get_tree_config(&program.rpc(), &minter.merkle_tree)?;
+
+    pb.finish_with_message("Done");
+
+    print_tree_info(&minter.merkle_tree, &tree_config);
+
+    report!(
+        "\n{} {}Minting compressed NFTs",
+        style("[2/2]").bold().dim(),
+        CANDY_EMOJI
+    );
+
+    let receiver = match args.receiver {
+        Some(receiver_id) => Pubkey::from_str(&receiver_id)
+            .map_err(|_| anyhow!("Failed to parse receiver pubkey: {}", receiver_id))?,
+        None => sugar_config.keypair.pubkey(),
+    };
+    report!("\nMinting to {}", &receiver);
+
+    let number = args.number.unwrap_or(1);
+    let available = minter.available(&tree_config);
+
+    if number > available || number == 0 {
+        let error = anyhow!("{} item(s) available, requested {}", available, number);
+        error!("{:?}", error);
+        return Err(error);
+    }
+
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        args.compute_budget.compute_unit_limit(COMPUTE_UNITS),
+        number,
+        args.max_total_priority_sol,
+    )?;
+
+    // compressed mints are sent sequentially since the asset id depends on the leaf index
+    let pb = progress_bar_with_style(number);
+
+    for _ in 0..number {
+        match minter.mint(
+            &program,
+            &sugar_config.keypair,
+            receiver,
+            priority_fee,
+            &args.compute_budget,
+        ) {
+            Ok(result) => {
+                pb.println(format!(
+                    "Asset: {} Signature: {}",
+                    result.asset_id, result.signature
+                ));
+                record_compressed_mint(&mut cache, &result)?;
+                pb.inc(1);
+            }
+            Err(err) => {
+                pb.abandon_with_message(format!("{}", style("Mint failed ").red().bold()));
+                error!("{:?}", err);
+                return Err(err);
+            }
+        }
+    }
+
+    pb.finish();
+
+    Ok(())
+}
@@ -1,3 +1,5 @@
+pub mod compressed;
 pub mod process;
 
+pub use compressed::*;
 pub use process::*;
@@ -1,14 +1,13 @@
 use std::{str::FromStr, sync::Arc};
 
 use anchor_client::solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
     compute_budget::ComputeBudgetInstruction,
     pubkey::Pubkey,
     signature::{Keypair, Signature, Signer},
     system_program, sysvar,
 };
 use anyhow::Result;
-use borsh::BorshDeserialize;
-use console::style;
 use mpl_candy_machine_core::{
     accounts as nft_accounts, instruction as nft_instruction, AccountVersion, CandyMachine,
 };
@@ -18,19 +17,25 @@ use mpl_token_metadata::{
         find_collection_authority_account, find_metadata_delegate_record_account,
         find_token_record_account,
     },
-    state::Metadata,
 };
-use solana_client::rpc_response::Response;
+use solana_client::{rpc_client::RpcClient, rpc_response::Response};
 use spl_associated_token_account::get_associated_token_address;
 use spl_token::ID as TOKEN_PROGRAM_ID;
 use tokio::sync::Semaphore;
 
 use crate::{
-    cache::load_cache,
+    cache::load_cache_or_chunk,
+    cancel,
     candy_machine::{CANDY_MACHINE_ID, *},
     common::*,
+    compute_budget::{send_transaction_with_lookup_table, ComputeBudget},
     config::{Cluster, SugarConfig},
+    lut::cache_lookup_table,
+    mint::process_mint_compressed,
+    output::style,
     pdas::*,
+    priority_fee::{resolve_priority_fee, PriorityFee},
+    progress_stream,
     utils::*,
 };
 
@@ -41,10 +46,22 @@ pub struct MintArgs {
     pub number: Option<u64>,
     pub receiver: Option<String>,
     pub candy_machine: Option<String>,
-    pub priority_fee: u64,
+    pub priority_fee: PriorityFee,
+    pub max_total_priority_sol: Option<f64>,
+    pub compressed: bool,
+    pub merkle_tree: Option<String>,
+    pub config: String,
+    pub use_lut: bool,
+    pub chunk: Option<u32>,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub async fn process_mint(args: MintArgs) -> Result<()> {
+    if args.compressed {
+        return process_mint_compressed(args).await;
+    }
+
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
     let client = setup_client(&sugar_config)?;
     let program = client.program(CANDY_MACHINE_ID);
@@ -54,7 +71,8 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
     let candy_machine_id = match args.candy_machine {
         Some(candy_machine_id) => candy_machine_id,
         None => {
-            let cache = load_cache(&args.cache, false)?;
+            let mut cache = load_cache_or_chunk(&args.cache, args.chunk)?;
+            cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
             cache.program.candy_machine
         }
     };
@@ -68,12 +86,13 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
         }
     };
 
-    println!(
+    report!(
         "{} {}Loading candy machine",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
-    println!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+    report!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+    progress_stream::phase_started("load_candy_machine", 1, 2);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -84,19 +103,28 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
     let collection_update_authority = collection_metadata.update_authority;
 
     pb.finish_with_message("Done");
+    progress_stream::phase_finished();
+
+    let lookup_table = if args.use_lut {
+        let cache = load_cache_or_chunk(&args.cache, args.chunk)?;
+        cache_lookup_table(&program.rpc(), &cache)?.map(Arc::new)
+    } else {
+        None
+    };
 
-    println!(
+    report!(
         "\n{} {}Minting from candy machine",
         style("[2/2]").bold().dim(),
         CANDY_EMOJI
     );
+    progress_stream::phase_started("mint", 2, 2);
 
     let receiver_pubkey = match args.receiver {
         Some(receiver_id) => Pubkey::from_str(&receiver_id)
             .map_err(|_| anyhow!("Failed to parse receiver pubkey: {}", receiver_id))?,
         None => sugar_config.keypair.pubkey(),
     };
-    println!("\nMinting to {}", &receiver_pubkey);
+    report!("\nMinting to {}", &receiver_pubkey);
 
     let number = args.number.unwrap_or(1);
     let available = candy_machine_state.data.items_available - candy_machine_state.items_redeemed;
@@ -107,8 +135,17 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
         return Err(error);
     }
 
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        args.compute_budget.compute_unit_limit(COMPUTE_UNITS),
+        number,
+        args.max_total_priority_sol,
+    )?;
+    let compute_budget = args.compute_budget;
+
     info!("Minting NFT from candy machine: {}", &candy_machine_id);
     info!("Candy machine program id: {:?}", CANDY_MACHINE_ID);
+    progress_stream::totals(number);
 
     if number == 1 {
         let pb = spinner_with_style();
@@ -124,16 +161,20 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
             Arc::clone(&candy_machine_state),
             collection_update_authority,
             receiver_pubkey,
-            args.priority_fee,
+            priority_fee,
+            lookup_table,
+            compute_budget,
         )
         .await
         {
             Ok((signature, mint)) => {
-                println!("Mint: {mint}");
-                println!("Signature: {signature}");
+                progress_stream::item_completed(Some(mint.to_string()), Ok::<_, String>(()));
+                report!("Mint: {mint}");
+                report!("Signature: {signature}");
                 format!("{}", style("Mint success").bold())
             }
             Err(err) => {
+                progress_stream::item_completed(None, Err(&err));
                 pb.abandon_with_message(format!("{}", style("Mint failed ").red().bold()));
                 error!("{:?}", err);
                 return Err(err);
@@ -148,10 +189,17 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
         let semaphore = Arc::new(Semaphore::new(10));
         let config = Arc::new(sugar_config);
 
+        let _cancellable = cancel::cancellable();
+
         for _i in 0..number {
+            if cancel::is_cancelled() {
+                break;
+            }
+
             let config = config.clone();
             let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
             let candy_machine_state = candy_machine_state.clone();
+            let lookup_table = lookup_table.clone();
             let pb = pb.clone();
 
             // Start tasks
@@ -163,15 +211,22 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
                     candy_machine_state,
                     collection_update_authority,
                     receiver_pubkey,
-                    args.priority_fee,
+                    priority_fee,
+                    lookup_table,
+                    compute_budget,
                 )
                 .await;
                 pb.inc(1);
+                progress_stream::item_completed(
+                    res.as_ref().ok().map(|(_, mint)| mint.to_string()),
+                    res.as_ref().map(|_| ()),
+                );
                 res
             }));
         }
 
         let mut error_count = 0;
+        let started = tasks.len() as u64;
 
         // Resolve tasks
         for task in tasks {
@@ -182,6 +237,17 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
             }
         }
 
+        if cancel::is_cancelled() {
+            pb.abandon_with_message(format!(
+                "{} {}/{} {}",
+                style("Minted").yellow().bold(),
+                started - error_count,
+                number,
+                style("of the items").yellow().bold()
+            ));
+            return Err(cancel::cancelled().into());
+        }
+
         if error_count > 0 {
             pb.abandon_with_message(format!(
                 "{} {} items failed.",
@@ -199,9 +265,43 @@ pub async fn process_mint(args: MintArgs) -> Result<()> {
         pb.finish();
     }
 
+    progress_stream::phase_finished();
+
     Ok(())
 }
 
+/// Collection delegate of the candy machine authority PDA: a collection authority record for
+/// `V1` candy machines and a metadata delegate record otherwise.
+pub fn collection_delegate_record(
+    candy_machine_state: &CandyMachine,
+    candy_machine_id: &Pubkey,
+    collection_update_authority: &Pubkey,
+) -> Pubkey {
+    let collection_mint = candy_machine_state.collection_mint;
+    let (authority_pda, _) = find_candy_machine_creator_pda(candy_machine_id);
+
+    if matches!(candy_machine_state.version, AccountVersion::V1) {
+        find_collection_authority_account(&collection_mint, &authority_pda).0
+    } else {
+        find_metadata_delegate_record_account(
+            &collection_mint,
+            MetadataDelegateRole::Collection,
+            collection_update_authority,
+            &authority_pda,
+        )
+        .0
+    }
+}
+
+/// NFT minted by [`send_mint`], not checked yet.
+pub struct MintedNft {
+    pub signature: Signature,
+    pub mint: Pubkey,
+    /// Associated token account of the receiver.
+    pub token: Pubkey,
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn mint(
     config: Arc<SugarConfig>,
     candy_machine_id: Pubkey,
@@ -209,7 +309,49 @@ pub async fn mint(
     collection_update_authority: Pubkey,
     receiver: Pubkey,
     priority_fee: u64,
+    lookup_table: Option<Arc<AddressLookupTableAccount>>,
+    compute_budget: ComputeBudget,
 ) -> Result<(Signature, Pubkey)> {
+    let minted = send_mint(
+        config.clone(),
+        candy_machine_id,
+        candy_machine_state,
+        collection_update_authority,
+        receiver,
+        priority_fee,
+        lookup_table,
+        compute_budget,
+    )
+    .await?;
+
+    let client = setup_client(&config)?;
+    let program = client.program(CANDY_MACHINE_ID);
+
+    if let Err(_) | Ok(Response { value: None, .. }) = program.rpc().get_account_with_commitment(
+        &find_metadata_pda(&minted.mint),
+        CommitmentConfig::processed(),
+    ) {
+        return Err(bot_tax_error(program.rpc(), &minted.signature));
+    }
+
+    info!("Minted! TxId: {}", minted.signature);
+
+    Ok((minted.signature, minted.mint))
+}
+
+/// Sends the mint transaction without checking that the NFT was minted: a transaction that
+/// paid the bot tax succeeds without creating the accounts of the NFT.
+#[allow(clippy::too_many_arguments)]
+pub async fn send_mint(
+    config: Arc<SugarConfig>,
+    candy_machine_id: Pubkey,
+    candy_machine_state: Arc<CandyMachine>,
+    collection_update_authority: Pubkey,
+    receiver: Pubkey,
+    priority_fee: u64,
+    lookup_table: Option<Arc<AddressLookupTableAccount>>,
+    compute_budget: ComputeBudget,
+) -> Result<MintedNft> {
     let client = setup_client(&config)?;
     let program = client.program(CANDY_MACHINE_ID);
     let payer = program.payer();
@@ -225,33 +367,18 @@ pub async fn mint(
     // derive associated token account
     let token = get_associated_token_address(&receiver, &nft_mint.pubkey());
 
-    let collection_mint = candy_machine_state.collection_mint;
-
     let (authority_pda, _) = find_candy_machine_creator_pda(&candy_machine_id);
 
-    let (token_record, collection_delegate_record) =
-        if matches!(candy_machine_state.version, AccountVersion::V1) {
-            (
-                None,
-                find_collection_authority_account(&collection_mint, &authority_pda).0,
-            )
-        } else {
-            (
-                Some(find_token_record_account(&nft_mint.pubkey(), &token).0),
-                find_metadata_delegate_record_account(
-                    &collection_mint,
-                    MetadataDelegateRole::Collection,
-                    &collection_update_authority,
-                    &authority_pda,
-                )
-                .0,
-            )
-        };
-
-    let collection_metadata = find_metadata_pda(&collection_mint);
-
-    let data = program.rpc().get_account_data(&collection_metadata)?;
-    let metadata = Metadata::deserialize(&mut data.as_slice())?;
+    let token_record = if matches!(candy_machine_state.version, AccountVersion::V1) {
+        None
+    } else {
+        Some(find_token_record_account(&nft_mint.pubkey(), &token).0)
+    };
+    let collection_delegate_record = collection_delegate_record(
+        &candy_machine_state,
+        &candy_machine_id,
+        &collection_update_authority,
+    );
 
     let metadata_pda = find_metadata_pda(&nft_mint.pubkey());
     let master_edition_pda = find_master_edition_pda(&nft_mint.pubkey());
@@ -276,7 +403,7 @@ pub async fn mint(
                 &candy_machine_state.collection_mint,
             ),
             collection_delegate_record,
-            collection_update_authority: metadata.update_authority,
+            collection_update_authority,
             token_metadata_program: metaplex_program_id,
             spl_token_program: TOKEN_PROGRAM_ID,
             spl_ata_program: Some(spl_associated_token_account::ID),
@@ -301,31 +428,33 @@ pub async fn mint(
     let compute_ix = ComputeBudgetInstruction::set_compute_unit_limit(COMPUTE_UNITS);
     let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
 
-    let builder = program
-        .request()
-        .instruction(compute_ix)
-        .instruction(priority_fee_ix)
-        .instruction(mint_ix[0].clone())
-        .signer(&nft_mint);
-
-    let sig = builder.send()?;
-
-    if let Err(_) | Ok(Response { value: None, .. }) = program
-        .rpc()
-        .get_account_with_commitment(&metadata_pda, CommitmentConfig::processed())
-    {
-        let cluster_param = match get_cluster(program.rpc()).unwrap_or(Cluster::Mainnet) {
-            Cluster::Devnet => "?devnet",
-            _ => "",
-        };
-        return Err(anyhow!(
-            "Minting most likely failed with a bot tax. Check the transaction link for more details: https://explorer.solana.com/tx/{}{}",
-            sig.to_string(),
-            cluster_param,
-        ));
-    }
-
-    info!("Minted! TxId: {}", sig);
+    let instructions = [compute_ix, priority_fee_ix, mint_ix[0].clone()];
+
+    let sig = send_transaction_with_lookup_table(
+        &program.rpc(),
+        &config.keypair,
+        &instructions,
+        &[&nft_mint],
+        lookup_table.as_deref(),
+        &compute_budget,
+    )?;
+
+    Ok(MintedNft {
+        signature: sig,
+        mint: nft_mint.pubkey(),
+        token,
+    })
+}
 
-    Ok((sig, nft_mint.pubkey()))
+/// Error of a mint transaction that did not create the NFT.
+pub fn bot_tax_error(rpc: RpcClient, signature: &Signature) -> anyhow::Error {
+    let cluster_param = match get_cluster(rpc).unwrap_or(Cluster::Mainnet) {
+        Cluster::Devnet => "?devnet",
+        _ => "",
+    };
+    anyhow!(
+        "Minting most likely failed with a bot tax. Check the transaction link for more details: https://explorer.solana.com/tx/{}{}",
+        signature.to_string(),
+        cluster_param,
+    )
 }
@@ -0,0 +1,65 @@
+//! NFTs selected by the commands that update existing NFTs (royalties, collection audit): the
+//! mints of a mint list file, or the NFTs minted from a candy machine.
+
+use std::{fs, time::Duration};
+
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    cache::load_cache,
+    common::*,
+    pdas::{find_candy_machine_creator_pda, find_metadata_pda},
+    setup::get_rpc_url,
+    utils::get_cm_creator_metadata_accounts,
+};
+
+// Timeout for the GPA call (in seconds).
+pub const DEFAULT_TIMEOUT: u64 = 300;
+
+/// Return the metadata accounts of the mints from the mint list, or of the NFTs
+/// minted from the candy machine when no mint list is specified.
+pub fn get_nft_metadata_pubkeys(
+    rpc_url: Option<String>,
+    cache: &str,
+    mint_list: Option<String>,
+    candy_machine: Option<String>,
+    timeout: Option<u64>,
+) -> Result<Vec<Pubkey>> {
+    if let Some(mint_list) = mint_list {
+        let mints = load_mint_list(&mint_list)?;
+        return Ok(mints.iter().map(find_metadata_pda).collect());
+    }
+
+    // the candy machine id specified takes precedence over the one from the cache
+
+    let candy_machine_id = match candy_machine {
+        Some(candy_machine) => candy_machine,
+        None => load_cache(cache, false)?.program.candy_machine,
+    };
+
+    let candy_machine_id = Pubkey::from_str(&candy_machine_id)
+        .map_err(|_| anyhow!("Failed to parse candy machine id: {}", candy_machine_id))?;
+
+    let client = RpcClient::new_with_timeout(
+        get_rpc_url(rpc_url)?,
+        Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+    );
+    let (creator, _) = find_candy_machine_creator_pda(&candy_machine_id);
+
+    get_cm_creator_metadata_accounts(&client, &creator.to_string(), 0)
+}
+
+/// Load a JSON list of mint addresses (e.g. `["mint1", "mint2"]`).
+pub fn load_mint_list(mint_list: &str) -> Result<Vec<Pubkey>> {
+    let contents = fs::read_to_string(mint_list)
+        .map_err(|e| anyhow!("Failed to read mint list file '{}': {}", mint_list, e))?;
+    let mints: Vec<String> = serde_json::from_str(&contents)
+        .map_err(|e| anyhow!("Failed to parse mint list file '{}': {}", mint_list, e))?;
+
+    mints
+        .iter()
+        .map(|mint| {
+            Pubkey::from_str(mint).map_err(|_| anyhow!("Failed to parse mint address: {}", mint))
+        })
+        .collect()
+}
@@ -0,0 +1,8 @@
+//! Offline signing of the transactions (`--unsigned-tx-out` and `send-signed`), for
+//! authorities whose key is not on the machine running sugar (e.g. a multisig).
+
+pub mod send_signed;
+pub mod writer;
+
+pub use send_signed::*;
+pub use writer::*;
@@ -0,0 +1,114 @@
+use std::fs;
+
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    cache::load_cache,
+    common::*,
+    offline::{decode_transaction, missing_signers, CacheUpdate, Manifest},
+    output::style,
+    setup::get_rpc_url,
+    utils::spinner_with_style,
+};
+
+pub struct SendSignedArgs {
+    pub dir: String,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub allow_cluster_mismatch: bool,
+}
+
+pub fn process_send_signed(args: SendSignedArgs) -> Result<()> {
+    let dir = Path::new(&args.dir);
+    let mut manifest = Manifest::load(dir)?;
+
+    let rpc =
+        RpcClient::new_with_commitment(get_rpc_url(args.rpc_url)?, CommitmentConfig::confirmed());
+
+    let mut cache = if manifest
+        .transactions
+        .iter()
+        .any(|entry| !entry.cache_update.is_none())
+    {
+        let mut cache = load_cache(&args.cache, false)?;
+        cache.check_cluster(&rpc, args.allow_cluster_mismatch)?;
+        Some(cache)
+    } else {
+        None
+    };
+
+    // all transactions are checked before sending any of them, since they depend on the
+    // previous ones
+    let mut pending = Vec::new();
+
+    for (index, entry) in manifest.transactions.iter().enumerate() {
+        if entry.signature.is_some() {
+            continue;
+        }
+
+        let path = dir.join(&entry.file);
+        let encoded = fs::read_to_string(&path)
+            .map_err(|e| anyhow!("Failed to read {}: {}", path.display(), e))?;
+        let transaction = decode_transaction(&encoded)
+            .map_err(|e| anyhow!("Failed to decode {}: {}", entry.file, e))?;
+
+        let missing = missing_signers(&transaction);
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "Transaction {} is missing the signature(s) of {}",
+                entry.file,
+                missing.join(", ")
+            ));
+        }
+        transaction
+            .verify()
+            .map_err(|_| anyhow!("Transaction {} has an invalid signature", entry.file))?;
+
+        pending.push((index, transaction));
+    }
+
+    let sent = manifest.transactions.len() - pending.len();
+
+    report!(
+        "{} {} ({} transaction(s), {} already sent)",
+        style("Sending signed transactions:").bold(),
+        manifest.command,
+        manifest.transactions.len(),
+        sent
+    );
+
+    for (index, transaction) in pending {
+        let entry = &mut manifest.transactions[index];
+
+        let pb = spinner_with_style();
+        pb.set_message(format!("{}...", entry.description));
+
+        let signature = rpc
+            .send_and_confirm_transaction(&transaction)
+            .map_err(|e| anyhow!("Failed to send {}: {}", entry.file, e))?;
+
+        entry.signature = Some(signature.to_string());
+
+        if let Some(cache) = cache.as_mut() {
+            entry.cache_update.apply(cache)?;
+
+            if matches!(entry.cache_update, CacheUpdate::CandyMachineCreated { .. }) {
+                cache
+                    .program
+                    .record_candy_machine_creation(&rpc, &signature)?;
+            }
+            cache.sync_file()?;
+        }
+
+        pb.finish_with_message(format!(
+            "{} {}",
+            style(format!("{}:", entry.description)).bold(),
+            signature
+        ));
+
+        // records the progress, so that a new run only sends the remaining transactions
+        manifest.save(dir)?;
+    }
+
+    Ok(())
+}
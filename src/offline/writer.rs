@@ -0,0 +1,531 @@
+use std::fs;
+
+use anchor_client::solana_sdk::{
+    hash::Hash, instruction::Instruction, message::Message, system_instruction,
+};
+use data_encoding::BASE64;
+use serde::Serialize;
+use solana_client::{nonce_utils, rpc_client::RpcClient};
+
+use crate::{
+    cache::CacheProgram, common::*, compute_budget::ComputeBudget, config::data::SugarConfig,
+    output::style, setup::get_rpc_url,
+};
+
+/// Name of the file listing the unsigned transactions of a directory.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Size added to a transaction by the instruction advancing its nonce: three account keys
+/// (the nonce, the recent blockhashes sysvar and the system program, 96) and the instruction
+/// (10).
+pub const NONCE_INSTRUCTION_SIZE: usize = 106;
+
+/// Options of the commands writing their transactions unsigned (`--unsigned-tx-out`).
+#[derive(Debug, Clone, Default)]
+pub struct UnsignedTxArgs {
+    /// Directory of the transaction files.
+    pub out_dir: String,
+    /// Authority of the transactions (and fee payer), defaults to the keypair.
+    pub authority: Option<String>,
+    /// Durable nonce accounts, one per transaction.
+    pub nonce_accounts: Vec<String>,
+    /// Compute budget of the transactions (only the compute unit limit applies).
+    pub compute_budget: ComputeBudget,
+}
+
+/// Changes to the cache once a transaction is confirmed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum CacheUpdate {
+    #[default]
+    None,
+    /// The collection NFT of the cache was minted.
+    CollectionMinted { mint: String },
+    /// A new candy machine was created.
+    #[serde(rename_all = "camelCase")]
+    CandyMachineCreated {
+        candy_machine: String,
+        collection_mint: String,
+    },
+    /// The config lines of the items were written.
+    ConfigLinesWritten { indices: Vec<u32> },
+    /// The collection of the candy machine was replaced.
+    CollectionSet { mint: String },
+}
+
+impl CacheUpdate {
+    pub fn is_none(&self) -> bool {
+        matches!(self, CacheUpdate::None)
+    }
+
+    /// Applies the changes to the cache (the creation of a candy machine is recorded by the
+    /// caller, which needs the signature and the RPC).
+    pub fn apply(&self, cache: &mut Cache) -> Result<()> {
+        match self {
+            CacheUpdate::None => {}
+            CacheUpdate::CollectionMinted { mint } => {
+                if let Some(item) = cache.items.get_mut("-1") {
+                    item.on_chain = true;
+                }
+                cache.program.collection_mint = mint.clone();
+            }
+            CacheUpdate::CandyMachineCreated {
+                candy_machine,
+                collection_mint,
+            } => {
+                cache.program = CacheProgram::new_from_cm(&Pubkey::from_str(candy_machine)?);
+                cache.program.collection_mint = collection_mint.clone();
+            }
+            CacheUpdate::ConfigLinesWritten { indices } => {
+                for index in indices {
+                    if let Some(item) = cache.items.get_mut(&index.to_string()) {
+                        item.on_chain = true;
+                    }
+                }
+            }
+            CacheUpdate::CollectionSet { mint } => {
+                cache.items.shift_remove("-1");
+                cache.program.collection_mint = mint.clone();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A transaction file of the manifest.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    pub file: String,
+    pub description: String,
+    /// Signers missing from the transaction.
+    pub signers: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce_account: Option<String>,
+    #[serde(default, skip_serializing_if = "CacheUpdate::is_none")]
+    pub cache_update: CacheUpdate,
+    /// Signature of the transaction once sent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+/// The unsigned transactions of a command, sent in order by `send-signed`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub command: String,
+    pub authority: String,
+    pub transactions: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    pub fn load(dir: &Path) -> Result<Self> {
+        let path = dir.join(MANIFEST_FILE);
+        let file =
+            File::open(&path).map_err(|e| anyhow!("Failed to open {}: {}", path.display(), e))?;
+
+        serde_json::from_reader(file)
+            .map_err(|e| anyhow!("Failed to parse {}: {}", path.display(), e))
+    }
+
+    pub fn save(&self, dir: &Path) -> Result<()> {
+        let file = File::create(dir.join(MANIFEST_FILE))?;
+        serde_json::to_writer_pretty(file, self)?;
+
+        Ok(())
+    }
+}
+
+/// Sets up a command writing unsigned transactions, returning its config and the authority
+/// of the transactions. With an `--authority`, the keypair is not read: the config holds a
+/// throwaway keypair that is only used to read accounts.
+pub fn unsigned_setup(
+    keypair: Option<String>,
+    rpc_url: Option<String>,
+    unsigned_tx: Option<&UnsignedTxArgs>,
+) -> Result<(SugarConfig, Pubkey)> {
+    match unsigned_tx.and_then(|args| args.authority.as_ref()) {
+        Some(authority) => {
+            let authority = Pubkey::from_str(authority)
+                .map_err(|_| anyhow!("Invalid authority address: {}", authority))?;
+            let sugar_config = SugarConfig {
+                rpc_url: get_rpc_url(rpc_url)?,
+                keypair: Keypair::new(),
+            };
+
+            Ok((sugar_config, authority))
+        }
+        None => {
+            let sugar_config = sugar_setup(keypair, rpc_url)?;
+            let authority = sugar_config.keypair.pubkey();
+
+            Ok((sugar_config, authority))
+        }
+    }
+}
+
+/// Collects the transactions of a command and writes them unsigned (partially signed by the
+/// new accounts they create) to a directory, with the manifest used by `send-signed`.
+pub struct UnsignedTxWriter {
+    dir: PathBuf,
+    command: String,
+    authority: Pubkey,
+    nonce_accounts: Vec<Pubkey>,
+    compute_budget: ComputeBudget,
+    blockhash: Option<Hash>,
+    transactions: Vec<(ManifestEntry, Transaction)>,
+}
+
+impl UnsignedTxWriter {
+    pub fn new(command: &str, args: &UnsignedTxArgs, authority: Pubkey) -> Result<Self> {
+        let dir = PathBuf::from(&args.out_dir);
+
+        if dir.join(MANIFEST_FILE).exists() {
+            return Err(anyhow!(
+                "{} already holds unsigned transactions, use a new directory",
+                dir.display()
+            ));
+        }
+
+        let nonce_accounts = args
+            .nonce_accounts
+            .iter()
+            .map(|nonce| {
+                Pubkey::from_str(nonce)
+                    .map_err(|_| anyhow!("Invalid nonce account address: {}", nonce))
+            })
+            .collect::<Result<Vec<Pubkey>>>()?;
+
+        Ok(Self {
+            dir,
+            command: command.to_string(),
+            authority,
+            nonce_accounts,
+            compute_budget: args.compute_budget,
+            blockhash: None,
+            transactions: Vec::new(),
+        })
+    }
+
+    /// Authority (and fee payer) of the transactions.
+    pub fn authority(&self) -> Pubkey {
+        self.authority
+    }
+
+    /// Whether the transactions use durable nonces.
+    pub fn uses_nonces(&self) -> bool {
+        !self.nonce_accounts.is_empty()
+    }
+
+    /// Size taken by the compute unit limit instruction added to the transactions.
+    pub fn compute_unit_limit_size(&self) -> usize {
+        self.compute_budget.unsigned_compute_unit_limit_size()
+    }
+
+    /// Adds the transaction of the instructions, partially signed by the `signers`. With
+    /// nonce accounts, the n-th transaction uses the n-th nonce instead of a recent blockhash.
+    pub fn add(
+        &mut self,
+        rpc: &RpcClient,
+        label: &str,
+        description: &str,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+        cache_update: CacheUpdate,
+    ) -> Result<()> {
+        let index = self.transactions.len();
+        let mut all_instructions = Vec::new();
+
+        let (blockhash, nonce_account) = if self.nonce_accounts.is_empty() {
+            let blockhash = match self.blockhash {
+                Some(blockhash) => blockhash,
+                None => *self.blockhash.insert(rpc.get_latest_blockhash()?),
+            };
+            (blockhash, None)
+        } else {
+            let nonce = *self.nonce_accounts.get(index).ok_or_else(|| {
+                anyhow!(
+                    "The command needs a nonce account per transaction, only {} given",
+                    self.nonce_accounts.len()
+                )
+            })?;
+            let account = nonce_utils::get_account(rpc, &nonce)?;
+            let data = nonce_utils::data_from_account(&account)?;
+
+            if data.authority != self.authority {
+                return Err(anyhow!(
+                    "The authority of nonce account {} is {}, expected {}",
+                    nonce,
+                    data.authority,
+                    self.authority
+                ));
+            }
+
+            all_instructions.push(system_instruction::advance_nonce_account(
+                &nonce,
+                &self.authority,
+            ));
+            (data.blockhash(), Some(nonce.to_string()))
+        };
+
+        all_instructions.extend(self.compute_budget.apply_compute_unit_limit(instructions));
+        let transaction =
+            unsigned_transaction(&all_instructions, &self.authority, blockhash, signers)?;
+
+        let entry = ManifestEntry {
+            file: format!("{:03}-{}.tx", index + 1, label),
+            description: description.to_string(),
+            signers: missing_signers(&transaction),
+            nonce_account,
+            cache_update,
+            signature: None,
+        };
+        self.transactions.push((entry, transaction));
+
+        Ok(())
+    }
+
+    /// Writes the transaction files and the manifest.
+    pub fn finish(self) -> Result<()> {
+        if self.transactions.is_empty() {
+            report!("\nNo transaction to write.");
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+
+        let mut manifest = Manifest {
+            command: self.command,
+            authority: self.authority.to_string(),
+            transactions: Vec::new(),
+        };
+
+        for (entry, transaction) in self.transactions {
+            fs::write(
+                self.dir.join(&entry.file),
+                encode_transaction(&transaction)?,
+            )?;
+            manifest.transactions.push(entry);
+        }
+
+        manifest.save(&self.dir)?;
+
+        report!(
+            "\n{} {} unsigned transaction(s) written to {}",
+            style("Unsigned transactions:").bold(),
+            manifest.transactions.len(),
+            self.dir.display()
+        );
+        let mut signers: Vec<&str> = Vec::new();
+        for signer in manifest
+            .transactions
+            .iter()
+            .flat_map(|entry| &entry.signers)
+        {
+            if !signers.contains(&signer.as_str()) {
+                signers.push(signer);
+            }
+        }
+        report!("Signer(s) required: {}", signers.join(", "));
+
+        if self.nonce_accounts.is_empty() {
+            report!(
+                "{}{}",
+                WARNING_EMOJI,
+                style(
+                    "The transactions use a recent blockhash and expire in about a minute, \
+                    use --nonce-account to sign them later."
+                )
+                .yellow()
+            );
+        }
+
+        report!(
+            "\nReplace each file with its signed transaction (base64) and run 'sugar send-signed \
+            --dir {}'.",
+            self.dir.display()
+        );
+
+        Ok(())
+    }
+}
+
+/// Builds the transaction of the instructions paid by `payer`, signed by the `signers` only.
+fn unsigned_transaction(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    blockhash: Hash,
+    signers: &[&Keypair],
+) -> Result<Transaction> {
+    let message = Message::new_with_blockhash(instructions, Some(payer), &blockhash);
+    let mut transaction = Transaction::new_unsigned(message);
+
+    if !signers.is_empty() {
+        transaction.try_partial_sign(&signers.to_vec(), blockhash)?;
+    }
+
+    Ok(transaction)
+}
+
+/// Signers of a transaction whose signature is missing.
+pub fn missing_signers(transaction: &Transaction) -> Vec<String> {
+    let required = transaction.message.header.num_required_signatures as usize;
+
+    transaction.message.account_keys[..required]
+        .iter()
+        .zip(&transaction.signatures)
+        .filter(|(_, signature)| **signature == Signature::default())
+        .map(|(signer, _)| signer.to_string())
+        .collect()
+}
+
+/// Serializes a transaction in the wire format, encoded in base64.
+pub fn encode_transaction(transaction: &Transaction) -> Result<String> {
+    Ok(BASE64.encode(&bincode::serialize(transaction)?))
+}
+
+pub fn decode_transaction(encoded: &str) -> Result<Transaction> {
+    let bytes = BASE64
+        .decode(encoded.trim().as_bytes())
+        .map_err(|e| anyhow!("Invalid base64 transaction: {}", e))?;
+
+    Ok(bincode::deserialize(&bytes)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
+
+    use super::*;
+    use crate::cache::CacheItem;
+
+    fn cache_item(on_chain: bool) -> CacheItem {
+        CacheItem {
+            name: "Item".to_string(),
+            image_hash: String::new(),
+            image_link: String::new(),
+            metadata_hash: String::new(),
+            metadata_link: "https://example.com/0.json".to_string(),
+            on_chain,
+            animation_hash: None,
+            animation_link: None,
+            optimized_image_hash: None,
+        }
+    }
+
+    #[test]
+    fn unsigned_transaction_is_partially_signed() {
+        let authority = Pubkey::new_unique();
+        let account = Keypair::new();
+        let instructions = [system_instruction::create_account(
+            &authority,
+            &account.pubkey(),
+            1_000_000,
+            100,
+            &system_program::ID,
+        )];
+
+        let transaction =
+            unsigned_transaction(&instructions, &authority, Hash::new_unique(), &[&account])
+                .unwrap();
+
+        assert_eq!(missing_signers(&transaction), vec![authority.to_string()]);
+
+        let decoded = decode_transaction(&encode_transaction(&transaction).unwrap()).unwrap();
+        assert_eq!(decoded, transaction);
+        assert!(!decoded.is_signed());
+    }
+
+    #[test]
+    fn nonce_instruction_size() {
+        let authority = Pubkey::new_unique();
+        let instructions = [ComputeBudgetInstruction::set_compute_unit_price(500)];
+        let mut with_nonce = vec![system_instruction::advance_nonce_account(
+            &Pubkey::new_unique(),
+            &authority,
+        )];
+        with_nonce.extend_from_slice(&instructions);
+
+        let size = |instructions: &[Instruction]| {
+            unsigned_transaction(instructions, &authority, Hash::new_unique(), &[])
+                .unwrap()
+                .message
+                .serialize()
+                .len()
+        };
+
+        assert_eq!(
+            size(&with_nonce) - size(&instructions),
+            NONCE_INSTRUCTION_SIZE
+        );
+    }
+
+    #[test]
+    fn cache_updates_are_applied() {
+        let mut cache = Cache::new();
+        cache.items.insert("-1".to_string(), cache_item(false));
+        cache.items.insert("0".to_string(), cache_item(false));
+        cache.items.insert("1".to_string(), cache_item(false));
+
+        let mint = Pubkey::new_unique().to_string();
+        let candy_machine = Pubkey::new_unique().to_string();
+
+        CacheUpdate::CollectionMinted { mint: mint.clone() }
+            .apply(&mut cache)
+            .unwrap();
+        assert!(cache.items["-1"].on_chain);
+
+        CacheUpdate::CandyMachineCreated {
+            candy_machine: candy_machine.clone(),
+            collection_mint: mint.clone(),
+        }
+        .apply(&mut cache)
+        .unwrap();
+        assert_eq!(cache.program.candy_machine, candy_machine);
+        assert_eq!(cache.program.collection_mint, mint);
+
+        CacheUpdate::ConfigLinesWritten { indices: vec![1] }
+            .apply(&mut cache)
+            .unwrap();
+        assert!(!cache.items["0"].on_chain);
+        assert!(cache.items["1"].on_chain);
+
+        let new_mint = Pubkey::new_unique().to_string();
+        CacheUpdate::CollectionSet {
+            mint: new_mint.clone(),
+        }
+        .apply(&mut cache)
+        .unwrap();
+        assert!(!cache.items.contains_key("-1"));
+        assert_eq!(cache.program.collection_mint, new_mint);
+    }
+
+    #[test]
+    fn manifest_omits_empty_fields() {
+        let entry = ManifestEntry {
+            file: "001-update-candy-guard.tx".to_string(),
+            description: "Update the candy guard".to_string(),
+            signers: vec![],
+            nonce_account: None,
+            cache_update: CacheUpdate::None,
+            signature: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            json!({
+                "file": "001-update-candy-guard.tx",
+                "description": "Update the candy guard",
+                "signers": []
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(CacheUpdate::ConfigLinesWritten {
+                indices: vec![0, 1]
+            })
+            .unwrap(),
+            json!({ "type": "configLinesWritten", "indices": [0, 1] })
+        );
+    }
+}
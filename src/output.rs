@@ -0,0 +1,188 @@
+//! Terminal output of the commands.
+//!
+//! The process functions do not print: they report their output and ask their confirmations
+//! through the [`Terminal`] set by the caller with [`set_terminal`]. The CLI sets one that
+//! prints to the terminal, while library callers get the typed results of the
+//! [`api`](crate::api) functions and nothing on stdout.
+
+use std::sync::{Arc, RwLock};
+
+use anyhow::{anyhow, Result};
+
+// styles the output of the CLI, and leaves it as plain text without the `cli` feature
+#[cfg(feature = "cli")]
+pub use console::{style, Emoji};
+
+#[cfg(not(feature = "cli"))]
+pub use self::plain::{style, Emoji};
+
+// set by the CLI
+static TERMINAL: RwLock<Option<Arc<dyn Terminal>>> = RwLock::new(None);
+
+/// Output reported by a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    /// Line of the standard output.
+    Line(String),
+    /// Text of the standard output, without a line break.
+    Text(String),
+    /// Line of the standard error.
+    ErrorLine(String),
+}
+
+/// Look of a confirmation prompt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PromptTheme {
+    /// Theme of the Sugar prompts.
+    Default,
+    /// Highlighted, for the prompts that follow a warning.
+    Warning,
+}
+
+/// Yes/no question asked by a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Confirmation {
+    pub prompt: String,
+    pub default: Option<bool>,
+    pub theme: PromptTheme,
+}
+
+impl Confirmation {
+    pub fn new(prompt: impl Into<String>) -> Self {
+        Self {
+            prompt: prompt.into(),
+            default: None,
+            theme: PromptTheme::Default,
+        }
+    }
+
+    pub fn default(mut self, default: bool) -> Self {
+        self.default = Some(default);
+        self
+    }
+
+    pub fn theme(mut self, theme: PromptTheme) -> Self {
+        self.theme = theme;
+        self
+    }
+}
+
+/// Receives the output of the commands and answers their confirmations.
+pub trait Terminal: Send + Sync {
+    fn output(&self, output: Output);
+
+    fn confirm(&self, confirmation: &Confirmation) -> Result<bool>;
+}
+
+/// Sets the terminal of the commands, replacing the current one.
+pub fn set_terminal(terminal: impl Terminal + 'static) {
+    *TERMINAL.write().unwrap() = Some(Arc::new(terminal));
+}
+
+/// Whether a terminal is set, so progress bars and prompts can be shown.
+pub fn has_terminal() -> bool {
+    TERMINAL.read().unwrap().is_some()
+}
+
+fn terminal() -> Option<Arc<dyn Terminal>> {
+    TERMINAL.read().unwrap().clone()
+}
+
+/// Reports the output to the terminal, if one is set.
+pub fn report(output: Output) {
+    if let Some(terminal) = terminal() {
+        terminal.output(output);
+    }
+}
+
+/// Fails when the prompts cannot be shown: without the terminal there is nobody to answer
+/// them, so the caller has to pass the answer in the arguments of the command instead.
+pub fn ensure_prompts() -> Result<()> {
+    if has_terminal() {
+        Ok(())
+    } else {
+        Err(no_terminal())
+    }
+}
+
+/// Asks for a confirmation on the terminal.
+pub fn confirm(confirmation: Confirmation) -> Result<bool> {
+    match terminal() {
+        Some(terminal) => terminal.confirm(&confirmation),
+        None => Err(no_terminal()),
+    }
+}
+
+fn no_terminal() -> anyhow::Error {
+    anyhow!("The command needs an answer on the terminal, which is only available in the CLI")
+}
+
+/// Reports a line of the standard output (see [`Output::Line`]).
+macro_rules! report {
+    () => {
+        $crate::output::report($crate::output::Output::Line(String::new()))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::report($crate::output::Output::Line(format!($($arg)*)))
+    };
+}
+
+/// Reports text of the standard output, without a line break (see [`Output::Text`]).
+macro_rules! report_text {
+    ($($arg:tt)*) => {
+        $crate::output::report($crate::output::Output::Text(format!($($arg)*)))
+    };
+}
+
+/// Reports a line of the standard error (see [`Output::ErrorLine`]).
+macro_rules! report_error {
+    () => {
+        $crate::output::report($crate::output::Output::ErrorLine(String::new()))
+    };
+    ($($arg:tt)*) => {
+        $crate::output::report($crate::output::Output::ErrorLine(format!($($arg)*)))
+    };
+}
+
+#[cfg(not(feature = "cli"))]
+mod plain {
+    use std::fmt::{self, Display, Formatter};
+
+    /// Emoji with a fallback, printed as is.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Emoji<'a, 'b>(pub &'a str, pub &'b str);
+
+    impl Display for Emoji<'_, '_> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{}", self.0)
+        }
+    }
+
+    /// Value printed without styles.
+    #[derive(Debug, Clone)]
+    pub struct StyledObject<D>(D);
+
+    pub fn style<D>(val: D) -> StyledObject<D> {
+        StyledObject(val)
+    }
+
+    macro_rules! plain_styles {
+        ($($name:ident),*) => {
+            impl<D> StyledObject<D> {
+                $(
+                    pub fn $name(self) -> Self {
+                        self
+                    }
+                )*
+            }
+        };
+    }
+
+    plain_styles!(red, green, yellow, blue, magenta, cyan, bold, dim, italic, underlined);
+
+    impl<D: Display> Display for StyledObject<D> {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            self.0.fmt(f)
+        }
+    }
+}
@@ -1,13 +1,18 @@
-use std::{env, fs::File, path::Path};
+use std::{
+    env,
+    fs::File,
+    path::{Path, PathBuf},
+};
 
 use anyhow::{anyhow, Result};
-use console::style;
+use chrono::{DateTime, Duration, Local, Utc};
 use lazy_static::lazy_static;
 use regex::Regex;
 
 use crate::{config::data::*, program_errors::*};
 
-pub fn parse_solana_config() -> Option<SolanaConfig> {
+/// Reads the Solana CLI config file, returning `None` when its contents are invalid.
+pub fn parse_solana_config() -> Result<Option<SolanaConfig>> {
     let home = if cfg!(unix) {
         env::var_os("HOME").expect("Couldn't find UNIX home key.")
     } else if cfg!(windows) {
@@ -26,18 +31,19 @@ pub fn parse_solana_config() -> Option<SolanaConfig> {
         .join("cli")
         .join("config.yml");
 
-    let conf_file = match File::open(config_path) {
-        Ok(f) => f,
-        Err(e) => {
-            println!(
-                "{} {}",
-                style("Failed to open Solana config file:").bold().red(),
-                style(e).bold().red(),
-            );
-            std::process::exit(1);
-        }
-    };
-    serde_yaml::from_reader(&conf_file).ok()
+    let conf_file =
+        File::open(config_path).map_err(|e| anyhow!("Failed to open Solana config file: {}", e))?;
+    Ok(serde_yaml::from_reader(&conf_file).ok())
+}
+
+/// Returns the path of the Solana CLI config file.
+pub fn solana_config_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| {
+        home.join(".config")
+            .join("solana")
+            .join("cli")
+            .join("config.yml")
+    })
 }
 
 pub fn path_to_string(path: &Path) -> Result<String> {
@@ -101,3 +107,136 @@ fn find_external_program_error(code: String) -> String {
         }
     }
 }
+
+/// Parses a duration such as "90s", "45m", "2h" or "1d12h" (units: s, m, h, d).
+pub fn parse_duration(value: &str) -> Result<Duration> {
+    let invalid = || {
+        anyhow!(
+            "Invalid duration '{}', expected a value such as 2h or 1d12h",
+            value
+        )
+    };
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+
+    for c in value.trim().chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let amount: i64 = number.parse().map_err(|_| invalid())?;
+        number.clear();
+
+        total = total
+            + match c.to_ascii_lowercase() {
+                's' => Duration::seconds(amount),
+                'm' => Duration::minutes(amount),
+                'h' => Duration::hours(amount),
+                'd' => Duration::days(amount),
+                _ => return Err(invalid()),
+            };
+    }
+
+    if !number.is_empty() || value.trim().is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(total)
+}
+
+/// Parses a date: "now", a duration relative to `base` (e.g. "+2h"), or any date format
+/// accepted by the config file (dates without a timezone are in local time).
+pub fn parse_date(value: &str, base: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    let value = value.trim();
+
+    if value.eq_ignore_ascii_case("now") {
+        return Ok(Utc::now());
+    }
+
+    if let Some(duration) = value.strip_prefix('+') {
+        return Ok(base + parse_duration(duration)?);
+    }
+
+    if let Ok(duration) = parse_duration(value) {
+        return Ok(base + duration);
+    }
+
+    dateparser::parse(value).map_err(|_| {
+        anyhow!(
+            "Invalid date '{}', expected a date (e.g. 2023-05-01T17:00:00Z) or a duration (e.g. +2h)",
+            value
+        )
+    })
+}
+
+/// Formats the date in UTC and in local time, e.g. "2023-05-01 17:00:00 UTC (19:00:00 +02:00)".
+pub fn format_date(date: &DateTime<Utc>) -> String {
+    let local = date.with_timezone(&Local);
+    format!(
+        "{} ({} local)",
+        date.format("%Y-%m-%d %H:%M:%S UTC"),
+        local.format("%Y-%m-%d %H:%M:%S %:z")
+    )
+}
+
+/// Formats the duration as a countdown, e.g. "1d 02h 03m 04s".
+pub fn format_countdown(duration: Duration) -> String {
+    let seconds = duration.num_seconds().max(0);
+    let (days, hours, minutes, seconds) = (
+        seconds / 86_400,
+        (seconds % 86_400) / 3_600,
+        (seconds % 3_600) / 60,
+        seconds % 60,
+    );
+
+    if days > 0 {
+        format!("{days}d {hours:02}h {minutes:02}m {seconds:02}s")
+    } else {
+        format!("{hours:02}h {minutes:02}m {seconds:02}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::seconds(90));
+        assert_eq!(parse_duration("2h").unwrap(), Duration::hours(2));
+        assert_eq!(
+            parse_duration("1d12h30m").unwrap(),
+            Duration::days(1) + Duration::hours(12) + Duration::minutes(30)
+        );
+        assert!(parse_duration("2").is_err());
+        assert!(parse_duration("2w").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn parses_dates_and_relative_durations() {
+        let base = Utc.with_ymd_and_hms(2023, 5, 1, 17, 0, 0).unwrap();
+
+        assert_eq!(parse_date("2023-05-01T17:00:00Z", base).unwrap(), base);
+        assert_eq!(parse_date("+2h", base).unwrap(), base + Duration::hours(2));
+        assert_eq!(
+            parse_date("30m", base).unwrap(),
+            base + Duration::minutes(30)
+        );
+        assert!(parse_date("tomorrow-ish", base).is_err());
+    }
+
+    #[test]
+    fn formats_countdowns() {
+        assert_eq!(format_countdown(Duration::seconds(3_723)), "01h 02m 03s");
+        assert_eq!(
+            format_countdown(Duration::seconds(90_061)),
+            "1d 01h 01m 01s"
+        );
+        assert_eq!(format_countdown(Duration::seconds(-5)), "00h 00m 00s");
+    }
+}
@@ -0,0 +1,250 @@
+use std::fmt;
+
+use anchor_client::solana_sdk::native_token::LAMPORTS_PER_SOL;
+
+use crate::{common::*, output::style};
+
+/// Compute units requested by a transaction that does not set a compute unit limit.
+pub const DEFAULT_COMPUTE_UNITS: u32 = 200_000;
+
+const MICRO_LAMPORTS_PER_LAMPORT: u128 = 1_000_000;
+
+/// Priority fee specified on the command line.
+///
+/// Accepted forms are:
+///   - `500` or `500cu`: micro-lamports per compute unit
+///   - `5000lamports-per-tx`: lamports per transaction
+///   - `0.0001sol-per-tx`: SOL per transaction
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PriorityFee {
+    MicroLamportsPerCu(u64),
+    LamportsPerTx(u64),
+}
+
+impl Default for PriorityFee {
+    fn default() -> Self {
+        PriorityFee::MicroLamportsPerCu(DEFAULT_PRIORITY_FEE)
+    }
+}
+
+impl FromStr for PriorityFee {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let value = value.trim().to_lowercase();
+        let invalid = || {
+            anyhow!(
+                "Invalid priority fee '{}', expected a value such as '500', '500cu', \
+                '5000lamports-per-tx' or '0.0001sol-per-tx'",
+                value
+            )
+        };
+
+        if let Some(amount) = value.strip_suffix("sol-per-tx") {
+            let sol = f64::from_str(amount).map_err(|_| invalid())?;
+            if !sol.is_finite() || sol < 0.0 {
+                return Err(invalid());
+            }
+            Ok(PriorityFee::LamportsPerTx(
+                (sol * LAMPORTS_PER_SOL as f64).round() as u64,
+            ))
+        } else if let Some(amount) = value.strip_suffix("lamports-per-tx") {
+            Ok(PriorityFee::LamportsPerTx(
+                u64::from_str(amount).map_err(|_| invalid())?,
+            ))
+        } else {
+            let amount = value.strip_suffix("cu").unwrap_or(&value);
+            Ok(PriorityFee::MicroLamportsPerCu(
+                u64::from_str(amount).map_err(|_| invalid())?,
+            ))
+        }
+    }
+}
+
+impl fmt::Display for PriorityFee {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PriorityFee::MicroLamportsPerCu(price) => write!(f, "{}", price),
+            PriorityFee::LamportsPerTx(lamports) => write!(f, "{}lamports-per-tx", lamports),
+        }
+    }
+}
+
+impl PriorityFee {
+    /// Returns the compute unit price (in micro-lamports) for a transaction requesting
+    /// `compute_units`.
+    pub fn micro_lamports_per_cu(&self, compute_units: u32) -> u64 {
+        match self {
+            PriorityFee::MicroLamportsPerCu(price) => *price,
+            PriorityFee::LamportsPerTx(lamports) => {
+                (*lamports as u128 * MICRO_LAMPORTS_PER_LAMPORT / compute_units.max(1) as u128)
+                    as u64
+            }
+        }
+    }
+}
+
+/// Returns the total priority fee (in lamports) paid by `transactions` transactions.
+pub fn total_priority_lamports(price: u64, compute_units: u32, transactions: u64) -> u64 {
+    // the runtime rounds the priority fee of each transaction up to the next lamport
+    let per_tx = (price as u128 * compute_units as u128 + MICRO_LAMPORTS_PER_LAMPORT - 1)
+        / MICRO_LAMPORTS_PER_LAMPORT;
+    (per_tx * transactions as u128) as u64
+}
+
+/// Returns the highest compute unit price (not above `price`) that keeps the total
+/// priority fee within `max_lamports`.
+pub fn apply_priority_budget(
+    price: u64,
+    compute_units: u32,
+    transactions: u64,
+    max_lamports: u64,
+) -> u64 {
+    if total_priority_lamports(price, compute_units, transactions) <= max_lamports {
+        return price;
+    }
+
+    let per_tx = max_lamports / transactions.max(1);
+    let budget_price = per_tx as u128 * MICRO_LAMPORTS_PER_LAMPORT / compute_units.max(1) as u128;
+
+    std::cmp::min(price as u128, budget_price) as u64
+}
+
+/// Resolves the compute unit price for the planned number of transactions, printing the
+/// projected total cost and lowering the price when it exceeds `max_total_priority_sol`.
+pub fn resolve_priority_fee(
+    priority_fee: PriorityFee,
+    compute_units: u32,
+    transactions: u64,
+    max_total_priority_sol: Option<f64>,
+) -> Result<u64> {
+    let mut price = priority_fee.micro_lamports_per_cu(compute_units);
+
+    if let Some(max_total_priority_sol) = max_total_priority_sol {
+        if !max_total_priority_sol.is_finite() || max_total_priority_sol < 0.0 {
+            return Err(anyhow!(
+                "Invalid --max-total-priority-sol value: {}",
+                max_total_priority_sol
+            ));
+        }
+
+        let max_lamports = (max_total_priority_sol * LAMPORTS_PER_SOL as f64) as u64;
+        let budget_price = apply_priority_budget(price, compute_units, transactions, max_lamports);
+
+        if budget_price < price {
+            report!(
+                "{}{}",
+                WARNING_EMOJI,
+                style(format!(
+                    "Priority fee lowered from {} to {} micro-lamports per compute unit \
+                    to stay within {} SOL",
+                    price, budget_price, max_total_priority_sol
+                ))
+                .yellow()
+                .bold()
+            );
+            price = budget_price;
+        }
+    }
+
+    let total = total_priority_lamports(price, compute_units, transactions);
+
+    report!(
+        "{} {} micro-lamports per compute unit (~{} SOL for {} transaction(s))",
+        style("Priority fee:").bold(),
+        price,
+        total as f64 / LAMPORTS_PER_SOL as f64,
+        transactions
+    );
+
+    Ok(price)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_micro_lamports_per_cu() {
+        assert_eq!(
+            PriorityFee::from_str("1000").unwrap(),
+            PriorityFee::MicroLamportsPerCu(1000)
+        );
+        assert_eq!(
+            PriorityFee::from_str("5000cu").unwrap(),
+            PriorityFee::MicroLamportsPerCu(5000)
+        );
+        assert!(PriorityFee::from_str("-5cu").is_err());
+    }
+
+    #[test]
+    fn parses_lamports_per_tx() {
+        assert_eq!(
+            PriorityFee::from_str("5000lamports-per-tx").unwrap(),
+            PriorityFee::LamportsPerTx(5000)
+        );
+        assert!(PriorityFee::from_str("0.5lamports-per-tx").is_err());
+    }
+
+    #[test]
+    fn parses_sol_per_tx() {
+        assert_eq!(
+            PriorityFee::from_str("0.0001sol-per-tx").unwrap(),
+            PriorityFee::LamportsPerTx(100_000)
+        );
+        assert_eq!(
+            PriorityFee::from_str("0.0001SOL-per-tx").unwrap(),
+            PriorityFee::LamportsPerTx(100_000)
+        );
+        assert!(PriorityFee::from_str("-1sol-per-tx").is_err());
+        assert!(PriorityFee::from_str("sol-per-tx").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for fee in [
+            PriorityFee::MicroLamportsPerCu(500),
+            PriorityFee::LamportsPerTx(100_000),
+        ] {
+            assert_eq!(PriorityFee::from_str(&fee.to_string()).unwrap(), fee);
+        }
+    }
+
+    #[test]
+    fn converts_per_tx_to_per_cu() {
+        // 100_000 lamports over 200_000 CU = 500_000 micro-lamports per CU
+        assert_eq!(
+            PriorityFee::LamportsPerTx(100_000).micro_lamports_per_cu(DEFAULT_COMPUTE_UNITS),
+            500_000
+        );
+        assert_eq!(
+            PriorityFee::MicroLamportsPerCu(500).micro_lamports_per_cu(COMPUTE_UNITS),
+            500
+        );
+    }
+
+    #[test]
+    fn estimates_total_priority_fee() {
+        // 500 micro-lamports * 200_000 CU = 100 lamports per transaction
+        assert_eq!(
+            total_priority_lamports(500, DEFAULT_COMPUTE_UNITS, 3_000),
+            300_000
+        );
+        // fractions of a lamport are rounded up per transaction
+        assert_eq!(total_priority_lamports(1, 1, 10), 10);
+        assert_eq!(total_priority_lamports(0, DEFAULT_COMPUTE_UNITS, 10), 0);
+    }
+
+    #[test]
+    fn lowers_price_to_fit_budget() {
+        // within budget: price is unchanged
+        assert_eq!(
+            apply_priority_budget(500, DEFAULT_COMPUTE_UNITS, 10, 1_000),
+            500
+        );
+        // 10 transactions within 500 lamports: 50 lamports per transaction
+        let price = apply_priority_budget(500, DEFAULT_COMPUTE_UNITS, 10, 500);
+        assert_eq!(price, 250);
+        assert!(total_priority_lamports(price, DEFAULT_COMPUTE_UNITS, 10) <= 500);
+    }
+}
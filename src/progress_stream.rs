@@ -0,0 +1,252 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    sync::Mutex,
+};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+use crate::common::*;
+
+/// Version of the event schema, incremented on incompatible changes.
+pub const PROGRESS_STREAM_VERSION: u32 = 1;
+
+/// Path value that selects the standard output as the progress stream.
+pub const STDOUT_STREAM: &str = "-";
+
+static STREAM: Mutex<Option<ProgressStream>> = Mutex::new(None);
+
+/// Event emitted to the progress stream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum ProgressEvent {
+    /// A phase (step) of the command started.
+    PhaseStarted {
+        phase: String,
+        step: usize,
+        total_steps: usize,
+    },
+    /// A phase (step) of the command finished.
+    PhaseFinished { phase: String },
+    /// Number of items to be processed by the current phase.
+    Totals { phase: String, total: u64 },
+    /// An item of the current phase was processed.
+    ItemCompleted {
+        phase: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        index: Option<String>,
+        success: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        error: Option<String>,
+    },
+    /// Bytes of a (streamed) file sent so far.
+    FileProgress {
+        phase: String,
+        file: String,
+        bytes: u64,
+        total: u64,
+    },
+    /// The command failed.
+    Error { message: String },
+    /// The command finished.
+    Finished { success: bool },
+}
+
+/// Line written to the progress stream: the event together with the schema version and
+/// the time (in milliseconds since the unix epoch) it was emitted.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressRecord {
+    pub version: u32,
+    pub timestamp: i64,
+    #[serde(flatten)]
+    pub event: ProgressEvent,
+}
+
+struct ProgressStream {
+    writer: Box<dyn Write + Send>,
+    phase: String,
+}
+
+/// Opens the progress stream: `-` for the standard output (the human output is then sent
+/// to the standard error), a unix socket, or a file/FIFO path.
+pub fn init_progress_stream(path: &str) -> Result<()> {
+    let writer: Box<dyn Write + Send> = if path == STDOUT_STREAM {
+        Box::new(redirect_stdout()?)
+    } else {
+        open_path(path).map_err(|e| anyhow!("Failed to open progress stream {}: {}", path, e))?
+    };
+
+    set_progress_writer(writer);
+    Ok(())
+}
+
+/// Sets the writer of the progress stream, replacing the current one.
+pub fn set_progress_writer(writer: Box<dyn Write + Send>) {
+    *STREAM.lock().unwrap() = Some(ProgressStream {
+        writer,
+        phase: String::new(),
+    });
+}
+
+/// Closes the progress stream; subsequent events are discarded.
+pub fn close_progress_stream() {
+    if let Some(mut stream) = STREAM.lock().unwrap().take() {
+        let _ = stream.writer.flush();
+    }
+}
+
+/// Emits the event, if a progress stream is open. Failing to write an event does not
+/// interrupt the command.
+pub fn emit(event: ProgressEvent) {
+    let mut stream = STREAM.lock().unwrap();
+
+    if let Some(stream) = stream.as_mut() {
+        if let ProgressEvent::PhaseStarted { phase, .. } = &event {
+            stream.phase = phase.clone();
+        }
+
+        let record = ProgressRecord {
+            version: PROGRESS_STREAM_VERSION,
+            timestamp: Utc::now().timestamp_millis(),
+            event,
+        };
+
+        let result = serde_json::to_string(&record)
+            .map_err(io::Error::from)
+            .and_then(|line| writeln!(stream.writer, "{line}"))
+            .and_then(|_| stream.writer.flush());
+
+        if let Err(err) = result {
+            warn!("Failed to write progress event: {}", err);
+        }
+    }
+}
+
+pub fn phase_started(phase: &str, step: usize, total_steps: usize) {
+    emit(ProgressEvent::PhaseStarted {
+        phase: phase.to_string(),
+        step,
+        total_steps,
+    });
+}
+
+pub fn phase_finished() {
+    if let Some(phase) = current_phase() {
+        emit(ProgressEvent::PhaseFinished { phase });
+    }
+}
+
+pub fn totals(total: u64) {
+    if let Some(phase) = current_phase() {
+        emit(ProgressEvent::Totals { phase, total });
+    }
+}
+
+pub fn item_completed<E: ToString>(index: Option<String>, result: Result<(), E>) {
+    if let Some(phase) = current_phase() {
+        let error = result.err().map(|e| e.to_string());
+        emit(ProgressEvent::ItemCompleted {
+            phase,
+            index,
+            success: error.is_none(),
+            error,
+        });
+    }
+}
+
+pub fn file_progress(file: &str, bytes: u64, total: u64) {
+    if let Some(phase) = current_phase() {
+        emit(ProgressEvent::FileProgress {
+            phase,
+            file: file.to_string(),
+            bytes,
+            total,
+        });
+    }
+}
+
+// returns the current phase, or None if there is no progress stream
+fn current_phase() -> Option<String> {
+    STREAM
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|stream| stream.phase.clone())
+}
+
+#[cfg(unix)]
+fn open_path(path: &str) -> io::Result<Box<dyn Write + Send>> {
+    use std::os::unix::{fs::FileTypeExt, net::UnixStream};
+
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.file_type().is_socket() {
+            return Ok(Box::new(UnixStream::connect(path)?));
+        }
+    }
+
+    open_file(path)
+}
+
+#[cfg(not(unix))]
+fn open_path(path: &str) -> io::Result<Box<dyn Write + Send>> {
+    open_file(path)
+}
+
+fn open_file(path: &str) -> io::Result<Box<dyn Write + Send>> {
+    let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(Box::new(file))
+}
+
+/// Returns a handle to the original standard output and points the standard output to the
+/// standard error, so the human output does not interleave with the events.
+#[cfg(unix)]
+fn redirect_stdout() -> Result<File> {
+    use std::os::unix::io::FromRawFd;
+
+    // SAFETY: the duplicated descriptor is owned by the returned file
+    unsafe {
+        let fd = libc::dup(libc::STDOUT_FILENO);
+        if fd < 0 || libc::dup2(libc::STDERR_FILENO, libc::STDOUT_FILENO) < 0 {
+            return Err(anyhow!(
+                "Failed to redirect the standard output: {}",
+                io::Error::last_os_error()
+            ));
+        }
+        Ok(File::from_raw_fd(fd))
+    }
+}
+
+#[cfg(not(unix))]
+fn redirect_stdout() -> Result<io::Stdout> {
+    Ok(io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_versioned_records() {
+        let record = ProgressRecord {
+            version: PROGRESS_STREAM_VERSION,
+            timestamp: 0,
+            event: ProgressEvent::ItemCompleted {
+                phase: "mint".to_string(),
+                index: Some("1".to_string()),
+                success: false,
+                error: Some("failed".to_string()),
+            },
+        };
+
+        let line = serde_json::to_string(&record).unwrap();
+        assert_eq!(
+            line,
+            r#"{"version":1,"timestamp":0,"event":"item_completed","phase":"mint","index":"1","success":false,"error":"failed"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<ProgressRecord>(&line).unwrap(),
+            record
+        );
+    }
+}
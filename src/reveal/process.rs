@@ -3,10 +3,7 @@ use std::{
     time::Duration,
 };
 
-use anchor_client::solana_sdk::account::Account;
-use anchor_lang::AnchorDeserialize;
-use console::style;
-use futures::future::join_all;
+use anchor_client::solana_sdk::compute_budget::ComputeBudgetInstruction;
 use mpl_token_metadata::{
     instruction::update_metadata_accounts_v2,
     state::{DataV2, Metadata},
@@ -17,11 +14,16 @@ use solana_client::{client_error::ClientError, rpc_client::RpcClient};
 use tokio::sync::Semaphore;
 
 use crate::{
+    account_fetcher::AccountFetcher,
     cache::load_cache,
+    cancel,
     candy_machine::CANDY_MACHINE_ID,
     common::*,
     config::{get_config_data, Cluster},
+    output::style,
     pdas::{find_candy_machine_creator_pda, find_metadata_pda},
+    priority_fee::{resolve_priority_fee, PriorityFee, DEFAULT_COMPUTE_UNITS},
+    progress_stream,
     setup::get_rpc_url,
     utils::*,
 };
@@ -33,6 +35,9 @@ pub struct RevealArgs {
     pub config: String,
     pub timeout: Option<u64>,
     pub new_update_authority: Option<String>,
+    pub priority_fee: PriorityFee,
+    pub max_total_priority_sol: Option<f64>,
+    pub allow_cluster_mismatch: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -61,11 +66,12 @@ enum RevealResult {
 const DEFAULT_TIMEOUT: u64 = 300;
 
 pub async fn process_reveal(args: RevealArgs) -> Result<()> {
-    println!(
+    report!(
         "{} {}Loading items from the cache",
         style("[1/4]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
+    progress_stream::phase_started("load_cache", 1, 4);
 
     let spinner = spinner_with_style();
     spinner.set_message("Connecting...");
@@ -79,7 +85,7 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
         return Err(anyhow!("Candy machine is not a Hidden Settings mint."));
     };
 
-    let cache = load_cache(&args.cache, false)?;
+    let mut cache = load_cache(&args.cache, false)?;
 
     // Check if the cache file is incomplete
     let num_items = config.number;
@@ -95,7 +101,7 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
             WARNING_EMOJI,
             num_items.saturating_sub(cache_items_sans_collection)
         );
-        println!(
+        report!(
             "\n{}\n{}\n",
             style(warning).bold().yellow(),
             style(
@@ -110,6 +116,8 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
     let anchor_client = setup_client(&sugar_config)?;
     let program = anchor_client.program(CANDY_MACHINE_ID);
 
+    cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
+
     let candy_machine_id = match Pubkey::from_str(&cache.program.candy_machine) {
         Ok(candy_machine_id) => candy_machine_id,
         Err(_) => {
@@ -139,18 +147,20 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
     };
 
     spinner.finish_with_message("Done");
+    progress_stream::phase_finished();
 
-    println!(
+    report!(
         "\n{} {}Getting minted NFTs for candy machine {}",
         style("[2/4]").bold().dim(),
         LOOKING_GLASS_EMOJI,
         candy_machine_id
     );
+    progress_stream::phase_started("find_nfts", 2, 4);
 
     let spinner = spinner_with_style();
     spinner.set_message("Loading...");
     let solana_cluster: Cluster = get_cluster(program.rpc())?;
-    let rpc_url = get_rpc_url(args.rpc_url);
+    let rpc_url = get_rpc_url(args.rpc_url)?;
 
     let solana_cluster = if rpc_url.ends_with("8899") {
         Cluster::Localnet
@@ -194,36 +204,23 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
         "Found {:?} accounts",
         metadata_pubkeys.len() as u64
     ));
+    progress_stream::totals(metadata_pubkeys.len() as u64);
+    progress_stream::phase_finished();
 
-    println!(
+    report!(
         "\n{} {}Matching NFTs to cache values",
         style("[3/4]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
+    progress_stream::phase_started("match_nfts", 3, 4);
     let spinner = spinner_with_style();
 
-    let mut futures = Vec::new();
     let client = RpcClient::new(&rpc_url);
     let client = Arc::new(client);
 
     // Get all metadata accounts.
-    metadata_pubkeys.as_slice().chunks(100).for_each(|chunk| {
-        let client = client.clone();
-        futures.push(async move { async_get_multiple_accounts(client, chunk).await });
-    });
-    let results = join_all(futures).await;
-    let mut accounts = Vec::new();
-
-    for result in results {
-        let res = result.unwrap();
-        accounts.extend(res);
-    }
-
-    let metadata: Vec<Metadata> = accounts
-        .into_iter()
-        .map(|a| a.unwrap().data)
-        .map(|d| Metadata::deserialize(&mut d.as_slice()).unwrap())
-        .collect();
+    let fetcher = AccountFetcher::new(client.clone());
+    let metadata = get_metadata_accounts(&fetcher, &metadata_pubkeys)?;
 
     let patterns: Vec<&str> = hidden_settings.name.split('$').collect();
     let index_pattern = patterns
@@ -249,14 +246,16 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
     serde_json::to_writer_pretty(File::create("temp.json")?, &nft_lookup)?;
 
     spinner.finish_with_message("Done");
+    progress_stream::phase_finished();
 
     let mut update_values = Vec::new();
 
-    println!(
+    report!(
         "\n{} {}Updating NFT URIs from cache values",
         style("[4/4]").bold().dim(),
         UPLOAD_EMOJI
     );
+    progress_stream::phase_started("update_nfts", 4, 4);
 
     let name_prefix_pattern = patterns.first().unwrap_or(&"");
     let name_suffix_pattern = patterns.get(2).unwrap_or(&"");
@@ -274,7 +273,7 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
         let num = match pattern.captures(&name).map(|c| c[1].to_string()) {
             Some(num) => num,
             None => {
-                println!(
+                report!(
                     "{}",
                     &format!(
                         "{}{}{}",
@@ -283,7 +282,7 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
                         style("\nIt may have already been updated").yellow().bold(),
                     )
                 );
-                println!();
+                report!();
                 continue;
             }
         };
@@ -321,6 +320,14 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
         });
     }
     spinner.finish_and_clear();
+    progress_stream::totals(update_values.len() as u64);
+
+    let priority_fee = resolve_priority_fee(
+        args.priority_fee,
+        DEFAULT_COMPUTE_UNITS,
+        update_values.len() as u64,
+        args.max_total_priority_sol,
+    )?;
 
     let keypair = Arc::new(sugar_config.keypair);
     let sem = Arc::new(Semaphore::new(1000));
@@ -331,8 +338,13 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
     pb.set_message("Updating NFTs... ");
 
     let cache = Arc::new(Mutex::new(cache));
+    let _cancellable = cancel::cancellable();
 
     for item in update_values {
+        if cancel::is_cancelled() {
+            break;
+        }
+
         let permit = Arc::clone(&sem).acquire_owned().await.unwrap();
         let client = client.clone();
         let keypair = keypair.clone();
@@ -351,14 +363,16 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
                 result: RevealResult::Success,
             };
 
-            match update_metadata_value(client, keypair, item).await {
+            match update_metadata_value(client, keypair, item, priority_fee).await {
                 Ok(_) => {
                     let mut cache_mutex = cache.lock().unwrap();
                     let v = cache_mutex.items.get_mut(&index).unwrap();
                     v.on_chain = true;
                     reveal_results.lock().unwrap().push(tx);
+                    progress_stream::item_completed(Some(index), Ok::<_, String>(()));
                 }
                 Err(e) => {
+                    progress_stream::item_completed(Some(index), Err(&e));
                     tx.result = RevealResult::Failure(e.to_string());
                     reveal_results.lock().unwrap().push(tx);
                 }
@@ -371,7 +385,6 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
     for task in tx_tasks {
         task.await.unwrap();
     }
-    pb.finish();
 
     let results = reveal_results.lock().unwrap();
 
@@ -380,32 +393,45 @@ pub async fn process_reveal(args: RevealArgs) -> Result<()> {
         .filter(|r| matches!(r.result, RevealResult::Failure(_)))
         .collect();
 
+    if cancel::is_cancelled() {
+        pb.abandon_with_message(format!(
+            "{} {}/{} {}",
+            style("Revealed").yellow().bold(),
+            results.len() - errors.len(),
+            metadata_pubkeys.len(),
+            style("NFTs").yellow().bold()
+        ));
+    } else {
+        pb.finish();
+        progress_stream::phase_finished();
+    }
+
     if !errors.is_empty() {
-        println!(
+        report!(
             "{}Some reveals failed. See the reveal cache file for details. Re-run the command.",
             WARNING_EMOJI
         );
         let f = File::create("sugar-reveal-cache.json")
             .map_err(|e| anyhow!("Failed to create sugar reveal cache file: {e}"))?;
         serde_json::to_writer_pretty(f, &errors).unwrap();
-    } else {
-        println!("\n{}Reveal complete!", CONFETTI_EMOJI);
     }
 
-    Ok(())
-}
+    if cancel::is_cancelled() {
+        return Err(cancel::cancelled().into());
+    } else if errors.is_empty() {
+        report!("\n{}Reveal complete!", CONFETTI_EMOJI);
+    }
 
-async fn async_get_multiple_accounts(
-    client: Arc<RpcClient>,
-    pubkeys: &[Pubkey],
-) -> Result<Vec<Option<Account>>, ClientError> {
-    client.get_multiple_accounts(pubkeys)
+    fetcher.print_summary();
+
+    Ok(())
 }
 
 async fn update_metadata_value(
     client: Arc<RpcClient>,
     update_authority: Arc<Keypair>,
     value: MetadataUpdateValues,
+    priority_fee: u64,
 ) -> Result<(), ClientError> {
     let mut data = value.metadata.data;
     if data.uri.trim_matches(char::from(0)) != value.new_uri.trim_matches(char::from(0)) {
@@ -432,9 +458,11 @@ async fn update_metadata_value(
             None,
         );
 
+        let priority_fee_ix = ComputeBudgetInstruction::set_compute_unit_price(priority_fee);
+
         let recent_blockhash = client.get_latest_blockhash()?;
         let tx = Transaction::new_signed_with_payer(
-            &[ix],
+            &[priority_fee_ix, ix],
             Some(&update_authority.pubkey()),
             &[&*update_authority],
             recent_blockhash,
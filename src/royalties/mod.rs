@@ -0,0 +1,7 @@
+pub mod set;
+pub mod show;
+pub mod utils;
+
+pub use set::*;
+pub use show::*;
+pub use utils::*;
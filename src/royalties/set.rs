@@ -0,0 +1,577 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex},
+};
+
+use mpl_token_metadata::{
+    instruction::{
+        builders::UpdateBuilder, update_metadata_accounts_v2, InstructionBuilder, RuleSetToggle,
+        UpdateArgs,
+    },
+    state::{DataV2, Metadata},
+    ID as TOKEN_METADATA_PROGRAM_ID,
+};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use tokio::sync::Semaphore;
+
+use crate::{
+    account_fetcher::AccountFetcher,
+    common::*,
+    mint_list::get_nft_metadata_pubkeys,
+    output::style,
+    pdas::{find_master_edition_pda, find_metadata_pda},
+    royalties::{get_rule_set, is_programmable},
+    setup::get_rpc_url,
+    utils::*,
+};
+
+pub const ROYALTIES_CACHE: &str = "sugar-royalties-cache.json";
+
+pub struct RoyaltiesSetArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub basis_points: u16,
+    pub rule_set: Option<String>,
+    pub mint_list: Option<String>,
+    pub candy_machine: Option<String>,
+    pub dry_run: bool,
+    pub timeout: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+struct RoyaltyUpdateValues {
+    metadata_pubkey: Pubkey,
+    metadata: Metadata,
+    basis_points: u16,
+    rule_set: Option<Pubkey>,
+}
+
+/// NFTs grouped by the change of their royalties.
+#[derive(Debug, Default)]
+struct RoyaltyPlan {
+    /// Mutable NFTs of the update authority to update.
+    update: Vec<RoyaltyUpdateValues>,
+    /// NFTs that already have the royalties (e.g. updated in a previous run).
+    up_to_date: Vec<Pubkey>,
+    /// Immutable NFTs that do not have the royalties (updating them fails).
+    immutable: Vec<Pubkey>,
+    /// NFTs of another update authority that do not have the royalties (updating them fails).
+    not_authority: Vec<Pubkey>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct RoyaltyTx {
+    mint: String,
+    result: RoyaltyResult,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+enum RoyaltyResult {
+    Success,
+    Failure(String),
+}
+
+pub async fn process_royalties_set(args: RoyaltiesSetArgs) -> Result<()> {
+    if args.basis_points > 10000 {
+        return Err(anyhow!(
+            "Invalid basis points {}, the maximum value is 10000.",
+            args.basis_points
+        ));
+    }
+
+    let rule_set = if let Some(rule_set) = &args.rule_set {
+        Some(
+            Pubkey::from_str(rule_set)
+                .map_err(|_| anyhow!("Failed to parse rule set: {}", rule_set))?,
+        )
+    } else {
+        None
+    };
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url.clone())?;
+    let update_authority = sugar_config.keypair.pubkey();
+
+    report!(
+        "{} {}Loading NFTs",
+        style("[1/4]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Loading...");
+
+    let metadata_pubkeys = get_nft_metadata_pubkeys(
+        args.rpc_url.clone(),
+        &args.cache,
+        args.mint_list,
+        args.candy_machine,
+        args.timeout,
+    )?;
+
+    if metadata_pubkeys.is_empty() {
+        spinner.finish_and_clear();
+        return Err(anyhow!("No NFTs found to update."));
+    }
+
+    spinner.finish_with_message(format!("Found {} NFTs", metadata_pubkeys.len()));
+
+    report!(
+        "\n{} {}Comparing royalties",
+        style("[2/4]").bold().dim(),
+        PAPER_EMOJI
+    );
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Fetching metadata...");
+
+    let client = Arc::new(RpcClient::new(get_rpc_url(args.rpc_url)?));
+    let fetcher = AccountFetcher::new(client.clone());
+    let metadata = get_metadata_accounts(&fetcher, &metadata_pubkeys)?;
+
+    let plan = plan_royalties(metadata, args.basis_points, rule_set, &update_authority);
+
+    spinner.finish_and_clear();
+
+    report!("{} {}", style("Up to date:").bold(), plan.up_to_date.len());
+    report!("{} {}", style("To update:").bold(), plan.update.len());
+    report_skipped(&plan);
+
+    if args.dry_run {
+        report!(
+            "\n{} {}Dry run: no transactions sent",
+            style("[3/4]").bold().dim(),
+            UPLOAD_EMOJI
+        );
+        for change in dry_run_changes(&plan) {
+            report!("  {}", change);
+        }
+        return Ok(());
+    }
+
+    // only the NFTs sent for update are verified
+    let updated: HashSet<Pubkey> = plan.update.iter().map(|item| item.metadata.mint).collect();
+    let update_values = plan.update;
+
+    report!(
+        "\n{} {}Updating royalties",
+        style("[3/4]").bold().dim(),
+        UPLOAD_EMOJI
+    );
+
+    let keypair = Arc::new(sugar_config.keypair);
+    let sem = Arc::new(Semaphore::new(1000));
+    let royalty_results = Arc::new(Mutex::new(Vec::new()));
+    let mut tx_tasks = Vec::new();
+
+    let pb = progress_bar_with_style(update_values.len() as u64);
+    pb.set_message("Updating NFTs... ");
+
+    for item in update_values {
+        let permit = Arc::clone(&sem).acquire_owned().await.unwrap();
+        let client = client.clone();
+        let keypair = keypair.clone();
+        let royalty_results = royalty_results.clone();
+        let pb = pb.clone();
+
+        tx_tasks.push(tokio::spawn(async move {
+            // Move permit into the closure so it is dropped when the task is dropped.
+            let _permit = permit;
+            let mut tx = RoyaltyTx {
+                mint: item.metadata.mint.to_string(),
+                result: RoyaltyResult::Success,
+            };
+
+            if let Err(e) = update_royalty_value(client, keypair, item).await {
+                tx.result = RoyaltyResult::Failure(e.to_string());
+            }
+            royalty_results.lock().unwrap().push(tx);
+
+            pb.inc(1);
+        }));
+    }
+
+    for task in tx_tasks {
+        task.await.unwrap();
+    }
+    pb.finish();
+
+    report!(
+        "\n{} {}Verifying royalties",
+        style("[4/4]").bold().dim(),
+        PAPER_EMOJI
+    );
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Fetching metadata...");
+
+    // the metadata accounts were updated, so they must not come from the cache
+    fetcher.invalidate(&metadata_pubkeys);
+    let metadata = get_metadata_accounts(&fetcher, &metadata_pubkeys)?;
+    let mismatched = mismatched_royalties(&metadata, &updated, args.basis_points, rule_set);
+
+    spinner.finish_and_clear();
+
+    let results = royalty_results.lock().unwrap();
+
+    let errors: Vec<&RoyaltyTx> = results
+        .iter()
+        .filter(|r| matches!(r.result, RoyaltyResult::Failure(_)))
+        .collect();
+
+    if !errors.is_empty() {
+        let f = File::create(ROYALTIES_CACHE)
+            .map_err(|e| anyhow!("Failed to create sugar royalties cache file: {e}"))?;
+        serde_json::to_writer_pretty(f, &errors)?;
+    }
+
+    if !errors.is_empty() || !mismatched.is_empty() {
+        report!(
+            "{}{} updated item(s) do not have the expected royalties{}. See the royalties \
+            cache file for details. Re-run the command.",
+            WARNING_EMOJI,
+            mismatched.len(),
+            if rule_set.is_some() {
+                " or rule set"
+            } else {
+                ""
+            }
+        );
+        for mint in &mismatched {
+            report!("  {}", mint);
+        }
+    } else {
+        report!("{}Royalties updated!", CONFETTI_EMOJI);
+    }
+
+    // the items that could not be updated are reported apart from the failed updates
+    let skipped = plan.immutable.len() + plan.not_authority.len();
+    if skipped > 0 {
+        report!(
+            "{}{} item(s) were not updated (immutable or another update authority).",
+            WARNING_EMOJI,
+            skipped
+        );
+    }
+
+    Ok(())
+}
+
+/// Checks if the NFT has the basis points and, for a programmable NFT, the rule set (when
+/// one is set).
+fn has_expected_royalties(
+    metadata: &Metadata,
+    basis_points: u16,
+    rule_set: Option<Pubkey>,
+) -> bool {
+    let rule_set_matches =
+        rule_set.is_none() || !is_programmable(metadata) || get_rule_set(metadata) == rule_set;
+
+    metadata.data.seller_fee_basis_points == basis_points && rule_set_matches
+}
+
+/// Groups the NFTs by the change of their royalties.
+fn plan_royalties(
+    metadata: Vec<Metadata>,
+    basis_points: u16,
+    rule_set: Option<Pubkey>,
+    update_authority: &Pubkey,
+) -> RoyaltyPlan {
+    let mut plan = RoyaltyPlan::default();
+
+    for m in metadata {
+        if has_expected_royalties(&m, basis_points, rule_set) {
+            plan.up_to_date.push(m.mint);
+        } else if !m.is_mutable {
+            plan.immutable.push(m.mint);
+        } else if m.update_authority != *update_authority {
+            plan.not_authority.push(m.mint);
+        } else {
+            plan.update.push(RoyaltyUpdateValues {
+                metadata_pubkey: find_metadata_pda(&m.mint),
+                basis_points,
+                rule_set: if is_programmable(&m) { rule_set } else { None },
+                metadata: m,
+            });
+        }
+    }
+
+    plan
+}
+
+/// Reports the NFTs that cannot be updated.
+fn report_skipped(plan: &RoyaltyPlan) {
+    if !plan.immutable.is_empty() {
+        report!(
+            "{}{} {}",
+            WARNING_EMOJI,
+            style("Immutable (cannot be updated):").yellow().bold(),
+            plan.immutable.len()
+        );
+        for mint in &plan.immutable {
+            report!("  {}", mint);
+        }
+    }
+
+    if !plan.not_authority.is_empty() {
+        report!(
+            "{}{} {}",
+            WARNING_EMOJI,
+            style("Keypair is not the update authority:")
+                .yellow()
+                .bold(),
+            plan.not_authority.len()
+        );
+        for mint in &plan.not_authority {
+            report!("  {}", mint);
+        }
+    }
+}
+
+/// Changes of the NFTs to update, listed by a dry run.
+fn dry_run_changes(plan: &RoyaltyPlan) -> Vec<String> {
+    plan.update
+        .iter()
+        .map(|item| {
+            let mut change = format!(
+                "{}: {} -> {} basis points",
+                item.metadata.mint, item.metadata.data.seller_fee_basis_points, item.basis_points
+            );
+            if let Some(rule_set) = item.rule_set {
+                let current = get_rule_set(&item.metadata)
+                    .map_or_else(|| "none".to_string(), |rule_set| rule_set.to_string());
+                change.push_str(&format!(", rule set {} -> {}", current, rule_set));
+            }
+            change
+        })
+        .collect()
+}
+
+/// Mints of the updated NFTs that do not have the expected royalties after the update.
+fn mismatched_royalties(
+    metadata: &[Metadata],
+    updated: &HashSet<Pubkey>,
+    basis_points: u16,
+    rule_set: Option<Pubkey>,
+) -> Vec<Pubkey> {
+    metadata
+        .iter()
+        .filter(|m| updated.contains(&m.mint) && !has_expected_royalties(m, basis_points, rule_set))
+        .map(|m| m.mint)
+        .collect()
+}
+
+async fn update_royalty_value(
+    client: Arc<RpcClient>,
+    update_authority: Arc<Keypair>,
+    value: RoyaltyUpdateValues,
+) -> Result<()> {
+    let authority = update_authority.pubkey();
+    let metadata = value.metadata;
+
+    let ix = if is_programmable(&metadata) {
+        // programmable NFTs need to be updated through the token metadata 'update' instruction
+        let mut data = metadata.data;
+        data.seller_fee_basis_points = value.basis_points;
+
+        let mut update_args = UpdateArgs::default();
+        let UpdateArgs::V1 {
+            data: ref mut update_data,
+            rule_set: ref mut update_rule_set,
+            ..
+        } = update_args;
+        *update_data = Some(data);
+        if let Some(rule_set) = value.rule_set {
+            *update_rule_set = RuleSetToggle::Set(rule_set);
+        }
+
+        UpdateBuilder::new()
+            .authority(authority)
+            .metadata(value.metadata_pubkey)
+            .mint(metadata.mint)
+            .edition(find_master_edition_pda(&metadata.mint))
+            .payer(authority)
+            .build(update_args)
+            .map_err(|e| anyhow!("Failed to build update instruction: {}", e))?
+            .instruction()
+    } else {
+        let data = metadata.data;
+        let data_v2 = DataV2 {
+            name: data.name,
+            symbol: data.symbol,
+            uri: data.uri,
+            seller_fee_basis_points: value.basis_points,
+            creators: data.creators,
+            collection: metadata.collection,
+            uses: metadata.uses,
+        };
+
+        update_metadata_accounts_v2(
+            TOKEN_METADATA_PROGRAM_ID,
+            value.metadata_pubkey,
+            authority,
+            None,
+            Some(data_v2),
+            None,
+            None,
+        )
+    };
+
+    let recent_blockhash = client.get_latest_blockhash()?;
+    let tx = Transaction::new_signed_with_payer(
+        &[ix],
+        Some(&authority),
+        &[&*update_authority],
+        recent_blockhash,
+    );
+
+    client.send_and_confirm_transaction(&tx)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use mpl_token_metadata::state::{Data, ProgrammableConfig, TokenStandard};
+
+    use super::*;
+
+    fn nft(basis_points: u16, is_mutable: bool, update_authority: Pubkey) -> Metadata {
+        Metadata {
+            mint: Pubkey::new_unique(),
+            update_authority,
+            is_mutable,
+            data: Data {
+                seller_fee_basis_points: basis_points,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn programmable(metadata: Metadata, rule_set: Option<Pubkey>) -> Metadata {
+        Metadata {
+            token_standard: Some(TokenStandard::ProgrammableNonFungible),
+            programmable_config: Some(ProgrammableConfig::V1 { rule_set }),
+            ..metadata
+        }
+    }
+
+    #[test]
+    fn test_plan_royalties() {
+        let authority = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let rule_set = Pubkey::new_unique();
+
+        let to_update = nft(500, true, authority);
+        let up_to_date = nft(750, true, authority);
+        let immutable = nft(500, false, authority);
+        let immutable_up_to_date = nft(750, false, other);
+        let not_authority = nft(500, true, other);
+        let without_rule_set = programmable(nft(750, true, authority), None);
+        let with_rule_set = programmable(nft(750, true, authority), Some(rule_set));
+
+        let mints: Vec<Pubkey> = [
+            &to_update,
+            &up_to_date,
+            &immutable,
+            &immutable_up_to_date,
+            &not_authority,
+            &without_rule_set,
+            &with_rule_set,
+        ]
+        .iter()
+        .map(|m| m.mint)
+        .collect();
+
+        let plan = plan_royalties(
+            vec![
+                to_update,
+                up_to_date,
+                immutable,
+                immutable_up_to_date,
+                not_authority,
+                without_rule_set,
+                with_rule_set,
+            ],
+            750,
+            Some(rule_set),
+            &authority,
+        );
+
+        let update: Vec<Pubkey> = plan.update.iter().map(|item| item.metadata.mint).collect();
+        assert_eq!(update, vec![mints[0], mints[5]]);
+        assert_eq!(plan.up_to_date, vec![mints[1], mints[3], mints[6]]);
+        assert_eq!(plan.immutable, vec![mints[2]]);
+        assert_eq!(plan.not_authority, vec![mints[4]]);
+
+        // the rule set is only set on the programmable NFTs
+        assert_eq!(plan.update[0].rule_set, None);
+        assert_eq!(plan.update[1].rule_set, Some(rule_set));
+
+        // without a rule set, only the basis points are compared
+        let plan = plan_royalties(
+            vec![programmable(nft(750, true, authority), None)],
+            750,
+            None,
+            &authority,
+        );
+        assert!(plan.update.is_empty());
+        assert_eq!(plan.up_to_date.len(), 1);
+    }
+
+    #[test]
+    fn test_dry_run_changes() {
+        let authority = Pubkey::new_unique();
+        let rule_set = Pubkey::new_unique();
+        let nft_mint = nft(500, true, authority);
+        let pnft_mint = programmable(nft(500, true, authority), None);
+        let (nft_key, pnft_key) = (nft_mint.mint, pnft_mint.mint);
+
+        let plan = plan_royalties(
+            vec![nft_mint, pnft_mint, nft(500, true, Pubkey::new_unique())],
+            750,
+            Some(rule_set),
+            &authority,
+        );
+
+        // only the NFTs to update are listed
+        assert_eq!(
+            dry_run_changes(&plan),
+            vec![
+                format!("{}: 500 -> 750 basis points", nft_key),
+                format!(
+                    "{}: 500 -> 750 basis points, rule set none -> {}",
+                    pnft_key, rule_set
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mismatched_royalties() {
+        let authority = Pubkey::new_unique();
+        let rule_set = Pubkey::new_unique();
+
+        let updated_nft = nft(750, true, authority);
+        let failed_nft = nft(500, true, authority);
+        let wrong_rule_set = programmable(nft(750, true, authority), Some(Pubkey::new_unique()));
+        // not updated: another update authority
+        let not_authority = nft(500, true, Pubkey::new_unique());
+
+        let updated: HashSet<Pubkey> = [&updated_nft, &failed_nft, &wrong_rule_set]
+            .iter()
+            .map(|m| m.mint)
+            .collect();
+        let expected = vec![failed_nft.mint, wrong_rule_set.mint];
+
+        let mismatched = mismatched_royalties(
+            &[updated_nft, failed_nft, wrong_rule_set, not_authority],
+            &updated,
+            750,
+            Some(rule_set),
+        );
+        assert_eq!(mismatched, expected);
+    }
+}
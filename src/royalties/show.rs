@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    account_fetcher::AccountFetcher,
+    common::*,
+    config::get_config_data,
+    mint_list::get_nft_metadata_pubkeys,
+    output::style,
+    royalties::{get_rule_set, is_programmable},
+    setup::get_rpc_url,
+    show::print_with_style,
+    utils::*,
+};
+
+pub struct RoyaltiesShowArgs {
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub config: String,
+    pub mint_list: Option<String>,
+    pub candy_machine: Option<String>,
+    pub timeout: Option<u64>,
+}
+
+pub async fn process_royalties_show(args: RoyaltiesShowArgs) -> Result<()> {
+    report!(
+        "{} {}Loading NFTs",
+        style("[1/2]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    let spinner = spinner_with_style();
+    spinner.set_message("Loading...");
+
+    let metadata_pubkeys = get_nft_metadata_pubkeys(
+        args.rpc_url.clone(),
+        &args.cache,
+        args.mint_list,
+        args.candy_machine,
+        args.timeout,
+    )?;
+
+    let fetcher = AccountFetcher::new(RpcClient::new(get_rpc_url(args.rpc_url)?));
+    let metadata = get_metadata_accounts(&fetcher, &metadata_pubkeys)?;
+
+    spinner.finish_with_message(format!("Found {} NFTs", metadata.len()));
+
+    report!(
+        "\n{} {}Royalties summary",
+        style("[2/2]").bold().dim(),
+        PAPER_EMOJI
+    );
+
+    // the config value is optional, the summary is still useful without it
+    let expected = get_config_data(&args.config)
+        .ok()
+        .map(|config| config.seller_fee_basis_points);
+
+    let mut basis_points: BTreeMap<u16, usize> = BTreeMap::new();
+    let mut rule_sets: BTreeMap<String, usize> = BTreeMap::new();
+    let mut immutable = 0;
+    let mut diverging = 0;
+
+    for m in &metadata {
+        *basis_points
+            .entry(m.data.seller_fee_basis_points)
+            .or_default() += 1;
+
+        if is_programmable(m) {
+            let rule_set = get_rule_set(m)
+                .map(|rule_set| rule_set.to_string())
+                .unwrap_or_else(|| "none".to_string());
+            *rule_sets.entry(rule_set).or_default() += 1;
+        }
+
+        if !m.is_mutable {
+            immutable += 1;
+        }
+
+        if let Some(expected) = expected {
+            if m.data.seller_fee_basis_points != expected {
+                diverging += 1;
+            }
+        }
+    }
+
+    report!("\n{}", style("Seller fee basis points:").dim());
+    for (value, count) in &basis_points {
+        print_with_style("", &value.to_string(), format!("{count} item(s)"));
+    }
+
+    if !rule_sets.is_empty() {
+        report!("\n{}", style("Rule sets:").dim());
+        for (rule_set, count) in &rule_sets {
+            print_with_style("", rule_set, format!("{count} item(s)"));
+        }
+    }
+
+    report!();
+    print_with_style("", "immutable", immutable.to_string());
+
+    if let Some(expected) = expected {
+        print_with_style(
+            "",
+            "diverging",
+            format!(
+                "{} item(s) differ from the config value ({})",
+                diverging, expected
+            ),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,18 @@
+use mpl_token_metadata::state::{Metadata, ProgrammableConfig, TokenStandard};
+
+use crate::common::*;
+
+/// Return the rule set of a programmable NFT, if any.
+pub fn get_rule_set(metadata: &Metadata) -> Option<Pubkey> {
+    match metadata.programmable_config {
+        Some(ProgrammableConfig::V1 { rule_set }) => rule_set,
+        None => None,
+    }
+}
+
+pub fn is_programmable(metadata: &Metadata) -> bool {
+    matches!(
+        metadata.token_standard,
+        Some(TokenStandard::ProgrammableNonFungible)
+    )
+}
@@ -8,7 +8,6 @@ use anchor_client::{
     Client, Cluster,
 };
 use anyhow::{anyhow, Result};
-use console::style;
 use tracing::error;
 
 use crate::{config::data::SugarConfig, constants::DEFAULT_KEYPATH, parse::*};
@@ -31,9 +30,7 @@ pub fn sugar_setup(
     keypair_opt: Option<String>,
     rpc_url_opt: Option<String>,
 ) -> Result<SugarConfig> {
-    let sol_config_option = parse_solana_config();
-
-    let rpc_url = get_rpc_url(rpc_url_opt);
+    let rpc_url = get_rpc_url(rpc_url_opt)?;
 
     let keypair = match keypair_opt {
         Some(keypair_path) => match read_keypair_file(&keypair_path) {
@@ -48,7 +45,8 @@ pub fn sugar_setup(
             }
         },
 
-        None => match sol_config_option {
+        // the Solana config is only read when the keypair is not specified
+        None => match parse_solana_config()? {
             Some(ref sol_config) => match read_keypair_file(&sol_config.keypair_path) {
                 Ok(keypair) => keypair,
                 Err(e) => {
@@ -80,22 +78,12 @@ pub fn sugar_setup(
     Ok(SugarConfig { rpc_url, keypair })
 }
 
-pub fn get_rpc_url(rpc_url_opt: Option<String>) -> String {
-    let sol_config_option = parse_solana_config();
-
+pub fn get_rpc_url(rpc_url_opt: Option<String>) -> Result<String> {
     match rpc_url_opt {
-        Some(rpc_url) => rpc_url,
-        None => match sol_config_option {
-            Some(ref sol_config) => sol_config.json_rpc_url.clone(),
-            None => {
-                println!(
-                    "{}",
-                    style("No RPCL URL found in Solana config file.")
-                        .bold()
-                        .red(),
-                );
-                std::process::exit(1);
-            }
+        Some(rpc_url) => Ok(rpc_url),
+        None => match parse_solana_config()? {
+            Some(ref sol_config) => Ok(sol_config.json_rpc_url.clone()),
+            None => Err(anyhow!("No RPC URL found in Solana config file.")),
         },
     }
 }
@@ -0,0 +1,516 @@
+use std::{collections::HashSet, fmt, fs, thread, time::Duration};
+
+use anchor_client::solana_sdk::{commitment_config::CommitmentConfig, pubkey::Pubkey};
+use anchor_lang::Discriminator;
+use chrono::NaiveDateTime;
+use mpl_candy_guard::instruction as guard_instruction;
+use mpl_candy_machine_core::instruction as nft_instruction;
+use serde::Serialize;
+use solana_client::{
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::RpcTransactionConfig,
+    rpc_response::RpcConfirmedTransactionStatusWithSignature,
+};
+use solana_transaction_status::UiTransactionEncoding;
+
+use crate::{candy_machine::CANDY_MACHINE_ID, common::*, output::style, utils::*};
+
+// maximum number of signatures returned by a single 'getSignaturesForAddress' call
+const PAGE_SIZE: usize = 1000;
+
+// delay between transaction requests to avoid hitting RPC rate limits
+const REQUEST_DELAY: Duration = Duration::from_millis(50);
+
+// maximum number of times to retry each individual request
+const MAX_RETRY: u32 = 3;
+
+/// Cutoff for the transaction history.
+pub enum HistorySince {
+    Signature(Signature),
+    Timestamp(i64),
+}
+
+impl FromStr for HistorySince {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if let Ok(signature) = Signature::from_str(value) {
+            return Ok(HistorySince::Signature(signature));
+        }
+
+        match dateparser::parse(value) {
+            Ok(date) => Ok(HistorySince::Timestamp(date.timestamp())),
+            Err(_) => Err(anyhow!(
+                "Invalid --since value '{}', expected a transaction signature or a date",
+                value
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    Initialize,
+    AddConfigLines,
+    Mint,
+    SettingsUpdate,
+    SetAuthority,
+    SetCollection,
+    Withdraw,
+    GuardInitialize,
+    GuardUpdate,
+    Wrap,
+    Unwrap,
+    Unknown,
+}
+
+impl fmt::Display for TransactionKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            TransactionKind::Initialize => "initialize",
+            TransactionKind::AddConfigLines => "add config lines",
+            TransactionKind::Mint => "mint",
+            TransactionKind::SettingsUpdate => "settings update",
+            TransactionKind::SetAuthority => "set authority",
+            TransactionKind::SetCollection => "set collection",
+            TransactionKind::Withdraw => "withdraw",
+            TransactionKind::GuardInitialize => "guard initialize",
+            TransactionKind::GuardUpdate => "guard update",
+            TransactionKind::Wrap => "wrap",
+            TransactionKind::Unwrap => "unwrap",
+            TransactionKind::Unknown => "unknown",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub signature: String,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    pub kind: TransactionKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    pub fee_payer: Option<String>,
+    pub fee: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub struct HistoryArgs<'a> {
+    pub candy_machine: &'a Pubkey,
+    pub candy_guard: Option<&'a Pubkey>,
+    pub limit: Option<usize>,
+    pub since: Option<HistorySince>,
+    pub output: Option<String>,
+}
+
+/// Retrieves and prints the transaction history of a candy machine (and its candy guard).
+pub fn process_history(rpc: &RpcClient, args: HistoryArgs) -> Result<()> {
+    let pb = spinner_with_style();
+    pb.set_message("Retrieving signatures...");
+
+    let mut addresses = vec![*args.candy_machine];
+    if let Some(candy_guard) = args.candy_guard {
+        addresses.push(*candy_guard);
+    }
+
+    let mut statuses = Vec::new();
+    let mut seen = HashSet::new();
+
+    for address in &addresses {
+        for status in get_signatures(rpc, address, args.limit, &args.since)? {
+            // a transaction can reference both the candy machine and the candy guard
+            if seen.insert(status.signature.clone()) {
+                statuses.push(status);
+            }
+        }
+    }
+
+    // newest first, so the limit keeps the most recent transactions
+    statuses.sort_by(|a, b| b.slot.cmp(&a.slot));
+    if let Some(limit) = args.limit {
+        statuses.truncate(limit);
+    }
+
+    pb.finish_and_clear();
+
+    if statuses.is_empty() {
+        report!("\n{}{}", PAPER_EMOJI, style("No transactions found.").dim());
+        return Ok(());
+    }
+
+    let pb = progress_bar_with_style(statuses.len() as u64);
+    let mut entries = Vec::with_capacity(statuses.len());
+
+    for status in statuses.into_iter().rev() {
+        let signature = Signature::from_str(&status.signature)?;
+        let (kind, details, fee_payer, fee) =
+            match get_transaction(rpc, &signature, args.candy_machine, args.candy_guard) {
+                Ok(classified) => classified,
+                Err(err) => {
+                    warn!("Failed to retrieve transaction {}: {:?}", signature, err);
+                    (TransactionKind::Unknown, None, None, None)
+                }
+            };
+
+        entries.push(HistoryEntry {
+            signature: status.signature,
+            slot: status.slot,
+            block_time: status.block_time,
+            kind,
+            details,
+            fee_payer,
+            fee,
+            error: status.err.map(|err| err.to_string()),
+        });
+
+        pb.inc(1);
+        thread::sleep(REQUEST_DELAY);
+    }
+
+    pb.finish_and_clear();
+
+    report!("\n{}{}\n", PAPER_EMOJI, style("Transaction history:").dim());
+
+    for entry in &entries {
+        let date = entry
+            .block_time
+            .and_then(|time| NaiveDateTime::from_timestamp_opt(time, 0))
+            .map(|date| date.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+            .unwrap_or_else(|| "unknown time".to_string());
+
+        let kind = match &entry.details {
+            Some(details) => format!("{} ({})", entry.kind, details),
+            None => entry.kind.to_string(),
+        };
+
+        report!(
+            " {} {} {}{}",
+            style(date).dim(),
+            style(format!("{:<32}", kind)).bold(),
+            entry.fee_payer.as_deref().unwrap_or("-"),
+            if entry.error.is_some() {
+                style(" [failed]").red().to_string()
+            } else {
+                String::new()
+            }
+        );
+        report!("   {} {}", style("signature:").dim(), entry.signature);
+    }
+
+    report!(
+        "\n{}",
+        style(format!("{} total transactions.", entries.len())).dim()
+    );
+
+    if let Some(output) = args.output {
+        fs::write(&output, serde_json::to_string_pretty(&entries)?)?;
+        report!("{} {}", style("History saved to:").bold(), output);
+    }
+
+    Ok(())
+}
+
+/// Pages through 'getSignaturesForAddress' until the limit or the cutoff is reached.
+fn get_signatures(
+    rpc: &RpcClient,
+    address: &Pubkey,
+    limit: Option<usize>,
+    since: &Option<HistorySince>,
+) -> Result<Vec<RpcConfirmedTransactionStatusWithSignature>> {
+    let until = match since {
+        Some(HistorySince::Signature(signature)) => Some(*signature),
+        _ => None,
+    };
+    let cutoff = match since {
+        Some(HistorySince::Timestamp(timestamp)) => Some(*timestamp),
+        _ => None,
+    };
+
+    let mut signatures = Vec::new();
+    let mut before = None;
+
+    loop {
+        let page = with_retry(|| {
+            rpc.get_signatures_for_address_with_config(
+                address,
+                GetConfirmedSignaturesForAddress2Config {
+                    before,
+                    until,
+                    limit: Some(PAGE_SIZE),
+                    commitment: Some(CommitmentConfig::confirmed()),
+                },
+            )
+        })?;
+
+        let page_len = page.len();
+        before = page
+            .last()
+            .map(|status| Signature::from_str(&status.signature))
+            .transpose()?;
+
+        for status in page {
+            if let (Some(cutoff), Some(block_time)) = (cutoff, status.block_time) {
+                if block_time < cutoff {
+                    return Ok(signatures);
+                }
+            }
+
+            signatures.push(status);
+
+            if limit
+                .map(|limit| signatures.len() >= limit)
+                .unwrap_or(false)
+            {
+                return Ok(signatures);
+            }
+        }
+
+        if page_len < PAGE_SIZE {
+            return Ok(signatures);
+        }
+
+        thread::sleep(REQUEST_DELAY);
+    }
+}
+
+type Classified = (TransactionKind, Option<String>, Option<String>, Option<u64>);
+
+/// Retrieves a transaction and classifies it by inspecting its instructions.
+fn get_transaction(
+    rpc: &RpcClient,
+    signature: &Signature,
+    candy_machine: &Pubkey,
+    candy_guard: Option<&Pubkey>,
+) -> Result<Classified> {
+    let transaction = with_retry(|| {
+        rpc.get_transaction_with_config(
+            signature,
+            RpcTransactionConfig {
+                encoding: Some(UiTransactionEncoding::Base64),
+                commitment: Some(CommitmentConfig::confirmed()),
+                max_supported_transaction_version: Some(0),
+            },
+        )
+    })?;
+
+    let fee = transaction.transaction.meta.as_ref().map(|meta| meta.fee);
+    let decoded = transaction
+        .transaction
+        .transaction
+        .decode()
+        .ok_or_else(|| anyhow!("Failed to decode transaction {}", signature))?;
+
+    let account_keys = decoded.message.static_account_keys();
+    let fee_payer = account_keys.first().map(|key| key.to_string());
+
+    for instruction in decoded.message.instructions() {
+        let program_id = match account_keys.get(instruction.program_id_index as usize) {
+            Some(program_id) => program_id,
+            None => continue,
+        };
+
+        // only instructions referencing the candy machine or its candy guard are classified
+        let references = |address: &Pubkey| {
+            instruction
+                .accounts
+                .iter()
+                .any(|index| account_keys.get(*index as usize) == Some(address))
+        };
+
+        if *program_id == CANDY_MACHINE_ID && references(candy_machine) {
+            let (kind, details) = classify_candy_machine(&instruction.data);
+            return Ok((kind, details, fee_payer, fee));
+        }
+
+        if *program_id == mpl_candy_guard::ID
+            && (references(candy_machine) || candy_guard.map(references).unwrap_or(false))
+        {
+            return Ok((
+                classify_candy_guard(&instruction.data),
+                None,
+                fee_payer,
+                fee,
+            ));
+        }
+    }
+
+    Ok((TransactionKind::Unknown, None, fee_payer, fee))
+}
+
+fn classify_candy_machine(data: &[u8]) -> (TransactionKind, Option<String>) {
+    let discriminator = match data.get(..8) {
+        Some(discriminator) => discriminator,
+        None => return (TransactionKind::Unknown, None),
+    };
+    let is_any = |discriminators: &[[u8; 8]]| discriminators.iter().any(|d| d == discriminator);
+
+    let kind = if is_any(&[
+        nft_instruction::Initialize::DISCRIMINATOR,
+        nft_instruction::InitializeV2::DISCRIMINATOR,
+    ]) {
+        TransactionKind::Initialize
+    } else if is_any(&[nft_instruction::AddConfigLines::DISCRIMINATOR]) {
+        // args: index (u32) followed by the config lines vector length (u32)
+        // (no details when the range overflows, the instruction would fail anyway)
+        let details = if data.len() >= 16 {
+            let index = u32::from_le_bytes(data[8..12].try_into().unwrap());
+            let count = u32::from_le_bytes(data[12..16].try_into().unwrap());
+            index
+                .checked_add(count)
+                .map(|end| format!("{}-{}", index, end.saturating_sub(1)))
+        } else {
+            None
+        };
+        return (TransactionKind::AddConfigLines, details);
+    } else if is_any(&[
+        nft_instruction::Mint::DISCRIMINATOR,
+        nft_instruction::MintV2::DISCRIMINATOR,
+    ]) {
+        TransactionKind::Mint
+    } else if is_any(&[
+        nft_instruction::Update::DISCRIMINATOR,
+        nft_instruction::SetTokenStandard::DISCRIMINATOR,
+    ]) {
+        TransactionKind::SettingsUpdate
+    } else if is_any(&[
+        nft_instruction::SetAuthority::DISCRIMINATOR,
+        nft_instruction::SetMintAuthority::DISCRIMINATOR,
+    ]) {
+        TransactionKind::SetAuthority
+    } else if is_any(&[
+        nft_instruction::SetCollection::DISCRIMINATOR,
+        nft_instruction::SetCollectionV2::DISCRIMINATOR,
+    ]) {
+        TransactionKind::SetCollection
+    } else if is_any(&[nft_instruction::Withdraw::DISCRIMINATOR]) {
+        TransactionKind::Withdraw
+    } else {
+        TransactionKind::Unknown
+    };
+
+    (kind, None)
+}
+
+fn classify_candy_guard(data: &[u8]) -> TransactionKind {
+    let discriminator = match data.get(..8) {
+        Some(discriminator) => discriminator,
+        None => return TransactionKind::Unknown,
+    };
+    let is_any = |discriminators: &[[u8; 8]]| discriminators.iter().any(|d| d == discriminator);
+
+    if is_any(&[guard_instruction::Initialize::DISCRIMINATOR]) {
+        TransactionKind::GuardInitialize
+    } else if is_any(&[
+        guard_instruction::Mint::DISCRIMINATOR,
+        guard_instruction::MintV2::DISCRIMINATOR,
+    ]) {
+        TransactionKind::Mint
+    } else if is_any(&[guard_instruction::Update::DISCRIMINATOR]) {
+        TransactionKind::GuardUpdate
+    } else if is_any(&[guard_instruction::SetAuthority::DISCRIMINATOR]) {
+        TransactionKind::SetAuthority
+    } else if is_any(&[guard_instruction::Withdraw::DISCRIMINATOR]) {
+        TransactionKind::Withdraw
+    } else if is_any(&[guard_instruction::Wrap::DISCRIMINATOR]) {
+        TransactionKind::Wrap
+    } else if is_any(&[guard_instruction::Unwrap::DISCRIMINATOR]) {
+        TransactionKind::Unwrap
+    } else {
+        TransactionKind::Unknown
+    }
+}
+
+/// Retries an RPC request with an exponential backoff (also respects rate limits).
+fn with_retry<T, E: fmt::Display>(mut request: impl FnMut() -> Result<T, E>) -> Result<T> {
+    let mut attempt = 0;
+
+    loop {
+        match request() {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt < MAX_RETRY => {
+                attempt += 1;
+                warn!("RPC request failed (attempt {}): {}", attempt, err);
+                thread::sleep(Duration::from_secs(2u64.pow(attempt)));
+            }
+            Err(err) => return Err(anyhow!("RPC request failed: {}", err)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add_config_lines_data(index: u32, count: u32) -> Vec<u8> {
+        let mut data = nft_instruction::AddConfigLines::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&index.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn classify_candy_machine_instructions() {
+        assert_eq!(
+            classify_candy_machine(&nft_instruction::InitializeV2::DISCRIMINATOR),
+            (TransactionKind::Initialize, None)
+        );
+        assert_eq!(
+            classify_candy_machine(&nft_instruction::MintV2::DISCRIMINATOR),
+            (TransactionKind::Mint, None)
+        );
+        assert_eq!(
+            classify_candy_machine(&nft_instruction::Withdraw::DISCRIMINATOR),
+            (TransactionKind::Withdraw, None)
+        );
+        assert_eq!(
+            classify_candy_machine(&add_config_lines_data(10, 5)),
+            (TransactionKind::AddConfigLines, Some("10-14".to_string()))
+        );
+        // truncated config lines arguments
+        assert_eq!(
+            classify_candy_machine(&nft_instruction::AddConfigLines::DISCRIMINATOR),
+            (TransactionKind::AddConfigLines, None)
+        );
+        assert_eq!(
+            classify_candy_machine(&[1, 2, 3]),
+            (TransactionKind::Unknown, None)
+        );
+        assert_eq!(
+            classify_candy_machine(&[0; 8]),
+            (TransactionKind::Unknown, None)
+        );
+    }
+
+    #[test]
+    fn classify_candy_machine_range_does_not_overflow() {
+        assert_eq!(
+            classify_candy_machine(&add_config_lines_data(u32::MAX, 2)),
+            (TransactionKind::AddConfigLines, None)
+        );
+    }
+
+    #[test]
+    fn classify_candy_guard_instructions() {
+        assert_eq!(
+            classify_candy_guard(&guard_instruction::Initialize::DISCRIMINATOR),
+            TransactionKind::GuardInitialize
+        );
+        assert_eq!(
+            classify_candy_guard(&guard_instruction::MintV2::DISCRIMINATOR),
+            TransactionKind::Mint
+        );
+        assert_eq!(
+            classify_candy_guard(&guard_instruction::Wrap::DISCRIMINATOR),
+            TransactionKind::Wrap
+        );
+        assert_eq!(
+            classify_candy_guard(&guard_instruction::Unwrap::DISCRIMINATOR),
+            TransactionKind::Unwrap
+        );
+        assert_eq!(classify_candy_guard(&[]), TransactionKind::Unknown);
+    }
+}
@@ -1,3 +1,5 @@
+pub mod history;
 pub mod process;
 
+pub use history::*;
 pub use process::*;
@@ -2,7 +2,6 @@ use std::str::FromStr;
 
 use anchor_client::solana_sdk::pubkey::Pubkey;
 use anyhow::Result;
-use console::style;
 use mpl_candy_machine_core::{
     constants::{HIDDEN_SECTION, NULL_STRING},
     AccountVersion,
@@ -13,7 +12,15 @@ use tabled::{
     settings::{object::Segment, Alignment, Modify, Style},
 };
 
-use crate::{cache::load_cache, candy_machine::*, common::*, utils::*};
+use crate::{
+    cache::{cluster_name, load_cache},
+    candy_machine::*,
+    common::*,
+    output::style,
+    show::{process_history, HistoryArgs, HistorySince},
+    tree::{process_tree_show, TreeShowArgs},
+    utils::*,
+};
 
 pub struct ShowArgs {
     pub keypair: Option<String>,
@@ -21,15 +28,19 @@ pub struct ShowArgs {
     pub cache: String,
     pub candy_machine: Option<String>,
     pub unminted: bool,
+    pub history: bool,
+    pub limit: Option<usize>,
+    pub since: Option<String>,
+    pub output: Option<String>,
 }
 
 // number of indices per line
 const PER_LINE: usize = 10;
 
 pub fn process_show(args: ShowArgs) -> Result<()> {
-    println!(
+    report!(
         "{} {}Looking up candy machine",
-        if args.unminted {
+        if args.unminted || args.history {
             style("[1/2]").bold().dim()
         } else {
             style("[1/1]").bold().dim()
@@ -42,11 +53,23 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
 
     // the candy machine id specified takes precedence over the one from the cache
 
-    let candy_machine_id = if let Some(candy_machine) = args.candy_machine {
-        candy_machine
+    let (candy_machine_id, provenance) = if let Some(candy_machine) = args.candy_machine {
+        (candy_machine, None)
     } else {
         let cache = load_cache(&args.cache, false)?;
-        cache.program.candy_machine
+
+        // compressed deployments have a merkle tree instead of a candy machine
+        if cache.program.candy_machine.is_empty() && !cache.program.merkle_tree.is_empty() {
+            pb.finish_and_clear();
+            return process_tree_show(TreeShowArgs {
+                keypair: args.keypair,
+                rpc_url: args.rpc_url,
+                cache: args.cache,
+                merkle_tree: Some(cache.program.merkle_tree),
+            });
+        }
+
+        (cache.program.candy_machine.clone(), Some(cache.program))
     };
 
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
@@ -67,7 +90,56 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
 
     pb.finish_and_clear();
 
-    println!(
+    if args.history {
+        // the since cutoff is validated before retrieving any transaction
+        let since = args
+            .since
+            .as_deref()
+            .map(HistorySince::from_str)
+            .transpose()?;
+
+        // the mint authority is the candy guard when the candy machine is wrapped
+        let candy_guard = program
+            .rpc()
+            .get_account(&cndy_state.mint_authority)
+            .ok()
+            .filter(|account| account.owner == mpl_candy_guard::ID)
+            .map(|_| cndy_state.mint_authority);
+
+        report!(
+            "\n{}{} {}",
+            CANDY_EMOJI,
+            style("Candy machine ID:").dim(),
+            &candy_machine_id
+        );
+        if let Some(candy_guard) = &candy_guard {
+            report!(
+                "{}{} {}",
+                GUARD_EMOJI,
+                style("Candy guard ID:").dim(),
+                candy_guard
+            );
+        }
+
+        report!(
+            "\n{} {}Retrieving transaction history",
+            style("[2/2]").bold().dim(),
+            LOOKING_GLASS_EMOJI
+        );
+
+        return process_history(
+            &program.rpc(),
+            HistoryArgs {
+                candy_machine: &candy_machine_id,
+                candy_guard: candy_guard.as_ref(),
+                limit: args.limit,
+                since,
+                output: args.output,
+            },
+        );
+    }
+
+    report!(
         "\n{}{} {}",
         CANDY_EMOJI,
         style("Candy machine ID:").dim(),
@@ -76,7 +148,7 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
 
     // candy machine state and data
 
-    println!(" {}", style(":").dim());
+    report!(" {}", style(":").dim());
     print_with_style("", "authority", cndy_state.authority.to_string());
     print_with_style("", "mint authority", cndy_state.mint_authority.to_string());
     print_with_style(
@@ -192,10 +264,31 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
         print_with_style("", "config line settings", "none");
     }
 
+    // provenance recorded in the cache
+
+    if let Some(provenance) = provenance.filter(|p| p.genesis_hash.is_some()) {
+        print_with_style("", "provenance", "");
+        if let Some(genesis_hash) = &provenance.genesis_hash {
+            print_with_style("    ", "cluster", cluster_name(genesis_hash));
+        }
+        if let Some(creation_slot) = provenance.creation_slot {
+            print_with_style("    ", "creation slot", creation_slot.to_string());
+        }
+        if let Some(creation_signature) = &provenance.creation_signature {
+            print_with_style("    ", "creation signature", creation_signature);
+        }
+        if let Some(candy_machine_program) = &provenance.candy_machine_program {
+            print_with_style("    ", "candy machine program", candy_machine_program);
+        }
+        if let Some(candy_guard_program) = &provenance.candy_guard_program {
+            print_with_style("    ", "candy guard program", candy_guard_program);
+        }
+    }
+
     // unminted indices
 
     if args.unminted {
-        println!(
+        report!(
             "\n{} {}Retrieving unminted indices",
             style("[2/2]").bold().dim(),
             LOOKING_GLASS_EMOJI
@@ -227,7 +320,7 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
         }
 
         if indices.is_empty() {
-            println!(
+            report!(
                 "\n{}{}",
                 PAPER_EMOJI,
                 style("All items of the candy machine have been minted.").dim()
@@ -238,7 +331,7 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
             // logs all indices
             info!("unminted list: {:?}", indices);
 
-            println!("\n{}{}\n", PAPER_EMOJI, style("Unminted list:").dim());
+            report!("\n{}{}\n", PAPER_EMOJI, style("Unminted list:").dim());
 
             let mut remaining = indices.as_slice();
             let mut builder = Builder::default();
@@ -253,9 +346,9 @@ pub fn process_show(args: ShowArgs) -> Result<()> {
             table
                 .with(Style::blank())
                 .with(Modify::new(Segment::all()).with(Alignment::right()));
-            println!("{}", table);
+            report!("{}", table);
 
-            println!(
+            report!(
                 "\n{}",
                 style(format!("{} total unminted.", indices.len())).dim()
             );
@@ -269,7 +362,7 @@ pub fn print_with_style<S>(indent: &str, key: &str, value: S)
 where
     S: core::fmt::Display,
 {
-    println!(
+    report!(
         " {} {}",
         style(format!("{}:.. {}:", indent, key)).dim(),
         value
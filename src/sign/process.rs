@@ -13,17 +13,19 @@ pub use anchor_client::{
     Client, Program,
 };
 use anyhow::Error;
-use console::style;
 use mpl_token_metadata::{instruction::sign_metadata, ID as METAPLEX_PROGRAM_ID};
 use retry::{delay::Exponential, retry};
 use solana_client::rpc_client::RpcClient;
 use tokio::sync::Semaphore;
 
 use crate::{
+    account_fetcher::{AccountFetcher, AccountSource},
     cache::load_cache,
+    cancel,
     candy_machine::CANDY_MACHINE_ID,
     common::*,
     config::{Cluster, SugarConfig},
+    output::style,
     pdas::{find_candy_machine_creator_pda, find_metadata_pda},
     setup::{get_rpc_url, setup_client, sugar_setup},
     utils::*,
@@ -35,11 +37,13 @@ pub struct SignArgs {
     pub cache: String,
     pub rpc_url: Option<String>,
     pub mint: Option<String>,
+    pub dry_run: bool,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub async fn process_sign(args: SignArgs) -> Result<()> {
     // (1) Setting up connection
-    println!(
+    report!(
         "{} {}Initializing connection",
         if args.mint.is_some() {
             style("[1/2]").bold().dim()
@@ -60,7 +64,7 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
     pb.finish_with_message("Connected");
 
     if let Some(mint_id) = args.mint {
-        println!(
+        report!(
             "\n{} {}Signing one NFT",
             style("[2/2]").bold().dim(),
             SIGNING_EMOJI,
@@ -81,7 +85,7 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
 
         pb.finish();
     } else {
-        println!(
+        report!(
             "\n{} {}Fetching mint ids",
             style("[2/3]").bold().dim(),
             LOOKING_GLASS_EMOJI,
@@ -92,7 +96,8 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
         let candy_machine_id = match args.candy_machine_id {
             Some(candy_machine_id) => candy_machine_id,
             None => {
-                let cache = load_cache(&args.cache, false)?;
+                let mut cache = load_cache(&args.cache, false)?;
+                cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
                 cache.program.candy_machine
             }
         };
@@ -101,7 +106,7 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
             .expect("Failed to parse pubkey from candy machine id.");
 
         let solana_cluster: Cluster = get_cluster(program.rpc())?;
-        let rpc_url = get_rpc_url(args.rpc_url);
+        let rpc_url = get_rpc_url(args.rpc_url)?;
 
         let solana_cluster = if rpc_url.ends_with("8899") {
             Cluster::Localnet
@@ -130,18 +135,50 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
             )));
         } else {
             pb.finish_with_message(format!("Found {:?} accounts", account_keys.len() as u64));
-            println!(
-                "\n{} {}Signing mint accounts",
+        }
+
+        if args.dry_run {
+            report!(
+                "\n{} {}Dry run: no transactions sent",
                 style("[3/3]").bold().dim(),
                 SIGNING_EMOJI
             );
+
+            let fetcher = AccountFetcher::new(RpcClient::new(&rpc_url));
+            let plan = plan_signatures(&fetcher, &account_keys, &sugar_config.keypair.pubkey())?;
+
+            report!(
+                "  {} NFT(s) to sign, {} already signed, {} without the signer as creator",
+                plan.unsigned.len(),
+                plan.signed.len(),
+                plan.not_creator.len()
+            );
+            for metadata in &plan.not_creator {
+                report!("  {}", metadata);
+            }
+            fetcher.print_summary();
+
+            return Ok(());
         }
 
-        let pb = progress_bar_with_style(account_keys.len() as u64);
+        report!(
+            "\n{} {}Signing mint accounts",
+            style("[3/3]").bold().dim(),
+            SIGNING_EMOJI
+        );
+
+        let pending = account_keys.len();
+        let pb = progress_bar_with_style(pending as u64);
 
         let semaphore = Arc::new(Semaphore::new(100));
         let mut join_handles = Vec::new();
+        let _cancellable = cancel::cancellable();
+
         for account in account_keys {
+            if cancel::is_cancelled() {
+                break;
+            }
+
             let permit = Arc::clone(&semaphore).acquire_owned().await.unwrap();
             let config = sugar_config.clone();
             let pb = pb.clone();
@@ -153,11 +190,22 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
             }));
         }
 
+        let started = join_handles.len();
+
         for handle in join_handles {
             handle.await.map_err(|err| errors.push(err)).ok();
         }
 
-        if !errors.is_empty() {
+        if cancel::is_cancelled() {
+            pb.abandon_with_message(format!(
+                "{} {}/{} {}",
+                style("Processed").yellow().bold(),
+                started,
+                pending,
+                style("NFTs").yellow().bold()
+            ));
+            return Err(cancel::cancelled().into());
+        } else if !errors.is_empty() {
             pb.abandon_with_message(format!("{}", style("Signing command failed ").red().bold()));
             return Err(anyhow!("Not all NFTs were signed.".to_string()));
         } else {
@@ -171,6 +219,45 @@ pub async fn process_sign(args: SignArgs) -> Result<()> {
     Ok(())
 }
 
+/// Signing status of the metadata accounts, from the creators of their metadata.
+#[derive(Debug, Default)]
+pub struct SignPlan {
+    /// Metadata with the signer as an unverified creator.
+    pub unsigned: Vec<Pubkey>,
+    /// Metadata already signed by the signer.
+    pub signed: Vec<Pubkey>,
+    /// Metadata without the signer as creator (signing them fails).
+    pub not_creator: Vec<Pubkey>,
+}
+
+/// Loads the metadata accounts (in batches of 100 accounts) and groups them by signing
+/// status of `signer`.
+pub fn plan_signatures<S: AccountSource>(
+    fetcher: &AccountFetcher<S>,
+    metadata_pubkeys: &[Pubkey],
+    signer: &Pubkey,
+) -> Result<SignPlan> {
+    let metadata = get_metadata_accounts(fetcher, metadata_pubkeys)?;
+    let mut plan = SignPlan::default();
+
+    for (pubkey, metadata) in metadata_pubkeys.iter().zip(metadata) {
+        let creator = metadata
+            .data
+            .creators
+            .unwrap_or_default()
+            .into_iter()
+            .find(|creator| creator.address == *signer);
+
+        match creator {
+            Some(creator) if creator.verified => plan.signed.push(*pubkey),
+            Some(_) => plan.unsigned.push(*pubkey),
+            None => plan.not_creator.push(*pubkey),
+        }
+    }
+
+    Ok(plan)
+}
+
 async fn sign(config: Arc<SugarConfig>, metadata: Pubkey) -> Result<(), Error> {
     let client = setup_client(&config)?;
     let program = client.program(CANDY_MACHINE_ID);
@@ -193,3 +280,89 @@ async fn sign(config: Arc<SugarConfig>, metadata: Pubkey) -> Result<(), Error> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use borsh::BorshSerialize;
+    use mpl_token_metadata::state::{Creator, Data, Metadata};
+
+    use super::*;
+
+    /// Mock transport serving the metadata accounts, counting the requests.
+    struct MockSource {
+        accounts: HashMap<Pubkey, Account>,
+        requests: AtomicUsize,
+    }
+
+    impl AccountSource for MockSource {
+        fn get_multiple_accounts(&self, pubkeys: &[Pubkey]) -> Result<Vec<Option<Account>>> {
+            self.requests.fetch_add(1, Ordering::Relaxed);
+            Ok(pubkeys
+                .iter()
+                .map(|pubkey| self.accounts.get(pubkey).cloned())
+                .collect())
+        }
+    }
+
+    #[test]
+    fn dry_run_batches_metadata_lookups() {
+        let signer = Pubkey::new_unique();
+        let items = 10_000;
+        let mut accounts = HashMap::new();
+        let mut metadata_pubkeys = Vec::new();
+
+        // every 4th NFT is already signed and every 10th is not from the signer
+        for i in 0..items {
+            let creator = Creator {
+                address: if i % 10 == 9 {
+                    Pubkey::new_unique()
+                } else {
+                    signer
+                },
+                verified: i % 4 == 0,
+                share: 100,
+            };
+            let pubkey = Pubkey::new_unique();
+            let data = Metadata {
+                data: Data {
+                    creators: Some(vec![creator]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .try_to_vec()
+            .unwrap();
+
+            accounts.insert(
+                pubkey,
+                Account {
+                    data,
+                    ..Account::default()
+                },
+            );
+            metadata_pubkeys.push(pubkey);
+        }
+
+        let source = Arc::new(MockSource {
+            accounts,
+            requests: AtomicUsize::new(0),
+        });
+        let fetcher = AccountFetcher::new(source.clone());
+        let plan = plan_signatures(&fetcher, &metadata_pubkeys, &signer).unwrap();
+
+        assert_eq!(plan.unsigned.len(), 6500);
+        assert_eq!(plan.signed.len(), 2500);
+        assert_eq!(plan.not_creator.len(), 1000);
+
+        // one request per 100 NFTs instead of one per NFT
+        let requests = source.requests.load(Ordering::Relaxed);
+        assert_eq!(requests, 100);
+        assert!(requests * 10 <= items, "{} requests", requests);
+        assert_eq!(fetcher.requests(), 100);
+    }
+}
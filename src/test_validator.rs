@@ -0,0 +1,241 @@
+//! Harness of the tests against a local validator: a `solana-test-validator` with the Candy
+//! Machine, Candy Guard and Token Metadata programs loaded from `tests/programs` (or the folder
+//! set in `SUGAR_TEST_PROGRAMS`), and a funded keypair with the files of a collection. Used by
+//! `tests/integration.rs` and the example of the [`api`](crate::api) module.
+
+use std::{
+    env, fs,
+    net::TcpListener,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread::sleep,
+    time::{Duration, Instant},
+};
+
+use anchor_client::solana_sdk::{
+    commitment_config::CommitmentConfig,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{write_keypair_file, Keypair, Signer},
+};
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    api::{self, SugarOptions},
+    config::{guard_data::CandyGuardData as GuardConfig, ConfigData, UploadMethod},
+};
+
+/// Number of items of the fixture collection.
+pub const ITEMS: u64 = 5;
+
+/// Programs loaded in the validator: (program id, file name).
+const PROGRAMS: [(&str, &str); 3] = [
+    (
+        "CndyV3LdqHUfDLmE5naZjVN8rBZz4tqhdefbAnjHG3JR",
+        "mpl_candy_machine_core.so",
+    ),
+    (
+        "Guard1JwRhJkVH6XZhzoYxeBVQe872VH6QggF4BWmS9g",
+        "mpl_candy_guard.so",
+    ),
+    (
+        "metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s",
+        "mpl_token_metadata.so",
+    ),
+];
+
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Local validator running for the duration of a test.
+pub struct TestValidator {
+    process: Child,
+    pub rpc_url: String,
+    ledger: PathBuf,
+}
+
+impl TestValidator {
+    pub fn start(name: &str) -> Result<Self> {
+        let programs = env::var("SUGAR_TEST_PROGRAMS")
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs"));
+
+        let rpc_port = free_port()?;
+        let ledger = env::temp_dir().join(format!("sugar-ledger-{}-{}", name, std::process::id()));
+
+        let mut command = Command::new("solana-test-validator");
+        command
+            .arg("--reset")
+            .arg("--quiet")
+            .arg("--ledger")
+            .arg(&ledger)
+            .arg("--rpc-port")
+            .arg(rpc_port.to_string())
+            .arg("--faucet-port")
+            .arg(free_port()?.to_string());
+
+        for (id, file) in PROGRAMS {
+            let path = programs.join(file);
+            if !path.is_file() {
+                return Err(anyhow!(
+                    "Missing program '{}', run script/fetch-test-programs.sh",
+                    path.display()
+                ));
+            }
+            command.arg("--bpf-program").arg(id).arg(path);
+        }
+
+        let process = command
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| anyhow!("Failed to start solana-test-validator: {e}"))?;
+
+        let validator = TestValidator {
+            process,
+            rpc_url: format!("http://127.0.0.1:{rpc_port}"),
+            ledger,
+        };
+
+        let rpc = validator.rpc();
+        let start = Instant::now();
+
+        while rpc.get_health().is_err() {
+            if start.elapsed() > STARTUP_TIMEOUT {
+                return Err(anyhow!("Validator did not start in {:?}", STARTUP_TIMEOUT));
+            }
+            sleep(Duration::from_millis(500));
+        }
+
+        Ok(validator)
+    }
+
+    pub fn rpc(&self) -> RpcClient {
+        RpcClient::new_with_commitment(self.rpc_url.clone(), CommitmentConfig::confirmed())
+    }
+
+    pub fn airdrop(&self, address: &Pubkey, sol: u64) -> Result<()> {
+        let rpc = self.rpc();
+        let signature = rpc.request_airdrop(address, sol * LAMPORTS_PER_SOL)?;
+
+        while !rpc.confirm_transaction(&signature)? {
+            sleep(Duration::from_millis(200));
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for TestValidator {
+    fn drop(&mut self) {
+        let _ = self.process.kill();
+        let _ = self.process.wait();
+        let _ = fs::remove_dir_all(&self.ledger);
+    }
+}
+
+fn free_port() -> Result<u16> {
+    Ok(TcpListener::bind("127.0.0.1:0")?.local_addr()?.port())
+}
+
+/// Files of a test run: keypair, assets, config and cache.
+pub struct Fixture {
+    pub dir: PathBuf,
+    pub keypair: Keypair,
+    pub options: SugarOptions,
+}
+
+impl Fixture {
+    pub fn new(name: &str, validator: &TestValidator) -> Result<Self> {
+        let dir = env::temp_dir().join(format!("sugar-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("assets"))?;
+
+        let keypair = Keypair::new();
+        let keypair_path = dir.join("keypair.json");
+        write_keypair_file(&keypair, &keypair_path).map_err(|e| anyhow!("{e}"))?;
+        validator.airdrop(&keypair.pubkey(), 100)?;
+
+        create_assets(&dir.join("assets"))?;
+
+        let path = |file: &str| dir.join(file).to_string_lossy().to_string();
+
+        let options = SugarOptions {
+            keypair: Some(path("keypair.json")),
+            rpc_url: Some(validator.rpc_url.clone()),
+            cache: path("cache.json"),
+            config: path("config.json"),
+            priority_fee: 0,
+            ..SugarOptions::default()
+        };
+
+        Ok(Fixture {
+            dir,
+            keypair,
+            options,
+        })
+    }
+
+    pub fn assets_dir(&self) -> String {
+        self.dir.join("assets").to_string_lossy().to_string()
+    }
+
+    /// Creates the config file using the mock storage and the specified guards.
+    pub fn create_config(&self, guards: Option<GuardConfig>) -> Result<ConfigData> {
+        let mut config_data = api::create_config(&self.options, &self.assets_dir())?;
+        assert_eq!(config_data.number, ITEMS);
+
+        config_data.upload_method = UploadMethod::Mock;
+        config_data.guards = guards;
+        fs::write(
+            &self.options.config,
+            serde_json::to_string_pretty(&config_data)?,
+        )?;
+
+        Ok(config_data)
+    }
+}
+
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Generates the fixture collection (images from `tests/assets`).
+fn create_assets(assets: &Path) -> Result<()> {
+    let images = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets");
+
+    let metadata = |name: &str, image: &str| {
+        json!({
+            "name": name,
+            "symbol": "TEST",
+            "description": "Sugar integration test",
+            "seller_fee_basis_points": 500,
+            "image": image,
+            "attributes": [],
+            "properties": {
+                "files": [{ "uri": image, "type": "image/png" }],
+                "category": "image"
+            }
+        })
+    };
+
+    for index in 0..ITEMS {
+        let image = format!("{index}.png");
+        fs::copy(images.join(&image), assets.join(&image))?;
+        fs::write(
+            assets.join(format!("{index}.json")),
+            metadata(&format!("Test #{index}"), &image).to_string(),
+        )?;
+    }
+
+    fs::copy(images.join("collection.png"), assets.join("collection.png"))?;
+    fs::write(
+        assets.join("collection.json"),
+        metadata("Test Collection", "collection.png").to_string(),
+    )?;
+
+    Ok(())
+}
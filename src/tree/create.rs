@@ -0,0 +1,137 @@
+use anchor_client::solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, native_token::LAMPORTS_PER_SOL,
+};
+
+use crate::{
+    bubblegum::{
+        create_tree_instruction, get_tree_account_size, tree_size_for_capacity,
+        ACCOUNT_COMPRESSION_ID, BUBBLEGUM_ID, VALID_TREE_SIZES,
+    },
+    cache::load_cache,
+    common::*,
+    output::style,
+    utils::*,
+};
+
+pub struct TreeCreateArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub capacity: u64,
+    pub max_buffer_size: Option<u32>,
+    pub canopy_depth: u32,
+    pub collection_mint: Option<String>,
+    pub priority_fee: u64,
+}
+
+pub fn process_tree_create(args: TreeCreateArgs) -> Result<()> {
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(BUBBLEGUM_ID);
+    let payer = program.payer();
+
+    let mut cache = load_cache(&args.cache, true)?;
+
+    if !cache.program.merkle_tree.is_empty() {
+        return Err(anyhow!(
+            "Cache already has a merkle tree: {}",
+            cache.program.merkle_tree
+        ));
+    }
+
+    if let Some(collection_mint) = args.collection_mint {
+        Pubkey::from_str(&collection_mint)
+            .map_err(|_| anyhow!("Failed to parse collection mint: {}", collection_mint))?;
+        cache.program.collection_mint = collection_mint;
+    }
+
+    let (max_depth, max_buffer_size) = match args.max_buffer_size {
+        Some(max_buffer_size) => {
+            let (max_depth, _) = tree_size_for_capacity(args.capacity)?;
+            if !VALID_TREE_SIZES.contains(&(max_depth, max_buffer_size)) {
+                return Err(anyhow!(
+                    "Invalid max buffer size {} for a tree of depth {}.",
+                    max_buffer_size,
+                    max_depth
+                ));
+            }
+            (max_depth, max_buffer_size)
+        }
+        None => tree_size_for_capacity(args.capacity)?,
+    };
+
+    if args.canopy_depth >= max_depth {
+        return Err(anyhow!(
+            "Canopy depth must be lower than the tree depth ({}).",
+            max_depth
+        ));
+    }
+
+    let tree_size = get_tree_account_size(max_depth, max_buffer_size, args.canopy_depth);
+
+    report!(
+        "{} {}Creating merkle tree",
+        style("[1/1]").bold().dim(),
+        TREE_EMOJI
+    );
+    report!(
+        "{} {} (depth {}, buffer {}, canopy {})",
+        style("Tree capacity:").bold(),
+        2u64.pow(max_depth),
+        max_depth,
+        max_buffer_size,
+        args.canopy_depth
+    );
+
+    let lamports = program
+        .rpc()
+        .get_minimum_balance_for_rent_exemption(tree_size)?;
+    let balance = program.rpc().get_account(&payer)?.lamports;
+
+    if lamports > balance {
+        return Err(anyhow!(
+            "Payer balance ({:.3} SOL) is too low to create the tree ({:.3} SOL).",
+            balance as f64 / LAMPORTS_PER_SOL as f64,
+            lamports as f64 / LAMPORTS_PER_SOL as f64
+        ));
+    }
+
+    let pb = spinner_with_style();
+    pb.set_message("Sending create tree transaction...");
+
+    let merkle_tree = Keypair::new();
+
+    let signature = program
+        .request()
+        .instruction(ComputeBudgetInstruction::set_compute_unit_price(
+            args.priority_fee,
+        ))
+        .instruction(system_instruction::create_account(
+            &payer,
+            &merkle_tree.pubkey(),
+            lamports,
+            tree_size as u64,
+            &ACCOUNT_COMPRESSION_ID,
+        ))
+        .instruction(create_tree_instruction(
+            &merkle_tree.pubkey(),
+            &payer,
+            max_depth,
+            max_buffer_size,
+            false,
+        )?)
+        .signer(&merkle_tree)
+        .send()?;
+
+    info!("Merkle tree created: {}", signature);
+
+    cache.program.merkle_tree = merkle_tree.pubkey().to_string();
+    cache.sync_file()?;
+
+    pb.finish_and_clear();
+
+    report!("{} {}", style("Merkle tree:").bold(), merkle_tree.pubkey());
+    report!("{} {}", style("Signature:").bold(), signature);
+
+    Ok(())
+}
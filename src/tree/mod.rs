@@ -0,0 +1,5 @@
+pub mod create;
+pub mod show;
+
+pub use create::*;
+pub use show::*;
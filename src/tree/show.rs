@@ -0,0 +1,87 @@
+use solana_client::rpc_client::RpcClient;
+
+use crate::{
+    bubblegum::{find_tree_config_pda, TreeConfig, BUBBLEGUM_ID},
+    cache::load_cache,
+    common::*,
+    output::style,
+    show::print_with_style,
+    utils::*,
+};
+
+pub struct TreeShowArgs {
+    pub keypair: Option<String>,
+    pub rpc_url: Option<String>,
+    pub cache: String,
+    pub merkle_tree: Option<String>,
+}
+
+pub fn process_tree_show(args: TreeShowArgs) -> Result<()> {
+    report!(
+        "{} {}Looking up merkle tree",
+        style("[1/1]").bold().dim(),
+        LOOKING_GLASS_EMOJI
+    );
+
+    // the merkle tree specified takes precedence over the one from the cache
+
+    let merkle_tree = match args.merkle_tree {
+        Some(merkle_tree) => merkle_tree,
+        None => load_cache(&args.cache, false)?.program.merkle_tree,
+    };
+    let merkle_tree = parse_merkle_tree(&merkle_tree)?;
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(BUBBLEGUM_ID);
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let tree_config = get_tree_config(&program.rpc(), &merkle_tree)?;
+
+    pb.finish_and_clear();
+
+    print_tree_info(&merkle_tree, &tree_config);
+
+    Ok(())
+}
+
+pub fn parse_merkle_tree(merkle_tree: &str) -> Result<Pubkey> {
+    if merkle_tree.is_empty() {
+        return Err(anyhow!(
+            "Missing merkle tree, run 'sugar tree create' to create one."
+        ));
+    }
+
+    Pubkey::from_str(merkle_tree)
+        .map_err(|_| anyhow!("Failed to parse merkle tree id: {}", merkle_tree))
+}
+
+pub fn get_tree_config(rpc: &RpcClient, merkle_tree: &Pubkey) -> Result<TreeConfig> {
+    let data = rpc
+        .get_account_data(&find_tree_config_pda(merkle_tree))
+        .map_err(|_| anyhow!("Tree config account not found for tree {}", merkle_tree))?;
+
+    TreeConfig::from_account_data(&data)
+}
+
+pub fn print_tree_info(merkle_tree: &Pubkey, tree_config: &TreeConfig) {
+    report!(
+        "\n{}{} {}",
+        TREE_EMOJI,
+        style("Merkle tree:").dim(),
+        merkle_tree
+    );
+    report!(" {}", style(":").dim());
+    print_with_style("", "capacity", tree_config.total_mint_capacity.to_string());
+    print_with_style("", "minted", tree_config.num_minted.to_string());
+    print_with_style(
+        "",
+        "utilization",
+        format!(
+            "{:.2}%",
+            tree_config.num_minted as f64 / tree_config.total_mint_capacity.max(1) as f64 * 100.0
+        ),
+    );
+}
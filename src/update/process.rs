@@ -1,17 +1,23 @@
 use std::str::FromStr;
 
-use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
+use anchor_client::solana_sdk::{
+    compute_budget::ComputeBudgetInstruction, native_token::LAMPORTS_PER_SOL, pubkey::Pubkey,
+};
 use anyhow::Result;
-use console::style;
 use mpl_candy_machine_core::{
     accounts as nft_accounts, instruction as nft_instruction, CandyMachineData,
 };
+use solana_client::rpc_client::RpcClient;
 
 use crate::{
     cache::load_cache,
     candy_machine::{get_candy_machine_state, CANDY_MACHINE_ID},
     common::*,
+    compute_budget::{send_transaction, ComputeBudget},
     config::{data::ConfigData, parser::get_config_data},
+    estimate::get_rent_exemption,
+    offline::{unsigned_setup, CacheUpdate, UnsignedTxArgs, UnsignedTxWriter},
+    output::style,
     utils::{assert_correct_authority, spinner_with_style},
 };
 
@@ -23,18 +29,34 @@ pub struct UpdateArgs {
     pub config: String,
     pub candy_machine: Option<String>,
     pub priority_fee: u64,
+    pub unsigned_tx: Option<UnsignedTxArgs>,
+    pub compute_budget: ComputeBudget,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_update(args: UpdateArgs) -> Result<()> {
-    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let (sugar_config, authority) =
+        unsigned_setup(args.keypair, args.rpc_url, args.unsigned_tx.as_ref())?;
     let client = setup_client(&sugar_config)?;
+    let mut writer = match &args.unsigned_tx {
+        Some(unsigned_tx) => Some(UnsignedTxWriter::new(
+            "config update",
+            unsigned_tx,
+            authority,
+        )?),
+        None => None,
+    };
     let config_data = get_config_data(&args.config)?;
 
     // the candy machine id specified takes precedence over the one from the cache
     let candy_machine_id = match args.candy_machine {
         Some(candy_machine_id) => candy_machine_id,
         None => {
-            let cache = load_cache(&args.cache, false)?;
+            let mut cache = load_cache(&args.cache, false)?;
+            cache.check_cluster(
+                &client.program(CANDY_MACHINE_ID).rpc(),
+                args.allow_cluster_mismatch,
+            )?;
             cache.program.candy_machine
         }
     };
@@ -48,12 +70,12 @@ pub fn process_update(args: UpdateArgs) -> Result<()> {
         }
     };
 
-    println!(
+    report!(
         "{} {}Loading candy machine",
         style("[1/2]").bold().dim(),
         LOOKING_GLASS_EMOJI
     );
-    println!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
+    report!("{} {}", style("Candy machine ID:").bold(), candy_machine_id);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -63,12 +85,17 @@ pub fn process_update(args: UpdateArgs) -> Result<()> {
 
     pb.finish_with_message("Done");
 
-    assert_correct_authority(
-        &sugar_config.keypair.pubkey(),
-        &candy_machine_state.authority,
-    )?;
+    assert_correct_authority(&authority, &candy_machine_state.authority)?;
+
+    if candy_machine_data.items_available != candy_machine_state.data.items_available {
+        check_items_available_change(
+            Some(&client.program(CANDY_MACHINE_ID).rpc()),
+            &candy_machine_state.data,
+            &candy_machine_data,
+        )?;
+    }
 
-    println!(
+    report!(
         "\n{} {}Updating configuration",
         style("[2/2]").bold().dim(),
         COMPUTER_EMOJI
@@ -77,58 +104,159 @@ pub fn process_update(args: UpdateArgs) -> Result<()> {
     let program = client.program(CANDY_MACHINE_ID);
     let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
-    let builder = program
+    let instructions = program
         .request()
         .instruction(priority_fee)
         .accounts(nft_accounts::Update {
             candy_machine: candy_pubkey,
-            authority: program.payer(),
+            authority,
         })
         .args(nft_instruction::Update {
             data: candy_machine_data,
-        });
+        })
+        .instructions()?;
 
-    let pb = spinner_with_style();
-    pb.set_message("Sending update transaction...");
+    if let Some(writer) = writer.as_mut() {
+        writer.add(
+            &program.rpc(),
+            "update-candy-machine",
+            "Update the candy machine",
+            &instructions,
+            &[],
+            CacheUpdate::None,
+        )?;
+    } else {
+        let pb = spinner_with_style();
+        pb.set_message("Sending update transaction...");
 
-    let update_signature = builder.send()?;
+        let update_signature = send_transaction(
+            &program.rpc(),
+            &sugar_config.keypair,
+            &instructions,
+            &[],
+            &args.compute_budget,
+        )?;
 
-    pb.finish_with_message(format!(
-        "{} {}",
-        style("Update signature:").bold(),
-        update_signature
-    ));
+        pb.finish_with_message(format!(
+            "{} {}",
+            style("Update signature:").bold(),
+            update_signature
+        ));
+    }
 
     if let Some(new_authority) = args.new_authority {
-        let pb = spinner_with_style();
-        pb.set_message("Sending update authority transaction...");
-
         let new_authority_pubkey = Pubkey::from_str(&new_authority)?;
 
         let priority_fee = ComputeBudgetInstruction::set_compute_unit_price(args.priority_fee);
 
-        let builder = program
+        let instructions = program
             .request()
             .instruction(priority_fee)
             .accounts(nft_accounts::SetAuthority {
                 candy_machine: candy_pubkey,
-                authority: program.payer(),
+                authority,
             })
             .args(nft_instruction::SetAuthority {
                 new_authority: new_authority_pubkey,
-            });
+            })
+            .instructions()?;
 
-        let authority_signature = builder.send()?;
-        pb.finish_with_message(format!(
-            "{} {}",
-            style("Authority signature:").bold(),
-            authority_signature
-        ));
+        if let Some(writer) = writer.as_mut() {
+            writer.add(
+                &program.rpc(),
+                "set-authority",
+                "Set the candy machine authority",
+                &instructions,
+                &[],
+                CacheUpdate::None,
+            )?;
+        } else {
+            let pb = spinner_with_style();
+            pb.set_message("Sending update authority transaction...");
+
+            let authority_signature = send_transaction(
+                &program.rpc(),
+                &sugar_config.keypair,
+                &instructions,
+                &[],
+                &args.compute_budget,
+            )?;
+            pb.finish_with_message(format!(
+                "{} {}",
+                style("Authority signature:").bold(),
+                authority_signature
+            ));
+        }
+    }
+
+    if let Some(writer) = writer {
+        writer.finish()?;
     }
 
     Ok(())
 }
 
+/// Checks whether the number of items can be changed from the on-chain value to the
+/// config value.
+///
+/// Machines with hidden settings do not store config lines, so the number of items can be
+/// freely changed. Otherwise the account size depends on the number of items: shrinking
+/// would drop config lines that may already be written, and growing requires the account
+/// to be resized, which Candy Machine v3 cannot do: it has no resize instruction.
+fn check_items_available_change(
+    rpc: Option<&RpcClient>,
+    current: &CandyMachineData,
+    new: &CandyMachineData,
+) -> Result<()> {
+    report!(
+        "\n{}{} {} -> {}",
+        WARNING_EMOJI,
+        style("Number of items changed:").yellow().bold(),
+        current.items_available,
+        new.items_available
+    );
+
+    if current.hidden_settings.is_some() && new.hidden_settings.is_some() {
+        report!("Candy machine uses hidden settings, no account resize is needed.");
+        return Ok(());
+    }
+
+    if new.items_available < current.items_available {
+        return Err(anyhow!(
+            "Reducing the number of items is not supported once a candy machine is created \
+            since config lines may already be written. Set 'number' back to {} in the config file.",
+            current.items_available
+        ));
+    }
+
+    let current_size = current.get_space_for_candy()?;
+    let new_size = new.get_space_for_candy()?;
+    let additional_rent =
+        get_rent_exemption(rpc, new_size).saturating_sub(get_rent_exemption(rpc, current_size));
+
+    report!(
+        "{} {} -> {} bytes (◎ {:.5} additional rent)",
+        style("Account size:").bold(),
+        current_size,
+        new_size,
+        additional_rent as f64 / LAMPORTS_PER_SOL as f64
+    );
+
+    // Candy Machine v3 does not have a resize instruction, so the account cannot grow in
+    // place; the cache is left untouched
+    Err(anyhow!(
+        "Growing the candy machine requires resizing the account from {} to {} bytes, which is \
+        impossible: Candy Machine v3 has no resize instruction. Set 'number' back to {} in the \
+        config file to keep this candy machine, or migrate to a new one: remove 'candyMachine' \
+        from the cache file, run 'sugar deploy' to create a candy machine with {} items, then \
+        close the current one with 'sugar withdraw'.",
+        current_size,
+        new_size,
+        current.items_available,
+        new.items_available
+    ))
+}
+
 fn create_candy_machine_data(
     config: &ConfigData,
     candy_machine: &CandyMachineData,
@@ -154,3 +282,40 @@ fn create_candy_machine_data(
     };
     Ok(data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::estimate::estimate_candy_machine_data;
+
+    #[test]
+    fn hidden_settings_allow_items_available_change() {
+        let current = estimate_candy_machine_data(100, 32, 200, true);
+        let new = estimate_candy_machine_data(50, 32, 200, true);
+
+        assert!(check_items_available_change(None, &current, &new).is_ok());
+    }
+
+    #[test]
+    fn shrinking_items_available_fails() {
+        let current = estimate_candy_machine_data(100, 32, 200, false);
+        let new = estimate_candy_machine_data(50, 32, 200, false);
+
+        let err = check_items_available_change(None, &current, &new).unwrap_err();
+        assert!(err.to_string().contains("Reducing the number of items"));
+        assert!(err.to_string().contains("back to 100"));
+    }
+
+    #[test]
+    fn growing_items_available_fails_with_new_size() {
+        let current = estimate_candy_machine_data(100, 32, 200, false);
+        let new = estimate_candy_machine_data(200, 32, 200, false);
+        let new_size = new.get_space_for_candy().unwrap();
+
+        let err = check_items_available_change(None, &current, &new).unwrap_err();
+        assert!(err.to_string().contains("Growing the candy machine"));
+        assert!(err.to_string().contains(&format!("to {} bytes", new_size)));
+        assert!(err.to_string().contains("back to 100"));
+        assert!(err.to_string().contains("with 200 items"));
+    }
+}
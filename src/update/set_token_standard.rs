@@ -2,7 +2,6 @@ use std::str::FromStr;
 
 use anchor_client::solana_sdk::{compute_budget::ComputeBudgetInstruction, pubkey::Pubkey};
 use anyhow::Result;
-use console::style;
 use mpl_candy_machine_core::{accounts::SetTokenStandard, AccountVersion};
 use mpl_token_metadata::{
     instruction::MetadataDelegateRole,
@@ -14,6 +13,7 @@ use crate::{
     candy_machine::{get_candy_machine_state, CANDY_MACHINE_ID},
     common::*,
     config::TokenStandard,
+    output::style,
     pdas::{find_candy_machine_creator_pda, find_metadata_pda, get_metadata_pda},
     utils::*,
 };
@@ -26,6 +26,7 @@ pub struct SetTokenStandardArgs {
     pub candy_machine: Option<String>,
     pub rule_set: Option<String>,
     pub priority_fee: u64,
+    pub allow_cluster_mismatch: bool,
 }
 
 pub fn process_set_token_stardard(args: SetTokenStandardArgs) -> Result<()> {
@@ -36,14 +37,19 @@ pub fn process_set_token_stardard(args: SetTokenStandardArgs) -> Result<()> {
         ));
     }
 
-    println!("[1/2] {}Loading candy machine", LOOKING_GLASS_EMOJI);
+    report!("[1/2] {}Loading candy machine", LOOKING_GLASS_EMOJI);
+
+    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
+    let client = setup_client(&sugar_config)?;
+    let program = client.program(CANDY_MACHINE_ID);
 
     // the candy machine id specified takes precedence over the one from the cache
 
     let candy_machine_id = if let Some(candy_machine) = args.candy_machine {
         candy_machine
     } else {
-        let cache = load_cache(&args.cache, false)?;
+        let mut cache = load_cache(&args.cache, false)?;
+        cache.check_cluster(&program.rpc(), args.allow_cluster_mismatch)?;
         cache.program.candy_machine
     };
 
@@ -60,10 +66,6 @@ pub fn process_set_token_stardard(args: SetTokenStandardArgs) -> Result<()> {
         }
     };
 
-    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
-    let client = setup_client(&sugar_config)?;
-    let program = client.program(CANDY_MACHINE_ID);
-
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
 
@@ -81,7 +83,7 @@ pub fn process_set_token_stardard(args: SetTokenStandardArgs) -> Result<()> {
         "rule set"
     };
 
-    println!("\n[2/2] {}Setting {}", WITHDRAW_EMOJI, message);
+    report!("\n[2/2] {}Setting {}", WITHDRAW_EMOJI, message);
 
     let pb = spinner_with_style();
     pb.set_message("Connecting...");
@@ -150,7 +152,7 @@ pub fn process_set_token_stardard(args: SetTokenStandardArgs) -> Result<()> {
     let sig = tx.send()?;
 
     pb.finish_and_clear();
-    println!("{} {}", style("Signature:").bold(), sig);
+    report!("{} {}", style("Signature:").bold(), sig);
 
     Ok(())
 }
@@ -0,0 +1,124 @@
+//! Listing of the files of an assets directory. With `--recursive`, the subdirectories are
+//! walked too, following symlinks, so that the assets of a pipeline writing one folder per
+//! batch (e.g. `assets/batch-01/0.png`) can be uploaded without moving them.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+};
+
+use crate::common::*;
+
+/// Files of the assets directory by file name (e.g. `0.png`). Hidden files and directories
+/// (including `.DS_Store`) are skipped.
+///
+/// When `recursive` is set, the subdirectories are walked following symlinks; a directory
+/// already walked (e.g. the target of a symlink cycle) is skipped. The file names must be
+/// unique across the directories, a duplicate fails naming both paths.
+pub fn asset_files(assets_dir: &Path, recursive: bool) -> Result<BTreeMap<String, PathBuf>> {
+    let mut files: BTreeMap<String, PathBuf> = BTreeMap::new();
+    let mut visited = HashSet::new();
+    let mut dirs = vec![assets_dir.to_path_buf()];
+
+    while let Some(dir) = dirs.pop() {
+        let canonical = fs::canonicalize(&dir)
+            .map_err(|e| anyhow!("Failed to read directory '{}': {}", dir.display(), e))?;
+
+        if !visited.insert(canonical) {
+            debug!("Skipping '{}': directory already listed", dir.display());
+            continue;
+        }
+
+        let mut entries = fs::read_dir(&dir)
+            .map_err(|e| anyhow!("Failed to read directory '{}': {}", dir.display(), e))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if name.starts_with('.') {
+                continue;
+            }
+
+            let path = entry.path();
+            // follows symlinks
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(err) => {
+                    warn!("Skipping '{}': {}", path.display(), err);
+                    continue;
+                }
+            };
+
+            if metadata.is_dir() {
+                if recursive {
+                    dirs.push(path);
+                }
+            } else if metadata.is_file() {
+                if let Some(other) = files.get(&name) {
+                    return Err(anyhow!(
+                        "Duplicated asset file name '{}': {} and {}",
+                        name,
+                        other.display(),
+                        path.display()
+                    ));
+                }
+                files.insert(name, path);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_asset_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let assets = dir.path().join("assets");
+        let batch_1 = dir.path().join("batch-01");
+        let batch_2 = assets.join("batch-02");
+        fs::create_dir_all(&batch_1).unwrap();
+        fs::create_dir_all(&batch_2).unwrap();
+
+        fs::write(batch_1.join("0.png"), "").unwrap();
+        fs::write(batch_1.join("0.json"), "{}").unwrap();
+        fs::write(batch_1.join(".DS_Store"), "").unwrap();
+        fs::write(batch_2.join("1.png"), "").unwrap();
+        fs::write(batch_2.join("1.json"), "{}").unwrap();
+        fs::write(assets.join("collection.json"), "{}").unwrap();
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::symlink;
+
+            symlink(&batch_1, assets.join("batch-01")).unwrap();
+            // a cycle back to the assets directory
+            symlink(&assets, batch_2.join("loop")).unwrap();
+        }
+
+        let files = asset_files(&assets, false).unwrap();
+        assert_eq!(files.keys().collect::<Vec<_>>(), vec!["collection.json"]);
+
+        #[cfg(unix)]
+        {
+            let files = asset_files(&assets, true).unwrap();
+            assert_eq!(
+                files.keys().collect::<Vec<_>>(),
+                vec!["0.json", "0.png", "1.json", "1.png", "collection.json"]
+            );
+            assert_eq!(files["1.png"], batch_2.join("1.png"));
+
+            // the same file name in two folders
+            fs::write(batch_2.join("0.png"), "").unwrap();
+            let err = asset_files(&assets, true).unwrap_err().to_string();
+            assert!(err.contains("'0.png'"), "{}", err);
+            assert!(err.contains("batch-01"), "{}", err);
+            assert!(err.contains("batch-02"), "{}", err);
+        }
+    }
+}
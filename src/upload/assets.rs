@@ -1,6 +1,7 @@
 use std::{
+    collections::HashSet,
     ffi::OsStr,
-    fs::{self, DirEntry, File, OpenOptions},
+    fs::{self, DirEntry, File},
     io::{BufReader, Read},
 };
 
@@ -11,9 +12,36 @@ use ring::digest::{Context, SHA256};
 use serde::Serialize;
 use serde_json;
 
-use crate::{common::*, validate::format::Metadata};
+use crate::{
+    common::*,
+    upload::{
+        asset_files::asset_files,
+        remote::{is_remote, RemoteAsset},
+    },
+    validate::format::{FileAttr, Metadata},
+};
+
+/// Extensions of the image files, with their content type.
+pub const IMAGE_CONTENT_TYPES: [(&str, &str); 4] = [
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("png", "image/png"),
+];
+
+/// Extensions of the (optional) animation files, with their content type.
+pub const ANIMATION_CONTENT_TYPES: [(&str, &str); 7] = [
+    ("mp3", "audio/mpeg"),
+    ("mp4", "video/mp4"),
+    ("mov", "video/quicktime"),
+    ("webm", "video/webm"),
+    ("glb", "model/gltf-binary"),
+    ("gltf", "model/gltf+json"),
+    ("html", "text/html"),
+];
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum DataType {
     Image,
     Metadata,
@@ -29,9 +57,34 @@ pub struct AssetPair {
     pub image_hash: String,
     pub animation: Option<String>,
     pub animation_hash: Option<String>,
+    /// Remote files of an asset of a manifest (`upload --from-manifest`).
+    #[serde(skip)]
+    pub remote: Option<RemoteAsset>,
 }
 
 impl AssetPair {
+    /// Size of the image, from the `Content-Length` of a remote image.
+    pub fn image_size(&self) -> Result<u64> {
+        match &self.remote {
+            Some(remote) => remote.image.size(),
+            None => Ok(fs::metadata(&self.image)?.len()),
+        }
+    }
+
+    /// Content of the metadata, downloaded before the upload for a remote asset.
+    pub fn metadata_content(&self) -> Result<Vec<u8>> {
+        match &self.remote {
+            Some(remote) => Ok(remote.metadata.as_ref().clone()),
+            None => fs::read(&self.metadata).map_err(|e| {
+                anyhow!(
+                    "Failed to read metadata file '{}' with error: {}",
+                    self.metadata,
+                    e
+                )
+            }),
+        }
+    }
+
     pub fn into_cache_item(self) -> CacheItem {
         CacheItem {
             name: self.name,
@@ -42,10 +95,35 @@ impl AssetPair {
             on_chain: false,
             animation_hash: self.animation_hash,
             animation_link: None,
+            optimized_image_hash: None,
         }
     }
 }
 
+/// Name of the uploaded file of an asset: its index (or `collection`) with the extension of
+/// its file, which can be the URL of a remote file.
+pub fn asset_file_name(index: isize, path: &str) -> Result<String> {
+    let path = if is_remote(path) {
+        url::Url::parse(path)
+            .map_err(|e| anyhow!("Invalid URL '{}': {}", path, e))?
+            .path()
+            .to_string()
+    } else {
+        path.to_string()
+    };
+
+    let extension = Path::new(&path)
+        .extension()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| anyhow!("Missing file extension of '{}'", path))?;
+
+    Ok(if index == -1 {
+        format!("collection.{}", extension)
+    } else {
+        format!("{}.{}", index, extension)
+    })
+}
+
 pub fn get_cache_item<'a>(path: &Path, cache: &'a mut Cache) -> Result<(String, &'a CacheItem)> {
     let file_stem = String::from(
         path.file_stem()
@@ -114,25 +192,104 @@ pub fn list_files(assets_dir: &str, include_collection: bool) -> Result<Vec<DirE
     Ok(files.collect())
 }
 
+/// Collection files of an assets directory (`collection.json` and `collection.<ext>`), uploaded
+/// to the `-1` item of the cache. Returns `None` when the directory has no collection files and
+/// fails when only one of the files is present.
+pub fn find_collection_files(assets_dir: &Path) -> Result<Option<(PathBuf, PathBuf)>> {
+    let metadata = assets_dir.join("collection.json");
+    let image = IMAGE_CONTENT_TYPES.iter().find_map(|(extension, _)| {
+        fs::read_dir(assets_dir)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .find(|path| {
+                path.is_file()
+                    && path.file_stem() == Some(OsStr::new("collection"))
+                    && path
+                        .extension()
+                        .and_then(OsStr::to_str)
+                        .map_or(false, |ext| ext.eq_ignore_ascii_case(extension))
+            })
+    });
+
+    match (metadata.is_file(), image) {
+        (true, Some(image)) => Ok(Some((metadata, image))),
+        (false, None) => Ok(None),
+        (true, None) => Err(anyhow!(
+            "Found collection.json without a collection image (collection.png, .jpg, .jpeg or \
+            .gif) in '{}', both files are needed for the collection item (-1)",
+            assets_dir.display()
+        )),
+        (false, Some(image)) => Err(anyhow!(
+            "Found {} without a collection.json in '{}', both files are needed for the \
+            collection item (-1)",
+            image.display(),
+            assets_dir.display()
+        )),
+    }
+}
+
 pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
-    // filters out directories and hidden files
-    let filtered_files = list_files(assets_dir, true)?;
+    read_asset_pairs(assets_dir, false, None, true, true)
+}
 
-    let paths = filtered_files
+/// Reads the asset pairs, only hashing the images and animations when requested (the hashes
+/// of the files not hashed are left empty).
+pub fn get_asset_pairs_hashing(
+    assets_dir: &str,
+    recursive: bool,
+    hash_images: bool,
+    hash_animations: bool,
+) -> Result<HashMap<isize, AssetPair>> {
+    read_asset_pairs(assets_dir, recursive, None, hash_images, hash_animations)
+}
+
+/// Reads the asset pairs of the indices only, without reading (or hashing) the files of the
+/// other assets.
+pub fn get_asset_pairs_of(
+    assets_dir: &str,
+    recursive: bool,
+    indices: &HashSet<isize>,
+) -> Result<HashMap<isize, AssetPair>> {
+    read_asset_pairs(assets_dir, recursive, Some(indices), true, true)
+}
+
+fn read_asset_pairs(
+    assets_dir: &str,
+    recursive: bool,
+    only: Option<&HashSet<isize>>,
+    hash_images: bool,
+    hash_animations: bool,
+) -> Result<HashMap<isize, AssetPair>> {
+    // an incomplete collection would leave the cache without the -1 item
+    find_collection_files(Path::new(assets_dir))?;
+
+    // numeric and collection files, by file name (unique across the subdirectories)
+    let files = asset_files(Path::new(assets_dir), recursive)?
         .into_iter()
-        .map(|entry| {
-            let file_name_as_string =
-                String::from(entry.path().file_name().unwrap().to_str().unwrap());
-            file_name_as_string
+        .filter(|(name, _)| {
+            let file_stem = Path::new(name)
+                .file_stem()
+                .unwrap_or_default()
+                .to_string_lossy();
+            file_stem == "collection" || file_stem.chars().all(|c| c.is_ascii_digit())
         })
-        .collect::<Vec<String>>();
+        .collect::<HashMap<String, PathBuf>>();
+
+    let paths = files.keys().cloned().collect::<Vec<String>>();
+    let file_path = |name: &str| -> String {
+        files[name]
+            .to_str()
+            .expect("Failed to convert asset path from unicode.")
+            .to_string()
+    };
 
     let mut asset_pairs: HashMap<isize, AssetPair> = HashMap::new();
 
     let paths_ref = &paths;
 
-    let animation_exists_regex =
-        Regex::new("^(.+)\\.((mp3)|(mp4)|(mov)|(webm)|(glb))$").expect("Failed to create regex.");
+    let animation_extensions = extensions_pattern(&ANIMATION_CONTENT_TYPES);
+    let animation_exists_regex = Regex::new(&format!("^(.+)\\.({})$", animation_extensions))
+        .expect("Failed to create regex.");
 
     // since there doesn't have to be video for each image/json pair, need to get rid of
     // invalid file names before entering metadata filename loop
@@ -171,7 +328,11 @@ pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
             return Err(error);
         };
 
-        let img_pattern = format!("^{}\\.((jpg)|(jpeg)|(gif)|(png))$", i);
+        if only.map_or(false, |indices| !indices.contains(&index)) {
+            continue;
+        }
+
+        let img_pattern = format!("^{}\\.({})$", i, extensions_pattern(&IMAGE_CONTENT_TYPES));
 
         let img_regex = RegexBuilder::new(&img_pattern)
             .case_insensitive(true)
@@ -184,7 +345,19 @@ pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
             .filter(|p| img_regex.is_match(p))
             .collect::<Vec<String>>();
 
-        let img_filename = if img_filenames.len() != 1 {
+        let img_filename = if img_filenames.len() > 1 {
+            let error = anyhow!(
+                "Found several images for '{}': {}",
+                metadata_filename,
+                img_filenames
+                    .iter()
+                    .map(|name| file_path(name))
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            error!("{:?}", error);
+            return Err(error);
+        } else if img_filenames.len() != 1 {
             let error = if is_collection_index {
                 anyhow!("Couldn't find the collection image filename.")
             } else {
@@ -202,7 +375,7 @@ pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
         // need a similar check for animation as above, this one checking if there is animation
         // on specific index
 
-        let animation_pattern = format!("^{}\\.((mp3)|(mp4)|(mov)|(webm)|(glb))$", i);
+        let animation_pattern = format!("^{}\\.({})$", i, animation_extensions);
         let animation_regex = RegexBuilder::new(&animation_pattern)
             .case_insensitive(true)
             .build()
@@ -214,44 +387,29 @@ pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
             .filter(|p| animation_regex.is_match(p))
             .collect::<Vec<String>>();
 
-        let metadata_filepath = Path::new(assets_dir)
-            .join(&metadata_filename)
-            .to_str()
-            .expect("Failed to convert metadata path from unicode.")
-            .to_string();
+        let metadata_filepath = file_path(&metadata_filename);
 
         let m = File::open(&metadata_filepath)?;
         let metadata: Metadata = serde_json::from_reader(m).map_err(|e| {
             anyhow!("Failed to read metadata file '{metadata_filepath}' with error: {e}")
         })?;
         if metadata.properties.creators.is_some() {
-            println!("The creators field is deprecated in the JSON metadata, it should be set in the config file instead.")
+            report!("The creators field is deprecated in the JSON metadata, it should be set in the config file instead.")
         }
         let name = metadata.name.clone();
 
-        let img_filepath = Path::new(assets_dir)
-            .join(img_filename)
-            .to_str()
-            .expect("Failed to convert image path from unicode.")
-            .to_string();
+        let img_filepath = file_path(img_filename);
 
         let animation_filename = if animation_filenames.len() == 1 {
-            let animation_filepath = Path::new(assets_dir)
-                .join(&animation_filenames[0])
-                .to_str()
-                .expect("Failed to convert animation path from unicode.")
-                .to_string();
-
-            Some(animation_filepath)
+            Some(file_path(&animation_filenames[0]))
         } else {
             None
         };
 
-        let animation_hash = if let Some(animation_file) = &animation_filename {
-            let encoded_filename = encode(animation_file)?;
-            Some(encoded_filename)
-        } else {
-            None
+        let animation_hash = match &animation_filename {
+            Some(animation_file) if hash_animations => Some(encode(animation_file)?),
+            Some(_) => Some(String::new()),
+            None => None,
         };
 
         let asset_pair = AssetPair {
@@ -259,9 +417,14 @@ pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
             metadata: metadata_filepath.clone(),
             metadata_hash: encode(&metadata_filepath)?,
             image: img_filepath.clone(),
-            image_hash: encode(&img_filepath)?,
+            image_hash: if hash_images {
+                encode(&img_filepath)?
+            } else {
+                String::new()
+            },
             animation_hash,
             animation: animation_filename,
+            remote: None,
         };
 
         asset_pairs.insert(index, asset_pair);
@@ -270,6 +433,36 @@ pub fn get_asset_pairs(assets_dir: &str) -> Result<HashMap<isize, AssetPair>> {
     Ok(asset_pairs)
 }
 
+/// Alternation of the extensions of a content type table (e.g. `(jpg)|(png)`).
+fn extensions_pattern(content_types: &[(&str, &str)]) -> String {
+    content_types
+        .iter()
+        .map(|(extension, _)| format!("({})", extension))
+        .collect::<Vec<String>>()
+        .join("|")
+}
+
+/// Content type of an asset file, from its extension.
+pub fn content_type(path: &str, data_type: &DataType) -> Result<String> {
+    let content_types: &[(&str, &str)] = match data_type {
+        DataType::Metadata => return Ok("application/json".to_string()),
+        DataType::Image => &IMAGE_CONTENT_TYPES,
+        DataType::Animation => &ANIMATION_CONTENT_TYPES,
+    };
+
+    let extension = Path::new(path)
+        .extension()
+        .and_then(OsStr::to_str)
+        .unwrap_or_default()
+        .to_lowercase();
+
+    content_types
+        .iter()
+        .find(|(known, _)| *known == extension)
+        .map(|(_, content_type)| content_type.to_string())
+        .ok_or_else(|| anyhow!("Unsupported file type of '{}'", path))
+}
+
 pub fn encode(file: &str) -> Result<String> {
     let input = File::open(file)?;
     let mut reader = BufReader::new(input);
@@ -320,24 +513,19 @@ fn ensure_sequential_files(metadata_filenames: Vec<String>) -> Result<()> {
 }
 
 pub fn get_updated_metadata(
-    metadata_file: &str,
+    pair: &AssetPair,
     image_link: &str,
     animation_link: &Option<String>,
 ) -> Result<String> {
-    let mut metadata: Metadata = {
-        let m = OpenOptions::new()
-            .read(true)
-            .open(metadata_file)
-            .map_err(|e| {
-                anyhow!("Failed to read metadata file '{metadata_file}' with error: {e}")
-            })?;
-        serde_json::from_reader(&m)?
-    };
+    // the metadata of a remote asset is downloaded before the upload
+    let mut metadata: Metadata = serde_json::from_slice(&pair.metadata_content()?)?;
 
     if metadata.properties.creators.is_some() {
-        println!("The creators field is deprecated in the JSON metadata, it should be set in the config file instead.")
+        report!("The creators field is deprecated in the JSON metadata, it should be set in the config file instead.")
     }
 
+    let mut animation_listed = false;
+
     for file in &mut metadata.properties.files {
         if file.uri.eq(&metadata.image) {
             file.uri = image_link.to_string();
@@ -346,11 +534,25 @@ pub fn get_updated_metadata(
             if let Some(ref animation_url) = metadata.animation_url {
                 if file.uri.eq(animation_url) {
                     file.uri = animation_link.to_string();
+                    animation_listed = true;
                 }
             }
         }
     }
 
+    // wallets look for the animation in the files, with its content type
+    if let (Some(animation_link), Some(animation_url), false) =
+        (animation_link, &metadata.animation_url, animation_listed)
+    {
+        if let Ok(file_type) = content_type(animation_url, &DataType::Animation) {
+            metadata.properties.files.push(FileAttr {
+                uri: animation_link.clone(),
+                file_type,
+                cdn: false,
+            });
+        }
+    }
+
     metadata.image = image_link.to_string();
 
     if animation_link.is_some() {
@@ -364,3 +566,29 @@ pub fn get_updated_metadata(
 pub fn is_complete_uri(value: &str) -> bool {
     url::Url::parse(value).is_ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_collection_files() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("0.json"), "{}").unwrap();
+        fs::write(dir.path().join("0.png"), "").unwrap();
+        assert!(find_collection_files(dir.path()).unwrap().is_none());
+
+        fs::write(dir.path().join("collection.json"), "{}").unwrap();
+        let err = find_collection_files(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("without a collection image"), "{}", err);
+
+        fs::write(dir.path().join("collection.PNG"), "").unwrap();
+        let (metadata, image) = find_collection_files(dir.path()).unwrap().unwrap();
+        assert_eq!(metadata, dir.path().join("collection.json"));
+        assert_eq!(image, dir.path().join("collection.PNG"));
+
+        fs::remove_file(dir.path().join("collection.json")).unwrap();
+        let err = find_collection_files(dir.path()).unwrap_err().to_string();
+        assert!(err.contains("without a collection.json"), "{}", err);
+    }
+}
@@ -0,0 +1,554 @@
+//! Encoding of a directory of files as a CAR (content addressable archive), the format of the
+//! DAG uploads of nft.storage. The files are chunked into raw leaves of 256KiB linked by
+//! UnixFS nodes (CIDv1, sha2-256), the layout of `ipfs add --cid-version 1`.
+//!
+//! The leaves of local files are not kept in memory: the files are hashed a first time to build
+//! the DAG and read again, a block at a time, when the CAR is written.
+
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+};
+
+use anyhow::{anyhow, Result};
+use data_encoding::BASE32_NOPAD;
+use sha2::{Digest, Sha256};
+
+// Size of the raw leaves of a file.
+const CHUNK_SIZE: usize = 256 * 1024;
+// Maximum number of links of a file node.
+const MAX_LINKS: usize = 174;
+// Multicodec of the raw blocks.
+const RAW_CODEC: u64 = 0x55;
+// Multicodec of the dag-pb blocks.
+const DAG_PB_CODEC: u64 = 0x70;
+// Multihash code and length of sha2-256.
+const SHA2_256: u64 = 0x12;
+const SHA2_256_LENGTH: u64 = 32;
+// UnixFS data types.
+const UNIXFS_DIRECTORY: u64 = 1;
+const UNIXFS_FILE: u64 = 2;
+
+/// Directory encoded as a CAR, with the CID of its root.
+pub struct DirectoryCar {
+    /// CID of the directory (base32 CIDv1).
+    pub root: String,
+    /// Content of the CAR.
+    pub data: Vec<u8>,
+}
+
+/// Content of a file of a directory CAR.
+pub enum CarFile<'a> {
+    /// Content in memory.
+    Bytes(&'a [u8]),
+    /// Path of a local file, read when the CAR is written.
+    Path(&'a str),
+}
+
+/// Directory encoded as a CAR whose blocks are written on demand, with the CID of its root.
+pub struct StreamingCar {
+    /// CID of the directory (base32 CIDv1).
+    pub root: String,
+    header: Vec<u8>,
+    blocks: Vec<Block>,
+}
+
+/// Content of a block: in memory or a range of a local file.
+enum BlockData {
+    Bytes(Vec<u8>),
+    File {
+        path: String,
+        offset: u64,
+        len: usize,
+    },
+}
+
+impl BlockData {
+    fn len(&self) -> usize {
+        match self {
+            BlockData::Bytes(data) => data.len(),
+            BlockData::File { len, .. } => *len,
+        }
+    }
+}
+
+/// CID and content of a block.
+type Block = (Vec<u8>, BlockData);
+
+/// Blocks of the CAR, in order, without duplicates.
+#[derive(Default)]
+struct Blocks {
+    blocks: Vec<Block>,
+    cids: HashSet<Vec<u8>>,
+}
+
+impl Blocks {
+    fn add(&mut self, cid: &[u8], block: BlockData) {
+        if self.cids.insert(cid.to_vec()) {
+            self.blocks.push((cid.to_vec(), block));
+        }
+    }
+}
+
+/// Block of the DAG and the size of the DAG under it (the `Tsize` of its links).
+struct Node {
+    cid: Vec<u8>,
+    content_size: u64,
+    dag_size: u64,
+}
+
+/// Encodes the `(name, content)` files as a directory, linked by name.
+pub fn directory_car(files: &[(&str, &[u8])]) -> DirectoryCar {
+    let files: Vec<(&str, CarFile)> = files
+        .iter()
+        .map(|(name, content)| (*name, CarFile::Bytes(content)))
+        .collect();
+    // all the blocks are in memory, the encoding cannot fail
+    let car = streaming_directory_car(&files).expect("in-memory CAR");
+    let root = car.root.clone();
+    let data = car
+        .into_chunks(usize::MAX)
+        .collect::<Result<Vec<_>>>()
+        .expect("in-memory CAR")
+        .concat();
+
+    DirectoryCar { root, data }
+}
+
+/// Encodes the `(name, content)` files as a directory, linked by name, reading the local files
+/// to compute the CIDs of their leaves.
+pub fn streaming_directory_car(files: &[(&str, CarFile)]) -> Result<StreamingCar> {
+    let mut blocks = Blocks::default();
+
+    let mut links = Vec::with_capacity(files.len());
+    for (name, content) in files {
+        let node = match content {
+            CarFile::Bytes(content) => add_file(&mut blocks, content),
+            CarFile::Path(path) => add_local_file(&mut blocks, path)?,
+        };
+        links.push((*name, node));
+    }
+    // the links of a directory are sorted by name
+    links.sort_by(|(a, _), (b, _)| a.as_bytes().cmp(b.as_bytes()));
+
+    let links: Vec<(&str, &Node)> = links.iter().map(|(name, node)| (*name, node)).collect();
+    let data = unixfs_data(UNIXFS_DIRECTORY, None, &[]);
+    let root = add_node(&mut blocks, &links, &data, 0);
+
+    Ok(StreamingCar {
+        root: cid_string(&root.cid),
+        header: car_header(&root.cid),
+        blocks: blocks.blocks,
+    })
+}
+
+impl StreamingCar {
+    /// Size of the CAR in bytes.
+    pub fn size(&self) -> u64 {
+        let sections = std::iter::once(self.header.len()).chain(
+            self.blocks
+                .iter()
+                .map(|(cid, block)| cid.len() + block.len()),
+        );
+        sections
+            .map(|length| varint_len(length as u64) + length as u64)
+            .sum()
+    }
+
+    /// Content of the CAR in chunks of at least `chunk_size` bytes (except the last one); the
+    /// blocks of local files are read as the chunks are consumed.
+    pub fn into_chunks(self, chunk_size: usize) -> CarChunks {
+        CarChunks {
+            header: Some(self.header),
+            blocks: self.blocks.into_iter(),
+            chunk_size,
+            file: None,
+        }
+    }
+}
+
+/// Iterator over the content of a [`StreamingCar`].
+pub struct CarChunks {
+    header: Option<Vec<u8>>,
+    blocks: std::vec::IntoIter<Block>,
+    chunk_size: usize,
+    // last file read, the leaves of a file are consecutive
+    file: Option<(String, File)>,
+}
+
+impl CarChunks {
+    fn read_block(&mut self, path: String, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let file = match self.file.take() {
+            Some((current, file)) if current == path => file,
+            _ => File::open(&path)
+                .map_err(|err| anyhow!("Failed to open file '{}': {}", path, err))?,
+        };
+        let (_, file) = self.file.insert((path, file));
+
+        let mut block = vec![0; len];
+        file.seek(SeekFrom::Start(offset))
+            .and_then(|_| file.read_exact(&mut block))
+            .map_err(|err| anyhow!("Failed to read file: {}", err))?;
+        Ok(block)
+    }
+}
+
+impl Iterator for CarChunks {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::new();
+
+        if let Some(header) = self.header.take() {
+            write_varint(&mut chunk, header.len() as u64);
+            chunk.extend_from_slice(&header);
+        }
+
+        while chunk.len() < self.chunk_size {
+            let (cid, block) = match self.blocks.next() {
+                Some(block) => block,
+                None => break,
+            };
+            let block = match block {
+                BlockData::Bytes(data) => data,
+                BlockData::File { path, offset, len } => match self.read_block(path, offset, len) {
+                    Ok(data) => data,
+                    Err(err) => return Some(Err(err)),
+                },
+            };
+            write_varint(&mut chunk, (cid.len() + block.len()) as u64);
+            chunk.extend_from_slice(&cid);
+            chunk.extend_from_slice(&block);
+        }
+
+        if chunk.is_empty() {
+            None
+        } else {
+            Some(Ok(chunk))
+        }
+    }
+}
+
+/// Adds the blocks of a file: a single raw leaf, or the leaves linked by a balanced tree of
+/// file nodes.
+fn add_file(blocks: &mut Blocks, content: &[u8]) -> Node {
+    if content.len() <= CHUNK_SIZE {
+        return add_raw(blocks, content);
+    }
+
+    let leaves = content
+        .chunks(CHUNK_SIZE)
+        .map(|chunk| add_raw(blocks, chunk))
+        .collect();
+
+    link_leaves(blocks, leaves)
+}
+
+/// Adds the blocks of a local file, keeping only the location of its leaves.
+fn add_local_file(blocks: &mut Blocks, path: &str) -> Result<Node> {
+    let mut file =
+        File::open(path).map_err(|err| anyhow!("Failed to open file '{}': {}", path, err))?;
+    let mut leaves = Vec::new();
+    let mut offset = 0;
+
+    loop {
+        let mut chunk = Vec::with_capacity(CHUNK_SIZE);
+        (&mut file)
+            .take(CHUNK_SIZE as u64)
+            .read_to_end(&mut chunk)
+            .map_err(|err| anyhow!("Failed to read file '{}': {}", path, err))?;
+
+        if chunk.is_empty() {
+            break;
+        }
+
+        let cid = cid(RAW_CODEC, &chunk);
+        blocks.add(
+            &cid,
+            BlockData::File {
+                path: path.to_string(),
+                offset,
+                len: chunk.len(),
+            },
+        );
+        offset += chunk.len() as u64;
+        leaves.push(Node {
+            cid,
+            content_size: chunk.len() as u64,
+            dag_size: chunk.len() as u64,
+        });
+    }
+
+    if leaves.is_empty() {
+        return Ok(add_raw(blocks, &[]));
+    }
+
+    Ok(link_leaves(blocks, leaves))
+}
+
+/// Links the leaves of a file by a balanced tree of file nodes; a single leaf is the file.
+fn link_leaves(blocks: &mut Blocks, mut level: Vec<Node>) -> Node {
+    while level.len() > 1 {
+        level = level
+            .chunks(MAX_LINKS)
+            .map(|children| {
+                let sizes: Vec<u64> = children.iter().map(|child| child.content_size).collect();
+                let file_size = sizes.iter().sum();
+                let links: Vec<(&str, &Node)> = children.iter().map(|child| ("", child)).collect();
+                let data = unixfs_data(UNIXFS_FILE, Some(file_size), &sizes);
+                add_node(blocks, &links, &data, file_size)
+            })
+            .collect();
+    }
+
+    level.pop().unwrap()
+}
+
+fn add_raw(blocks: &mut Blocks, content: &[u8]) -> Node {
+    let cid = cid(RAW_CODEC, content);
+    blocks.add(&cid, BlockData::Bytes(content.to_vec()));
+
+    Node {
+        cid,
+        content_size: content.len() as u64,
+        dag_size: content.len() as u64,
+    }
+}
+
+/// Adds a dag-pb node with the links (in order) and the UnixFS data.
+fn add_node(blocks: &mut Blocks, links: &[(&str, &Node)], data: &[u8], content_size: u64) -> Node {
+    let mut block = Vec::new();
+
+    // the links (field 2) come before the data (field 1) in the canonical encoding
+    for (name, node) in links {
+        let mut link = Vec::new();
+        write_bytes_field(&mut link, 1, &node.cid);
+        write_bytes_field(&mut link, 2, name.as_bytes());
+        write_varint_field(&mut link, 3, node.dag_size);
+        write_bytes_field(&mut block, 2, &link);
+    }
+    write_bytes_field(&mut block, 1, data);
+
+    let cid = cid(DAG_PB_CODEC, &block);
+    let dag_size = block.len() as u64 + links.iter().map(|(_, node)| node.dag_size).sum::<u64>();
+    blocks.add(&cid, BlockData::Bytes(block));
+
+    Node {
+        cid,
+        content_size,
+        dag_size,
+    }
+}
+
+/// UnixFS `Data` message of a node.
+fn unixfs_data(data_type: u64, file_size: Option<u64>, block_sizes: &[u64]) -> Vec<u8> {
+    let mut data = Vec::new();
+    write_varint_field(&mut data, 1, data_type);
+    if let Some(file_size) = file_size {
+        write_varint_field(&mut data, 3, file_size);
+    }
+    for size in block_sizes {
+        write_varint_field(&mut data, 4, *size);
+    }
+    data
+}
+
+/// CARv1 header: the dag-cbor map `{"roots": [root], "version": 1}`.
+fn car_header(root: &[u8]) -> Vec<u8> {
+    let mut header = vec![0xa2];
+    write_cbor_text(&mut header, "roots");
+    header.push(0x81);
+    // tag 42 (CID) of the bytes of the CID prefixed by the identity multibase
+    header.extend_from_slice(&[0xd8, 0x2a]);
+    write_cbor_head(&mut header, 2, root.len() as u64 + 1);
+    header.push(0x00);
+    header.extend_from_slice(root);
+    write_cbor_text(&mut header, "version");
+    header.push(0x01);
+    header
+}
+
+/// Binary CIDv1 of a block.
+fn cid(codec: u64, block: &[u8]) -> Vec<u8> {
+    let mut cid = Vec::new();
+    write_varint(&mut cid, 1);
+    write_varint(&mut cid, codec);
+    write_varint(&mut cid, SHA2_256);
+    write_varint(&mut cid, SHA2_256_LENGTH);
+    cid.extend_from_slice(&Sha256::digest(block));
+    cid
+}
+
+/// Base32 (lowercase, `b` multibase prefix) string of a CID.
+fn cid_string(cid: &[u8]) -> String {
+    format!("b{}", BASE32_NOPAD.encode(cid).to_lowercase())
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+fn varint_len(mut value: u64) -> u64 {
+    let mut len = 1;
+    while value >= 0x80 {
+        value >>= 7;
+        len += 1;
+    }
+    len
+}
+
+fn write_varint_field(buffer: &mut Vec<u8>, field: u64, value: u64) {
+    write_varint(buffer, field << 3);
+    write_varint(buffer, value);
+}
+
+fn write_bytes_field(buffer: &mut Vec<u8>, field: u64, value: &[u8]) {
+    write_varint(buffer, (field << 3) | 2);
+    write_varint(buffer, value.len() as u64);
+    buffer.extend_from_slice(value);
+}
+
+fn write_cbor_head(buffer: &mut Vec<u8>, major: u8, length: u64) {
+    let major = major << 5;
+    match length {
+        0..=23 => buffer.push(major | length as u8),
+        24..=0xff => buffer.extend_from_slice(&[major | 24, length as u8]),
+        _ => {
+            buffer.push(major | 25);
+            buffer.extend_from_slice(&(length as u16).to_be_bytes());
+        }
+    }
+}
+
+fn write_cbor_text(buffer: &mut Vec<u8>, text: &str) {
+    write_cbor_head(buffer, 3, text.len() as u64);
+    buffer.extend_from_slice(text.as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_varint(data: &[u8], position: &mut usize) -> u64 {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = data[*position];
+            *position += 1;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return value;
+            }
+            shift += 7;
+        }
+    }
+
+    /// Splits a CAR into its header and `(cid, block)` sections.
+    fn read_car(car: &[u8]) -> (Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>) {
+        let mut position = 0;
+        let length = read_varint(car, &mut position) as usize;
+        let header = car[position..position + length].to_vec();
+        position += length;
+
+        let mut blocks = Vec::new();
+        while position < car.len() {
+            let length = read_varint(car, &mut position) as usize;
+            // CIDv1 with a sha2-256 multihash: 4 single-byte varints and the digest
+            let cid = car[position..position + 36].to_vec();
+            let block = car[position + 36..position + length].to_vec();
+            blocks.push((cid, block));
+            position += length;
+        }
+        (header, blocks)
+    }
+
+    #[test]
+    fn raw_leaf_cid() {
+        // `ipfs add --cid-version 1 --raw-leaves` of "hello world"
+        assert_eq!(
+            cid_string(&cid(RAW_CODEC, b"hello world")),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+    }
+
+    #[test]
+    fn encodes_directory() {
+        let large = vec![7u8; CHUNK_SIZE * 2 + 10];
+        let car = directory_car(&[("1.json", b"{}"), ("0.png", &large)]);
+
+        let (header, blocks) = read_car(&car.data);
+        assert_eq!(header, car_header(&blocks.last().unwrap().0));
+
+        // every block is addressed by the hash of its content
+        for (cid, block) in &blocks {
+            assert_eq!(&cid[4..], Sha256::digest(block).as_slice());
+        }
+
+        // 1.json, the 2 distinct leaves of 0.png, its file node and the directory
+        assert_eq!(blocks.len(), 5);
+        let (root, directory) = blocks.last().unwrap();
+        assert_eq!(car.root, cid_string(root));
+        assert!(car.root.starts_with("bafybei"));
+
+        // the links are sorted by name
+        let png = directory
+            .windows(5)
+            .position(|window| window == b"0.png")
+            .unwrap();
+        let json = directory
+            .windows(6)
+            .position(|window| window == b"1.json")
+            .unwrap();
+        assert!(png < json);
+
+        // the file node records the size of each leaf
+        let (_, file) = &blocks[3];
+        let sizes = [CHUNK_SIZE as u64, CHUNK_SIZE as u64, 10];
+        assert!(file.ends_with(&unixfs_data(UNIXFS_FILE, Some(large.len() as u64), &sizes)));
+    }
+
+    #[test]
+    fn streams_local_files() {
+        let large: Vec<u8> = (0..CHUNK_SIZE * 3 + 10).map(|i| (i % 251) as u8).collect();
+        let dir = tempfile::tempdir().unwrap();
+        let large_path = dir.path().join("0.png");
+        let empty_path = dir.path().join("2.png");
+        std::fs::write(&large_path, &large).unwrap();
+        std::fs::write(&empty_path, b"").unwrap();
+        let large_path = large_path.to_str().unwrap();
+        let empty_path = empty_path.to_str().unwrap();
+
+        let expected = directory_car(&[("1.json", b"{}"), ("0.png", &large), ("2.png", b"")]);
+        let car = streaming_directory_car(&[
+            ("1.json", CarFile::Bytes(b"{}")),
+            ("0.png", CarFile::Path(large_path)),
+            ("2.png", CarFile::Path(empty_path)),
+        ])
+        .unwrap();
+
+        assert_eq!(car.root, expected.root);
+        assert_eq!(car.size(), expected.data.len() as u64);
+
+        let chunks = car
+            .into_chunks(CHUNK_SIZE)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert!(chunks.len() > 1);
+        assert!(chunks[..chunks.len() - 1]
+            .iter()
+            .all(|chunk| chunk.len() >= CHUNK_SIZE));
+        assert_eq!(chunks.concat(), expected.data);
+    }
+
+    #[test]
+    fn varint() {
+        let mut buffer = Vec::new();
+        write_varint(&mut buffer, 300);
+        assert_eq!(buffer, vec![0xac, 0x02]);
+    }
+}
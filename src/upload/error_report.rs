@@ -0,0 +1,116 @@
+//! Report of the files that failed to upload, written at the end of an upload so that the
+//! failures of a large collection can be read after the run.
+
+use std::{fs, io::ErrorKind};
+
+use serde::Serialize;
+
+use crate::{common::*, upload::*};
+
+/// A file that failed to upload (after retries).
+#[derive(Debug, Serialize)]
+pub struct UploadFailure {
+    /// Index of the asset (-1 for the collection).
+    pub index: isize,
+    pub file: String,
+    /// Type of the file: `image`, `metadata` or `animation`.
+    pub stage: DataType,
+    pub error: String,
+    /// Whether the upload might succeed if run again.
+    pub retriable: bool,
+}
+
+/// Failures of the errors of an upload, sorted by index and stage.
+pub fn upload_failures(errors: &[UploadError]) -> Vec<UploadFailure> {
+    let mut failures: Vec<UploadFailure> = errors
+        .iter()
+        .filter_map(|error| match error {
+            UploadError::FileFailed {
+                asset_id,
+                file,
+                data_type,
+                error,
+                retriable,
+            } => Some(UploadFailure {
+                index: asset_id.parse().unwrap_or(-1),
+                file: file.clone(),
+                stage: data_type.clone(),
+                error: error.clone(),
+                retriable: *retriable,
+            }),
+            _ => None,
+        })
+        .collect();
+
+    failures.sort_by_key(|failure| (failure.index, failure.file.clone()));
+    failures
+}
+
+/// Writes the failures to the report file (a JSON array).
+pub fn write_error_report(path: &str, failures: &[UploadFailure]) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(failures)?)
+        .map_err(|err| anyhow!("Failed to write the error report '{}': {}", path, err))
+}
+
+/// Removes the report file of a previous upload, if there is one.
+pub fn remove_error_report(path: &str) -> Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != ErrorKind::NotFound => Err(anyhow!(
+            "Failed to remove the error report '{}': {}",
+            path,
+            err
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_report() {
+        let errors = vec![
+            UploadError::FileFailed {
+                asset_id: "12".to_string(),
+                file: "12.json".to_string(),
+                data_type: DataType::Metadata,
+                error: "Error uploading batch (413 Payload Too Large)".to_string(),
+                retriable: false,
+            },
+            UploadError::Incomplete("Not all files were uploaded.".to_string()),
+            UploadError::FileFailed {
+                asset_id: "3".to_string(),
+                file: "3.png".to_string(),
+                data_type: DataType::Image,
+                error: "operation timed out".to_string(),
+                retriable: true,
+            },
+        ];
+
+        let failures = upload_failures(&errors);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].index, 3);
+
+        let path = std::env::temp_dir().join(format!("upload-errors-{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+
+        write_error_report(&path, &failures).unwrap();
+        let report: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(
+            report[1],
+            json!({
+                "index": 12,
+                "file": "12.json",
+                "stage": "metadata",
+                "error": "Error uploading batch (413 Payload Too Large)",
+                "retriable": false
+            })
+        );
+
+        remove_error_report(&path).unwrap();
+        assert!(fs::metadata(&path).is_err());
+        // no report to remove
+        remove_error_report(&path).unwrap();
+    }
+}
@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::upload::{is_throttled, DataType};
+
 #[derive(Debug, Error)]
 pub enum UploadError {
     #[error("Invalid assets directory: {0}")]
@@ -27,4 +29,38 @@ pub enum UploadError {
 
     #[error("Metadata file {0} is not formatted correctly for animations.")]
     AnimationFileError(String),
+
+    #[error("Request throttled by the storage: {0}")]
+    Throttled(String),
+
+    #[error("Upload error: {error}")]
+    FileFailed {
+        /// Id of the asset in the cache.
+        asset_id: String,
+        /// Name of the file.
+        file: String,
+        data_type: DataType,
+        error: String,
+        /// Whether the upload might succeed if sent again.
+        retriable: bool,
+    },
+}
+
+/// Checks if a failed upload might succeed if sent again: throttled requests, network errors
+/// and server errors.
+pub fn is_retriable(error: &anyhow::Error) -> bool {
+    if is_throttled(error) {
+        return true;
+    }
+
+    match error.downcast_ref::<reqwest::Error>() {
+        Some(error) => {
+            error.is_timeout()
+                || error.is_connect()
+                || error
+                    .status()
+                    .map_or(false, |status| status.is_server_error())
+        }
+        None => false,
+    }
 }
@@ -0,0 +1,183 @@
+//! Manifest of remote assets (a JSON array of `{index, image_url, metadata_url}`), uploaded
+//! from their URLs instead of the files of the assets directory.
+
+use std::{collections::HashSet, io::BufReader, sync::Arc};
+
+use futures::{stream, StreamExt, TryStreamExt};
+
+use crate::{
+    common::*,
+    upload::{remote::*, AssetPair},
+    utils::progress_bar_with_style,
+    validate::format::Metadata,
+};
+
+// number of remote files read at the same time before the upload
+const MANIFEST_CONCURRENCY: usize = 16;
+
+/// An asset of the manifest.
+#[derive(Debug, Deserialize)]
+pub struct ManifestEntry {
+    pub index: isize,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub metadata_url: Option<String>,
+}
+
+/// Reads the manifest, checking that every entry has both URLs before any request is sent.
+pub fn read_manifest(path: &str) -> Result<Vec<ManifestEntry>> {
+    let file =
+        File::open(path).map_err(|e| anyhow!("Failed to open manifest '{}': {}", path, e))?;
+    let entries: Vec<ManifestEntry> = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| anyhow!("Invalid manifest '{}': {}", path, e))?;
+
+    let mut indices = HashSet::new();
+    let mut incomplete = Vec::new();
+
+    for entry in &entries {
+        if entry.index < -1 || !indices.insert(entry.index) {
+            return Err(anyhow!(
+                "Invalid or duplicated index {} in manifest",
+                entry.index
+            ));
+        }
+
+        let missing: Vec<&str> = [
+            ("image_url", &entry.image_url),
+            ("metadata_url", &entry.metadata_url),
+        ]
+        .iter()
+        .filter(|(_, url)| url.as_deref().map_or(true, |url| url.trim().is_empty()))
+        .map(|(field, _)| *field)
+        .collect();
+
+        if !missing.is_empty() {
+            incomplete.push(format!(
+                "  index {}: missing {}",
+                entry.index,
+                missing.join(" and ")
+            ));
+        }
+    }
+
+    if !incomplete.is_empty() {
+        return Err(anyhow!(
+            "{} manifest entr(ies) without a URL:\n{}",
+            incomplete.len(),
+            incomplete.join("\n")
+        ));
+    }
+
+    Ok(entries)
+}
+
+/// Downloads the metadata and reads the size of the image of the entries, returning their
+/// asset pairs. The images are only downloaded (and hashed) when they are uploaded.
+pub async fn fetch_manifest_assets(
+    entries: &[&ManifestEntry],
+) -> Result<HashMap<isize, AssetPair>> {
+    let pb = progress_bar_with_style(entries.len() as u64);
+    // the remote files of the upload share the connections of the client
+    let client = HttpClient::new();
+
+    let asset_pairs = stream::iter(entries)
+        .map(|entry| {
+            let pb = &pb;
+            let client = &client;
+            async move {
+                // both URLs are checked when the manifest is read
+                let image_url = entry.image_url.clone().unwrap_or_default();
+                let metadata_url = entry.metadata_url.clone().unwrap_or_default();
+
+                let metadata_file = Arc::new(RemoteFile::new(client.clone(), &metadata_url));
+                let content = metadata_file.download().await?;
+                let metadata_hash = metadata_file
+                    .hash()
+                    .ok_or_else(|| anyhow!("Missing hash of remote file {}", metadata_url))?;
+                let metadata: Metadata = serde_json::from_slice(&content)
+                    .map_err(|e| anyhow!("Invalid metadata {}: {}", metadata_url, e))?;
+                let image = RemoteFile::fetch(client.clone(), &image_url).await?;
+
+                pb.inc(1);
+
+                Ok::<_, anyhow::Error>((
+                    entry.index,
+                    AssetPair {
+                        name: metadata.name,
+                        metadata: metadata_url,
+                        metadata_hash,
+                        image: image_url,
+                        // set once the image was streamed to the upload method
+                        image_hash: String::new(),
+                        animation: None,
+                        animation_hash: None,
+                        remote: Some(RemoteAsset {
+                            metadata: Arc::new(content),
+                            image: Arc::new(image),
+                        }),
+                    },
+                ))
+            }
+        })
+        .buffer_unordered(MANIFEST_CONCURRENCY)
+        .try_collect()
+        .await?;
+
+    pb.finish_and_clear();
+
+    Ok(asset_pairs)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_read_manifest() {
+        let path = std::env::temp_dir().join(format!("sugar-manifest-{}.json", std::process::id()));
+        let path_str = path.to_string_lossy().to_string();
+
+        fs::write(
+            &path,
+            json!([
+                { "index": 0, "image_url": "https://farm/0.png", "metadata_url": "https://farm/0.json" },
+                { "index": 1, "image_url": "https://farm/1.png" },
+                { "index": 2, "image_url": "", "metadata_url": "https://farm/2.json" }
+            ])
+            .to_string(),
+        )
+        .unwrap();
+
+        let err = read_manifest(&path_str).unwrap_err().to_string();
+        assert!(err.starts_with("2 manifest entr(ies)"), "{err}");
+        assert!(err.contains("index 1: missing metadata_url"));
+        assert!(err.contains("index 2: missing image_url"));
+
+        fs::write(
+            &path,
+            json!([
+                { "index": 0, "image_url": "https://farm/0.png", "metadata_url": "https://farm/0.json" },
+                { "index": 0, "image_url": "https://farm/1.png", "metadata_url": "https://farm/1.json" }
+            ])
+            .to_string(),
+        )
+        .unwrap();
+        assert!(read_manifest(&path_str).is_err());
+
+        fs::write(
+            &path,
+            json!([
+                { "index": -1, "image_url": "https://farm/c.png", "metadata_url": "https://farm/c.json" },
+                { "index": 0, "image_url": "https://farm/0.png", "metadata_url": "https://farm/0.json" }
+            ])
+            .to_string(),
+        )
+        .unwrap();
+        assert_eq!(read_manifest(&path_str).unwrap().len(), 2);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
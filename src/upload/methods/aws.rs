@@ -1,6 +1,7 @@
-use std::{fs, sync::Arc};
+use std::sync::Arc;
 
 use async_trait::async_trait;
+use futures::{stream, Stream, StreamExt};
 use ini::ini;
 use s3::{bucket::Bucket, creds::Credentials, region::Region};
 use tokio::task::JoinHandle;
@@ -10,6 +11,8 @@ use crate::{
     config::*,
     upload::{
         assets::{AssetPair, DataType},
+        remote::read_file,
+        streaming::{file_chunks, should_stream, streaming_threshold, STREAM_CHUNK_SIZE},
         uploader::{AssetInfo, ParallelUploader, Prepare},
     },
 };
@@ -17,60 +20,88 @@ use crate::{
 // Maximum number of times to retry each individual upload.
 const MAX_RETRY: u8 = 3;
 
+/// Upload method for AWS S3 and S3-compatible storages (MinIO, R2), serving the files from the
+/// bucket domain or a custom domain (e.g. a CloudFront distribution).
+///
+/// The credentials are read from the standard AWS environment variables or the profile.
 pub struct AWSMethod {
     pub bucket: Arc<Bucket>,
     pub directory: String,
     pub domain: String,
+    /// Size (in bytes) above which a local file is streamed from disk.
+    pub streaming_threshold: u64,
 }
 
 impl AWSMethod {
     pub async fn new(config_data: &ConfigData) -> Result<Self> {
-        let profile = &config_data
+        let config = config_data
             .aws_config
             .as_ref()
-            .ok_or_else(|| anyhow!("AWS values not specified in config file!"))?
-            .profile;
-
-        let credentials = Credentials::from_profile(Some(profile))?;
-        let region = AWSMethod::load_region(config_data)?;
-
-        if let Some(config) = &config_data.aws_config {
-            let domain = if let Some(domain) = &config.domain {
-                match url::Url::parse(domain) {
-                    Ok(url) => url.to_string(),
-                    Err(error) => {
-                        return Err(anyhow!("Malformed domain URL ({})", error.to_string()))
-                    }
-                }
-            } else {
-                format!("https://{}.s3.amazonaws.com", &config.bucket)
-            };
+            .ok_or_else(|| anyhow!("AWS values not specified in config file!"))?;
+
+        let credentials = Credentials::from_env()
+            .or_else(|_| Credentials::from_profile(Some(&config.profile)))
+            .map_err(|error| anyhow!("Failed to load the AWS credentials: {}", error))?;
 
-            Ok(Self {
-                bucket: Arc::new(Bucket::new(&config.bucket, region, credentials)?),
-                directory: config.directory.clone(),
-                domain,
-            })
+        AWSMethod::with_credentials(config, credentials, streaming_threshold(config_data))
+    }
+
+    fn with_credentials(
+        config: &AwsConfig,
+        credentials: Credentials,
+        streaming_threshold: u64,
+    ) -> Result<Self> {
+        let region = AWSMethod::load_region(config)?;
+
+        let domain = if let Some(domain) = &config.domain {
+            match url::Url::parse(domain) {
+                Ok(url) => url.to_string(),
+                Err(error) => return Err(anyhow!("Malformed domain URL ({})", error.to_string())),
+            }
+        } else if let Some(endpoint) = &config.endpoint {
+            format!("{}/{}/", endpoint.trim_end_matches('/'), &config.bucket)
         } else {
-            Err(anyhow!("Missing AwsConfig 'bucket' value in config file."))
-        }
+            format!("https://{}.s3.amazonaws.com", &config.bucket)
+        };
+
+        Ok(Self {
+            bucket: Arc::new(AWSMethod::bucket(config, region, credentials)?),
+            directory: config.directory.clone(),
+            domain,
+            streaming_threshold,
+        })
+    }
+
+    /// Bucket of the config, addressed by path on a custom endpoint.
+    fn bucket(config: &AwsConfig, region: String, credentials: Credentials) -> Result<Bucket> {
+        Ok(match &config.endpoint {
+            Some(endpoint) => Bucket::new(
+                &config.bucket,
+                Region::Custom {
+                    region,
+                    endpoint: endpoint.clone(),
+                },
+                credentials,
+            )?
+            .with_path_style(),
+            None => Bucket::new(&config.bucket, region.parse()?, credentials)?,
+        })
     }
 
-    fn load_region(config_data: &ConfigData) -> Result<Region> {
+    /// Region of the config, or of the profile in the AWS credentials file.
+    fn load_region(config: &AwsConfig) -> Result<String> {
+        if let Some(region) = &config.region {
+            return Ok(region.clone());
+        }
+
         let home_dir = dirs::home_dir().expect("Couldn't find home dir.");
         let credentials = home_dir.join(Path::new(".aws/credentials"));
         let configuration = ini!(credentials
             .to_str()
             .ok_or_else(|| anyhow!("Failed to load AWS credentials"))?);
 
-        let profile = &config_data
-            .aws_config
-            .as_ref()
-            .ok_or_else(|| anyhow!("AWS values not specified in config file!"))?
-            .profile;
-
-        let region = &configuration
-            .get(profile)
+        let region = configuration
+            .get(&config.profile)
             .ok_or_else(|| anyhow!("Profile not found in AWS credentials file!"))?
             .get("region")
             .ok_or_else(|| anyhow!("Region not found in AWS credentials file!"))?
@@ -78,21 +109,16 @@ impl AWSMethod {
             .ok_or_else(|| anyhow!("Region not found in AWS credentials file!"))?
             .to_string();
 
-        Ok(region.parse()?)
+        Ok(region)
     }
 
     async fn send(
         bucket: Arc<Bucket>,
         directory: String,
         domain: String,
+        streaming_threshold: u64,
         asset_info: AssetInfo,
     ) -> Result<(String, String)> {
-        let data = match asset_info.data_type {
-            DataType::Image => fs::read(&asset_info.content)?,
-            DataType::Metadata => asset_info.content.into_bytes(),
-            DataType::Animation => fs::read(&asset_info.content)?,
-        };
-
         // Take care of any spaces in the directory path.
         let directory = directory.replace(' ', "_");
 
@@ -101,27 +127,54 @@ impl AWSMethod {
             .to_str()
             .ok_or_else(|| anyhow!("Failed to convert S3 bucket directory path to string."))?;
 
+        // large files are streamed from disk (or from their URL) with a multipart upload
+        if should_stream(&asset_info, streaming_threshold) {
+            put_multipart_file(&bucket, path_str, &asset_info).await?;
+            let link = url::Url::parse(&domain)?.join(path_str)?;
+            return Ok((asset_info.asset_id, link.to_string()));
+        }
+
+        let data = match asset_info.data_type {
+            DataType::Image => read_file(&asset_info).await?,
+            DataType::Metadata => asset_info.content.into_bytes(),
+            DataType::Animation => read_file(&asset_info).await?,
+        };
+
         let mut retry = MAX_RETRY;
         // send data to AWS S3 with a simple retry logic (mitigates dns lookup errors)
         loop {
-            match bucket
-                .put_object_with_content_type(path_str, &data, &asset_info.content_type)
+            let result = if data.len() > STREAM_CHUNK_SIZE {
+                let parts = data.chunks(STREAM_CHUNK_SIZE).map(|part| Ok(part.to_vec()));
+                put_multipart(
+                    &bucket,
+                    path_str,
+                    &asset_info.content_type,
+                    stream::iter(parts),
+                )
                 .await
-            {
-                Ok((_, code)) => match code {
-                    200 => {
-                        break;
-                    }
-                    _ => {
-                        return Err(anyhow!(
-                            "Failed to upload {} to S3 with Http Code: {code}",
-                            asset_info.name
-                        ));
-                    }
-                },
+            } else {
+                match bucket
+                    .put_object_with_content_type(path_str, &data, &asset_info.content_type)
+                    .await
+                {
+                    Ok(response) => match response.status_code() {
+                        200 => Ok(()),
+                        code => {
+                            return Err(anyhow!(
+                                "Failed to upload {} to S3 with Http Code: {code}",
+                                asset_info.name
+                            ));
+                        }
+                    },
+                    Err(error) => Err(error.into()),
+                }
+            };
+
+            match result {
+                Ok(()) => break,
                 Err(error) => {
                     if retry == 0 {
-                        return Err(error.into());
+                        return Err(error);
                     }
                     // we try one more time before reporting the error
                     retry -= 1;
@@ -135,6 +188,76 @@ impl AWSMethod {
     }
 }
 
+/// Streams a file from disk (or from its URL) with a multipart upload, retrying the whole
+/// upload on failure.
+async fn put_multipart_file(bucket: &Bucket, key: &str, asset_info: &AssetInfo) -> Result<()> {
+    let mut retry = MAX_RETRY;
+
+    loop {
+        let chunks = file_chunks(asset_info, STREAM_CHUNK_SIZE)?;
+
+        match put_multipart(bucket, key, &asset_info.content_type, chunks).await {
+            Ok(()) => return Ok(()),
+            Err(error) if retry == 0 => return Err(error),
+            // we try one more time before reporting the error
+            Err(_) => retry -= 1,
+        }
+    }
+}
+
+/// Uploads the parts of the `chunks` stream, aborting the upload on failure so that no parts
+/// are left in the bucket. Only one part is in memory at a time.
+async fn put_multipart(
+    bucket: &Bucket,
+    key: &str,
+    content_type: &str,
+    mut chunks: impl Stream<Item = Result<Vec<u8>>> + Unpin,
+) -> Result<()> {
+    let upload = bucket.initiate_multipart_upload(key, content_type).await?;
+    let mut parts = Vec::new();
+
+    loop {
+        let part = match chunks.next().await {
+            Some(Ok(part)) => part,
+            None => break,
+            Some(Err(error)) => {
+                let _ = bucket.abort_upload(key, &upload.upload_id).await;
+                return Err(error);
+            }
+        };
+
+        match bucket
+            .put_multipart_chunk(
+                part,
+                key,
+                parts.len() as u32 + 1,
+                &upload.upload_id,
+                content_type,
+            )
+            .await
+        {
+            Ok(part) => parts.push(part),
+            Err(error) => {
+                let _ = bucket.abort_upload(key, &upload.upload_id).await;
+                return Err(error.into());
+            }
+        }
+    }
+
+    let response = bucket
+        .complete_multipart_upload(key, &upload.upload_id, parts)
+        .await?;
+
+    match response.status_code() {
+        200 => Ok(()),
+        code => Err(anyhow!(
+            "Failed to complete the multipart upload of {} with Http Code: {}",
+            key,
+            code
+        )),
+    }
+}
+
 #[async_trait]
 impl Prepare for AWSMethod {
     async fn prepare(
@@ -154,7 +277,114 @@ impl ParallelUploader for AWSMethod {
         let bucket = self.bucket.clone();
         let directory = self.directory.clone();
         let domain = self.domain.clone();
+        let streaming_threshold = self.streaming_threshold;
+
+        tokio::spawn(async move {
+            AWSMethod::send(bucket, directory, domain, streaming_threshold, asset_info).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::methods::test_utils::{serve, upload_collection};
+
+    fn aws_config(endpoint: &str, domain: Option<&str>) -> AwsConfig {
+        AwsConfig {
+            bucket: "sugar".to_string(),
+            profile: "default".to_string(),
+            directory: "my drop".to_string(),
+            domain: domain.map(String::from),
+            region: Some("us-east-1".to_string()),
+            endpoint: Some(endpoint.to_string()),
+        }
+    }
+
+    fn method(config: AwsConfig) -> AWSMethod {
+        let credentials = Credentials {
+            access_key: Some("key".to_string()),
+            secret_key: Some("secret".to_string()),
+            security_token: None,
+            session_token: None,
+            expiration: None,
+        };
+
+        AWSMethod::with_credentials(&config, credentials, u64::MAX).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_upload_to_endpoint() {
+        let (base_url, requests) = serve(|_| (200, String::new()));
+        let method = method(aws_config(&base_url, None));
+
+        let cache = upload_collection(&method).await;
+
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            format!("{base_url}/sugar/my_drop/collection.json")
+        );
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert!(requests
+            .iter()
+            .any(|request| request.method == "PUT" && request.path == "/sugar/my_drop/0.json"));
+    }
+
+    #[tokio::test]
+    async fn test_public_base_url() {
+        let (base_url, _) = serve(|_| (200, String::new()));
+        let method = method(aws_config(&base_url, Some("https://cdn.example.com")));
+
+        let cache = upload_collection(&method).await;
+
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            "https://cdn.example.com/my_drop/collection.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_failed_upload() {
+        let (base_url, _) = serve(|_| (403, String::new()));
+        let method = method(aws_config(&base_url, None));
+
+        let error = AWSMethod::send(
+            method.bucket.clone(),
+            method.directory.clone(),
+            method.domain.clone(),
+            method.streaming_threshold,
+            AssetInfo {
+                asset_id: "0".to_string(),
+                name: "0.json".to_string(),
+                content: "{}".to_string(),
+                data_type: DataType::Metadata,
+                content_type: "application/json".to_string(),
+                remote: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert!(error.to_string().contains("403"), "{error}");
+    }
+
+    #[test]
+    fn test_config_aliases() {
+        let config: AwsConfig = serde_json::from_value(json!({
+            "bucket": "sugar",
+            "profile": "default",
+            "prefix": "drops",
+            "publicBaseUrl": "https://d111111abcdef8.cloudfront.net",
+            "endpoint": "https://account.r2.cloudflarestorage.com"
+        }))
+        .unwrap();
 
-        tokio::spawn(async move { AWSMethod::send(bucket, directory, domain, asset_info).await })
+        assert_eq!(config.directory, "drops");
+        assert_eq!(
+            config.domain.as_deref(),
+            Some("https://d111111abcdef8.cloudfront.net")
+        );
+        assert_eq!(config.region, None);
     }
 }
@@ -3,8 +3,9 @@ use std::{cmp, fs, path::Path, sync::Arc};
 use anchor_client::solana_sdk::native_token::LAMPORTS_PER_SOL;
 use async_trait::async_trait;
 use bundlr_sdk::{tags::Tag, Bundlr, Ed25519Signer as SolanaSigner};
-use clap::crate_version;
-use console::style;
+use data_encoding::BASE64URL_NOPAD;
+use futures::{StreamExt, TryStreamExt};
+use sha2::{Digest, Sha256, Sha384};
 use solana_client::rpc_client::RpcClient;
 use tokio::{
     task::JoinHandle,
@@ -15,8 +16,11 @@ use crate::{
     candy_machine::CANDY_MACHINE_ID,
     common::*,
     config::*,
+    output::{has_terminal, style},
     upload::{
         assets::{get_updated_metadata, AssetPair, DataType},
+        remote::read_file,
+        streaming::{file_chunks, should_stream, streaming_threshold, STREAM_CHUNK_SIZE},
         uploader::{AssetInfo, ParallelUploader, Prepare, MOCK_URI_SIZE},
     },
     utils::*,
@@ -34,30 +38,32 @@ const HEADER_SIZE: u64 = 2_000;
 /// Minimum file size for cost calculation
 const MINIMUM_SIZE: u64 = 80_000;
 
+/// Value of the `App-Name` tag of the uploads
+const APP_NAME: &str = concat!("Sugar ", env!("CARGO_PKG_VERSION"));
+
+/// Signature type of the ed25519 (Solana) data items
+const ED25519_SIGNATURE_TYPE: u16 = 2;
+
+/// Header selecting the version of the chunked upload API
+const CHUNKING_VERSION_HEADER: &str = "x-chunking-version";
+
 pub struct BundlrMethod {
     pub client: Arc<Bundlr<SolanaSigner>>,
     pub sugar_tag: Tag,
+    chunked: Arc<ChunkedUploader>,
     pubkey: Pubkey,
     node: String,
+    funding: BundlrConfig,
 }
 
 impl BundlrMethod {
-    pub async fn new(sugar_config: &SugarConfig, _config_data: &ConfigData) -> Result<Self> {
-        let client = setup_client(sugar_config)?;
-        let program = client.program(CANDY_MACHINE_ID);
-        let solana_cluster: Cluster = get_cluster(program.rpc())?;
-
-        let bundlr_node = match solana_cluster {
-            Cluster::Devnet => BUNDLR_DEVNET,
-            Cluster::Mainnet => BUNDLR_MAINNET,
-            Cluster::Unknown | Cluster::Localnet => {
-                return Err(anyhow!("Bundlr is only supported on devnet or mainnet"));
-            }
-        };
+    pub async fn new(sugar_config: &SugarConfig, config_data: &ConfigData) -> Result<Self> {
+        let bundlr_node =
+            BundlrMethod::get_bundlr_node(sugar_config, config_data.bundlr_node.as_ref())?;
 
         let http_client = reqwest::Client::new();
         let bundlr_address =
-            BundlrMethod::get_bundlr_solana_address(&http_client, bundlr_node).await?;
+            BundlrMethod::get_bundlr_solana_address(&http_client, &bundlr_node).await?;
 
         let bundlr_pubkey = Pubkey::from_str(&bundlr_address)?;
         // get keypair as base58 string for Bundlr
@@ -65,22 +71,80 @@ impl BundlrMethod {
         let signer = SolanaSigner::from_base58(&keypair);
 
         let bundlr_client = Bundlr::new(
-            bundlr_node.to_string(),
+            bundlr_node.clone(),
             "solana".to_string(),
             "sol".to_string(),
             signer,
         );
 
-        let sugar_tag = Tag::new("App-Name".into(), format!("Sugar {}", crate_version!()));
+        let sugar_tag = Tag::new("App-Name".into(), APP_NAME.to_string());
+
+        let chunked = ChunkedUploader {
+            http_client,
+            node: bundlr_node.clone(),
+            keypair: Keypair::from_bytes(&sugar_config.keypair.to_bytes())?,
+            streaming_threshold: streaming_threshold(config_data),
+        };
 
         Ok(Self {
             client: Arc::new(bundlr_client),
             pubkey: bundlr_pubkey,
             sugar_tag,
-            node: bundlr_node.to_string(),
+            chunked: Arc::new(chunked),
+            node: bundlr_node,
+            funding: config_data.bundlr_config.clone().unwrap_or_default(),
         })
     }
 
+    /// Return the URL of the Bundlr node: the selected node, or the node of the cluster when
+    /// no node is selected.
+    pub fn get_bundlr_node(
+        sugar_config: &SugarConfig,
+        bundlr_node: Option<&BundlrNode>,
+    ) -> Result<String> {
+        let client = setup_client(sugar_config)?;
+        let program = client.program(CANDY_MACHINE_ID);
+        let solana_cluster: Cluster = get_cluster(program.rpc())?;
+
+        if let Some(node) = bundlr_node {
+            // the funds of a node are only valid on its cluster
+            match (node.cluster(), &solana_cluster) {
+                (Some(Cluster::Devnet), Cluster::Mainnet)
+                | (Some(Cluster::Mainnet), Cluster::Devnet) => Err(anyhow!(
+                    "Bundlr node '{}' is not a {} node",
+                    node,
+                    solana_cluster
+                )),
+                _ => Ok(node.url().to_string()),
+            }
+        } else {
+            match solana_cluster {
+                Cluster::Devnet => Ok(BUNDLR_DEVNET.to_string()),
+                Cluster::Mainnet => Ok(BUNDLR_MAINNET.to_string()),
+                Cluster::Unknown | Cluster::Localnet => Err(anyhow!(
+                    "Bundlr is only supported on devnet or mainnet, select a node with \
+                    --bundlr-node"
+                )),
+            }
+        }
+    }
+
+    /// Return the Bundlr fee (in lamports) for the upload of files of the sizes, without
+    /// funding or uploading anything.
+    pub async fn estimate_fee(
+        sugar_config: &SugarConfig,
+        config_data: &ConfigData,
+        file_sizes: &[u64],
+    ) -> Result<u64> {
+        let node = BundlrMethod::get_bundlr_node(sugar_config, config_data.bundlr_node.as_ref())?;
+        let total_size = file_sizes
+            .iter()
+            .map(|size| HEADER_SIZE + cmp::max(MINIMUM_SIZE, *size))
+            .sum();
+
+        BundlrMethod::get_bundlr_fee(&reqwest::Client::new(), &node, total_size).await
+    }
+
     /// Return the solana address for Bundlr.
     async fn get_bundlr_solana_address(http_client: &HttpClient, node: &str) -> Result<String> {
         let url = format!("{}/info", node);
@@ -118,9 +182,9 @@ impl BundlrMethod {
             recent_blockhash,
         );
 
-        println!("Funding address:");
-        println!("  -> pubkey: {}", payer_pubkey);
-        println!(
+        report!("Funding address:");
+        report!("  -> pubkey: {}", payer_pubkey);
+        report!(
             "  -> lamports: {} (◎ {})",
             amount,
             amount as f64 / LAMPORTS_PER_SOL as f64
@@ -131,7 +195,7 @@ impl BundlrMethod {
             CommitmentConfig::confirmed(),
         )?;
 
-        println!("{} {sig}", style("Signature:").bold());
+        report!("{} {sig}", style("Signature:").bold());
 
         let mut map = HashMap::new();
         map.insert("tx_id", sig.to_string());
@@ -152,13 +216,10 @@ impl BundlrMethod {
         let response = http_client.get(&url).send().await?.json::<Value>().await?;
         let value = response
             .get("balance")
-            .expect("Failed to get balance from bundlr.");
+            .ok_or_else(|| anyhow!("Failed to get balance from bundlr."))?;
 
-        Ok(value
-            .as_str()
-            .unwrap()
-            .parse::<u64>()
-            .expect("Failed to parse bundlr balance."))
+        // Irys nodes return the balance as a string, legacy nodes as a number
+        parse_u64(value).ok_or_else(|| anyhow!("Failed to parse bundlr balance: {value}"))
     }
 
     /// Return the Bundlr fee for upload based on the data size.
@@ -175,27 +236,33 @@ impl BundlrMethod {
 
     async fn send(
         client: Arc<Bundlr<SolanaSigner>>,
+        chunked: Arc<ChunkedUploader>,
         tag: Tag,
         asset_info: AssetInfo,
     ) -> Result<(String, String)> {
-        let data = match asset_info.data_type {
-            DataType::Image => fs::read(&asset_info.content)?,
-            DataType::Metadata => asset_info.content.into_bytes(),
-            DataType::Animation => fs::read(&asset_info.content)?,
+        let receipt = if should_stream(&asset_info, chunked.streaming_threshold) {
+            let tags = [
+                ("App-Name", APP_NAME),
+                ("Content-Type", asset_info.content_type.as_str()),
+            ];
+            chunked.upload(&asset_info, &tags).await?
+        } else {
+            let data = match asset_info.data_type {
+                DataType::Image => read_file(&asset_info).await?,
+                DataType::Metadata => asset_info.content.into_bytes(),
+                DataType::Animation => read_file(&asset_info).await?,
+            };
+
+            let tags = vec![
+                tag,
+                Tag::new("Content-Type".into(), asset_info.content_type.clone()),
+            ];
+
+            let tx = client.create_transaction_with_tags(data, tags);
+            let response = client.send_transaction(tx).await?;
+            parse_receipt(response)?
         };
-
-        let tags = vec![
-            tag,
-            Tag::new("Content-Type".into(), asset_info.content_type.clone()),
-        ];
-
-        let tx = client.create_transaction_with_tags(data, tags);
-        let response = client.send_transaction(tx).await?;
-        let id = response
-            .get("id")
-            .expect("Failed to convert transaction id to string.")
-            .as_str()
-            .expect("Failed to get an id from bundlr transaction.");
+        let id = receipt.id;
 
         // Get extension for the asset type.
         let ext = asset_info
@@ -231,9 +298,7 @@ impl Prepare for BundlrMethod {
                 DataType::Image => {
                     for index in indices {
                         let item = assets.get(index).unwrap();
-                        let path = Path::new(&item.image);
-                        total_size +=
-                            HEADER_SIZE + cmp::max(MINIMUM_SIZE, fs::metadata(path)?.len());
+                        total_size += HEADER_SIZE + cmp::max(MINIMUM_SIZE, item.image_size()?);
                     }
                 }
                 DataType::Animation => {
@@ -261,7 +326,7 @@ impl Prepare for BundlrMethod {
                         total_size += HEADER_SIZE
                             + cmp::max(
                                 MINIMUM_SIZE,
-                                get_updated_metadata(&item.metadata, &mock_uri.clone(), &animation)?
+                                get_updated_metadata(item, &mock_uri.clone(), &animation)?
                                     .into_bytes()
                                     .len() as u64,
                             );
@@ -294,8 +359,28 @@ impl Prepare for BundlrMethod {
         };
 
         if lamports_fee > balance {
-            // calculates the additional amount to fund the wallet, with padding.
-            let amount = ((lamports_fee - balance) as f64 * 1.3).ceil() as u64;
+            if !self.funding.auto_fund {
+                return Err(anyhow!(
+                    "Bundlr balance of {} lamports is not enough for the upload ({} lamports \
+                    required), run the upload with --auto-fund to fund it",
+                    balance,
+                    lamports_fee
+                ));
+            }
+
+            // calculates the additional amount to fund the wallet, with the safety margin
+            let amount = fund_amount(lamports_fee, balance, self.funding.fund_margin_percent);
+
+            if let Some(max_fund_lamports) = self.funding.max_fund_lamports {
+                if amount > max_fund_lamports {
+                    return Err(anyhow!(
+                        "The upload requires funding Bundlr with {} lamports, over the \
+                        --max-fund-lamports limit of {} lamports",
+                        amount,
+                        max_fund_lamports
+                    ));
+                }
+            }
 
             BundlrMethod::fund_bundlr_address(
                 rpc_client,
@@ -307,7 +392,11 @@ impl Prepare for BundlrMethod {
             )
             .await?;
 
-            let pb = ProgressBar::new(MAX_RETRY);
+            let pb = if has_terminal() {
+                ProgressBar::new(MAX_RETRY)
+            } else {
+                ProgressBar::hidden()
+            };
             pb.set_style(ProgressStyle::default_bar().template("{spinner} {msg} {wide_bar}"));
             pb.enable_steady_tick(60);
             pb.set_message("Verifying balance:");
@@ -341,6 +430,8 @@ impl Prepare for BundlrMethod {
                 error!("{error}");
                 return Err(error);
             }
+
+            report!("{} {} lamports", style("Bundlr balance:").bold(), balance);
         }
 
         Ok(())
@@ -351,7 +442,429 @@ impl Prepare for BundlrMethod {
 impl ParallelUploader for BundlrMethod {
     fn upload_asset(&self, asset_info: AssetInfo) -> JoinHandle<Result<(String, String)>> {
         let client = self.client.clone();
+        let chunked = self.chunked.clone();
         let tag = self.sugar_tag.clone();
-        tokio::spawn(async move { BundlrMethod::send(client, tag, asset_info).await })
+        tokio::spawn(async move { BundlrMethod::send(client, chunked, tag, asset_info).await })
+    }
+}
+
+/// Upload of the files over the streaming threshold with the chunked upload API of the node.
+/// The data item (ANS-104) is signed with the keypair after a first read of the file (a first
+/// download of a remote file), which is then read again and sent one chunk at a time.
+struct ChunkedUploader {
+    http_client: HttpClient,
+    node: String,
+    keypair: Keypair,
+    streaming_threshold: u64,
+}
+
+/// Upload session returned by the node, with the limits of the chunk size.
+#[derive(Debug, Deserialize)]
+struct ChunkedSession {
+    id: String,
+    min: u64,
+    max: u64,
+}
+
+impl ChunkedUploader {
+    async fn upload(&self, asset_info: &AssetInfo, tags: &[(&str, &str)]) -> Result<Receipt> {
+        let mut hasher = Sha384::new();
+        let size = file_chunks(asset_info, STREAM_CHUNK_SIZE)?
+            .try_fold(0, |size, chunk| {
+                hasher.update(&chunk);
+                futures::future::ok(size + chunk.len() as u64)
+            })
+            .await?;
+        let (header, id) = data_item_header(&self.keypair, tags, size, &hasher.finalize());
+
+        let session: ChunkedSession = self.chunks_request("-1", -1, Vec::new()).await?;
+        let chunk_size = cmp::max(
+            cmp::max(session.min, 1),
+            cmp::min(STREAM_CHUNK_SIZE as u64, session.max),
+        ) as usize;
+        debug!("Uploading data item {id} in chunks of {chunk_size} bytes");
+
+        // the header and the file are sent as a single data item
+        let mut pending = header;
+        let mut offset = 0;
+        let mut chunks = file_chunks(asset_info, chunk_size)?;
+
+        loop {
+            let chunk = chunks.next().await.transpose()?;
+            let last = chunk.is_none();
+            if let Some(chunk) = chunk {
+                pending.extend_from_slice(&chunk);
+            }
+
+            while pending.len() >= chunk_size || (last && !pending.is_empty()) {
+                let rest = pending.split_off(cmp::min(chunk_size, pending.len()));
+                let chunk = std::mem::replace(&mut pending, rest);
+                let length = chunk.len();
+                self.chunks_request::<Value>(&session.id, offset as i64, chunk)
+                    .await?;
+                offset += length;
+            }
+
+            if last {
+                break;
+            }
+        }
+
+        let response: Value = self.chunks_request(&session.id, -1, Vec::new()).await?;
+        let receipt = parse_receipt(response)?;
+
+        if receipt.id != id {
+            return Err(anyhow!(
+                "Bundlr receipt id {} does not match the data item {}",
+                receipt.id,
+                id
+            ));
+        }
+
+        Ok(receipt)
+    }
+
+    /// Request of the chunked upload API: `GET` of a new session (`-1/-1`), `POST` of the
+    /// chunk at `offset` or of the end of the upload (`-1` offset).
+    async fn chunks_request<T: serde::de::DeserializeOwned>(
+        &self,
+        id: &str,
+        offset: i64,
+        chunk: Vec<u8>,
+    ) -> Result<T> {
+        let url = format!("{}/chunks/solana/{}/{}", self.node, id, offset);
+        let request = if id == "-1" {
+            self.http_client.get(&url)
+        } else {
+            self.http_client
+                .post(&url)
+                .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+                .body(chunk)
+        };
+
+        let response = request.header(CHUNKING_VERSION_HEADER, "2").send().await?;
+        let status = response.status();
+        let body = response.text().await?;
+
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Error uploading chunk {offset} of {id} to Bundlr ({status}): {body}"
+            ));
+        }
+
+        // the chunks are acknowledged without a body
+        serde_json::from_str(if body.is_empty() { "null" } else { &body })
+            .map_err(|err| anyhow!("Invalid Bundlr response {body} ({err})"))
+    }
+}
+
+/// Signed header of an ed25519 data item (ANS-104) without target and anchor, followed by
+/// the data of `size` bytes with the sha-384 `digest`. Returns the header and the id of the
+/// data item.
+fn data_item_header(
+    keypair: &Keypair,
+    tags: &[(&str, &str)],
+    size: u64,
+    digest: &[u8],
+) -> (Vec<u8>, String) {
+    let owner = keypair.pubkey().to_bytes();
+    let encoded_tags = encode_tags(tags);
+
+    let message = deep_hash_list(&[
+        deep_hash_blob(b"dataitem"),
+        deep_hash_blob(b"1"),
+        deep_hash_blob(ED25519_SIGNATURE_TYPE.to_string().as_bytes()),
+        deep_hash_blob(&owner),
+        deep_hash_blob(&[]),
+        deep_hash_blob(&[]),
+        deep_hash_blob(&encoded_tags),
+        deep_hash_digest(size, digest),
+    ]);
+    let signature = keypair.sign_message(&message);
+    let signature = signature.as_ref();
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&ED25519_SIGNATURE_TYPE.to_le_bytes());
+    header.extend_from_slice(signature);
+    header.extend_from_slice(&owner);
+    // no target and no anchor
+    header.extend_from_slice(&[0, 0]);
+    header.extend_from_slice(&(tags.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(encoded_tags.len() as u64).to_le_bytes());
+    header.extend_from_slice(&encoded_tags);
+
+    let id = BASE64URL_NOPAD.encode(&Sha256::digest(signature));
+
+    (header, id)
+}
+
+/// Avro encoding of the `(name, value)` tags of a data item.
+fn encode_tags(tags: &[(&str, &str)]) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    if tags.is_empty() {
+        return buffer;
+    }
+
+    write_zigzag(&mut buffer, tags.len() as u64);
+    for (name, value) in tags {
+        for field in [name, value] {
+            write_zigzag(&mut buffer, field.len() as u64);
+            buffer.extend_from_slice(field.as_bytes());
+        }
+    }
+    // end of the array
+    buffer.push(0);
+    buffer
+}
+
+/// Zigzag varint of a (positive) avro long.
+fn write_zigzag(buffer: &mut Vec<u8>, value: u64) {
+    let mut value = value << 1;
+    while value >= 0x80 {
+        buffer.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// Deep hash of a blob.
+fn deep_hash_blob(data: &[u8]) -> Vec<u8> {
+    deep_hash_digest(data.len() as u64, &Sha384::digest(data))
+}
+
+/// Deep hash of a blob of `size` bytes from its sha-384 `digest`.
+fn deep_hash_digest(size: u64, digest: &[u8]) -> Vec<u8> {
+    let tag = Sha384::digest(format!("blob{size}").as_bytes());
+    Sha384::new()
+        .chain_update(tag)
+        .chain_update(digest)
+        .finalize()
+        .to_vec()
+}
+
+/// Deep hash of a list from the deep hashes of its items.
+fn deep_hash_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let tag = Sha384::digest(format!("list{}", items.len()).as_bytes());
+    items.iter().fold(tag.to_vec(), |accumulator, item| {
+        Sha384::new()
+            .chain_update(accumulator)
+            .chain_update(item)
+            .finalize()
+            .to_vec()
+    })
+}
+
+/// Receipt of a Bundlr transaction.
+///
+/// Legacy Bundlr nodes return `{id, signature, block}`, Irys nodes return
+/// `{id, timestamp, version, public, signature, deadlineHeight}`.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Receipt {
+    pub id: String,
+    pub signature: Option<String>,
+    pub timestamp: Option<u64>,
+    pub version: Option<String>,
+    #[serde(alias = "block")]
+    pub deadline_height: Option<u64>,
+}
+
+/// Parse the receipt of a Bundlr transaction, checking that it has an id and a signature.
+fn parse_receipt(response: Value) -> Result<Receipt> {
+    let receipt: Receipt = serde_json::from_value(response.clone())
+        .map_err(|err| anyhow!("Invalid bundlr receipt {response} ({err})"))?;
+
+    if receipt.id.is_empty() {
+        return Err(anyhow!(
+            "Bundlr receipt has an empty transaction id: {response}"
+        ));
+    }
+
+    if receipt.signature.as_deref().map_or(true, str::is_empty) {
+        return Err(anyhow!(
+            "Bundlr receipt of transaction {} is not signed",
+            receipt.id
+        ));
+    }
+
+    Ok(receipt)
+}
+
+/// Parse a number returned by a Bundlr node either as a string or a number.
+pub fn parse_u64(value: &Value) -> Option<u64> {
+    match value {
+        Value::String(value) => value.parse::<u64>().ok(),
+        value => value.as_u64(),
+    }
+}
+
+/// Amount to fund for a fee, over the balance by a margin (in percent) of the difference.
+fn fund_amount(fee: u64, balance: u64, margin_percent: u32) -> u64 {
+    let missing = fee.saturating_sub(balance);
+    missing + (missing * margin_percent as u64 + 99) / 100
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::upload::methods::test_utils::serve;
+
+    #[test]
+    fn test_fund_amount() {
+        assert_eq!(fund_amount(1_000, 0, 30), 1_300);
+        assert_eq!(fund_amount(1_000, 400, 0), 600);
+        // the margin is rounded up
+        assert_eq!(fund_amount(1_001, 0, 30), 1_302);
+        assert_eq!(fund_amount(1_000, 2_000, 30), 0);
+    }
+
+    #[test]
+    fn test_parse_receipt() {
+        let legacy = json!({
+            "id": "legacy-id",
+            "signature": "c2lnbmF0dXJl",
+            "block": 1_100_000,
+        });
+        let receipt = parse_receipt(legacy).unwrap();
+        assert_eq!(receipt.id, "legacy-id");
+        assert_eq!(receipt.deadline_height, Some(1_100_000));
+
+        let irys = json!({
+            "id": "irys-id",
+            "timestamp": 1_700_000_000_000u64,
+            "version": "1.0.0",
+            "public": "cHVibGlj",
+            "signature": "c2lnbmF0dXJl",
+            "deadlineHeight": 1_300_000,
+        });
+        let receipt = parse_receipt(irys).unwrap();
+        assert_eq!(receipt.id, "irys-id");
+        assert_eq!(receipt.version.as_deref(), Some("1.0.0"));
+        assert_eq!(receipt.deadline_height, Some(1_300_000));
+
+        assert!(parse_receipt(json!({ "id": "unsigned-id" })).is_err());
+        assert!(parse_receipt(json!({ "signature": "c2lnbmF0dXJl" })).is_err());
+    }
+
+    #[test]
+    fn test_parse_u64() {
+        assert_eq!(parse_u64(&json!("12345")), Some(12_345));
+        assert_eq!(parse_u64(&json!(12_345)), Some(12_345));
+        assert_eq!(parse_u64(&json!("-1")), None);
+    }
+
+    #[tokio::test]
+    async fn test_chunked_upload() {
+        // the node answers with the id of the data item, from the signature of its first chunk
+        let signature = Arc::new(Mutex::new(Vec::new()));
+        let received = signature.clone();
+        let (node, requests) = serve(move |request| match request.path.as_str() {
+            "/chunks/solana/-1/-1" => (
+                200,
+                json!({ "id": "upload-id", "min": 1, "max": 100_000 }).to_string(),
+            ),
+            "/chunks/solana/upload-id/0" => {
+                *received.lock().unwrap() = request.body[2..66].to_vec();
+                (200, String::new())
+            }
+            "/chunks/solana/upload-id/-1" => {
+                let id = BASE64URL_NOPAD.encode(&Sha256::digest(&*received.lock().unwrap()));
+                (
+                    200,
+                    json!({ "id": id, "signature": "c2lnbmF0dXJl" }).to_string(),
+                )
+            }
+            _ => (200, String::new()),
+        });
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.mp4");
+        let content: Vec<u8> = (0..250_000).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &content).unwrap();
+
+        let keypair = Keypair::new();
+        let signer = SolanaSigner::from_base58(&bs58::encode(keypair.to_bytes()).into_string());
+        let client = Bundlr::new(node.clone(), "solana".into(), "sol".into(), signer);
+        let chunked = ChunkedUploader {
+            http_client: HttpClient::new(),
+            node,
+            keypair: Keypair::from_bytes(&keypair.to_bytes()).unwrap(),
+            streaming_threshold: 0,
+        };
+        let asset_info = AssetInfo {
+            asset_id: "0".to_string(),
+            name: "0.mp4".to_string(),
+            content: path.to_string_lossy().to_string(),
+            data_type: DataType::Animation,
+            content_type: "video/mp4".to_string(),
+            remote: None,
+        };
+
+        let (asset_id, link) = BundlrMethod::send(
+            Arc::new(client),
+            Arc::new(chunked),
+            Tag::new("App-Name".into(), APP_NAME.to_string()),
+            asset_info,
+        )
+        .await
+        .unwrap();
+        assert_eq!(asset_id, "0");
+
+        let requests = requests.lock().unwrap();
+        let paths: Vec<&str> = requests.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "/chunks/solana/-1/-1",
+                "/chunks/solana/upload-id/0",
+                "/chunks/solana/upload-id/100000",
+                "/chunks/solana/upload-id/200000",
+                "/chunks/solana/upload-id/-1",
+            ]
+        );
+        assert!(requests[1..3].iter().all(|r| r.size == 100_000));
+
+        // the chunks form the signed data item
+        let item: Vec<u8> = requests[1..4].iter().flat_map(|r| r.body.clone()).collect();
+        assert_eq!(item[..2], ED25519_SIGNATURE_TYPE.to_le_bytes());
+        let owner = &item[66..98];
+        assert_eq!(owner, keypair.pubkey().to_bytes());
+        assert_eq!(item[98..100], [0, 0]);
+        assert_eq!(item[100..108], 2u64.to_le_bytes());
+
+        let tags_length = u64::from_le_bytes(item[108..116].try_into().unwrap()) as usize;
+        let tags = &item[116..116 + tags_length];
+        assert_eq!(
+            tags,
+            encode_tags(&[("App-Name", APP_NAME), ("Content-Type", "video/mp4")])
+        );
+        assert_eq!(item[116 + tags_length..], content);
+
+        let message = deep_hash_list(&[
+            deep_hash_blob(b"dataitem"),
+            deep_hash_blob(b"1"),
+            deep_hash_blob(b"2"),
+            deep_hash_blob(owner),
+            deep_hash_blob(&[]),
+            deep_hash_blob(&[]),
+            deep_hash_blob(tags),
+            deep_hash_blob(&content),
+        ]);
+        assert!(Signature::new(&item[2..66]).verify(owner, &message));
+
+        let id = BASE64URL_NOPAD.encode(&Sha256::digest(&item[2..66]));
+        assert_eq!(link, format!("https://gateway.irys.xyz/{id}?ext=mp4"));
+    }
+
+    #[test]
+    fn test_encode_tags() {
+        // count 1 (zigzag 2), "a" and "bc", end of the array
+        assert_eq!(
+            encode_tags(&[("a", "bc")]),
+            vec![2, 2, b'a', 4, b'b', b'c', 0]
+        );
+        assert!(encode_tags(&[]).is_empty());
     }
 }
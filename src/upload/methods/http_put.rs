@@ -0,0 +1,318 @@
+use std::{ops::Deref, sync::Arc};
+
+use async_trait::async_trait;
+use regex::Regex;
+use reqwest::{
+    header::{self, HeaderMap, HeaderName, HeaderValue},
+    Client, StatusCode,
+};
+use tokio::task::JoinHandle;
+
+use crate::{common::*, config::*, upload::*};
+
+// Maximum number of times to retry each individual upload.
+const MAX_RETRY: u8 = 3;
+// Placeholder of the file name in the URL template.
+const NAME_PLACEHOLDER: &str = "{name}";
+
+pub struct HttpPutStorage {
+    client: Client,
+    url: String,
+    public_base_url: Option<String>,
+    parallel_limit: u16,
+    streaming_threshold: u64,
+}
+
+/// Upload method for self-hosted storages accepting the files with PUT requests, e.g. an
+/// internal CDN authenticated with a bearer token in the `headers` of the config.
+pub struct HttpPutMethod(Arc<HttpPutStorage>);
+
+impl Deref for HttpPutMethod {
+    type Target = Arc<HttpPutStorage>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl HttpPutMethod {
+    /// Initialize a new HttpPutMethod.
+    pub async fn new(config_data: &ConfigData) -> Result<Self> {
+        let config = config_data
+            .http_put_config
+            .as_ref()
+            .ok_or_else(|| anyhow!("Missing 'httpPutConfig' value in config file."))?;
+
+        if !config.url.contains(NAME_PLACEHOLDER) {
+            return Err(anyhow!(
+                "The 'httpPutConfig' 'url' must contain the {} placeholder of the file name, \
+                found {}",
+                NAME_PLACEHOLDER,
+                config.url
+            ));
+        }
+        check_http_url("url", &file_url(&config.url, "0.json"))?;
+
+        if let Some(public_base_url) = &config.public_base_url {
+            check_http_url("publicBaseUrl", public_base_url)?;
+        }
+
+        let mut headers = HeaderMap::new();
+
+        for (name, value) in &config.headers {
+            let value = interpolate_env(value, |var| std::env::var(var).ok())
+                .map_err(|e| anyhow!("Invalid 'httpPutConfig' header '{}': {}", name, e))?;
+            let name = HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!("Invalid 'httpPutConfig' header name '{}'", name))?;
+            let mut value = HeaderValue::from_str(&value)
+                .map_err(|_| anyhow!("Invalid 'httpPutConfig' value of header '{}'", name))?;
+            // the values can hold secrets
+            value.set_sensitive(true);
+            headers.insert(name, value);
+        }
+
+        let client = Client::builder().default_headers(headers).build()?;
+
+        Ok(Self(Arc::new(HttpPutStorage {
+            client,
+            url: config.url.clone(),
+            public_base_url: config.public_base_url.clone(),
+            parallel_limit: config.parallel_limit.unwrap_or(PARALLEL_LIMIT as u16),
+            streaming_threshold: streaming_threshold(config_data),
+        })))
+    }
+}
+
+#[async_trait]
+impl Prepare for HttpPutMethod {
+    async fn prepare(
+        &self,
+        _sugar_config: &SugarConfig,
+        _asset_pairs: &HashMap<isize, AssetPair>,
+        _asset_indices: Vec<(DataType, &[isize])>,
+    ) -> Result<()> {
+        // nothing to do here
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ParallelUploader for HttpPutMethod {
+    /// Returns the number of files that to be send in parallel.
+    fn parallel_limit(&self) -> usize {
+        self.parallel_limit as usize
+    }
+
+    fn upload_asset(&self, asset_info: AssetInfo) -> JoinHandle<Result<(String, String)>> {
+        let config = self.0.clone();
+        tokio::spawn(async move { config.send(asset_info).await })
+    }
+}
+
+impl HttpPutStorage {
+    async fn send(&self, asset_info: AssetInfo) -> Result<(String, String)> {
+        // large files are streamed from disk
+        let data = UploadData::load(&asset_info, self.streaming_threshold).await?;
+
+        let url = file_url(&self.url, &asset_info.name);
+        let mut retry = MAX_RETRY;
+
+        // send data with a simple retry logic (mitigates dns lookup errors)
+        let response = loop {
+            let result = self
+                .client
+                .put(&url)
+                .header(header::CONTENT_TYPE, &asset_info.content_type)
+                .header(header::CONTENT_LENGTH, data.size())
+                .body(data.body()?)
+                .send()
+                .await;
+
+            match result {
+                Ok(response) => break response,
+                Err(error) if retry == 0 => return Err(error.into()),
+                // we try one more time before reporting the error
+                Err(_) => retry -= 1,
+            }
+        };
+        let status = response.status();
+
+        if status.is_success() {
+            let link = match &self.public_base_url {
+                Some(public_base_url) => public_link(public_base_url, &asset_info.name),
+                None => url,
+            };
+
+            Ok((asset_info.asset_id, link))
+        } else if status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::SERVICE_UNAVAILABLE
+        {
+            // retried by the uploader, at a lower rate
+            Err(anyhow!(UploadError::Throttled(format!(
+                "{} ({})",
+                asset_info.name, status
+            ))))
+        } else {
+            let body = response.text().await.unwrap_or_default();
+            Err(anyhow!(UploadError::SendDataFailed(format!(
+                "Error uploading {} ({}): {}",
+                asset_info.name, status, body
+            ))))
+        }
+    }
+}
+
+fn check_http_url(field: &str, value: &str) -> Result<()> {
+    let url = url::Url::parse(value)
+        .map_err(|error| anyhow!("Malformed 'httpPutConfig' '{}' ({})", field, error))?;
+
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(anyhow!(
+            "The 'httpPutConfig' '{}' must be an http(s) URL, found {}",
+            field,
+            value
+        ));
+    }
+
+    Ok(())
+}
+
+/// URL of the PUT request of a file.
+fn file_url(template: &str, name: &str) -> String {
+    template.replace(NAME_PLACEHOLDER, name)
+}
+
+/// Link of a file under the public base URL.
+fn public_link(public_base_url: &str, name: &str) -> String {
+    format!("{}/{}", public_base_url.trim_end_matches('/'), name)
+}
+
+/// Replaces the `${VAR}` references of a value by the variables returned by `lookup`, failing
+/// when a variable is not set.
+fn interpolate_env(value: &str, lookup: impl Fn(&str) -> Option<String>) -> Result<String> {
+    let regex = Regex::new(r"\$\{([A-Za-z_][A-Za-z0-9_]*)\}").expect("Failed to create regex.");
+    let mut interpolated = String::with_capacity(value.len());
+    let mut last = 0;
+
+    for captures in regex.captures_iter(value) {
+        let reference = captures.get(0).unwrap();
+        let var = &captures[1];
+        let resolved =
+            lookup(var).ok_or_else(|| anyhow!("environment variable {} is not set", var))?;
+
+        interpolated.push_str(&value[last..reference.start()]);
+        interpolated.push_str(&resolved);
+        last = reference.end();
+    }
+    interpolated.push_str(&value[last..]);
+
+    Ok(interpolated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use crate::upload::methods::test_utils::resident_bytes;
+    use crate::upload::methods::test_utils::{serve, upload_collection};
+
+    #[test]
+    fn test_interpolate_env() {
+        let lookup = |var: &str| (var == "CDN_TOKEN").then(|| "s3cr3t".to_string());
+
+        assert_eq!(
+            interpolate_env("Bearer ${CDN_TOKEN}", lookup).unwrap(),
+            "Bearer s3cr3t"
+        );
+        assert_eq!(interpolate_env("sugar-cli", lookup).unwrap(), "sugar-cli");
+
+        let err = interpolate_env("Bearer ${MISSING}", lookup).unwrap_err();
+        assert_eq!(err.to_string(), "environment variable MISSING is not set");
+    }
+
+    #[test]
+    fn test_links() {
+        let template = "https://cdn.example.com/assets/{name}?overwrite=true";
+
+        assert_eq!(
+            file_url(template, "0.png"),
+            "https://cdn.example.com/assets/0.png?overwrite=true"
+        );
+        assert_eq!(
+            public_link("https://assets.example.com/", "0.png"),
+            "https://assets.example.com/0.png"
+        );
+        assert!(check_http_url("url", &file_url(template, "0.json")).is_ok());
+        assert!(check_http_url("publicBaseUrl", "ftp://cdn.example.com").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_collection_item() {
+        let (base_url, requests) = serve(|_| (200, String::new()));
+        let method = HttpPutMethod(Arc::new(HttpPutStorage {
+            client: Client::new(),
+            url: format!("{base_url}/assets/{{name}}"),
+            public_base_url: Some("https://assets.example.com".to_string()),
+            parallel_limit: 2,
+            streaming_threshold: DEFAULT_STREAMING_THRESHOLD_MB * 1024 * 1024,
+        }));
+
+        let cache = upload_collection(&method).await;
+
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            "https://assets.example.com/collection.json"
+        );
+        assert!(requests
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|request| request.method == "PUT" && request.path == "/assets/collection.json"));
+    }
+
+    /// Uploads a sparse 200 MB file to the local server, which only counts the bytes of the
+    /// large bodies, checking that the memory of the process stays bounded.
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn test_streaming_memory_is_bounded() {
+        const FILE_SIZE: u64 = 200 * 1024 * 1024;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.mp4");
+        std::fs::File::create(&path)
+            .unwrap()
+            .set_len(FILE_SIZE)
+            .unwrap();
+
+        let (base_url, requests) = serve(|_| (200, String::new()));
+        let storage = HttpPutStorage {
+            client: Client::new(),
+            url: format!("{base_url}/assets/{{name}}"),
+            public_base_url: None,
+            parallel_limit: 1,
+            streaming_threshold: DEFAULT_STREAMING_THRESHOLD_MB * 1024 * 1024,
+        };
+
+        let baseline = resident_bytes();
+        let (_, link) = storage
+            .send(AssetInfo {
+                asset_id: "0".to_string(),
+                name: "0.mp4".to_string(),
+                content: path.to_string_lossy().to_string(),
+                data_type: DataType::Animation,
+                content_type: "video/mp4".to_string(),
+                remote: None,
+            })
+            .await
+            .unwrap();
+        let growth = resident_bytes().saturating_sub(baseline);
+
+        assert_eq!(link, format!("{base_url}/assets/0.mp4"));
+        assert_eq!(requests.lock().unwrap()[0].size as u64, FILE_SIZE);
+        // a few chunks at most, far from the size of the file
+        assert!(
+            growth < 4 * STREAM_CHUNK_SIZE as u64,
+            "resident memory grew by {} bytes",
+            growth
+        );
+    }
+}
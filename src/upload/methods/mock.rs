@@ -0,0 +1,47 @@
+use async_trait::async_trait;
+use tokio::task::JoinHandle;
+
+use crate::{common::*, config::*, upload::*};
+
+/// Base URL of the generated links.
+pub const MOCK_STORAGE_URL: &str = "https://mock.storage.local";
+
+/// Upload method that does not store the files: it returns a deterministic link for each
+/// asset so that a collection can be deployed without a storage provider.
+pub struct MockMethod;
+
+impl MockMethod {
+    /// Initialize a new MockMethod.
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl Default for MockMethod {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Prepare for MockMethod {
+    /// Nothing to prepare, the files are not stored.
+    async fn prepare(
+        &self,
+        _sugar_config: &SugarConfig,
+        _asset_pairs: &HashMap<isize, AssetPair>,
+        _asset_indices: Vec<(DataType, &[isize])>,
+    ) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ParallelUploader for MockMethod {
+    fn upload_asset(&self, asset_info: AssetInfo) -> JoinHandle<Result<(String, String)>> {
+        tokio::spawn(async move {
+            let link = format!("{}/{}", MOCK_STORAGE_URL, asset_info.name);
+            Ok((asset_info.asset_id, link))
+        })
+    }
+}
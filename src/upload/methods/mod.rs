@@ -1,11 +1,17 @@
 pub mod aws;
 pub mod bundlr;
+pub mod http_put;
+#[cfg(feature = "integration-tests")]
+pub mod mock;
 pub mod nft_storage;
 pub mod pinata;
 pub mod sdrive;
 pub mod shdw;
+#[cfg(test)]
+pub mod test_utils;
 
 pub use aws::*;
 pub use bundlr::*;
+pub use http_put::*;
 pub use nft_storage::*;
 pub use sdrive::*;
@@ -8,25 +8,19 @@ use std::{
 };
 
 use async_trait::async_trait;
-use reqwest::{
-    header,
-    multipart::{Form, Part},
-    Client, StatusCode,
-};
+use reqwest::{header, Client, StatusCode};
 use tokio::time::{sleep, Duration};
 
-use crate::{common::*, config::*, upload::*};
+use crate::{cancel, common::*, config::*, output::style, progress_stream, upload::*};
 
 // API end point.
 const NFT_STORAGE_API_URL: &str = "https://api.nft.storage";
-// Storage end point.
-const NFT_STORAGE_GATEWAY_URL: &str = "https://nftstorage.link/ipfs";
 // Request time window (ms) to avoid the rate limit.
 const REQUEST_WAIT: u64 = 10000;
 // File size limit (100mb).
 const FILE_SIZE_LIMIT: u64 = 100 * 1024 * 1024;
-// Number of files per request limit.
-const FILE_COUNT_LIMIT: u64 = 100;
+// Number of files per CAR, keeping its directory node small.
+const FILE_COUNT_LIMIT: u64 = 1000;
 
 pub enum NftStorageError {
     ApiError(Value),
@@ -68,6 +62,8 @@ pub struct NftError {
 
 pub struct NftStorageMethod {
     client: Arc<Client>,
+    api_url: String,
+    streaming_threshold: u64,
 }
 
 impl NftStorageMethod {
@@ -90,10 +86,10 @@ impl NftStorageMethod {
             match response.status() {
                 StatusCode::OK => Ok(Self {
                     client: Arc::new(client),
+                    api_url: NFT_STORAGE_API_URL.to_string(),
+                    streaming_threshold: streaming_threshold(config_data),
                 }),
-                StatusCode::UNAUTHORIZED => {
-                    Err(anyhow!("Invalid nft.storage authentication token."))
-                }
+                StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(auth_error()),
                 code => Err(anyhow!("Could not initialize nft.storage client: {code}")),
             }
         } else {
@@ -118,10 +114,7 @@ impl Prepare for NftStorageMethod {
             for index in indices {
                 let item = asset_pairs.get(index).unwrap();
                 let size = match data_type {
-                    DataType::Image => {
-                        let path = Path::new(&item.image);
-                        fs::metadata(path)?.len()
-                    }
+                    DataType::Image => item.image_size()?,
                     DataType::Animation => {
                         if let Some(animation) = &item.animation {
                             let path = Path::new(animation);
@@ -138,7 +131,7 @@ impl Prepare for NftStorageMethod {
                             None
                         };
 
-                        get_updated_metadata(&item.metadata, &mock_uri.clone(), &animation)?
+                        get_updated_metadata(item, &mock_uri.clone(), &animation)?
                             .into_bytes()
                             .len() as u64
                     }
@@ -158,7 +151,12 @@ impl Prepare for NftStorageMethod {
 
 #[async_trait]
 impl Uploader for NftStorageMethod {
-    /// Upload the data to Nft Storage
+    /// Upload the data to Nft Storage, one batch at a time (the batches are limited by the
+    /// size limit of the API, so `concurrency` does not apply). Each batch request waits for
+    /// the `rate_limiter`.
+    ///
+    /// Each batch is stored as a directory, sent as a single CAR, and the items are linked
+    /// as `ipfs://<cid>/<file name>`.
     async fn upload(
         &self,
         _sugar_config: &SugarConfig,
@@ -167,6 +165,9 @@ impl Uploader for NftStorageMethod {
         assets: &mut Vec<AssetInfo>,
         progress: &ProgressBar,
         interrupted: Arc<AtomicBool>,
+        _concurrency: Option<usize>,
+        _flush_every: usize,
+        rate_limiter: &RateLimiter,
     ) -> Result<Vec<UploadError>> {
         let mut batches: Vec<Vec<&AssetInfo>> = Vec::new();
         let mut current: Vec<&AssetInfo> = Vec::new();
@@ -175,10 +176,7 @@ impl Uploader for NftStorageMethod {
 
         for asset_info in assets {
             let size = match data_type {
-                DataType::Image | DataType::Animation => {
-                    let path = Path::new(&asset_info.content);
-                    fs::metadata(path)?.len()
-                }
+                DataType::Image | DataType::Animation => file_size(asset_info)?,
                 DataType::Metadata => {
                     let content = String::from(&asset_info.content);
                     content.into_bytes().len() as u64
@@ -202,37 +200,49 @@ impl Uploader for NftStorageMethod {
         }
 
         let mut errors = Vec::new();
+        let mut throttled_retries = 0;
         // sets the length of the progress bar as the number of batches
         progress.set_length(batches.len() as u64);
 
         while !interrupted.load(Ordering::SeqCst) && !batches.is_empty() {
             let batch = batches.remove(0);
-            let mut form = Form::new();
+            let mut files = Vec::with_capacity(batch.len());
 
             for asset_info in &batch {
-                let data = match asset_info.data_type {
-                    DataType::Image | DataType::Animation => fs::read(&asset_info.content)?,
-                    DataType::Metadata => {
-                        let content = String::from(&asset_info.content);
-                        content.into_bytes()
-                    }
+                let data = match UploadData::load(asset_info, self.streaming_threshold).await? {
+                    // the blocks of the CAR are hashed before it is sent, a remote file would
+                    // have to be downloaded twice
+                    UploadData::Remote { file, .. } => UploadData::Memory(file.download().await?),
+                    data => data,
                 };
-
-                let file = Part::bytes(data)
-                    .file_name(asset_info.name.clone())
-                    .mime_str(asset_info.content_type.as_str())?;
-                form = form.part("file", file);
+                files.push((asset_info.name.as_str(), data));
             }
 
+            // the local files over the streaming threshold are read again as the CAR is sent
+            let files: Vec<(&str, CarFile)> = files
+                .iter()
+                .map(|(name, data)| match data {
+                    UploadData::Memory(data) => (*name, CarFile::Bytes(data)),
+                    UploadData::File { path, .. } => (*name, CarFile::Path(path)),
+                    UploadData::Remote { .. } => unreachable!("remote files are downloaded"),
+                })
+                .collect();
+            let car = streaming_directory_car(&files)?;
+
+            rate_limiter.acquire().await;
             let response = self
                 .client
-                .post(format!("{NFT_STORAGE_API_URL}/upload"))
-                .multipart(form)
+                .post(format!("{}/upload", self.api_url))
+                .header(header::CONTENT_TYPE, "application/car")
+                .header(header::CONTENT_LENGTH, car.size())
+                .body(chunks_body(car.into_chunks(STREAM_CHUNK_SIZE)))
                 .send()
                 .await?;
             let status = response.status();
 
             if status.is_success() {
+                rate_limiter.succeeded();
+                throttled_retries = 0;
                 let body = response.json::<Value>().await?;
                 let StoreNftResponse {
                     value: NftValue { cid },
@@ -243,7 +253,8 @@ impl Uploader for NftStorageMethod {
 
                 for asset_info in batch {
                     let id = asset_info.asset_id.clone();
-                    let uri = format!("{NFT_STORAGE_GATEWAY_URL}/{cid}/{}", asset_info.name);
+                    progress_stream::item_completed(Some(id.clone()), Ok::<_, String>(()));
+                    let uri = ipfs_link(&cid, &asset_info.name);
                     // cache item to update
                     let item = cache.items.get_mut(&id).unwrap();
 
@@ -257,17 +268,47 @@ impl Uploader for NftStorageMethod {
                 cache.sync_file()?;
                 // updates the progress bar
                 progress.inc(1);
+            } else if status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN {
+                // the token was deleted or expired during the upload: the remaining batches
+                // would fail the same way
+                cache.sync_file()?;
+                progress.abandon();
+                return Err(auth_error());
             } else {
-                let body = response.json::<Value>().await?;
-                let StoreNftError {
-                    error: NftError { message },
-                    ..
-                }: StoreNftError = serde_json::from_value(body)?;
+                if (status == StatusCode::TOO_MANY_REQUESTS
+                    || status == StatusCode::SERVICE_UNAVAILABLE)
+                    && throttled_retries < MAX_THROTTLED_RETRY
+                {
+                    // sends the batch again at a lower rate
+                    rate_limiter.throttled();
+                    throttled_retries += 1;
+                    batches.insert(0, batch);
+                    continue;
+                }
+
+                let body = response.text().await?;
+                let message = match serde_json::from_str::<StoreNftError>(&body) {
+                    Ok(StoreNftError {
+                        error: NftError { message },
+                        ..
+                    }) => message,
+                    Err(_) => body,
+                };
 
-                errors.push(UploadError::SendDataFailed(format!(
-                    "Error uploading batch ({}): {}",
-                    status, message
-                )));
+                let retriable = status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS;
+
+                // every file of the batch failed
+                for asset_info in batch {
+                    let error = UploadError::FileFailed {
+                        asset_id: asset_info.asset_id.clone(),
+                        file: asset_info.name.clone(),
+                        data_type: data_type.clone(),
+                        error: format!("Error uploading batch ({}): {}", status, message),
+                        retriable,
+                    };
+                    progress_stream::item_completed(Some(asset_info.asset_id.clone()), Err(&error));
+                    errors.push(error);
+                }
             }
             if !batches.is_empty() {
                 // wait to minimize the chance of getting caught by the rate limit
@@ -275,6 +316,59 @@ impl Uploader for NftStorageMethod {
             }
         }
 
+        if !batches.is_empty() {
+            // the cache is saved after each batch
+            progress.abandon_with_message(format!("{}", style("Upload interrupted ").red().bold()));
+            return Err(cancel::cancelled().into());
+        }
+
         Ok(errors)
     }
 }
+
+fn auth_error() -> anyhow::Error {
+    anyhow!(
+        "The nft.storage authentication token was rejected: it may have been deleted or have \
+        expired. Create a new token and update 'nftStorageAuthToken' in the config file."
+    )
+}
+
+/// Link of a file of a stored directory.
+fn ipfs_link(cid: &str, name: &str) -> String {
+    format!("ipfs://{cid}/{name}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::methods::test_utils::{serve, upload_collection};
+
+    #[tokio::test]
+    async fn test_upload_collection_item() {
+        let (base_url, requests) = serve(|_| {
+            (
+                200,
+                json!({ "ok": true, "value": { "cid": "bafy-dir" } }).to_string(),
+            )
+        });
+        let method = NftStorageMethod {
+            client: Arc::new(Client::new()),
+            api_url: base_url,
+            streaming_threshold: DEFAULT_STREAMING_THRESHOLD_MB * 1024 * 1024,
+        };
+
+        let cache = upload_collection(&method).await;
+
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            "ipfs://bafy-dir/collection.json"
+        );
+        // both files are stored with a single CAR
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/upload");
+
+        let car = directory_car(&[("collection.json", b"{}"), ("0.json", b"{}")]);
+        assert_eq!(requests[0].body, car.data);
+    }
+}
@@ -1,11 +1,7 @@
-use std::{fs, ops::Deref, path::Path, sync::Arc};
+use std::{env, fs, ops::Deref, path::Path, sync::Arc};
 
 use async_trait::async_trait;
-use reqwest::{
-    header,
-    multipart::{Form, Part},
-    Client, StatusCode,
-};
+use reqwest::{header, multipart::Form, Client, StatusCode};
 use tokio::task::JoinHandle;
 
 use crate::{common::*, config::*, upload::*};
@@ -16,6 +12,8 @@ const UPLOAD_ENDPOINT: &str = "/pinning/pinFileToIPFS";
 const AUTH_TEST_URL: &str = "https://api.pinata.cloud/data/testAuthentication";
 // File size limit (10mb).
 const FILE_SIZE_LIMIT: u64 = 10 * 1024 * 1024;
+// Environment variable with the JWT, used when the config file does not set one.
+const JWT_ENV: &str = "PINATA_JWT";
 
 /// response after an nft was stored
 #[derive(Debug, Deserialize, Default)]
@@ -28,10 +26,18 @@ pub struct PinataResponse {
 pub struct Config {
     client: Client,
     endpoint: String,
-    content_gateway: String,
+    content_gateway: Option<String>,
     parallel_limit: u16,
+    cid_version: CidVersion,
+    streaming_threshold: u64,
 }
 
+/// Upload method that pins each file to IPFS through the Pinata API, writing links of the
+/// content gateway (or `ipfs://` links) to the cache.
+///
+/// Images and animations are uploaded before the metadata, which is rewritten with their
+/// links, so the `image` of the pinned metadata is the link of the pinned image. Throttled
+/// requests are retried by the uploader, at the rate of the shared limiter.
 pub struct PinataMethod(Arc<Config>);
 
 impl Deref for PinataMethod {
@@ -47,8 +53,17 @@ impl PinataMethod {
         if let Some(pinata_config) = &config_data.pinata_config {
             let client_builder = Client::builder();
 
+            let jwt = match &pinata_config.jwt {
+                Some(jwt) => jwt.clone(),
+                None => env::var(JWT_ENV).map_err(|_| {
+                    anyhow!(
+                        "Missing Pinata JWT: set 'pinataConfig.jwt' in config file or {JWT_ENV}"
+                    )
+                })?,
+            };
+
             let mut headers = header::HeaderMap::new();
-            let bearer_value = format!("Bearer {}", &pinata_config.jwt);
+            let bearer_value = format!("Bearer {}", jwt);
             let mut auth_value = header::HeaderValue::from_str(&bearer_value)?;
             auth_value.set_sensitive(true);
             headers.insert(header::AUTHORIZATION, auth_value);
@@ -76,6 +91,8 @@ impl PinataMethod {
                         endpoint: endpoint_url.to_string(),
                         content_gateway: pinata_config.content_gateway.clone(),
                         parallel_limit,
+                        cid_version: pinata_config.cid_version,
+                        streaming_threshold: streaming_threshold(config_data),
                     })))
                 }
                 StatusCode::UNAUTHORIZED => Err(anyhow!("Invalid pinata JWT token.")),
@@ -101,10 +118,7 @@ impl Prepare for PinataMethod {
             for index in indices {
                 let item = asset_pairs.get(index).unwrap();
                 let size = match data_type {
-                    DataType::Image => {
-                        let path = Path::new(&item.image);
-                        fs::metadata(path)?.len()
-                    }
+                    DataType::Image => item.image_size()?,
                     DataType::Animation => {
                         if let Some(animation) = &item.animation {
                             let path = Path::new(animation);
@@ -121,7 +135,7 @@ impl Prepare for PinataMethod {
                             None
                         };
 
-                        get_updated_metadata(&item.metadata, &mock_uri.clone(), &animation)?
+                        get_updated_metadata(item, &mock_uri.clone(), &animation)?
                             .into_bytes()
                             .len() as u64
                     }
@@ -154,20 +168,22 @@ impl ParallelUploader for PinataMethod {
 
 impl Config {
     async fn send(&self, asset_info: AssetInfo) -> Result<(String, String)> {
-        let data = match asset_info.data_type {
-            DataType::Image => fs::read(&asset_info.content)?,
-            DataType::Metadata => asset_info.content.into_bytes(),
-            DataType::Animation => fs::read(&asset_info.content)?,
-        };
+        // large files are streamed from disk
+        let data = UploadData::load(&asset_info, self.streaming_threshold).await?;
 
         let mut form = Form::new();
 
-        let file = Part::bytes(data)
+        let file = data
+            .part()?
             .file_name(asset_info.name.clone())
             .mime_str(asset_info.content_type.as_str())?;
+        let options = json!({
+            "wrapWithDirectory": true,
+            "cidVersion": self.cid_version.number(),
+        });
         form = form
             .part("file", file)
-            .text("pinataOptions", "{\"wrapWithDirectory\": true}");
+            .text("pinataOptions", options.to_string());
 
         let response = self
             .client
@@ -181,10 +197,21 @@ impl Config {
             let body = response.json::<Value>().await?;
             let PinataResponse { ipfs_hash } = serde_json::from_value(body)?;
 
-            let uri = url::Url::parse(&self.content_gateway)?
-                .join(&format!("/ipfs/{}/{}", ipfs_hash, asset_info.name))?;
+            let uri = pinned_link(
+                self.content_gateway.as_deref(),
+                &ipfs_hash,
+                &asset_info.name,
+            )?;
 
-            Ok((asset_info.asset_id, uri.to_string()))
+            Ok((asset_info.asset_id, uri))
+        } else if status == StatusCode::TOO_MANY_REQUESTS
+            || status == StatusCode::SERVICE_UNAVAILABLE
+        {
+            // retried by the uploader, at a lower rate
+            Err(anyhow!(UploadError::Throttled(format!(
+                "{} ({})",
+                asset_info.name, status
+            ))))
         } else {
             let body = response.json::<Value>().await?;
             let details = if let Some(details) = &body["error"]["details"].as_str() {
@@ -199,3 +226,91 @@ impl Config {
         }
     }
 }
+
+/// Link of a file pinned in the `cid` directory: `<gateway>/ipfs/<cid>/<name>`, or
+/// `ipfs://<cid>/<name>` without a gateway.
+fn pinned_link(gateway: Option<&str>, cid: &str, name: &str) -> Result<String> {
+    match gateway {
+        Some(gateway) => Ok(url::Url::parse(gateway)?
+            .join(&format!("/ipfs/{}/{}", cid, name))?
+            .to_string()),
+        None => Ok(format!("ipfs://{}/{}", cid, name)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::methods::test_utils::{serve, upload_collection};
+
+    #[tokio::test]
+    async fn test_upload_collection_item() {
+        let (base_url, requests) = serve(|_| (200, json!({ "IpfsHash": "QmHash" }).to_string()));
+        let method = PinataMethod(Arc::new(Config {
+            client: Client::new(),
+            endpoint: format!("{base_url}{UPLOAD_ENDPOINT}"),
+            content_gateway: Some("https://gateway.example.com".to_string()),
+            parallel_limit: 2,
+            cid_version: CidVersion::V0,
+            streaming_threshold: DEFAULT_STREAMING_THRESHOLD_MB * 1024 * 1024,
+        }));
+
+        let cache = upload_collection(&method).await;
+
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            "https://gateway.example.com/ipfs/QmHash/collection.json"
+        );
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_upload_ipfs_links() {
+        let (base_url, requests) = serve(|_| (200, json!({ "IpfsHash": "bafyHash" }).to_string()));
+        let method = PinataMethod(Arc::new(Config {
+            client: Client::new(),
+            endpoint: format!("{base_url}{UPLOAD_ENDPOINT}"),
+            content_gateway: None,
+            parallel_limit: 2,
+            cid_version: CidVersion::V1,
+            streaming_threshold: DEFAULT_STREAMING_THRESHOLD_MB * 1024 * 1024,
+        }));
+
+        let cache = upload_collection(&method).await;
+
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            "ipfs://bafyHash/collection.json"
+        );
+        let body = String::from_utf8_lossy(&requests.lock().unwrap()[0].body).to_string();
+        assert!(body.contains(r#""cidVersion":1"#));
+    }
+
+    #[tokio::test]
+    async fn test_throttled_upload() {
+        let (base_url, _) = serve(|_| (429, "{}".to_string()));
+        let config = Config {
+            client: Client::new(),
+            endpoint: format!("{base_url}{UPLOAD_ENDPOINT}"),
+            content_gateway: None,
+            parallel_limit: 2,
+            cid_version: CidVersion::V0,
+            streaming_threshold: DEFAULT_STREAMING_THRESHOLD_MB * 1024 * 1024,
+        };
+
+        let error = config
+            .send(AssetInfo {
+                asset_id: "0".to_string(),
+                name: "0.json".to_string(),
+                content: "{}".to_string(),
+                data_type: DataType::Metadata,
+                content_type: "application/json".to_string(),
+                remote: None,
+            })
+            .await
+            .unwrap_err();
+
+        // retried by the uploader with the shared rate limiter
+        assert!(is_throttled(&error));
+    }
+}
@@ -1,4 +1,4 @@
-use std::{fs, sync::Arc};
+use std::sync::Arc;
 
 use async_trait::async_trait;
 use reqwest::multipart::{Form, Part};
@@ -9,6 +9,7 @@ use crate::{
     config::*,
     upload::{
         assets::{AssetPair, DataType},
+        remote::read_file,
         uploader::{AssetInfo, ParallelUploader, Prepare},
         UploadError,
     },
@@ -42,9 +43,9 @@ impl SdriveMethod {
 
     async fn send(apikey: String, asset_info: AssetInfo) -> Result<(String, String)> {
         let data = match asset_info.data_type {
-            DataType::Image => fs::read(&asset_info.content)?,
+            DataType::Image => read_file(&asset_info).await?,
             DataType::Metadata => asset_info.content.into_bytes(),
-            DataType::Animation => fs::read(&asset_info.content)?,
+            DataType::Animation => read_file(&asset_info).await?,
         };
         let data_clone = data.clone(); // Clone the data outside the loop
         let apikey_clone = apikey.clone(); // Clone the apikey outside the loop
@@ -15,6 +15,7 @@ use crate::{
     config::*,
     upload::{
         assets::{get_updated_metadata, AssetPair, DataType},
+        remote::read_file,
         uploader::{AssetInfo, ParallelUploader, Prepare, MOCK_URI_SIZE},
         UploadError,
     },
@@ -126,8 +127,7 @@ impl Prepare for SHDWMethod {
                 DataType::Image => {
                     for index in indices {
                         let item = assets.get(index).unwrap();
-                        let path = Path::new(&item.image);
-                        total_size += fs::metadata(path)?.len();
+                        total_size += item.image_size()?;
                     }
                 }
                 DataType::Animation => {
@@ -151,10 +151,9 @@ impl Prepare for SHDWMethod {
                             None
                         };
 
-                        total_size +=
-                            get_updated_metadata(&item.metadata, &mock_uri.clone(), &animation)?
-                                .into_bytes()
-                                .len() as u64;
+                        total_size += get_updated_metadata(item, &mock_uri.clone(), &animation)?
+                            .into_bytes()
+                            .len() as u64;
                     }
                 }
             }
@@ -182,9 +181,9 @@ impl ParallelUploader for SHDWMethod {
 impl Config {
     async fn send(&self, asset_info: AssetInfo) -> Result<(String, String)> {
         let data = match asset_info.data_type {
-            DataType::Image => fs::read(&asset_info.content)?,
+            DataType::Image => read_file(&asset_info).await?,
             DataType::Metadata => asset_info.content.into_bytes(),
-            DataType::Animation => fs::read(&asset_info.content)?,
+            DataType::Animation => read_file(&asset_info).await?,
         };
 
         let mut context = Context::new(&SHA256);
@@ -0,0 +1,165 @@
+//! Helpers of the upload method tests: a local HTTP server standing in for the storage API
+//! and an upload of the collection item.
+
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::{atomic::AtomicBool, Arc, Mutex},
+    thread,
+};
+
+use crate::{common::*, config::SugarConfig, upload::*};
+
+// Size above which the body of a request is only counted, so that the server does not hold
+// the large files of the streaming tests in memory.
+const MAX_RECORDED_BODY: usize = 1024 * 1024;
+
+/// Request received by the local server.
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    /// Body of the request, empty when over `MAX_RECORDED_BODY`.
+    pub body: Vec<u8>,
+    /// Size of the body.
+    pub size: usize,
+}
+
+/// Serves the requests on a local port with the `(status, body)` returned by `respond`,
+/// recording them. Returns the base URL of the server and the recorded requests.
+pub fn serve<F>(respond: F) -> (String, Arc<Mutex<Vec<Request>>>)
+where
+    F: Fn(&Request) -> (u16, String) + Send + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+    let recorded = requests.clone();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap();
+                    }
+                }
+            }
+            let mut body = Vec::new();
+            let mut buffer = [0; 64 * 1024];
+            let mut remaining = content_length;
+            while remaining > 0 {
+                let read = reader
+                    .read(&mut buffer[..remaining.min(buffer.len())])
+                    .unwrap();
+                if read == 0 {
+                    break;
+                }
+                if content_length <= MAX_RECORDED_BODY {
+                    body.extend_from_slice(&buffer[..read]);
+                }
+                remaining -= read;
+            }
+
+            let mut parts = request_line.split_whitespace();
+            let request = Request {
+                method: parts.next().unwrap_or_default().to_string(),
+                path: parts.next().unwrap_or_default().to_string(),
+                body,
+                size: content_length - remaining,
+            };
+            let (status, body) = respond(&request);
+            recorded.lock().unwrap().push(request);
+
+            write!(
+                stream,
+                "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+                Connection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            )
+            .unwrap();
+        }
+    });
+
+    (format!("http://127.0.0.1:{}", port), requests)
+}
+
+/// Uploads the metadata of the collection item (`-1`) and of item `0` with `uploader`,
+/// returning the cache with their links.
+pub async fn upload_collection(uploader: &dyn Uploader) -> Cache {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut cache = Cache::new();
+    cache.file_path = dir.path().join("cache.json").to_string_lossy().to_string();
+
+    let mut assets = Vec::new();
+    for (asset_id, name) in [("-1", "collection.json"), ("0", "0.json")] {
+        cache.items.insert(
+            asset_id.to_string(),
+            CacheItem {
+                name: asset_id.to_string(),
+                image_hash: String::new(),
+                image_link: String::new(),
+                metadata_hash: String::new(),
+                metadata_link: String::new(),
+                on_chain: false,
+                animation_hash: None,
+                animation_link: None,
+                optimized_image_hash: None,
+            },
+        );
+        assets.push(AssetInfo {
+            asset_id: asset_id.to_string(),
+            name: name.to_string(),
+            content: "{}".to_string(),
+            data_type: DataType::Metadata,
+            content_type: "application/json".to_string(),
+            remote: None,
+        });
+    }
+
+    let sugar_config = SugarConfig {
+        keypair: Keypair::new(),
+        rpc_url: String::new(),
+    };
+
+    let errors = uploader
+        .upload(
+            &sugar_config,
+            &mut cache,
+            DataType::Metadata,
+            &mut assets,
+            &ProgressBar::hidden(),
+            Arc::new(AtomicBool::new(false)),
+            None,
+            DEFAULT_UPLOAD_FLUSH_EVERY,
+            &RateLimiter::new(None),
+        )
+        .await
+        .unwrap();
+    assert!(errors.is_empty(), "{:?}", errors);
+
+    cache
+}
+
+/// Resident set size of the process, read from `/proc/self/statm`.
+#[cfg(target_os = "linux")]
+pub fn resident_bytes() -> u64 {
+    let statm = std::fs::read_to_string("/proc/self/statm").unwrap();
+    let pages: u64 = statm.split_whitespace().nth(1).unwrap().parse().unwrap();
+    // SAFETY: sysconf has no preconditions
+    pages * unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64
+}
@@ -1,11 +1,35 @@
+pub mod asset_files;
 pub mod assets;
+pub mod car;
+pub mod error_report;
 pub mod errors;
+pub mod manifest;
 pub mod methods;
+pub mod optimize;
+pub mod preflight;
 pub mod process;
+pub mod rate_limit;
+pub mod receipts;
+pub mod remote;
+pub mod shuffle;
+pub mod streaming;
+pub mod summary;
 pub mod uploader;
 
+pub use asset_files::*;
 pub use assets::*;
+pub use car::*;
+pub use error_report::*;
 pub use errors::*;
+pub use manifest::*;
 pub use methods::*;
+pub use optimize::*;
+pub use preflight::*;
 pub use process::*;
+pub use rate_limit::*;
+pub use receipts::*;
+pub use remote::*;
+pub use shuffle::*;
+pub use streaming::*;
+pub use summary::*;
 pub use uploader::*;
@@ -0,0 +1,258 @@
+//! Optimization of the images before the upload (`--optimize-images`). The images above the
+//! size threshold are re-encoded (PNG losslessly, JPEG at the configured quality) and scaled
+//! down to the maximum dimension, then the optimized copies, written to the work directory,
+//! are uploaded instead of the images. The source assets are never modified.
+//!
+//! GIF images (and any other format) are uploaded unchanged.
+
+use std::{
+    fs,
+    io::{BufWriter, Write},
+};
+
+use image::{
+    codecs::{
+        jpeg::JpegEncoder,
+        png::{CompressionType, FilterType, PngEncoder},
+    },
+    imageops, DynamicImage, ImageEncoder,
+};
+use rayon::prelude::*;
+
+use crate::{cache::Cache, common::*, config::ImageOptimization, upload::*};
+
+/// Images optimized and bytes saved by the optimization.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct OptimizeReport {
+    pub optimized: usize,
+    pub bytes_saved: u64,
+}
+
+/// Optimizes the images of the indices, replacing the image of their asset pair by the
+/// optimized copy and recording the hash of the copy in the cache item.
+///
+/// An image is kept when it is under the threshold, when its format is not re-encoded or when
+/// the optimized copy is not smaller.
+pub fn optimize_images(
+    asset_pairs: &mut HashMap<isize, AssetPair>,
+    indices: &[isize],
+    cache: &mut Cache,
+    options: &ImageOptimization,
+) -> Result<OptimizeReport> {
+    if !(1..=100).contains(&options.jpeg_quality) {
+        return Err(anyhow!(
+            "Invalid JPEG quality {} of 'imageOptimization', expected a value between 1 and 100",
+            options.jpeg_quality
+        ));
+    }
+
+    let work_dir = Path::new(&options.work_dir);
+    fs::create_dir_all(work_dir).map_err(|e| {
+        anyhow!(
+            "Failed to create the directory of the optimized images '{}': {}",
+            work_dir.display(),
+            e
+        )
+    })?;
+
+    let threshold = options.threshold_kb * 1024;
+
+    let optimized = indices
+        .par_iter()
+        .filter_map(|index| asset_pairs.get(index).map(|pair| (*index, pair)))
+        .filter(|(_, pair)| !is_remote(&pair.image))
+        .map(|(index, pair)| {
+            let output = work_dir.join(asset_file_name(index, &pair.image)?);
+            let result = optimize_image(Path::new(&pair.image), &output, threshold, options)
+                .map_err(|e| anyhow!("Failed to optimize image '{}': {}", pair.image, e))?;
+            Ok((index, output, result))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut report = OptimizeReport::default();
+
+    for (index, output, result) in optimized {
+        let pair = asset_pairs.get_mut(&index).unwrap();
+        let item = cache.items.get_mut(&index.to_string());
+
+        match result {
+            Some(saved) => {
+                let path = output
+                    .to_str()
+                    .expect("Failed to convert optimized image path from unicode.")
+                    .to_string();
+
+                if let Some(item) = item {
+                    item.optimized_image_hash = Some(encode(&path)?);
+                }
+                debug!(
+                    "Optimized {} to {} ({} bytes saved)",
+                    pair.image, path, saved
+                );
+                pair.image = path;
+
+                report.optimized += 1;
+                report.bytes_saved += saved;
+            }
+            None => {
+                if let Some(item) = item {
+                    item.optimized_image_hash = None;
+                }
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Writes the optimized copy of an image to the output path, returning the number of bytes
+/// saved, or `None` when the image is kept.
+fn optimize_image(
+    path: &Path,
+    output: &Path,
+    threshold: u64,
+    options: &ImageOptimization,
+) -> Result<Option<u64>> {
+    let size = fs::metadata(path)?.len();
+
+    if size <= threshold {
+        return Ok(None);
+    }
+
+    let extension = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    if !matches!(extension.as_str(), "png" | "jpg" | "jpeg") {
+        return Ok(None);
+    }
+
+    let mut image = image::open(path)?;
+
+    if let Some(max_dimension) = options.max_dimension {
+        if image.width() > max_dimension || image.height() > max_dimension {
+            image = image.resize(max_dimension, max_dimension, imageops::FilterType::Lanczos3);
+        }
+    }
+
+    let mut writer = BufWriter::new(File::create(output)?);
+
+    if extension == "png" {
+        PngEncoder::new_with_quality(&mut writer, CompressionType::Best, FilterType::Adaptive)
+            .write_image(
+                image.as_bytes(),
+                image.width(),
+                image.height(),
+                image.color(),
+            )?;
+    } else {
+        // JPEG has no alpha channel
+        let image = match image {
+            DynamicImage::ImageLuma8(_) => image,
+            image => DynamicImage::ImageRgb8(image.to_rgb8()),
+        };
+        JpegEncoder::new_with_quality(&mut writer, options.jpeg_quality).write_image(
+            image.as_bytes(),
+            image.width(),
+            image.height(),
+            image.color(),
+        )?;
+    }
+
+    writer.flush()?;
+    drop(writer);
+
+    let optimized_size = fs::metadata(output)?.len();
+
+    if optimized_size >= size {
+        fs::remove_file(output)?;
+        return Ok(None);
+    }
+
+    Ok(Some(size - optimized_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{ImageBuffer, Rgb};
+
+    use super::*;
+    use crate::cache::CacheItem;
+
+    fn pair(image: &Path) -> AssetPair {
+        AssetPair {
+            name: "Asset".to_string(),
+            metadata: String::new(),
+            metadata_hash: String::new(),
+            image: image.to_string_lossy().to_string(),
+            image_hash: encode(&image.to_string_lossy()).unwrap(),
+            animation: None,
+            animation_hash: None,
+            remote: None,
+        }
+    }
+
+    #[test]
+    fn test_optimize_images() {
+        let dir = tempfile::tempdir().unwrap();
+        let assets = dir.path().join("assets");
+        fs::create_dir_all(&assets).unwrap();
+
+        // a gradient saved with the fastest compression, larger than its optimized copy
+        let png = assets.join("0.png");
+        let image = ImageBuffer::from_fn(256, 256, |x, y| Rgb([x as u8, y as u8, (x ^ y) as u8]));
+        PngEncoder::new_with_quality(
+            BufWriter::new(File::create(&png).unwrap()),
+            CompressionType::Fast,
+            FilterType::NoFilter,
+        )
+        .write_image(image.as_raw(), 256, 256, image::ColorType::Rgb8)
+        .unwrap();
+        let gif = assets.join("1.gif");
+        fs::write(&gif, vec![0u8; 4096]).unwrap();
+
+        let source = fs::read(&png).unwrap();
+
+        let mut asset_pairs: HashMap<isize, AssetPair> =
+            HashMap::from([(0, pair(&png)), (1, pair(&gif))]);
+        let mut cache = Cache::new();
+        for (index, pair) in &asset_pairs {
+            cache
+                .items
+                .insert(index.to_string(), pair.clone().into_cache_item());
+        }
+        cache.items.get_mut("1").unwrap().optimized_image_hash = Some("stale".to_string());
+
+        let options = ImageOptimization {
+            threshold_kb: 1,
+            max_dimension: Some(128),
+            work_dir: dir.path().join("work").to_string_lossy().to_string(),
+            ..Default::default()
+        };
+
+        let report = optimize_images(&mut asset_pairs, &[0, 1], &mut cache, &options).unwrap();
+
+        assert_eq!(report.optimized, 1);
+        let optimized = &asset_pairs[&0].image;
+        assert!(optimized.ends_with("0.png"));
+        assert!(Path::new(optimized).starts_with(dir.path().join("work")));
+        assert_eq!(
+            report.bytes_saved,
+            source.len() as u64 - fs::metadata(optimized).unwrap().len()
+        );
+        assert_eq!(image::image_dimensions(optimized).unwrap(), (128, 128));
+
+        // the source image is untouched
+        assert_eq!(fs::read(&png).unwrap(), source);
+
+        let item: &CacheItem = &cache.items["0"];
+        assert_eq!(item.image_hash, encode(&png.to_string_lossy()).unwrap());
+        assert_eq!(item.optimized_image_hash, Some(encode(optimized).unwrap()));
+
+        // GIF images are uploaded unchanged
+        assert_eq!(asset_pairs[&1].image, gif.to_string_lossy());
+        assert_eq!(cache.items["1"].optimized_image_hash, None);
+    }
+}
@@ -0,0 +1,215 @@
+//! Preflight of an upload: size of the files to upload by type, estimated cost of the upload
+//! method and files over the `maxFileSizeMb` limit of the config file.
+
+use std::{collections::HashSet, fmt, fs};
+
+use anchor_client::solana_sdk::native_token::LAMPORTS_PER_SOL;
+use indicatif::HumanBytes;
+
+use crate::{
+    common::*,
+    config::{ConfigData, SugarConfig, UploadMethod},
+    upload::*,
+};
+
+/// A file larger than the size limit.
+#[derive(Debug, PartialEq, Eq)]
+pub struct OversizedFile {
+    pub path: String,
+    pub size: u64,
+}
+
+/// Files of an upload, read before any network call.
+#[derive(Debug, Default)]
+pub struct PreflightReport {
+    /// Number of asset pairs with a file to upload.
+    pub assets: usize,
+    pub image_bytes: u64,
+    pub animation_bytes: u64,
+    pub metadata_bytes: u64,
+    /// Size of each file to upload.
+    pub file_sizes: Vec<u64>,
+    /// Size limit (in MB) of a file.
+    pub max_file_size_mb: Option<u64>,
+    pub oversized: Vec<OversizedFile>,
+}
+
+impl PreflightReport {
+    pub fn total_bytes(&self) -> u64 {
+        self.image_bytes + self.animation_bytes + self.metadata_bytes
+    }
+
+    /// Lists the files over the size limit, one per line.
+    pub fn format_oversized(&self) -> String {
+        self.oversized
+            .iter()
+            .map(|file| format!("  {} ({})", file.path, HumanBytes(file.size)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "assets:    {}", self.assets)?;
+        writeln!(f, "images:    {}", HumanBytes(self.image_bytes))?;
+        if self.animation_bytes > 0 {
+            writeln!(f, "animation: {}", HumanBytes(self.animation_bytes))?;
+        }
+        writeln!(f, "metadata:  {}", HumanBytes(self.metadata_bytes))?;
+        write!(f, "total:     {}", HumanBytes(self.total_bytes()))
+    }
+}
+
+/// Reads the size of the files of the indices, checking them against the size limit.
+///
+/// The metadata files are measured with mock links, as they are uploaded with the links of
+/// their image and animation.
+pub fn preflight(
+    asset_pairs: &HashMap<isize, AssetPair>,
+    indices: &AssetType,
+    max_file_size_mb: Option<u64>,
+) -> Result<PreflightReport> {
+    let mut report = PreflightReport {
+        max_file_size_mb,
+        ..Default::default()
+    };
+    let limit = max_file_size_mb.map(|mb| mb * 1024 * 1024);
+    let mock_uri = "x".repeat(MOCK_URI_SIZE);
+
+    let mut assets = HashSet::new();
+
+    for (data_type, indices) in [
+        (DataType::Image, &indices.image),
+        (DataType::Animation, &indices.animation),
+        (DataType::Metadata, &indices.metadata),
+    ] {
+        for index in indices {
+            let pair = asset_pairs
+                .get(index)
+                .ok_or_else(|| anyhow!("Failed to get asset at index {}", index))?;
+            assets.insert(*index);
+
+            let (path, size) = match data_type {
+                DataType::Image => (pair.image.clone(), pair.image_size()?),
+                DataType::Animation => match &pair.animation {
+                    Some(animation) => (animation.clone(), fs::metadata(animation)?.len()),
+                    None => continue,
+                },
+                DataType::Metadata => {
+                    let animation = pair.animation.as_ref().map(|_| mock_uri.clone());
+                    let metadata = get_updated_metadata(pair, &mock_uri, &animation)?;
+                    (pair.metadata.clone(), metadata.len() as u64)
+                }
+            };
+
+            match data_type {
+                DataType::Image => report.image_bytes += size,
+                DataType::Animation => report.animation_bytes += size,
+                DataType::Metadata => report.metadata_bytes += size,
+            }
+            report.file_sizes.push(size);
+
+            if limit.map_or(false, |limit| size > limit) {
+                report.oversized.push(OversizedFile { path, size });
+            }
+        }
+    }
+
+    report.assets = assets.len();
+    report.oversized.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(report)
+}
+
+/// Estimated cost of the upload with the upload method of the config file.
+pub async fn estimate_cost(
+    sugar_config: &SugarConfig,
+    config_data: &ConfigData,
+    report: &PreflightReport,
+) -> String {
+    match config_data.upload_method {
+        UploadMethod::Bundlr => {
+            match BundlrMethod::estimate_fee(sugar_config, config_data, &report.file_sizes).await {
+                Ok(lamports) => format!(
+                    "{} SOL ({} lamports)",
+                    lamports as f64 / LAMPORTS_PER_SOL as f64,
+                    lamports
+                ),
+                Err(err) => format!("unavailable ({})", err),
+            }
+        }
+        UploadMethod::AWS => {
+            "free on upload (storage and transfer billed by the bucket provider)".to_string()
+        }
+        UploadMethod::NftStorage => "free".to_string(),
+        UploadMethod::Pinata => "covered by the plan of the Pinata account".to_string(),
+        UploadMethod::SHDW => "covered by the size of the storage account".to_string(),
+        UploadMethod::Sdrive => "covered by the SDrive plan".to_string(),
+        UploadMethod::HttpPut => "covered by the self-hosted storage".to_string(),
+        #[cfg(feature = "integration-tests")]
+        UploadMethod::Mock => "free".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preflight() {
+        let dir = std::env::temp_dir().join(format!("sugar-preflight-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let mut asset_pairs = HashMap::new();
+        for (index, image_size) in [(0, 1024), (1, 2 * 1024 * 1024)] {
+            let image = dir.join(format!("{index}.png"));
+            let metadata = dir.join(format!("{index}.json"));
+            fs::write(&image, vec![0u8; image_size]).unwrap();
+            fs::write(
+                &metadata,
+                json!({
+                    "name": format!("#{index}"),
+                    "description": "",
+                    "image": format!("{index}.png"),
+                    "properties": { "files": [{ "uri": format!("{index}.png"), "type": "image/png" }] }
+                })
+                .to_string(),
+            )
+            .unwrap();
+
+            asset_pairs.insert(
+                index,
+                AssetPair {
+                    name: format!("#{index}"),
+                    metadata: metadata.to_string_lossy().to_string(),
+                    metadata_hash: String::new(),
+                    image: image.to_string_lossy().to_string(),
+                    image_hash: String::new(),
+                    animation: None,
+                    animation_hash: None,
+                    remote: None,
+                },
+            );
+        }
+
+        let indices = AssetType {
+            image: vec![0, 1],
+            metadata: vec![0, 1],
+            animation: Vec::new(),
+        };
+
+        let report = preflight(&asset_pairs, &indices, Some(1)).unwrap();
+        assert_eq!(report.assets, 2);
+        assert_eq!(report.image_bytes, 1024 + 2 * 1024 * 1024);
+        assert_eq!(report.file_sizes.len(), 4);
+        assert_eq!(report.oversized.len(), 1);
+        assert!(report.oversized[0].path.ends_with("1.png"));
+
+        let report = preflight(&asset_pairs, &indices, None).unwrap();
+        assert!(report.oversized.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
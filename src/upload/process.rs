@@ -1,24 +1,26 @@
 use std::{
     borrow::Borrow,
     collections::HashSet,
-    ffi::OsStr,
-    fmt::Write as _,
+    fmt::{self, Write as _},
     fs::OpenOptions,
     sync::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::{Duration, Instant},
 };
 
-use console::style;
+use indicatif::HumanBytes;
 
 use crate::{
-    cache::{load_cache, Cache},
+    cache::{format_indices, load_cache, Cache, CacheItem},
     common::*,
-    config::{get_config_data, SugarConfig},
+    config::{get_config_data, BundlrNode, ConfigData, SugarConfig, UploadMethod},
+    output::style,
+    progress_stream,
     upload::*,
     utils::*,
-    validate::format::Metadata,
+    validate::{check_consistency, format::Metadata, ValidateParserError},
 };
 
 pub struct UploadArgs {
@@ -29,6 +31,48 @@ pub struct UploadArgs {
     pub cache: String,
     pub interrupted: Arc<AtomicBool>,
     pub priority_fee: u64,
+    /// Maximum number of files uploaded at the same time (defaults to the limit of the
+    /// upload method).
+    pub concurrency: Option<usize>,
+    /// Only upload the files of the cache items missing a link, without checking the files
+    /// of the complete items for changes.
+    pub only_missing: bool,
+    /// Upload all files, even the ones that did not change since the last upload.
+    pub force: bool,
+    /// Print the size and estimated cost of the upload without uploading.
+    pub dry_run: bool,
+    /// Fund the Bundlr balance before the upload when it is not enough (overrides the
+    /// config file).
+    pub auto_fund: bool,
+    /// Maximum amount (in lamports) funded before the upload (overrides the config file).
+    pub max_fund_lamports: Option<u64>,
+    /// Bundlr (Irys) node of the upload (overrides the config file).
+    pub bundlr_node: Option<BundlrNode>,
+    /// Maximum number of requests per second sent to the storage (no limit when not set).
+    pub requests_per_second: Option<f64>,
+    /// Types of files uploaded.
+    pub scope: UploadScope,
+    /// Path of the report of the files that failed to upload.
+    pub error_report: String,
+    /// Manifest of remote assets uploaded instead of the files of the assets directory.
+    pub from_manifest: Option<String>,
+    /// Assign the assets to the cache indices of a seeded shuffle.
+    pub shuffle: bool,
+    /// Seed of the shuffle (reused from the shuffle map, or random, when not set).
+    pub shuffle_seed: Option<u64>,
+    /// Path of the JSON summary written at the end of the upload, also when it fails.
+    pub summary: Option<String>,
+    /// Read the assets of the subdirectories of the assets directory too.
+    pub recursive: bool,
+    /// Upload optimized copies of the images above the threshold of the config file.
+    pub optimize_images: bool,
+    /// Wait for the confirmation of the Bundlr transactions of the upload.
+    pub verify_receipts: bool,
+    /// Time (in seconds) to wait for the confirmation of a transaction.
+    pub receipt_timeout: u64,
+    /// Number of completed uploads between two writes of the cache (0 writes it at the end
+    /// only).
+    pub flush_every: usize,
 }
 
 pub struct AssetType {
@@ -37,198 +81,144 @@ pub struct AssetType {
     pub animation: Vec<isize>,
 }
 
-pub async fn process_upload(args: UploadArgs) -> Result<()> {
-    let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
-    let config_data = get_config_data(&args.config)?;
+/// Types of files of an upload.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UploadScope {
+    Images,
+    Metadata,
+    Animations,
+    #[default]
+    All,
+}
 
-    // loading assets
-    println!(
-        "{} {}Loading assets",
-        style("[1/4]").bold().dim(),
-        ASSETS_EMOJI
-    );
+impl UploadScope {
+    pub fn includes(&self, data_type: &DataType) -> bool {
+        matches!(
+            (self, data_type),
+            (UploadScope::All, _)
+                | (UploadScope::Images, DataType::Image)
+                | (UploadScope::Metadata, DataType::Metadata)
+                | (UploadScope::Animations, DataType::Animation)
+        )
+    }
+}
 
-    let pb = spinner_with_style();
-    pb.enable_steady_tick(120);
-    pb.set_message("Reading files...");
-    let asset_pairs = get_asset_pairs(&args.assets_dir)?;
+impl FromStr for UploadScope {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value {
+            "images" => Ok(UploadScope::Images),
+            "metadata" => Ok(UploadScope::Metadata),
+            "animations" => Ok(UploadScope::Animations),
+            "all" => Ok(UploadScope::All),
+            _ => Err(anyhow!(
+                "Invalid upload scope '{}', expected 'images', 'metadata', 'animations' or 'all'",
+                value
+            )),
+        }
+    }
+}
 
-    // creates/loads the cache
-    let mut cache = load_cache(&args.cache, true)?;
-    if !asset_pairs.contains_key(&-1) {
-        cache.items.remove("-1");
+impl fmt::Display for UploadScope {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UploadScope::Images => write!(f, "images"),
+            UploadScope::Metadata => write!(f, "metadata"),
+            UploadScope::Animations => write!(f, "animations"),
+            UploadScope::All => write!(f, "all"),
+        }
     }
+}
 
-    // list of indices to upload
-    let mut indices = AssetType {
-        image: Vec::new(),
-        metadata: Vec::new(),
-        animation: Vec::new(),
+pub async fn process_upload(args: UploadArgs) -> Result<()> {
+    let start = Instant::now();
+    let summary_path = args.summary.clone();
+    let mut summary = UploadSummary {
+        cache: args.cache.clone(),
+        dry_run: args.dry_run,
+        ..Default::default()
     };
 
-    for (index, pair) in &asset_pairs {
-        // checks if we have complete URIs in the metadata file;
-        // if true, no upload is necessary and we will use the
-        // existing URIs
-
-        let m: Metadata = {
-            let m = OpenOptions::new()
-                .read(true)
-                .open(&pair.metadata)
-                .map_err(|e| {
-                    anyhow!(
-                        "Failed to read metadata file '{}' with error: {}",
-                        &pair.metadata,
-                        e
-                    )
-                })?;
-            serde_json::from_reader(&m)?
-        };
-
-        if m.properties.creators.is_some() {
-            println!("The creators field is deprecated in the JSON metadata, it should be set in the config file instead.")
-        }
+    let result = run_upload(args, &mut summary).await;
 
-        // retrieve the existing image uri from the metadata
-        let existing_image = if is_complete_uri(&m.image) {
-            m.image.clone()
-        } else {
-            String::new()
-        };
+    if let Some(path) = summary_path {
+        summary.finish(&result, start.elapsed());
 
-        // retrieve the existing animation uri from the metadata
-        let existing_animation = match m.animation_url {
-            Some(ref url) => {
-                if is_complete_uri(url) {
-                    url.clone()
-                } else {
-                    String::new()
-                }
+        if let Err(err) = write_summary(&path, &summary) {
+            // the error of the upload is the one reported
+            if result.is_ok() {
+                return Err(err);
             }
-            None => String::new(),
-        };
-
-        match cache.items.get_mut(&index.to_string()) {
-            Some(item) => {
-                let image_changed = (!item.image_hash.eq(&pair.image_hash)
-                    || item.image_link.is_empty())
-                    && existing_image.is_empty();
+            error!("{}", err);
+        }
+    }
 
-                let animation_changed = (!item.animation_hash.eq(&pair.animation_hash)
-                    || (item.animation_link.is_none() && pair.animation.is_some()))
-                    && existing_animation.is_empty();
+    result
+}
 
-                let metadata_changed =
-                    !item.metadata_hash.eq(&pair.metadata_hash) || item.metadata_link.is_empty();
+async fn run_upload(args: UploadArgs, summary: &mut UploadSummary) -> Result<()> {
+    let sugar_config = sugar_setup(args.keypair.clone(), args.rpc_url.clone())?;
+    let mut config_data = get_config_data(&args.config)?;
+    summary.upload_method = Some(config_data.upload_method.clone());
+
+    if args.auto_fund || args.max_fund_lamports.is_some() {
+        let bundlr_config = config_data
+            .bundlr_config
+            .get_or_insert_with(Default::default);
+        bundlr_config.auto_fund |= args.auto_fund;
+        if args.max_fund_lamports.is_some() {
+            bundlr_config.max_fund_lamports = args.max_fund_lamports;
+        }
+    }
 
-                if image_changed {
-                    // triggers the image upload
-                    item.image_hash = pair.image_hash.clone();
-                    item.image_link = String::new();
-                    indices.image.push(*index);
-                } else if !existing_image.is_empty() {
-                    item.image_hash = pair.image_hash.clone();
-                    item.image_link = existing_image;
-                }
+    if args.bundlr_node.is_some() {
+        config_data.bundlr_node = args.bundlr_node.clone();
+    }
 
-                if animation_changed {
-                    // triggers the animation upload
-                    item.animation_hash = pair.animation_hash.clone();
-                    item.animation_link = None;
-                    indices.animation.push(*index);
-                } else if !existing_animation.is_empty() {
-                    item.animation_hash = pair.animation_hash.clone();
-                    item.animation_link = Some(existing_animation);
-                }
+    // loading assets
+    report!(
+        "{} {}Loading assets",
+        style("[1/4]").bold().dim(),
+        ASSETS_EMOJI
+    );
+    progress_stream::phase_started("load_assets", 1, 4);
 
-                if metadata_changed || image_changed || animation_changed {
-                    // triggers the metadata upload
-                    item.metadata_hash = pair.metadata_hash.clone();
-                    item.metadata_link = String::new();
-                    item.on_chain = false;
-                    // we need to upload metadata only
-                    indices.metadata.push(*index);
-                }
-            }
-            None => {
-                let mut item = pair.clone().into_cache_item();
+    let (mut asset_pairs, mut cache, mut indices) = if let Some(manifest) = &args.from_manifest {
+        load_manifest_assets(&args, manifest).await?
+    } else if args.only_missing {
+        load_missing_assets(&args)?
+    } else {
+        load_changed_assets(&args, &config_data)?
+    };
 
-                // check if we need to upload the image
-                if existing_image.is_empty() {
-                    indices.image.push(*index);
-                } else {
-                    item.image_hash = pair.image_hash.clone();
-                    item.image_link = existing_image;
-                }
+    // the asset pairs of the complete items are not read
+    let partial = args.only_missing || args.from_manifest.is_some();
 
-                // and we might need to upload the animation
-                if pair.animation.is_some() {
-                    if existing_animation.is_empty() {
-                        indices.animation.push(*index);
-                    } else {
-                        item.animation_hash = pair.animation_hash.clone();
-                        item.animation_link = Some(existing_animation);
-                    }
-                }
+    progress_stream::totals(asset_pairs.len() as u64);
+    progress_stream::phase_finished();
 
-                indices.metadata.push(*index);
-                cache.items.insert(index.to_string(), item);
-            }
-        }
-        // sanity check: verifies that both symbol and seller-fee-basis-points are the
-        // same as the ones in the config file
-        let f = File::open(Path::new(&pair.metadata))?;
-        match serde_json::from_reader(f) {
-            Ok(metadata) => {
-                let metadata: Metadata = metadata;
-                // symbol check, but only if the asset actually has the value
-                if let Some(symbol) = metadata.symbol {
-                    if config_data.symbol.ne(&symbol) {
-                        return Err(UploadError::MismatchValue(
-                            "symbol".to_string(),
-                            pair.metadata.clone(),
-                            config_data.symbol,
-                            symbol,
-                        )
-                        .into());
-                    }
-                }
-                // seller-fee-basis-points check, but only if the asset actually has the value
-                if let Some(seller_fee_basis_points) = metadata.seller_fee_basis_points {
-                    if config_data.seller_fee_basis_points != seller_fee_basis_points {
-                        return Err(UploadError::MismatchValue(
-                            "seller_fee_basis_points".to_string(),
-                            pair.metadata.clone(),
-                            config_data.seller_fee_basis_points.to_string(),
-                            seller_fee_basis_points.to_string(),
-                        )
-                        .into());
-                    }
-                }
-            }
-            Err(err) => {
-                let error = anyhow!("Error parsing metadata ({}): {}", pair.metadata, err);
-                error!("{:?}", error);
-                return Err(error);
-            }
-        }
+    if partial {
+        report!(
+            "Skipped {} complete item(s), uploading files of {} item(s):",
+            cache.items.len() - asset_pairs.len(),
+            asset_pairs.len()
+        );
+    } else {
+        report!(
+            "Found {} asset pair(s), uploading files:",
+            asset_pairs.len()
+        );
     }
-
-    pb.finish_and_clear();
-
-    println!(
-        "Found {} asset pair(s), uploading files:",
-        asset_pairs.len()
-    );
-    println!("+--------------------+");
-    println!("| images    | {:>6} |", indices.image.len());
-    println!("| metadata  | {:>6} |", indices.metadata.len());
+    report!("+--------------------+");
+    report!("| images    | {:>6} |", indices.image.len());
+    report!("| metadata  | {:>6} |", indices.metadata.len());
 
     if !indices.animation.is_empty() {
-        println!("| animation | {:>6} |", indices.animation.len());
+        report!("| animation | {:>6} |", indices.animation.len());
     }
 
-    println!("+--------------------+");
+    report!("+--------------------+");
 
     // this should never happen, since every time we update the image file we
     // need to update the metadata
@@ -240,20 +230,80 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
         )));
     }
 
+    if args.optimize_images && !indices.image.is_empty() {
+        let options = config_data.image_optimization.clone().unwrap_or_default();
+
+        let pb = spinner_with_style();
+        pb.enable_steady_tick(120);
+        pb.set_message("Optimizing images...");
+
+        let report = optimize_images(&mut asset_pairs, &indices.image, &mut cache, &options)?;
+        summary.bytes_saved = report.bytes_saved;
+
+        pb.finish_and_clear();
+        report!(
+            "Optimized {} image(s) into '{}', saving {}",
+            report.optimized,
+            options.work_dir,
+            HumanBytes(report.bytes_saved)
+        );
+    }
+
+    // checks the files before any network call
+    let report = preflight(&asset_pairs, &indices, config_data.max_file_size_mb)?;
+
+    if args.dry_run {
+        report!("\n{}", report);
+        report!(
+            "estimated cost ({}): {}",
+            config_data.upload_method,
+            estimate_cost(&sugar_config, &config_data, &report).await
+        );
+
+        if !report.oversized.is_empty() {
+            report!(
+                "\n{}{}\n{}",
+                WARNING_EMOJI,
+                style(format!(
+                    "{} file(s) exceed the 'maxFileSizeMb' limit:",
+                    report.oversized.len()
+                ))
+                .yellow()
+                .bold(),
+                report.format_oversized()
+            );
+        }
+
+        report!("\nDry run: nothing was uploaded.");
+        return Ok(());
+    }
+
+    if !report.oversized.is_empty() {
+        return Err(anyhow!(
+            "{} file(s) exceed the {} MB limit of 'maxFileSizeMb':\n{}",
+            report.oversized.len(),
+            config_data.max_file_size_mb.unwrap_or_default(),
+            report.format_oversized()
+        ));
+    }
+
     let need_upload =
         !indices.image.is_empty() || !indices.metadata.is_empty() || !indices.animation.is_empty();
 
     // ready to upload data
 
     let mut errors = Vec::new();
+    // number of files whose transaction was not confirmed with --verify-receipts
+    let mut receipts_failed = 0;
 
     if need_upload {
         let total_steps = if indices.animation.is_empty() { 4 } else { 5 };
-        println!(
+        report!(
             "\n{} {}Initializing upload",
             style(format!("[2/{}]", total_steps)).bold().dim(),
             COMPUTER_EMOJI
         );
+        progress_stream::phase_started("initialize_upload", 2, total_steps);
 
         let pb = spinner_with_style();
         pb.set_message("Connecting...");
@@ -274,10 +324,15 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
             )
             .await?;
 
+        progress_stream::phase_finished();
+
         // clear the interruption handler value ahead of the upload
         args.interrupted.store(false, Ordering::SeqCst);
 
-        println!(
+        // shared by the uploads of all file types
+        let rate_limiter = RateLimiter::new(args.requests_per_second);
+
+        report!(
             "\n{} {}Uploading image files {}",
             style(format!("[3/{}]", total_steps)).bold().dim(),
             UPLOAD_EMOJI,
@@ -287,6 +342,8 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
                 ""
             }
         );
+        progress_stream::phase_started("upload_images", 3, total_steps);
+        progress_stream::totals(indices.image.len() as u64);
 
         if !indices.image.is_empty() {
             errors.extend(
@@ -298,6 +355,10 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
                     DataType::Image,
                     storage.borrow(),
                     args.interrupted.clone(),
+                    args.concurrency,
+                    args.flush_every,
+                    &rate_limiter,
+                    summary,
                 )
                 .await?,
             );
@@ -305,7 +366,7 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
             // updates the list of metadata indices since the image upload
             // might fail - removes any index that the image upload failed
             if !indices.metadata.is_empty() {
-                for index in indices.image {
+                for &index in &indices.image {
                     let item = cache.items.get(&index.to_string()).unwrap();
 
                     if item.image_link.is_empty() {
@@ -316,12 +377,16 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
             }
         }
 
+        progress_stream::phase_finished();
+
         if !indices.animation.is_empty() {
-            println!(
+            report!(
                 "\n{} {}Uploading animation files",
                 style("[4/5]").bold().dim(),
                 UPLOAD_EMOJI
             );
+            progress_stream::phase_started("upload_animations", 4, total_steps);
+            progress_stream::totals(indices.animation.len() as u64);
         }
 
         if !indices.animation.is_empty() {
@@ -334,6 +399,10 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
                     DataType::Animation,
                     storage.borrow(),
                     args.interrupted.clone(),
+                    args.concurrency,
+                    args.flush_every,
+                    &rate_limiter,
+                    summary,
                 )
                 .await?,
             );
@@ -341,7 +410,7 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
             // updates the list of metadata indices since the image upload
             // might fail - removes any index that the animation upload failed
             if !indices.metadata.is_empty() {
-                for index in indices.animation {
+                for &index in &indices.animation {
                     let item = cache.items.get(&index.to_string()).unwrap();
 
                     if item.animation_link.is_none() {
@@ -350,9 +419,11 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
                     }
                 }
             }
+
+            progress_stream::phase_finished();
         }
 
-        println!(
+        report!(
             "\n{} {}Uploading metadata files {}",
             style(format!("[{}/{}]", total_steps, total_steps))
                 .bold()
@@ -364,6 +435,8 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
                 ""
             }
         );
+        progress_stream::phase_started("upload_metadata", total_steps, total_steps);
+        progress_stream::totals(indices.metadata.len() as u64);
 
         if !indices.metadata.is_empty() {
             errors.extend(
@@ -375,12 +448,31 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
                     DataType::Metadata,
                     storage.borrow(),
                     args.interrupted.clone(),
+                    args.concurrency,
+                    args.flush_every,
+                    &rate_limiter,
+                    summary,
                 )
                 .await?,
             );
         }
+
+        progress_stream::phase_finished();
+
+        report!("\n{}", style(rate_limiter.summary()).dim());
+
+        if args.verify_receipts {
+            receipts_failed = verify_upload_receipts(
+                &sugar_config,
+                &config_data,
+                &mut cache,
+                &indices,
+                args.receipt_timeout,
+            )
+            .await?;
+        }
     } else {
-        println!("\n....no files need uploading, skipping remaining steps.");
+        report!("\n....no files need uploading, skipping remaining steps.");
     }
 
     // move all non-numeric keys to the beginning and sort as strings
@@ -410,46 +502,82 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
     // sanity check
 
     let mut count = 0;
+    let mut animations = 0;
 
     for (index, item) in &cache.items.0 {
-        let asset_pair = asset_pairs.get(&isize::from_str(index)?).ok_or_else(|| {
-            anyhow!(
-                "cache item {} does not have a corresponding asset pair",
-                index
-            )
-        })?;
+        let has_animation = match asset_pairs.get(&isize::from_str(index)?) {
+            Some(asset_pair) => asset_pair.animation.is_some(),
+            // the asset pairs of the complete items are not read with --only-missing
+            None if partial => item.animation_hash.is_some(),
+            None => {
+                return Err(anyhow!(
+                    "cache item {} does not have a corresponding asset pair",
+                    index
+                ))
+            }
+        };
 
-        // we first check that the asset has an animation file; if there is one,
-        // we need to check that the cache item has the link and the link is not empty
-        let missing_animation_link = if asset_pair.animation.is_some() {
-            if let Some(link) = &item.animation_link {
-                link.is_empty()
-            } else {
-                true
+        if has_animation {
+            animations += 1;
+        }
+
+        // only increment the count if the cache item is complete (all links of the scope
+        // are present)
+        let uploaded = match args.scope {
+            UploadScope::All => is_complete(item, has_animation),
+            UploadScope::Images => !item.image_link.is_empty(),
+            UploadScope::Metadata => !item.metadata_link.is_empty(),
+            UploadScope::Animations => {
+                !has_animation
+                    || item
+                        .animation_link
+                        .as_deref()
+                        .map_or(false, |l| !l.is_empty())
             }
-        } else {
-            // the asset does not have animation file
-            false
         };
 
-        // only increment the count if the cache item is complete (all links are present)
-        if !(item.image_link.is_empty() || item.metadata_link.is_empty() || missing_animation_link)
-        {
+        if uploaded {
             count += 1;
         }
     }
 
-    println!(
+    let total = if partial {
+        cache.items.len()
+    } else {
+        asset_pairs.len()
+    };
+
+    report!(
         "\n{}",
-        style(format!(
-            "{}/{} asset pair(s) uploaded.",
-            count,
-            asset_pairs.len()
-        ))
-        .bold()
+        style(format!("{}/{} asset pair(s) uploaded.", count, total)).bold()
     );
 
-    if count != asset_pairs.len() {
+    summary.set_skipped(&DataType::Image, total);
+    summary.set_skipped(&DataType::Metadata, total);
+    summary.set_skipped(&DataType::Animation, animations);
+
+    // the report of a previous upload is removed once the files upload
+    let failures = upload_failures(&errors);
+    if failures.is_empty() {
+        remove_error_report(&args.error_report)?;
+    } else {
+        write_error_report(&args.error_report, &failures)?;
+        report!(
+            "{} file(s) failed to upload, see {}",
+            failures.len(),
+            style(&args.error_report).bold()
+        );
+    }
+
+    if receipts_failed > 0 {
+        return Err(anyhow!(
+            "{} file(s) failed the receipt verification and were marked as not uploaded, run \
+            the upload again to send them",
+            receipts_failed
+        ));
+    }
+
+    if count != total {
         let message = if !errors.is_empty() {
             let mut message = String::new();
             write!(
@@ -480,114 +608,1139 @@ pub async fn process_upload(args: UploadArgs) -> Result<()> {
     Ok(())
 }
 
-/// Upload the data to the selected storage.
-async fn upload_data(
+/// Verifies the Bundlr transactions of the files uploaded, returning the number of files that
+/// failed (their links are removed from the cache).
+async fn verify_upload_receipts(
     sugar_config: &SugarConfig,
-    asset_pairs: &HashMap<isize, AssetPair>,
+    config_data: &ConfigData,
     cache: &mut Cache,
-    indices: &[isize],
-    data_type: DataType,
-    uploader: &dyn Uploader,
-    interrupted: Arc<AtomicBool>,
-) -> Result<Vec<UploadError>> {
-    let mut extension = String::new();
-    let mut paths = Vec::new();
-
-    for index in indices {
-        let item = match asset_pairs.get(index) {
-            Some(asset_index) => asset_index,
-            None => return Err(anyhow::anyhow!("Failed to get asset at index {}", index)),
-        };
-        // chooses the file path based on the data type
-        let file_path = match data_type {
-            DataType::Image => item.image.clone(),
-            DataType::Metadata => item.metadata.clone(),
-            DataType::Animation => {
-                if let Some(animation) = item.animation.clone() {
-                    animation
-                } else {
-                    return Err(anyhow::anyhow!(
-                        "Missing animation path for asset at index {}",
-                        index
-                    ));
-                }
-            }
-        };
+    indices: &AssetType,
+    timeout: u64,
+) -> Result<usize> {
+    if !matches!(config_data.upload_method, UploadMethod::Bundlr) {
+        report!(
+            "{}{}",
+            WARNING_EMOJI,
+            style("Receipts are only verified for Bundlr uploads, skipping.").yellow()
+        );
+        return Ok(0);
+    }
 
-        let path = Path::new(&file_path);
-        let ext = path
-            .extension()
-            .and_then(OsStr::to_str)
-            .expect("Failed to convert extension from unicode");
+    let node = BundlrMethod::get_bundlr_node(sugar_config, config_data.bundlr_node.as_ref())?;
 
-        extension = String::from(ext);
+    let pb = spinner_with_style();
+    pb.enable_steady_tick(120);
+    pb.set_message("Verifying receipts...");
+
+    let report = verify_receipts(
+        &node,
+        cache,
+        vec![
+            (DataType::Image, &indices.image),
+            (DataType::Animation, &indices.animation),
+            (DataType::Metadata, &indices.metadata),
+        ],
+        Duration::from_secs(timeout),
+    )
+    .await?;
 
-        paths.push(file_path);
+    pb.finish_and_clear();
+    report!(
+        "Receipts: {} verified, {} failed",
+        style(report.verified).green(),
+        if report.failed > 0 {
+            style(report.failed).red()
+        } else {
+            style(report.failed)
+        }
+    );
+
+    Ok(report.failed)
+}
+
+/// Assigns the assets to the cache indices of a seeded shuffle, writing the mapping to the
+/// shuffle map.
+fn shuffle_assets(
+    args: &UploadArgs,
+    asset_pairs: HashMap<isize, AssetPair>,
+) -> Result<HashMap<isize, AssetPair>> {
+    // a resumed upload must keep the indices of the previous run
+    let seed = match (args.shuffle_seed, read_shuffle_map(DEFAULT_SHUFFLE_MAP)?) {
+        (Some(seed), _) => seed,
+        (None, Some(map)) => {
+            report!(
+                "Reusing the seed of {}: {}",
+                DEFAULT_SHUFFLE_MAP,
+                style(map.seed).bold()
+            );
+            map.seed
+        }
+        (None, None) => {
+            let seed = rand::random::<u64>();
+            report!(
+                "Shuffle seed: {} (use --shuffle-seed {} to reproduce the mapping)",
+                style(seed).bold(),
+                seed
+            );
+            seed
+        }
+    };
+
+    let (asset_pairs, map) = shuffle_asset_pairs(asset_pairs, seed)?;
+
+    if !args.dry_run {
+        write_shuffle_map(DEFAULT_SHUFFLE_MAP, &map)?;
     }
 
-    let content_type = match data_type {
-        DataType::Image => format!("image/{}", extension),
-        DataType::Metadata => "application/json".to_string(),
-        DataType::Animation => format!("video/{}", extension),
+    Ok(asset_pairs)
+}
+
+/// Loads the asset pairs and the cache, listing the files that changed since the last upload
+/// or that were not uploaded.
+fn load_changed_assets(
+    args: &UploadArgs,
+    config_data: &ConfigData,
+) -> Result<(HashMap<isize, AssetPair>, Cache, AssetType)> {
+    let report = check_consistency(
+        Some(Path::new(&args.assets_dir)),
+        Some(config_data.number),
+        None,
+        args.recursive,
+    )?;
+
+    if !report.is_consistent() {
+        // with hidden settings, the assets are only used for the reveal
+        if config_data.hidden_settings.is_some() {
+            report!(
+                "{}{}\n{}",
+                WARNING_EMOJI,
+                style("Assets do not match the config number of items:")
+                    .yellow()
+                    .bold(),
+                report
+            );
+        } else {
+            return Err(ValidateParserError::InconsistentAssets(report.to_string()).into());
+        }
+    }
+
+    let pb = spinner_with_style();
+    pb.enable_steady_tick(120);
+    pb.set_message("Reading files...");
+    let images = args.scope.includes(&DataType::Image);
+    let animations = args.scope.includes(&DataType::Animation);
+    let metadata = args.scope.includes(&DataType::Metadata);
+
+    // the files outside of the scope are not hashed
+    let mut asset_pairs =
+        get_asset_pairs_hashing(&args.assets_dir, args.recursive, images, animations)?;
+
+    // a shuffled upload keeps the indices of its shuffle map, also without --shuffle
+    if args.shuffle || Path::new(DEFAULT_SHUFFLE_MAP).exists() {
+        asset_pairs = shuffle_assets(args, asset_pairs)?;
+    }
+
+    // creates/loads the cache
+    let mut cache = load_cache(&args.cache, true)?;
+    if !asset_pairs.contains_key(&-1) {
+        cache.items.remove("-1");
+    }
+
+    // files not hashed keep the hash of their last upload
+    for (index, pair) in asset_pairs.iter_mut() {
+        if let Some(item) = cache.items.get(&index.to_string()) {
+            if !images {
+                pair.image_hash = item.image_hash.clone();
+            }
+            if !animations && pair.animation.is_some() {
+                pair.animation_hash = item.animation_hash.clone();
+            }
+        }
+    }
+
+    // list of indices to upload
+    let mut indices = AssetType {
+        image: Vec::new(),
+        metadata: Vec::new(),
+        animation: Vec::new(),
     };
+    // indices of the items with modified files and of the deployed items uploaded again
+    let mut modified = Vec::new();
+    let mut deployed = Vec::new();
+    // indices of the items with new media links whose metadata is outside of the scope
+    let mut stale = Vec::new();
+    // indices of the items whose metadata would be uploaded without a media link
+    let mut missing_image = Vec::new();
+    let mut missing_animation = Vec::new();
+
+    for (index, pair) in &asset_pairs {
+        // checks if we have complete URIs in the metadata file;
+        // if true, no upload is necessary and we will use the
+        // existing URIs
+
+        let m: Metadata = {
+            let m = OpenOptions::new()
+                .read(true)
+                .open(&pair.metadata)
+                .map_err(|e| {
+                    anyhow!(
+                        "Failed to read metadata file '{}' with error: {}",
+                        &pair.metadata,
+                        e
+                    )
+                })?;
+            serde_json::from_reader(&m)?
+        };
+
+        if m.properties.creators.is_some() {
+            report!("The creators field is deprecated in the JSON metadata, it should be set in the config file instead.")
+        }
+
+        // retrieve the existing image uri from the metadata
+        let existing_image = if is_complete_uri(&m.image) {
+            m.image.clone()
+        } else {
+            String::new()
+        };
+
+        // retrieve the existing animation uri from the metadata
+        let existing_animation = match m.animation_url {
+            Some(ref url) => {
+                if is_complete_uri(url) {
+                    url.clone()
+                } else {
+                    String::new()
+                }
+            }
+            None => String::new(),
+        };
+
+        match cache.items.get_mut(&index.to_string()) {
+            Some(item) => {
+                // files modified since the last upload (compared by their SHA-256)
+                let image_modified = !item.image_hash.eq(&pair.image_hash);
+                let animation_modified = !item.animation_hash.eq(&pair.animation_hash);
+                let metadata_modified = !item.metadata_hash.eq(&pair.metadata_hash);
+
+                let image_changed = images
+                    && (args.force || image_modified || item.image_link.is_empty())
+                    && existing_image.is_empty();
+
+                let animation_changed = animations
+                    && (args.force
+                        || animation_modified
+                        || (item.animation_link.is_none() && pair.animation.is_some()))
+                    && existing_animation.is_empty();
+
+                let metadata_changed = metadata
+                    && (args.force
+                        || metadata_modified
+                        || item.metadata_link.is_empty()
+                        || image_changed
+                        || animation_changed);
+
+                if image_modified || animation_modified || (metadata && metadata_modified) {
+                    modified.push(*index);
+                }
+
+                if image_changed {
+                    // triggers the image upload
+                    item.image_hash = pair.image_hash.clone();
+                    item.image_link = String::new();
+                    indices.image.push(*index);
+                } else if !existing_image.is_empty() {
+                    item.image_hash = pair.image_hash.clone();
+                    item.image_link = existing_image;
+                }
+
+                if animation_changed {
+                    // triggers the animation upload
+                    item.animation_hash = pair.animation_hash.clone();
+                    item.animation_link = None;
+                    indices.animation.push(*index);
+                } else if !existing_animation.is_empty() {
+                    item.animation_hash = pair.animation_hash.clone();
+                    item.animation_link = Some(existing_animation);
+                }
+
+                if (image_changed || animation_changed) && !metadata {
+                    // the metadata has the previous links: it is uploaded with the next
+                    // upload of the metadata
+                    item.metadata_hash = String::new();
+                    stale.push(*index);
+                }
+
+                if metadata_changed {
+                    if !images && item.image_link.is_empty() {
+                        missing_image.push(*index);
+                    }
+                    if !animations
+                        && pair.animation.is_some()
+                        && item.animation_link.as_deref().map_or(true, str::is_empty)
+                    {
+                        missing_animation.push(*index);
+                    }
+
+                    if item.on_chain {
+                        // the config line of the item has the previous link
+                        deployed.push(*index);
+                    }
+                    // triggers the metadata upload
+                    item.metadata_hash = pair.metadata_hash.clone();
+                    item.metadata_link = String::new();
+                    item.on_chain = false;
+                    // we need to upload metadata only
+                    indices.metadata.push(*index);
+                }
+            }
+            None => {
+                let mut item = pair.clone().into_cache_item();
+
+                // check if we need to upload the image
+                if !existing_image.is_empty() {
+                    item.image_hash = pair.image_hash.clone();
+                    item.image_link = existing_image;
+                } else if images {
+                    indices.image.push(*index);
+                } else if metadata {
+                    missing_image.push(*index);
+                }
+
+                // and we might need to upload the animation
+                if pair.animation.is_some() {
+                    if !existing_animation.is_empty() {
+                        item.animation_hash = pair.animation_hash.clone();
+                        item.animation_link = Some(existing_animation);
+                    } else if animations {
+                        indices.animation.push(*index);
+                    } else if metadata {
+                        missing_animation.push(*index);
+                    }
+                }
+
+                if metadata {
+                    indices.metadata.push(*index);
+                }
+                cache.items.insert(index.to_string(), item);
+            }
+        }
+        // sanity check: verifies that both symbol and seller-fee-basis-points are the
+        // same as the ones in the config file
+        let f = File::open(Path::new(&pair.metadata))?;
+        match serde_json::from_reader(f) {
+            Ok(metadata) => {
+                let metadata: Metadata = metadata;
+                // symbol check, but only if the asset actually has the value
+                if let Some(symbol) = metadata.symbol {
+                    if config_data.symbol.ne(&symbol) {
+                        return Err(UploadError::MismatchValue(
+                            "symbol".to_string(),
+                            pair.metadata.clone(),
+                            config_data.symbol.clone(),
+                            symbol,
+                        )
+                        .into());
+                    }
+                }
+                // seller-fee-basis-points check, but only if the asset actually has the value
+                if let Some(seller_fee_basis_points) = metadata.seller_fee_basis_points {
+                    if config_data.seller_fee_basis_points != seller_fee_basis_points {
+                        return Err(UploadError::MismatchValue(
+                            "seller_fee_basis_points".to_string(),
+                            pair.metadata.clone(),
+                            config_data.seller_fee_basis_points.to_string(),
+                            seller_fee_basis_points.to_string(),
+                        )
+                        .into());
+                    }
+                }
+            }
+            Err(err) => {
+                let error = anyhow!("Error parsing metadata ({}): {}", pair.metadata, err);
+                error!("{:?}", error);
+                return Err(error);
+            }
+        }
+    }
+
+    pb.finish_and_clear();
+
+    for (missing, file_type) in [
+        (&mut missing_image, "images"),
+        (&mut missing_animation, "animations"),
+    ] {
+        if !missing.is_empty() {
+            return Err(anyhow!(
+                "{} item(s) do not have a link to their {}, upload the {} first with \
+                'sugar upload --scope {}': {}",
+                missing.len(),
+                file_type.trim_end_matches('s'),
+                file_type,
+                file_type,
+                describe_indices(missing)
+            ));
+        }
+    }
+
+    if !modified.is_empty() {
+        report!(
+            "Files changed since the last upload: {}",
+            describe_indices(&mut modified)
+        );
+    }
+
+    if !deployed.is_empty() {
+        report!(
+            "{}{}",
+            WARNING_EMOJI,
+            style(format!(
+                "{} deployed item(s) will be uploaded again, run 'sugar deploy' to update \
+                their config lines: {}",
+                deployed.len(),
+                describe_indices(&mut deployed)
+            ))
+            .yellow()
+            .bold()
+        );
+    }
+
+    if !stale.is_empty() {
+        report!(
+            "{}{}",
+            WARNING_EMOJI,
+            style(format!(
+                "{} item(s) will have new media links, run 'sugar upload --scope metadata' to \
+                upload their metadata: {}",
+                stale.len(),
+                describe_indices(&mut stale)
+            ))
+            .yellow()
+            .bold()
+        );
+    }
+
+    Ok((asset_pairs, cache, indices))
+}
+
+/// Formats the indices as ranges, the collection (index -1) first.
+fn describe_indices(indices: &mut [isize]) -> String {
+    indices.sort_unstable();
+
+    let numbers: Vec<u64> = indices
+        .iter()
+        .filter(|index| **index >= 0)
+        .map(|index| *index as u64)
+        .collect();
+
+    match (indices.first(), numbers.is_empty()) {
+        (Some(-1), true) => "collection".to_string(),
+        (Some(-1), false) => format!("collection, {}", format_indices(&numbers)),
+        _ => format_indices(&numbers),
+    }
+}
+
+/// Loads the cache and the asset pairs of its items that are missing a link, without reading
+/// the files of the complete items.
+fn load_missing_assets(args: &UploadArgs) -> Result<(HashMap<isize, AssetPair>, Cache, AssetType)> {
+    let mut cache = load_cache(&args.cache, false)?;
+    let missing = missing_items(&cache)?;
+
+    // the items of a shuffled upload are read from the files of the shuffle map
+    let files = match read_shuffle_map(DEFAULT_SHUFFLE_MAP)? {
+        Some(map) => Some(map.files_by_index()),
+        None if args.shuffle => {
+            return Err(anyhow!(
+                "Cannot resume a shuffled upload without its shuffle map {}",
+                DEFAULT_SHUFFLE_MAP
+            ))
+        }
+        None => None,
+    };
+    let file_of = |index: isize| match &files {
+        // the collection is not shuffled
+        Some(files) if index >= 0 => files.get(&index).copied().ok_or_else(|| {
+            anyhow!(
+                "Cache item {} is not in the shuffle map {}",
+                index,
+                DEFAULT_SHUFFLE_MAP
+            )
+        }),
+        _ => Ok(index),
+    };
+
+    let mut file_pairs = get_asset_pairs_of(
+        &args.assets_dir,
+        args.recursive,
+        &missing
+            .iter()
+            .map(|index| file_of(*index))
+            .collect::<Result<_>>()?,
+    )?;
+    let mut asset_pairs = HashMap::new();
+    for index in &missing {
+        if let Some(pair) = file_pairs.remove(&file_of(*index)?) {
+            asset_pairs.insert(*index, pair);
+        }
+    }
+
+    let mut indices = AssetType {
+        image: Vec::new(),
+        metadata: Vec::new(),
+        animation: Vec::new(),
+    };
+
+    for index in missing {
+        let pair = asset_pairs.get(&index).ok_or_else(|| {
+            anyhow!(
+                "Cache item {} does not have a corresponding asset pair",
+                index
+            )
+        })?;
+        let item = cache.items.get_mut(&index.to_string()).unwrap();
+
+        let upload_image = item.image_link.is_empty();
+        let upload_animation =
+            pair.animation.is_some() && item.animation_link.as_deref().map_or(true, str::is_empty);
+
+        if upload_image {
+            item.image_hash = pair.image_hash.clone();
+            indices.image.push(index);
+        }
+
+        if upload_animation {
+            item.animation_hash = pair.animation_hash.clone();
+            item.animation_link = None;
+            indices.animation.push(index);
+        }
+
+        if upload_image || upload_animation || item.metadata_link.is_empty() {
+            // the metadata is uploaded again with the new links
+            item.metadata_hash = pair.metadata_hash.clone();
+            item.metadata_link = String::new();
+            item.on_chain = false;
+            indices.metadata.push(index);
+        }
+    }
+
+    Ok((asset_pairs, cache, indices))
+}
+
+/// Loads the cache and the asset pairs of the entries of a manifest of remote assets that are
+/// not in the cache or are missing a link (all entries with `--force`). The remote files of
+/// the complete items are not checked for changes.
+async fn load_manifest_assets(
+    args: &UploadArgs,
+    manifest: &str,
+) -> Result<(HashMap<isize, AssetPair>, Cache, AssetType)> {
+    let entries = read_manifest(manifest)?;
+    let mut cache = load_cache(&args.cache, true)?;
+
+    let pending: Vec<&ManifestEntry> = entries
+        .iter()
+        .filter(|entry| {
+            args.force
+                || cache
+                    .items
+                    .get(&entry.index.to_string())
+                    .map_or(true, |item| {
+                        !is_complete(item, item.animation_hash.is_some())
+                    })
+        })
+        .collect();
+
+    let asset_pairs = fetch_manifest_assets(&pending).await?;
+
+    let mut indices = AssetType {
+        image: Vec::new(),
+        metadata: Vec::new(),
+        animation: Vec::new(),
+    };
+
+    for entry in pending {
+        let pair = &asset_pairs[&entry.index];
+
+        match cache.items.get_mut(&entry.index.to_string()) {
+            Some(item) => {
+                // the image is not uploaded again when an upload was interrupted after it
+                if args.force || item.image_link.is_empty() {
+                    item.image_link = String::new();
+                    indices.image.push(entry.index);
+                }
+                item.name = pair.name.clone();
+                item.metadata_hash = pair.metadata_hash.clone();
+                item.metadata_link = String::new();
+                item.on_chain = false;
+            }
+            None => {
+                cache
+                    .items
+                    .insert(entry.index.to_string(), pair.clone().into_cache_item());
+                indices.image.push(entry.index);
+            }
+        }
+
+        indices.metadata.push(entry.index);
+    }
+
+    Ok((asset_pairs, cache, indices))
+}
+
+/// Indices of the cache items without an image, metadata or animation link.
+fn missing_items(cache: &Cache) -> Result<Vec<isize>> {
+    let mut missing = Vec::new();
+
+    for (key, item) in cache.items.iter() {
+        if !is_complete(item, item.animation_hash.is_some()) {
+            missing.push(
+                key.parse::<isize>()
+                    .map_err(|_| anyhow!("Invalid cache item key '{}'", key))?,
+            );
+        }
+    }
+
+    missing.sort_unstable();
+    Ok(missing)
+}
+
+/// Whether the item has all of its links.
+fn is_complete(item: &CacheItem, has_animation: bool) -> bool {
+    let missing_animation_link =
+        has_animation && item.animation_link.as_deref().map_or(true, str::is_empty);
+
+    !(item.image_link.is_empty() || item.metadata_link.is_empty() || missing_animation_link)
+}
+
+/// Upload the data to the selected storage.
+#[allow(clippy::too_many_arguments)]
+async fn upload_data(
+    sugar_config: &SugarConfig,
+    asset_pairs: &HashMap<isize, AssetPair>,
+    cache: &mut Cache,
+    indices: &[isize],
+    data_type: DataType,
+    uploader: &dyn Uploader,
+    interrupted: Arc<AtomicBool>,
+    concurrency: Option<usize>,
+    flush_every: usize,
+    rate_limiter: &RateLimiter,
+    summary: &mut UploadSummary,
+) -> Result<Vec<UploadError>> {
+    let mut paths = Vec::new();
+
+    for index in indices {
+        let item = match asset_pairs.get(index) {
+            Some(asset_index) => asset_index,
+            None => return Err(anyhow::anyhow!("Failed to get asset at index {}", index)),
+        };
+        // chooses the file path based on the data type
+        let file_path = match data_type {
+            DataType::Image => item.image.clone(),
+            DataType::Metadata => item.metadata.clone(),
+            DataType::Animation => {
+                if let Some(animation) = item.animation.clone() {
+                    animation
+                } else {
+                    return Err(anyhow::anyhow!(
+                        "Missing animation path for asset at index {}",
+                        index
+                    ));
+                }
+            }
+        };
+
+        paths.push((*index, file_path));
+    }
 
     // uploading data
 
-    println!("\nSending data: (Ctrl+C to abort)");
+    report!("\nSending data: (Ctrl+C to abort)");
 
     let pb = progress_bar_with_style(paths.len() as u64);
 
     let mut assets = Vec::new();
+    // size of the file of each asset, for the summary
+    let mut sizes = HashMap::new();
 
-    for file_path in paths {
-        // path to the media/metadata file
-        let path = Path::new(&file_path);
-        let file_name = String::from(
-            path.file_name()
-                .and_then(OsStr::to_str)
-                .expect("Filed to get file name."),
-        );
-        let (asset_id, cache_item) = get_cache_item(path, cache)?;
+    for (index, file_path) in paths {
+        // files are named after the index of their asset (remote and shuffled files do not
+        // have the name of their index)
+        let file_name = asset_file_name(index, &file_path)?;
+        let asset_id = index.to_string();
+        let cache_item = cache
+            .items
+            .get(&asset_id)
+            .ok_or_else(|| anyhow!("Failed to get config item at index '{}'", asset_id))?;
+
+        let pair = &asset_pairs[&index];
 
         let content = match data_type {
             // replaces the media link without modifying the original file to avoid
             // changing the hash of the metadata file
-            DataType::Metadata => get_updated_metadata(
-                &file_path,
-                &cache_item.image_link,
-                &cache_item.animation_link,
-            )?,
+            DataType::Metadata => {
+                get_updated_metadata(pair, &cache_item.image_link, &cache_item.animation_link)?
+            }
             _ => file_path.clone(),
         };
+        // the remote image of a manifest asset is streamed from its URL
+        let remote = match (&data_type, &pair.remote) {
+            (DataType::Image, Some(remote)) => Some(remote.image.clone()),
+            _ => None,
+        };
 
-        assets.push(AssetInfo {
-            asset_id: asset_id.to_string(),
+        let asset_info = AssetInfo {
+            asset_id: asset_id.clone(),
+            content_type: content_type(&file_name, &data_type)?,
             name: file_name,
             content,
             data_type: data_type.clone(),
-            content_type: content_type.clone(),
-        });
+            remote,
+        };
+
+        let size = match data_type {
+            DataType::Metadata => asset_info.content.len() as u64,
+            _ => file_size(&asset_info).unwrap_or_default(),
+        };
+        sizes.insert(asset_id, size);
+
+        assets.push(asset_info);
     }
+    let sent = assets.len();
 
     let errors = uploader
         .upload(
             sugar_config,
             cache,
-            data_type,
+            data_type.clone(),
             &mut assets,
             &pb,
             interrupted,
+            concurrency,
+            flush_every,
+            rate_limiter,
         )
         .await?;
 
+    let failed: HashSet<&String> = errors
+        .iter()
+        .filter_map(|error| match error {
+            UploadError::FileFailed { asset_id, .. } => Some(asset_id),
+            _ => None,
+        })
+        .collect();
+    let bytes = sizes
+        .iter()
+        .filter(|(asset_id, _)| !failed.contains(asset_id))
+        .map(|(_, size)| size)
+        .sum();
+    summary.record(&data_type, sent, failed.len(), bytes);
+
     if !errors.is_empty() {
         pb.abandon_with_message(format!("{}", style("Upload failed ").red().bold()));
     } else {
         pb.finish_with_message(format!("{}", style("Upload successful ").green().bold()));
     }
 
+    // remote images are hashed as they are streamed to the upload method
+    if matches!(data_type, DataType::Image) {
+        for index in indices {
+            let hash = asset_pairs[index]
+                .remote
+                .as_ref()
+                .and_then(|remote| remote.image.hash());
+
+            if let (Some(hash), Some(item)) = (hash, cache.items.get_mut(&index.to_string())) {
+                item.image_hash = hash;
+            }
+        }
+    }
+
     // makes sure the cache file is updated
     cache.sync_file()?;
 
     Ok(errors)
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use anchor_client::solana_sdk::signature::write_keypair_file;
+
+    use super::*;
+    use crate::progress_stream::{close_progress_stream, init_progress_stream};
+
+    const CONFIG: &str = r#"{
+        "number": 2,
+        "symbol": "",
+        "sellerFeeBasisPoints": 0,
+        "isMutable": true,
+        "isSequential": false,
+        "creators": [{ "address": "ENsRsTm9GtfvPKJwg9HhHNUFqoRSUp2TYdfyqDHeNJAn", "share": 100 }],
+        "uploadMethod": "bundlr",
+        "ruleSet": null
+    }"#;
+
+    // the progress stream is global: the tests running an upload do not run at the same time
+    static UPLOAD_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    // writes two asset pairs and a cache where both were already uploaded
+    fn write_fixtures(dir: &Path) -> Result<()> {
+        let _ = fs::remove_dir_all(dir);
+        let assets_dir = dir.join("assets");
+        fs::create_dir_all(&assets_dir)?;
+
+        for i in 0..2 {
+            fs::write(assets_dir.join(format!("{i}.png")), [i as u8; 16])?;
+            fs::write(
+                assets_dir.join(format!("{i}.json")),
+                json!({
+                    "name": format!("#{i}"),
+                    "symbol": "",
+                    "description": "",
+                    "image": format!("{i}.png"),
+                    "properties": {
+                        "files": [{ "uri": format!("{i}.png"), "type": "image/png" }],
+                        "category": "image"
+                    }
+                })
+                .to_string(),
+            )?;
+        }
+
+        let mut cache = Cache::new();
+        for (index, pair) in get_asset_pairs(&assets_dir.to_string_lossy())? {
+            let mut item = pair.into_cache_item();
+            item.image_link = format!("https://example.com/{index}.png");
+            item.metadata_link = format!("https://example.com/{index}.json");
+            cache.items.insert(index.to_string(), item);
+        }
+        cache.write_to_file(dir.join("cache.json"))?;
+
+        fs::write(dir.join("config.json"), CONFIG)?;
+        write_keypair_file(&Keypair::new(), dir.join("keypair.json"))
+            .map_err(|e| anyhow!("{e}"))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn upload_emits_progress_events() {
+        let _lock = UPLOAD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let dir = std::env::temp_dir().join(format!("sugar-progress-{}", std::process::id()));
+        write_fixtures(&dir).unwrap();
+
+        let events_file = dir.join("events.jsonl");
+        init_progress_stream(&events_file.to_string_lossy()).unwrap();
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+        let result = futures::executor::block_on(process_upload(UploadArgs {
+            assets_dir: path("assets"),
+            config: path("config.json"),
+            keypair: Some(path("keypair.json")),
+            rpc_url: Some("http://localhost:8899".to_string()),
+            cache: path("cache.json"),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            priority_fee: 0,
+            concurrency: None,
+            only_missing: false,
+            force: false,
+            dry_run: false,
+            auto_fund: false,
+            max_fund_lamports: None,
+            bundlr_node: None,
+            requests_per_second: None,
+            scope: UploadScope::All,
+            error_report: path("upload-errors.json"),
+            from_manifest: None,
+            shuffle: false,
+            shuffle_seed: None,
+            summary: None,
+            recursive: false,
+            optimize_images: false,
+            verify_receipts: false,
+            receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+            flush_every: DEFAULT_UPLOAD_FLUSH_EVERY,
+        }));
+        close_progress_stream();
+        result.unwrap();
+
+        // example consumer: one JSON event per line
+        let events: Vec<(String, String, Value)> = fs::read_to_string(&events_file)
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let event: Value = serde_json::from_str(line).unwrap();
+                assert_eq!(event["version"], 1);
+                (
+                    event["event"].as_str().unwrap().to_string(),
+                    event["phase"].as_str().unwrap().to_string(),
+                    event,
+                )
+            })
+            .collect();
+
+        let sequence: Vec<(&str, &str)> = events
+            .iter()
+            .map(|(event, phase, _)| (event.as_str(), phase.as_str()))
+            .collect();
+        assert_eq!(
+            sequence,
+            vec![
+                ("phase_started", "load_assets"),
+                ("totals", "load_assets"),
+                ("phase_finished", "load_assets"),
+            ]
+        );
+        assert_eq!(events[0].2["step"], 1);
+        assert_eq!(events[1].2["total"], 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn oversized_file_aborts_before_upload() {
+        let _lock = UPLOAD_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+
+        let dir = std::env::temp_dir().join(format!("sugar-oversized-{}", std::process::id()));
+        write_fixtures(&dir).unwrap();
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // the image of item 1 changed and is over the 1 MB limit
+        fs::write(dir.join("assets").join("1.png"), vec![1u8; 2 * 1024 * 1024]).unwrap();
+        let mut config: Value = serde_json::from_str(CONFIG).unwrap();
+        config["maxFileSizeMb"] = json!(1);
+        fs::write(dir.join("config.json"), config.to_string()).unwrap();
+        let cache = fs::read_to_string(dir.join("cache.json")).unwrap();
+
+        // nothing listens on the RPC url: the upload would fail to connect to the storage
+        let err = futures::executor::block_on(process_upload(UploadArgs {
+            assets_dir: path("assets"),
+            config: path("config.json"),
+            keypair: Some(path("keypair.json")),
+            rpc_url: Some("http://localhost:1".to_string()),
+            cache: path("cache.json"),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            priority_fee: 0,
+            concurrency: None,
+            only_missing: false,
+            force: false,
+            dry_run: false,
+            auto_fund: false,
+            max_fund_lamports: None,
+            bundlr_node: None,
+            requests_per_second: None,
+            scope: UploadScope::All,
+            error_report: path("upload-errors.json"),
+            from_manifest: None,
+            shuffle: false,
+            shuffle_seed: None,
+            summary: Some(path("summary.json")),
+            recursive: false,
+            optimize_images: false,
+            verify_receipts: false,
+            receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+            flush_every: DEFAULT_UPLOAD_FLUSH_EVERY,
+        }))
+        .unwrap_err();
+
+        assert!(err.to_string().contains("maxFileSizeMb"), "{}", err);
+        assert!(err.to_string().contains("1.png"), "{}", err);
+        assert_eq!(fs::read_to_string(dir.join("cache.json")).unwrap(), cache);
+
+        // the summary of the failed run
+        let summary: Value =
+            serde_json::from_str(&fs::read_to_string(dir.join("summary.json")).unwrap()).unwrap();
+        assert_eq!(summary["status"], "failed");
+        assert!(summary["error"].as_str().unwrap().contains("maxFileSizeMb"));
+        assert_eq!(summary["upload_method"], "bundlr");
+        assert_eq!(summary["cache"], path("cache.json"));
+        assert_eq!(summary["bytes_transferred"], 0);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn only_missing_skips_complete_items() {
+        let dir = std::env::temp_dir().join(format!("sugar-only-missing-{}", std::process::id()));
+        write_fixtures(&dir).unwrap();
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // item 1 is missing its metadata link and the image of item 0 is not read again
+        let mut cache = load_cache(&path("cache.json"), false).unwrap();
+        cache.items.get_mut("1").unwrap().metadata_link = String::new();
+        cache.write_to_file(dir.join("cache.json")).unwrap();
+        fs::remove_file(dir.join("assets").join("0.png")).unwrap();
+
+        let args = UploadArgs {
+            assets_dir: path("assets"),
+            config: path("config.json"),
+            keypair: None,
+            rpc_url: None,
+            cache: path("cache.json"),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            priority_fee: 0,
+            concurrency: None,
+            only_missing: true,
+            force: false,
+            dry_run: false,
+            auto_fund: false,
+            max_fund_lamports: None,
+            bundlr_node: None,
+            requests_per_second: None,
+            scope: UploadScope::All,
+            error_report: path("upload-errors.json"),
+            from_manifest: None,
+            shuffle: false,
+            shuffle_seed: None,
+            summary: None,
+            recursive: false,
+            optimize_images: false,
+            verify_receipts: false,
+            receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+            flush_every: DEFAULT_UPLOAD_FLUSH_EVERY,
+        };
+        let (asset_pairs, cache, indices) = load_missing_assets(&args).unwrap();
+
+        assert_eq!(asset_pairs.keys().collect::<Vec<_>>(), vec![&1]);
+        assert!(indices.image.is_empty());
+        assert_eq!(indices.metadata, vec![1]);
+        assert!(!cache.items["1"].image_link.is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn changed_files_are_uploaded_again() {
+        let dir = std::env::temp_dir().join(format!("sugar-changed-{}", std::process::id()));
+        write_fixtures(&dir).unwrap();
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // both items are deployed, then the metadata of item 1 is edited
+        let mut cache = load_cache(&path("cache.json"), false).unwrap();
+        for item in cache.items.values_mut() {
+            item.on_chain = true;
+        }
+        cache.write_to_file(dir.join("cache.json")).unwrap();
+
+        let metadata = dir.join("assets").join("1.json");
+        let edited = fs::read_to_string(&metadata)
+            .unwrap()
+            .replace("\"#1\"", "\"#One\"");
+        fs::write(&metadata, edited).unwrap();
+
+        let config_data = get_config_data(&path("config.json")).unwrap();
+        let args = |force| UploadArgs {
+            assets_dir: path("assets"),
+            config: path("config.json"),
+            keypair: None,
+            rpc_url: None,
+            cache: path("cache.json"),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            priority_fee: 0,
+            concurrency: None,
+            only_missing: false,
+            force,
+            dry_run: false,
+            auto_fund: false,
+            max_fund_lamports: None,
+            bundlr_node: None,
+            requests_per_second: None,
+            scope: UploadScope::All,
+            error_report: path("upload-errors.json"),
+            from_manifest: None,
+            shuffle: false,
+            shuffle_seed: None,
+            summary: None,
+            recursive: false,
+            optimize_images: false,
+            verify_receipts: false,
+            receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+            flush_every: DEFAULT_UPLOAD_FLUSH_EVERY,
+        };
+
+        let (_, cache, indices) = load_changed_assets(&args(false), &config_data).unwrap();
+        assert!(indices.image.is_empty());
+        assert_eq!(indices.metadata, vec![1]);
+        assert!(cache.items["0"].on_chain);
+        assert!(!cache.items["1"].on_chain);
+        assert!(cache.items["1"].metadata_link.is_empty());
+
+        let (_, _, mut indices) = load_changed_assets(&args(true), &config_data).unwrap();
+        indices.image.sort_unstable();
+        assert_eq!(indices.image, vec![0, 1]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn metadata_scope_keeps_image_links() {
+        let dir = std::env::temp_dir().join(format!("sugar-scope-{}", std::process::id()));
+        write_fixtures(&dir).unwrap();
+
+        let path = |name: &str| dir.join(name).to_string_lossy().to_string();
+
+        // the image of item 0 and the metadata of item 1 are edited
+        fs::write(dir.join("assets").join("0.png"), [9u8; 16]).unwrap();
+        let metadata = dir.join("assets").join("1.json");
+        let edited = fs::read_to_string(&metadata)
+            .unwrap()
+            .replace("\"#1\"", "\"#One\"");
+        fs::write(&metadata, edited).unwrap();
+
+        let config_data = get_config_data(&path("config.json")).unwrap();
+        let args = |scope| UploadArgs {
+            assets_dir: path("assets"),
+            config: path("config.json"),
+            keypair: None,
+            rpc_url: None,
+            cache: path("cache.json"),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            priority_fee: 0,
+            concurrency: None,
+            only_missing: false,
+            force: false,
+            dry_run: false,
+            auto_fund: false,
+            max_fund_lamports: None,
+            bundlr_node: None,
+            requests_per_second: None,
+            scope,
+            error_report: path("upload-errors.json"),
+            from_manifest: None,
+            shuffle: false,
+            shuffle_seed: None,
+            summary: None,
+            recursive: false,
+            optimize_images: false,
+            verify_receipts: false,
+            receipt_timeout: DEFAULT_RECEIPT_TIMEOUT_SECS,
+            flush_every: DEFAULT_UPLOAD_FLUSH_EVERY,
+        };
+
+        let (_, cache, indices) =
+            load_changed_assets(&args(UploadScope::Metadata), &config_data).unwrap();
+        assert!(indices.image.is_empty());
+        assert_eq!(indices.metadata, vec![1]);
+        assert_eq!(cache.items["0"].image_link, "https://example.com/0.png");
+        assert_eq!(cache.items["1"].image_link, "https://example.com/1.png");
+
+        // the image upload leaves the metadata for the next metadata upload
+        let (_, mut cache, indices) =
+            load_changed_assets(&args(UploadScope::Images), &config_data).unwrap();
+        assert_eq!(indices.image, vec![0]);
+        assert!(indices.metadata.is_empty());
+        assert!(cache.items["0"].metadata_hash.is_empty());
+
+        // the metadata is not uploaded without the image links
+        cache.write_to_file(dir.join("cache.json")).unwrap();
+        let err = load_changed_assets(&args(UploadScope::Metadata), &config_data)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("--scope images"), "{err}");
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,217 @@
+//! Token bucket shared by the upload workers, slowed down when the storage throttles the
+//! requests (429/503) and sped up again, slowly, while the requests succeed.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::upload::UploadError;
+
+/// Maximum number of times a throttled upload is sent again.
+pub const MAX_THROTTLED_RETRY: usize = 5;
+
+// lowest rate (requests per second) the backoff goes down to
+const MIN_RATE: f64 = 0.5;
+// factor applied to the rate after each successful request
+const RECOVERY_FACTOR: f64 = 1.02;
+// without a --requests-per-second limit, the limiter stops limiting above this rate
+const UNLIMITED_RATE: f64 = 1000.0;
+
+struct Bucket {
+    /// Current rate; `None` while the requests are not limited.
+    rate: Option<f64>,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        if let Some(rate) = self.rate {
+            let elapsed = now.duration_since(self.updated).as_secs_f64();
+            // holds at most one second of requests
+            self.tokens = (self.tokens + elapsed * rate).min(rate.max(1.0));
+        }
+        self.updated = now;
+    }
+}
+
+/// Limits the requests of an upload to `requests_per_second`, halving the rate each time the
+/// storage throttles a request.
+pub struct RateLimiter {
+    /// Rate set by the user (`None` for no limit).
+    target: Option<f64>,
+    bucket: Mutex<Bucket>,
+    requests: AtomicUsize,
+    retries: AtomicUsize,
+    started: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: Option<f64>) -> Self {
+        let target = requests_per_second.map(|rate| rate.max(MIN_RATE));
+        let now = Instant::now();
+
+        Self {
+            target,
+            bucket: Mutex::new(Bucket {
+                rate: target,
+                tokens: target.map_or(0.0, |rate| rate.max(1.0)),
+                updated: now,
+            }),
+            requests: AtomicUsize::new(0),
+            retries: AtomicUsize::new(0),
+            started: now,
+        }
+    }
+
+    /// Waits for a token before sending a request.
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut bucket = self.bucket.lock().unwrap();
+                bucket.refill(Instant::now());
+
+                match bucket.rate {
+                    Some(rate) if bucket.tokens < 1.0 => {
+                        Duration::from_secs_f64((1.0 - bucket.tokens) / rate)
+                    }
+                    Some(_) => {
+                        bucket.tokens -= 1.0;
+                        break;
+                    }
+                    None => break,
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+
+        self.requests.fetch_add(1, Ordering::SeqCst);
+    }
+
+    /// Halves the rate after a throttled request, which will be retried.
+    pub fn throttled(&self) {
+        self.retries.fetch_add(1, Ordering::SeqCst);
+
+        let mut bucket = self.bucket.lock().unwrap();
+        bucket.refill(Instant::now());
+        let rate = bucket.rate.unwrap_or_else(|| self.average_rate());
+        bucket.rate = Some((rate / 2.0).max(MIN_RATE));
+        // no burst until the storage recovers
+        bucket.tokens = 0.0;
+    }
+
+    /// Raises the rate, slowly, back to its limit after a successful request.
+    pub fn succeeded(&self) {
+        let mut bucket = self.bucket.lock().unwrap();
+
+        if let Some(rate) = bucket.rate {
+            let rate = rate * RECOVERY_FACTOR;
+            bucket.rate = match self.target {
+                Some(target) => Some(rate.min(target)),
+                None if rate >= UNLIMITED_RATE => None,
+                None => Some(rate),
+            };
+        }
+    }
+
+    /// Current rate (requests per second), `None` when not limited.
+    pub fn rate(&self) -> Option<f64> {
+        self.bucket.lock().unwrap().rate
+    }
+
+    /// Number of requests retried after being throttled.
+    pub fn retries(&self) -> usize {
+        self.retries.load(Ordering::SeqCst)
+    }
+
+    /// Average rate (requests per second) since the limiter was created.
+    pub fn average_rate(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64().max(0.001);
+        self.requests.load(Ordering::SeqCst) as f64 / elapsed
+    }
+
+    /// One line summary of the requests sent.
+    pub fn summary(&self) -> String {
+        format!(
+            "{} request(s) sent, {} retried after throttling, {:.1} requests/s on average",
+            self.requests.load(Ordering::SeqCst),
+            self.retries(),
+            self.average_rate()
+        )
+    }
+}
+
+/// Checks if an upload failed because the storage throttled the request (429 or 503).
+pub fn is_throttled(error: &anyhow::Error) -> bool {
+    if let Some(UploadError::Throttled(_)) = error.downcast_ref::<UploadError>() {
+        return true;
+    }
+
+    let message = error.to_string();
+    message.contains("429 Too Many Requests") || message.contains("503 Service Unavailable")
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_rate_limiter() {
+        // one second of requests is sent right away, the others are spaced
+        let limiter = RateLimiter::new(Some(100.0));
+        let start = Instant::now();
+
+        for _ in 0..105 {
+            limiter.acquire().await;
+        }
+
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(limiter.requests.load(Ordering::SeqCst), 105);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let limiter = RateLimiter::new(Some(10.0));
+
+        limiter.throttled();
+        limiter.throttled();
+        assert_eq!(limiter.rate(), Some(2.5));
+        assert_eq!(limiter.retries(), 2);
+
+        limiter.succeeded();
+        assert!(limiter.rate().unwrap() > 2.5);
+
+        // recovers up to the limit, never above
+        for _ in 0..200 {
+            limiter.succeeded();
+        }
+        assert_eq!(limiter.rate(), Some(10.0));
+
+        // the backoff never stops the requests
+        for _ in 0..20 {
+            limiter.throttled();
+        }
+        assert_eq!(limiter.rate(), Some(MIN_RATE));
+
+        let unlimited = RateLimiter::new(None);
+        unlimited.succeeded();
+        assert_eq!(unlimited.rate(), None);
+    }
+
+    #[test]
+    fn test_is_throttled() {
+        assert!(is_throttled(&anyhow!(UploadError::Throttled(
+            "0.png".to_string()
+        ))));
+        assert!(is_throttled(&anyhow!(
+            "Error uploading 0.png: 429 Too Many Requests"
+        )));
+        assert!(!is_throttled(&anyhow!("Error uploading 429.png: 500")));
+    }
+}
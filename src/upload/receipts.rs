@@ -0,0 +1,208 @@
+//! Verification of the Bundlr transactions of an upload (`--verify-receipts`). A node can
+//! accept a file whose transaction never lands on Arweave, so the status of each transaction
+//! is polled until it is confirmed; the links of the transactions not confirmed before the
+//! timeout are removed from the cache, so that the next upload sends their files again.
+
+use std::time::Instant;
+
+use futures::{stream, StreamExt};
+use reqwest::StatusCode;
+use tokio::time::{sleep, Duration};
+
+use crate::{common::*, upload::*};
+
+/// Status endpoint of the Arweave transactions, checked when the node no longer knows a
+/// transaction (after its bundle was finalized).
+const ARWEAVE_GATEWAY: &str = "https://arweave.net";
+
+/// Time between two checks of a pending transaction.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Status of a Bundlr transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxStatus {
+    /// Confirmed by the node, or finalized on Arweave.
+    Confirmed,
+    Pending,
+    NotFound,
+}
+
+/// Number of transactions verified and failed.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ReceiptReport {
+    pub verified: usize,
+    pub failed: usize,
+}
+
+/// Id of the Bundlr transaction of a link (`https://gateway.irys.xyz/<id>?ext=png` or
+/// `https://arweave.net/<id>`), `None` for the links of other storages.
+pub fn tx_id(link: &str) -> Option<String> {
+    let url = url::Url::parse(link).ok()?;
+    let host = url.host_str()?;
+
+    if !(host.ends_with("irys.xyz") || host.ends_with("arweave.net")) {
+        return None;
+    }
+
+    let mut segments = url.path_segments()?;
+
+    match (segments.next(), segments.next()) {
+        (Some(id), None) if !id.is_empty() => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Parses the response of the `tx/<id>/status` endpoint of a node.
+fn parse_status(body: &Value) -> TxStatus {
+    match body["status"].as_str().map(str::to_uppercase).as_deref() {
+        Some("CONFIRMED") | Some("FINALIZED") => TxStatus::Confirmed,
+        _ => TxStatus::Pending,
+    }
+}
+
+async fn tx_status(client: &HttpClient, node: &str, id: &str) -> Result<TxStatus> {
+    let response = client.get(format!("{node}/tx/{id}/status")).send().await?;
+
+    match response.status() {
+        StatusCode::OK => Ok(parse_status(&response.json::<Value>().await?)),
+        StatusCode::NOT_FOUND => {
+            let response = client
+                .get(format!("{ARWEAVE_GATEWAY}/tx/{id}/status"))
+                .send()
+                .await?;
+
+            Ok(match response.status() {
+                StatusCode::OK => TxStatus::Confirmed,
+                StatusCode::NOT_FOUND => TxStatus::NotFound,
+                _ => TxStatus::Pending,
+            })
+        }
+        code => Err(anyhow!(
+            "Failed to get the status of transaction {id}: {code}"
+        )),
+    }
+}
+
+/// Polls the status of a transaction until it is confirmed or the timeout expires, returning
+/// the last status.
+async fn wait_confirmed(client: &HttpClient, node: &str, id: &str, timeout: Duration) -> TxStatus {
+    let start = Instant::now();
+
+    loop {
+        let status = match tx_status(client, node, id).await {
+            Ok(status) => status,
+            Err(err) => {
+                debug!("{:#}", err);
+                TxStatus::Pending
+            }
+        };
+
+        if status == TxStatus::Confirmed || start.elapsed() + POLL_INTERVAL > timeout {
+            return status;
+        }
+
+        sleep(POLL_INTERVAL).await;
+    }
+}
+
+/// Verifies the transactions of the links of the files uploaded (the cache items of the
+/// indices of each type), removing the links that fail. The metadata link of an item is
+/// removed too when its image or animation fails, since the metadata file points to them.
+pub async fn verify_receipts(
+    node: &str,
+    cache: &mut Cache,
+    uploaded: Vec<(DataType, &[isize])>,
+    timeout: Duration,
+) -> Result<ReceiptReport> {
+    let client = HttpClient::new();
+    let mut links = Vec::new();
+
+    for (data_type, indices) in uploaded {
+        for index in indices {
+            let key = index.to_string();
+            let link = cache.items.get(&key).and_then(|item| match data_type {
+                DataType::Image => Some(&item.image_link),
+                DataType::Animation => item.animation_link.as_ref(),
+                DataType::Metadata => Some(&item.metadata_link),
+            });
+
+            if let Some(id) = link.and_then(|link| tx_id(link)) {
+                links.push((key, data_type.clone(), id));
+            }
+        }
+    }
+
+    let client = &client;
+    let mut results = stream::iter(links)
+        .map(|(key, data_type, id)| async move {
+            let status = wait_confirmed(client, node, &id, timeout).await;
+            (key, data_type, id, status)
+        })
+        .buffer_unordered(PARALLEL_LIMIT);
+
+    let mut report = ReceiptReport::default();
+
+    while let Some((key, data_type, id, status)) = results.next().await {
+        if status == TxStatus::Confirmed {
+            report.verified += 1;
+            continue;
+        }
+
+        warn!("Transaction {} of item {} is {:?}", id, key, status);
+        report.failed += 1;
+
+        let item = cache.items.get_mut(&key).unwrap();
+        match data_type {
+            DataType::Image => {
+                item.image_link = String::new();
+                item.metadata_link = String::new();
+            }
+            DataType::Animation => {
+                item.animation_link = None;
+                item.metadata_link = String::new();
+            }
+            DataType::Metadata => item.metadata_link = String::new(),
+        }
+    }
+
+    cache.sync_file()?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tx_id() {
+        assert_eq!(
+            tx_id("https://gateway.irys.xyz/AbC-123_x?ext=png"),
+            Some("AbC-123_x".to_string())
+        );
+        assert_eq!(
+            tx_id("https://arweave.net/AbC-123_x"),
+            Some("AbC-123_x".to_string())
+        );
+        assert_eq!(tx_id(""), None);
+        assert_eq!(tx_id("https://gateway.irys.xyz/"), None);
+        assert_eq!(tx_id("https://cdn.example.com/0.png"), None);
+    }
+
+    #[test]
+    fn test_parse_status() {
+        assert_eq!(
+            parse_status(&json!({ "status": "CONFIRMED" })),
+            TxStatus::Confirmed
+        );
+        assert_eq!(
+            parse_status(&json!({ "status": "FINALIZED" })),
+            TxStatus::Confirmed
+        );
+        assert_eq!(
+            parse_status(&json!({ "status": "PENDING" })),
+            TxStatus::Pending
+        );
+        assert_eq!(parse_status(&json!({})), TxStatus::Pending);
+    }
+}
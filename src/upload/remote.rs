@@ -0,0 +1,345 @@
+//! Remote files of an upload from a manifest. The metadata files are downloaded before the
+//! upload, the media files are streamed from their URL by the upload method when they are
+//! sent, resuming interrupted downloads with range requests and hashing them as they arrive.
+//! The files are read once per upload: their content, size and hash are kept by the asset
+//! pairs of the upload.
+
+use std::{
+    fs,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use data_encoding::HEXLOWER;
+use futures::{stream, Stream, TryStreamExt};
+use reqwest::{header, StatusCode};
+use ring::digest::{Context, SHA256};
+
+use crate::{common::*, progress_stream, upload::AssetInfo};
+
+// number of times an interrupted download is resumed
+const MAX_RESUME: u32 = 5;
+// delay before resuming a download, doubled on each attempt
+const RESUME_DELAY: Duration = Duration::from_millis(500);
+
+/// Remote files of an asset of a manifest.
+#[derive(Debug, Clone)]
+pub struct RemoteAsset {
+    /// Content of the metadata, downloaded before the upload.
+    pub metadata: Arc<Vec<u8>>,
+    /// Image, streamed when it is uploaded.
+    pub image: Arc<RemoteFile>,
+}
+
+/// A remote file. The hash of the file is known once it was downloaded.
+#[derive(Debug)]
+pub struct RemoteFile {
+    client: HttpClient,
+    url: String,
+    size: Option<u64>,
+    hash: Mutex<Option<String>>,
+}
+
+impl RemoteFile {
+    /// A remote file of unknown size.
+    pub fn new(client: HttpClient, url: &str) -> Self {
+        Self {
+            client,
+            url: url.to_string(),
+            size: None,
+            hash: Mutex::new(None),
+        }
+    }
+
+    /// Reads the size of a remote file (from the `Content-Length` of a `HEAD` request).
+    pub async fn fetch(client: HttpClient, url: &str) -> Result<Self> {
+        let response = client.head(url).send().await?.error_for_status()?;
+        let size = response
+            .headers()
+            .get(header::CONTENT_LENGTH)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| anyhow!("Missing content length of remote file {}", url))?;
+
+        Ok(Self {
+            size: Some(size),
+            ..Self::new(client, url)
+        })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Size of the file, from the `Content-Length` read by [`RemoteFile::fetch`].
+    pub fn size(&self) -> Result<u64> {
+        self.size
+            .ok_or_else(|| anyhow!("Unknown size of remote file {}", self.url))
+    }
+
+    /// Hash of the file, known once the file was downloaded.
+    pub fn hash(&self) -> Option<String> {
+        self.hash.lock().ok().and_then(|hash| hash.clone())
+    }
+
+    /// Streams the file in chunks of `chunk_size` bytes (the last chunk can be smaller), so
+    /// that at most one chunk is in memory. The file is hashed as it is streamed.
+    pub fn chunks(
+        self: &Arc<Self>,
+        chunk_size: usize,
+    ) -> impl Stream<Item = Result<Vec<u8>>> + Send + 'static {
+        stream::try_unfold(
+            Download::new(self.clone()),
+            move |mut download| async move {
+                let chunk = download.next_chunk(chunk_size).await?;
+                Ok(chunk.map(|chunk| (chunk, download)))
+            },
+        )
+    }
+
+    /// Downloads the file in memory.
+    pub async fn download(self: &Arc<Self>) -> Result<Vec<u8>> {
+        let chunks: Vec<Vec<u8>> = self.chunks(usize::MAX).try_collect().await?;
+        Ok(chunks.concat())
+    }
+}
+
+/// Download of a remote file, resuming the download with a range request when it is
+/// interrupted.
+struct Download {
+    file: Arc<RemoteFile>,
+    response: Option<Response>,
+    context: Context,
+    /// Bytes of the file received.
+    read: u64,
+    /// Bytes received but not yet returned.
+    pending: Vec<u8>,
+    /// Bytes to skip when a server without range requests sends the file again.
+    skip: u64,
+    attempt: u32,
+}
+
+impl Download {
+    fn new(file: Arc<RemoteFile>) -> Self {
+        Self {
+            file,
+            response: None,
+            context: Context::new(&SHA256),
+            read: 0,
+            pending: Vec::new(),
+            skip: 0,
+            attempt: 0,
+        }
+    }
+
+    async fn next_chunk(&mut self, chunk_size: usize) -> Result<Option<Vec<u8>>> {
+        while self.pending.len() < chunk_size {
+            match self.next_bytes().await? {
+                Some(bytes) => self.pending.extend_from_slice(&bytes),
+                None => break,
+            }
+        }
+
+        if self.pending.is_empty() {
+            self.finish()?;
+            return Ok(None);
+        }
+
+        let rest = self
+            .pending
+            .split_off(std::cmp::min(chunk_size, self.pending.len()));
+        Ok(Some(std::mem::replace(&mut self.pending, rest)))
+    }
+
+    /// Next bytes of the file, or `None` once the whole file was received.
+    async fn next_bytes(&mut self) -> Result<Option<Vec<u8>>> {
+        loop {
+            match self.receive().await {
+                Ok(Some(mut bytes)) => {
+                    if self.skip > 0 {
+                        let skipped = std::cmp::min(self.skip, bytes.len() as u64);
+                        bytes.drain(..skipped as usize);
+                        self.skip -= skipped;
+                        if bytes.is_empty() {
+                            continue;
+                        }
+                    }
+
+                    self.context.update(&bytes);
+                    self.read += bytes.len() as u64;
+                    if let Some(size) = self.file.size {
+                        progress_stream::file_progress(&self.file.url, self.read, size);
+                    }
+                    return Ok(Some(bytes));
+                }
+                Ok(None) => return Ok(None),
+                Err(err) if self.attempt < MAX_RESUME && !is_client_error(&err) => {
+                    warn!(
+                        "Download of {} interrupted after {} bytes (attempt {}): {}",
+                        self.file.url,
+                        self.read,
+                        self.attempt + 1,
+                        err
+                    );
+                    self.response = None;
+                    tokio::time::sleep(RESUME_DELAY * 2u32.pow(self.attempt)).await;
+                    self.attempt += 1;
+                }
+                Err(err) => return Err(anyhow!("Failed to download {}: {}", self.file.url, err)),
+            }
+        }
+    }
+
+    /// Receives the next bytes of the response, sending the request of the remaining bytes of
+    /// the file first.
+    async fn receive(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut response = match self.response.take() {
+            Some(response) => response,
+            None => {
+                let mut request = self.file.client.get(&self.file.url);
+                if self.read > 0 {
+                    request = request.header(header::RANGE, format!("bytes={}-", self.read));
+                }
+                let response = request.send().await?.error_for_status()?;
+
+                // the server does not support range requests: skips the bytes received
+                self.skip = if self.read > 0 && response.status() != StatusCode::PARTIAL_CONTENT {
+                    self.read
+                } else {
+                    0
+                };
+                response
+            }
+        };
+
+        // the response is dropped on error, the download is resumed with a new request
+        let bytes = response.chunk().await?;
+        self.response = Some(response);
+        Ok(bytes.map(|bytes| bytes.to_vec()))
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if let Some(size) = self.file.size.filter(|size| *size != self.read) {
+            return Err(anyhow!(
+                "Remote file {} changed during the upload: received {} of {} bytes",
+                self.file.url,
+                self.read,
+                size
+            ));
+        }
+
+        let context = std::mem::replace(&mut self.context, Context::new(&SHA256));
+        let hash = HEXLOWER.encode(context.finish().as_ref());
+        if let Ok(mut file_hash) = self.file.hash.lock() {
+            *file_hash = Some(hash);
+        }
+
+        Ok(())
+    }
+}
+
+fn is_client_error(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<reqwest::Error>()
+        .and_then(|error| error.status())
+        .map_or(false, |status| status.is_client_error())
+}
+
+/// Checks if the path of an asset is the URL of a remote file.
+pub fn is_remote(path: &str) -> bool {
+    path.starts_with("https://") || path.starts_with("http://")
+}
+
+/// Size of a media file: a local file, or the remote file of an asset of a manifest.
+pub fn file_size(asset_info: &AssetInfo) -> Result<u64> {
+    match &asset_info.remote {
+        Some(file) => file.size(),
+        None => Ok(fs::metadata(&asset_info.content)?.len()),
+    }
+}
+
+/// Reads the data of a media file: a local file, or the remote file of an asset of a
+/// manifest, which is downloaded.
+pub async fn read_file(asset_info: &AssetInfo) -> Result<Vec<u8>> {
+    match &asset_info.remote {
+        Some(file) => file.download().await,
+        None => fs::read(&asset_info.content)
+            .map_err(|e| anyhow!("Failed to read file '{}': {}", asset_info.content, e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upload::{
+        assets::{asset_file_name, encode},
+        methods::test_utils::serve,
+        streaming::should_stream,
+        DataType,
+    };
+
+    #[test]
+    fn test_remote_files() {
+        assert!(is_remote("https://renders.example.com/0.png"));
+        assert!(!is_remote("assets/0.png"));
+
+        assert_eq!(
+            asset_file_name(7, "https://renders.example.com/job/frame.PNG?sig=abc").unwrap(),
+            "7.PNG"
+        );
+        assert_eq!(
+            asset_file_name(-1, "assets/collection.jpg").unwrap(),
+            "collection.jpg"
+        );
+        // the name of a shuffled asset follows its index, not its file
+        assert_eq!(asset_file_name(12, "assets/5.png").unwrap(), "12.png");
+        assert!(asset_file_name(0, "https://renders.example.com/0").is_err());
+
+        let file = RemoteFile::new(HttpClient::new(), "https://renders.example.com/test/0.json");
+        assert!(file.size().is_err());
+        assert_eq!(file.hash(), None);
+    }
+
+    #[tokio::test]
+    async fn test_stream_remote_file() {
+        let content = "0123456789".repeat(3);
+        let (base_url, requests) = serve({
+            let content = content.clone();
+            move |_| (200, content.clone())
+        });
+        let url = format!("{}/renders/0.png", base_url);
+
+        let file = Arc::new(RemoteFile::fetch(HttpClient::new(), &url).await.unwrap());
+        assert_eq!(file.size().unwrap(), 30);
+        assert_eq!(file.hash(), None);
+
+        let asset_info = AssetInfo {
+            asset_id: "0".to_string(),
+            name: "0.png".to_string(),
+            content: url.clone(),
+            data_type: DataType::Image,
+            content_type: "image/png".to_string(),
+            remote: Some(file.clone()),
+        };
+        assert!(should_stream(&asset_info, 24));
+        assert!(!should_stream(&asset_info, 30));
+
+        let sizes: Vec<usize> = file
+            .chunks(8)
+            .map_ok(|chunk| chunk.len())
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(sizes, vec![8, 8, 8, 6]);
+
+        // the file is hashed as it is streamed, like the local files
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.png");
+        fs::write(&path, &content).unwrap();
+        assert_eq!(file.hash(), Some(encode(path.to_str().unwrap()).unwrap()));
+
+        assert_eq!(read_file(&asset_info).await.unwrap(), content.as_bytes());
+        // HEAD, then a GET for each read
+        assert_eq!(requests.lock().unwrap().len(), 3);
+    }
+}
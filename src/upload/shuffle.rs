@@ -0,0 +1,194 @@
+//! Seeded shuffle of the assets of an upload: the files keep their names, but each asset is
+//! assigned to the cache index of a Fisher–Yates permutation, so that the order of the config
+//! lines is not the order of the files.
+//!
+//! The permutation only depends on the seed and the number of assets (the random numbers are
+//! generated with SplitMix64), so the same seed always produces the same mapping.
+
+use std::fs;
+
+use serde::Serialize;
+
+use crate::{common::*, upload::AssetPair};
+
+/// Mapping of the assets of a shuffled upload.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShuffleMap {
+    pub seed: u64,
+    /// Cache index of each asset file (the collection is not shuffled).
+    pub assets: Vec<ShuffledAsset>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShuffledAsset {
+    /// Index of the files of the asset in the assets directory.
+    pub file: usize,
+    /// Index of the asset in the cache.
+    pub index: usize,
+}
+
+impl ShuffleMap {
+    /// File index of the asset at each cache index.
+    pub fn files_by_index(&self) -> HashMap<isize, isize> {
+        self.assets
+            .iter()
+            .map(|asset| (asset.index as isize, asset.file as isize))
+            .collect()
+    }
+}
+
+/// SplitMix64 generator, used for its stable output across versions and platforms.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform number in `0..bound`, rejecting the values that would bias the result.
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - (u64::MAX % bound);
+
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+}
+
+/// Cache index of each of the `len` assets, from a Fisher–Yates shuffle.
+pub fn permutation(len: usize, seed: u64) -> Vec<usize> {
+    let mut rng = SplitMix64(seed);
+    let mut indices: Vec<usize> = (0..len).collect();
+
+    for i in (1..len).rev() {
+        let j = rng.below(i as u64 + 1) as usize;
+        indices.swap(i, j);
+    }
+
+    indices
+}
+
+/// Assigns the asset pairs (indexed by file) to their shuffled cache index.
+pub fn shuffle_asset_pairs(
+    asset_pairs: HashMap<isize, AssetPair>,
+    seed: u64,
+) -> Result<(HashMap<isize, AssetPair>, ShuffleMap)> {
+    // the files must be numbered from 0 without gaps, the collection is -1
+    let len = asset_pairs.keys().filter(|index| **index >= 0).count();
+    if let Some(file) = asset_pairs.keys().find(|index| **index >= len as isize) {
+        return Err(anyhow!(
+            "Cannot shuffle the assets: the {} files must be numbered from 0 to {} without \
+            gaps, found file {}",
+            len,
+            len - 1,
+            file
+        ));
+    }
+    let indices = permutation(len, seed);
+
+    let shuffled = asset_pairs
+        .into_iter()
+        .map(|(file, pair)| match usize::try_from(file) {
+            Ok(file) => (indices[file] as isize, pair),
+            Err(_) => (file, pair),
+        })
+        .collect();
+
+    let map = ShuffleMap {
+        seed,
+        assets: indices
+            .into_iter()
+            .enumerate()
+            .map(|(file, index)| ShuffledAsset { file, index })
+            .collect(),
+    };
+
+    Ok((shuffled, map))
+}
+
+/// Reads the shuffle map of a previous upload, if there is one.
+pub fn read_shuffle_map(path: &str) -> Result<Option<ShuffleMap>> {
+    match fs::read_to_string(path) {
+        Ok(content) => {
+            Ok(Some(serde_json::from_str(&content).map_err(|e| {
+                anyhow!("Invalid shuffle map '{}': {}", path, e)
+            })?))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+pub fn write_shuffle_map(path: &str, map: &ShuffleMap) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(map)?)
+        .map_err(|e| anyhow!("Failed to write the shuffle map '{}': {}", path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_permutation() {
+        let first = permutation(1000, 42);
+        assert_eq!(first, permutation(1000, 42));
+        assert_ne!(first, permutation(1000, 43));
+
+        // every index is assigned once
+        let unique: HashSet<usize> = first.iter().copied().collect();
+        assert_eq!(unique.len(), 1000);
+        assert!(first.iter().all(|index| *index < 1000));
+
+        // the output does not change across versions
+        assert_eq!(permutation(5, 7), vec![4, 1, 3, 0, 2]);
+
+        assert!(permutation(0, 1).is_empty());
+    }
+
+    #[test]
+    fn test_shuffle_asset_pairs() {
+        let pair = |name: &str| AssetPair {
+            name: name.to_string(),
+            metadata: format!("{name}.json"),
+            metadata_hash: String::new(),
+            image: format!("{name}.png"),
+            image_hash: String::new(),
+            animation: None,
+            animation_hash: None,
+            remote: None,
+        };
+
+        let mut asset_pairs = HashMap::new();
+        asset_pairs.insert(-1, pair("collection"));
+        for i in 0..10 {
+            asset_pairs.insert(i, pair(&i.to_string()));
+        }
+
+        let (shuffled, map) = shuffle_asset_pairs(asset_pairs.clone(), 99).unwrap();
+        assert_eq!(shuffled.len(), 11);
+        assert_eq!(shuffled[&-1].name, "collection");
+        assert_eq!(map.assets.len(), 10);
+
+        let files = map.files_by_index();
+        for asset in &map.assets {
+            assert_eq!(
+                shuffled[&(asset.index as isize)].name,
+                asset.file.to_string()
+            );
+            assert_eq!(files[&(asset.index as isize)], asset.file as isize);
+        }
+
+        // a gap in the file indices is an error, not a panic
+        asset_pairs.remove(&3);
+        let err = shuffle_asset_pairs(asset_pairs, 99).unwrap_err();
+        assert!(err.to_string().contains("without gaps, found file"));
+    }
+}
@@ -0,0 +1,214 @@
+//! Streaming of large files. A file over the streaming threshold is read from disk (or from
+//! its URL for a remote file) in chunks of [`STREAM_CHUNK_SIZE`] bytes, so that at most one
+//! chunk per file is in memory: AWS sends the chunks with a multipart upload, Bundlr with its
+//! chunked upload API, and the other HTTP methods as a streamed request body.
+
+use std::{fs::File, io::Read, sync::Arc};
+
+use futures::{stream::BoxStream, StreamExt};
+use reqwest::{multipart::Part, Body};
+
+use crate::{
+    common::*,
+    config::ConfigData,
+    constants::DEFAULT_STREAMING_THRESHOLD_MB,
+    progress_stream,
+    upload::{
+        remote::{file_size, read_file, RemoteFile},
+        AssetInfo, DataType,
+    },
+};
+
+/// Size of the chunks of a streamed file (and of the parts of a multipart upload).
+pub const STREAM_CHUNK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Size (in bytes) above which a file is streamed, from the `streamingThresholdMb` of the
+/// config file.
+pub fn streaming_threshold(config_data: &ConfigData) -> u64 {
+    config_data
+        .streaming_threshold_mb
+        .unwrap_or(DEFAULT_STREAMING_THRESHOLD_MB)
+        * 1024
+        * 1024
+}
+
+/// Checks if a media file (local or remote) is larger than the threshold. Metadata is never
+/// streamed.
+pub fn should_stream(asset_info: &AssetInfo, threshold: u64) -> bool {
+    !matches!(asset_info.data_type, DataType::Metadata)
+        && file_size(asset_info)
+            .map(|size| size > threshold)
+            .unwrap_or(false)
+}
+
+/// Chunks of a streamed media file, read from disk or downloaded from the URL of a remote
+/// file. Each call reads the file again.
+pub fn file_chunks(
+    asset_info: &AssetInfo,
+    chunk_size: usize,
+) -> Result<BoxStream<'static, Result<Vec<u8>>>> {
+    Ok(match &asset_info.remote {
+        Some(file) => file.chunks(chunk_size).boxed(),
+        None => futures::stream::iter(FileChunks::open(&asset_info.content, chunk_size)?).boxed(),
+    })
+}
+
+/// Reader of the chunks of a file, reporting the progress of the file as the chunks are read.
+pub struct FileChunks {
+    file: File,
+    name: String,
+    chunk_size: usize,
+    size: u64,
+    read: u64,
+}
+
+impl FileChunks {
+    pub fn open(path: &str, chunk_size: usize) -> Result<Self> {
+        let file =
+            File::open(path).map_err(|e| anyhow!("Failed to open file '{}': {}", path, e))?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            file,
+            name: path.to_string(),
+            chunk_size,
+            size,
+            read: 0,
+        })
+    }
+
+    /// Size of the file.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Returns the next chunk of the file, or `None` once the whole file was read.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<u8>>> {
+        let mut chunk = Vec::with_capacity(self.chunk_size);
+        (&mut self.file)
+            .take(self.chunk_size as u64)
+            .read_to_end(&mut chunk)
+            .map_err(|e| anyhow!("Failed to read file '{}': {}", self.name, e))?;
+
+        if chunk.is_empty() {
+            return Ok(None);
+        }
+
+        self.read += chunk.len() as u64;
+        debug!("Read {} of {} bytes of {}", self.read, self.size, self.name);
+        progress_stream::file_progress(&self.name, self.read, self.size);
+
+        Ok(Some(chunk))
+    }
+}
+
+impl Iterator for FileChunks {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_chunk().transpose()
+    }
+}
+
+/// Data of an asset upload: the files over the streaming threshold are streamed from disk or
+/// from their URL, the other files and the metadata are read in memory.
+pub enum UploadData {
+    Memory(Vec<u8>),
+    File { path: String, size: u64 },
+    Remote { file: Arc<RemoteFile>, size: u64 },
+}
+
+impl UploadData {
+    pub async fn load(asset_info: &AssetInfo, streaming_threshold: u64) -> Result<Self> {
+        match (&asset_info.data_type, &asset_info.remote) {
+            (DataType::Metadata, _) => Ok(Self::Memory(asset_info.content.clone().into_bytes())),
+            (_, remote) if should_stream(asset_info, streaming_threshold) => Ok(match remote {
+                Some(file) => Self::Remote {
+                    file: file.clone(),
+                    size: file.size()?,
+                },
+                None => Self::File {
+                    path: asset_info.content.clone(),
+                    size: std::fs::metadata(&asset_info.content)?.len(),
+                },
+            }),
+            _ => Ok(Self::Memory(read_file(asset_info).await?)),
+        }
+    }
+
+    /// Size of the data.
+    pub fn size(&self) -> u64 {
+        match self {
+            Self::Memory(data) => data.len() as u64,
+            Self::File { size, .. } | Self::Remote { size, .. } => *size,
+        }
+    }
+
+    /// Request body with the data, reading a file one chunk at a time as it is sent. A body
+    /// is consumed by its request, so each attempt creates a new one.
+    pub fn body(&self) -> Result<Body> {
+        match self {
+            Self::Memory(data) => Ok(Body::from(data.clone())),
+            Self::File { path, .. } => Ok(chunks_body(FileChunks::open(path, STREAM_CHUNK_SIZE)?)),
+            Self::Remote { file, .. } => Ok(Body::wrap_stream(file.chunks(STREAM_CHUNK_SIZE))),
+        }
+    }
+
+    /// Multipart form part with the data.
+    pub fn part(&self) -> Result<Part> {
+        match self {
+            Self::Memory(data) => Ok(Part::bytes(data.clone())),
+            Self::File { size, .. } | Self::Remote { size, .. } => {
+                Ok(Part::stream_with_length(self.body()?, *size))
+            }
+        }
+    }
+}
+
+/// Request body sending the chunks as they are read.
+pub fn chunks_body<I>(chunks: I) -> Body
+where
+    I: Iterator<Item = Result<Vec<u8>>> + Send + Sync + 'static,
+{
+    Body::wrap_stream(futures::stream::iter(chunks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_chunks() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("0.mp4");
+        std::fs::write(&path, vec![7u8; 25]).unwrap();
+        let path = path.to_str().unwrap();
+
+        let mut chunks = FileChunks::open(path, 10).unwrap();
+        assert_eq!(chunks.size(), 25);
+
+        let mut sizes = Vec::new();
+        while let Some(chunk) = chunks.next_chunk().unwrap() {
+            sizes.push(chunk.len());
+        }
+        assert_eq!(sizes, vec![10, 10, 5]);
+
+        let sizes: Vec<usize> = FileChunks::open(path, 20)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().len())
+            .collect();
+        assert_eq!(sizes, vec![20, 5]);
+
+        let asset_info = |data_type| AssetInfo {
+            asset_id: "0".to_string(),
+            name: "0.mp4".to_string(),
+            content: path.to_string(),
+            data_type,
+            content_type: "video/mp4".to_string(),
+            remote: None,
+        };
+        assert!(should_stream(&asset_info(DataType::Animation), 24));
+        assert!(!should_stream(&asset_info(DataType::Animation), 25));
+        assert!(!should_stream(&asset_info(DataType::Metadata), 0));
+    }
+}
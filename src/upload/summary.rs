@@ -0,0 +1,135 @@
+//! Machine-readable summary of an upload (`--summary`), written at the end of the run, also
+//! when the upload fails, so that CI pipelines can read the result without parsing the console
+//! output.
+
+use std::{fs, time::Duration};
+
+use serde::Serialize;
+
+use crate::{common::*, config::UploadMethod, upload::*};
+
+/// Result of the upload.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SummaryStatus {
+    #[default]
+    Succeeded,
+    Failed,
+}
+
+/// Number of files of a type uploaded, skipped (unchanged or not ready to upload) and failed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct FileCounts {
+    pub uploaded: usize,
+    pub skipped: usize,
+    pub failed: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct UploadSummary {
+    pub status: SummaryStatus,
+    /// Error of a failed upload.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Upload method of the config file (unknown when the config file could not be read).
+    pub upload_method: Option<UploadMethod>,
+    pub cache: String,
+    pub dry_run: bool,
+    pub wall_time_seconds: f64,
+    /// Bytes of the files uploaded successfully.
+    pub bytes_transferred: u64,
+    /// Bytes saved by the image optimization (`--optimize-images`).
+    pub bytes_saved: u64,
+    pub images: FileCounts,
+    pub metadata: FileCounts,
+    pub animations: FileCounts,
+}
+
+impl UploadSummary {
+    pub fn counts_mut(&mut self, data_type: &DataType) -> &mut FileCounts {
+        match data_type {
+            DataType::Image => &mut self.images,
+            DataType::Metadata => &mut self.metadata,
+            DataType::Animation => &mut self.animations,
+        }
+    }
+
+    /// Records the upload of the files of a type: the number of files sent, the number that
+    /// failed and the bytes of the files uploaded.
+    pub fn record(&mut self, data_type: &DataType, sent: usize, failed: usize, bytes: u64) {
+        let counts = self.counts_mut(data_type);
+        counts.uploaded += sent.saturating_sub(failed);
+        counts.failed += failed;
+        self.bytes_transferred += bytes;
+    }
+
+    /// Sets the number of files of a type that were not sent, out of the total.
+    pub fn set_skipped(&mut self, data_type: &DataType, total: usize) {
+        let counts = self.counts_mut(data_type);
+        counts.skipped = total.saturating_sub(counts.uploaded + counts.failed);
+    }
+
+    /// Sets the status and duration of the run from its result.
+    pub fn finish(&mut self, result: &Result<()>, elapsed: Duration) {
+        self.wall_time_seconds = elapsed.as_secs_f64();
+
+        if let Err(err) = result {
+            self.status = SummaryStatus::Failed;
+            self.error = Some(format!("{:#}", err));
+        }
+    }
+}
+
+/// Writes the summary file (a JSON object).
+pub fn write_summary(path: &str, summary: &UploadSummary) -> Result<()> {
+    fs::write(path, serde_json::to_string_pretty(summary)?)
+        .map_err(|err| anyhow!("Failed to write the upload summary '{}': {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let mut summary = UploadSummary {
+            upload_method: Some(UploadMethod::Bundlr),
+            cache: "cache.json".to_string(),
+            ..Default::default()
+        };
+
+        summary.record(&DataType::Image, 10, 2, 4_000);
+        summary.record(&DataType::Metadata, 8, 0, 800);
+        summary.set_skipped(&DataType::Image, 12);
+        summary.set_skipped(&DataType::Metadata, 12);
+        summary.set_skipped(&DataType::Animation, 0);
+        summary.finish(
+            &Err(anyhow!("Failed to upload all files")),
+            Duration::from_millis(1_500),
+        );
+
+        let path = std::env::temp_dir().join(format!("upload-summary-{}.json", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+
+        write_summary(&path, &summary).unwrap();
+        let written: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            written,
+            json!({
+                "status": "failed",
+                "error": "Failed to upload all files",
+                "upload_method": "bundlr",
+                "cache": "cache.json",
+                "dry_run": false,
+                "wall_time_seconds": 1.5,
+                "bytes_transferred": 4_800,
+                "bytes_saved": 0,
+                "images": { "uploaded": 8, "skipped": 2, "failed": 2 },
+                "metadata": { "uploaded": 8, "skipped": 4, "failed": 0 },
+                "animations": { "uploaded": 0, "skipped": 0, "failed": 0 }
+            })
+        );
+    }
+}
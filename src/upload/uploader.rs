@@ -1,5 +1,4 @@
 use std::{
-    cmp,
     collections::HashMap,
     sync::{
         atomic::{AtomicBool, Ordering},
@@ -7,20 +6,25 @@ use std::{
     },
 };
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use console::style;
-use futures::future::select_all;
+use futures::{stream, StreamExt};
 pub use indicatif::ProgressBar;
 use tokio::task::JoinHandle;
 
 use crate::{
     cache::Cache,
+    cancel,
     config::{ConfigData, SugarConfig, UploadMethod},
     constants::PARALLEL_LIMIT,
+    output::style,
+    progress_stream,
     upload::{
         assets::{AssetPair, DataType},
+        is_retriable,
         methods::*,
+        rate_limit::{is_throttled, RateLimiter, MAX_THROTTLED_RETRY},
+        remote::RemoteFile,
         UploadError,
     },
 };
@@ -28,6 +32,9 @@ use crate::{
 // Size of the mock media URI for cost calculations.
 pub const MOCK_URI_SIZE: usize = 100;
 
+/// Default number of completed uploads between two writes of the cache.
+pub const DEFAULT_UPLOAD_FLUSH_EVERY: usize = 20;
+
 /// Struct representing an asset ready for upload. An `AssetInfo` can represent
 /// a physical file, in which case the `content` will correspond to the name
 /// of the file; or an in-memory asset, in which case the `content` will correspond
@@ -36,6 +43,7 @@ pub const MOCK_URI_SIZE: usize = 100;
 /// For example, for image files, the `content` contains the path of the file on the
 /// file system. In the case of json metadata files, the `content` contains the string
 /// representation of the json metadata.
+#[derive(Clone)]
 pub struct AssetInfo {
     /// Id of the asset in the cache.
     pub asset_id: String,
@@ -47,6 +55,9 @@ pub struct AssetInfo {
     pub data_type: DataType,
     /// MIME content type.
     pub content_type: String,
+    /// Remote file of a media asset of a manifest, streamed from its URL (the `content` is
+    /// the URL of the file).
+    pub remote: Option<Arc<RemoteFile>>,
 }
 
 /// Types that can be prepared to upload assets (files).
@@ -100,6 +111,11 @@ pub trait Uploader: Prepare {
     ///                the console
     /// * `interrupted` - Reference to the shared interruption handler [`flag`](std::sync::atomic::AtomicBool)
     ///                   to receive notifications
+    /// * `concurrency` - Maximum number of files uploaded at the same time, when set by the user
+    ///                   (implementations choose their own limit otherwise)
+    /// * `flush_every` - Number of completed uploads between two syncs of the cache file (0 syncs
+    ///                   it at the end only)
+    /// * `rate_limiter` - Shared [`limiter`](RateLimiter) of the requests sent to the storage
     ///
     /// # Examples
     ///
@@ -140,6 +156,7 @@ pub trait Uploader: Prepare {
     /// // after several uploads
     /// cache.sync_file()?;
     /// ```
+    #[allow(clippy::too_many_arguments)]
     async fn upload(
         &self,
         sugar_config: &SugarConfig,
@@ -148,6 +165,9 @@ pub trait Uploader: Prepare {
         assets: &mut Vec<AssetInfo>,
         progress: &ProgressBar,
         interrupted: Arc<AtomicBool>,
+        concurrency: Option<usize>,
+        flush_every: usize,
+        rate_limiter: &RateLimiter,
     ) -> Result<Vec<UploadError>>;
 }
 
@@ -185,9 +205,14 @@ pub trait ParallelUploader: Uploader + Send + Sync {
 /// Default implementation of the trait ['Uploader'](Uploader) for all ['ParallelUploader'](ParallelUploader).
 #[async_trait]
 impl<T: ParallelUploader> Uploader for T {
-    /// Uploads assets in parallel. It creates [`self::parallel_limit()`] tasks at a time to avoid
-    /// reaching the limit of concurrent files open and it syncs the cache file at every `self.parallel_limit() / 2`
-    /// step.
+    /// Uploads assets with a pool of `concurrency` workers (by default
+    /// [`self::parallel_limit()`]), which also bounds the number of files open at a time. The
+    /// links are written to the cache as the uploads complete, from this task only, and the
+    /// cache file is synced every `flush_every` completed uploads (0 syncs it at the end
+    /// only).
+    ///
+    /// Each upload waits for the `rate_limiter`; a throttled upload slows it down and is
+    /// sent again, up to `MAX_THROTTLED_RETRY` times.
     async fn upload(
         &self,
         _sugar_config: &SugarConfig,
@@ -196,72 +221,100 @@ impl<T: ParallelUploader> Uploader for T {
         assets: &mut Vec<AssetInfo>,
         progress: &ProgressBar,
         interrupted: Arc<AtomicBool>,
+        concurrency: Option<usize>,
+        flush_every: usize,
+        rate_limiter: &RateLimiter,
     ) -> Result<Vec<UploadError>> {
-        let limit = self.parallel_limit();
-        let mut handles = Vec::new();
+        let concurrency = concurrency.unwrap_or_else(|| self.parallel_limit()).max(1);
+        let total = assets.len();
 
-        for task in assets.drain(0..cmp::min(assets.len(), limit)) {
-            handles.push(self.upload_asset(task));
-        }
+        let mut results = stream::iter(assets.drain(..))
+            .map(|asset| async move {
+                let mut retries = 0;
 
-        let mut errors = Vec::new();
+                loop {
+                    rate_limiter.acquire().await;
+                    let result = self.upload_asset(asset.clone()).await;
 
-        while !interrupted.load(Ordering::SeqCst) && !handles.is_empty() {
-            match select_all(handles).await {
-                (Ok(res), _index, remaining) => {
-                    // independently if the upload was successful or not
-                    // we continue to try the remaining ones
-                    handles = remaining;
-                    if res.is_ok() {
-                        let val = res?;
-                        let link = val.clone().1;
-                        // cache item to update
-                        let item = cache.items.0.get_mut(&val.0).unwrap();
-                        match data_type {
-                            DataType::Image => item.image_link = link,
-                            DataType::Metadata => item.metadata_link = link,
-                            DataType::Animation => item.animation_link = Some(link),
+                    match &result {
+                        Ok(Err(err)) if is_throttled(err) && retries < MAX_THROTTLED_RETRY => {
+                            rate_limiter.throttled();
+                            retries += 1;
+                            continue;
                         }
-                        // updates the progress bar
-                        progress.inc(1);
-                    } else {
-                        // user will need to retry the upload
-                        errors.push(UploadError::SendDataFailed(format!(
-                            "Upload error: {:?}",
-                            res.err().unwrap()
-                        )));
+                        Ok(Ok(_)) => rate_limiter.succeeded(),
+                        _ => (),
                     }
+
+                    return (asset.asset_id, asset.name, result);
                 }
-                (Err(err), _index, remaining) => {
-                    errors.push(UploadError::SendDataFailed(format!(
-                        "Upload error: {:?}",
-                        err
-                    )));
-                    // ignoring all errors
-                    handles = remaining;
-                }
-            }
-            if !assets.is_empty() {
-                // if we are half way through, let spawn more transactions
-                if (limit - handles.len()) > (limit / 2) {
-                    // syncs cache (checkpoint)
-                    cache.sync_file()?;
-                    // determine the number of task to release
-                    let task_count =
-                        cmp::min(assets.len(), if limit < 2 { limit } else { limit / 2 });
-
-                    for task in assets.drain(0..task_count) {
-                        handles.push(self.upload_asset(task));
+            })
+            .buffer_unordered(concurrency);
+
+        let mut errors = Vec::new();
+        let mut completed = 0;
+
+        while !interrupted.load(Ordering::SeqCst) {
+            let (asset_id, file, result) = match results.next().await {
+                Some(result) => result,
+                None => break,
+            };
+            completed += 1;
+
+            // independently if the upload was successful or not
+            // we continue to try the remaining ones
+            match result {
+                Ok(Ok((asset_id, link))) => {
+                    // cache item to update
+                    let item = cache
+                        .items
+                        .get_mut(&asset_id)
+                        .ok_or_else(|| anyhow!("Missing cache item {}", asset_id))?;
+                    match data_type {
+                        DataType::Image => item.image_link = link,
+                        DataType::Metadata => item.metadata_link = link,
+                        DataType::Animation => item.animation_link = Some(link),
                     }
+                    // updates the progress bar
+                    progress.inc(1);
+                    progress_stream::item_completed(Some(asset_id), Ok::<_, String>(()));
+                }
+                Ok(Err(err)) => {
+                    // user will need to retry the upload
+                    let error = UploadError::FileFailed {
+                        asset_id: asset_id.clone(),
+                        file,
+                        data_type: data_type.clone(),
+                        error: format!("{:#}", err),
+                        retriable: is_retriable(&err),
+                    };
+                    progress_stream::item_completed(Some(asset_id), Err(&error));
+                    errors.push(error);
+                }
+                Err(err) => {
+                    let error = UploadError::FileFailed {
+                        asset_id: asset_id.clone(),
+                        file,
+                        data_type: data_type.clone(),
+                        error: err.to_string(),
+                        retriable: true,
+                    };
+                    progress_stream::item_completed(Some(asset_id), Err(&error));
+                    errors.push(error);
                 }
             }
+
+            if flush_every > 0 && completed % flush_every == 0 {
+                // syncs cache (checkpoint)
+                cache.sync_file()?;
+            }
         }
 
-        if errors.is_empty() && !assets.is_empty() {
-            progress.abandon_with_message(format!("{}", style("Upload aborted ").red().bold()));
-            return Err(
-                UploadError::SendDataFailed("Not all files were uploaded.".to_string()).into(),
-            );
+        if completed < total {
+            // saves the links of the files uploaded so far
+            cache.sync_file()?;
+            progress.abandon_with_message(format!("{}", style("Upload interrupted ").red().bold()));
+            return Err(cancel::cancelled().into());
         }
 
         Ok(errors)
@@ -292,5 +345,269 @@ pub async fn initialize(
         UploadMethod::Sdrive => {
             Box::new(sdrive::SdriveMethod::new(config_data).await?) as Box<dyn Uploader>
         }
+        UploadMethod::HttpPut => {
+            Box::new(HttpPutMethod::new(config_data).await?) as Box<dyn Uploader>
+        }
+        #[cfg(feature = "integration-tests")]
+        UploadMethod::Mock => Box::new(mock::MockMethod::new()) as Box<dyn Uploader>,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicUsize, Mutex},
+        time::Duration,
+    };
+
+    use anchor_client::solana_sdk::signature::Keypair;
+
+    use super::*;
+    use crate::cache::CacheItem;
+
+    /// Uploader that counts the uploads of each asset and the uploads in flight.
+    #[derive(Default)]
+    struct MockUploader {
+        uploads: Arc<Mutex<HashMap<String, usize>>>,
+        in_flight: Arc<AtomicUsize>,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl Prepare for MockUploader {
+        async fn prepare(
+            &self,
+            _sugar_config: &SugarConfig,
+            _asset_pairs: &HashMap<isize, AssetPair>,
+            _asset_indices: Vec<(DataType, &[isize])>,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    impl ParallelUploader for MockUploader {
+        fn upload_asset(&self, asset: AssetInfo) -> JoinHandle<Result<(String, String)>> {
+            let uploads = self.uploads.clone();
+            let in_flight = self.in_flight.clone();
+            let max_in_flight = self.max_in_flight.clone();
+
+            tokio::spawn(async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                let index: i64 = asset.asset_id.parse()?;
+                tokio::time::sleep(Duration::from_millis(index.unsigned_abs() % 7)).await;
+
+                *uploads
+                    .lock()
+                    .unwrap()
+                    .entry(asset.asset_id.clone())
+                    .or_default() += 1;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                Ok((
+                    asset.asset_id,
+                    format!("https://mock.storage/{}", asset.name),
+                ))
+            })
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_parallel_upload_keeps_every_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let total = 1000;
+
+        let mut cache = Cache::new();
+        cache.file_path = dir.path().join("cache.json").to_string_lossy().to_string();
+
+        let mut assets = Vec::new();
+        for i in 0..total {
+            cache.items.insert(
+                i.to_string(),
+                CacheItem {
+                    name: format!("#{}", i),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: String::new(),
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+            assets.push(AssetInfo {
+                asset_id: i.to_string(),
+                name: format!("{}.png", i),
+                content: String::new(),
+                data_type: DataType::Image,
+                content_type: "image/png".to_string(),
+                remote: None,
+            });
+        }
+
+        let uploader = MockUploader::default();
+        let sugar_config = SugarConfig {
+            keypair: Keypair::new(),
+            rpc_url: String::new(),
+        };
+
+        let errors = uploader
+            .upload(
+                &sugar_config,
+                &mut cache,
+                DataType::Image,
+                &mut assets,
+                &ProgressBar::hidden(),
+                Arc::new(AtomicBool::new(false)),
+                Some(32),
+                DEFAULT_UPLOAD_FLUSH_EVERY,
+                &RateLimiter::new(None),
+            )
+            .await
+            .unwrap();
+
+        assert!(errors.is_empty());
+
+        let uploads = uploader.uploads.lock().unwrap();
+        assert_eq!(uploads.len(), total);
+        assert!(uploads.values().all(|count| *count == 1));
+
+        let max_in_flight = uploader.max_in_flight.load(Ordering::SeqCst);
+        assert!(max_in_flight <= 32, "{} uploads in flight", max_in_flight);
+        assert!(max_in_flight > 1);
+
+        for (key, item) in cache.items.iter() {
+            assert_eq!(item.image_link, format!("https://mock.storage/{}.png", key));
+        }
+
+        // the cache file has the links of the checkpoints
+        let synced = crate::cache::load_cache(&cache.file_path, false).unwrap();
+        assert!(synced
+            .items
+            .values()
+            .any(|item| !item.image_link.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn test_parallel_upload_flush_every() {
+        let sugar_config = SugarConfig {
+            keypair: Keypair::new(),
+            rpc_url: String::new(),
+        };
+
+        for flush_every in [0, 5] {
+            let dir = tempfile::tempdir().unwrap();
+
+            let mut cache = Cache::new();
+            cache.file_path = dir.path().join("cache.json").to_string_lossy().to_string();
+
+            let mut assets = Vec::new();
+            for i in 0..10 {
+                cache.items.insert(
+                    i.to_string(),
+                    CacheItem {
+                        name: format!("#{}", i),
+                        image_hash: String::new(),
+                        image_link: String::new(),
+                        metadata_hash: String::new(),
+                        metadata_link: String::new(),
+                        on_chain: false,
+                        animation_hash: None,
+                        animation_link: None,
+                        optimized_image_hash: None,
+                    },
+                );
+                assets.push(AssetInfo {
+                    asset_id: i.to_string(),
+                    name: format!("{}.png", i),
+                    content: String::new(),
+                    data_type: DataType::Image,
+                    content_type: "image/png".to_string(),
+                    remote: None,
+                });
+            }
+
+            let errors = MockUploader::default()
+                .upload(
+                    &sugar_config,
+                    &mut cache,
+                    DataType::Image,
+                    &mut assets,
+                    &ProgressBar::hidden(),
+                    Arc::new(AtomicBool::new(false)),
+                    None,
+                    flush_every,
+                    &RateLimiter::new(None),
+                )
+                .await
+                .unwrap();
+            assert!(errors.is_empty());
+
+            // without checkpoints, the cache file is left to the caller
+            let synced = std::path::Path::new(&cache.file_path).exists();
+            assert_eq!(synced, flush_every > 0, "flush every {}", flush_every);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_parallel_upload_writes_collection_item() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let mut cache = Cache::new();
+        cache.file_path = dir.path().join("cache.json").to_string_lossy().to_string();
+
+        let mut assets = Vec::new();
+        for (asset_id, name) in [("-1", "collection.json"), ("0", "0.json")] {
+            cache.items.insert(
+                asset_id.to_string(),
+                CacheItem {
+                    name: asset_id.to_string(),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: String::new(),
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+            assets.push(AssetInfo {
+                asset_id: asset_id.to_string(),
+                name: name.to_string(),
+                content: "{}".to_string(),
+                data_type: DataType::Metadata,
+                content_type: "application/json".to_string(),
+                remote: None,
+            });
+        }
+
+        let sugar_config = SugarConfig {
+            keypair: Keypair::new(),
+            rpc_url: String::new(),
+        };
+
+        let errors = MockUploader::default()
+            .upload(
+                &sugar_config,
+                &mut cache,
+                DataType::Metadata,
+                &mut assets,
+                &ProgressBar::hidden(),
+                Arc::new(AtomicBool::new(false)),
+                None,
+                DEFAULT_UPLOAD_FLUSH_EVERY,
+                &RateLimiter::new(None),
+            )
+            .await
+            .unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            cache.items["-1"].metadata_link,
+            "https://mock.storage/collection.json"
+        );
+    }
+}
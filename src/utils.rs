@@ -11,10 +11,9 @@ use anchor_client::{
     Program,
 };
 pub use anyhow::{anyhow, Result};
-use console::{style, Style};
-use dialoguer::theme::ColorfulTheme;
+use borsh::BorshDeserialize;
 pub use indicatif::{ProgressBar, ProgressStyle};
-use mpl_token_metadata::ID as TOKEN_METADATA_PROGRAM_ID;
+use mpl_token_metadata::{state::Metadata, ID as TOKEN_METADATA_PROGRAM_ID};
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::{
     rpc_client::RpcClient,
@@ -23,7 +22,12 @@ use solana_client::{
 };
 use spl_token::state::{Account as SplAccount, Mint};
 
-use crate::{common::*, config::data::Cluster};
+use crate::{
+    account_fetcher::{AccountFetcher, AccountSource},
+    common::*,
+    config::data::Cluster,
+    output::{has_terminal, style},
+};
 
 /// Hash for devnet cluster
 pub const DEVNET_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
@@ -31,6 +35,9 @@ pub const DEVNET_HASH: &str = "EtWTRABZaYq6iMfeYKouRu166VU2xqa1wcaWoxPkrZBG";
 /// Hash for mainnet-beta cluster
 pub const MAINNET_HASH: &str = "5eykt4UsFv8P8NJdTREpY1vzqKqZKvdpKuc147dw2N9d";
 
+/// Hash for testnet cluster
+pub const TESTNET_HASH: &str = "4uhcVJyU9pJkvQyS88uRDiswHXSCkY3zQawwpjk2NsNY";
+
 /// Return the environment of the current connected RPC.
 pub fn get_cluster(rpc_client: RpcClient) -> Result<Cluster> {
     let devnet_hash = Hash::from_str(DEVNET_HASH).unwrap();
@@ -88,6 +95,10 @@ pub fn check_spl_token_account<C: Deref<Target = impl Signer> + Clone>(
 }
 
 pub fn spinner_with_style() -> ProgressBar {
+    if !has_terminal() {
+        return ProgressBar::hidden();
+    }
+
     let pb = ProgressBar::new_spinner();
     pb.enable_steady_tick(120);
     pb.set_style(
@@ -121,6 +132,10 @@ pub fn wait_with_spinner_and_countdown(seconds: u64) {
 }
 
 pub fn progress_bar_with_style(len: u64) -> ProgressBar {
+    if !has_terminal() {
+        return ProgressBar::hidden();
+    }
+
     let pb = ProgressBar::new(len);
     // forces the progress bar to show immediately
     pb.tick();
@@ -131,15 +146,6 @@ pub fn progress_bar_with_style(len: u64) -> ProgressBar {
     pb
 }
 
-pub fn get_dialoguer_theme() -> ColorfulTheme {
-    ColorfulTheme {
-        prompt_style: Style::new(),
-        checked_item_prefix: style("✔".to_string()).green().force_styling(true),
-        unchecked_item_prefix: style("✔".to_string()).black().force_styling(true),
-        ..Default::default()
-    }
-}
-
 pub fn assert_correct_authority(user_keypair: &Pubkey, update_authority: &Pubkey) -> Result<()> {
     if user_keypair != update_authority {
         return Err(anyhow!(
@@ -198,8 +204,7 @@ fn get_cm_creator_accounts(
     position: usize,
 ) -> Result<Vec<(Pubkey, Account)>> {
     if position > 4 {
-        error!("CM Creator position cannot be greator than 4");
-        std::process::exit(1);
+        return Err(anyhow!("CM Creator position cannot be greater than 4"));
     }
     let creator = Pubkey::from_str(creator)?;
 
@@ -242,3 +247,34 @@ fn get_cm_creator_accounts(
 
     Ok(results)
 }
+
+/// Fetch and deserialize the metadata accounts, requesting 100 accounts at a time.
+pub fn get_metadata_accounts<S: AccountSource>(
+    fetcher: &AccountFetcher<S>,
+    metadata_pubkeys: &[Pubkey],
+) -> Result<Vec<Metadata>> {
+    let accounts = fetcher.get_multiple_accounts(metadata_pubkeys)?;
+
+    accounts
+        .into_iter()
+        .zip(metadata_pubkeys)
+        .map(|(account, pubkey)| {
+            let account =
+                account.ok_or_else(|| anyhow!("Metadata account not found: {}", pubkey))?;
+            Metadata::deserialize(&mut account.data.as_slice())
+                .map_err(|_| anyhow!("Failed to deserialize metadata account: {}", pubkey))
+        })
+        .collect()
+}
+
+/// URL of a link on the gateway when it is an `ipfs://` link, the link itself otherwise.
+pub fn resolve_ipfs_link(link: &str, gateway: &str) -> String {
+    match link.strip_prefix("ipfs://") {
+        Some(path) => format!(
+            "{}/ipfs/{}",
+            gateway.trim_end_matches('/'),
+            path.trim_start_matches("ipfs/")
+        ),
+        None => link.to_string(),
+    }
+}
@@ -0,0 +1,336 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt,
+    path::Path,
+};
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    cache::Cache,
+    upload::{asset_files, ANIMATION_CONTENT_TYPES, IMAGE_CONTENT_TYPES},
+};
+
+// maximum number of indices/files listed in the message
+const MAX_LISTED: usize = 20;
+
+/// Consistency between the config item number, the assets directory and the cache.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConsistencyReport {
+    /// Number of items in the config file.
+    pub config_number: Option<u64>,
+    /// Number of numbered metadata (JSON) files in the assets directory.
+    pub metadata_files: Option<usize>,
+    /// Number of numbered metadata files with a paired image.
+    pub paired_images: Option<usize>,
+    /// Number of items in the cache file (excluding the collection).
+    pub cache_items: Option<usize>,
+    /// Indices without a metadata file.
+    pub missing: Vec<usize>,
+    /// Indices with a metadata file but without an image.
+    pub missing_images: Vec<usize>,
+    /// Indices with an animation file but without an image.
+    pub unpaired_animations: Vec<usize>,
+    /// Indices without a cache item.
+    pub missing_cache_items: Vec<usize>,
+    /// Files (or cache items) outside of the expected index series.
+    pub extra: Vec<String>,
+}
+
+impl ConsistencyReport {
+    pub fn is_consistent(&self) -> bool {
+        let number_matches = match (self.config_number, self.metadata_files) {
+            (Some(number), Some(files)) => number == files as u64,
+            _ => true,
+        };
+
+        number_matches
+            && self.missing.is_empty()
+            && self.missing_images.is_empty()
+            && self.unpaired_animations.is_empty()
+            && self.missing_cache_items.is_empty()
+            && self.extra.is_empty()
+    }
+}
+
+impl fmt::Display for ConsistencyReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fn list<T: ToString>(values: &[T]) -> String {
+            let mut listed: Vec<String> =
+                values.iter().take(MAX_LISTED).map(T::to_string).collect();
+            if values.len() > MAX_LISTED {
+                listed.push(format!("... ({} more)", values.len() - MAX_LISTED));
+            }
+            listed.join(", ")
+        }
+
+        if let Some(number) = self.config_number {
+            writeln!(f, "config number: {}", number)?;
+        }
+        if let Some(files) = self.metadata_files {
+            writeln!(f, "numbered JSON files: {}", files)?;
+        }
+        if let Some(images) = self.paired_images {
+            writeln!(f, "paired images: {}", images)?;
+        }
+        if let Some(items) = self.cache_items {
+            writeln!(f, "cache items: {}", items)?;
+        }
+        if !self.missing.is_empty() {
+            writeln!(f, "missing: {}", list(&self.missing))?;
+        }
+        if !self.missing_images.is_empty() {
+            writeln!(f, "missing images: {}", list(&self.missing_images))?;
+        }
+        if !self.unpaired_animations.is_empty() {
+            writeln!(
+                f,
+                "animations without image: {}",
+                list(&self.unpaired_animations)
+            )?;
+        }
+        if !self.missing_cache_items.is_empty() {
+            writeln!(
+                f,
+                "missing cache items: {}",
+                list(&self.missing_cache_items)
+            )?;
+        }
+        if !self.extra.is_empty() {
+            writeln!(f, "extra: {}", list(&self.extra))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Checks that the config item number, the assets directory and the cache describe the
+/// same series of indices (0 to n-1).
+///
+/// The expected number of items is the config number when present, otherwise the size
+/// of the series found in the assets directory (or cache). With `recursive`, the files of the
+/// subdirectories of the assets directory are included.
+pub fn check_consistency(
+    assets_dir: Option<&Path>,
+    config_number: Option<u64>,
+    cache: Option<&Cache>,
+    recursive: bool,
+) -> Result<ConsistencyReport> {
+    let mut report = ConsistencyReport {
+        config_number,
+        ..ConsistencyReport::default()
+    };
+
+    let mut metadata = BTreeSet::new();
+    let mut images = HashSet::new();
+    let mut animations = BTreeSet::new();
+    let mut extra = Vec::new();
+
+    if let Some(assets_dir) = assets_dir {
+        for path in asset_files(assets_dir, recursive)?.into_values() {
+            let file_name = path
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let stem = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default();
+            let extension = path
+                .extension()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_lowercase();
+
+            // hidden files and the collection files are not part of the series
+            if file_name.starts_with('.') || stem == "collection" {
+                continue;
+            }
+
+            match stem.parse::<usize>() {
+                Ok(index) if extension == "json" => {
+                    metadata.insert(index);
+                }
+                Ok(index) if has_extension(&IMAGE_CONTENT_TYPES, &extension) => {
+                    images.insert(index);
+                }
+                Ok(index) if has_extension(&ANIMATION_CONTENT_TYPES, &extension) => {
+                    animations.insert(index);
+                }
+                _ => extra.push(file_name.to_string()),
+            }
+        }
+
+        report.metadata_files = Some(metadata.len());
+        report.paired_images = Some(metadata.iter().filter(|i| images.contains(i)).count());
+    }
+
+    let cache_indices = cache.map(|cache| {
+        let mut indices = BTreeSet::new();
+        for key in cache.items.keys() {
+            match key.parse::<usize>() {
+                Ok(index) => {
+                    indices.insert(index);
+                }
+                Err(_) if key == "-1" => (),
+                Err(_) => extra.push(format!("cache item '{}'", key)),
+            }
+        }
+        indices
+    });
+
+    report.cache_items = cache_indices.as_ref().map(|indices| indices.len());
+
+    let expected = match config_number {
+        Some(number) => number as usize,
+        None => assets_dir
+            .map(|_| &metadata)
+            .or(cache_indices.as_ref())
+            .and_then(|indices| indices.iter().next_back())
+            .map(|last| last + 1)
+            .unwrap_or(0),
+    };
+
+    if assets_dir.is_some() {
+        report.missing = (0..expected).filter(|i| !metadata.contains(i)).collect();
+        report.missing_images = (0..expected)
+            .filter(|i| metadata.contains(i) && !images.contains(i))
+            .collect();
+        report.unpaired_animations = animations
+            .iter()
+            .filter(|i| !images.contains(i))
+            .copied()
+            .collect();
+
+        extra.extend(
+            metadata
+                .range(expected..)
+                .map(|index| format!("{}.json", index)),
+        );
+        extra.extend(
+            images
+                .iter()
+                .filter(|index| !metadata.contains(index) && **index >= expected)
+                .map(|index| format!("{} (image)", index)),
+        );
+    }
+
+    if let Some(cache_indices) = &cache_indices {
+        report.missing_cache_items = (0..expected)
+            .filter(|i| !cache_indices.contains(i))
+            .collect();
+
+        extra.extend(
+            cache_indices
+                .range(expected..)
+                .map(|index| format!("cache item {}", index)),
+        );
+    }
+
+    extra.sort();
+    report.extra = extra;
+
+    Ok(report)
+}
+
+fn has_extension(content_types: &[(&str, &str)], extension: &str) -> bool {
+    content_types.iter().any(|(known, _)| *known == extension)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+
+    use super::*;
+    use crate::cache::CacheItem;
+
+    fn assets_dir(name: &str, files: &[&str]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("sugar-consistency-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        for file in files {
+            File::create(dir.join(file)).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn consistent_assets() {
+        let dir = assets_dir(
+            "consistent",
+            &["0.json", "0.png", "1.json", "1.jpg", "collection.json"],
+        );
+        let report = check_consistency(Some(&dir), Some(2), None, false).unwrap();
+
+        assert!(report.is_consistent());
+        assert_eq!(report.metadata_files, Some(2));
+        assert_eq!(report.paired_images, Some(2));
+    }
+
+    #[test]
+    fn reports_missing_and_extra_files() {
+        let dir = assets_dir(
+            "mismatch",
+            &[
+                "0.json",
+                "0.png",
+                "2.json",
+                "2.png",
+                "3.json",
+                "5.json",
+                "notes.txt",
+            ],
+        );
+        let report = check_consistency(Some(&dir), Some(4), None, false).unwrap();
+
+        assert!(!report.is_consistent());
+        assert_eq!(report.missing, vec![1]);
+        assert_eq!(report.missing_images, vec![3]);
+        assert_eq!(report.extra, vec!["5.json", "notes.txt"]);
+    }
+
+    #[test]
+    fn reports_animations_without_image() {
+        let dir = assets_dir(
+            "animations",
+            &[
+                "0.json", "0.png", "0.glb", "1.json", "1.gltf", "1.jpg", "2.json", "2.html",
+            ],
+        );
+        let report = check_consistency(Some(&dir), Some(3), None, false).unwrap();
+
+        assert!(!report.is_consistent());
+        assert_eq!(report.missing_images, vec![2]);
+        assert_eq!(report.unpaired_animations, vec![2]);
+        assert!(report.extra.is_empty());
+    }
+
+    #[test]
+    fn reports_missing_cache_items() {
+        let mut cache = Cache::new();
+        for key in ["-1", "0", "2"] {
+            cache.items.insert(
+                key.to_string(),
+                CacheItem {
+                    name: String::new(),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: String::new(),
+                    on_chain: false,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+        }
+
+        let report = check_consistency(None, Some(3), Some(&cache), false).unwrap();
+
+        assert!(!report.is_consistent());
+        assert_eq!(report.cache_items, Some(2));
+        assert_eq!(report.missing_cache_items, vec![1]);
+    }
+}
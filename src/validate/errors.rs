@@ -57,6 +57,9 @@ pub enum ValidateParserError {
     #[error("Assets list isn't continuous please check files")]
     NonContinuousSeries,
 
+    #[error("Assets are inconsistent:\n{0}")]
+    InconsistentAssets(String),
+
     #[error("Invalid category '{0}': must be one of: {1}")]
     InvalidCategory(String, String),
 }
@@ -48,9 +48,11 @@ impl Metadata {
             };
             self.properties.category = Some(category.to_string());
 
-            println!(
+            report!(
                 "{} missing `properties.category` for nft {}, defaulting to {}",
-                WARNING_EMOJI, &self.name, category
+                WARNING_EMOJI,
+                &self.name,
+                category
             );
         }
         parser::check_category(
@@ -1,9 +1,11 @@
+pub mod consistency;
 pub mod errors;
 pub mod format;
 pub mod helpers;
 pub mod parser;
 pub mod process;
 
+pub use consistency::*;
 pub use errors::*;
 pub use format::*;
 pub use helpers::*;
@@ -5,22 +5,32 @@ use std::{
 };
 
 use anyhow::Result;
-use console::{style, Style};
-use dialoguer::{theme::ColorfulTheme, Confirm};
-use glob::glob;
 use rayon::prelude::*;
 
-use crate::{common::*, utils::*, validate::*};
+use crate::{
+    cache::load_cache,
+    common::*,
+    config::get_config_data,
+    output::{confirm, style, Confirmation, PromptTheme},
+    upload::{asset_files, find_collection_files},
+    utils::*,
+    validate::*,
+};
 
 pub struct ValidateArgs {
     pub assets_dir: String,
     pub strict: bool,
     pub skip_collection_prompt: bool,
+    pub config: Option<String>,
+    pub cache: Option<String>,
+    pub json: bool,
+    /// Read the assets of the subdirectories of the assets directory too.
+    pub recursive: bool,
 }
 
 pub fn process_validate(args: ValidateArgs) -> Result<()> {
     // loading assets
-    println!(
+    report!(
         "{} {}Loading assets",
         style("[1/1]").bold().dim(),
         ASSETS_EMOJI
@@ -34,49 +44,72 @@ pub fn process_validate(args: ValidateArgs) -> Result<()> {
         return Err(ValidateParserError::MissingOrEmptyAssetsDirectory.into());
     }
 
-    if !args.skip_collection_prompt {
-        let collection_path = assets_dir.join("collection.json");
-        if !collection_path.is_file() {
-            let warning = format!(
-                "+----------------------------------------------+\n\
-                 | {} MISSING COLLECTION FILES IN ASSETS FOLDER |\n\
-                 +----------------------------------------------+",
-                WARNING_EMOJI
-            );
-            println!(
-                "\n{}\n{}\n",
-                style(warning).bold().yellow(),
-                style(
-                    "Check https://developers.metaplex.com/candy-machine/guides/create-an-nft-collection-on-solana-with-candy-machine#collection-details for the collection file requirements \
-                    if you want a collection to be set automatically."
-                )
-                .italic()
-                .yellow()
-            );
-
-            let theme = ColorfulTheme {
-                success_prefix: style("✔".to_string()).yellow().force_styling(true),
-                values_style: Style::new().yellow(),
-                ..get_dialoguer_theme()
-            };
-
-            if !Confirm::with_theme(&theme).with_prompt("Do you want to continue without automatically setting the candy machine collection?").interact()? {
-                return Err(anyhow!("Operation aborted"));
-            }
-            println!();
+    // a collection.json without its image (or the opposite) fails even without the prompt
+    let collection = find_collection_files(assets_dir)?;
+
+    if !args.skip_collection_prompt && collection.is_none() {
+        let warning = format!(
+            "+----------------------------------------------+\n\
+             | {} MISSING COLLECTION FILES IN ASSETS FOLDER |\n\
+             +----------------------------------------------+",
+            WARNING_EMOJI
+        );
+        report!(
+            "\n{}\n{}\n",
+            style(warning).bold().yellow(),
+            style(
+                "Check https://developers.metaplex.com/candy-machine/guides/create-an-nft-collection-on-solana-with-candy-machine#collection-details for the collection file requirements \
+                if you want a collection to be set automatically."
+            )
+            .italic()
+            .yellow()
+        );
+
+        if !confirm(
+            Confirmation::new(
+                "Do you want to continue without automatically setting the candy machine collection?",
+            )
+            .theme(PromptTheme::Warning),
+        )? {
+            return Err(anyhow!("Operation aborted"));
         }
+        report!();
     }
 
     let errors = Arc::new(Mutex::new(Vec::new()));
 
-    let path = assets_dir.join("*.json");
-    let pattern = path
-        .to_str()
-        .ok_or(ValidateParserError::InvalidAssetsDirectory)?;
+    let paths: Vec<PathBuf> = asset_files(assets_dir, args.recursive)?
+        .into_values()
+        .filter(|path| {
+            path.extension()
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("json"))
+        })
+        .collect();
+
+    // the config number and cache are only checked when the files are present
+    let config_data = match &args.config {
+        Some(config) if Path::new(config).exists() => Some(get_config_data(config)?),
+        _ => None,
+    };
+    let cache = match &args.cache {
+        Some(cache) if Path::new(cache).exists() => Some(load_cache(cache, false)?),
+        _ => None,
+    };
+
+    let report = check_consistency(
+        Some(assets_dir),
+        config_data.as_ref().map(|c| c.number),
+        cache.as_ref(),
+        args.recursive,
+    )?;
+
+    if args.json {
+        report!("{}", serde_json::to_string_pretty(&report)?);
+    }
 
-    // Unwrapping here because we know the pattern is valid and GlobErrors should
-    // be rare or impossible to produce.
-    let paths: Vec<PathBuf> = glob(pattern).unwrap().map(Result::unwrap).collect();
+    if !report.is_consistent() {
+        return Err(ValidateParserError::InconsistentAssets(report.to_string()).into());
+    }
 
     // Validating continuous assets in directory
     validate_continuous_assets(&paths)?;
@@ -148,7 +181,7 @@ pub fn process_validate(args: ValidateArgs) -> Result<()> {
 
     let message = "Validation complete, your metadata file(s) look good.";
     info!("{message}");
-    println!("\n{message}");
+    report!("\n{message}");
 
     Ok(())
 }
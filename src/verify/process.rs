@@ -2,25 +2,35 @@ use std::{thread, time::Duration};
 
 use anchor_lang::AccountDeserialize;
 use borsh::BorshDeserialize;
-use console::style;
-use mpl_candy_machine_core::{constants::HIDDEN_SECTION, CandyMachine};
+use futures::{stream, StreamExt};
+use mpl_candy_machine_core::{constants::HIDDEN_SECTION, CandyMachine, CandyMachineData};
 use mpl_token_metadata::state::Metadata;
+use reqwest::Client;
 
 use crate::{
+    account_fetcher::AccountFetcher,
+    bubblegum::BUBBLEGUM_ID,
     cache::*,
     candy_machine::CANDY_MACHINE_ID,
     common::*,
-    config::Cluster,
+    config::{Cluster, SugarConfig},
     constants::{CANDY_EMOJI, PAPER_EMOJI},
+    output::style,
     pdas::find_metadata_pda,
+    tree::{get_tree_config, parse_merkle_tree, print_tree_info},
     utils::*,
     verify::VerifyError,
 };
 
+// number of ipfs links requested in parallel
+const IPFS_PARALLEL_LIMIT: usize = 10;
+
 pub struct VerifyArgs {
     pub keypair: Option<String>,
     pub rpc_url: Option<String>,
     pub cache: String,
+    pub chunk: Option<u32>,
+    pub ipfs_gateway: String,
 }
 
 #[derive(Debug)]
@@ -29,15 +39,15 @@ pub struct OnChainItem {
     pub uri: String,
 }
 
-pub fn process_verify(args: VerifyArgs) -> Result<()> {
+pub async fn process_verify(args: VerifyArgs) -> Result<()> {
     let sugar_config = sugar_setup(args.keypair, args.rpc_url)?;
 
     // loads the cache file (this needs to have been created by
     // the upload command)
-    let mut cache = load_cache(&args.cache, false)?;
+    let mut cache = load_cache_or_chunk(&args.cache, args.chunk)?;
 
     if cache.items.is_empty() {
-        println!(
+        report!(
             "{}",
             style("No cache items found - run 'upload' to create the cache file first.")
                 .red()
@@ -48,7 +58,12 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
         return Err(CacheError::CacheFileNotFound(args.cache).into());
     }
 
-    println!(
+    // compressed deployments have a merkle tree instead of a candy machine
+    if cache.program.candy_machine.is_empty() && !cache.program.merkle_tree.is_empty() {
+        return verify_compressed(&sugar_config, &cache);
+    }
+
+    report!(
         "{} {}Loading candy machine",
         style("[1/2]").bold().dim(),
         CANDY_EMOJI
@@ -70,9 +85,25 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
 
     let client = setup_client(&sugar_config)?;
     let program = client.program(CANDY_MACHINE_ID);
+    let fetcher = AccountFetcher::new(program.rpc());
 
-    let data = match program.rpc().get_account_data(&candy_machine_pubkey) {
-        Ok(account_data) => account_data,
+    // the collection metadata of the cache is loaded with the candy machine
+    let mut pubkeys = vec![candy_machine_pubkey];
+    if let Ok(collection_mint) = Pubkey::from_str(&cache.program.collection_mint) {
+        pubkeys.push(find_metadata_pda(&collection_mint));
+    }
+
+    let data = match fetcher.get_multiple_accounts(&pubkeys) {
+        Ok(mut accounts) => match accounts.swap_remove(0) {
+            Some(account) => account.data,
+            None => {
+                return Err(VerifyError::FailedToGetAccountData(format!(
+                    "account {} not found",
+                    candy_machine_pubkey
+                ))
+                .into());
+            }
+        },
         Err(err) => {
             return Err(VerifyError::FailedToGetAccountData(err.to_string()).into());
         }
@@ -81,7 +112,7 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
 
     pb.finish_with_message("Completed");
 
-    println!(
+    report!(
         "\n{} {}Verification",
         style("[2/2]").bold().dim(),
         PAPER_EMOJI
@@ -90,13 +121,14 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
     if candy_machine.data.hidden_settings.is_some() {
         // nothing else to do, there are no config lines in a candy machine
         // with hidden settings
-        println!("\nHidden settings enabled. No config items to verify.");
-    } else if let Some(config_line_settings) = &candy_machine.data.config_line_settings {
+        report!("\nHidden settings enabled. No config items to verify.");
+    } else if candy_machine.data.config_line_settings.is_some() {
         let num_items = candy_machine.data.items_available;
+        let on_chain_items = decode_config_lines(&data, &candy_machine.data)?;
         let cache_items = &mut cache.items;
         let mut errors = Vec::new();
 
-        println!("Verifying {} config line(s): (Ctrl+C to abort)", num_items);
+        report!("Verifying {} config line(s): (Ctrl+C to abort)", num_items);
         let pb = progress_bar_with_style(num_items);
         // sleeps for a about 1 second
         let step: u64 = if num_items > 0 {
@@ -105,38 +137,12 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
             0
         };
 
-        let line_size = candy_machine.data.get_config_line_size();
-        let name_length = config_line_settings.name_length as usize;
-        let uri_length = config_line_settings.uri_length as usize;
-
-        for i in 0..num_items {
-            let name_start = HIDDEN_SECTION + STRING_LEN_SIZE + line_size * (i as usize);
-            let name_end = name_start + name_length;
-
-            let uri_start = name_end;
-            let uri_end = uri_start + uri_length;
-
-            let name_error = format!("Failed to decode name for item {}", i);
-            let name = String::from_utf8(data[name_start..name_end].to_vec())
-                .expect(&name_error)
-                .trim_matches(char::from(0))
-                .to_string();
-
-            let uri_error = format!("Failed to decode uri for item {}", i);
-            let uri = String::from_utf8(data[uri_start..uri_end].to_vec())
-                .expect(&uri_error)
-                .trim_matches(char::from(0))
-                .to_string();
-
-            let on_chain_item = OnChainItem {
-                name: config_line_settings.prefix_name.to_string() + &name,
-                uri: config_line_settings.prefix_uri.to_string() + &uri,
-            };
+        for (i, on_chain_item) in on_chain_items.iter().enumerate() {
             let cache_item = cache_items
                 .get_mut(&i.to_string())
                 .expect("Failed to get item from config.");
 
-            if let Err(err) = items_match(cache_item, &on_chain_item) {
+            if let Err(err) = items_match(cache_item, on_chain_item) {
                 cache_item.on_chain = false;
                 errors.push((i.to_string(), err.to_string()));
             }
@@ -150,12 +156,12 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
             cache.sync_file()?;
 
             let total = errors.len();
-            println!("\nInvalid items found: ");
+            report!("\nInvalid items found: ");
 
             for e in errors {
-                println!("- Item {}: {}", e.0, e.1);
+                report!("- Item {}: {}", e.0, e.1);
             }
-            println!("\nCache updated - re-run `deploy`.");
+            report!("\nCache updated - re-run `deploy`.");
             return Err(anyhow!("{} invalid item(s) found.", total));
         } else {
             pb.finish_with_message(format!(
@@ -170,7 +176,7 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
     }
 
     if candy_machine.items_redeemed > 0 {
-        println!(
+        report!(
             "\nAn item has already been minted. Skipping candy machine collection verification..."
         );
     } else {
@@ -183,28 +189,53 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
         let collection_item = cache.items.get_mut("-1");
 
         let collection_metadata = find_metadata_pda(&candy_machine.collection_mint);
-        let data = program.rpc().get_account_data(&collection_metadata)?;
+        let data = fetcher
+            .get_account(&collection_metadata)?
+            .ok_or_else(|| {
+                anyhow!(
+                    "Collection metadata account not found: {}",
+                    collection_metadata
+                )
+            })?
+            .data;
         let metadata: Metadata = BorshDeserialize::deserialize(&mut data.as_slice())?;
 
         if metadata.mint.to_string() != collection_mint_cache {
-            println!("\nInvalid collection state found");
+            report!("\nInvalid collection state found");
             cache.program.collection_mint = metadata.mint.to_string();
             if let Some(collection_item) = collection_item {
                 collection_item.on_chain = false;
             }
             cache.sync_file()?;
-            println!("Cache updated - re-run `deploy`.");
+            report!("Cache updated - re-run `deploy`.");
             return Err(anyhow!(
                 "Collection mint in cache {} doesn't match on chain collection mint {}!",
                 collection_mint_cache,
                 metadata.mint.to_string()
             ));
         } else if collection_needs_deploy {
-            println!("\nInvalid collection state found - re-run `deploy`.");
+            report!("\nInvalid collection state found - re-run `deploy`.");
             return Err(CacheError::InvalidState.into());
         }
     }
 
+    fetcher.print_summary();
+
+    let failures = resolve_ipfs_links(&cache.items, &args.ipfs_gateway).await;
+
+    if !failures.is_empty() {
+        report!("\nUnresolved ipfs links: ");
+
+        for (key, error) in &failures {
+            report!("- Item {}: {}", key, error);
+        }
+        return Err(anyhow!(
+            "{} ipfs link(s) do not resolve through {}.",
+            failures.len(),
+            args.ipfs_gateway
+        ));
+    }
+
     let cluster = match get_cluster(program.rpc())? {
         Cluster::Devnet => "devnet-alpha",
         Cluster::Mainnet => "mainnet-alpha",
@@ -213,9 +244,9 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
     };
 
     if cluster.is_empty() {
-        println!("\nVerification successful. You're good to go!");
+        report!("\nVerification successful. You're good to go!");
     } else {
-        println!(
+        report!(
             "\nVerification successful. You're good to go!\n\nSee your candy machine at:\n  -> https://www.solana.fm/address/{}?cluster={}",
             cache.program.candy_machine,
             cluster
@@ -224,6 +255,173 @@ pub fn process_verify(args: VerifyArgs) -> Result<()> {
     Ok(())
 }
 
+/// Requests the `ipfs://` metadata links of the cache through the gateway, returning the
+/// items that do not resolve with the error.
+async fn resolve_ipfs_links(cache_items: &CacheItems, gateway: &str) -> Vec<(String, String)> {
+    let links: Vec<(String, String)> = cache_items
+        .iter()
+        .filter(|(_, item)| item.metadata_link.starts_with("ipfs://"))
+        .map(|(key, item)| (key.clone(), resolve_ipfs_link(&item.metadata_link, gateway)))
+        .collect();
+
+    if links.is_empty() {
+        return Vec::new();
+    }
+
+    report!(
+        "\nResolving {} ipfs link(s) through {}:",
+        links.len(),
+        gateway
+    );
+    let pb = progress_bar_with_style(links.len() as u64);
+    let client = Client::new();
+
+    let mut failures: Vec<(String, String)> = stream::iter(links)
+        .map(|(key, url)| {
+            let client = &client;
+            let pb = &pb;
+            async move {
+                let result = match client.get(&url).send().await {
+                    Ok(response) if response.status().is_success() => None,
+                    Ok(response) => Some(format!("{} returned {}", url, response.status())),
+                    Err(err) => Some(format!("failed to fetch {}: {}", url, err)),
+                };
+                pb.inc(1);
+                result.map(|error| (key, error))
+            }
+        })
+        .buffer_unordered(IPFS_PARALLEL_LIMIT)
+        .filter_map(|failure| async move { failure })
+        .collect()
+        .await;
+
+    if failures.is_empty() {
+        pb.finish_with_message(format!(
+            "{}",
+            style("Ipfs link verification successful ").green().bold()
+        ));
+    } else {
+        pb.abandon_with_message(format!("{}", style("Verification failed ").red().bold()));
+    }
+
+    failures.sort();
+    failures
+}
+
+fn verify_compressed(sugar_config: &SugarConfig, cache: &Cache) -> Result<()> {
+    report!(
+        "{} {}Loading merkle tree",
+        style("[1/2]").bold().dim(),
+        TREE_EMOJI
+    );
+
+    let pb = spinner_with_style();
+    pb.set_message("Connecting...");
+
+    let merkle_tree = parse_merkle_tree(&cache.program.merkle_tree)?;
+    let client = setup_client(sugar_config)?;
+    let program = client.program(BUBBLEGUM_ID);
+    let tree_config = get_tree_config(&program.rpc(), &merkle_tree)?;
+
+    pb.finish_with_message("Completed");
+
+    report!(
+        "\n{} {}Verification",
+        style("[2/2]").bold().dim(),
+        PAPER_EMOJI
+    );
+
+    print_tree_info(&merkle_tree, &tree_config);
+
+    let cache_minted = cache
+        .items
+        .iter()
+        .filter(|(key, item)| key.as_str() != "-1" && item.on_chain)
+        .count() as u64;
+
+    if cache_minted > tree_config.num_minted {
+        return Err(anyhow!(
+            "Cache has {} item(s) marked as minted, but the tree only has {} leaves.",
+            cache_minted,
+            tree_config.num_minted
+        ));
+    }
+
+    report!(
+        "\n{}",
+        style("Compressed deployment verification successful")
+            .green()
+            .bold()
+    );
+
+    Ok(())
+}
+
+/// Decodes the config lines of a raw candy machine account, with the name and URI prefixes.
+/// Bytes that are not valid UTF-8 (e.g. a character cut by the name or URI length) are
+/// replaced, so that the line does not match its cache item.
+pub fn decode_config_lines(data: &[u8], candy_data: &CandyMachineData) -> Result<Vec<OnChainItem>> {
+    let settings = candy_data
+        .config_line_settings
+        .as_ref()
+        .ok_or_else(|| anyhow!("Could not determine candy machine config line settings"))?;
+
+    let line_size = candy_data.get_config_line_size();
+    let name_length = settings.name_length as usize;
+    let uri_length = settings.uri_length as usize;
+    let lines_start = HIDDEN_SECTION + STRING_LEN_SIZE;
+
+    if data.len() < lines_start + line_size * candy_data.items_available as usize {
+        return Err(anyhow!(
+            "Candy machine account is too small for {} config line(s)",
+            candy_data.items_available
+        ));
+    }
+
+    let decode = |bytes: &[u8]| {
+        String::from_utf8_lossy(bytes)
+            .trim_matches(char::from(0))
+            .to_string()
+    };
+
+    Ok((0..candy_data.items_available as usize)
+        .map(|i| {
+            let name_start = lines_start + line_size * i;
+            let uri_start = name_start + name_length;
+
+            OnChainItem {
+                name: settings.prefix_name.clone() + decode(&data[name_start..uri_start]).as_str(),
+                uri: settings.prefix_uri.clone()
+                    + decode(&data[uri_start..uri_start + uri_length]).as_str(),
+            }
+        })
+        .collect())
+}
+
+/// Compares the config lines with the cache items byte for byte, marking the items that do
+/// not match as not on-chain so that the next deploy writes them again. Returns the index of
+/// each mismatched item with the difference.
+pub fn check_config_lines(
+    cache_items: &mut CacheItems,
+    on_chain_items: &[OnChainItem],
+) -> Vec<(usize, String)> {
+    let mut mismatches = Vec::new();
+
+    for (i, on_chain_item) in on_chain_items.iter().enumerate() {
+        match cache_items.get_mut(&i.to_string()) {
+            Some(cache_item) => {
+                if let Err(err) = items_match(cache_item, on_chain_item) {
+                    cache_item.on_chain = false;
+                    mismatches.push((i, err.to_string()));
+                }
+            }
+            None => mismatches.push((i, "missing cache item".to_string())),
+        }
+    }
+
+    mismatches
+}
+
 fn items_match(cache_item: &CacheItem, on_chain_item: &OnChainItem) -> Result<()> {
     if cache_item.name != on_chain_item.name {
         return Err(VerifyError::Mismatch(
@@ -243,3 +441,140 @@ fn items_match(cache_item: &CacheItem, on_chain_item: &OnChainItem) -> Result<()
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use mpl_candy_machine_core::ConfigLineSettings;
+
+    use super::*;
+    use crate::{estimate::estimate_candy_machine_data, upload::methods::test_utils::serve};
+
+    fn cache_item(name: &str, uri: &str) -> CacheItem {
+        CacheItem {
+            name: name.to_string(),
+            image_hash: String::new(),
+            image_link: String::new(),
+            metadata_hash: String::new(),
+            metadata_link: uri.to_string(),
+            on_chain: true,
+            animation_hash: None,
+            animation_link: None,
+            optimized_image_hash: None,
+        }
+    }
+
+    /// Account data with the config lines written as the program does, cut to the lengths of
+    /// the candy machine.
+    fn account_data(candy_data: &CandyMachineData, lines: &[(&str, &str)]) -> Vec<u8> {
+        let settings = candy_data.config_line_settings.as_ref().unwrap();
+        let name_length = settings.name_length as usize;
+        let uri_length = settings.uri_length as usize;
+        let lines_start = HIDDEN_SECTION + STRING_LEN_SIZE;
+
+        let mut data = vec![0; lines_start + (name_length + uri_length) * lines.len()];
+
+        for (i, (name, uri)) in lines.iter().enumerate() {
+            let name_start = lines_start + (name_length + uri_length) * i;
+            let name = &name.as_bytes()[..name.len().min(name_length)];
+            data[name_start..name_start + name.len()].copy_from_slice(name);
+
+            let uri_start = name_start + name_length;
+            let uri = &uri.as_bytes()[..uri.len().min(uri_length)];
+            data[uri_start..uri_start + uri.len()].copy_from_slice(uri);
+        }
+
+        data
+    }
+
+    #[test]
+    fn truncated_config_lines_do_not_match() {
+        let mut candy_data = estimate_candy_machine_data(3, 5, 12, false);
+        candy_data.config_line_settings = Some(ConfigLineSettings {
+            prefix_name: "#".to_string(),
+            name_length: 5,
+            prefix_uri: "https://".to_string(),
+            uri_length: 12,
+            is_sequential: false,
+        });
+
+        let lines = [
+            ("1", "arweave.net"),
+            // one byte over the URI length
+            ("2", "arweave.net/2"),
+            // a two-byte character cut by the name length
+            ("Caffé", "arweave.net"),
+        ];
+        let data = account_data(&candy_data, &lines);
+
+        let on_chain_items = decode_config_lines(&data, &candy_data).unwrap();
+        assert_eq!(on_chain_items[0].name, "#1");
+        assert_eq!(on_chain_items[0].uri, "https://arweave.net");
+
+        let mut cache_items = CacheItems::new();
+        for (i, (name, uri)) in lines.iter().enumerate() {
+            cache_items.insert(
+                i.to_string(),
+                cache_item(&format!("#{}", name), &format!("https://{}", uri)),
+            );
+        }
+
+        let mismatches = check_config_lines(&mut cache_items, &on_chain_items);
+
+        let indices: Vec<usize> = mismatches.iter().map(|(i, _)| *i).collect();
+        assert_eq!(indices, vec![1, 2]);
+        assert!(mismatches[0].1.starts_with("uri mismatch"));
+        assert!(mismatches[1].1.starts_with("name mismatch"));
+
+        let on_chain: Vec<bool> = cache_items.values().map(|item| item.on_chain).collect();
+        assert_eq!(on_chain, vec![true, false, false]);
+    }
+
+    #[test]
+    fn short_account_data_is_an_error() {
+        let candy_data = estimate_candy_machine_data(3, 5, 12, false);
+        let data = account_data(&candy_data, &[("1", "a"), ("2", "b")]);
+
+        assert!(decode_config_lines(&data, &candy_data).is_err());
+    }
+
+    #[tokio::test]
+    async fn resolves_ipfs_links_through_the_gateway() {
+        let (gateway, requests) = serve(|request| {
+            if request.path == "/ipfs/bafy-dir/1.json" {
+                (404, String::new())
+            } else {
+                (200, "{}".to_string())
+            }
+        });
+
+        let mut cache = Cache::new();
+        for (key, link) in [
+            ("0", "ipfs://bafy-dir/0.json"),
+            ("1", "ipfs://bafy-dir/1.json"),
+            ("2", "https://arweave.net/metadata"),
+        ] {
+            cache.items.insert(
+                key.to_string(),
+                CacheItem {
+                    name: key.to_string(),
+                    image_hash: String::new(),
+                    image_link: String::new(),
+                    metadata_hash: String::new(),
+                    metadata_link: link.to_string(),
+                    on_chain: true,
+                    animation_hash: None,
+                    animation_link: None,
+                    optimized_image_hash: None,
+                },
+            );
+        }
+
+        let failures = resolve_ipfs_links(&cache.items, &gateway).await;
+
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "1");
+        assert!(failures[0].1.contains("404"), "{}", failures[0].1);
+        // only the ipfs links are requested
+        assert_eq!(requests.lock().unwrap().len(), 2);
+    }
+}
@@ -12,8 +12,6 @@ pub use anchor_client::{
     },
     Client, Program,
 };
-use console::{style, Style};
-use dialoguer::{theme::ColorfulTheme, Confirm};
 use mpl_candy_machine_core::{accounts as nft_accounts, instruction as nft_instruction};
 use solana_account_decoder::UiAccountEncoding;
 use solana_client::{
@@ -22,8 +20,10 @@ use solana_client::{
 };
 
 use crate::{
+    cache::load_cache_or_chunk,
     candy_machine::CANDY_MACHINE_ID,
     common::*,
+    output::{confirm, style, Confirmation, PromptTheme},
     parse::parse_sugar_errors,
     setup::{setup_client, sugar_setup},
     utils::*,
@@ -36,6 +36,9 @@ pub struct WithdrawArgs {
     pub list: bool,
     pub authority: Option<String>,
     pub priority_fee: u64,
+    pub cache: String,
+    pub chunk: Option<u32>,
+    pub allow_cluster_mismatch: bool,
 }
 
 #[derive(Debug)]
@@ -47,7 +50,7 @@ struct WithdrawError {
 pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
     // (1) Setting up connection
 
-    println!(
+    report!(
         "{} {}Initializing connection",
         style("[1/2]").bold().dim(),
         COMPUTER_EMOJI
@@ -64,17 +67,27 @@ pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
     // then we cannot withdraw
     let list = args.list || (payer != authority);
 
-    println!(
+    // the --list flag takes precedence; even if a candy machine id is passed
+    // as an argument, we will list the candy machines (no draining happens)
+    let candy_machine = if list {
+        None
+    } else {
+        withdraw_target(
+            &program,
+            &args.cache,
+            args.chunk,
+            args.candy_machine,
+            args.allow_cluster_mismatch,
+        )?
+    };
+
+    report!(
         "\n{} {}{} funds",
         style("[2/2]").bold().dim(),
         WITHDRAW_EMOJI,
         if list { "Listing" } else { "Retrieving" }
     );
 
-    // the --list flag takes precedence; even if a candy machine id is passed
-    // as an argument, we will list the candy machines (no draining happens)
-    let candy_machine = if list { None } else { args.candy_machine };
-
     // (2) Retrieving data for listing/draining
 
     match &candy_machine {
@@ -122,7 +135,7 @@ pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
                 total += account.lamports as f64;
             });
 
-            println!(
+            report!(
                 "\nFound {} candy machines, total amount: ◎ {}",
                 accounts.len(),
                 total / LAMPORTS_PER_SOL as f64
@@ -130,11 +143,11 @@ pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
 
             if !accounts.is_empty() {
                 if list {
-                    println!("\n{:48} Balance", "Candy Machine ID");
-                    println!("{:-<61}", "-");
+                    report!("\n{:48} Balance", "Candy Machine ID");
+                    report!("{:-<61}", "-");
 
                     for (pubkey, account) in accounts {
-                        println!(
+                        report!(
                             "{:48} {:>12.8}",
                             pubkey.to_string(),
                             account.lamports as f64 / LAMPORTS_PER_SOL as f64
@@ -149,18 +162,11 @@ pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
                         WARNING_EMOJI
                     );
 
-                    println!("{}\n", style(warning).bold().yellow());
-
-                    let theme = ColorfulTheme {
-                        success_prefix: style("✔".to_string()).yellow().force_styling(true),
-                        values_style: Style::new().yellow(),
-                        ..get_dialoguer_theme()
-                    };
+                    report!("{}\n", style(warning).bold().yellow());
 
-                    if !Confirm::with_theme(&theme)
-                        .with_prompt("Do you want to continue?")
-                        .interact()?
-                    {
+                    if !confirm(
+                        Confirmation::new("Do you want to continue?").theme(PromptTheme::Warning),
+                    )? {
                         return Err(anyhow!("Withdraw aborted"));
                     }
 
@@ -186,16 +192,16 @@ pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
                     pb.finish();
 
                     if not_drained > 0 {
-                        println!(
+                        report!(
                             "{}",
                             style(format!("Could not drain {} candy machine(s)", not_drained))
                                 .red()
                                 .bold()
                                 .dim()
                         );
-                        println!("{}", style("Errors:").red().bold().dim());
+                        report!("{}", style("Errors:").red().bold().dim());
                         for error in error_messages {
-                            println!(
+                            report!(
                                 "{} {}\n{} {}",
                                 style("Candy Machine:").bold().dim(),
                                 style(error.candy_machine).bold().red(),
@@ -212,6 +218,35 @@ pub fn process_withdraw(args: WithdrawArgs) -> Result<()> {
     Ok(())
 }
 
+/// Returns the candy machine to drain: the one of the chunk selected with `--chunk`, or the
+/// one given. The cluster of the cache is only checked (and recorded for old caches) when the
+/// cache is the one of the drained candy machine.
+fn withdraw_target(
+    program: &Program<Rc<Keypair>>,
+    cache_path: &str,
+    chunk: Option<u32>,
+    candy_machine: Option<String>,
+    allow_cluster_mismatch: bool,
+) -> Result<Option<String>> {
+    if chunk.is_some() {
+        let mut cache = load_cache_or_chunk(cache_path, chunk)?;
+        cache.check_cluster(&program.rpc(), allow_cluster_mismatch)?;
+        return Ok(Some(cache.program.candy_machine));
+    }
+
+    if let Some(candy_machine) = &candy_machine {
+        if Path::new(cache_path).exists() {
+            let mut cache = load_cache_or_chunk(cache_path, None)?;
+
+            if cache.program.candy_machine == *candy_machine {
+                cache.check_cluster(&program.rpc(), allow_cluster_mismatch)?;
+            }
+        }
+    }
+
+    Ok(candy_machine)
+}
+
 fn setup_withdraw(
     keypair: Option<String>,
     rpc_url: Option<String>,
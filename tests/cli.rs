@@ -0,0 +1,117 @@
+//! Output of the `sugar` binary: the commands print to the terminal through the CLI only, so
+//! these tests check that the output of the CLI is unchanged.
+#![cfg(feature = "cli")]
+
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+    thread,
+};
+
+use clap::Parser;
+use serde_json::json;
+use sugar_cli::cli::Cli;
+
+/// Writes an item (metadata and image) in the assets directory.
+fn write_item(assets: &Path, name: &str) {
+    let image = format!("{name}.png");
+    fs::copy(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/assets/0.png"),
+        assets.join(&image),
+    )
+    .unwrap();
+
+    let metadata = json!({
+        "name": format!("Test {name}"),
+        "symbol": "TEST",
+        "description": "Sugar CLI test",
+        "seller_fee_basis_points": 500,
+        "image": image,
+        "attributes": [],
+        "properties": {
+            "files": [{ "uri": image, "type": "image/png" }],
+            "category": "image"
+        }
+    });
+    fs::write(assets.join(format!("{name}.json")), metadata.to_string()).unwrap();
+}
+
+/// Runs `sugar` in `dir` with the arguments (in a UTF-8 locale, which enables the emojis).
+fn sugar(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sugar"))
+        .args(args)
+        .current_dir(dir)
+        .env_remove("LC_ALL")
+        .env_remove("LC_CTYPE")
+        .env("LANG", "en_US.UTF-8")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn validate_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = dir.path().join("assets");
+    fs::create_dir(&assets).unwrap();
+    write_item(&assets, "0");
+    write_item(&assets, "1");
+    write_item(&assets, "collection");
+
+    let output = sugar(dir.path(), &["validate", "assets"]);
+
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[1/1] 🗂  Loading assets\n\nValidation complete, your metadata file(s) look good.\n\n✅ Command successful.\n"
+    );
+}
+
+#[test]
+fn validate_error_output() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("assets")).unwrap();
+
+    let output = sugar(dir.path(), &["validate", "assets"]);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[1/1] 🗂  Loading assets\n\n🛑 Error running command (re-run needed): Missing or empty \
+        assets directory\n"
+    );
+}
+
+#[test]
+fn ignore_cluster_check_flag() {
+    let dir = tempfile::tempdir().unwrap();
+    let assets = dir.path().join("assets");
+    fs::create_dir(&assets).unwrap();
+    write_item(&assets, "0");
+    write_item(&assets, "collection");
+
+    // both spellings of the flag are accepted by every command
+    for flag in ["--ignore-cluster-check", "--allow-cluster-mismatch"] {
+        let output = sugar(dir.path(), &[flag, "validate", "assets"]);
+        assert!(output.status.success(), "{:?}", output);
+
+        let output = sugar(dir.path(), &["validate", "assets", flag]);
+        assert!(output.status.success(), "{:?}", output);
+    }
+
+    let output = sugar(dir.path(), &["validate", "assets", "--ignore-cluster"]);
+    assert!(!output.status.success());
+
+    // the parser of all the commands needs more than the stack of a test thread
+    thread::Builder::new()
+        .stack_size(16 * 1024 * 1024)
+        .spawn(|| {
+            let cli = Cli::try_parse_from(["sugar", "validate", "--ignore-cluster-check"]).unwrap();
+            assert!(cli.allow_cluster_mismatch);
+            let cli = Cli::try_parse_from(["sugar", "validate"]).unwrap();
+            assert!(!cli.allow_cluster_mismatch);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
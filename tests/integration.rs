@@ -0,0 +1,276 @@
+//! End-to-end tests against a local validator.
+//!
+//! The tests start a `solana-test-validator` (see [`sugar_cli::test_validator`]) with the
+//! Candy Machine, Candy Guard and Token Metadata programs loaded from `tests/programs` (or the
+//! folder set in `SUGAR_TEST_PROGRAMS`), which can be populated with
+//! `script/fetch-test-programs.sh`.
+//! Run them with:
+//!
+//! ```text
+//! cargo integration
+//! ```
+#![cfg(feature = "integration-tests")]
+
+use anchor_client::solana_sdk::{
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    native_token::LAMPORTS_PER_SOL,
+    pubkey::Pubkey,
+    signature::{Keypair, Signer},
+    system_program, sysvar,
+    transaction::Transaction,
+};
+use anchor_lang::{AccountDeserialize, InstructionData, ToAccountMetas};
+use anyhow::Result;
+use mpl_candy_guard::state::{CandyGuardData, DATA_OFFSET};
+use mpl_candy_machine_core::CandyMachine;
+use mpl_token_metadata::{
+    instruction::MetadataDelegateRole,
+    pda::{find_metadata_delegate_record_account, find_token_record_account},
+};
+use solana_client::rpc_client::RpcClient;
+use solana_program::{instruction::AccountMeta, program_pack::Pack};
+use spl_associated_token_account::get_associated_token_address;
+use spl_token::state::{Account as TokenAccount, AccountState};
+use sugar_cli::{
+    api,
+    config::guard_data::{CandyGuardData as GuardConfig, FreezeSolPayment, GuardSet, SolPayment},
+    freeze::find_freeze_pda,
+    pdas::{find_candy_machine_creator_pda, find_master_edition_pda, find_metadata_pda},
+    test_validator::{Fixture, TestValidator, ITEMS},
+};
+
+// on-chain state
+
+fn candy_machine(rpc: &RpcClient, candy_machine: &Pubkey) -> Result<CandyMachine> {
+    let data = rpc.get_account_data(candy_machine)?;
+    Ok(CandyMachine::try_deserialize(&mut data.as_slice())?)
+}
+
+fn candy_guard_data(rpc: &RpcClient, candy_guard: &Pubkey) -> Result<CandyGuardData> {
+    let data = rpc.get_account_data(candy_guard)?;
+    Ok(*CandyGuardData::load(&data[DATA_OFFSET..])?)
+}
+
+fn token_account(rpc: &RpcClient, owner: &Pubkey, mint: &Pubkey) -> Result<TokenAccount> {
+    let data = rpc.get_account_data(&get_associated_token_address(owner, mint))?;
+    Ok(TokenAccount::unpack(&data)?)
+}
+
+/// Mints through the candy guard with the freeze sol payment guard; `sugar mint` only
+/// mints from candy machines without a guard.
+fn mint_with_freeze_guard(
+    rpc: &RpcClient,
+    payer: &Keypair,
+    candy_machine_id: &Pubkey,
+    candy_guard_id: &Pubkey,
+    destination: &Pubkey,
+) -> Result<Pubkey> {
+    let state = candy_machine(rpc, candy_machine_id)?;
+
+    let nft_mint = Keypair::new();
+    let token = get_associated_token_address(&payer.pubkey(), &nft_mint.pubkey());
+    let (authority_pda, _) = find_candy_machine_creator_pda(candy_machine_id);
+    let collection_update_authority = state.authority;
+
+    let accounts = mpl_candy_guard::accounts::MintV2 {
+        candy_guard: *candy_guard_id,
+        candy_machine_program: mpl_candy_machine_core::ID,
+        candy_machine: *candy_machine_id,
+        candy_machine_authority_pda: authority_pda,
+        payer: payer.pubkey(),
+        minter: payer.pubkey(),
+        nft_mint: nft_mint.pubkey(),
+        nft_mint_authority: payer.pubkey(),
+        nft_metadata: find_metadata_pda(&nft_mint.pubkey()),
+        nft_master_edition: find_master_edition_pda(&nft_mint.pubkey()),
+        token: Some(token),
+        token_record: Some(find_token_record_account(&nft_mint.pubkey(), &token).0),
+        collection_delegate_record: find_metadata_delegate_record_account(
+            &state.collection_mint,
+            MetadataDelegateRole::Collection,
+            &collection_update_authority,
+            &authority_pda,
+        )
+        .0,
+        collection_mint: state.collection_mint,
+        collection_metadata: find_metadata_pda(&state.collection_mint),
+        collection_master_edition: find_master_edition_pda(&state.collection_mint),
+        collection_update_authority,
+        token_metadata_program: mpl_token_metadata::ID,
+        spl_token_program: spl_token::ID,
+        spl_ata_program: Some(spl_associated_token_account::ID),
+        system_program: system_program::ID,
+        sysvar_instructions: sysvar::instructions::ID,
+        recent_slothashes: sysvar::slot_hashes::ID,
+        authorization_rules_program: None,
+        authorization_rules: None,
+    };
+
+    let mut metas = accounts.to_account_metas(None);
+    for meta in &mut metas {
+        if meta.pubkey == nft_mint.pubkey() {
+            meta.is_signer = true;
+            meta.is_writable = true;
+        }
+    }
+    // freeze sol payment guard accounts
+    let (freeze_pda, _) = find_freeze_pda(candy_guard_id, candy_machine_id, destination);
+    metas.push(AccountMeta::new(freeze_pda, false));
+    metas.push(AccountMeta::new_readonly(token, false));
+
+    let mint_ix = solana_program::instruction::Instruction {
+        program_id: mpl_candy_guard::ID,
+        accounts: metas,
+        data: mpl_candy_guard::instruction::MintV2 {
+            mint_args: vec![],
+            label: None,
+        }
+        .data(),
+    };
+
+    let transaction = Transaction::new_signed_with_payer(
+        &[
+            ComputeBudgetInstruction::set_compute_unit_limit(400_000),
+            mint_ix,
+        ],
+        Some(&payer.pubkey()),
+        &[payer, &nft_mint],
+        rpc.get_latest_blockhash()?,
+    );
+    rpc.send_and_confirm_transaction(&transaction)?;
+
+    Ok(nft_mint.pubkey())
+}
+
+// tests
+
+#[tokio::test]
+async fn candy_machine_lifecycle() -> Result<()> {
+    let validator = TestValidator::start("lifecycle")?;
+    let fixture = Fixture::new("lifecycle", &validator)?;
+    let rpc = validator.rpc();
+    let authority = fixture.keypair.pubkey();
+
+    fixture.create_config(Some(GuardConfig {
+        default: GuardSet {
+            sol_payment: Some(SolPayment {
+                value: 0.1,
+                destination: authority,
+            }),
+            ..Default::default()
+        },
+        groups: None,
+    }))?;
+
+    // upload
+    let upload = api::upload(&fixture.options, &fixture.assets_dir()).await?;
+    assert_eq!(upload.items, ITEMS as usize);
+    assert!(upload.collection_metadata_link.is_some());
+
+    // deploy
+    let deploy = api::deploy(&fixture.options, None).await?;
+    let state = candy_machine(&rpc, &deploy.candy_machine)?;
+    assert_eq!(state.authority, authority);
+    assert_eq!(state.collection_mint, deploy.collection_mint);
+    assert_eq!(state.data.items_available, ITEMS);
+    assert_eq!(state.items_redeemed, 0);
+
+    // verify
+    let verify = api::verify(&fixture.options).await?;
+    assert_eq!(verify.items, ITEMS as usize);
+
+    // mint (before the guard wraps the candy machine)
+    api::mint(&fixture.options, 2, None).await?;
+    let state = candy_machine(&rpc, &deploy.candy_machine)?;
+    assert_eq!(state.items_redeemed, 2);
+
+    // guard add
+    let candy_guard = api::guard_add(&fixture.options)?;
+    let state = candy_machine(&rpc, &deploy.candy_machine)?;
+    assert_eq!(state.mint_authority, candy_guard);
+    let guards = candy_guard_data(&rpc, &candy_guard)?;
+    let sol_payment = guards
+        .default
+        .sol_payment
+        .expect("missing solPayment guard");
+    assert_eq!(sol_payment.lamports, LAMPORTS_PER_SOL / 10);
+    assert_eq!(sol_payment.destination, authority);
+
+    // withdraw
+    api::withdraw(&fixture.options)?;
+    assert!(rpc
+        .get_account_with_commitment(&deploy.candy_machine, CommitmentConfig::confirmed())?
+        .value
+        .is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn freeze_guard_lifecycle() -> Result<()> {
+    let validator = TestValidator::start("freeze")?;
+    let fixture = Fixture::new("freeze", &validator)?;
+    let rpc = validator.rpc();
+    let authority = fixture.keypair.pubkey();
+    let destination = Keypair::new().pubkey();
+
+    fixture.create_config(Some(GuardConfig {
+        default: GuardSet {
+            freeze_sol_payment: Some(FreezeSolPayment {
+                value: 0.1,
+                destination,
+            }),
+            ..Default::default()
+        },
+        groups: None,
+    }))?;
+
+    api::upload(&fixture.options, &fixture.assets_dir()).await?;
+    let deploy = api::deploy(&fixture.options, None).await?;
+    assert_eq!(api::verify(&fixture.options).await?.items, ITEMS as usize);
+
+    let candy_guard = api::guard_add(&fixture.options)?;
+    api::freeze_initialize(&fixture.options, None, 3600)?;
+
+    let (freeze_pda, _) = find_freeze_pda(&candy_guard, &deploy.candy_machine, &destination);
+    assert!(!rpc.get_account_data(&freeze_pda)?.is_empty());
+
+    // mints the whole collection so that thawing is allowed before the freeze period ends
+    let mut mints = Vec::new();
+    for _ in 0..ITEMS {
+        mints.push(mint_with_freeze_guard(
+            &rpc,
+            &fixture.keypair,
+            &deploy.candy_machine,
+            &candy_guard,
+            &destination,
+        )?);
+    }
+
+    let state = candy_machine(&rpc, &deploy.candy_machine)?;
+    assert_eq!(state.items_redeemed, ITEMS);
+
+    for mint in &mints {
+        let account = token_account(&rpc, &authority, mint)?;
+        assert_eq!(account.state, AccountState::Frozen);
+        assert_eq!(account.amount, 1);
+    }
+
+    // thaw
+    api::thaw_all(&fixture.options, None).await?;
+
+    for mint in &mints {
+        let account = token_account(&rpc, &authority, mint)?;
+        assert_eq!(account.state, AccountState::Initialized);
+    }
+
+    // withdraw
+    api::withdraw(&fixture.options)?;
+    assert!(rpc
+        .get_account_with_commitment(&deploy.candy_machine, CommitmentConfig::confirmed())?
+        .value
+        .is_none());
+
+    Ok(())
+}